@@ -4,8 +4,14 @@
 //! implementation to Python, enabling drop-in replacement of the
 //! Python stageflow module.
 
+use pyo3::exceptions::{PyOverflowError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyDict, PyList, PyLong};
+use stageflow::context::{ContextSnapshot, PipelineContext, RunIdentity};
+use stageflow::core::StageOutput;
+use stageflow::pipeline::{ManifestError, PipelineManifest, StageGraph};
+use stageflow::stages::get_stage_registry;
+use stageflow::utils::{number_policy, set_number_policy as set_policy, NumberPolicy};
 use std::collections::HashMap;
 
 /// Python wrapper for StageOutput.
@@ -151,6 +157,18 @@ impl PyStageOutput {
     }
 }
 
+impl From<StageOutput> for PyStageOutput {
+    fn from(output: StageOutput) -> Self {
+        Self {
+            status: output.status.to_string(),
+            data: output.data.map(stageflow::core::StageData::into_inner),
+            error: output.error,
+            retryable: output.retryable,
+            metadata: output.metadata,
+        }
+    }
+}
+
 /// Python wrapper for StageStatus.
 #[pyclass(name = "StageStatus")]
 #[derive(Clone)]
@@ -381,6 +399,18 @@ fn py_to_json(obj: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
     if let Ok(i) = obj.extract::<i64>() {
         return Ok(serde_json::Value::Number(i.into()));
     }
+
+    if let Ok(u) = obj.extract::<u64>() {
+        return Ok(serde_json::Value::Number(u.into()));
+    }
+
+    if obj.is_instance_of::<PyLong>() {
+        // Beyond 64 bits: extracting as f64 would silently round
+        let digits = obj.str()?.to_string();
+        return number_policy()
+            .integer(&digits)
+            .map_err(|e| PyOverflowError::new_err(e.to_string()));
+    }
     
     if let Ok(f) = obj.extract::<f64>() {
         if let Some(n) = serde_json::Number::from_f64(f) {
@@ -420,10 +450,15 @@ fn json_to_py(py: Python<'_>, value: &serde_json::Value) -> PyObject {
         serde_json::Value::Number(n) => {
             if let Some(i) = n.as_i64() {
                 i.into_py(py)
-            } else if let Some(f) = n.as_f64() {
-                f.into_py(py)
+            } else if let Some(u) = n.as_u64() {
+                u.into_py(py)
+            } else if n.is_f64() {
+                n.as_f64().into_py(py)
             } else {
-                py.None()
+                // Only reachable with arbitrary precision numbers
+                py.get_type_bound::<PyLong>()
+                    .call1((n.to_string(),))
+                    .map_or_else(|_| py.None(), |i| i.into_py(py))
             }
         }
         serde_json::Value::String(s) => s.into_py(py),
@@ -649,9 +684,103 @@ impl PyPipelineValidationError {
     }
 }
 
+/// Sets how integers beyond 64 bits are converted to JSON.
+///
+/// Accepts "lossy" (the default), "preserve_as_string", "error", and
+/// "arbitrary_precision" when the extension is built with that feature.
+#[pyfunction]
+fn set_number_policy(name: &str) -> PyResult<()> {
+    let policy = NumberPolicy::from_name(name)
+        .ok_or_else(|| PyValueError::new_err(format!("Unknown number policy: {name}")))?;
+    if !policy.is_available() {
+        return Err(PyValueError::new_err(format!(
+            "Number policy {name} requires the arbitrary_precision feature"
+        )));
+    }
+    set_policy(policy);
+    Ok(())
+}
+
+/// Returns the name of the active number policy.
+#[pyfunction]
+fn get_number_policy() -> &'static str {
+    number_policy().as_str()
+}
+
+/// Returns the stage types registered with the Rust stage registry.
+#[pyfunction]
+fn registered_stage_types() -> Vec<String> {
+    get_stage_registry().stage_types()
+}
+
+/// A pipeline built from a manifest, ready to run.
+#[pyclass(name = "Pipeline")]
+pub struct PyPipeline {
+    graph: StageGraph,
+}
+
+#[pymethods]
+impl PyPipeline {
+    /// Returns the pipeline name.
+    #[getter]
+    fn name(&self) -> &str {
+        self.graph.name()
+    }
+
+    /// Returns the stage names in execution order.
+    #[getter]
+    fn stages(&self) -> Vec<String> {
+        self.graph.execution_order().to_vec()
+    }
+
+    /// Runs the pipeline to completion and returns each stage's output.
+    ///
+    /// Raises RuntimeError if the run itself fails; stage failures are
+    /// reported through the returned outputs.
+    fn run(&self, py: Python<'_>) -> PyResult<HashMap<String, PyStageOutput>> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        let ctx = std::sync::Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = py
+            .allow_threads(|| runtime.block_on(self.graph.execute(ctx, ContextSnapshot::new())))
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(result.outputs.into_iter().map(|(name, output)| (name, output.into())).collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Pipeline('{}', stages={})", self.graph.name(), self.graph.stage_count())
+    }
+}
+
+/// Builds a pipeline from a JSON manifest of registered stage types.
+///
+/// Raises ValueError if a stage type is unknown, a config is rejected, or
+/// the pipeline is invalid.
+#[pyfunction]
+fn load_manifest(manifest: &str) -> PyResult<PyPipeline> {
+    let to_err = |e: ManifestError| PyValueError::new_err(e.to_string());
+    let manifest = PipelineManifest::from_json(manifest).map_err(to_err)?;
+    let graph = manifest.build(&get_stage_registry()).map_err(to_err)?;
+    Ok(PyPipeline { graph })
+}
+
+/// Validates a JSON pipeline manifest of registered stage types.
+///
+/// Returns the stage names in execution order; use `load_manifest` to get
+/// a runnable pipeline. Raises ValueError like `load_manifest`.
+#[pyfunction]
+fn build_manifest(manifest: &str) -> PyResult<Vec<String>> {
+    Ok(load_manifest(manifest)?.stages())
+}
+
 /// The stageflow Python module.
 #[pymodule]
 fn stageflow_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(set_number_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(get_number_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(registered_stage_types, m)?)?;
+    m.add_function(wrap_pyfunction!(build_manifest, m)?)?;
+    m.add_function(wrap_pyfunction!(load_manifest, m)?)?;
+    m.add_class::<PyPipeline>()?;
     m.add_class::<PyStageOutput>()?;
     m.add_class::<PyStageStatus>()?;
     m.add_class::<PyRunIdentity>()?;
@@ -1,14 +1,162 @@
 //! Benchmarks for pipeline execution.
+//!
+//! Covers the executor's scheduling overhead per stage, snapshot cloning
+//! and sharing across wide pipelines, delta compression and event emission, so regressions in any of them
+//! show up as criterion reports against the previous run.
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use stageflow::compression::{compute_delta, diff, DeltaChain};
+use stageflow::context::{
+    ContextSnapshot, Conversation, Enrichments, ExecutionContext, Message, PipelineContext, RunIdentity,
+};
+use stageflow::events::{CollectingEventSink, NoOpEventSink};
+use stageflow::testing::{BenchShape, PipelineBench};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-fn pipeline_benchmark(c: &mut Criterion) {
-    c.bench_function("noop", |b| {
-        b.iter(|| {
-            black_box(42)
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("benchmark runtime starts")
+}
+
+fn dag_scheduling(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("dag_scheduling");
+    for shape in [BenchShape::Linear, BenchShape::Wide, BenchShape::Diamond] {
+        for stages in [10, 50] {
+            let bench = PipelineBench::new(shape, stages).expect("benchmark pipeline builds");
+            group.throughput(Throughput::Elements(stages as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{shape:?}"), stages), &bench, |b, bench| {
+                b.iter_custom(|iters| {
+                    rt.block_on(async {
+                        let mut total = Duration::ZERO;
+                        for _ in 0..iters {
+                            total += bench.run_once().await.expect("benchmark run succeeds");
+                        }
+                        total
+                    })
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+fn large_snapshot(messages: usize, documents: usize) -> ContextSnapshot {
+    let conversation = Conversation::with_messages(
+        (0..messages)
+            .map(|i| Message::user(format!("message {i} with some representative conversational text")))
+            .collect(),
+    );
+    let enrichments = Enrichments::new().with_documents(
+        (0..documents)
+            .map(|i| serde_json::json!({"id": i, "title": format!("doc {i}"), "body": "lorem ipsum ".repeat(40)}))
+            .collect(),
+    );
+    ContextSnapshot::new().with_conversation(conversation).with_enrichments(enrichments)
+}
+
+fn snapshot_clone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot_clone");
+    for size in [10, 100, 1000] {
+        let snapshot = large_snapshot(size, size / 10);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &snapshot, |b, snapshot| {
+            b.iter(|| black_box(snapshot.clone()));
+        });
+    }
+    group.finish();
+}
+
+fn snapshot_sharing(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("snapshot_sharing");
+    let snapshot = large_snapshot(1000, 100);
+    for stages in [10, 50, 200] {
+        let bench = PipelineBench::wide(stages)
+            .expect("benchmark pipeline builds")
+            .with_snapshot(snapshot.clone());
+        group.throughput(Throughput::Elements(stages as u64));
+        group.bench_with_input(BenchmarkId::new("wide", stages), &bench, |b, bench| {
+            b.iter_custom(|iters| {
+                rt.block_on(async {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..iters {
+                        total += bench.run_once().await.expect("benchmark run succeeds");
+                    }
+                    total
+                })
+            });
+        });
+    }
+    group.finish();
+}
+
+fn state(keys: usize, changed: usize) -> HashMap<String, serde_json::Value> {
+    (0..keys)
+        .map(|i| {
+            let version = usize::from(i < changed);
+            (format!("key_{i}"), serde_json::json!({"value": i, "version": version, "tags": ["a", "b", "c"]}))
         })
+        .collect()
+}
+
+fn delta_compression(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delta_compression");
+    for keys in [100, 1000] {
+        let base = state(keys, 0);
+        let current = state(keys, keys / 10);
+        let (base_json, current_json) = (serde_json::json!(base), serde_json::json!(current));
+        group.bench_with_input(BenchmarkId::new("shallow", keys), &(&base, &current), |b, (base, current)| {
+            b.iter(|| black_box(compute_delta(base, current)));
+        });
+        group.bench_with_input(BenchmarkId::new("deep", keys), &(&base_json, &current_json), |b, (base, current)| {
+            b.iter(|| black_box(diff(base, current)));
+        });
+        group.bench_with_input(BenchmarkId::new("chain_push", keys), &(&base, &current), |b, (base, current)| {
+            b.iter(|| {
+                let mut chain = DeltaChain::new(base);
+                black_box(chain.push(current))
+            });
+        });
+    }
+    group.finish();
+}
+
+fn event_emission(c: &mut Criterion) {
+    const EVENTS: u64 = 1000;
+    let mut group = c.benchmark_group("event_emission");
+    group.throughput(Throughput::Elements(EVENTS));
+    let payload = serde_json::json!({"stage": "bench", "attempt": 1, "detail": "x".repeat(64)});
+    group.bench_function("noop_sink", |b| {
+        let ctx = PipelineContext::new(RunIdentity::new()).with_event_sink(Arc::new(NoOpEventSink));
+        b.iter(|| {
+            for _ in 0..EVENTS {
+                ctx.try_emit_event("stage.bench", Some(payload.clone()));
+            }
+        });
+    });
+    group.bench_function("collecting_sink", |b| {
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone());
+        b.iter(|| {
+            for _ in 0..EVENTS {
+                ctx.try_emit_event("stage.bench", Some(payload.clone()));
+            }
+            sink.clear();
+        });
     });
+    group.finish();
 }
 
-criterion_group!(benches, pipeline_benchmark);
+criterion_group!(
+    benches,
+    dag_scheduling,
+    snapshot_clone,
+    snapshot_sharing,
+    delta_compression,
+    event_emission
+);
 criterion_main!(benches);
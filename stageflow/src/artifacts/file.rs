@@ -0,0 +1,160 @@
+//! Filesystem artifact store.
+
+use super::{ArtifactStore, StoredArtifact};
+use crate::core::StageArtifact;
+use crate::errors::StageflowError;
+use crate::utils::{decode_path_component, encode_path_component};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+/// [`ArtifactStore`] writing one JSON file per artifact under
+/// `root/run/stage/id.json`, with `file://` URIs.
+///
+/// Path components are percent-encoded with
+/// [`encode_path_component`], so distinct ids never share a file.
+#[derive(Debug, Clone)]
+pub struct FileArtifactStore {
+    root: PathBuf,
+}
+
+impl FileArtifactStore {
+    /// Creates a store rooted at the given directory.
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the root directory.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn uri_for(path: &Path) -> String {
+        format!("file://{}", path.display())
+    }
+
+    async fn read(path: &Path) -> Result<Option<StageArtifact>, StageflowError> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(Some(
+                serde_json::from_slice(&bytes).map_err(|e| StageflowError::Serialization(e.to_string()))?,
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn child_dirs(dir: &Path) -> Result<Vec<PathBuf>, StageflowError> {
+        Self::entries(dir, true).await
+    }
+
+    async fn entries(dir: &Path, dirs: bool) -> Result<Vec<PathBuf>, StageflowError> {
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let is_artifact = path.extension().is_some_and(|ext| ext == "json");
+            if entry.file_type().await?.is_dir() == dirs && (dirs || is_artifact) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for FileArtifactStore {
+    async fn put(&self, run_id: &str, stage: &str, artifact: &StageArtifact) -> Result<StoredArtifact, StageflowError> {
+        let dir = self.root.join(encode_path_component(run_id)).join(encode_path_component(stage));
+        tokio::fs::create_dir_all(&dir).await?;
+        let bytes = serde_json::to_vec(artifact).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+
+        // Write then rename so readers never see a partial artifact.
+        let path = dir.join(format!("{}.json", encode_path_component(&artifact.id)));
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(StoredArtifact::new(Self::uri_for(&path), run_id, stage, artifact))
+    }
+
+    async fn get(&self, uri: &str) -> Result<Option<StageArtifact>, StageflowError> {
+        let Some(path) = uri.strip_prefix("file://").map(PathBuf::from) else {
+            return Ok(None);
+        };
+        // Only resolve files this store could have written
+        if !path.starts_with(&self.root) || path.components().any(|c| c == std::path::Component::ParentDir) {
+            return Ok(None);
+        }
+        Self::read(&path).await
+    }
+
+    async fn list(&self, run_id: &str, stage: Option<&str>) -> Result<Vec<StoredArtifact>, StageflowError> {
+        let run_dir = self.root.join(encode_path_component(run_id));
+        let stage_dirs = match stage {
+            Some(stage) => vec![run_dir.join(encode_path_component(stage))],
+            None => Self::child_dirs(&run_dir).await?,
+        };
+
+        let mut stored = Vec::new();
+        for dir in stage_dirs {
+            let stage_name = dir
+                .file_name()
+                .and_then(|name| decode_path_component(&name.to_string_lossy()))
+                .unwrap_or_default();
+            for path in Self::entries(&dir, false).await? {
+                if let Some(artifact) = Self::read(&path).await? {
+                    stored.push(StoredArtifact::new(Self::uri_for(&path), run_id, stage.unwrap_or(&stage_name), &artifact));
+                }
+            }
+        }
+        stored.sort_by(|a, b| (&a.stage, &a.id).cmp(&(&b.stage, &b.id)));
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_file_store_round_trip_and_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileArtifactStore::new(dir.path());
+        let stored = store
+            .put("run-1", "render", &StageArtifact::new("image", "img/1", "chart", json!({"png": "..."})))
+            .await
+            .unwrap();
+        store
+            .put("run-1", "fetch", &StageArtifact::new("page", "p1", "page", json!("<html>")))
+            .await
+            .unwrap();
+        store
+            .put("run-1", "fetch.page", &StageArtifact::new("page", "p1", "page", json!("<body>")))
+            .await
+            .unwrap();
+        store
+            .put("run-1", "fetch_page", &StageArtifact::new("page", "p1", "page", json!("<head>")))
+            .await
+            .unwrap();
+
+        assert!(stored.uri.starts_with("file://") && stored.uri.ends_with("run-1/render/img%2F1.json"));
+        let loaded = store.get(&stored.uri).await.unwrap().unwrap();
+        assert_eq!(loaded.name, "chart");
+        assert!(store.get("file:///etc/passwd").await.unwrap().is_none());
+
+        let listed = store.list("run-1", None).await.unwrap();
+        let ids: Vec<(&str, &str)> = listed.iter().map(|s| (s.stage.as_str(), s.id.as_str())).collect();
+        assert_eq!(
+            ids,
+            vec![("fetch", "p1"), ("fetch.page", "p1"), ("fetch_page", "p1"), ("render", "img/1")]
+        );
+        assert_eq!(store.list("run-1", Some("render")).await.unwrap().len(), 1);
+        assert!(store.list("run-2", None).await.unwrap().is_empty());
+    }
+}
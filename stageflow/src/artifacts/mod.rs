@@ -0,0 +1,23 @@
+//! Storage for stage artifacts.
+//!
+//! An [`ArtifactStore`] keeps artifact payloads outside the run's outputs,
+//! organised by run id and stage, and hands back a URI that stands in for
+//! the payload. [`FileArtifactStore`] writes to a local directory and
+//! [`ObjectArtifactStore`] to S3-compatible storage through an
+//! [`ObjectStore`](crate::context::ObjectStore), such as the `s3` feature's
+//! `S3ObjectStore`.
+//!
+//! When a store is attached with
+//! [`PipelineContext::with_artifact_store`](crate::context::PipelineContext::with_artifact_store),
+//! the executor persists every artifact a stage returns and replaces its
+//! data with an [`artifact_reference`].
+
+mod file;
+mod object;
+mod store;
+
+pub use file::FileArtifactStore;
+pub use object::ObjectArtifactStore;
+pub use store::{
+    artifact_reference, reference_uri, ArtifactStore, InMemoryArtifactStore, StoredArtifact,
+};
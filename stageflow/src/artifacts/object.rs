@@ -0,0 +1,146 @@
+//! Artifact store over S3-compatible object storage.
+
+use super::{ArtifactStore, StoredArtifact};
+use crate::context::ObjectStore;
+use crate::core::StageArtifact;
+use crate::errors::StageflowError;
+use crate::utils::{decode_path_component, encode_path_component};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// [`ArtifactStore`] backed by an [`ObjectStore`], writing one object per
+/// artifact under `prefix/run/stage/id.json`.
+///
+/// URIs are the object key behind a configurable prefix, e.g.
+/// `s3://bucket/` for an S3 client. Listing needs
+/// [`ObjectStore::list_objects`].
+pub struct ObjectArtifactStore {
+    objects: Arc<dyn ObjectStore>,
+    prefix: String,
+    uri_prefix: String,
+}
+
+impl ObjectArtifactStore {
+    /// Creates a store writing objects under `artifacts/` with `object://`
+    /// URIs.
+    #[must_use]
+    pub fn new(objects: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            objects,
+            prefix: "artifacts/".to_string(),
+            uri_prefix: "object://".to_string(),
+        }
+    }
+
+    /// Sets the object key prefix.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets what URIs start with before the object key.
+    #[must_use]
+    pub fn with_uri_prefix(mut self, uri_prefix: impl Into<String>) -> Self {
+        self.uri_prefix = uri_prefix.into();
+        self
+    }
+
+    fn run_prefix(&self, run_id: &str) -> String {
+        format!("{}{}/", self.prefix, encode_path_component(run_id))
+    }
+
+    fn uri_for(&self, key: &str) -> String {
+        format!("{}{key}", self.uri_prefix)
+    }
+
+    async fn read(&self, key: &str) -> Result<Option<StageArtifact>, StageflowError> {
+        match self.objects.get_object(key).await? {
+            Some(bytes) => Ok(Some(
+                serde_json::from_slice(&bytes).map_err(|e| StageflowError::Serialization(e.to_string()))?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl std::fmt::Debug for ObjectArtifactStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectArtifactStore")
+            .field("prefix", &self.prefix)
+            .field("uri_prefix", &self.uri_prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for ObjectArtifactStore {
+    async fn put(&self, run_id: &str, stage: &str, artifact: &StageArtifact) -> Result<StoredArtifact, StageflowError> {
+        let key = format!(
+            "{}{}/{}.json",
+            self.run_prefix(run_id),
+            encode_path_component(stage),
+            encode_path_component(&artifact.id)
+        );
+        let bytes = serde_json::to_vec(artifact).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        self.objects.put_object(&key, bytes).await?;
+        Ok(StoredArtifact::new(self.uri_for(&key), run_id, stage, artifact))
+    }
+
+    async fn get(&self, uri: &str) -> Result<Option<StageArtifact>, StageflowError> {
+        match uri.strip_prefix(&self.uri_prefix) {
+            Some(key) if key.starts_with(&self.prefix) => self.read(key).await,
+            _ => Ok(None),
+        }
+    }
+
+    async fn list(&self, run_id: &str, stage: Option<&str>) -> Result<Vec<StoredArtifact>, StageflowError> {
+        let run_prefix = self.run_prefix(run_id);
+        let prefix = match stage {
+            Some(stage) => format!("{run_prefix}{}/", encode_path_component(stage)),
+            None => run_prefix.clone(),
+        };
+
+        let mut stored = Vec::new();
+        for key in self.objects.list_objects(&prefix).await? {
+            let Some(stage_name) = key[run_prefix.len()..]
+                .split_once('/')
+                .and_then(|(stage_name, _)| decode_path_component(stage_name))
+            else {
+                continue;
+            };
+            if let Some(artifact) = self.read(&key).await? {
+                stored.push(StoredArtifact::new(self.uri_for(&key), run_id, stage.unwrap_or(&stage_name), &artifact));
+            }
+        }
+        stored.sort_by(|a, b| (&a.stage, &a.id).cmp(&(&b.stage, &b.id)));
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::InMemoryObjectStore;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_object_store_keys_uris_and_listing() {
+        let objects = Arc::new(InMemoryObjectStore::new());
+        let store = ObjectArtifactStore::new(objects.clone()).with_uri_prefix("s3://bucket/");
+        let stored = store
+            .put("run 1", "render", &StageArtifact::new("image", "chart", "chart", json!([1, 2])))
+            .await
+            .unwrap();
+
+        assert_eq!(stored.uri, "s3://bucket/artifacts/run%201/render/chart.json");
+        assert_eq!(objects.keys(), vec!["artifacts/run%201/render/chart.json"]);
+        assert_eq!(store.get(&stored.uri).await.unwrap().unwrap().data, json!([1, 2]));
+        assert!(store.get("s3://other/artifacts/run%201/render/chart.json").await.unwrap().is_none());
+
+        let listed = store.list("run 1", None).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].stage, "render");
+        assert!(store.list("run 1", Some("fetch")).await.unwrap().is_empty());
+    }
+}
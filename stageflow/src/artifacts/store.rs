@@ -0,0 +1,171 @@
+//! The artifact store trait and an in-memory implementation.
+
+use crate::core::StageArtifact;
+use crate::errors::StageflowError;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Where an artifact was stored, as returned by [`ArtifactStore::put`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredArtifact {
+    /// URI that [`ArtifactStore::get`] resolves.
+    pub uri: String,
+    /// The run that produced the artifact.
+    pub run_id: String,
+    /// The stage that produced the artifact.
+    pub stage: String,
+    /// The artifact id.
+    pub id: String,
+    /// The artifact name.
+    pub name: String,
+    /// The artifact type.
+    pub artifact_type: String,
+    /// Size of the artifact data serialized as JSON.
+    pub size_bytes: usize,
+}
+
+impl StoredArtifact {
+    /// Describes `artifact` stored under `uri`.
+    #[must_use]
+    pub fn new(uri: impl Into<String>, run_id: &str, stage: &str, artifact: &StageArtifact) -> Self {
+        Self {
+            uri: uri.into(),
+            run_id: run_id.to_string(),
+            stage: stage.to_string(),
+            id: artifact.id.clone(),
+            name: artifact.name.clone(),
+            artifact_type: artifact.artifact_type.clone(),
+            size_bytes: serde_json::to_vec(&artifact.data).map_or(0, |data| data.len()),
+        }
+    }
+}
+
+/// Returns the value that replaces a stored payload:
+/// `{"artifact_uri": ..., "size_bytes": ...}`.
+#[must_use]
+pub fn artifact_reference(stored: &StoredArtifact) -> serde_json::Value {
+    serde_json::json!({
+        "artifact_uri": stored.uri,
+        "size_bytes": stored.size_bytes,
+    })
+}
+
+/// Returns the URI if `value` is a reference made by [`artifact_reference`].
+#[must_use]
+pub fn reference_uri(value: &serde_json::Value) -> Option<&str> {
+    let object = value.as_object()?;
+    if object.len() != 2 || !object.contains_key("size_bytes") {
+        return None;
+    }
+    object.get("artifact_uri")?.as_str()
+}
+
+/// Storage for artifact payloads, keyed by run id and stage.
+///
+/// Storing an artifact with the same run, stage and id again replaces it.
+#[async_trait]
+pub trait ArtifactStore: Send + Sync {
+    /// Stores an artifact and returns where it was stored.
+    async fn put(&self, run_id: &str, stage: &str, artifact: &StageArtifact) -> Result<StoredArtifact, StageflowError>;
+
+    /// Loads the artifact stored under `uri`.
+    async fn get(&self, uri: &str) -> Result<Option<StageArtifact>, StageflowError>;
+
+    /// Lists a run's artifacts, optionally only those of one stage, ordered
+    /// by stage and id.
+    async fn list(&self, run_id: &str, stage: Option<&str>) -> Result<Vec<StoredArtifact>, StageflowError>;
+}
+
+/// Run id, stage and artifact id.
+type ArtifactKey = (String, String, String);
+
+/// In-memory [`ArtifactStore`] with `memory://run/stage/id` URIs, useful
+/// for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryArtifactStore {
+    artifacts: RwLock<BTreeMap<ArtifactKey, (StoredArtifact, StageArtifact)>>,
+}
+
+impl InMemoryArtifactStore {
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of stored artifacts.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.artifacts.read().len()
+    }
+
+    /// Returns true if nothing is stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.artifacts.read().is_empty()
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for InMemoryArtifactStore {
+    async fn put(&self, run_id: &str, stage: &str, artifact: &StageArtifact) -> Result<StoredArtifact, StageflowError> {
+        let uri = format!("memory://{run_id}/{stage}/{}", artifact.id);
+        let stored = StoredArtifact::new(uri, run_id, stage, artifact);
+        let key = (run_id.to_string(), stage.to_string(), artifact.id.clone());
+        self.artifacts.write().insert(key, (stored.clone(), artifact.clone()));
+        Ok(stored)
+    }
+
+    async fn get(&self, uri: &str) -> Result<Option<StageArtifact>, StageflowError> {
+        Ok(self
+            .artifacts
+            .read()
+            .values()
+            .find(|(stored, _)| stored.uri == uri)
+            .map(|(_, artifact)| artifact.clone()))
+    }
+
+    async fn list(&self, run_id: &str, stage: Option<&str>) -> Result<Vec<StoredArtifact>, StageflowError> {
+        Ok(self
+            .artifacts
+            .read()
+            .values()
+            .map(|(stored, _)| stored)
+            .filter(|stored| stored.run_id == run_id && stage.map_or(true, |stage| stored.stage == stage))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_in_memory_store_put_get_list() {
+        let store = InMemoryArtifactStore::new();
+        let report = StageArtifact::new("report", "r1", "summary", json!({"words": 120}));
+        let stored = store.put("run-1", "summarize", &report).await.unwrap();
+        assert_eq!(stored.uri, "memory://run-1/summarize/r1");
+        assert_eq!(stored.size_bytes, 13);
+        store
+            .put("run-1", "fetch", &StageArtifact::new("page", "p1", "page", json!("<html>")))
+            .await
+            .unwrap();
+        store
+            .put("run-2", "fetch", &StageArtifact::new("page", "p1", "page", json!("<html>")))
+            .await
+            .unwrap();
+
+        let loaded = store.get(&stored.uri).await.unwrap().unwrap();
+        assert_eq!(loaded.data, json!({"words": 120}));
+        assert!(store.get("memory://run-1/summarize/missing").await.unwrap().is_none());
+
+        let stages: Vec<String> = store.list("run-1", None).await.unwrap().into_iter().map(|s| s.stage).collect();
+        assert_eq!(stages, vec!["fetch", "summarize"]);
+        assert_eq!(store.list("run-1", Some("fetch")).await.unwrap().len(), 1);
+    }
+}
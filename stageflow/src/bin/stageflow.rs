@@ -0,0 +1,73 @@
+//! Command-line tools for stageflow pipelines.
+//!
+//! ```text
+//! stageflow validate <pipeline.json> [--json] [--fix]
+//! ```
+//!
+//! `validate` lints a JSON [`PipelineManifest`], the same file
+//! `PipelineManifest::build` loads; `PipelineBuilder::manifest()` describes
+//! a pipeline built in code. It prints one line per finding, or the report
+//! as JSON with `--json`; `--fix` prints the manifest with the suggested
+//! fixes applied instead. The exit code is 1 if any finding is a
+//! warning or worse, 2 on usage or input errors.
+
+use stageflow::pipeline::{LintSeverity, PipelineLinter, PipelineManifest};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: stageflow validate <pipeline.json> [--json] [--fix]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("validate") => match validate(&args[1..]) {
+            Ok(code) => code,
+            Err(message) => {
+                eprintln!("stageflow: {message}");
+                ExitCode::from(2)
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn validate(args: &[String]) -> Result<ExitCode, String> {
+    let mut path = None;
+    let (mut json, mut fix) = (false, false);
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--fix" => fix = true,
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{flag}'\n{USAGE}")),
+            file if path.is_none() => path = Some(file),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or_else(|| USAGE.to_string())?;
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let manifest = PipelineManifest::from_json(&text).map_err(|e| format!("invalid pipeline in {path}: {e}"))?;
+
+    let report = PipelineLinter::new().lint(&manifest);
+    if fix {
+        let fixed = report.fix(&manifest);
+        println!("{}", serde_json::to_string_pretty(&fixed).map_err(|e| e.to_string())?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+    } else {
+        for finding in &report.findings {
+            println!("{finding}");
+            if let Some(edit) = &finding.suggestion {
+                println!("  fix: {}", serde_json::to_string(edit).map_err(|e| e.to_string())?);
+            }
+        }
+        println!("{}: {} finding(s)", manifest.name, report.findings.len());
+    }
+
+    let failed = report.max_severity() >= Some(LintSeverity::Warning);
+    Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
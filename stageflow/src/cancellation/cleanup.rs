@@ -1,5 +1,7 @@
 //! Cleanup registry and utilities.
 
+use crate::context::leak::{CleanupToken, LeakTracker};
+use crate::context::LeakDetector;
 use parking_lot::RwLock;
 use std::future::Future;
 use std::sync::Arc;
@@ -12,6 +14,17 @@ pub struct CleanupCallback {
     callback: Box<dyn Fn() + Send + Sync>,
     /// Optional name for the callback.
     name: Option<String>,
+    /// Registration with a leak detector, if one is attached.
+    token: Option<CleanupToken>,
+}
+
+impl CleanupCallback {
+    /// Marks the callback as run or deliberately discarded.
+    fn settle(&mut self) {
+        if let Some(token) = self.token.take() {
+            token.settle();
+        }
+    }
 }
 
 /// Registry for cleanup callbacks executed in LIFO order.
@@ -19,6 +32,8 @@ pub struct CleanupCallback {
 pub struct CleanupRegistry {
     /// Registered callbacks.
     callbacks: RwLock<Vec<CleanupCallback>>,
+    /// Leak tracker notified of registered and settled callbacks.
+    leak_tracker: Option<Arc<LeakTracker>>,
 }
 
 impl CleanupRegistry {
@@ -28,6 +43,16 @@ impl CleanupRegistry {
         Self::default()
     }
 
+    /// Reports callbacks that are never run to a leak detector.
+    ///
+    /// Callbacks removed with [`unregister_by_name`](Self::unregister_by_name)
+    /// or [`clear`](Self::clear) count as settled.
+    #[must_use]
+    pub fn with_leak_detector(mut self, detector: &LeakDetector) -> Self {
+        self.leak_tracker = Some(detector.tracker());
+        self
+    }
+
     /// Registers a cleanup callback.
     ///
     /// If a name is provided, it's stored with the callback for debugging.
@@ -35,9 +60,14 @@ impl CleanupRegistry {
     where
         F: Fn() + Send + Sync + 'static,
     {
+        let token = self
+            .leak_tracker
+            .as_ref()
+            .map(|tracker| tracker.track_cleanup(name.unwrap_or("<unnamed>")));
         self.callbacks.write().push(CleanupCallback {
             callback: Box::new(callback),
             name: name.map(String::from),
+            token,
         });
     }
 
@@ -46,9 +76,12 @@ impl CleanupRegistry {
     /// Returns true if a callback was removed.
     pub fn unregister_by_name(&self, name: &str) -> bool {
         let mut callbacks = self.callbacks.write();
-        let initial_len = callbacks.len();
-        callbacks.retain(|cb| cb.name.as_deref() != Some(name));
-        callbacks.len() < initial_len
+        let (mut removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *callbacks)
+            .into_iter()
+            .partition(|cb| cb.name.as_deref() == Some(name));
+        *callbacks = kept;
+        removed.iter_mut().for_each(CleanupCallback::settle);
+        !removed.is_empty()
     }
 
     /// Runs all cleanup callbacks in LIFO order.
@@ -70,8 +103,9 @@ impl CleanupRegistry {
         let mut failures = Vec::new();
 
         // Execute in LIFO order (reverse)
-        for entry in callbacks.into_iter().rev() {
+        for mut entry in callbacks.into_iter().rev() {
             let name = entry.name.clone().unwrap_or_else(|| "<unnamed>".to_string());
+            entry.settle();
 
             let result = tokio::time::timeout(
                 Duration::from_secs_f64(per_callback_timeout),
@@ -116,7 +150,9 @@ impl CleanupRegistry {
 
     /// Clears all registered callbacks without running them.
     pub fn clear(&self) {
-        self.callbacks.write().clear();
+        let mut callbacks = self.callbacks.write();
+        callbacks.iter_mut().for_each(CleanupCallback::settle);
+        callbacks.clear();
     }
 }
 
@@ -272,4 +308,21 @@ mod tests {
         let not_found = registry.unregister_by_name("nonexistent");
         assert!(!not_found);
     }
+
+    #[tokio::test]
+    async fn test_leak_detector_tracks_unrun_callbacks() {
+        let detector = LeakDetector::new();
+        let registry = CleanupRegistry::new().with_leak_detector(&detector);
+        registry.register(|| {}, Some("flush"));
+        registry.register(|| {}, Some("discarded"));
+        assert_eq!(detector.report().pending_cleanups.len(), 2);
+
+        registry.unregister_by_name("discarded");
+        registry.run_all(1.0).await;
+        detector.assert_no_leaks();
+
+        registry.register(|| {}, Some("close_socket"));
+        drop(registry);
+        assert_eq!(detector.report().abandoned_cleanups, vec!["close_socket"]);
+    }
 }
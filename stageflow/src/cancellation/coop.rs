@@ -0,0 +1,256 @@
+//! Cooperative yielding for long synchronous loops inside async stages.
+
+use crate::context::{ExecutionContext, StageContext};
+use crate::core::StageOutput;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Default time a stage may run between yields.
+pub const DEFAULT_COOP_SLICE: Duration = Duration::from_millis(10);
+
+/// Default longest acceptable gap between yields before it is reported.
+pub const DEFAULT_POLL_BUDGET: Duration = Duration::from_millis(50);
+
+/// Why a [`CoopYield::tick`] asked the stage to stop.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CoopInterrupt {
+    /// The pipeline was cancelled.
+    #[error("stage cancelled: {0}")]
+    Cancelled(String),
+    /// The deadline passed.
+    #[error("stage deadline exceeded after {elapsed_ms} ms")]
+    DeadlineExceeded {
+        /// Time since the helper was created.
+        elapsed_ms: u64,
+    },
+}
+
+impl CoopInterrupt {
+    /// Converts the interrupt into the output the stage should return.
+    #[must_use]
+    pub fn into_output(self) -> StageOutput {
+        match self {
+            Self::Cancelled(reason) => StageOutput::cancel(reason),
+            Self::DeadlineExceeded { .. } => StageOutput::fail(self.to_string()),
+        }
+    }
+}
+
+/// Counters describing how a stage yielded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoopStats {
+    /// Calls to [`CoopYield::tick`].
+    pub ticks: u64,
+    /// Times the stage yielded to the runtime.
+    pub yields: u64,
+    /// Gaps between yields longer than the poll budget.
+    pub budget_overruns: u64,
+    /// Longest gap between yields, in milliseconds.
+    pub max_slice_ms: f64,
+}
+
+/// Time-sliced yielding for CPU-bound loops that cannot be moved off the
+/// async runtime.
+///
+/// Call [`tick`](Self::tick) once per iteration. Once the current time slice
+/// has elapsed it checks for cancellation and the deadline, then yields so
+/// other tasks on the worker thread can make progress. Gaps between yields
+/// longer than the poll budget are logged and emitted as
+/// `stage.poll_budget_exceeded`. The executor attaches the final
+/// [`CoopStats`] to the stage output metadata under `coop`.
+///
+/// ```ignore
+/// let mut coop = ctx.coop();
+/// for row in rows {
+///     if let Err(interrupt) = coop.tick().await {
+///         return interrupt.into_output();
+///     }
+///     score(row);
+/// }
+/// ```
+pub struct CoopYield<'a> {
+    ctx: &'a StageContext,
+    slice: Duration,
+    poll_budget: Duration,
+    deadline: Option<Instant>,
+    started: Instant,
+    last_yield: Instant,
+    stats: CoopStats,
+}
+
+impl<'a> CoopYield<'a> {
+    /// Creates a helper with the default slice and poll budget.
+    #[must_use]
+    pub fn new(ctx: &'a StageContext) -> Self {
+        let now = Instant::now();
+        Self {
+            ctx,
+            slice: DEFAULT_COOP_SLICE,
+            poll_budget: DEFAULT_POLL_BUDGET,
+            deadline: None,
+            started: now,
+            last_yield: now,
+            stats: CoopStats::default(),
+        }
+    }
+
+    /// Sets how long the stage runs between yields.
+    #[must_use]
+    pub fn with_slice(mut self, slice: Duration) -> Self {
+        self.slice = slice;
+        self
+    }
+
+    /// Sets the longest acceptable gap between yields.
+    #[must_use]
+    pub fn with_poll_budget(mut self, budget: Duration) -> Self {
+        self.poll_budget = budget;
+        self
+    }
+
+    /// Stops the loop once `timeout` has elapsed from now.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(self.started + timeout);
+        self
+    }
+
+    /// Yields if the current slice is used up.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoopInterrupt`] if the pipeline was cancelled or the
+    /// deadline passed; the stage should stop and return
+    /// [`CoopInterrupt::into_output`].
+    pub async fn tick(&mut self) -> Result<(), CoopInterrupt> {
+        self.stats.ticks += 1;
+        let now = Instant::now();
+        let gap = now.duration_since(self.last_yield);
+        if gap < self.slice {
+            return Ok(());
+        }
+
+        self.record_slice(gap);
+        if let Some(interrupt) = self.interrupt(now) {
+            self.publish();
+            return Err(interrupt);
+        }
+
+        tokio::task::yield_now().await;
+        self.stats.yields += 1;
+        self.last_yield = Instant::now();
+        self.publish();
+        Ok(())
+    }
+
+    /// Returns the counters so far.
+    #[must_use]
+    pub fn stats(&self) -> &CoopStats {
+        &self.stats
+    }
+
+    fn record_slice(&mut self, gap: Duration) {
+        let gap_ms = gap.as_secs_f64() * 1000.0;
+        self.stats.max_slice_ms = self.stats.max_slice_ms.max(gap_ms);
+        if gap <= self.poll_budget {
+            return;
+        }
+
+        self.stats.budget_overruns += 1;
+        let budget_ms = self.poll_budget.as_secs_f64() * 1000.0;
+        warn!(
+            stage = %self.ctx.stage_name(),
+            slice_ms = gap_ms,
+            budget_ms,
+            "Stage ran past its poll budget without yielding"
+        );
+        self.ctx.try_emit_event(
+            "stage.poll_budget_exceeded",
+            Some(serde_json::json!({
+                "stage": self.ctx.stage_name(),
+                "slice_ms": gap_ms,
+                "budget_ms": budget_ms,
+                "overruns": self.stats.budget_overruns,
+            })),
+        );
+    }
+
+    fn interrupt(&self, now: Instant) -> Option<CoopInterrupt> {
+        if let Err(interrupt) = self.ctx.checkpoint() {
+            return Some(interrupt);
+        }
+        match self.deadline {
+            Some(deadline) if now >= deadline => Some(CoopInterrupt::DeadlineExceeded {
+                elapsed_ms: u64::try_from(now.duration_since(self.started).as_millis())
+                    .unwrap_or(u64::MAX),
+            }),
+            _ => None,
+        }
+    }
+
+    fn publish(&self) {
+        self.ctx.record_coop_stats(self.stats.clone());
+    }
+}
+
+impl std::fmt::Debug for CoopYield<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoopYield")
+            .field("stage", &self.ctx.stage_name())
+            .field("slice", &self.slice)
+            .field("poll_budget", &self.poll_budget)
+            .field("deadline", &self.deadline)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageInputs};
+    use std::sync::Arc;
+
+    fn stage_context(pipeline_ctx: Arc<PipelineContext>) -> StageContext {
+        StageContext::new(pipeline_ctx, "crunch", StageInputs::default(), ContextSnapshot::new())
+    }
+
+    #[tokio::test]
+    async fn test_yields_once_slice_elapses() {
+        let ctx = stage_context(Arc::new(PipelineContext::new(RunIdentity::new())));
+        let mut coop = CoopYield::new(&ctx)
+            .with_slice(Duration::ZERO)
+            .with_poll_budget(Duration::from_millis(1));
+
+        coop.tick().await.unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        coop.tick().await.unwrap();
+
+        assert_eq!(coop.stats().yields, 2);
+        assert_eq!(coop.stats().budget_overruns, 1);
+        assert_eq!(ctx.coop_stats(), Some(coop.stats().clone()));
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_cancel_and_deadline() {
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let ctx = stage_context(pipeline_ctx.clone());
+
+        let mut coop = CoopYield::new(&ctx).with_slice(Duration::ZERO);
+        coop.tick().await.unwrap();
+        pipeline_ctx.mark_cancelled_with_reason("user abort");
+        let interrupt = coop.tick().await.unwrap_err();
+        assert_eq!(interrupt, CoopInterrupt::Cancelled("user abort".to_string()));
+        assert!(interrupt.into_output().status == crate::core::StageStatus::Cancel);
+
+        let ctx = stage_context(Arc::new(PipelineContext::new(RunIdentity::new())));
+        let mut coop = CoopYield::new(&ctx)
+            .with_slice(Duration::ZERO)
+            .with_timeout(Duration::ZERO);
+        assert!(matches!(
+            coop.tick().await,
+            Err(CoopInterrupt::DeadlineExceeded { .. })
+        ));
+    }
+}
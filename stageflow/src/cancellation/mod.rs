@@ -4,11 +4,14 @@
 //! - CancellationToken for cooperative cancellation
 //! - CleanupRegistry for LIFO cleanup execution
 //! - StructuredTaskGroup for managing related tasks
+//! - `CoopYield` for time-sliced yielding in CPU-bound stage loops
 
 mod cleanup;
+mod coop;
 mod task_group;
 mod token;
 
 pub use cleanup::{cleanup_on_cancel, run_with_cleanup, CleanupRegistry};
+pub use coop::{CoopInterrupt, CoopStats, CoopYield, DEFAULT_COOP_SLICE, DEFAULT_POLL_BUDGET};
 pub use task_group::StructuredTaskGroup;
 pub use token::CancellationToken;
@@ -80,6 +80,22 @@ impl CancellationToken {
         self.reason.read().clone()
     }
 
+    /// Creates a token that is cancelled, with this token's reason, when
+    /// this one is.
+    ///
+    /// Cancelling the child leaves this token untouched.
+    #[must_use]
+    pub fn child(self: &Arc<Self>) -> Arc<Self> {
+        let child = Arc::new(Self::new());
+        let (parent, weak_child) = (Arc::downgrade(self), Arc::downgrade(&child));
+        self.on_cancel(move || {
+            if let (Some(parent), Some(child)) = (parent.upgrade(), weak_child.upgrade()) {
+                child.cancel(parent.reason().unwrap_or_default());
+            }
+        });
+        child
+    }
+
     /// Resets the token (for testing).
     pub fn reset(&self) {
         self.cancelled.store(false, Ordering::SeqCst);
@@ -161,6 +177,19 @@ mod tests {
         assert_eq!(counter.load(Ordering::SeqCst), 1);
     }
 
+    #[test]
+    fn test_child_follows_parent() {
+        let parent = Arc::new(CancellationToken::new());
+        let child = parent.child();
+        child.cancel("child only");
+        assert!(!parent.is_cancelled());
+
+        let child = parent.child();
+        parent.cancel("parent");
+        assert_eq!(child.reason().as_deref(), Some("parent"));
+        assert_eq!(parent.child().reason().as_deref(), Some("parent"));
+    }
+
     #[test]
     fn test_callback_exception_suppressed() {
         let token = CancellationToken::new();
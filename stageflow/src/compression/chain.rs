@@ -0,0 +1,186 @@
+//! Delta chains for storing a sequence of snapshots compactly.
+
+use super::patch::{apply_patch, diff, PatchOp};
+use super::{encoded_len, to_object, CompressionMetrics};
+use crate::errors::PatchError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Default number of deltas kept before the chain is re-based.
+pub const DEFAULT_REBASE_INTERVAL: usize = 32;
+
+/// A base snapshot followed by deep deltas to each later version.
+///
+/// Each [`push`](Self::push) stores only a JSON Patch from the previous
+/// version. The chain re-bases onto the latest version once it holds
+/// `rebase_interval` deltas or once the deltas together outgrow the base,
+/// which bounds both replay cost and storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaChain {
+    base: Value,
+    deltas: Vec<Vec<PatchOp>>,
+    head: Value,
+    base_version: u64,
+    rebase_interval: usize,
+    base_bytes: usize,
+    delta_bytes: usize,
+}
+
+impl DeltaChain {
+    /// Creates a chain starting from `base`.
+    #[must_use]
+    pub fn new(base: &HashMap<String, Value>) -> Self {
+        let base = to_object(base);
+        Self {
+            base_bytes: encoded_len(&base),
+            head: base.clone(),
+            base,
+            deltas: Vec::new(),
+            base_version: 0,
+            rebase_interval: DEFAULT_REBASE_INTERVAL,
+            delta_bytes: 0,
+        }
+    }
+
+    /// Sets how many deltas are kept before re-basing.
+    #[must_use]
+    pub fn with_rebase_interval(mut self, interval: usize) -> Self {
+        self.rebase_interval = interval.max(1);
+        self
+    }
+
+    /// Records a new version and returns the size of its delta.
+    ///
+    /// Versions identical to the current head are not stored.
+    pub fn push(&mut self, snapshot: &HashMap<String, Value>) -> CompressionMetrics {
+        let next = to_object(snapshot);
+        let patch = diff(&self.head, &next);
+        let original = encoded_len(&next);
+        if patch.is_empty() {
+            return CompressionMetrics::new(original, 0);
+        }
+
+        let patch_bytes = encoded_len(&patch);
+        self.head = next;
+        self.deltas.push(patch);
+        self.delta_bytes += patch_bytes;
+        if self.deltas.len() >= self.rebase_interval || self.delta_bytes > self.base_bytes {
+            self.rebase();
+        }
+        CompressionMetrics::new(original, patch_bytes)
+    }
+
+    /// Makes the latest version the new base and drops the stored deltas.
+    pub fn rebase(&mut self) {
+        self.base_version = self.version();
+        self.base = self.head.clone();
+        self.base_bytes = encoded_len(&self.base);
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+
+    /// Returns the latest version.
+    #[must_use]
+    pub fn head(&self) -> HashMap<String, Value> {
+        from_object(&self.head)
+    }
+
+    /// Returns the number of the latest version; the first base is version 0.
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.base_version + self.deltas.len() as u64
+    }
+
+    /// Returns the oldest version still reconstructible.
+    #[must_use]
+    pub fn base_version(&self) -> u64 {
+        self.base_version
+    }
+
+    /// Returns the deltas stored since the base.
+    #[must_use]
+    pub fn deltas(&self) -> &[Vec<PatchOp>] {
+        &self.deltas
+    }
+
+    /// Returns the encoded size of the base and stored deltas.
+    #[must_use]
+    pub fn stored_bytes(&self) -> usize {
+        self.base_bytes + self.delta_bytes
+    }
+
+    /// Reconstructs a version by replaying deltas onto the base.
+    ///
+    /// Returns `Ok(None)` if the version was re-based away or does not exist
+    /// yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PatchError`] if a stored delta no longer applies, which
+    /// indicates a corrupted chain.
+    pub fn materialize(&self, version: u64) -> Result<Option<HashMap<String, Value>>, PatchError> {
+        if version < self.base_version || version > self.version() {
+            return Ok(None);
+        }
+        let count = usize::try_from(version - self.base_version).unwrap_or(usize::MAX);
+        let mut doc = self.base.clone();
+        for patch in &self.deltas[..count] {
+            apply_patch(&mut doc, patch)?;
+        }
+        Ok(Some(from_object(&doc)))
+    }
+}
+
+fn from_object(value: &Value) -> HashMap<String, Value> {
+    value
+        .as_object()
+        .map(|map| map.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state(step: u64) -> HashMap<String, Value> {
+        let mut state = HashMap::new();
+        state.insert("document".to_string(), json!({"body": "x".repeat(1000), "step": step}));
+        state.insert("history".to_string(), json!((0..step).collect::<Vec<_>>()));
+        state
+    }
+
+    #[test]
+    fn test_chain_stores_small_deltas_and_replays() {
+        let mut chain = DeltaChain::new(&state(0)).with_rebase_interval(100);
+        for step in 1..=3 {
+            let metrics = chain.push(&state(step));
+            assert!(metrics.ratio < 0.5);
+        }
+        assert_eq!(chain.push(&state(3)).delta_bytes, 0);
+
+        assert_eq!(chain.version(), 3);
+        assert_eq!(chain.head(), state(3));
+        assert_eq!(chain.materialize(1).unwrap(), Some(state(1)));
+        assert_eq!(chain.materialize(4).unwrap(), None);
+        assert!(chain.stored_bytes() < 2 * encoded_len(&to_object(&state(3))));
+    }
+
+    #[test]
+    fn test_chain_rebases_periodically() {
+        let mut chain = DeltaChain::new(&state(0)).with_rebase_interval(2);
+        chain.push(&state(1));
+        assert_eq!(chain.deltas().len(), 1);
+        chain.push(&state(2));
+        assert!(chain.deltas().is_empty());
+        assert_eq!(chain.base_version(), 2);
+        assert_eq!(chain.materialize(1).unwrap(), None);
+        assert_eq!(chain.materialize(2).unwrap(), Some(state(2)));
+
+        // Deltas larger than the base force a rebase too
+        let mut chain = DeltaChain::new(&HashMap::new());
+        chain.push(&state(5));
+        assert_eq!((chain.base_version(), chain.deltas().len()), (1, 0));
+    }
+}
@@ -0,0 +1,239 @@
+//! Binary codecs for compressing persisted payloads.
+//!
+//! Gzip and zstd support are behind the `gzip` and `zstd` features.
+//! Compressed payloads are recognized by their magic bytes, so readers can
+//! use [`decompress`] without knowing which codec, if any, wrote them.
+
+use crate::errors::CodecError;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Magic bytes starting a gzip member.
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Magic bytes starting a zstd frame.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A byte-level compression codec.
+pub trait Codec: Send + Sync + std::fmt::Debug {
+    /// Returns the codec name, which is also its HTTP `Content-Encoding`
+    /// token.
+    fn name(&self) -> &'static str;
+
+    /// Compresses a payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CodecError`] if compression fails.
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError>;
+
+    /// Decompresses a payload produced by [`encode`](Self::encode).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CodecError`] if the payload is corrupt.
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError>;
+}
+
+/// Codec that leaves payloads uncompressed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityCodec;
+
+impl Codec for IdentityCodec {
+    fn name(&self) -> &'static str {
+        "identity"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        Ok(data.to_vec())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        Ok(data.to_vec())
+    }
+}
+
+/// Gzip codec.
+#[cfg(feature = "gzip")]
+#[derive(Debug, Clone, Copy)]
+pub struct GzipCodec {
+    level: u32,
+}
+
+#[cfg(feature = "gzip")]
+impl Default for GzipCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl GzipCodec {
+    /// Creates a codec using compression level 6.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { level: 6 }
+    }
+
+    /// Sets the compression level, from 0 (none) to 9 (best).
+    #[must_use]
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Codec for GzipCodec {
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        use std::io::Write;
+
+        let mut encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(self.level));
+        encoder
+            .write_all(data)
+            .and_then(|()| encoder.finish())
+            .map_err(|e| CodecError::new(self.name(), e))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        use std::io::Read;
+
+        // Appended writes produce several members; read them all.
+        let mut out = Vec::new();
+        flate2::read::MultiGzDecoder::new(data)
+            .read_to_end(&mut out)
+            .map_err(|e| CodecError::new(self.name(), e))?;
+        Ok(out)
+    }
+}
+
+/// Zstandard codec.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy)]
+pub struct ZstdCodec {
+    level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCodec {
+    /// Creates a codec using compression level 3.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { level: 3 }
+    }
+
+    /// Sets the compression level, from 1 (fastest) to 22 (best).
+    #[must_use]
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level.clamp(1, 22);
+        self
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Codec for ZstdCodec {
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        zstd::encode_all(data, self.level).map_err(|e| CodecError::new(self.name(), e))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        zstd::decode_all(data).map_err(|e| CodecError::new(self.name(), e))
+    }
+}
+
+/// Returns the codec registered under `name`, if it is compiled in.
+#[must_use]
+pub fn codec_by_name(name: &str) -> Option<Arc<dyn Codec>> {
+    match name {
+        "identity" => Some(Arc::new(IdentityCodec)),
+        #[cfg(feature = "gzip")]
+        "gzip" => Some(Arc::new(GzipCodec::new())),
+        #[cfg(feature = "zstd")]
+        "zstd" => Some(Arc::new(ZstdCodec::new())),
+        _ => None,
+    }
+}
+
+/// Returns the name of the codec that produced `data`, judging by its magic
+/// bytes, or `None` for uncompressed data.
+#[must_use]
+pub fn detect_codec(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&GZIP_MAGIC) {
+        Some("gzip")
+    } else if data.starts_with(&ZSTD_MAGIC) {
+        Some("zstd")
+    } else {
+        None
+    }
+}
+
+/// Decompresses `data` with the codec detected from its magic bytes.
+///
+/// Uncompressed data is returned as is.
+///
+/// # Errors
+///
+/// Returns [`CodecError`] if the data is corrupt or its codec is not
+/// compiled in.
+pub fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>, CodecError> {
+    let Some(name) = detect_codec(data) else {
+        return Ok(Cow::Borrowed(data));
+    };
+    let codec = codec_by_name(name).ok_or_else(|| {
+        CodecError::new(
+            name,
+            std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("enable the `{name}` feature to read this payload"),
+            ),
+        )
+    })?;
+    codec.decode(data).map(Cow::Owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_detects_codec() {
+        let payload = br#"{"messages":["hello","hello","hello","hello"]}"#.repeat(50);
+        assert!(matches!(decompress(&payload).unwrap(), Cow::Borrowed(_)));
+
+        // Codecs whose feature is disabled are skipped
+        for codec in ["identity", "gzip", "zstd"].into_iter().filter_map(codec_by_name) {
+            let encoded = codec.encode(&payload).unwrap();
+            if codec.name() != "identity" {
+                assert_eq!(detect_codec(&encoded), Some(codec.name()));
+                assert!(encoded.len() < payload.len() / 10);
+            }
+            assert_eq!(decompress(&encoded).unwrap().as_ref(), payload.as_slice());
+            assert_eq!(codec.decode(&encoded).unwrap(), payload);
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_reads_appended_members() {
+        let codec = GzipCodec::new();
+        let mut data = codec.encode(b"a\n").unwrap();
+        data.extend(codec.encode(b"b\n").unwrap());
+        assert_eq!(decompress(&data).unwrap().as_ref(), b"a\nb\n");
+    }
+}
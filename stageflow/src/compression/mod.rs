@@ -1,7 +1,29 @@
 //! Compression utilities for context delta encoding.
-
+//!
+//! Shallow deltas ([`compute_delta`]) replace whole top-level values. Deep
+//! deltas ([`compute_deep_delta`]) are RFC 6902 JSON Patches that touch only
+//! the nested values that changed, and [`DeltaChain`] stores a series of
+//! snapshots as a base plus deep deltas. [`Codec`]s compress serialized
+//! payloads such as persisted snapshots and exported event files.
+
+mod chain;
+mod codec;
+mod patch;
+
+pub use chain::{DeltaChain, DEFAULT_REBASE_INTERVAL};
+#[cfg(feature = "gzip")]
+pub use codec::GzipCodec;
+#[cfg(feature = "zstd")]
+pub use codec::ZstdCodec;
+pub use codec::{
+    codec_by_name, decompress, detect_codec, Codec, IdentityCodec, GZIP_MAGIC, ZSTD_MAGIC,
+};
+pub use patch::{apply_patch, diff, PatchOp};
+
+use crate::errors::PatchError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::BuildHasher;
 
 /// Metrics about a compression operation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -111,6 +133,43 @@ pub fn apply_delta(
     result
 }
 
+/// Computes a deep delta between two dictionaries as a JSON Patch.
+#[must_use]
+pub fn compute_deep_delta<S: BuildHasher>(
+    base: &HashMap<String, serde_json::Value, S>,
+    current: &HashMap<String, serde_json::Value, S>,
+) -> Vec<PatchOp> {
+    diff(&to_object(base), &to_object(current))
+}
+
+/// Applies a deep delta to a base dictionary.
+///
+/// # Errors
+///
+/// Returns [`PatchError`] if the patch does not apply to `base`.
+pub fn apply_deep_delta<S: BuildHasher>(
+    base: &HashMap<String, serde_json::Value, S>,
+    delta: &[PatchOp],
+) -> Result<HashMap<String, serde_json::Value>, PatchError> {
+    let mut doc = to_object(base);
+    apply_patch(&mut doc, delta)?;
+    match doc {
+        serde_json::Value::Object(map) => Ok(map.into_iter().collect()),
+        _ => Err(PatchError::new(0, "", "patch replaced the root object")),
+    }
+}
+
+/// Compresses current state relative to base as a deep delta with metrics.
+#[must_use]
+pub fn compress_deep<S: BuildHasher>(
+    base: &HashMap<String, serde_json::Value, S>,
+    current: &HashMap<String, serde_json::Value, S>,
+) -> (Vec<PatchOp>, CompressionMetrics) {
+    let delta = compute_deep_delta(base, current);
+    let metrics = CompressionMetrics::new(encoded_len(&to_object(current)), encoded_len(&delta));
+    (delta, metrics)
+}
+
 /// Compresses current state relative to base and returns delta with metrics.
 pub fn compress(
     base: &HashMap<String, serde_json::Value>,
@@ -126,6 +185,14 @@ pub fn compress(
     (delta, metrics)
 }
 
+fn to_object<S: BuildHasher>(map: &HashMap<String, serde_json::Value, S>) -> serde_json::Value {
+    serde_json::Value::Object(map.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+}
+
+fn encoded_len<T: Serialize + ?Sized>(value: &T) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
 fn json_safe_bytes(data: &HashMap<String, serde_json::Value>) -> usize {
     serde_json::to_string(&make_json_safe(data))
         .map(|s| s.len())
@@ -224,6 +291,26 @@ mod tests {
         assert_eq!(result, current);
     }
 
+    #[test]
+    fn test_deep_delta_is_smaller_than_shallow() {
+        let mut base = HashMap::new();
+        base.insert(
+            "profile".to_string(),
+            serde_json::json!({"bio": "x".repeat(500), "visits": 1}),
+        );
+        let mut current = base.clone();
+        current.insert(
+            "profile".to_string(),
+            serde_json::json!({"bio": "x".repeat(500), "visits": 2}),
+        );
+
+        let (deep, deep_metrics) = compress_deep(&base, &current);
+        let (_, shallow_metrics) = compress(&base, &current);
+        assert_eq!(deep.len(), 1);
+        assert!(deep_metrics.delta_bytes * 10 < shallow_metrics.delta_bytes);
+        assert_eq!(apply_deep_delta(&base, &deep).unwrap(), current);
+    }
+
     #[test]
     fn test_compression_metrics() {
         let base = HashMap::new();
@@ -0,0 +1,297 @@
+//! JSON Patch (RFC 6902) generation and application.
+
+use crate::errors::PatchError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single JSON Patch operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    /// Adds a value, inserting into arrays and replacing object members.
+    Add {
+        /// Target location.
+        path: String,
+        /// Value to add.
+        value: Value,
+    },
+    /// Removes the value at the target location.
+    Remove {
+        /// Target location.
+        path: String,
+    },
+    /// Replaces the value at the target location.
+    Replace {
+        /// Target location.
+        path: String,
+        /// Replacement value.
+        value: Value,
+    },
+    /// Moves a value from one location to another.
+    Move {
+        /// Source location.
+        from: String,
+        /// Target location.
+        path: String,
+    },
+    /// Copies a value from one location to another.
+    Copy {
+        /// Source location.
+        from: String,
+        /// Target location.
+        path: String,
+    },
+    /// Checks that the target location holds a value.
+    Test {
+        /// Target location.
+        path: String,
+        /// Expected value.
+        value: Value,
+    },
+}
+
+impl PatchOp {
+    /// Returns the target location.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Add { path, .. }
+            | Self::Remove { path }
+            | Self::Replace { path, .. }
+            | Self::Move { path, .. }
+            | Self::Copy { path, .. }
+            | Self::Test { path, .. } => path,
+        }
+    }
+}
+
+/// Generates a patch transforming `base` into `current`.
+///
+/// Objects and arrays are compared recursively, so a change deep inside a
+/// large value produces a single small operation. Only `add`, `remove` and
+/// `replace` are generated; array elements are compared by position.
+#[must_use]
+pub fn diff(base: &Value, current: &Value) -> Vec<PatchOp> {
+    let mut ops = Vec::new();
+    diff_into(&mut ops, "", base, current);
+    ops
+}
+
+fn diff_into(ops: &mut Vec<PatchOp>, path: &str, base: &Value, current: &Value) {
+    if base == current {
+        return;
+    }
+    match (base, current) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let child = child_path(path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_into(ops, &child, old_value, new_value),
+                    None => ops.push(PatchOp::Remove { path: child }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    ops.push(PatchOp::Add {
+                        path: child_path(path, key),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            let common = old.len().min(new.len());
+            for i in 0..common {
+                diff_into(ops, &format!("{path}/{i}"), &old[i], &new[i]);
+            }
+            for (i, value) in new.iter().enumerate().skip(common) {
+                ops.push(PatchOp::Add {
+                    path: format!("{path}/{i}"),
+                    value: value.clone(),
+                });
+            }
+            // Remove from the end so earlier indices stay valid
+            for i in (common..old.len()).rev() {
+                ops.push(PatchOp::Remove {
+                    path: format!("{path}/{i}"),
+                });
+            }
+        }
+        _ => ops.push(PatchOp::Replace {
+            path: path.to_string(),
+            value: current.clone(),
+        }),
+    }
+}
+
+/// Applies a patch to a document.
+///
+/// The patch is applied atomically: if any operation fails the document is
+/// left unchanged.
+///
+/// # Errors
+///
+/// Returns [`PatchError`] for the first operation that targets a missing
+/// location, fails a `test`, or has an invalid pointer.
+pub fn apply_patch(doc: &mut Value, patch: &[PatchOp]) -> Result<(), PatchError> {
+    let mut patched = doc.clone();
+    for (index, op) in patch.iter().enumerate() {
+        apply_op(&mut patched, op).map_err(|reason| PatchError::new(index, op.path(), reason))?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+fn apply_op(doc: &mut Value, op: &PatchOp) -> Result<(), String> {
+    match op {
+        PatchOp::Add { path, value } => add(doc, path, value.clone()),
+        PatchOp::Remove { path } => remove(doc, path).map(drop),
+        PatchOp::Replace { path, value } => {
+            let target = doc
+                .pointer_mut(path)
+                .ok_or_else(|| "target does not exist".to_string())?;
+            *target = value.clone();
+            Ok(())
+        }
+        PatchOp::Move { from, path } => {
+            if path.starts_with(&format!("{from}/")) {
+                return Err("cannot move a value into one of its children".to_string());
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        PatchOp::Copy { from, path } => {
+            let value = doc
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| format!("source '{from}' does not exist"))?;
+            add(doc, path, value)
+        }
+        PatchOp::Test { path, value } => match doc.pointer(path) {
+            Some(actual) if actual == value => Ok(()),
+            Some(_) => Err("test failed: value differs".to_string()),
+            None => Err("target does not exist".to_string()),
+        },
+    }
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent, key) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(key, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let index = if key == "-" {
+                items.len()
+            } else {
+                array_index(&key, items.len() + 1)?
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        Some(_) => Err("parent is not an object or array".to_string()),
+        None => Err("parent does not exist".to_string()),
+    }
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Err("cannot remove the document root".to_string());
+    }
+    let (parent, key) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => map
+            .remove(&key)
+            .ok_or_else(|| "target does not exist".to_string()),
+        Some(Value::Array(items)) => {
+            let index = array_index(&key, items.len())?;
+            Ok(items.remove(index))
+        }
+        _ => Err("target does not exist".to_string()),
+    }
+}
+
+/// Splits a pointer into its parent pointer and unescaped last token.
+fn split_pointer(path: &str) -> Result<(&str, String), String> {
+    if !path.starts_with('/') {
+        return Err("pointer must start with '/'".to_string());
+    }
+    let split = path.rfind('/').unwrap_or(0);
+    let token = path[split + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((&path[..split], token))
+}
+
+fn array_index(token: &str, bound: usize) -> Result<usize, String> {
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && index < bound => Ok(index),
+        _ => Err(format!("invalid array index '{token}'")),
+    }
+}
+
+fn child_path(parent: &str, key: &str) -> String {
+    format!("{parent}/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_is_deep_and_roundtrips() {
+        let base = json!({
+            "profile": {"name": "Ada", "tags": ["a", "b", "c"], "a/b": 1},
+            "stale": true,
+        });
+        let current = json!({
+            "profile": {"name": "Ada", "tags": ["a", "x"], "a/b": 2, "age": 36},
+            "fresh": null,
+        });
+
+        let patch = diff(&base, &current);
+        assert!(patch.contains(&PatchOp::Replace { path: "/profile/a~1b".into(), value: json!(2) }));
+        assert!(patch.contains(&PatchOp::Replace { path: "/profile/tags/1".into(), value: json!("x") }));
+        assert!(patch.contains(&PatchOp::Remove { path: "/profile/tags/2".into() }));
+        assert!(!patch.iter().any(|op| op.path() == "/profile/name"));
+
+        let mut doc = base.clone();
+        apply_patch(&mut doc, &patch).unwrap();
+        assert_eq!(doc, current);
+        assert!(diff(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_apply_rfc6902_operations() {
+        let mut doc = json!({"foo": ["bar", "baz"], "qux": {"n": 1}});
+        let patch: Vec<PatchOp> = serde_json::from_value(json!([
+            {"op": "add", "path": "/foo/1", "value": "mid"},
+            {"op": "add", "path": "/foo/-", "value": "end"},
+            {"op": "move", "from": "/qux/n", "path": "/n"},
+            {"op": "copy", "from": "/n", "path": "/m"},
+            {"op": "test", "path": "/foo/0", "value": "bar"},
+        ]))
+        .unwrap();
+        apply_patch(&mut doc, &patch).unwrap();
+        assert_eq!(doc, json!({"foo": ["bar", "mid", "baz", "end"], "qux": {}, "n": 1, "m": 1}));
+
+        // Failures leave the document untouched
+        let before = doc.clone();
+        let bad = vec![
+            PatchOp::Remove { path: "/m".into() },
+            PatchOp::Test { path: "/n".into(), value: json!(2) },
+        ];
+        let err = apply_patch(&mut doc, &bad).unwrap_err();
+        assert_eq!((err.index, err.path.as_str()), (1, "/n"));
+        assert_eq!(doc, before);
+        assert!(apply_patch(&mut doc, &[PatchOp::Remove { path: "/foo/01".into() }]).is_err());
+    }
+}
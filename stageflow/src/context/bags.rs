@@ -2,15 +2,42 @@
 
 use crate::errors::{DataConflictError, OutputConflictError};
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Separator between a namespace and the keys inside it.
+pub const NAMESPACE_SEPARATOR: char = '.';
+
+#[derive(Debug, Clone)]
+struct BagEntry {
+    value: serde_json::Value,
+    expires_at: Option<Instant>,
+}
+
+impl BagEntry {
+    fn new(value: serde_json::Value, ttl: Option<Duration>) -> Self {
+        Self {
+            value,
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+        }
+    }
+
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at.map_or(true, |at| now < at)
+    }
+}
 
 /// A thread-safe bag for storing context data.
 ///
-/// Writing to an existing key raises a `DataConflictError`.
+/// Writing to an existing key raises a `DataConflictError`. Entries written
+/// with a TTL disappear once it elapses, after which the key can be written
+/// again; expired entries are dropped on the next write or by
+/// [`purge_expired`](Self::purge_expired). Related keys can be grouped under
+/// a [`namespace`](Self::namespace).
 #[derive(Debug, Default)]
 pub struct ContextBag {
-    data: RwLock<HashMap<String, serde_json::Value>>,
+    data: RwLock<HashMap<String, BagEntry>>,
 }
 
 impl ContextBag {
@@ -24,20 +51,30 @@ impl ContextBag {
     #[must_use]
     pub fn from_data(data: HashMap<String, serde_json::Value>) -> Self {
         Self {
-            data: RwLock::new(data),
+            data: RwLock::new(
+                data.into_iter()
+                    .map(|(key, value)| (key, BagEntry::new(value, None)))
+                    .collect(),
+            ),
         }
     }
 
     /// Gets a value from the bag.
     #[must_use]
     pub fn get(&self, key: &str) -> Option<serde_json::Value> {
-        self.data.read().get(key).cloned()
+        let now = Instant::now();
+        self.data
+            .read()
+            .get(key)
+            .filter(|entry| entry.is_live(now))
+            .map(|entry| entry.value.clone())
     }
 
     /// Checks if a key exists.
     #[must_use]
     pub fn contains_key(&self, key: &str) -> bool {
-        self.data.read().contains_key(key)
+        let now = Instant::now();
+        self.data.read().get(key).is_some_and(|entry| entry.is_live(now))
     }
 
     /// Sets a value in the bag.
@@ -46,44 +83,112 @@ impl ContextBag {
     ///
     /// Returns `DataConflictError` if the key already exists.
     pub fn set(&self, key: impl Into<String>, value: serde_json::Value) -> Result<(), DataConflictError> {
-        let key = key.into();
-        let mut data = self.data.write();
-
-        if data.contains_key(&key) {
-            return Err(DataConflictError::new(&key));
-        }
+        self.insert(key.into(), BagEntry::new(value, None))
+    }
 
-        data.insert(key, value);
-        Ok(())
+    /// Sets a value that expires after `ttl`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DataConflictError` if the key already exists and has not
+    /// expired.
+    pub fn set_with_ttl(
+        &self,
+        key: impl Into<String>,
+        value: serde_json::Value,
+        ttl: Duration,
+    ) -> Result<(), DataConflictError> {
+        self.insert(key.into(), BagEntry::new(value, Some(ttl)))
     }
 
     /// Sets a value, allowing overwrites.
     pub fn set_force(&self, key: impl Into<String>, value: serde_json::Value) {
-        self.data.write().insert(key.into(), value);
+        let mut data = self.data.write();
+        Self::purge(&mut data, Instant::now());
+        data.insert(key.into(), BagEntry::new(value, None));
+    }
+
+    /// Returns the time left before a key expires.
+    ///
+    /// Returns `None` if the key is absent or has no TTL.
+    #[must_use]
+    pub fn ttl(&self, key: &str) -> Option<Duration> {
+        let now = Instant::now();
+        self.data
+            .read()
+            .get(key)
+            .filter(|entry| entry.is_live(now))
+            .and_then(|entry| entry.expires_at)
+            .map(|at| at - now)
+    }
+
+    /// Removes expired entries, returning how many were dropped.
+    pub fn purge_expired(&self) -> usize {
+        Self::purge(&mut self.data.write(), Instant::now())
+    }
+
+    /// Returns a view of the keys under `namespace`.
+    #[must_use]
+    pub fn namespace(&self, namespace: impl Into<String>) -> ContextNamespace<'_> {
+        ContextNamespace {
+            bag: self,
+            prefix: format!("{}{NAMESPACE_SEPARATOR}", namespace.into()),
+        }
     }
 
     /// Returns a copy of all data.
     #[must_use]
     pub fn to_dict(&self) -> HashMap<String, serde_json::Value> {
-        self.data.read().clone()
+        let now = Instant::now();
+        self.data
+            .read()
+            .iter()
+            .filter(|(_, entry)| entry.is_live(now))
+            .map(|(key, entry)| (key.clone(), entry.value.clone()))
+            .collect()
     }
 
     /// Returns the number of entries.
     #[must_use]
     pub fn len(&self) -> usize {
-        self.data.read().len()
+        let now = Instant::now();
+        self.data.read().values().filter(|entry| entry.is_live(now)).count()
     }
 
     /// Returns true if the bag is empty.
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.data.read().is_empty()
+        self.len() == 0
     }
 
     /// Returns all keys.
     #[must_use]
     pub fn keys(&self) -> Vec<String> {
-        self.data.read().keys().cloned().collect()
+        let now = Instant::now();
+        self.data
+            .read()
+            .iter()
+            .filter(|(_, entry)| entry.is_live(now))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn insert(&self, key: String, entry: BagEntry) -> Result<(), DataConflictError> {
+        let mut data = self.data.write();
+        Self::purge(&mut data, Instant::now());
+
+        if data.contains_key(&key) {
+            return Err(DataConflictError::new(&key));
+        }
+
+        data.insert(key, entry);
+        Ok(())
+    }
+
+    fn purge(data: &mut HashMap<String, BagEntry>, now: Instant) -> usize {
+        let before = data.len();
+        data.retain(|_, entry| entry.is_live(now));
+        before - data.len()
     }
 }
 
@@ -95,6 +200,103 @@ impl Clone for ContextBag {
     }
 }
 
+/// A view of the [`ContextBag`] keys under a common prefix.
+///
+/// Keys are stored in the parent bag as `namespace.key`, so they keep the
+/// bag's conflict and TTL rules and show up in snapshots of the whole bag.
+#[derive(Debug, Clone)]
+pub struct ContextNamespace<'a> {
+    bag: &'a ContextBag,
+    prefix: String,
+}
+
+impl<'a> ContextNamespace<'a> {
+    /// Returns the namespace name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.prefix.trim_end_matches(NAMESPACE_SEPARATOR)
+    }
+
+    /// Returns a view of a nested namespace.
+    #[must_use]
+    pub fn namespace(&self, namespace: &str) -> ContextNamespace<'a> {
+        self.bag.namespace(self.key(namespace))
+    }
+
+    /// Gets a value from the namespace.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.bag.get(&self.key(key))
+    }
+
+    /// Checks if a key exists in the namespace.
+    #[must_use]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.bag.contains_key(&self.key(key))
+    }
+
+    /// Sets a value in the namespace.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DataConflictError` if the key already exists.
+    pub fn set(&self, key: &str, value: serde_json::Value) -> Result<(), DataConflictError> {
+        self.bag.set(self.key(key), value)
+    }
+
+    /// Sets a value in the namespace that expires after `ttl`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DataConflictError` if the key already exists and has not
+    /// expired.
+    pub fn set_with_ttl(
+        &self,
+        key: &str,
+        value: serde_json::Value,
+        ttl: Duration,
+    ) -> Result<(), DataConflictError> {
+        self.bag.set_with_ttl(self.key(key), value, ttl)
+    }
+
+    /// Sets a value in the namespace, allowing overwrites.
+    pub fn set_force(&self, key: &str, value: serde_json::Value) {
+        self.bag.set_force(self.key(key), value);
+    }
+
+    /// Returns the namespace contents, keyed without the prefix.
+    #[must_use]
+    pub fn to_dict(&self) -> HashMap<String, serde_json::Value> {
+        self.bag
+            .to_dict()
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(&self.prefix)?.to_string(), value)))
+            .collect()
+    }
+
+    /// Returns the keys in the namespace, without the prefix.
+    #[must_use]
+    pub fn keys(&self) -> Vec<String> {
+        self.bag
+            .keys()
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect()
+    }
+
+    /// Removes every key in the namespace, returning how many were removed.
+    pub fn clear(&self) -> usize {
+        let mut data = self.bag.data.write();
+        let before = data.len();
+        data.retain(|key, _| !key.starts_with(&self.prefix));
+        before - data.len()
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
+
 /// Per-stage output entry with attempt tracking.
 #[derive(Debug, Clone)]
 pub struct StageOutputEntry {
@@ -108,10 +310,12 @@ pub struct StageOutputEntry {
 
 /// A thread-safe bag for storing per-stage outputs.
 ///
-/// Supports retry semantics with attempt tracking.
+/// Supports retry semantics with attempt tracking. Stages with
+/// [declared output keys](Self::declare_outputs) may only write those keys.
 #[derive(Debug, Default)]
 pub struct OutputBag {
     outputs: RwLock<HashMap<String, StageOutputEntry>>,
+    declared: RwLock<HashMap<String, HashSet<String>>>,
 }
 
 impl OutputBag {
@@ -139,11 +343,19 @@ impl OutputBag {
         self.outputs.read().contains_key(stage)
     }
 
+    /// Limits the keys `stage` may write with [`set`](Self::set).
+    pub fn declare_outputs(&self, stage: impl Into<String>, keys: impl IntoIterator<Item = impl Into<String>>) {
+        self.declared
+            .write()
+            .insert(stage.into(), keys.into_iter().map(Into::into).collect());
+    }
+
     /// Sets output for a stage.
     ///
     /// # Errors
     ///
-    /// Returns `OutputConflictError` if the stage already has a final output.
+    /// Returns `OutputConflictError` if the stage already has a final output
+    /// or `data` has keys the stage did not declare.
     pub fn set(
         &self,
         stage: impl Into<String>,
@@ -152,6 +364,13 @@ impl OutputBag {
         is_final: bool,
     ) -> Result<(), OutputConflictError> {
         let stage = stage.into();
+        if let Some(declared) = self.declared.read().get(&stage) {
+            let mut undeclared: Vec<&String> = data.keys().filter(|key| !declared.contains(*key)).collect();
+            if !undeclared.is_empty() {
+                undeclared.sort();
+                return Err(OutputConflictError::undeclared_keys(&stage, undeclared));
+            }
+        }
         let mut outputs = self.outputs.write();
 
         if let Some(existing) = outputs.get(&stage) {
@@ -226,6 +445,7 @@ impl Clone for OutputBag {
     fn clone(&self) -> Self {
         Self {
             outputs: RwLock::new(self.outputs.read().clone()),
+            declared: RwLock::new(self.declared.read().clone()),
         }
     }
 }
@@ -272,6 +492,42 @@ mod tests {
         assert_eq!(dict.len(), 2);
     }
 
+    #[test]
+    fn test_context_bag_ttl_expiry() {
+        let bag = ContextBag::new();
+        bag.set_with_ttl("token", serde_json::json!("a"), Duration::ZERO).unwrap();
+        bag.set_with_ttl("lease", serde_json::json!(1), Duration::from_secs(60)).unwrap();
+
+        assert_eq!(bag.get("token"), None);
+        assert_eq!(bag.keys(), vec!["lease".to_string()]);
+        assert!(bag.ttl("lease").is_some_and(|ttl| ttl <= Duration::from_secs(60)));
+        assert!(bag.set("lease", serde_json::json!(2)).is_err());
+
+        // The expired key can be written again and is purged on write
+        bag.set("token", serde_json::json!("b")).unwrap();
+        assert_eq!(bag.get("token"), Some(serde_json::json!("b")));
+        assert_eq!(bag.ttl("token"), None);
+        assert_eq!(bag.purge_expired(), 0);
+    }
+
+    #[test]
+    fn test_context_bag_namespaces() {
+        let bag = ContextBag::new();
+        let retrieval = bag.namespace("retrieval");
+        retrieval.set("query", serde_json::json!("rust")).unwrap();
+        retrieval.namespace("cache").set_force("hits", serde_json::json!(3));
+        bag.set("query", serde_json::json!("top-level")).unwrap();
+
+        assert!(retrieval.set("query", serde_json::json!("again")).is_err());
+        assert_eq!(bag.get("retrieval.query"), Some(serde_json::json!("rust")));
+        assert_eq!(bag.get("retrieval.cache.hits"), Some(serde_json::json!(3)));
+        assert_eq!(retrieval.to_dict().len(), 2);
+
+        assert_eq!(retrieval.clear(), 2);
+        assert!(retrieval.keys().is_empty());
+        assert_eq!(bag.keys(), vec!["query".to_string()]);
+    }
+
     #[test]
     fn test_output_bag_set_and_get() {
         let bag = OutputBag::new();
@@ -319,4 +575,18 @@ mod tests {
         assert_eq!(entry.attempt, 3);
         assert!(entry.is_final);
     }
+
+    #[test]
+    fn test_output_bag_rejects_undeclared_keys() {
+        let bag = OutputBag::new();
+        bag.declare_outputs("stage1", ["x"]);
+
+        let data = HashMap::from([("x".to_string(), serde_json::json!(1)), ("y".to_string(), serde_json::json!(2))]);
+        let err = bag.set("stage1", data, 1, true).unwrap_err();
+        assert!(err.message.contains("undeclared output keys: y"));
+        assert!(!bag.contains("stage1"));
+
+        bag.set("stage1", HashMap::from([("x".to_string(), serde_json::json!(1))]), 1, true).unwrap();
+        bag.set("stage2", HashMap::from([("y".to_string(), serde_json::json!(2))]), 1, true).unwrap();
+    }
 }
@@ -0,0 +1,381 @@
+//! Token-budgeted assembly of a snapshot into prompt messages.
+//!
+//! [`ContextBudget`] fills a token budget section by section in priority
+//! order, by default system messages, then recent conversation turns, then
+//! memory, then documents. Whatever does not fit is dropped whole and
+//! counted in the [`TruncationReport`].
+
+use super::{ContextSnapshot, Conversation, Enrichments, Message};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Counts the tokens in a piece of text.
+///
+/// Implement this over the model's own tokenizer for exact budgets.
+pub trait Tokenizer: Send + Sync {
+    /// Returns the number of tokens in `text`.
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Estimates tokens from the character count, rounding up.
+#[derive(Debug, Clone, Copy)]
+pub struct CharTokenizer {
+    chars_per_token: usize,
+}
+
+impl CharTokenizer {
+    /// Creates a tokenizer assuming `chars_per_token` characters per token.
+    #[must_use]
+    pub fn new(chars_per_token: usize) -> Self {
+        Self { chars_per_token: chars_per_token.max(1) }
+    }
+}
+
+impl Default for CharTokenizer {
+    /// Four characters per token, a common estimate for English text.
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+impl Tokenizer for CharTokenizer {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(self.chars_per_token)
+    }
+}
+
+/// A part of the context competing for the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextSection {
+    /// Messages with the `system` role.
+    System,
+    /// The other conversation turns plus the input text, newest first.
+    Conversation,
+    /// `Enrichments.memory`.
+    Memory,
+    /// `Enrichments.documents` followed by `Enrichments.web_results`.
+    Documents,
+}
+
+/// What one section contributed to an assembled context.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionReport {
+    /// The section.
+    pub section: ContextSection,
+    /// Items included.
+    pub included: usize,
+    /// Items dropped for lack of budget.
+    pub dropped: usize,
+    /// Tokens spent on the section, including message overhead.
+    pub tokens: usize,
+}
+
+/// How an assembled context used its budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TruncationReport {
+    /// The budget.
+    pub max_tokens: usize,
+    /// Tokens used.
+    pub used_tokens: usize,
+    /// Per-section usage, in priority order.
+    pub sections: Vec<SectionReport>,
+}
+
+impl TruncationReport {
+    /// Returns whether anything was dropped.
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        self.sections.iter().any(|s| s.dropped > 0)
+    }
+
+    /// Returns the report for a section, if it was assembled.
+    #[must_use]
+    pub fn section(&self, section: ContextSection) -> Option<&SectionReport> {
+        self.sections.iter().find(|s| s.section == section)
+    }
+}
+
+/// A prompt-ready context.
+#[derive(Debug, Clone)]
+pub struct AssembledContext {
+    /// Messages in prompt order: system messages, memory, documents, then
+    /// conversation turns oldest first.
+    pub messages: Vec<Message>,
+    /// How the budget was spent.
+    pub report: TruncationReport,
+}
+
+/// Assembles conversations and enrichments under a token budget.
+///
+/// Sections are filled in priority order. System messages, memory and
+/// documents take every item that still fits; conversation turns are taken
+/// newest first and stop at the first turn that does not fit, so the kept
+/// history has no gaps. Memory and documents are each rendered as one
+/// system message. Sections left out of the priorities are omitted.
+#[derive(Clone)]
+pub struct ContextBudget {
+    max_tokens: usize,
+    message_overhead: usize,
+    priorities: Vec<ContextSection>,
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl ContextBudget {
+    /// Creates a budget of `max_tokens`, estimated with [`CharTokenizer`].
+    #[must_use]
+    pub fn new(max_tokens: usize) -> Self {
+        Self {
+            max_tokens,
+            message_overhead: 4,
+            priorities: vec![
+                ContextSection::System,
+                ContextSection::Conversation,
+                ContextSection::Memory,
+                ContextSection::Documents,
+            ],
+            tokenizer: Arc::new(CharTokenizer::default()),
+        }
+    }
+
+    /// Sets the tokens charged per message for role and formatting.
+    #[must_use]
+    pub fn with_message_overhead(mut self, tokens: usize) -> Self {
+        self.message_overhead = tokens;
+        self
+    }
+
+    /// Sets the order in which sections are filled.
+    #[must_use]
+    pub fn with_priorities(mut self, priorities: impl IntoIterator<Item = ContextSection>) -> Self {
+        self.priorities = priorities.into_iter().collect();
+        self
+    }
+
+    /// Sets the tokenizer.
+    #[must_use]
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Assembles a snapshot's conversation, input text and enrichments.
+    #[must_use]
+    pub fn assemble(&self, snapshot: &ContextSnapshot) -> AssembledContext {
+        self.assemble_parts(&snapshot.conversation, &snapshot.enrichments, snapshot.input_text.as_deref())
+    }
+
+    /// Assembles a conversation and enrichments. `input_text` is treated as
+    /// the newest user turn unless it repeats the last message.
+    #[must_use]
+    pub fn assemble_parts(
+        &self,
+        conversation: &Conversation,
+        enrichments: &Enrichments,
+        input_text: Option<&str>,
+    ) -> AssembledContext {
+        let mut remaining = self.max_tokens;
+        let mut sections = Vec::with_capacity(self.priorities.len());
+        let (mut system, mut memory, mut documents, mut turns) = (Vec::new(), None, None, Vec::new());
+
+        for &section in &self.priorities {
+            let mut report = SectionReport { section, included: 0, dropped: 0, tokens: 0 };
+            match section {
+                ContextSection::System => {
+                    for message in conversation.messages.iter().filter(|m| m.role == "system") {
+                        let cost = self.message_overhead + self.tokenizer.count(&message.content);
+                        if admit(cost, &mut remaining, &mut report) {
+                            system.push(message.clone());
+                        }
+                    }
+                }
+                ContextSection::Conversation => {
+                    let mut history: Vec<Message> =
+                        conversation.messages.iter().filter(|m| m.role != "system").cloned().collect();
+                    if let Some(input) = input_text {
+                        if history.last().map_or(true, |m| m.content != input) {
+                            history.push(Message::user(input));
+                        }
+                    }
+                    while let Some(message) = history.pop() {
+                        let cost = self.message_overhead + self.tokenizer.count(&message.content);
+                        if !admit(cost, &mut remaining, &mut report) {
+                            report.dropped += history.len();
+                            break;
+                        }
+                        turns.push(message);
+                    }
+                    turns.reverse();
+                }
+                ContextSection::Memory => {
+                    let items = enrichments.memory.as_ref().map(memory_items).unwrap_or_default();
+                    memory = self.render("Relevant memory:", items, &mut remaining, &mut report);
+                }
+                ContextSection::Documents => {
+                    let items = enrichments.documents.iter().chain(&enrichments.web_results).map(item_text).collect();
+                    documents = self.render("Relevant documents:", items, &mut remaining, &mut report);
+                }
+            }
+            sections.push(report);
+        }
+
+        let mut messages = system;
+        messages.extend(memory);
+        messages.extend(documents);
+        messages.extend(turns);
+        AssembledContext {
+            messages,
+            report: TruncationReport { max_tokens: self.max_tokens, used_tokens: self.max_tokens - remaining, sections },
+        }
+    }
+
+    /// Renders the items that fit as one system message under `header`.
+    fn render(
+        &self,
+        header: &str,
+        items: Vec<String>,
+        remaining: &mut usize,
+        report: &mut SectionReport,
+    ) -> Option<Message> {
+        let header_cost = self.message_overhead + self.tokenizer.count(header);
+        let mut content = header.to_string();
+        for item in items {
+            let mut cost = self.tokenizer.count(&item) + 1;
+            if report.included == 0 {
+                cost += header_cost;
+            }
+            if admit(cost, remaining, report) {
+                content.push_str("\n- ");
+                content.push_str(&item);
+            }
+        }
+        (report.included > 0).then(|| Message::system(content))
+    }
+}
+
+impl std::fmt::Debug for ContextBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextBudget")
+            .field("max_tokens", &self.max_tokens)
+            .field("message_overhead", &self.message_overhead)
+            .field("priorities", &self.priorities)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Charges `cost` if it fits, recording the outcome in `report`.
+fn admit(cost: usize, remaining: &mut usize, report: &mut SectionReport) -> bool {
+    if cost > *remaining {
+        report.dropped += 1;
+        return false;
+    }
+    *remaining -= cost;
+    report.included += 1;
+    report.tokens += cost;
+    true
+}
+
+/// Items of `Enrichments.memory`: the `entries` written by the memory
+/// fetch stage, a plain array, or a single value.
+fn memory_items(memory: &serde_json::Value) -> Vec<String> {
+    let entries = memory.get("entries").unwrap_or(memory);
+    match entries {
+        serde_json::Value::Array(items) => items.iter().map(item_text).collect(),
+        serde_json::Value::Null => Vec::new(),
+        other => vec![item_text(other)],
+    }
+}
+
+/// Text of one enrichment item: a string, an object's `content` or `text`
+/// (prefixed by its `role`, if any), or the item as JSON.
+fn item_text(item: &serde_json::Value) -> String {
+    if let Some(text) = item.as_str() {
+        return text.to_string();
+    }
+    let text = item.get("content").or_else(|| item.get("text")).and_then(serde_json::Value::as_str);
+    match (item.get("role").and_then(serde_json::Value::as_str), text) {
+        (Some(role), Some(text)) => format!("{role}: {text}"),
+        (None, Some(text)) => text.to_string(),
+        _ => item.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One token per word, for readable budgets.
+    struct Words;
+
+    impl Tokenizer for Words {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    fn budget(max_tokens: usize) -> ContextBudget {
+        ContextBudget::new(max_tokens).with_message_overhead(1).with_tokenizer(Arc::new(Words))
+    }
+
+    fn snapshot() -> ContextSnapshot {
+        ContextSnapshot::new()
+            .with_conversation(Conversation::with_messages(vec![
+                Message::system("be brief"),
+                Message::user("one two three"),
+                Message::assistant("four five"),
+                Message::user("six"),
+            ]))
+            .with_enrichments(
+                Enrichments::new()
+                    .with_memory(serde_json::json!({ "entries": [{ "role": "user", "content": "likes tea" }] }))
+                    .with_documents(vec![serde_json::json!({ "text": "doc body" }), serde_json::json!("web page")]),
+            )
+    }
+
+    #[test]
+    fn test_everything_fits_in_prompt_order() {
+        let assembled = budget(100).assemble(&snapshot());
+        let contents: Vec<&str> = assembled.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "be brief",
+                "Relevant memory:\n- user: likes tea",
+                "Relevant documents:\n- doc body\n- web page",
+                "one two three",
+                "four five",
+                "six"
+            ]
+        );
+        assert!(!assembled.report.is_truncated());
+        assert_eq!(assembled.report.used_tokens, 3 + (4 + 3 + 2) + (3 + 4) + (3 + 3 + 3));
+    }
+
+    #[test]
+    fn test_low_priority_sections_are_dropped_first() {
+        // System (3) and the two newest turns (2 + 3) fit; the oldest turn
+        // and all of memory and documents do not.
+        let assembled = budget(9).assemble(&snapshot());
+        let report = &assembled.report;
+        assert!(report.is_truncated());
+        assert_eq!(report.used_tokens, 8);
+        let conversation = report.section(ContextSection::Conversation).unwrap();
+        assert_eq!((conversation.included, conversation.dropped), (2, 1));
+        assert_eq!(report.section(ContextSection::Memory).map(|s| s.dropped), Some(1));
+        assert_eq!(report.section(ContextSection::Documents).map(|s| s.dropped), Some(2));
+        let roles: Vec<&str> = assembled.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "assistant", "user"]);
+    }
+
+    #[test]
+    fn test_priorities_and_input_text() {
+        let snapshot = snapshot().with_input_text("seven eight");
+        let assembled = budget(12)
+            .with_priorities([ContextSection::Memory, ContextSection::Conversation])
+            .assemble(&snapshot);
+        let contents: Vec<&str> = assembled.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["Relevant memory:\n- user: likes tea", "six", "seven eight"]);
+        assert_eq!(assembled.report.sections.len(), 2);
+        assert_eq!(CharTokenizer::default().count("abcdefghi"), 3);
+    }
+}
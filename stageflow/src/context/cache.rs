@@ -0,0 +1,227 @@
+//! Cache of enrichment results shared across runs.
+//!
+//! Profile, memory and web lookups are often repeated verbatim by every run
+//! of a session. An [`EnrichmentCache`] attached to the pipeline context lets
+//! enrichment stages reuse earlier results through
+//! [`StageContext::cached_enrichment`](super::StageContext::cached_enrichment).
+
+use super::fingerprint::value_hash;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Default time an enrichment result stays cached.
+pub const DEFAULT_ENRICHMENT_TTL: Duration = Duration::from_secs(300);
+
+/// Identifies a cached enrichment result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnrichmentKey {
+    /// User (or session) the result belongs to.
+    pub user_id: Uuid,
+    /// Enrichment type, such as `profile` or `web_results`.
+    pub kind: String,
+    /// Structural hash of the lookup source.
+    pub source_hash: u64,
+}
+
+impl EnrichmentKey {
+    /// Creates a key, hashing the source that produced the enrichment.
+    #[must_use]
+    pub fn new(user_id: Uuid, kind: impl Into<String>, source: &Value) -> Self {
+        Self {
+            user_id,
+            kind: kind.into(),
+            source_hash: value_hash(source),
+        }
+    }
+}
+
+/// Hit and miss counts for enrichment lookups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnrichmentCacheStats {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups that had to be computed.
+    pub misses: u64,
+}
+
+impl EnrichmentCacheStats {
+    /// Returns the fraction of lookups served from the cache.
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits.saturating_add(self.misses);
+        if total == 0 {
+            return 0.0;
+        }
+        // Scale both counts into 32 bits so they convert to f64 exactly.
+        let shift = 32u32.saturating_sub(total.leading_zeros());
+        let scaled = |n: u64| f64::from(u32::try_from(n >> shift).unwrap_or(u32::MAX));
+        scaled(self.hits) / scaled(total)
+    }
+}
+
+/// Lock-free hit and miss counters.
+#[derive(Debug, Default)]
+pub(crate) struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    pub(crate) fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> EnrichmentCacheStats {
+        EnrichmentCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    value: Value,
+    expires_at: Instant,
+}
+
+/// TTL cache of enrichment results keyed by user, type and source.
+#[derive(Debug)]
+pub struct EnrichmentCache {
+    entries: RwLock<HashMap<EnrichmentKey, CacheEntry>>,
+    ttl: Duration,
+    counters: CacheCounters,
+}
+
+impl Default for EnrichmentCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnrichmentCache {
+    /// Creates an empty cache using [`DEFAULT_ENRICHMENT_TTL`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            ttl: DEFAULT_ENRICHMENT_TTL,
+            counters: CacheCounters::default(),
+        }
+    }
+
+    /// Sets the default entry TTL.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns the default entry TTL.
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Looks up a result, counting the lookup as a hit or miss.
+    ///
+    /// Expired entries are dropped and count as misses.
+    pub fn get(&self, key: &EnrichmentKey) -> Option<Value> {
+        let now = Instant::now();
+        let found = {
+            let entries = self.entries.read();
+            entries.get(key).map(|entry| (entry.expires_at > now, entry.value.clone()))
+        };
+        let value = match found {
+            Some((true, value)) => Some(value),
+            Some((false, _)) => {
+                self.entries.write().remove(key);
+                None
+            }
+            None => None,
+        };
+        self.counters.record(value.is_some());
+        value
+    }
+
+    /// Stores a result with the default TTL.
+    pub fn insert(&self, key: EnrichmentKey, value: Value) {
+        self.insert_with_ttl(key, value, self.ttl);
+    }
+
+    /// Stores a result that expires after `ttl`.
+    pub fn insert_with_ttl(&self, key: EnrichmentKey, value: Value, ttl: Duration) {
+        let entry = CacheEntry {
+            value,
+            expires_at: Instant::now() + ttl,
+        };
+        self.entries.write().insert(key, entry);
+    }
+
+    /// Drops every result cached for a user.
+    pub fn invalidate_user(&self, user_id: Uuid) {
+        self.entries.write().retain(|key, _| key.user_id != user_id);
+    }
+
+    /// Drops expired entries and returns how many were removed.
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|_, entry| entry.expires_at > now);
+        before - entries.len()
+    }
+
+    /// Returns the number of stored entries, including expired ones not yet
+    /// purged.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.read().len()
+    }
+
+    /// Returns true if nothing is stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().is_empty()
+    }
+
+    /// Returns lifetime hit and miss counts across all runs.
+    #[must_use]
+    pub fn stats(&self) -> EnrichmentCacheStats {
+        self.counters.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_hits_and_expiry() {
+        let cache = EnrichmentCache::new();
+        let user = Uuid::new_v4();
+        let key = EnrichmentKey::new(user, "profile", &json!({"id": 1}));
+
+        assert!(cache.get(&key).is_none());
+        cache.insert(key.clone(), json!({"name": "Ada"}));
+        assert_eq!(cache.get(&EnrichmentKey::new(user, "profile", &json!({"id": 1}))), Some(json!({"name": "Ada"})));
+        assert!(cache.get(&EnrichmentKey::new(user, "profile", &json!({"id": 2}))).is_none());
+        assert_eq!(cache.stats(), EnrichmentCacheStats { hits: 1, misses: 2 });
+        assert!((cache.stats().hit_rate() - 1.0 / 3.0).abs() < 1e-9);
+
+        cache.insert_with_ttl(key.clone(), json!(1), Duration::ZERO);
+        assert!(cache.get(&key).is_none());
+        assert!(cache.is_empty());
+
+        cache.insert(key, json!(2));
+        cache.invalidate_user(user);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+//! Context consistency modes for stage reads and writes.
+
+use serde::{Deserialize, Serialize};
+
+/// Controls how stages observe and mutate the shared `ContextBag`.
+///
+/// Parallel branches that read and write the live bag can observe each
+/// other's writes depending on scheduling. The mode is chosen per pipeline
+/// on [`PipelineContext`](super::PipelineContext) and enforced by
+/// [`StageContext`](super::StageContext).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextConsistency {
+    /// Stages read a copy of the bag taken when they start and may not write;
+    /// writes are rejected, or reported as violations if made directly on
+    /// the copy.
+    FrozenSnapshot,
+    /// Stages read and write the live bag directly.
+    #[default]
+    LiveReads,
+    /// Stages read the live bag until their first write, then switch to a
+    /// private copy; writes are committed to the bag when the stage succeeds
+    /// and otherwise dropped with a `context.writes_discarded` event.
+    CopyOnWrite,
+}
+
+impl ContextConsistency {
+    /// Returns the mode name used in events.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FrozenSnapshot => "frozen_snapshot",
+            Self::LiveReads => "live_reads",
+            Self::CopyOnWrite => "copy_on_write",
+        }
+    }
+
+    /// Returns true if stages may write to the context.
+    #[must_use]
+    pub fn allows_writes(&self) -> bool {
+        !matches!(self, Self::FrozenSnapshot)
+    }
+}
+
+impl std::fmt::Display for ContextConsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether a stage may write to the context at all.
+///
+/// Applies on top of the [`ContextConsistency`] mode; see
+/// [`StageSpec::with_read_only`](crate::pipeline::StageSpec::with_read_only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextAccess {
+    /// Writes follow the consistency mode.
+    #[default]
+    ReadWrite,
+    /// Every write is rejected.
+    ReadOnly,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_live_reads() {
+        assert_eq!(ContextConsistency::default(), ContextConsistency::LiveReads);
+    }
+
+    #[test]
+    fn test_allows_writes() {
+        assert!(!ContextConsistency::FrozenSnapshot.allows_writes());
+        assert!(ContextConsistency::LiveReads.allows_writes());
+        assert!(ContextConsistency::CopyOnWrite.allows_writes());
+    }
+
+    #[test]
+    fn test_serialization() {
+        let json = serde_json::to_string(&ContextConsistency::CopyOnWrite).unwrap();
+        assert_eq!(json, "\"copy_on_write\"");
+    }
+}
@@ -74,6 +74,98 @@ mod tests {
         assert_eq!(ctx.execution_mode(), "development");
     }
 
+    #[test]
+    fn test_pipeline_context_dry_run() {
+        use crate::events::CollectingEventSink;
+
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_dry_run(),
+        );
+        assert!(ctx.is_dry_run());
+        assert!(!PipelineContext::new(RunIdentity::new()).is_dry_run());
+
+        let child = ctx.fork_for_subpipeline(RunIdentity::new());
+        assert!(child.is_dry_run());
+
+        ctx.try_emit_event("test.event", None);
+        assert_eq!(sink.events()[0].1.as_ref().unwrap()["dry_run"], true);
+    }
+
+    #[tokio::test]
+    async fn test_stage_sandbox_helpers() {
+        use crate::context::{AccessKind, SandboxPolicy};
+
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("out");
+        std::fs::create_dir(&allowed).unwrap();
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_sandbox(SandboxPolicy::strict().allow_env("PATH").allow_write(&allowed)),
+        );
+        let stage = StageContext::new(ctx.clone(), "writer", StageInputs::default(), ContextSnapshot::new());
+
+        assert!(stage.env_var("PATH").unwrap().is_some());
+        let err = stage.env_var("STAGEFLOW_SECRET").unwrap_err();
+        assert_eq!(err.target, "STAGEFLOW_SECRET");
+        assert_eq!(err.kind, "env_var");
+
+        let file = allowed.join("a.txt");
+        stage.write_file(&file, "hello").await.unwrap();
+        assert_eq!(stage.read_file(&file).await.unwrap(), "hello");
+        let escape = allowed.join("../b.txt");
+        assert!(stage.write_file(&escape, "x").await.is_err());
+        assert!(!dir.path().join("b.txt").exists());
+
+        let report = ctx.access_report().unwrap();
+        assert_eq!(report.records.len(), 5);
+        let denied: Vec<AccessKind> = report.denied().iter().map(|r| r.kind).collect();
+        assert_eq!(denied, vec![AccessKind::EnvVar, AccessKind::FileWrite]);
+
+        // Subpipelines keep the policy but start a fresh report
+        let child = ctx.fork_for_subpipeline(RunIdentity::new());
+        assert!(child.access_report().unwrap().records.is_empty());
+        assert!(PipelineContext::new(RunIdentity::new()).access_report().is_none());
+
+        // Symlinks out of the allowed directory are followed before the check
+        #[cfg(unix)]
+        {
+            let outside = dir.path().join("secret.txt");
+            std::fs::write(&outside, "secret").unwrap();
+            std::os::unix::fs::symlink(&outside, allowed.join("link.txt")).unwrap();
+            std::os::unix::fs::symlink(dir.path(), allowed.join("up")).unwrap();
+            assert!(matches!(
+                stage.read_file(allowed.join("link.txt")).await,
+                Err(crate::errors::StageflowError::AccessDenied(_))
+            ));
+            assert!(stage.write_file(allowed.join("up/new.txt"), "x").await.is_err());
+            assert!(!dir.path().join("new.txt").exists());
+        }
+    }
+
+    #[test]
+    fn test_child_contexts_observe_parent_cancellation() {
+        let parent = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let child = parent.fork_for_subpipeline(RunIdentity::new());
+        let grandchild = child.fork_for_subpipeline(RunIdentity::new());
+        let detached = parent.fork_detached_for_subpipeline(RunIdentity::new());
+        assert!(!detached.inherits_cancellation());
+
+        parent.cancel_with(crate::pipeline::CancelReason::requested("user stop"));
+        assert!(child.is_cancelled() && grandchild.is_cancelled());
+        assert_eq!(grandchild.cancel_reason().as_deref(), Some("user stop"));
+        assert_eq!(child.cancel_cause(), parent.cancel_cause());
+        assert!(!detached.is_cancelled());
+        assert!(detached.cancel_cause().is_none());
+
+        // Cancelling a child leaves the parent running
+        let parent = Arc::new(PipelineContext::new(RunIdentity::new()));
+        parent.fork_for_subpipeline(RunIdentity::new()).mark_cancelled();
+        assert!(!parent.is_cancelled());
+    }
+
     #[test]
     fn test_pipeline_context_with_service() {
         let ctx = PipelineContext::new(RunIdentity::new())
@@ -140,4 +232,122 @@ mod tests {
         let deserialized: RunIdentity = serde_json::from_str(&json).unwrap();
         assert_eq!(identity.pipeline_run_id, deserialized.pipeline_run_id);
     }
+
+    fn stage_ctx_with(consistency: crate::context::ContextConsistency) -> (StageContext, Arc<crate::events::CollectingEventSink>) {
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_consistency(consistency),
+        );
+        ctx.data.set("existing", serde_json::json!(1)).unwrap();
+        let stage = StageContext::new(ctx, "stage", StageInputs::default(), ContextSnapshot::new());
+        (stage, sink)
+    }
+
+    #[test]
+    fn test_frozen_snapshot_rejects_writes_and_ignores_later_changes() {
+        let (stage, sink) = stage_ctx_with(crate::context::ContextConsistency::FrozenSnapshot);
+
+        stage.pipeline_ctx().data.set("late", serde_json::json!(2)).unwrap();
+        assert_eq!(stage.read_data("existing"), Some(serde_json::json!(1)));
+        assert!(stage.read_data("late").is_none());
+
+        assert!(stage.write_data("new", serde_json::json!(3)).is_err());
+        let violations = sink.events_of_type("context.consistency_violation");
+        assert_eq!(violations.len(), 1);
+
+        // The bag itself is the frozen copy, not the live one
+        assert!(stage.data().get("late").is_none());
+        stage.data().set("through_bag", serde_json::json!(4)).unwrap();
+        assert!(!stage.pipeline_ctx().data.contains_key("through_bag"));
+
+        // Writes through the bag are lost, so they are reported at the end
+        assert_eq!(stage.commit_writes(), 0);
+        let violations = sink.events_of_type("context.consistency_violation");
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[1].1.as_ref().unwrap()["key"], "through_bag");
+    }
+
+    #[test]
+    fn test_live_reads_write_through() {
+        let (stage, _sink) = stage_ctx_with(crate::context::ContextConsistency::LiveReads);
+
+        stage.write_data("new", serde_json::json!(3)).unwrap();
+        assert_eq!(stage.pipeline_ctx().data.get("new"), Some(serde_json::json!(3)));
+        assert!(stage.write_data("new", serde_json::json!(4)).is_err());
+    }
+
+    #[test]
+    fn test_copy_on_write_isolates_until_commit() {
+        let (stage, sink) = stage_ctx_with(crate::context::ContextConsistency::CopyOnWrite);
+
+        stage.write_data("mine", serde_json::json!("a")).unwrap();
+        stage.write_data("theirs", serde_json::json!("a")).unwrap();
+        assert_eq!(stage.read_data("mine"), Some(serde_json::json!("a")));
+        assert!(!stage.pipeline_ctx().data.contains_key("mine"));
+
+        // A parallel branch claims one of the keys first.
+        stage.pipeline_ctx().data.set("theirs", serde_json::json!("b")).unwrap();
+
+        assert_eq!(stage.commit_writes(), 1);
+        assert_eq!(stage.pipeline_ctx().data.get("mine"), Some(serde_json::json!("a")));
+        assert_eq!(stage.pipeline_ctx().data.get("theirs"), Some(serde_json::json!("b")));
+        assert_eq!(sink.events_of_type("context.consistency_violation").len(), 1);
+    }
+
+    #[test]
+    fn test_copy_on_write_discards_writes_of_failed_stage() {
+        let (stage, sink) = stage_ctx_with(crate::context::ContextConsistency::CopyOnWrite);
+
+        stage.write_data("mine", serde_json::json!("a")).unwrap();
+        assert_eq!(stage.discard_writes(), 1);
+        assert!(!stage.pipeline_ctx().data.contains_key("mine"));
+        assert_eq!(stage.commit_writes(), 0);
+
+        let discarded = sink.events_of_type("context.writes_discarded");
+        assert_eq!(discarded.len(), 1);
+        assert_eq!(discarded[0].1.as_ref().unwrap()["keys"], serde_json::json!(["mine"]));
+    }
+
+    #[tokio::test]
+    async fn test_enrichment_cache_shared_across_runs() {
+        let cache = Arc::new(crate::context::EnrichmentCache::new());
+        let user = uuid::Uuid::new_v4();
+        let source = serde_json::json!({"query": "weather"});
+        let lookup = |stage: StageContext| {
+            let source = source.clone();
+            async move {
+                let computed = std::sync::atomic::AtomicBool::new(false);
+                let value = stage
+                    .cached_enrichment("web_results", &source, || async {
+                        computed.store(true, std::sync::atomic::Ordering::SeqCst);
+                        Ok::<_, std::convert::Infallible>(serde_json::json!(["sunny"]))
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(value, serde_json::json!(["sunny"]));
+                computed.into_inner()
+            }
+        };
+
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let ctx = Arc::new(
+                PipelineContext::new(RunIdentity::new().with_user_id(user))
+                    .with_enrichment_cache(cache.clone()),
+            );
+            let stage = StageContext::new(ctx.clone(), "enrich", StageInputs::default(), ContextSnapshot::new());
+            runs.push((lookup(stage).await, ctx.enrichment_cache_stats().unwrap()));
+        }
+        assert!(runs[0].0 && !runs[1].0);
+        assert_eq!(runs[1].1, crate::context::EnrichmentCacheStats { hits: 1, misses: 0 });
+        assert_eq!((cache.stats().hits, cache.stats().misses), (1, 1));
+
+        // Runs without a cache always compute
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new().with_user_id(user)));
+        let stage = StageContext::new(ctx.clone(), "enrich", StageInputs::default(), ContextSnapshot::new());
+        assert!(lookup(stage).await);
+        assert!(ctx.enrichment_cache_stats().is_none());
+    }
 }
@@ -1,12 +1,29 @@
 //! Mutable execution contexts for pipeline and stage execution.
 
-use super::{ContextBag, ContextSnapshot, OutputBag, RunIdentity, StageInputs};
-use crate::events::{get_event_sink, EventSink};
+use super::leak::{ContextKind, ContextToken, LeakDetector, LeakTracker};
+use super::cache::{CacheCounters, EnrichmentCache, EnrichmentCacheStats, EnrichmentKey};
+use super::sandbox::{resolve_path, AccessKind, AccessReport, Sandbox, SandboxPolicy};
+use super::{
+    ContextBag, ContextConsistency, ContextSnapshot, ExecutionProfile, OutputBag, RunIdentity,
+    StageInputs,
+};
+use crate::artifacts::ArtifactStore;
+use crate::cancellation::{CancellationToken, CoopInterrupt, CoopStats, CoopYield};
+use crate::errors::{AccessDeniedError, DataConflictError, ReadOnlyContextError, StageflowError};
+use crate::events::{get_event_sink, EmittedEvent, EventMetadata, EventSink, PipelineEvent};
+use crate::observability::WideEventEmitter;
+use crate::pipeline::{
+    BudgetTracker, BudgetUsage, CancelReason, CleanupRegistry, Heartbeat, IdempotencyConfig, IdempotencyStore,
+    RetryBudget, RetryBudgetExhausted, RetryBudgetTracker, RetryBudgetUsage, RunBudget,
+};
+use crate::secrets::{SecretError, SecretResolver};
+use crate::tools::{get_tool_registry, ToolCallRecord, ToolRegistry, ToolTranscript};
+use crate::utils::DeterministicSource;
 use async_trait::async_trait;
 use parking_lot::RwLock;
 use std::collections::{HashMap, HashSet};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use uuid::Uuid;
 
 /// Trait unifying pipeline and stage context behaviors.
@@ -27,8 +44,46 @@ pub trait ExecutionContext: Send + Sync {
     /// Tries to emit an event.
     fn try_emit_event(&self, event_type: &str, data: Option<serde_json::Value>);
 
+    /// Emits a typed event.
+    ///
+    /// The default serializes it and calls
+    /// [`try_emit_event`](Self::try_emit_event); pipeline contexts hand it
+    /// to the sink as is, leaving serialization to sinks that need JSON.
+    fn emit_pipeline_event(&self, event: PipelineEvent) {
+        let event_type = event.event_type().to_string();
+        self.try_emit_event(&event_type, event.into_data());
+    }
+
     /// Checks if the context is cancelled.
     fn is_cancelled(&self) -> bool;
+
+    /// Records a finished tool invocation. Contexts without a transcript ignore it.
+    fn record_tool_call(&self, _record: ToolCallRecord) {}
+
+    /// Returns true if side effects should be simulated rather than performed.
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+
+    /// Returns the execution profile.
+    fn profile(&self) -> ExecutionProfile {
+        ExecutionProfile::Standard
+    }
+
+    /// Admits a tool call, counting it against the run's budget.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`CancelReason`] if the run is cancelled or the call
+    /// would exceed the budget, which cancels the run.
+    fn reserve_tool_call(&self) -> Result<(), CancelReason> {
+        Ok(())
+    }
+
+    /// Returns the resolver for `secret://` references, if one is attached.
+    fn secrets(&self) -> Option<Arc<SecretResolver>> {
+        None
+    }
 }
 
 /// The mutable context for a pipeline execution.
@@ -51,10 +106,54 @@ pub struct PipelineContext {
     cancelled: AtomicBool,
     /// Cancel reason.
     cancel_reason: RwLock<Option<String>>,
+    /// Typed cause of the cancellation, when known.
+    cancel_cause: RwLock<Option<CancelReason>>,
+    /// Token cancelled with the run; stages get child tokens of it.
+    cancel_token: Arc<CancellationToken>,
     /// Service name.
     service: Option<String>,
     /// Parent context (for subpipelines).
     parent: Option<Arc<PipelineContext>>,
+    /// Whether cancelling the parent also cancels this context.
+    inherit_cancellation: bool,
+    /// How stages observe and mutate `data`.
+    consistency: ContextConsistency,
+    /// Execution profile.
+    profile: ExecutionProfile,
+    /// Seeded source used for reproducible runs.
+    deterministic_source: Option<Arc<DeterministicSource>>,
+    /// Tool invocations made during the run.
+    tool_transcript: RwLock<ToolTranscript>,
+    /// Registration with a leak detector, if one is attached.
+    leak_token: Option<ContextToken>,
+    /// Whether side-effecting stages and tools should only simulate.
+    dry_run: bool,
+    /// Environment and filesystem access policy, if one is attached.
+    sandbox: Option<Arc<Sandbox>>,
+    /// Enrichment results shared with other runs, if a cache is attached.
+    enrichment_cache: Option<Arc<EnrichmentCache>>,
+    /// Enrichment cache lookups made by this run.
+    enrichment_cache_counters: CacheCounters,
+    /// Run-level limits, shared with subpipelines.
+    budget: OnceLock<Arc<BudgetTracker>>,
+    /// Retries shared by every stage, shared with subpipelines.
+    retry_budget: OnceLock<Arc<RetryBudgetTracker>>,
+    /// Emitter for per-stage wide events, if one is attached.
+    wide_events: Option<Arc<WideEventEmitter>>,
+    /// Where stage artifacts are persisted, if a store is attached.
+    artifact_store: Option<Arc<dyn ArtifactStore>>,
+    /// Where outputs of keyed stages are cached, if a store is attached.
+    idempotency_store: Option<Arc<dyn IdempotencyStore>>,
+    /// How keyed stages use the idempotency store.
+    idempotency_config: IdempotencyConfig,
+    /// Times each stage has run, counted while wide events are emitted.
+    stage_runs: RwLock<HashMap<String, u32>>,
+    /// Tools available to this run; the global registry if unset.
+    tool_registry: Option<Arc<ToolRegistry>>,
+    /// Sequence number of the last event emitted in this run.
+    event_sequence: AtomicU64,
+    /// Resolver for `secret://` references, if one is attached.
+    secrets: Option<Arc<SecretResolver>>,
 }
 
 impl PipelineContext {
@@ -71,8 +170,30 @@ impl PipelineContext {
             event_sink: get_event_sink(),
             cancelled: AtomicBool::new(false),
             cancel_reason: RwLock::new(None),
+            cancel_cause: RwLock::new(None),
+            cancel_token: Arc::new(CancellationToken::new()),
             service: None,
             parent: None,
+            inherit_cancellation: true,
+            consistency: ContextConsistency::default(),
+            profile: ExecutionProfile::default(),
+            deterministic_source: None,
+            tool_transcript: RwLock::new(ToolTranscript::new()),
+            leak_token: None,
+            dry_run: false,
+            sandbox: None,
+            enrichment_cache: None,
+            enrichment_cache_counters: CacheCounters::default(),
+            budget: OnceLock::new(),
+            retry_budget: OnceLock::new(),
+            wide_events: None,
+            artifact_store: None,
+            idempotency_store: None,
+            idempotency_config: IdempotencyConfig::default(),
+            stage_runs: RwLock::new(HashMap::new()),
+            tool_registry: None,
+            event_sequence: AtomicU64::new(0),
+            secrets: None,
         }
     }
 
@@ -89,8 +210,30 @@ impl PipelineContext {
             event_sink: get_event_sink(),
             cancelled: AtomicBool::new(false),
             cancel_reason: RwLock::new(None),
+            cancel_cause: RwLock::new(None),
+            cancel_token: Arc::new(CancellationToken::new()),
             service: None,
             parent: None,
+            inherit_cancellation: true,
+            consistency: ContextConsistency::default(),
+            profile: ExecutionProfile::default(),
+            deterministic_source: None,
+            tool_transcript: RwLock::new(ToolTranscript::new()),
+            leak_token: None,
+            dry_run: false,
+            sandbox: None,
+            enrichment_cache: None,
+            enrichment_cache_counters: CacheCounters::default(),
+            budget: OnceLock::new(),
+            retry_budget: OnceLock::new(),
+            wide_events: None,
+            artifact_store: None,
+            idempotency_store: None,
+            idempotency_config: IdempotencyConfig::default(),
+            stage_runs: RwLock::new(HashMap::new()),
+            tool_registry: None,
+            event_sequence: AtomicU64::new(0),
+            secrets: None,
         }
     }
 
@@ -108,6 +251,19 @@ impl PipelineContext {
         self
     }
 
+    /// Marks the run as a dry run.
+    ///
+    /// Side-effecting stages should check [`ExecutionContext::is_dry_run`]
+    /// and skip their effects; [`AdvancedToolExecutor`] simulates
+    /// non-idempotent tools. Subpipelines inherit the flag.
+    ///
+    /// [`AdvancedToolExecutor`]: crate::tools::AdvancedToolExecutor
+    #[must_use]
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
     /// Sets the event sink.
     #[must_use]
     pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
@@ -122,26 +278,286 @@ impl PipelineContext {
         self
     }
 
+    /// Sets the context consistency mode for stages.
+    #[must_use]
+    pub fn with_consistency(mut self, consistency: ContextConsistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Returns the context consistency mode.
+    #[must_use]
+    pub fn consistency(&self) -> ContextConsistency {
+        self.consistency
+    }
+
+    /// Sets the execution profile.
+    ///
+    /// See [`ExecutionProfile::FastPath`] for what the lean profile drops.
+    #[must_use]
+    pub fn with_profile(mut self, profile: ExecutionProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Enables deterministic execution using the given source.
+    ///
+    /// Engines run stages one at a time in topological order and scope the
+    /// source around each stage, so UUIDs, timestamps, and retry jitter are
+    /// reproducible for the same seed.
+    #[must_use]
+    pub fn with_deterministic_source(mut self, source: Arc<DeterministicSource>) -> Self {
+        self.deterministic_source = Some(source);
+        self
+    }
+
+    /// Returns the deterministic source, if deterministic execution is enabled.
+    #[must_use]
+    pub fn deterministic_source(&self) -> Option<&Arc<DeterministicSource>> {
+        self.deterministic_source.as_ref()
+    }
+
+    /// Returns true if deterministic execution is enabled.
+    #[must_use]
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic_source.is_some()
+    }
+
     /// Marks the context as cancelled.
     pub fn mark_cancelled(&self) {
         self.cancelled.store(true, Ordering::SeqCst);
+        self.cancel_token.cancel("Pipeline cancelled");
     }
 
     /// Marks the context as cancelled with a reason.
     pub fn mark_cancelled_with_reason(&self, reason: impl Into<String>) {
+        let reason = reason.into();
         self.cancelled.store(true, Ordering::SeqCst);
-        *self.cancel_reason.write() = Some(reason.into());
+        *self.cancel_reason.write() = Some(reason.clone());
+        self.cancel_token.cancel(reason);
+    }
+
+    /// Returns the token cancelled when this run is.
+    ///
+    /// The token of a subpipeline forked with
+    /// [`fork_for_subpipeline`](Self::fork_for_subpipeline) is a child of
+    /// its parent's.
+    #[must_use]
+    pub fn cancellation_token(&self) -> &Arc<CancellationToken> {
+        &self.cancel_token
     }
 
     /// Returns the cancel reason, if any.
+    ///
+    /// A context cancelled through its parent reports the parent's reason.
     #[must_use]
     pub fn cancel_reason(&self) -> Option<String> {
-        self.cancel_reason.read().clone()
+        let reason = self.cancel_reason.read().clone();
+        reason.or_else(|| self.cancelled_parent().and_then(|parent| parent.cancel_reason()))
+    }
+
+    /// Returns true if cancelling the parent also cancels this context.
+    #[must_use]
+    pub fn inherits_cancellation(&self) -> bool {
+        self.inherit_cancellation
+    }
+
+    /// Returns the parent if this context observes its cancellation and it
+    /// has been cancelled.
+    fn cancelled_parent(&self) -> Option<&Arc<PipelineContext>> {
+        self.parent
+            .as_ref()
+            .filter(|parent| self.inherit_cancellation && parent.is_cancelled())
+    }
+
+    /// Cancels the run for a typed reason.
+    ///
+    /// The first typed reason is kept. Budget cancellations also emit
+    /// `pipeline.budget_exceeded`.
+    pub fn cancel_with(&self, reason: CancelReason) {
+        if self.cancel_cause.read().is_some() {
+            return;
+        }
+        if let CancelReason::BudgetExceeded {
+            limit,
+            max,
+            attempted,
+        } = &reason
+        {
+            self.try_emit_event(
+                "pipeline.budget_exceeded",
+                Some(serde_json::json!({
+                    "limit": limit,
+                    "max": max,
+                    "attempted": attempted,
+                })),
+            );
+        }
+        self.mark_cancelled_with_reason(reason.to_string());
+        *self.cancel_cause.write() = Some(reason);
+    }
+
+    /// Returns why the run was cancelled, if it was.
+    ///
+    /// Cancellations made with a plain string reason are reported as
+    /// [`CancelReason::Requested`]; a context cancelled through its parent
+    /// reports the parent's cause.
+    #[must_use]
+    pub fn cancel_cause(&self) -> Option<CancelReason> {
+        if !self.cancelled.load(Ordering::SeqCst) {
+            return self.cancelled_parent().and_then(|parent| parent.cancel_cause());
+        }
+        let cause = self.cancel_cause.read().clone();
+        cause.or_else(|| {
+            Some(CancelReason::requested(
+                self.cancel_reason().unwrap_or_else(|| "Pipeline cancelled".to_string()),
+            ))
+        })
+    }
+
+    /// Applies run-level limits to this run and its subpipelines.
+    ///
+    /// Takes precedence over a budget set on the pipeline builder. Has no
+    /// effect once a budget is in place.
+    #[must_use]
+    pub fn with_budget(self, budget: RunBudget) -> Self {
+        self.install_budget(budget);
+        self
+    }
+
+    /// Returns the run-level limits in effect, if any.
+    #[must_use]
+    pub fn budget(&self) -> Option<RunBudget> {
+        self.budget.get().map(|tracker| *tracker.budget())
+    }
+
+    /// Returns what the run has used against its budget, if one is set.
+    #[must_use]
+    pub fn budget_usage(&self) -> Option<BudgetUsage> {
+        self.budget.get().map(|tracker| tracker.usage())
+    }
+
+    /// Starts enforcing `budget` unless one is already in place.
+    pub(crate) fn install_budget(&self, budget: RunBudget) {
+        if !budget.is_unlimited() {
+            self.budget.get_or_init(|| Arc::new(BudgetTracker::new(budget)));
+        }
+    }
+
+    /// Limits the retries of every stage of this run and its subpipelines
+    /// together.
+    ///
+    /// Has no effect once a retry budget is in place.
+    #[must_use]
+    pub fn with_retry_budget(self, budget: RetryBudget) -> Self {
+        if !budget.is_unlimited() {
+            self.retry_budget.get_or_init(|| Arc::new(RetryBudgetTracker::new(budget)));
+        }
+        self
+    }
+
+    /// Returns the retry budget in effect, if any.
+    #[must_use]
+    pub fn retry_budget(&self) -> Option<RetryBudget> {
+        self.retry_budget.get().map(|tracker| *tracker.budget())
+    }
+
+    /// Returns the retries used against the retry budget, if one is set.
+    #[must_use]
+    pub fn retry_budget_usage(&self) -> Option<RetryBudgetUsage> {
+        self.retry_budget.get().map(|tracker| tracker.usage())
+    }
+
+    /// Asks the retry budget for a retry of `stage` starting after `delay`.
+    ///
+    /// Retry policies call this once they have decided a retry is worth
+    /// making. Without a retry budget every retry is granted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RetryBudgetExhausted`] if the run cannot afford the retry,
+    /// after emitting `retry.budget_exhausted`.
+    pub fn reserve_retry_budget(&self, stage: &str, delay: std::time::Duration) -> Result<(), RetryBudgetExhausted> {
+        let Some(tracker) = self.retry_budget.get() else {
+            return Ok(());
+        };
+        tracker.reserve(delay).map_err(|exhausted| {
+            let usage = tracker.usage();
+            self.try_emit_event(
+                "retry.budget_exhausted",
+                Some(serde_json::json!({
+                    "stage": stage,
+                    "limit": exhausted.limit(),
+                    "retries": usage.retries,
+                    "retry_time_ms": u64::try_from(usage.retry_time.as_millis()).unwrap_or(u64::MAX),
+                    "error": exhausted.to_string(),
+                })),
+            );
+            exhausted
+        })
+    }
+
+    /// Charges the time a retried attempt took to the retry budget.
+    pub fn record_retry_time(&self, elapsed: std::time::Duration) {
+        if let Some(tracker) = self.retry_budget.get() {
+            tracker.record(elapsed);
+        }
+    }
+
+    /// Counts a stage retry against the budget, cancelling the run if it
+    /// would exceed it.
+    pub(crate) fn reserve_retry(&self) -> Result<(), CancelReason> {
+        self.enforce_budget(BudgetTracker::reserve_retry)
+    }
+
+    /// Records entering a subpipeline, cancelling the run if it nests too
+    /// deeply.
+    pub(crate) fn enter_subpipeline(&self, depth: u32) -> Result<(), CancelReason> {
+        self.enforce_budget(|tracker| tracker.enter_depth(depth))
+    }
+
+    /// Cancels the run if its wall-clock limit has passed.
+    pub(crate) fn check_wall_clock(&self) -> Result<(), CancelReason> {
+        self.enforce_budget(BudgetTracker::check_wall_clock)
+    }
+
+    /// Returns when the run's wall-clock limit runs out, if one is set.
+    pub(crate) fn budget_deadline(&self) -> Option<std::time::Instant> {
+        self.budget.get().and_then(|tracker| tracker.deadline())
+    }
+
+    fn enforce_budget(
+        &self,
+        check: impl FnOnce(&BudgetTracker) -> Result<(), CancelReason>,
+    ) -> Result<(), CancelReason> {
+        let Some(tracker) = self.budget.get() else {
+            return Ok(());
+        };
+        check(tracker).map_err(|reason| {
+            self.cancel_with(reason.clone());
+            reason
+        })
     }
 
     /// Creates a child context for a subpipeline.
+    ///
+    /// The child is cancelled whenever this context is.
     #[must_use]
     pub fn fork_for_subpipeline(self: &Arc<Self>, child_run_id: RunIdentity) -> Arc<Self> {
+        self.fork(child_run_id, true)
+    }
+
+    /// Creates a child context that keeps running when this context is
+    /// cancelled.
+    #[must_use]
+    pub fn fork_detached_for_subpipeline(self: &Arc<Self>, child_run_id: RunIdentity) -> Arc<Self> {
+        self.fork(child_run_id, false)
+    }
+
+    fn fork(self: &Arc<Self>, child_run_id: RunIdentity, inherit_cancellation: bool) -> Arc<Self> {
+        let leak_token = self
+            .leak_tracker()
+            .map(|tracker| tracker.track_context(ContextKind::Pipeline, run_label(&child_run_id)));
         Arc::new(Self {
             run_id: child_run_id,
             topology: self.topology.clone(),
@@ -152,8 +568,37 @@ impl PipelineContext {
             event_sink: self.event_sink.clone(),
             cancelled: AtomicBool::new(false),
             cancel_reason: RwLock::new(None),
+            cancel_cause: RwLock::new(None),
+            cancel_token: if inherit_cancellation {
+                self.cancel_token.child()
+            } else {
+                Arc::new(CancellationToken::new())
+            },
             service: self.service.clone(),
             parent: Some(self.clone()),
+            inherit_cancellation,
+            consistency: self.consistency,
+            profile: self.profile,
+            deterministic_source: self.deterministic_source.clone(),
+            tool_transcript: RwLock::new(ToolTranscript::new()),
+            leak_token,
+            dry_run: self.dry_run,
+            sandbox: self
+                .sandbox
+                .as_ref()
+                .map(|sandbox| Arc::new(Sandbox::new(sandbox.policy().clone()))),
+            enrichment_cache: self.enrichment_cache.clone(),
+            enrichment_cache_counters: CacheCounters::default(),
+            budget: self.budget.get().cloned().map_or_else(OnceLock::new, OnceLock::from),
+            retry_budget: self.retry_budget.get().cloned().map_or_else(OnceLock::new, OnceLock::from),
+            wide_events: self.wide_events.clone(),
+            artifact_store: self.artifact_store.clone(),
+            idempotency_store: self.idempotency_store.clone(),
+            idempotency_config: self.idempotency_config.clone(),
+            stage_runs: RwLock::new(HashMap::new()),
+            tool_registry: self.tool_registry.clone(),
+            event_sequence: AtomicU64::new(0),
+            secrets: self.secrets.clone(),
         })
     }
 
@@ -180,6 +625,238 @@ impl PipelineContext {
     pub fn parent(&self) -> Option<&Arc<PipelineContext>> {
         self.parent.as_ref()
     }
+
+    /// Returns a copy of the tool calls recorded so far in this run.
+    #[must_use]
+    pub fn tool_transcript(&self) -> ToolTranscript {
+        self.tool_transcript.read().clone()
+    }
+
+    /// Tracks this context, and the stage and subpipeline contexts derived
+    /// from it, with a leak detector.
+    #[must_use]
+    pub fn with_leak_detector(mut self, detector: &LeakDetector) -> Self {
+        let label = run_label(&self.run_id);
+        self.leak_token = Some(detector.tracker().track_context(ContextKind::Pipeline, label));
+        self
+    }
+
+    /// Checks environment and filesystem access made through the
+    /// [`StageContext`] helpers against a policy.
+    ///
+    /// Subpipelines inherit the policy and keep their own report.
+    #[must_use]
+    pub fn with_sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox = Some(Arc::new(Sandbox::new(policy)));
+        self
+    }
+
+    /// Returns the accesses recorded so far in this run, if a sandbox
+    /// policy is attached.
+    #[must_use]
+    pub fn access_report(&self) -> Option<AccessReport> {
+        self.sandbox.as_ref().map(|sandbox| sandbox.report())
+    }
+
+    /// Shares enrichment results with other runs through a cache.
+    ///
+    /// Subpipelines use the same cache and keep their own statistics.
+    #[must_use]
+    pub fn with_enrichment_cache(mut self, cache: Arc<EnrichmentCache>) -> Self {
+        self.enrichment_cache = Some(cache);
+        self
+    }
+
+    /// Returns the enrichment cache, if one is attached.
+    #[must_use]
+    pub fn enrichment_cache(&self) -> Option<&Arc<EnrichmentCache>> {
+        self.enrichment_cache.as_ref()
+    }
+
+    /// Returns this run's enrichment cache hits and misses, if a cache is
+    /// attached.
+    #[must_use]
+    pub fn enrichment_cache_stats(&self) -> Option<EnrichmentCacheStats> {
+        self.enrichment_cache
+            .as_ref()
+            .map(|_| self.enrichment_cache_counters.stats())
+    }
+
+    /// Emits a [`StageWideEvent`](crate::observability::StageWideEvent) for
+    /// every stage execution in this run.
+    ///
+    /// Subpipelines emit through the same emitter.
+    #[must_use]
+    pub fn with_wide_events(mut self, emitter: Arc<WideEventEmitter>) -> Self {
+        self.wide_events = Some(emitter);
+        self
+    }
+
+    /// Returns the wide event emitter, if one is attached.
+    #[must_use]
+    pub fn wide_events(&self) -> Option<&Arc<WideEventEmitter>> {
+        self.wide_events.as_ref()
+    }
+
+    /// Persists the artifacts of every stage in this run, and its
+    /// subpipelines, to `store`, leaving references in the outputs.
+    #[must_use]
+    pub fn with_artifact_store(mut self, store: Arc<dyn ArtifactStore>) -> Self {
+        self.artifact_store = Some(store);
+        self
+    }
+
+    /// Returns the artifact store, if one is attached.
+    #[must_use]
+    pub fn artifact_store(&self) -> Option<&Arc<dyn ArtifactStore>> {
+        self.artifact_store.as_ref()
+    }
+
+    /// Caches the outputs of stages with an
+    /// [idempotency key](crate::pipeline::StageSpec::with_idempotency_key)
+    /// in `store`, for this run and its subpipelines.
+    #[must_use]
+    pub fn with_idempotency_store(mut self, store: Arc<dyn IdempotencyStore>) -> Self {
+        self.idempotency_store = Some(store);
+        self
+    }
+
+    /// Returns the idempotency store, if one is attached.
+    #[must_use]
+    pub fn idempotency_store(&self) -> Option<&Arc<dyn IdempotencyStore>> {
+        self.idempotency_store.as_ref()
+    }
+
+    /// Sets how long keyed outputs are cached, whether differing inputs
+    /// under the same key fail, and which `dependency.key` inputs are
+    /// compared.
+    #[must_use]
+    pub fn with_idempotency_config(mut self, config: IdempotencyConfig) -> Self {
+        self.idempotency_config = config;
+        self
+    }
+
+    /// Returns how keyed stages use the idempotency store.
+    #[must_use]
+    pub fn idempotency_config(&self) -> &IdempotencyConfig {
+        &self.idempotency_config
+    }
+
+    /// Resolves tools for this run, and its subpipelines, from `registry`
+    /// instead of the global one.
+    ///
+    /// Use [`ToolRegistry::overlay`] over the global registry to add or
+    /// override tools for a single run.
+    #[must_use]
+    pub fn with_tool_registry(mut self, registry: Arc<ToolRegistry>) -> Self {
+        self.tool_registry = Some(registry);
+        self
+    }
+
+    /// Returns the registry tools of this run resolve from.
+    #[must_use]
+    pub fn tool_registry(&self) -> Arc<ToolRegistry> {
+        self.tool_registry.clone().unwrap_or_else(get_tool_registry)
+    }
+
+    /// Resolves `secret://` references for this run, and its subpipelines,
+    /// with `resolver`.
+    ///
+    /// Values the resolver returns are redacted from the run's events and
+    /// stage errors from then on.
+    #[must_use]
+    pub fn with_secrets(mut self, resolver: Arc<SecretResolver>) -> Self {
+        self.secrets = Some(resolver);
+        self
+    }
+
+    /// Returns the attached secret resolver.
+    #[must_use]
+    pub fn secrets(&self) -> Option<&Arc<SecretResolver>> {
+        self.secrets.as_ref()
+    }
+
+    /// Returns the sequence number of the last event emitted in this run,
+    /// or 0 if none was.
+    #[must_use]
+    pub fn event_sequence(&self) -> u64 {
+        self.event_sequence.load(Ordering::SeqCst)
+    }
+
+    /// Returns the `emitted_at` timestamp and the run's next `sequence`
+    /// number for an event.
+    ///
+    /// Timestamps come from the deterministic source when one is attached,
+    /// so reproducible runs emit reproducible events.
+    fn next_stamp(&self) -> (String, u64) {
+        let emitted_at = self
+            .deterministic_source
+            .as_ref()
+            .map_or_else(chrono::Utc::now, |source| source.now());
+        let sequence = self.event_sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        (crate::utils::timestamps::format_iso8601(&emitted_at), sequence)
+    }
+
+    /// Turns event data into an object carrying `emitted_at` and `sequence`.
+    ///
+    /// Data that is not an object is wrapped as `{"value": data}`.
+    fn stamp_event(&self, data: Option<serde_json::Value>) -> serde_json::Map<String, serde_json::Value> {
+        let mut map = match data {
+            None => serde_json::Map::new(),
+            Some(serde_json::Value::Object(map)) => map,
+            Some(value) => serde_json::Map::from_iter([("value".to_string(), value)]),
+        };
+        let (emitted_at, sequence) = self.next_stamp();
+        map.insert("emitted_at".to_string(), serde_json::json!(emitted_at));
+        map.insert("sequence".to_string(), serde_json::json!(sequence));
+        map
+    }
+
+    /// Stamps the envelope of a typed event, as [`stamp_event`](Self::stamp_event)
+    /// and `try_emit_event` do for JSON events.
+    fn event_metadata(&self) -> EventMetadata {
+        let (emitted_at, sequence) = self.next_stamp();
+        let mut metadata = EventMetadata {
+            pipeline_run_id: self.run_id.pipeline_run_id,
+            emitted_at: Some(emitted_at),
+            sequence: Some(sequence),
+            ..EventMetadata::default()
+        };
+        if !self.profile.is_fast_path() {
+            metadata.request_id = self.run_id.request_id;
+            metadata.execution_mode = Some(self.execution_mode.clone());
+            metadata.topology.clone_from(&self.topology);
+            metadata.dry_run = self.dry_run;
+        }
+        metadata
+    }
+
+    /// Sends an event to the sink with resolved secrets redacted.
+    fn emit_redacted(&self, event_type: &str, mut data: serde_json::Value) {
+        if let Some(secrets) = &self.secrets {
+            secrets.redactor().redact_value(&mut data);
+        }
+        self.event_sink.try_emit(event_type, Some(data));
+    }
+
+    /// Counts a run of `stage`, returning how many times it ran before.
+    pub(crate) fn record_stage_run(&self, stage: &str) -> u32 {
+        let mut runs = self.stage_runs.write();
+        let count = runs.entry(stage.to_string()).or_insert(0);
+        let prior = *count;
+        *count += 1;
+        prior
+    }
+
+    pub(crate) fn leak_tracker(&self) -> Option<&Arc<LeakTracker>> {
+        self.leak_token.as_ref().map(ContextToken::tracker)
+    }
+}
+
+fn run_label(run_id: &RunIdentity) -> String {
+    run_id
+        .pipeline_run_id
+        .map_or_else(|| "<no run id>".to_string(), |id| id.to_string())
 }
 
 #[async_trait]
@@ -201,12 +878,14 @@ impl ExecutionContext for PipelineContext {
     }
 
     fn try_emit_event(&self, event_type: &str, data: Option<serde_json::Value>) {
-        let mut enriched = data.unwrap_or(serde_json::json!({}));
-        
-        if let serde_json::Value::Object(ref mut map) = enriched {
-            if let Some(id) = self.run_id.pipeline_run_id {
-                map.insert("pipeline_run_id".to_string(), serde_json::json!(id.to_string()));
-            }
+        if !self.profile.emits(event_type) {
+            return;
+        }
+        let mut map = self.stamp_event(data);
+        if let Some(id) = self.run_id.pipeline_run_id {
+            map.insert("pipeline_run_id".to_string(), serde_json::json!(id.to_string()));
+        }
+        if !self.profile.is_fast_path() {
             if let Some(id) = self.run_id.request_id {
                 map.insert("request_id".to_string(), serde_json::json!(id.to_string()));
             }
@@ -214,14 +893,67 @@ impl ExecutionContext for PipelineContext {
             if let Some(ref topology) = self.topology {
                 map.insert("topology".to_string(), serde_json::json!(topology));
             }
+            if self.dry_run {
+                map.insert("dry_run".to_string(), serde_json::json!(true));
+            }
         }
 
-        self.event_sink.try_emit(event_type, Some(enriched));
+        self.emit_redacted(event_type, serde_json::Value::Object(map));
+    }
+
+    fn emit_pipeline_event(&self, event: PipelineEvent) {
+        if !self.profile.emits(event.event_type()) {
+            return;
+        }
+        // Redaction rewrites JSON, and custom events already carry it
+        if self.secrets.is_some() || matches!(event, PipelineEvent::Custom { .. }) {
+            let event_type = event.event_type().to_string();
+            self.try_emit_event(&event_type, event.into_data());
+            return;
+        }
+        let metadata = self.event_metadata();
+        self.event_sink.try_emit_event(Arc::new(EmittedEvent::new(event, metadata)));
     }
 
     fn is_cancelled(&self) -> bool {
-        self.cancelled.load(Ordering::SeqCst)
+        self.cancelled.load(Ordering::SeqCst) || self.cancelled_parent().is_some()
+    }
+
+    fn record_tool_call(&self, record: ToolCallRecord) {
+        self.tool_transcript.write().record(record);
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn profile(&self) -> ExecutionProfile {
+        self.profile
+    }
+
+    fn reserve_tool_call(&self) -> Result<(), CancelReason> {
+        if let Some(cause) = self.cancel_cause() {
+            return Err(cause);
+        }
+        self.enforce_budget(BudgetTracker::reserve_tool_call)
     }
+
+    fn secrets(&self) -> Option<Arc<SecretResolver>> {
+        self.secrets.clone()
+    }
+}
+
+/// Bags handed to a read-only stage in place of the shared ones.
+struct ReadOnlyBags {
+    data: ContextBag,
+    outputs: OutputBag,
+}
+
+/// Copy of the data bag handed to a `FrozenSnapshot` stage, with its
+/// contents at stage start to detect writes to it.
+struct FrozenData {
+    bag: ContextBag,
+    baseline: HashMap<String, serde_json::Value>,
 }
 
 /// The context for a single stage execution.
@@ -232,25 +964,297 @@ pub struct StageContext {
     stage_name: String,
     /// The stage inputs.
     inputs: StageInputs,
-    /// The context snapshot.
-    snapshot: ContextSnapshot,
+    /// The context snapshot, shared with the run's other stages until
+    /// mutated through [`snapshot_mut`](Self::snapshot_mut).
+    snapshot: Arc<ContextSnapshot>,
+    /// Copy of the data bag taken at stage start under `FrozenSnapshot`.
+    frozen_data: Option<FrozenData>,
+    /// Copy of the data bag taken at the first copy-on-write write.
+    local_view: RwLock<Option<HashMap<String, serde_json::Value>>>,
+    /// Keys written through a copy-on-write view, pending commit.
+    pending_writes: RwLock<Vec<String>>,
+    /// Callbacks run if the stage is aborted.
+    cancel_cleanup: Arc<CleanupRegistry>,
+    /// Child of the run's cancellation token, created on first use.
+    cancel_token: OnceLock<Arc<CancellationToken>>,
+    /// Progress reported by the stage, watched for stalls.
+    heartbeat: Heartbeat,
+    /// Yield counters published by [`CoopYield`], if the stage used it.
+    coop_stats: RwLock<Option<CoopStats>>,
+    /// Private copies of the bags handed to a read-only stage.
+    read_only: Option<ReadOnlyBags>,
+    /// Registration with the pipeline context's leak detector, held until drop.
+    _leak_token: Option<ContextToken>,
 }
 
 impl StageContext {
     /// Creates a new stage context.
+    ///
+    /// The snapshot may be passed owned or as an `Arc`; executors pass the
+    /// run's shared snapshot so stages do not each clone it.
     #[must_use]
     pub fn new(
         pipeline_ctx: Arc<PipelineContext>,
         stage_name: impl Into<String>,
         inputs: StageInputs,
-        snapshot: ContextSnapshot,
+        snapshot: impl Into<Arc<ContextSnapshot>>,
     ) -> Self {
+        let frozen_data = match pipeline_ctx.consistency {
+            ContextConsistency::FrozenSnapshot => Some(FrozenData {
+                bag: pipeline_ctx.data.clone(),
+                baseline: pipeline_ctx.data.to_dict(),
+            }),
+            ContextConsistency::LiveReads | ContextConsistency::CopyOnWrite => None,
+        };
+        let stage_name = stage_name.into();
+        let leak_token = pipeline_ctx
+            .leak_tracker()
+            .map(|tracker| tracker.track_context(ContextKind::Stage, stage_name.clone()));
         Self {
             pipeline_ctx,
-            stage_name: stage_name.into(),
+            stage_name,
             inputs,
-            snapshot,
+            snapshot: snapshot.into(),
+            frozen_data,
+            local_view: RwLock::new(None),
+            pending_writes: RwLock::new(Vec::new()),
+            cancel_cleanup: Arc::new(CleanupRegistry::new()),
+            cancel_token: OnceLock::new(),
+            heartbeat: Heartbeat::new(),
+            coop_stats: RwLock::new(None),
+            read_only: None,
+            _leak_token: leak_token,
+        }
+    }
+
+    /// Makes the context read-only: [`write_data`](Self::write_data) fails
+    /// whatever the consistency mode, and [`data`](Self::data) and
+    /// [`outputs`](Self::outputs) return copies taken now, so writes through
+    /// them never reach the pipeline.
+    ///
+    /// The copies are what the
+    /// [`ImmutabilityInterceptor`](crate::interceptors::ImmutabilityInterceptor)
+    /// compares to detect such writes. Access through
+    /// [`pipeline_ctx`](Self::pipeline_ctx) is not covered.
+    #[must_use]
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = Some(ReadOnlyBags {
+            data: self.pipeline_ctx.data.clone(),
+            outputs: self.pipeline_ctx.outputs.clone(),
+        });
+        self
+    }
+
+    /// Returns true if the stage may not write to the context.
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.is_some()
+    }
+
+    /// Registers cleanup to run if the stage is aborted.
+    ///
+    /// Callbacks run in LIFO order when the stage is cancelled, including
+    /// when its task is dropped by a pipeline cancel, timeout, or shutdown,
+    /// and are discarded once the stage finishes normally. Stages doing
+    /// multi-step external work use this to undo partial state.
+    pub fn on_cancel<F, Fut>(&self, name: impl Into<String>, callback: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.cancel_cleanup.register(name, callback);
+    }
+
+    /// Returns the registry holding the stage's cancel cleanup callbacks.
+    #[must_use]
+    pub fn cancel_cleanup(&self) -> &Arc<CleanupRegistry> {
+        &self.cancel_cleanup
+    }
+
+    /// Returns a token cancelled when the pipeline is.
+    ///
+    /// The token is a child of the run's, so stages can hand it to spawned
+    /// tasks or streaming helpers, or cancel it to stop their own work
+    /// without cancelling the run.
+    #[must_use]
+    pub fn cancellation_token(&self) -> Arc<CancellationToken> {
+        self.cancel_token
+            .get_or_init(|| self.pipeline_ctx.cancellation_token().child())
+            .clone()
+    }
+
+    /// Checks whether the stage should stop because the pipeline was
+    /// cancelled.
+    ///
+    /// Call it between units of work in long loops so the stage stops
+    /// promptly instead of running to completion.
+    ///
+    /// ```ignore
+    /// for chunk in chunks {
+    ///     if let Err(interrupt) = ctx.checkpoint() {
+    ///         return interrupt.into_output();
+    ///     }
+    ///     process(chunk);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoopInterrupt::Cancelled`] with the cancel reason once the
+    /// pipeline is cancelled.
+    pub fn checkpoint(&self) -> Result<(), CoopInterrupt> {
+        if !self.is_cancelled() {
+            return Ok(());
+        }
+        let reason = self
+            .pipeline_ctx
+            .cancel_reason()
+            .unwrap_or_else(|| "pipeline cancelled".to_string());
+        Err(CoopInterrupt::Cancelled(reason))
+    }
+
+    /// Reports that the stage is making progress.
+    ///
+    /// Long-running stages whose spec has a
+    /// [`StallPolicy`](crate::pipeline::StallPolicy) call this between units
+    /// of work; events emitted through the context count as progress too.
+    pub fn heartbeat(&self) {
+        self.heartbeat.beat();
+    }
+
+    /// Returns the number of heartbeats reported, including emitted events.
+    #[must_use]
+    pub fn heartbeats(&self) -> u64 {
+        self.heartbeat.beats()
+    }
+
+    /// Returns the time since the stage last reported progress, or since
+    /// it started.
+    #[must_use]
+    pub fn quiet_for(&self) -> std::time::Duration {
+        self.heartbeat.quiet_for()
+    }
+
+    /// Returns a cooperative yield helper for long CPU-bound loops.
+    #[must_use]
+    pub fn coop(&self) -> CoopYield<'_> {
+        CoopYield::new(self)
+    }
+
+    /// Returns the latest yield counters, if the stage used [`CoopYield`].
+    #[must_use]
+    pub fn coop_stats(&self) -> Option<CoopStats> {
+        self.coop_stats.read().clone()
+    }
+
+    pub(crate) fn record_coop_stats(&self, stats: CoopStats) {
+        *self.coop_stats.write() = Some(stats);
+    }
+
+    /// Checks an access against the run's sandbox policy and records it.
+    ///
+    /// Helpers for other resources call this before accessing them. Without
+    /// a sandbox every access is allowed and nothing is recorded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AccessDeniedError`] if the policy is strict and does not
+    /// allow the access.
+    pub fn check_access(&self, kind: AccessKind, target: &str) -> Result<(), AccessDeniedError> {
+        let Some(sandbox) = self.pipeline_ctx.sandbox.as_ref() else {
+            return Ok(());
+        };
+        let record = sandbox.check(&self.stage_name, kind, target);
+        if !record.allowed {
+            self.try_emit_event(
+                "sandbox.violation",
+                Some(serde_json::json!({
+                    "stage": self.stage_name,
+                    "kind": kind.as_str(),
+                    "target": target,
+                    "denied": record.denied,
+                })),
+            );
+        }
+        if record.denied {
+            return Err(AccessDeniedError::new(&self.stage_name, kind.as_str(), target));
+        }
+        Ok(())
+    }
+
+    /// Reads an environment variable through the sandbox policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AccessDeniedError`] if the policy denies it.
+    pub fn env_var(&self, name: &str) -> Result<Option<String>, AccessDeniedError> {
+        self.check_access(AccessKind::EnvVar, name)?;
+        Ok(std::env::var(name).ok())
+    }
+
+    /// Returns an enrichment result, reusing one cached by an earlier run.
+    ///
+    /// Results are keyed by the run's user ID, or its session ID when no
+    /// user is set, together with `kind` and a hash of `source`, the inputs
+    /// of the lookup. `compute` runs on a miss and only successful results
+    /// are cached. Without a cache or an identity it always runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from `compute`.
+    pub async fn cached_enrichment<F, Fut, E>(
+        &self,
+        kind: &str,
+        source: &serde_json::Value,
+        compute: F,
+    ) -> Result<serde_json::Value, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<serde_json::Value, E>>,
+    {
+        let run_id = self.pipeline_ctx.run_id();
+        let owner = run_id.user_id.or(run_id.session_id);
+        let (Some(cache), Some(owner)) = (self.pipeline_ctx.enrichment_cache(), owner) else {
+            return compute().await;
+        };
+
+        let key = EnrichmentKey::new(owner, kind, source);
+        let cached = cache.get(&key);
+        self.pipeline_ctx.enrichment_cache_counters.record(cached.is_some());
+        if let Some(value) = cached {
+            return Ok(value);
         }
+        let value = compute().await?;
+        cache.insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// Reads a file as UTF-8 through the sandbox policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StageflowError::AccessDenied`] if the policy denies it, or
+    /// [`StageflowError::Io`] if the read fails.
+    pub async fn read_file(&self, path: impl AsRef<std::path::Path>) -> Result<String, StageflowError> {
+        let path = resolve_path(path.as_ref());
+        self.check_access(AccessKind::FileRead, &path.to_string_lossy())?;
+        Ok(tokio::fs::read_to_string(&path).await?)
+    }
+
+    /// Writes a file through the sandbox policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StageflowError::AccessDenied`] if the policy denies it, or
+    /// [`StageflowError::Io`] if the write fails.
+    pub async fn write_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        contents: impl AsRef<[u8]>,
+    ) -> Result<(), StageflowError> {
+        let path = resolve_path(path.as_ref());
+        self.check_access(AccessKind::FileWrite, &path.to_string_lossy())?;
+        Ok(tokio::fs::write(&path, contents).await?)
     }
 
     /// Returns the stage name.
@@ -259,6 +1263,33 @@ impl StageContext {
         &self.stage_name
     }
 
+    /// Returns the tool calls recorded so far in the run, including those of
+    /// earlier stages.
+    #[must_use]
+    pub fn tool_transcript(&self) -> ToolTranscript {
+        self.pipeline_ctx.tool_transcript()
+    }
+
+    /// Returns the registry tools of the run resolve from.
+    #[must_use]
+    pub fn tool_registry(&self) -> Arc<ToolRegistry> {
+        self.pipeline_ctx.tool_registry()
+    }
+
+    /// Resolves the `secret://` references in `value`, typically the stage's
+    /// configuration, with the run's secret resolver.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SecretError`] if a reference is malformed or cannot be
+    /// resolved, including when the run has no resolver attached.
+    pub async fn resolve_secrets(&self, value: &serde_json::Value) -> Result<serde_json::Value, SecretError> {
+        match &self.pipeline_ctx.secrets {
+            Some(resolver) => resolver.resolve(value).await,
+            None => SecretResolver::new().resolve(value).await,
+        }
+    }
+
     /// Returns the stage inputs.
     #[must_use]
     pub fn inputs(&self) -> &StageInputs {
@@ -271,6 +1302,21 @@ impl StageContext {
         &self.snapshot
     }
 
+    /// Returns the shared context snapshot, to hand to nested stages without
+    /// cloning it.
+    #[must_use]
+    pub fn shared_snapshot(&self) -> &Arc<ContextSnapshot> {
+        &self.snapshot
+    }
+
+    /// Returns the snapshot for stage-local changes.
+    ///
+    /// The first call copies the snapshot if other stages share it, so the
+    /// changes are never seen outside this context.
+    pub fn snapshot_mut(&mut self) -> &mut ContextSnapshot {
+        Arc::make_mut(&mut self.snapshot)
+    }
+
     /// Returns the pipeline context.
     #[must_use]
     pub fn pipeline_ctx(&self) -> &Arc<PipelineContext> {
@@ -278,9 +1324,160 @@ impl StageContext {
     }
 
     /// Returns the context data bag.
+    ///
+    /// Read-only stages get their private copy and `FrozenSnapshot` stages
+    /// the copy taken at stage start, so writes through either never reach
+    /// the pipeline; under `FrozenSnapshot` they are reported as consistency
+    /// violations when the stage finishes. Otherwise this is the live bag; prefer
+    /// [`Self::read_data`] and [`Self::write_data`], which honour the
+    /// consistency mode.
     #[must_use]
     pub fn data(&self) -> &ContextBag {
-        &self.pipeline_ctx.data
+        if let Some(bags) = &self.read_only {
+            return &bags.data;
+        }
+        self.frozen_data.as_ref().map_or(&self.pipeline_ctx.data, |frozen| &frozen.bag)
+    }
+
+    /// Returns the output bag, or the private copy for read-only stages.
+    #[must_use]
+    pub fn outputs(&self) -> &OutputBag {
+        self.read_only
+            .as_ref()
+            .map_or(&self.pipeline_ctx.outputs, |bags| &bags.outputs)
+    }
+
+    /// Returns the consistency mode in effect for this stage.
+    #[must_use]
+    pub fn consistency(&self) -> ContextConsistency {
+        self.pipeline_ctx.consistency
+    }
+
+    /// Reads a context value according to the consistency mode.
+    #[must_use]
+    pub fn read_data(&self, key: &str) -> Option<serde_json::Value> {
+        if let Some(frozen) = &self.frozen_data {
+            return frozen.bag.get(key);
+        }
+        if let Some(view) = self.local_view.read().as_ref() {
+            return view.get(key).cloned();
+        }
+        self.pipeline_ctx.data.get(key)
+    }
+
+    /// Writes a context value according to the consistency mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ReadOnlyContext` for a read-only stage,
+    /// `ConsistencyViolation` in `FrozenSnapshot` mode, and `DataConflict`
+    /// if the key already exists.
+    pub fn write_data(&self, key: impl Into<String>, value: serde_json::Value) -> Result<(), StageflowError> {
+        let key = key.into();
+        if self.is_read_only() {
+            self.report_violation("write", Some(&key));
+            return Err(ReadOnlyContextError::new(&self.stage_name).with_data_key(key).into());
+        }
+        match self.pipeline_ctx.consistency {
+            ContextConsistency::FrozenSnapshot => {
+                self.report_violation("write", Some(&key));
+                Err(StageflowError::ConsistencyViolation(format!(
+                    "Stage '{}' cannot write '{}' under frozen_snapshot consistency",
+                    self.stage_name, key
+                )))
+            }
+            ContextConsistency::LiveReads => Ok(self.pipeline_ctx.data.set(key, value)?),
+            ContextConsistency::CopyOnWrite => {
+                let mut view = self.local_view.write();
+                let view = view.get_or_insert_with(|| self.pipeline_ctx.data.to_dict());
+                if view.contains_key(&key) {
+                    return Err(DataConflictError::new(&key).into());
+                }
+                view.insert(key.clone(), value);
+                self.pending_writes.write().push(key);
+                Ok(())
+            }
+        }
+    }
+
+    /// Commits copy-on-write values to the shared data bag.
+    ///
+    /// Called by the engines once the stage succeeds. Keys written to the
+    /// bag by another stage in the meantime are reported as violations and
+    /// left untouched, as are writes through [`data`](Self::data) under
+    /// `FrozenSnapshot`. Returns the number of committed keys.
+    pub fn commit_writes(&self) -> usize {
+        self.report_frozen_writes();
+        let keys = std::mem::take(&mut *self.pending_writes.write());
+        if keys.is_empty() {
+            return 0;
+        }
+        let view = self.local_view.read();
+        let Some(view) = view.as_ref() else {
+            return 0;
+        };
+
+        let mut committed = 0;
+        for key in keys {
+            if let Some(value) = view.get(&key) {
+                if self.pipeline_ctx.data.set(key.clone(), value.clone()).is_ok() {
+                    committed += 1;
+                } else {
+                    self.report_violation("commit_conflict", Some(&key));
+                }
+            }
+        }
+        committed
+    }
+
+    /// Drops copy-on-write values instead of committing them.
+    ///
+    /// Called by the engines when the stage does not succeed. Dropped keys
+    /// are reported in a `context.writes_discarded` event, and writes
+    /// through [`data`](Self::data) under `FrozenSnapshot` as violations.
+    /// Returns the number of dropped keys.
+    pub fn discard_writes(&self) -> usize {
+        self.report_frozen_writes();
+        let keys = std::mem::take(&mut *self.pending_writes.write());
+        if !keys.is_empty() {
+            self.try_emit_event(
+                "context.writes_discarded",
+                Some(serde_json::json!({
+                    "mode": self.pipeline_ctx.consistency.as_str(),
+                    "keys": keys,
+                })),
+            );
+        }
+        keys.len()
+    }
+
+    /// Reports keys changed in the frozen copy, whose writes are lost.
+    fn report_frozen_writes(&self) {
+        let Some(frozen) = &self.frozen_data else {
+            return;
+        };
+        let current = frozen.bag.to_dict();
+        let mut keys: Vec<&String> = current
+            .keys()
+            .chain(frozen.baseline.keys())
+            .filter(|key| current.get(*key) != frozen.baseline.get(*key))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            self.report_violation("write", Some(key));
+        }
+    }
+
+    fn report_violation(&self, operation: &str, key: Option<&str>) {
+        self.try_emit_event(
+            "context.consistency_violation",
+            Some(serde_json::json!({
+                "mode": if self.is_read_only() { "read_only" } else { self.pipeline_ctx.consistency.as_str() },
+                "operation": operation,
+                "key": key,
+            })),
+        );
     }
 }
 
@@ -303,25 +1500,53 @@ impl ExecutionContext for StageContext {
     }
 
     fn try_emit_event(&self, event_type: &str, data: Option<serde_json::Value>) {
-        let mut enriched = data.unwrap_or(serde_json::json!({}));
-        
-        if let serde_json::Value::Object(ref mut map) = enriched {
-            if let Some(id) = self.pipeline_run_id() {
-                map.insert("pipeline_run_id".to_string(), serde_json::json!(id.to_string()));
-            }
+        self.heartbeat.beat();
+        let profile = self.pipeline_ctx.profile;
+        if !profile.emits(event_type) {
+            return;
+        }
+        let mut map = self.pipeline_ctx.stamp_event(data);
+        if let Some(id) = self.pipeline_run_id() {
+            map.insert("pipeline_run_id".to_string(), serde_json::json!(id.to_string()));
+        }
+        map.insert("stage".to_string(), serde_json::json!(&self.stage_name));
+        if !profile.is_fast_path() {
             if let Some(id) = self.request_id() {
                 map.insert("request_id".to_string(), serde_json::json!(id.to_string()));
             }
             map.insert("execution_mode".to_string(), serde_json::json!(self.execution_mode()));
-            map.insert("stage".to_string(), serde_json::json!(&self.stage_name));
+            if self.is_dry_run() {
+                map.insert("dry_run".to_string(), serde_json::json!(true));
+            }
         }
 
-        self.pipeline_ctx.event_sink.try_emit(event_type, Some(enriched));
+        self.pipeline_ctx.emit_redacted(event_type, serde_json::Value::Object(map));
     }
 
     fn is_cancelled(&self) -> bool {
         self.pipeline_ctx.is_cancelled()
     }
+
+    fn record_tool_call(&self, mut record: ToolCallRecord) {
+        record.stage.get_or_insert_with(|| self.stage_name.clone());
+        self.pipeline_ctx.record_tool_call(record);
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.pipeline_ctx.is_dry_run()
+    }
+
+    fn profile(&self) -> ExecutionProfile {
+        self.pipeline_ctx.profile
+    }
+
+    fn reserve_tool_call(&self) -> Result<(), CancelReason> {
+        self.pipeline_ctx.reserve_tool_call()
+    }
+
+    fn secrets(&self) -> Option<Arc<SecretResolver>> {
+        self.pipeline_ctx.secrets.clone()
+    }
 }
 
 /// Adapts a plain dictionary into an execution context.
@@ -455,6 +1680,40 @@ mod tests {
         assert_ne!(child.pipeline_run_id(), parent.pipeline_run_id());
     }
 
+    #[test]
+    fn test_events_carry_timestamp_and_run_sequence() {
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_deterministic_source(Arc::new(DeterministicSource::new(7))),
+        );
+        let stage_ctx = StageContext::new(ctx.clone(), "stage", StageInputs::default(), ContextSnapshot::new());
+
+        ctx.try_emit_event("pipeline.started", None);
+        stage_ctx.try_emit_event("stage.started", Some(serde_json::json!({"attempt": 1})));
+        ctx.fork_for_subpipeline(RunIdentity::new())
+            .try_emit_event("pipeline.started", None);
+        ctx.try_emit_event("pipeline.completed", None);
+        stage_ctx.try_emit_event("stage.progress", Some(serde_json::json!(0.5)));
+
+        let events = sink.events();
+        let sequences: Vec<u64> = events
+            .iter()
+            .map(|(_, data)| data.as_ref().unwrap()["sequence"].as_u64().unwrap())
+            .collect();
+        assert_eq!(sequences, vec![1, 2, 1, 3, 4]);
+        assert_eq!(ctx.event_sequence(), 4);
+        // Data that is not an object is wrapped, then stamped
+        let progress = events[4].1.as_ref().unwrap();
+        assert_eq!(progress["value"], 0.5);
+        assert_eq!(progress["stage"], "stage");
+        assert_eq!(
+            events[0].1.as_ref().unwrap()["emitted_at"],
+            "1970-01-01T00:00:00.000000+00:00"
+        );
+    }
+
     #[test]
     fn test_stage_context() {
         let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
@@ -472,6 +1731,84 @@ mod tests {
         assert_eq!(stage_ctx.pipeline_run_id(), pipeline_ctx.pipeline_run_id());
     }
 
+    #[test]
+    fn test_snapshot_mut_copies_on_write() {
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let shared = Arc::new(ContextSnapshot::new().with_input_text("original"));
+        let mut stage_ctx = StageContext::new(pipeline_ctx.clone(), "a", StageInputs::default(), shared.clone());
+        let other = StageContext::new(pipeline_ctx, "b", StageInputs::default(), shared.clone());
+        assert!(Arc::ptr_eq(stage_ctx.shared_snapshot(), &shared));
+
+        stage_ctx.snapshot_mut().input_text = Some("local".to_string());
+
+        assert_eq!(stage_ctx.snapshot().input_text.as_deref(), Some("local"));
+        assert_eq!(other.snapshot().input_text.as_deref(), Some("original"));
+        assert_eq!(shared.input_text.as_deref(), Some("original"));
+        assert!(Arc::ptr_eq(other.shared_snapshot(), &shared));
+    }
+
+    #[test]
+    fn test_stage_cancellation_token_and_checkpoint() {
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let subpipeline = pipeline_ctx.fork_for_subpipeline(RunIdentity::new());
+        let stage_ctx = StageContext::new(subpipeline, "loop", StageInputs::default(), ContextSnapshot::new());
+        let token = stage_ctx.cancellation_token();
+        assert!(Arc::ptr_eq(&token, &stage_ctx.cancellation_token()));
+
+        // Cancelling the stage's own token leaves the run alone
+        stage_ctx.cancellation_token().cancel("local");
+        assert!(!pipeline_ctx.cancellation_token().is_cancelled());
+        assert!(stage_ctx.checkpoint().is_ok());
+
+        let other = StageContext::new(
+            pipeline_ctx.fork_for_subpipeline(RunIdentity::new()),
+            "other",
+            StageInputs::default(),
+            ContextSnapshot::new(),
+        );
+        let token = other.cancellation_token();
+        pipeline_ctx.mark_cancelled_with_reason("operator abort");
+        assert_eq!(token.reason().as_deref(), Some("operator abort"));
+        assert_eq!(
+            other.checkpoint(),
+            Err(CoopInterrupt::Cancelled("operator abort".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_leak_detector_tracks_derived_contexts() {
+        let detector = LeakDetector::new().panic_on_drop();
+        let pipeline_ctx =
+            Arc::new(PipelineContext::new(RunIdentity::new()).with_leak_detector(&detector));
+        let stage_ctx = StageContext::new(
+            pipeline_ctx.clone(),
+            "my_stage",
+            StageInputs::default(),
+            ContextSnapshot::new(),
+        );
+        let child = pipeline_ctx.fork_for_subpipeline(RunIdentity::new());
+
+        let kinds: Vec<ContextKind> = detector
+            .report()
+            .live_contexts
+            .iter()
+            .map(|c| c.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![ContextKind::Pipeline, ContextKind::Stage, ContextKind::Pipeline]
+        );
+
+        drop(stage_ctx);
+        drop(child);
+        let leaked = pipeline_ctx.clone();
+        drop(pipeline_ctx);
+        assert_eq!(detector.report().live_contexts.len(), 1);
+
+        drop(leaked);
+        detector.assert_no_leaks();
+    }
+
     #[test]
     fn test_dict_context_adapter() {
         let mut data = HashMap::new();
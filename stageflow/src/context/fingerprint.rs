@@ -0,0 +1,183 @@
+//! Structural fingerprints of context snapshots.
+//!
+//! Values are hashed by walking their JSON structure with FNV-1a rather than
+//! serializing them, and object entries are combined order-independently so
+//! map iteration order does not matter. The result is stable across
+//! processes, which makes fingerprints safe to persist alongside sessions or
+//! use as memoization keys.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit structural hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub(crate) fn from_raw(hash: u64) -> Self {
+        Self(hash)
+    }
+
+    /// Returns the raw hash value.
+    #[must_use]
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Fingerprints of each snapshot section.
+///
+/// The run identity is not part of the fingerprint, so two runs over the
+/// same conversation and enrichments produce the same value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SnapshotFingerprint {
+    /// Hash of the conversation messages and routing decision.
+    pub conversation: Fingerprint,
+    /// Hash of the enrichment data.
+    pub enrichments: Fingerprint,
+    /// Hash of the extension bundle.
+    pub extensions: Fingerprint,
+    /// Hash of the input text and metadata.
+    pub input: Fingerprint,
+}
+
+impl SnapshotFingerprint {
+    /// Returns a single fingerprint covering every section.
+    #[must_use]
+    pub fn combined(&self) -> Fingerprint {
+        let mut hasher = StructHasher::new();
+        for section in [self.conversation, self.enrichments, self.extensions, self.input] {
+            hasher.write_u64(section.0);
+        }
+        Fingerprint(hasher.finish())
+    }
+
+    /// Returns the names of the sections that differ from another fingerprint.
+    #[must_use]
+    pub fn changed_sections(&self, other: &Self) -> Vec<&'static str> {
+        [
+            ("conversation", self.conversation, other.conversation),
+            ("enrichments", self.enrichments, other.enrichments),
+            ("extensions", self.extensions, other.extensions),
+            ("input", self.input, other.input),
+        ]
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(name, _, _)| name)
+        .collect()
+    }
+}
+
+impl std::fmt::Display for SnapshotFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.combined())
+    }
+}
+
+/// FNV-1a hasher over a structural encoding of JSON values.
+pub(crate) struct StructHasher(u64);
+
+impl StructHasher {
+    pub(crate) fn new() -> Self {
+        Self(FNV_OFFSET)
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub(crate) fn write_u64(&mut self, n: u64) {
+        self.write_bytes(&n.to_le_bytes());
+    }
+
+    /// Writes a length-prefixed string so adjacent strings cannot collide.
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.write_u64(s.len() as u64);
+        self.write_bytes(s.as_bytes());
+    }
+
+    pub(crate) fn write_value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.write_bytes(b"n"),
+            Value::Bool(b) => self.write_bytes(if *b { b"t" } else { b"f" }),
+            Value::Number(n) => {
+                self.write_bytes(b"#");
+                self.write_str(&n.to_string());
+            }
+            Value::String(s) => {
+                self.write_bytes(b"s");
+                self.write_str(s);
+            }
+            Value::Array(items) => {
+                self.write_bytes(b"[");
+                self.write_u64(items.len() as u64);
+                for item in items {
+                    self.write_value(item);
+                }
+            }
+            Value::Object(map) => {
+                self.write_bytes(b"{");
+                self.write_u64(map.len() as u64);
+                self.write_u64(map_hash(map));
+            }
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes a single value.
+pub(crate) fn value_hash(value: &Value) -> u64 {
+    let mut hasher = StructHasher::new();
+    hasher.write_value(value);
+    hasher.finish()
+}
+
+/// Hashes a key/value entry; entries are summed so order does not matter.
+fn entry_hash(key: &str, value: &Value) -> u64 {
+    let mut hasher = StructHasher::new();
+    hasher.write_str(key);
+    hasher.write_value(value);
+    hasher.finish()
+}
+
+/// Hashes map entries independently of iteration order.
+pub(crate) fn map_hash<'a, I>(entries: I) -> u64
+where
+    I: IntoIterator<Item = (&'a String, &'a Value)>,
+{
+    entries
+        .into_iter()
+        .map(|(k, v)| entry_hash(k, v))
+        .fold(0, u64::wrapping_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_value_hash_is_structural() {
+        assert_eq!(
+            value_hash(&json!({"a": 1, "b": [true, null]})),
+            value_hash(&json!({"b": [true, null], "a": 1}))
+        );
+        assert_ne!(value_hash(&json!(["ab", "c"])), value_hash(&json!(["a", "bc"])));
+        assert_ne!(value_hash(&json!("1")), value_hash(&json!(1)));
+    }
+}
@@ -37,7 +37,7 @@ impl RunIdentity {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            pipeline_run_id: Some(Uuid::new_v4()),
+            pipeline_run_id: Some(crate::utils::generate_uuid()),
             ..Default::default()
         }
     }
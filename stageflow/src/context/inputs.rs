@@ -1,21 +1,32 @@
 //! Stage inputs with strictness enforcement.
 
-use crate::errors::UndeclaredDependencyError;
+use crate::errors::{InputError, UndeclaredDependencyError};
+use serde::de::DeserializeOwned;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 /// Provides an immutable view of prior stage outputs.
 ///
-/// In strict mode, accessing undeclared dependencies raises an error.
+/// In strict mode, accessing undeclared dependencies raises an error, and
+/// only declared dependencies are listed. Dependencies with
+/// [declared keys](Self::with_declared_keys) additionally expose only
+/// those keys.
+///
+/// Each stage's output is held behind an `Arc`, so inputs built with
+/// [`from_shared`](Self::from_shared) share the executor's copies instead
+/// of cloning them.
 #[derive(Debug, Clone)]
 pub struct StageInputs {
     /// The available outputs from prior stages.
-    outputs: HashMap<String, HashMap<String, serde_json::Value>>,
+    outputs: HashMap<String, Arc<HashMap<String, serde_json::Value>>>,
     /// The declared dependencies for this stage.
     declared_dependencies: HashSet<String>,
     /// The name of the current stage (for error messages).
     stage_name: String,
     /// Whether strict mode is enabled.
     strict: bool,
+    /// Keys the stage may read, for dependencies that restrict them.
+    declared_keys: HashMap<String, HashSet<String>>,
 }
 
 impl StageInputs {
@@ -26,15 +37,61 @@ impl StageInputs {
         declared_dependencies: HashSet<String>,
         stage_name: impl Into<String>,
         strict: bool,
+    ) -> Self {
+        let outputs = outputs.into_iter().map(|(stage, output)| (stage, Arc::new(output))).collect();
+        Self::from_shared(outputs, declared_dependencies, stage_name, strict)
+    }
+
+    /// Creates new stage inputs over shared stage outputs, without copying
+    /// them.
+    #[must_use]
+    pub fn from_shared(
+        outputs: HashMap<String, Arc<HashMap<String, serde_json::Value>>>,
+        declared_dependencies: HashSet<String>,
+        stage_name: impl Into<String>,
+        strict: bool,
     ) -> Self {
         Self {
             outputs,
             declared_dependencies,
             stage_name: stage_name.into(),
             strict,
+            declared_keys: HashMap::new(),
         }
     }
 
+    /// Limits reads from each listed dependency to its keys.
+    ///
+    /// In strict mode the other keys of those dependencies are dropped, and
+    /// reading one fails with an `UndeclaredDependencyError` naming
+    /// `stage.key`. Has no effect in permissive mode.
+    #[must_use]
+    pub fn with_declared_keys(mut self, declared_keys: HashMap<String, HashSet<String>>) -> Self {
+        if !self.strict {
+            return self;
+        }
+        for (stage, keys) in &declared_keys {
+            if let Some(output) = self.outputs.get_mut(stage) {
+                if output.keys().any(|key| !keys.contains(key)) {
+                    Arc::make_mut(output).retain(|key, _| keys.contains(key));
+                }
+            }
+        }
+        self.declared_keys = declared_keys;
+        self
+    }
+
+    fn check(&self, stage: &str) -> Result<(), UndeclaredDependencyError> {
+        if self.strict && !self.declared_dependencies.contains(stage) {
+            return Err(UndeclaredDependencyError::new(&self.stage_name, stage));
+        }
+        Ok(())
+    }
+
+    fn is_visible(&self, stage: &str) -> bool {
+        !self.strict || self.declared_dependencies.contains(stage)
+    }
+
     /// Creates permissive stage inputs (no strictness).
     #[must_use]
     pub fn permissive(
@@ -43,9 +100,10 @@ impl StageInputs {
     ) -> Self {
         Self {
             declared_dependencies: outputs.keys().cloned().collect(),
-            outputs,
+            outputs: outputs.into_iter().map(|(stage, output)| (stage, Arc::new(output))).collect(),
             stage_name: stage_name.into(),
             strict: false,
+            declared_keys: HashMap::new(),
         }
     }
 
@@ -56,10 +114,8 @@ impl StageInputs {
     /// Returns `UndeclaredDependencyError` in strict mode if the stage
     /// is not a declared dependency.
     pub fn get(&self, stage: &str) -> Result<Option<&HashMap<String, serde_json::Value>>, UndeclaredDependencyError> {
-        if self.strict && !self.declared_dependencies.contains(stage) {
-            return Err(UndeclaredDependencyError::new(&self.stage_name, stage));
-        }
-        Ok(self.outputs.get(stage))
+        self.check(stage)?;
+        Ok(self.outputs.get(stage).map(AsRef::as_ref))
     }
 
     /// Gets a specific value from a stage's output.
@@ -67,30 +123,69 @@ impl StageInputs {
     /// # Errors
     ///
     /// Returns `UndeclaredDependencyError` in strict mode if the stage
-    /// is not a declared dependency.
+    /// is not a declared dependency or `key` is not among its declared keys.
     pub fn get_value(&self, stage: &str, key: &str) -> Result<Option<&serde_json::Value>, UndeclaredDependencyError> {
-        if self.strict && !self.declared_dependencies.contains(stage) {
-            return Err(UndeclaredDependencyError::new(&self.stage_name, stage));
+        self.check(stage)?;
+        if self.declared_keys.get(stage).is_some_and(|keys| !keys.contains(key)) {
+            return Err(UndeclaredDependencyError::new(&self.stage_name, format!("{stage}.{key}")));
         }
         Ok(self.outputs.get(stage).and_then(|o| o.get(key)))
     }
 
+    /// Deserializes a value from a stage's output.
+    ///
+    /// Returns `Ok(None)` if the stage has no output or did not produce
+    /// `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InputError::Undeclared`] in strict mode if the stage is not
+    /// a declared dependency, or [`InputError::Invalid`] if the value does
+    /// not deserialize into `T`.
+    pub fn get_typed<T: DeserializeOwned>(&self, stage: &str, key: &str) -> Result<Option<T>, InputError> {
+        let Some(value) = self.get_value(stage, key)? else {
+            return Ok(None);
+        };
+        T::deserialize(value).map(Some).map_err(|source| InputError::Invalid {
+            stage: stage.to_string(),
+            key: key.to_string(),
+            source,
+        })
+    }
+
+    /// Deserializes a value that must be present in a stage's output.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`get_typed`](Self::get_typed), or
+    /// [`InputError::Missing`] if the value is absent.
+    pub fn require_typed<T: DeserializeOwned>(&self, stage: &str, key: &str) -> Result<T, InputError> {
+        self.get_typed(stage, key)?.ok_or_else(|| InputError::Missing {
+            stage: stage.to_string(),
+            key: key.to_string(),
+        })
+    }
+
     /// Gets output from a stage without strictness check.
     #[must_use]
     pub fn get_unchecked(&self, stage: &str) -> Option<&HashMap<String, serde_json::Value>> {
-        self.outputs.get(stage)
+        self.outputs.get(stage).map(AsRef::as_ref)
     }
 
     /// Checks if output exists for a stage.
+    ///
+    /// In strict mode only declared dependencies count.
     #[must_use]
     pub fn contains(&self, stage: &str) -> bool {
-        self.outputs.contains_key(stage)
+        self.is_visible(stage) && self.outputs.contains_key(stage)
     }
 
-    /// Returns all available stage names.
+    /// Returns the names of stages with output.
+    ///
+    /// In strict mode only declared dependencies are listed.
     #[must_use]
     pub fn stages(&self) -> Vec<&String> {
-        self.outputs.keys().collect()
+        self.outputs.keys().filter(|stage| self.is_visible(stage)).collect()
     }
 
     /// Returns the declared dependencies.
@@ -99,18 +194,41 @@ impl StageInputs {
         &self.declared_dependencies
     }
 
+    /// Returns the keys each restricted dependency exposes.
+    #[must_use]
+    pub fn declared_keys(&self) -> &HashMap<String, HashSet<String>> {
+        &self.declared_keys
+    }
+
     /// Returns whether strict mode is enabled.
     #[must_use]
     pub fn is_strict(&self) -> bool {
         self.strict
     }
 
-    /// Converts all outputs to a flat dictionary.
+    /// Finds `key` in the first upstream output that has it.
+    ///
+    /// Stages are searched in name order; in strict mode only declared
+    /// dependencies are searched.
+    #[must_use]
+    pub fn find_value(&self, key: &str) -> Option<&serde_json::Value> {
+        let mut stages: Vec<&String> = self
+            .outputs
+            .keys()
+            .filter(|stage| self.is_visible(stage))
+            .collect();
+        stages.sort();
+        stages
+            .into_iter()
+            .find_map(|stage| self.outputs[stage].get(key))
+    }
+
+    /// Converts the visible outputs to a flat dictionary.
     #[must_use]
     pub fn to_flat_dict(&self) -> HashMap<String, serde_json::Value> {
         let mut result = HashMap::new();
-        for (stage, outputs) in &self.outputs {
-            for (key, value) in outputs {
+        for (stage, outputs) in self.outputs.iter().filter(|(stage, _)| self.is_visible(stage)) {
+            for (key, value) in outputs.iter() {
                 result.insert(format!("{stage}.{key}"), value.clone());
             }
         }
@@ -125,6 +243,7 @@ impl Default for StageInputs {
             declared_dependencies: HashSet::new(),
             stage_name: String::new(),
             strict: false,
+            declared_keys: HashMap::new(),
         }
     }
 }
@@ -213,6 +332,39 @@ mod tests {
         assert!(flat.contains_key("stage2.value"));
     }
 
+    #[test]
+    fn test_typed_access() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Score {
+            value: u32,
+        }
+
+        let mut outputs = sample_outputs();
+        outputs
+            .get_mut("stage1")
+            .unwrap()
+            .insert("score".to_string(), serde_json::json!({"value": 7}));
+        let deps = HashSet::from(["stage1".to_string()]);
+        let inputs = StageInputs::new(outputs, deps, "current", true);
+
+        assert_eq!(inputs.require_typed::<Score>("stage1", "score").unwrap(), Score { value: 7 });
+        assert_eq!(inputs.get_typed::<String>("stage1", "result").unwrap().as_deref(), Some("ok"));
+        assert!(inputs.get_typed::<u32>("stage1", "missing").unwrap().is_none());
+
+        assert!(matches!(
+            inputs.require_typed::<u32>("stage1", "missing"),
+            Err(InputError::Missing { .. })
+        ));
+        assert!(matches!(
+            inputs.require_typed::<u32>("stage1", "result"),
+            Err(InputError::Invalid { .. })
+        ));
+        assert!(matches!(
+            inputs.get_typed::<u32>("stage2", "value"),
+            Err(InputError::Undeclared(_))
+        ));
+    }
+
     #[test]
     fn test_get_unchecked_bypasses_strict() {
         let inputs = StageInputs::new(sample_outputs(), HashSet::new(), "current", true);
@@ -220,4 +372,59 @@ mod tests {
         // Even with no declared deps, unchecked works
         assert!(inputs.get_unchecked("stage1").is_some());
     }
+
+    #[test]
+    fn test_find_value() {
+        let mut outputs = sample_outputs();
+        outputs
+            .get_mut("stage2")
+            .unwrap()
+            .insert("result".to_string(), serde_json::json!("later"));
+
+        let inputs = StageInputs::permissive(outputs.clone(), "current");
+        assert_eq!(inputs.find_value("result"), Some(&serde_json::json!("ok")));
+        assert_eq!(inputs.find_value("value"), Some(&serde_json::json!(42)));
+        assert!(inputs.find_value("missing").is_none());
+
+        let declared: HashSet<String> = ["stage2".to_string()].into();
+        let strict = StageInputs::new(outputs, declared, "current", true);
+        assert_eq!(strict.find_value("result"), Some(&serde_json::json!("later")));
+    }
+
+    #[test]
+    fn test_shared_outputs_are_not_copied() {
+        let shared: HashMap<String, Arc<HashMap<String, serde_json::Value>>> = sample_outputs()
+            .into_iter()
+            .map(|(stage, output)| (stage, Arc::new(output)))
+            .collect();
+        let declared: HashSet<String> = ["stage1".to_string()].into();
+        let inputs = StageInputs::from_shared(shared.clone(), declared.clone(), "current", true);
+        assert!(std::ptr::eq(inputs.get("stage1").unwrap().unwrap(), shared["stage1"].as_ref()));
+
+        let keys = HashMap::from([("stage1".to_string(), HashSet::new())]);
+        let restricted = StageInputs::from_shared(shared.clone(), declared, "current", true).with_declared_keys(keys);
+        assert!(restricted.get("stage1").unwrap().unwrap().is_empty());
+        assert_eq!(shared["stage1"].len(), 1);
+    }
+
+    #[test]
+    fn test_strict_hides_undeclared_and_restricts_keys() {
+        let mut outputs = sample_outputs();
+        outputs
+            .get_mut("stage1")
+            .unwrap()
+            .insert("secret".to_string(), serde_json::json!("hidden"));
+        let declared: HashSet<String> = ["stage1".to_string()].into();
+        let keys = HashMap::from([("stage1".to_string(), HashSet::from(["result".to_string()]))]);
+        let inputs = StageInputs::new(outputs, declared, "current", true).with_declared_keys(keys);
+
+        assert_eq!(inputs.stages(), vec!["stage1"]);
+        assert!(!inputs.contains("stage2"));
+        assert_eq!(inputs.to_flat_dict().keys().collect::<Vec<_>>(), vec!["stage1.result"]);
+
+        assert_eq!(inputs.get_value("stage1", "result").unwrap(), Some(&serde_json::json!("ok")));
+        let err = inputs.get_value("stage1", "secret").unwrap_err();
+        assert_eq!(err.key, "stage1.secret");
+        assert_eq!(inputs.get("stage1").unwrap().unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,291 @@
+//! Leak detection for contexts and cleanup callbacks.
+//!
+//! A [`LeakDetector`] is attached explicitly to a [`PipelineContext`] or a
+//! [`CleanupRegistry`]; untracked contexts pay nothing. Stage contexts and
+//! forked subpipeline contexts inherit tracking from their pipeline context.
+//!
+//! [`PipelineContext`]: super::PipelineContext
+//! [`CleanupRegistry`]: crate::cancellation::CleanupRegistry
+
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+/// The kind of a tracked context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextKind {
+    /// A [`PipelineContext`](super::PipelineContext).
+    Pipeline,
+    /// A [`StageContext`](super::StageContext).
+    Stage,
+}
+
+impl fmt::Display for ContextKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pipeline => write!(f, "pipeline"),
+            Self::Stage => write!(f, "stage"),
+        }
+    }
+}
+
+/// A context that was still alive when the report was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackedContext {
+    /// The context kind.
+    pub kind: ContextKind,
+    /// Run ID for pipeline contexts, stage name for stage contexts.
+    pub label: String,
+}
+
+/// Outstanding contexts and cleanups observed by a [`LeakDetector`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Contexts that have not been dropped, in creation order.
+    pub live_contexts: Vec<TrackedContext>,
+    /// Cleanups still registered on a live registry.
+    pub pending_cleanups: Vec<String>,
+    /// Cleanups whose registry was dropped before they ran.
+    pub abandoned_cleanups: Vec<String>,
+}
+
+impl LeakReport {
+    /// Returns true if nothing leaked.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.live_contexts.is_empty()
+            && self.pending_cleanups.is_empty()
+            && self.abandoned_cleanups.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no leaks");
+        }
+        let contexts: Vec<String> = self
+            .live_contexts
+            .iter()
+            .map(|c| format!("{} '{}'", c.kind, c.label))
+            .collect();
+        write!(
+            f,
+            "{} live context(s) [{}], {} pending cleanup(s) [{}], {} abandoned cleanup(s) [{}]",
+            self.live_contexts.len(),
+            contexts.join(", "),
+            self.pending_cleanups.len(),
+            self.pending_cleanups.join(", "),
+            self.abandoned_cleanups.len(),
+            self.abandoned_cleanups.join(", "),
+        )
+    }
+}
+
+/// Tracks live contexts and unrun cleanups, reporting them when dropped.
+///
+/// Dropping the detector logs a warning if anything is outstanding, or
+/// panics when built with [`LeakDetector::panic_on_drop`], which makes a
+/// leak fail the test that owns the detector.
+#[derive(Debug)]
+pub struct LeakDetector {
+    tracker: Arc<LeakTracker>,
+    panic_on_drop: bool,
+}
+
+impl Default for LeakDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeakDetector {
+    /// Creates a detector that warns on drop.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tracker: Arc::new(LeakTracker::default()),
+            panic_on_drop: false,
+        }
+    }
+
+    /// Panics instead of warning when leaks remain at drop.
+    #[must_use]
+    pub fn panic_on_drop(mut self) -> Self {
+        self.panic_on_drop = true;
+        self
+    }
+
+    /// Returns the current leak report.
+    #[must_use]
+    pub fn report(&self) -> LeakReport {
+        self.tracker.report()
+    }
+
+    /// Panics if anything is outstanding.
+    pub fn assert_no_leaks(&self) {
+        let report = self.report();
+        assert!(report.is_clean(), "Leak detected: {report}");
+    }
+
+    pub(crate) fn tracker(&self) -> Arc<LeakTracker> {
+        self.tracker.clone()
+    }
+}
+
+impl Drop for LeakDetector {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        let report = self.report();
+        if report.is_clean() {
+            return;
+        }
+        assert!(!self.panic_on_drop, "Leak detected: {report}");
+        warn!("Leak detected: {}", report);
+    }
+}
+
+/// Shared bookkeeping behind a [`LeakDetector`].
+#[derive(Debug, Default)]
+pub(crate) struct LeakTracker {
+    next_id: AtomicU64,
+    contexts: Mutex<BTreeMap<u64, TrackedContext>>,
+    cleanups: Mutex<BTreeMap<u64, String>>,
+    abandoned: Mutex<Vec<String>>,
+}
+
+impl LeakTracker {
+    /// Starts tracking a context until the returned token is dropped.
+    pub(crate) fn track_context(
+        self: &Arc<Self>,
+        kind: ContextKind,
+        label: impl Into<String>,
+    ) -> ContextToken {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.contexts.lock().insert(
+            id,
+            TrackedContext {
+                kind,
+                label: label.into(),
+            },
+        );
+        ContextToken {
+            tracker: self.clone(),
+            id,
+        }
+    }
+
+    /// Starts tracking a cleanup until the returned token is settled or dropped.
+    pub(crate) fn track_cleanup(self: &Arc<Self>, name: impl Into<String>) -> CleanupToken {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.cleanups.lock().insert(id, name.into());
+        CleanupToken {
+            tracker: self.clone(),
+            id,
+            settled: false,
+        }
+    }
+
+    fn report(&self) -> LeakReport {
+        LeakReport {
+            live_contexts: self.contexts.lock().values().cloned().collect(),
+            pending_cleanups: self.cleanups.lock().values().cloned().collect(),
+            abandoned_cleanups: self.abandoned.lock().clone(),
+        }
+    }
+}
+
+/// Keeps a context registered with its tracker while alive.
+#[derive(Debug)]
+pub(crate) struct ContextToken {
+    tracker: Arc<LeakTracker>,
+    id: u64,
+}
+
+impl ContextToken {
+    /// Returns the tracker this token reports to.
+    pub(crate) fn tracker(&self) -> &Arc<LeakTracker> {
+        &self.tracker
+    }
+}
+
+impl Drop for ContextToken {
+    fn drop(&mut self) {
+        self.tracker.contexts.lock().remove(&self.id);
+    }
+}
+
+/// Keeps a cleanup registered with its tracker until it runs or is discarded.
+///
+/// Dropping an unsettled token records the cleanup as abandoned.
+#[derive(Debug)]
+pub(crate) struct CleanupToken {
+    tracker: Arc<LeakTracker>,
+    id: u64,
+    settled: bool,
+}
+
+impl CleanupToken {
+    /// Marks the cleanup as run or deliberately discarded.
+    pub(crate) fn settle(mut self) {
+        self.settled = true;
+    }
+}
+
+impl Drop for CleanupToken {
+    fn drop(&mut self) {
+        let name = self.tracker.cleanups.lock().remove(&self.id);
+        if let (false, Some(name)) = (self.settled, name) {
+            self.tracker.abandoned.lock().push(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_token_released_on_drop() {
+        let detector = LeakDetector::new().panic_on_drop();
+        let token = detector.tracker().track_context(ContextKind::Stage, "fetch");
+
+        assert_eq!(
+            detector.report().live_contexts,
+            vec![TrackedContext {
+                kind: ContextKind::Stage,
+                label: "fetch".to_string(),
+            }]
+        );
+        drop(token);
+        detector.assert_no_leaks();
+    }
+
+    #[test]
+    fn test_unsettled_cleanup_is_abandoned() {
+        let detector = LeakDetector::new();
+        let tracker = detector.tracker();
+        tracker.track_cleanup("ran").settle();
+        let pending = tracker.track_cleanup("close_socket");
+        assert_eq!(detector.report().pending_cleanups, vec!["close_socket"]);
+
+        drop(pending);
+        let report = detector.report();
+        assert!(report.pending_cleanups.is_empty());
+        assert_eq!(report.abandoned_cleanups, vec!["close_socket"]);
+        assert!(report.to_string().contains("1 abandoned cleanup(s) [close_socket]"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Leak detected")]
+    fn test_panic_on_drop() {
+        let detector = LeakDetector::new().panic_on_drop();
+        let _leaked = detector.tracker().track_context(ContextKind::Pipeline, "run");
+        drop(detector);
+    }
+}
@@ -4,17 +4,55 @@
 //! - Immutable context snapshots for capturing state
 //! - Mutable execution contexts for stage execution
 //! - Thread-safe data bags for storing outputs
+//! - Leak detection for contexts and cleanups in tests
+//! - Sandbox policies auditing environment and filesystem access
+//! - Structural fingerprints of snapshots for change detection
+//! - Versioned snapshot persistence and rehydration
+//! - Execution profiles, including a low-latency fast path
+//! - A TTL cache sharing enrichment results across runs
+//! - Token-budgeted assembly of snapshots into prompt messages
 
 mod bags;
+mod budget;
+mod cache;
 #[cfg(test)]
 mod context_tests;
+mod consistency;
 mod execution;
+mod fingerprint;
 mod identity;
 mod inputs;
+pub(crate) mod leak;
+mod persistence;
+mod profile;
+#[cfg(feature = "s3")]
+mod s3;
+mod sandbox;
 mod snapshot;
 
-pub use bags::{ContextBag, OutputBag};
+pub use bags::{ContextBag, ContextNamespace, OutputBag, NAMESPACE_SEPARATOR};
+pub use budget::{
+    AssembledContext, CharTokenizer, ContextBudget, ContextSection, SectionReport, Tokenizer,
+    TruncationReport,
+};
+pub use cache::{
+    EnrichmentCache, EnrichmentCacheStats, EnrichmentKey, DEFAULT_ENRICHMENT_TTL,
+};
+pub use consistency::{ContextAccess, ContextConsistency};
 pub use execution::{DictContextAdapter, ExecutionContext, PipelineContext, StageContext};
+pub use fingerprint::{Fingerprint, SnapshotFingerprint};
 pub use identity::RunIdentity;
 pub use inputs::StageInputs;
-pub use snapshot::{ContextSnapshot, Conversation, Enrichments, ExtensionBundle};
+pub use leak::{ContextKind, LeakDetector, LeakReport, TrackedContext};
+pub use sandbox::{
+    normalize_path, resolve_path, AccessKind, AccessRecord, AccessReport, SandboxMode, SandboxPolicy,
+};
+pub use persistence::{
+    FileSnapshotStore, InMemoryObjectStore, ObjectSnapshotStore, ObjectStore, SnapshotStore,
+};
+pub use profile::{ExecutionProfile, FAST_PATH_SUPPRESSED_EVENTS};
+#[cfg(feature = "s3")]
+pub use s3::S3ObjectStore;
+pub use snapshot::{
+    ContextSnapshot, Conversation, Enrichments, ExtensionBundle, Message, SNAPSHOT_SCHEMA_VERSION,
+};
@@ -0,0 +1,393 @@
+//! Persistence of context snapshots between requests.
+//!
+//! Snapshots are stored in the versioned envelope produced by
+//! [`ContextSnapshot::to_bytes`]. [`FileSnapshotStore`] writes one file per
+//! key; [`ObjectSnapshotStore`] adapts any blob store with S3-style
+//! put/get/delete semantics through the [`ObjectStore`] trait. Both can
+//! compress snapshots with a [`Codec`]; loading detects the codec, so
+//! existing snapshots stay readable after one is configured. Both compare
+//! [fingerprints](ContextSnapshot::fingerprint) to skip serializing and
+//! writing a snapshot identical to the one they last saved under a key.
+
+use super::fingerprint::StructHasher;
+use super::{ContextSnapshot, Fingerprint, PipelineContext};
+use crate::compression::{Codec, IdentityCodec};
+use crate::errors::StageflowError;
+use crate::utils::encode_path_component;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Storage for context snapshots keyed by caller-chosen ids.
+#[async_trait]
+pub trait SnapshotStore: Send + Sync {
+    /// Saves a snapshot, replacing any previous one under `key`.
+    async fn save(&self, key: &str, snapshot: &ContextSnapshot) -> Result<(), StageflowError>;
+
+    /// Loads the snapshot stored under `key`.
+    async fn load(&self, key: &str) -> Result<Option<ContextSnapshot>, StageflowError>;
+
+    /// Deletes the snapshot stored under `key`.
+    async fn delete(&self, key: &str) -> Result<(), StageflowError>;
+
+    /// Loads a snapshot and rehydrates a pipeline context from it.
+    async fn load_context(&self, key: &str) -> Result<Option<PipelineContext>, StageflowError> {
+        Ok(self
+            .load(key)
+            .await?
+            .map(|snapshot| PipelineContext::from_snapshot(&snapshot)))
+    }
+}
+
+/// Fingerprints of the snapshots a store last saved, keyed by store key.
+#[derive(Debug, Default)]
+struct SavedFingerprints(Mutex<HashMap<String, Fingerprint>>);
+
+impl SavedFingerprints {
+    /// Returns the snapshot's fingerprint, or `None` if the same snapshot
+    /// was the last one saved under `key`.
+    fn changed(&self, key: &str, snapshot: &ContextSnapshot) -> Option<Fingerprint> {
+        // Section fingerprints leave out the run identity, which a store must keep
+        let mut hasher = StructHasher::new();
+        hasher.write_u64(snapshot.fingerprint().combined().as_u64());
+        let identity = &snapshot.run_id;
+        for id in [
+            identity.pipeline_run_id,
+            identity.request_id,
+            identity.session_id,
+            identity.user_id,
+            identity.org_id,
+            identity.interaction_id,
+        ] {
+            hasher.write_bytes(id.as_ref().map_or(&[0][..], |id| id.as_bytes()));
+        }
+        let fingerprint = Fingerprint::from_raw(hasher.finish());
+        (self.0.lock().get(key) != Some(&fingerprint)).then_some(fingerprint)
+    }
+
+    fn record(&self, key: &str, fingerprint: Fingerprint) {
+        self.0.lock().insert(key.to_string(), fingerprint);
+    }
+
+    fn forget(&self, key: &str) {
+        self.0.lock().remove(key);
+    }
+}
+
+/// File-backed snapshot store writing one file per key.
+///
+/// Clones share the record of what was last saved, which assumes the files
+/// are only changed through this store.
+#[derive(Debug, Clone)]
+pub struct FileSnapshotStore {
+    directory: PathBuf,
+    codec: Arc<dyn Codec>,
+    saved: Arc<SavedFingerprints>,
+}
+
+impl FileSnapshotStore {
+    /// Creates a store rooted at the given directory.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            codec: Arc::new(IdentityCodec),
+            saved: Arc::default(),
+        }
+    }
+
+    /// Compresses saved snapshots with `codec`.
+    #[must_use]
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Returns the snapshot directory.
+    #[must_use]
+    pub fn directory(&self) -> &std::path::Path {
+        &self.directory
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.snapshot.json", encode_path_component(key)))
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for FileSnapshotStore {
+    async fn save(&self, key: &str, snapshot: &ContextSnapshot) -> Result<(), StageflowError> {
+        let Some(fingerprint) = self.saved.changed(key, snapshot) else {
+            return Ok(());
+        };
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let bytes = snapshot.to_bytes_with(self.codec.as_ref())?;
+
+        // Write then rename so readers never see a partial snapshot.
+        let path = self.path_for(key);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        self.saved.record(key, fingerprint);
+        Ok(())
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<ContextSnapshot>, StageflowError> {
+        match tokio::fs::read(self.path_for(key)).await {
+            Ok(bytes) => Ok(Some(ContextSnapshot::from_bytes(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StageflowError> {
+        self.saved.forget(key);
+        match tokio::fs::remove_file(self.path_for(key)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A blob store with S3-style object semantics.
+///
+/// Implement this for S3, GCS or any other object storage client to persist
+/// snapshots there through [`ObjectSnapshotStore`]. The `s3` feature adds
+/// `S3ObjectStore`, built on the AWS SDK.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Writes an object, replacing any existing one.
+    async fn put_object(&self, key: &str, bytes: Vec<u8>) -> Result<(), StageflowError>;
+
+    /// Reads an object, or `None` if it does not exist.
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, StageflowError>;
+
+    /// Deletes an object; deleting a missing object is not an error.
+    async fn delete_object(&self, key: &str) -> Result<(), StageflowError>;
+
+    /// Lists the keys starting with `prefix`.
+    ///
+    /// Stores that cannot list return an error, which is the default.
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<String>, StageflowError> {
+        Err(StageflowError::Internal(format!(
+            "Object store cannot list objects under '{prefix}'"
+        )))
+    }
+}
+
+/// In-memory [`ObjectStore`], useful for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryObjectStore {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemoryObjectStore {
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the stored object keys.
+    #[must_use]
+    pub fn keys(&self) -> Vec<String> {
+        self.objects.lock().keys().cloned().collect()
+    }
+}
+
+#[async_trait]
+impl ObjectStore for InMemoryObjectStore {
+    async fn put_object(&self, key: &str, bytes: Vec<u8>) -> Result<(), StageflowError> {
+        self.objects.lock().insert(key.to_string(), bytes);
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, StageflowError> {
+        Ok(self.objects.lock().get(key).cloned())
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), StageflowError> {
+        self.objects.lock().remove(key);
+        Ok(())
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<String>, StageflowError> {
+        let mut keys: Vec<String> = self
+            .objects
+            .lock()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Snapshot store backed by an [`ObjectStore`].
+pub struct ObjectSnapshotStore {
+    objects: Arc<dyn ObjectStore>,
+    prefix: String,
+    codec: Arc<dyn Codec>,
+    saved: SavedFingerprints,
+}
+
+impl ObjectSnapshotStore {
+    /// Creates a store writing objects under `snapshots/`.
+    #[must_use]
+    pub fn new(objects: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            objects,
+            prefix: "snapshots/".to_string(),
+            codec: Arc::new(IdentityCodec),
+            saved: SavedFingerprints::default(),
+        }
+    }
+
+    /// Compresses saved snapshots with `codec`.
+    #[must_use]
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Sets the object key prefix.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        format!("{}{key}.json", self.prefix)
+    }
+}
+
+impl std::fmt::Debug for ObjectSnapshotStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectSnapshotStore")
+            .field("prefix", &self.prefix)
+            .field("codec", &self.codec.name())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for ObjectSnapshotStore {
+    async fn save(&self, key: &str, snapshot: &ContextSnapshot) -> Result<(), StageflowError> {
+        let Some(fingerprint) = self.saved.changed(key, snapshot) else {
+            return Ok(());
+        };
+        self.objects
+            .put_object(&self.object_key(key), snapshot.to_bytes_with(self.codec.as_ref())?)
+            .await?;
+        self.saved.record(key, fingerprint);
+        Ok(())
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<ContextSnapshot>, StageflowError> {
+        match self.objects.get_object(&self.object_key(key)).await? {
+            Some(bytes) => Ok(Some(ContextSnapshot::from_bytes(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StageflowError> {
+        self.saved.forget(key);
+        self.objects.delete_object(&self.object_key(key)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::snapshot::Message;
+    use crate::context::{Conversation, Enrichments};
+
+    fn sample() -> ContextSnapshot {
+        ContextSnapshot::new()
+            .with_conversation(Conversation::new().add_message(Message::user("hi")))
+            .with_enrichments(Enrichments::new().with_custom("tier", serde_json::json!("gold")))
+            .with_input_text("hi")
+    }
+
+    #[tokio::test]
+    async fn test_file_store_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("stageflow-snapshots-{}", uuid::Uuid::new_v4()));
+        let store = FileSnapshotStore::new(&dir);
+        let snapshot = sample();
+
+        store.save("session/1", &snapshot).await.unwrap();
+        let loaded = FileSnapshotStore::new(&dir).load("session/1").await.unwrap().unwrap();
+        assert_eq!(loaded.fingerprint(), snapshot.fingerprint());
+        assert_eq!(loaded.run_id.pipeline_run_id, snapshot.run_id.pipeline_run_id);
+
+        let ctx = store.load_context("session/1").await.unwrap().unwrap();
+        assert_eq!(ctx.run_id().pipeline_run_id, snapshot.run_id.pipeline_run_id);
+
+        store.delete("session/1").await.unwrap();
+        assert!(store.load("session/1").await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_object_store_uses_prefixed_keys() {
+        let objects = Arc::new(InMemoryObjectStore::new());
+        let store = ObjectSnapshotStore::new(objects.clone()).with_prefix("tenant-a/");
+
+        store.save("abc", &sample()).await.unwrap();
+        assert_eq!(objects.keys(), vec!["tenant-a/abc.json".to_string()]);
+        assert_eq!(store.load("abc").await.unwrap().unwrap().input_text.as_deref(), Some("hi"));
+        assert!(store.load("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_snapshots_are_not_rewritten() {
+        let objects = Arc::new(InMemoryObjectStore::new());
+        let store = ObjectSnapshotStore::new(objects.clone());
+        let snapshot = sample();
+        store.save("abc", &snapshot).await.unwrap();
+
+        // Removed behind the store's back, so only a real write brings it back
+        objects.delete_object("snapshots/abc.json").await.unwrap();
+        store.save("abc", &snapshot).await.unwrap();
+        assert!(objects.keys().is_empty());
+
+        let mut edited = snapshot.clone();
+        edited.enrichments.custom.insert("tier".to_string(), serde_json::json!("silver"));
+        store.save("abc", &edited).await.unwrap();
+        assert_eq!(store.load("abc").await.unwrap().unwrap().fingerprint(), edited.fingerprint());
+
+        // A new run identity is kept even when the content is the same
+        let rerun = edited.clone().with_run_id(crate::context::RunIdentity::new());
+        store.save("abc", &rerun).await.unwrap();
+        let loaded = store.load("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.run_id.pipeline_run_id, rerun.run_id.pipeline_run_id);
+
+        store.delete("abc").await.unwrap();
+        store.save("abc", &rerun).await.unwrap();
+        assert!(store.load("abc").await.unwrap().is_some());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn test_compressed_snapshots_stay_readable() {
+        let objects = Arc::new(InMemoryObjectStore::new());
+        let plain = ObjectSnapshotStore::new(objects.clone());
+        plain.save("old", &sample()).await.unwrap();
+
+        let store = ObjectSnapshotStore::new(objects.clone())
+            .with_codec(Arc::new(crate::compression::ZstdCodec::new()));
+        store.save("new", &sample()).await.unwrap();
+        let raw = objects.get_object("snapshots/new.json").await.unwrap().unwrap();
+        assert_eq!(crate::compression::detect_codec(&raw), Some("zstd"));
+
+        for key in ["old", "new"] {
+            assert_eq!(store.load(key).await.unwrap().unwrap().fingerprint(), sample().fingerprint());
+        }
+        assert!(plain.load("new").await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,77 @@
+//! Execution profiles trading observability for latency.
+
+use serde::{Deserialize, Serialize};
+
+/// Events dropped under [`ExecutionProfile::FastPath`].
+///
+/// These report routine progress; failures, cancellations, timeouts and
+/// policy violations are always emitted.
+pub const FAST_PATH_SUPPRESSED_EVENTS: &[&str] = &[
+    "stage.started",
+    "stage.completed",
+    "stage.skipped",
+    "stage.awaiting_ack",
+    "stage.acknowledged",
+    "stage.ack_restored",
+    "guard_retry.attempt",
+    "guard_retry.scheduled",
+];
+
+/// How much bookkeeping the engine does around each stage.
+///
+/// Chosen per pipeline on [`PipelineContext`](super::PipelineContext).
+/// For small pipelines, event emission and JSON handling can be a sizeable
+/// share of end-to-end latency; `FastPath` keeps only what is needed to
+/// diagnose failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionProfile {
+    /// Emit every event with full correlation metadata.
+    #[default]
+    Standard,
+    /// Skip routine progress events, tag events with only the run ID, and
+    /// leave diagnostic counters out of stage output metadata.
+    FastPath,
+}
+
+impl ExecutionProfile {
+    /// Returns the profile name.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::FastPath => "fast_path",
+        }
+    }
+
+    /// Returns true for the fast path.
+    #[must_use]
+    pub fn is_fast_path(&self) -> bool {
+        matches!(self, Self::FastPath)
+    }
+
+    /// Returns true if events of this type should be emitted.
+    #[must_use]
+    pub fn emits(&self, event_type: &str) -> bool {
+        !self.is_fast_path() || !FAST_PATH_SUPPRESSED_EVENTS.contains(&event_type)
+    }
+}
+
+impl std::fmt::Display for ExecutionProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_path_keeps_failure_events() {
+        assert!(ExecutionProfile::Standard.emits("stage.started"));
+        assert!(!ExecutionProfile::FastPath.emits("stage.started"));
+        assert!(ExecutionProfile::FastPath.emits("stage.failed"));
+        assert!(ExecutionProfile::FastPath.emits("pipeline_cancelled"));
+    }
+}
@@ -0,0 +1,315 @@
+//! [`ObjectStore`] over Amazon S3 and S3-compatible services.
+
+use super::ObjectStore;
+use crate::errors::StageflowError;
+use async_trait::async_trait;
+use aws_sdk_s3::config::{BehaviorVersion, RequestChecksumCalculation};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+
+/// [`ObjectStore`] writing to one bucket through the AWS SDK.
+///
+/// Keys are written under an optional prefix, so several stores can share
+/// a bucket. Snapshots and artifacts reach S3 through
+/// [`ObjectSnapshotStore`](super::ObjectSnapshotStore) and
+/// [`ObjectArtifactStore`](crate::artifacts::ObjectArtifactStore); give the
+/// latter [`uri_prefix`](Self::uri_prefix) so its URIs name the objects.
+#[derive(Debug, Clone)]
+pub struct S3ObjectStore {
+    client: Client,
+    bucket: String,
+    prefix: String,
+}
+
+impl S3ObjectStore {
+    /// Creates a store using an existing client.
+    #[must_use]
+    pub fn new(client: Client, bucket: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+            prefix: String::new(),
+        }
+    }
+
+    /// Creates a store with credentials and region from the environment.
+    ///
+    /// With an `endpoint`, such as a `MinIO` or R2 URL, requests go there
+    /// with path-style addressing and only the checksums S3 requires,
+    /// which S3-compatible services support more widely.
+    pub async fn connect(bucket: impl Into<String>, endpoint: Option<&str>) -> Self {
+        let shared = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        let mut config = aws_sdk_s3::config::Builder::from(&shared);
+        if let Some(endpoint) = endpoint {
+            config = config
+                .endpoint_url(endpoint)
+                .force_path_style(true)
+                .request_checksum_calculation(RequestChecksumCalculation::WhenRequired);
+        }
+        Self::new(Client::from_conf(config.build()), bucket)
+    }
+
+    /// Sets the prefix prepended to every key, e.g. `stageflow/`.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Returns the bucket name.
+    #[must_use]
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Returns the key prefix.
+    #[must_use]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns what `s3://` URIs of this store's objects start with before
+    /// the key.
+    #[must_use]
+    pub fn uri_prefix(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    fn error(&self, action: &str, key: &str, err: &impl std::error::Error) -> StageflowError {
+        StageflowError::Internal(format!(
+            "S3 {action} of '{key}' in bucket '{}' failed: {}",
+            self.bucket,
+            DisplayErrorContext(err)
+        ))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for S3ObjectStore {
+    async fn put_object(&self, key: &str, bytes: Vec<u8>) -> Result<(), StageflowError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .body(ByteStream::from(bytes))
+            .send()
+            .await
+            .map_err(|e| self.error("put", key, &e))?;
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, StageflowError> {
+        let response = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .send()
+            .await;
+        let object = match response {
+            Ok(object) => object,
+            // Some S3-compatible services answer 404 without a NoSuchKey code
+            Err(e) if e.raw_response().is_some_and(|r| r.status().as_u16() == 404) => return Ok(None),
+            Err(e) => return Err(self.error("get", key, &e)),
+        };
+        let body = object.body.collect().await.map_err(|e| self.error("get", key, &e))?;
+        Ok(Some(body.to_vec()))
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), StageflowError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .send()
+            .await
+            .map_err(|e| self.error("delete", key, &e))?;
+        Ok(())
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<String>, StageflowError> {
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(self.object_key(prefix))
+            .into_paginator()
+            .send();
+        let mut keys = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| self.error("list", prefix, &e))?;
+            keys.extend(
+                page.contents()
+                    .iter()
+                    .filter_map(|object| object.key()?.strip_prefix(self.prefix.as_str()))
+                    .map(ToString::to_string),
+            );
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::{ArtifactStore, ObjectArtifactStore};
+    use crate::core::StageArtifact;
+    use parking_lot::Mutex;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+    /// Serves the path-style S3 requests the store makes, one per connection.
+    async fn fake_s3() -> (String, Objects) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let objects = Objects::default();
+        let served = objects.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, served.clone()));
+            }
+        });
+        (endpoint, objects)
+    }
+
+    async fn serve(mut stream: TcpStream, objects: Objects) {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        let header_end = loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..n]);
+            if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+        let length: usize = head
+            .lines()
+            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+            .unwrap_or(0);
+        while request.len() < header_end + length {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        let body = request[header_end..header_end + length].to_vec();
+
+        let mut request_line = head.lines().next().unwrap().split(' ');
+        let (method, target) = (request_line.next().unwrap(), request_line.next().unwrap());
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let key = decode(path.trim_start_matches('/').split_once('/').map_or("", |(_, key)| key));
+        let (status, response) = match method {
+            "PUT" => {
+                objects.lock().insert(key, body);
+                ("200 OK", Vec::new())
+            }
+            "DELETE" => {
+                objects.lock().remove(&key);
+                ("204 No Content", Vec::new())
+            }
+            "GET" if query.contains("list-type=2") => {
+                let prefix = query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("prefix="))
+                    .map(decode)
+                    .unwrap_or_default();
+                let mut xml = String::from("<ListBucketResult><IsTruncated>false</IsTruncated>");
+                for key in objects.lock().keys().filter(|key| key.starts_with(&prefix)) {
+                    xml.push_str("<Contents><Key>");
+                    xml.push_str(key);
+                    xml.push_str("</Key></Contents>");
+                }
+                xml.push_str("</ListBucketResult>");
+                ("200 OK", xml.into_bytes())
+            }
+            _ => match objects.lock().get(&key) {
+                Some(bytes) => ("200 OK", bytes.clone()),
+                None => ("404 Not Found", b"<Error><Code>NoSuchKey</Code></Error>".to_vec()),
+            },
+        };
+        let head = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.len()
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(&response).await.unwrap();
+    }
+
+    fn decode(text: &str) -> String {
+        let bytes = text.as_bytes();
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                out.push(u8::from_str_radix(&text[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            } else {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn client(endpoint: &str) -> Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new("us-east-1"))
+            .credentials_provider(aws_sdk_s3::config::Credentials::new("key", "secret", None, None, "test"))
+            .endpoint_url(endpoint)
+            .force_path_style(true)
+            .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
+            .build();
+        Client::from_conf(config)
+    }
+
+    #[tokio::test]
+    async fn test_objects_round_trip_under_the_prefix() {
+        let (endpoint, objects) = fake_s3().await;
+        let store = S3ObjectStore::new(client(&endpoint), "bucket").with_prefix("stageflow/");
+
+        store.put_object("snapshots/a.json", b"{}".to_vec()).await.unwrap();
+        store.put_object("snapshots/b.json", b"[]".to_vec()).await.unwrap();
+        assert_eq!(
+            objects.lock().keys().cloned().collect::<Vec<_>>(),
+            vec!["stageflow/snapshots/a.json", "stageflow/snapshots/b.json"]
+        );
+        assert_eq!(store.get_object("snapshots/a.json").await.unwrap(), Some(b"{}".to_vec()));
+        assert_eq!(store.get_object("snapshots/missing.json").await.unwrap(), None);
+        assert_eq!(
+            store.list_objects("snapshots/").await.unwrap(),
+            vec!["snapshots/a.json", "snapshots/b.json"]
+        );
+
+        store.delete_object("snapshots/a.json").await.unwrap();
+        store.delete_object("snapshots/a.json").await.unwrap();
+        assert_eq!(store.list_objects("").await.unwrap(), vec!["snapshots/b.json"]);
+    }
+
+    #[tokio::test]
+    async fn test_artifacts_get_s3_uris() {
+        let (endpoint, _objects) = fake_s3().await;
+        let s3 = S3ObjectStore::new(client(&endpoint), "bucket").with_prefix("runs/");
+        let artifacts = ObjectArtifactStore::new(Arc::new(s3.clone())).with_uri_prefix(s3.uri_prefix());
+
+        let stored = artifacts
+            .put("run-1", "render", &StageArtifact::new("image", "chart", "chart", serde_json::json!([1, 2])))
+            .await
+            .unwrap();
+        assert_eq!(stored.uri, "s3://bucket/runs/artifacts/run-1/render/chart.json");
+        let loaded = artifacts.get(&stored.uri).await.unwrap().unwrap();
+        assert_eq!(loaded.data, serde_json::json!([1, 2]));
+        assert_eq!(artifacts.list("run-1", None).await.unwrap().len(), 1);
+    }
+}
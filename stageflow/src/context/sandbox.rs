@@ -0,0 +1,331 @@
+//! Environment and filesystem access policy for stages.
+//!
+//! Stages that read environment variables or touch files through the
+//! [`StageContext`](super::StageContext) helpers (`env_var`, `read_file`,
+//! `write_file`) are checked against the run's [`SandboxPolicy`]. Every
+//! access is recorded in an [`AccessReport`]; in strict mode accesses
+//! outside the policy are denied. Code that bypasses the helpers is not
+//! observed, so this is an audit aid rather than an isolation boundary.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Whether policy violations are only recorded or also denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxMode {
+    /// Record violations but let the access proceed.
+    #[default]
+    Audit,
+    /// Record violations and deny the access.
+    Strict,
+}
+
+/// The kind of access a stage performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessKind {
+    /// Reading an environment variable.
+    EnvVar,
+    /// Reading a file.
+    FileRead,
+    /// Writing a file.
+    FileWrite,
+}
+
+impl AccessKind {
+    /// Returns the kind as a lowercase string.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::EnvVar => "env_var",
+            Self::FileRead => "file_read",
+            Self::FileWrite => "file_write",
+        }
+    }
+}
+
+/// Which environment variables and paths stages may access.
+///
+/// Environment patterns match exactly, or by prefix when they end in `*`.
+/// Paths allow everything beneath them; write access implies read access.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxPolicy {
+    mode: SandboxMode,
+    env: Vec<String>,
+    read_paths: Vec<PathBuf>,
+    write_paths: Vec<PathBuf>,
+}
+
+impl SandboxPolicy {
+    /// Creates a policy that records accesses without denying any.
+    #[must_use]
+    pub fn audit() -> Self {
+        Self::default()
+    }
+
+    /// Creates a policy that denies accesses not explicitly allowed.
+    #[must_use]
+    pub fn strict() -> Self {
+        Self {
+            mode: SandboxMode::Strict,
+            ..Self::default()
+        }
+    }
+
+    /// Allows environment variables matching a pattern, e.g. `APP_*`.
+    #[must_use]
+    pub fn allow_env(mut self, pattern: impl Into<String>) -> Self {
+        self.env.push(pattern.into());
+        self
+    }
+
+    /// Allows reading files under a path.
+    #[must_use]
+    pub fn allow_read(mut self, path: impl AsRef<Path>) -> Self {
+        self.read_paths.push(resolve_path(path.as_ref()));
+        self
+    }
+
+    /// Allows reading and writing files under a path.
+    #[must_use]
+    pub fn allow_write(mut self, path: impl AsRef<Path>) -> Self {
+        self.write_paths.push(resolve_path(path.as_ref()));
+        self
+    }
+
+    /// Returns the mode.
+    #[must_use]
+    pub fn mode(&self) -> SandboxMode {
+        self.mode
+    }
+
+    /// Returns true if the policy allows the access.
+    ///
+    /// File targets are expected to be resolved with [`resolve_path`].
+    #[must_use]
+    pub fn allows(&self, kind: AccessKind, target: &str) -> bool {
+        match kind {
+            AccessKind::EnvVar => self.env.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => target.starts_with(prefix),
+                None => pattern == target,
+            }),
+            AccessKind::FileRead => {
+                let path = Path::new(target);
+                self.read_paths
+                    .iter()
+                    .chain(&self.write_paths)
+                    .any(|allowed| path.starts_with(allowed))
+            }
+            AccessKind::FileWrite => {
+                let path = Path::new(target);
+                self.write_paths.iter().any(|allowed| path.starts_with(allowed))
+            }
+        }
+    }
+}
+
+/// One access performed by a stage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRecord {
+    /// The stage that performed the access.
+    pub stage: String,
+    /// The kind of access.
+    pub kind: AccessKind,
+    /// The variable name or normalized path.
+    pub target: String,
+    /// Whether the policy allows the access.
+    pub allowed: bool,
+    /// Whether the access was denied (strict mode violations only).
+    pub denied: bool,
+}
+
+/// Every access recorded during a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessReport {
+    /// Records in the order they happened.
+    pub records: Vec<AccessRecord>,
+}
+
+impl AccessReport {
+    /// Returns the accesses the policy does not allow.
+    #[must_use]
+    pub fn violations(&self) -> Vec<&AccessRecord> {
+        self.records.iter().filter(|r| !r.allowed).collect()
+    }
+
+    /// Returns the accesses that were denied.
+    #[must_use]
+    pub fn denied(&self) -> Vec<&AccessRecord> {
+        self.records.iter().filter(|r| r.denied).collect()
+    }
+
+    /// Groups records by stage.
+    #[must_use]
+    pub fn by_stage(&self) -> HashMap<String, Vec<&AccessRecord>> {
+        let mut grouped: HashMap<String, Vec<&AccessRecord>> = HashMap::new();
+        for record in &self.records {
+            grouped.entry(record.stage.clone()).or_default().push(record);
+        }
+        grouped
+    }
+
+    /// Converts the report to a dictionary.
+    #[must_use]
+    pub fn to_dict(&self) -> serde_json::Value {
+        serde_json::json!({
+            "records": self.records,
+            "violations": self.violations().len(),
+            "denied": self.denied().len(),
+        })
+    }
+}
+
+/// A run's policy together with the accesses recorded against it.
+#[derive(Debug)]
+pub(crate) struct Sandbox {
+    policy: SandboxPolicy,
+    report: RwLock<AccessReport>,
+}
+
+impl Sandbox {
+    pub(crate) fn new(policy: SandboxPolicy) -> Self {
+        Self {
+            policy,
+            report: RwLock::new(AccessReport::default()),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+
+    /// Checks and records an access, returning it as recorded.
+    pub(crate) fn check(&self, stage: &str, kind: AccessKind, target: &str) -> AccessRecord {
+        let allowed = self.policy.allows(kind, target);
+        let record = AccessRecord {
+            stage: stage.to_string(),
+            kind,
+            target: target.to_string(),
+            allowed,
+            denied: !allowed && self.policy.mode == SandboxMode::Strict,
+        };
+        self.report.write().records.push(record.clone());
+        record
+    }
+
+    pub(crate) fn report(&self) -> AccessReport {
+        self.report.read().clone()
+    }
+}
+
+/// Makes a path absolute and resolves `.` and `..` without touching the
+/// filesystem, so `data/../secrets` cannot escape an allowed `data`.
+#[must_use]
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("/"))
+            .join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Normalizes a path with [`normalize_path`], then follows symlinks in its
+/// longest existing prefix, so a link inside an allowed directory cannot
+/// reach outside it. Components that do not exist yet, such as a file about
+/// to be written, are kept as they are.
+#[must_use]
+pub fn resolve_path(path: &Path) -> PathBuf {
+    let normalized = normalize_path(path);
+    let mut existing = normalized.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = std::fs::canonicalize(existing) {
+            return missing.iter().rev().fold(resolved, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_patterns() {
+        let policy = SandboxPolicy::strict().allow_env("APP_*").allow_env("HOME");
+
+        assert!(policy.allows(AccessKind::EnvVar, "APP_TOKEN"));
+        assert!(policy.allows(AccessKind::EnvVar, "HOME"));
+        assert!(!policy.allows(AccessKind::EnvVar, "HOMEBREW"));
+        assert!(!policy.allows(AccessKind::EnvVar, "AWS_SECRET_ACCESS_KEY"));
+    }
+
+    #[test]
+    fn test_path_rules() {
+        let policy = SandboxPolicy::strict()
+            .allow_read("/srv/data")
+            .allow_write("/tmp/out");
+        let target = |p: &str| resolve_path(Path::new(p)).to_string_lossy().into_owned();
+
+        assert!(policy.allows(AccessKind::FileRead, &target("/srv/data/a.csv")));
+        assert!(policy.allows(AccessKind::FileRead, &target("/tmp/out/report.json")));
+        assert!(!policy.allows(AccessKind::FileWrite, &target("/srv/data/a.csv")));
+        assert!(!policy.allows(AccessKind::FileRead, &target("/srv/data/../secrets")));
+        assert!(!policy.allows(AccessKind::FileRead, &target("/srv/database")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_follows_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        std::fs::create_dir(root.join("data")).unwrap();
+        std::fs::create_dir(root.join("secrets")).unwrap();
+        std::os::unix::fs::symlink(root.join("secrets"), root.join("data/link")).unwrap();
+
+        assert_eq!(resolve_path(&root.join("data/link/key")), root.join("secrets/key"));
+        assert_eq!(resolve_path(&root.join("data/new/file")), root.join("data/new/file"));
+        assert_eq!(resolve_path(&root.join("data/link/../x")), root.join("data/x"));
+    }
+
+    #[test]
+    fn test_sandbox_records_accesses() {
+        let audit = Sandbox::new(SandboxPolicy::audit());
+        let record = audit.check("fetch", AccessKind::EnvVar, "API_KEY");
+        assert!(!record.allowed);
+        assert!(!record.denied);
+
+        let strict = Sandbox::new(SandboxPolicy::strict().allow_env("API_KEY"));
+        assert!(!strict.check("fetch", AccessKind::EnvVar, "API_KEY").denied);
+        assert!(strict.check("fetch", AccessKind::EnvVar, "DB_URL").denied);
+
+        let report = strict.report();
+        assert_eq!(report.records.len(), 2);
+        assert_eq!(report.violations().len(), 1);
+        assert_eq!(report.by_stage()["fetch"].len(), 2);
+        assert_eq!(report.to_dict()["denied"], 1);
+    }
+}
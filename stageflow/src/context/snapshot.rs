@@ -1,10 +1,19 @@
 //! Immutable context snapshots for pipeline execution.
 
-use super::RunIdentity;
+use super::fingerprint::{map_hash, value_hash, StructHasher};
+use super::{Fingerprint, RunIdentity, SnapshotFingerprint};
+use crate::compression::{decompress, Codec};
+use crate::errors::{JsonParseError, SnapshotFormatError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Schema version written by [`ContextSnapshot::to_bytes`].
+///
+/// Bump it when the snapshot layout changes incompatibly; older versions
+/// must stay readable by [`ContextSnapshot::from_bytes`].
+pub const SNAPSHOT_SCHEMA_VERSION: u64 = 1;
+
 /// A message in a conversation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -45,6 +54,14 @@ impl Message {
     pub fn system(content: impl Into<String>) -> Self {
         Self::new("system", content)
     }
+
+    fn structural_hash(&self) -> u64 {
+        let mut hasher = StructHasher::new();
+        hasher.write_str(&self.role);
+        hasher.write_str(&self.content);
+        hasher.write_u64(map_hash(&self.metadata));
+        hasher.finish()
+    }
 }
 
 /// Conversation history with routing decision.
@@ -97,6 +114,20 @@ impl Conversation {
             .find(|m| m.role == "user")
             .map(|m| m.content.as_str())
     }
+
+    /// Returns the structural hash of the conversation.
+    #[must_use]
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = StructHasher::new();
+        hasher.write_u64(self.messages.len() as u64);
+        for message in &self.messages {
+            hasher.write_u64(message.structural_hash());
+        }
+        if let Some(ref decision) = self.routing_decision {
+            hasher.write_str(decision);
+        }
+        Fingerprint::from_raw(hasher.finish())
+    }
 }
 
 /// Enrichment data groups.
@@ -160,6 +191,28 @@ impl Enrichments {
         self.custom.insert(key.into(), value);
         self
     }
+
+    /// Returns the structural hash of the enrichments.
+    #[must_use]
+    pub fn fingerprint(&self) -> Fingerprint {
+        let optional = |value: &Option<serde_json::Value>| value.as_ref().map_or(0, value_hash);
+        let sequence = |values: &[serde_json::Value]| {
+            let mut hasher = StructHasher::new();
+            hasher.write_u64(values.len() as u64);
+            for value in values {
+                hasher.write_value(value);
+            }
+            hasher.finish()
+        };
+
+        let mut hasher = StructHasher::new();
+        hasher.write_u64(optional(&self.profile));
+        hasher.write_u64(optional(&self.memory));
+        hasher.write_u64(sequence(&self.documents));
+        hasher.write_u64(sequence(&self.web_results));
+        hasher.write_u64(map_hash(&self.custom));
+        Fingerprint::from_raw(hasher.finish())
+    }
 }
 
 /// A bundle of typed extensions.
@@ -193,6 +246,12 @@ impl ExtensionBundle {
     pub fn contains(&self, type_name: &str) -> bool {
         self.extensions.contains_key(type_name)
     }
+
+    /// Returns the structural hash of the registered extensions.
+    #[must_use]
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::from_raw(map_hash(&self.extensions))
+    }
 }
 
 /// An immutable snapshot of the execution context.
@@ -311,6 +370,107 @@ impl ContextSnapshot {
         self.run_id.user_id
     }
 
+    /// Returns structural fingerprints of the snapshot's sections.
+    ///
+    /// Values are hashed by walking them in place rather than serializing
+    /// the snapshot, so direct edits through the public fields are always
+    /// reflected. Use it as a cache key or compare fingerprints with
+    /// [`SnapshotFingerprint::changed_sections`] to find which sections
+    /// drifted.
+    #[must_use]
+    pub fn fingerprint(&self) -> SnapshotFingerprint {
+        let mut input = StructHasher::new();
+        if let Some(ref text) = self.input_text {
+            input.write_str(text);
+        }
+        input.write_u64(map_hash(&self.metadata));
+
+        SnapshotFingerprint {
+            conversation: self.conversation.fingerprint(),
+            enrichments: self.enrichments.fingerprint(),
+            extensions: self.extensions.fingerprint(),
+            input: Fingerprint::from_raw(input.finish()),
+        }
+    }
+
+    /// Loads a snapshot from JSON text.
+    ///
+    /// Integers beyond 64 bits are handled by the process-wide
+    /// [`NumberPolicy`](crate::utils::NumberPolicy) instead of being
+    /// silently rounded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JsonParseError`] if the text is not a valid snapshot or
+    /// holds an integer the policy rejects.
+    pub fn from_json(text: &str) -> Result<Self, JsonParseError> {
+        let value = crate::utils::parse_json(text)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Encodes the snapshot for persistence.
+    ///
+    /// The snapshot is wrapped in a JSON envelope recording
+    /// [`SNAPSHOT_SCHEMA_VERSION`] so later releases can migrate it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotFormatError`] if a value cannot be serialized.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SnapshotFormatError> {
+        let envelope = serde_json::json!({
+            "schema_version": SNAPSHOT_SCHEMA_VERSION,
+            "snapshot": self,
+        });
+        serde_json::to_vec(&envelope).map_err(|e| JsonParseError::from(e).into())
+    }
+
+    /// Encodes the snapshot like [`to_bytes`](Self::to_bytes), then
+    /// compresses it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotFormatError`] if serialization or compression fails.
+    pub fn to_bytes_with(&self, codec: &dyn Codec) -> Result<Vec<u8>, SnapshotFormatError> {
+        Ok(codec.encode(&self.to_bytes()?)?)
+    }
+
+    /// Decodes a snapshot written by [`to_bytes`](Self::to_bytes) or
+    /// [`to_bytes_with`](Self::to_bytes_with).
+    ///
+    /// Compressed input is detected by its magic bytes. Bare snapshots
+    /// without an envelope, as produced by serializing a snapshot directly,
+    /// are read as schema version 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotFormatError`] if the bytes are not a valid snapshot,
+    /// use a codec that is not compiled in, or were written by a newer
+    /// schema version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotFormatError> {
+        let bytes = decompress(bytes)?;
+        let text = std::str::from_utf8(&bytes).map_err(|e| {
+            JsonParseError::from(<serde_json::Error as serde::de::Error>::custom(e))
+        })?;
+        let mut value = crate::utils::parse_json(text)?;
+
+        let version = value.get("schema_version").and_then(serde_json::Value::as_u64);
+        let snapshot = match version {
+            Some(found) if found > SNAPSHOT_SCHEMA_VERSION => {
+                return Err(SnapshotFormatError::UnsupportedVersion {
+                    found,
+                    supported: SNAPSHOT_SCHEMA_VERSION,
+                })
+            }
+            Some(_) => value
+                .get_mut("snapshot")
+                .map(serde_json::Value::take)
+                .unwrap_or_default(),
+            None => value,
+        };
+        let snapshot: Self = serde_json::from_value(snapshot).map_err(JsonParseError::from)?;
+        Ok(snapshot)
+    }
+
     /// Converts to a dictionary representation.
     ///
     /// Includes both composed keys and legacy flattened keys for compatibility.
@@ -436,4 +596,75 @@ mod tests {
 
         assert_eq!(snapshot.input_text, deserialized.input_text);
     }
+
+    #[test]
+    fn test_snapshot_bytes_are_versioned() {
+        let snapshot = ContextSnapshot::new().with_input_text("test");
+        let bytes = snapshot.to_bytes().unwrap();
+        let envelope: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(envelope["schema_version"], SNAPSHOT_SCHEMA_VERSION);
+        assert_eq!(ContextSnapshot::from_bytes(&bytes).unwrap().input_text.as_deref(), Some("test"));
+
+        // Bare snapshots predate the envelope and still load
+        let bare = serde_json::to_vec(&snapshot).unwrap();
+        assert_eq!(ContextSnapshot::from_bytes(&bare).unwrap().input_text.as_deref(), Some("test"));
+
+        let future = serde_json::json!({"schema_version": 99, "snapshot": {}}).to_string();
+        assert!(matches!(
+            ContextSnapshot::from_bytes(future.as_bytes()),
+            Err(SnapshotFormatError::UnsupportedVersion { found: 99, .. })
+        ));
+        assert!(ContextSnapshot::from_bytes(b"\xff").is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_sections() {
+        let base = ContextSnapshot::new()
+            .with_conversation(Conversation::new().add_message(Message::user("Hi")))
+            .with_enrichments(Enrichments::new().with_custom("tier", serde_json::json!("gold")));
+        let other_run = base.clone().with_run_id(RunIdentity::new());
+        assert_eq!(base.fingerprint(), other_run.fingerprint());
+
+        let grown = base.clone().with_conversation(
+            base.conversation.clone().add_message(Message::assistant("Hello!")),
+        );
+        assert_eq!(
+            base.fingerprint().changed_sections(&grown.fingerprint()),
+            vec!["conversation"]
+        );
+
+        let mut extended = base.clone();
+        extended.extensions.register("ab_test", serde_json::json!({"arm": "b"}));
+        assert_eq!(
+            base.fingerprint().changed_sections(&extended.fingerprint()),
+            vec!["extensions"]
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_sees_direct_field_edits() {
+        let mut snapshot = ContextSnapshot::new()
+            .with_conversation(Conversation::with_messages(vec![Message::user("a")]))
+            .with_enrichments(
+                Enrichments::new()
+                    .with_profile(serde_json::json!({"name": "Alice"}))
+                    .with_custom("k", serde_json::json!(1)),
+            );
+        snapshot.conversation.messages.push(Message::user("b"));
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: ContextSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot.fingerprint(), restored.fingerprint());
+
+        let before = snapshot.fingerprint();
+        snapshot.enrichments.profile = Some(serde_json::json!({"name": "Bob"}));
+        assert_eq!(snapshot.fingerprint().changed_sections(&before), vec!["enrichments"]);
+
+        let before = snapshot.fingerprint();
+        snapshot.enrichments.custom.insert("k".to_string(), serde_json::json!(2));
+        snapshot.extensions = ExtensionBundle {
+            extensions: HashMap::from([("ab_test".to_string(), serde_json::json!("b"))]),
+        };
+        assert_eq!(snapshot.fingerprint().changed_sections(&before), vec!["enrichments", "extensions"]);
+    }
 }
@@ -98,6 +98,16 @@ pub mod codes {
     pub const SCHEMA_MISMATCH: &str = "CONTRACT-002-SCHEMA";
     /// Version mismatch error.
     pub const VERSION_MISMATCH: &str = "CONTRACT-003-VERSION";
+    /// Referenced contract version is not registered.
+    pub const UNREGISTERED: &str = "CONTRACT-003-UNREGISTERED";
+    /// Consumed contract has no producing stage.
+    pub const MISSING_PRODUCER: &str = "CONTRACT-004-MISSING_PRODUCER";
+    /// Stage output data exceeded its size limit.
+    pub const OUTPUT_SIZE: &str = "CONTRACT-005-OUTPUT_SIZE";
+    /// Stage artifacts exceeded their size limit.
+    pub const ARTIFACT_SIZE: &str = "CONTRACT-005-ARTIFACT_SIZE";
+    /// Stage exceeded its time limit.
+    pub const STAGE_DURATION: &str = "CONTRACT-005-DURATION";
 }
 
 #[cfg(test)]
@@ -9,13 +9,15 @@
 
 mod errors;
 mod registry;
+mod schema;
 mod suggestions;
 mod typed_output;
 
 pub use errors::{ContractErrorInfo, codes};
 pub use registry::{
-    ContractCompatibilityReport, ContractMetadata, ContractRegistry, REGISTRY,
+    ContractCompatibilityReport, ContractMetadata, ContractRef, ContractRegistry, REGISTRY,
 };
+pub use schema::{SchemaViolation, validate_json_schema};
 pub use suggestions::{
     ContractSuggestion, get_contract_suggestion, list_suggestions, register_suggestion,
 };
@@ -47,6 +47,45 @@ impl ContractMetadata {
     }
 }
 
+/// Reference to a registered contract, written `name@version`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ContractRef {
+    /// Contract name, usually the producing stage's name.
+    pub name: String,
+    /// Contract version.
+    pub version: String,
+}
+
+impl ContractRef {
+    /// Creates a contract reference.
+    #[must_use]
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ContractRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+impl std::str::FromStr for ContractRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once('@') {
+            Some((name, version)) if !name.is_empty() && !version.is_empty() => {
+                Ok(Self::new(name, version))
+            }
+            _ => Err(format!("Invalid contract reference '{s}', expected name@version")),
+        }
+    }
+}
+
 /// Simple compatibility diff between two contract versions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContractCompatibilityReport {
@@ -312,6 +351,15 @@ pub static REGISTRY: std::sync::LazyLock<Arc<ContractRegistry>> =
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_contract_ref_parse() {
+        let parsed: ContractRef = "summary@1.2".parse().unwrap();
+        assert_eq!(parsed, ContractRef::new("summary", "1.2"));
+        assert_eq!(parsed.to_string(), "summary@1.2");
+        assert!("summary".parse::<ContractRef>().is_err());
+        assert!("@1.0".parse::<ContractRef>().is_err());
+    }
+
     #[test]
     fn test_contract_registry_register() {
         let registry = ContractRegistry::new();
@@ -0,0 +1,382 @@
+//! JSON Schema validation for payloads.
+//!
+//! Implements the subset of JSON Schema used for tool parameters and stage
+//! payloads: `type`, `enum`, `const`, `properties`, `required`,
+//! `additionalProperties`, `items`, `minItems`/`maxItems`,
+//! `minLength`/`maxLength`, `pattern`, `minimum`/`maximum` and their
+//! exclusive forms, and `allOf`/`anyOf`/`oneOf`. Unknown keywords are
+//! ignored, so an empty schema accepts everything.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single validation failure at a location in the payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// Location of the offending value, e.g. `$.recipients[0].email`.
+    pub path: String,
+    /// What was wrong with it.
+    pub message: String,
+}
+
+impl SchemaViolation {
+    /// Creates a new violation.
+    #[must_use]
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Validates a value against a JSON Schema.
+///
+/// Returns every violation found, or an empty list if the value is valid.
+#[must_use]
+pub fn validate_json_schema(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, value, "$", &mut violations);
+    violations
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, out: &mut Vec<SchemaViolation>) {
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        Value::Bool(false) => {
+            out.push(SchemaViolation::new(path, "no value is allowed here"));
+            return;
+        }
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        if !matches_type(expected, value) {
+            out.push(SchemaViolation::new(
+                path,
+                format!("expected {}, got {}", describe_type(expected), type_name(value)),
+            ));
+            // Other keywords would only repeat the type mismatch
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            out.push(SchemaViolation::new(
+                path,
+                format!("must be one of {}", Value::Array(options.clone())),
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            out.push(SchemaViolation::new(path, format!("must equal {expected}")));
+        }
+    }
+
+    check_value(schema, value, path, out);
+    check_combinators(schema, value, path, out);
+}
+
+/// Applies the keywords specific to the value's type.
+fn check_value(
+    schema: &serde_json::Map<String, Value>,
+    value: &Value,
+    path: &str,
+    out: &mut Vec<SchemaViolation>,
+) {
+    match value {
+        Value::Object(fields) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        out.push(SchemaViolation::new(
+                            path,
+                            format!("missing required property '{name}'"),
+                        ));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                let field_path = format!("{path}.{name}");
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => validate_at(field_schema, field, &field_path, out),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => out.push(SchemaViolation::new(
+                            field_path,
+                            "unexpected property",
+                        )),
+                        Some(extra) => validate_at(extra, field, &field_path, out),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bound(schema, "minItems", path, items.len(), out, |n| {
+                format!("must have at least {n} items")
+            });
+            check_upper(schema, "maxItems", path, items.len(), out, |n| {
+                format!("must have at most {n} items")
+            });
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{path}[{i}]"), out);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count();
+            check_bound(schema, "minLength", path, len, out, |n| {
+                format!("must be at least {n} characters")
+            });
+            check_upper(schema, "maxLength", path, len, out, |n| {
+                format!("must be at most {n} characters")
+            });
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                match regex::Regex::new(pattern) {
+                    Ok(re) if !re.is_match(s) => out.push(SchemaViolation::new(
+                        path,
+                        format!("must match pattern '{pattern}'"),
+                    )),
+                    Ok(_) => {}
+                    Err(_) => out.push(SchemaViolation::new(
+                        path,
+                        format!("schema pattern '{pattern}' is not a valid regex"),
+                    )),
+                }
+            }
+        }
+        Value::Number(n) => {
+            if let Some(n) = n.as_f64() {
+                check_number(schema, path, n, out);
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+}
+
+/// Applies `allOf`, `anyOf` and `oneOf`.
+fn check_combinators(
+    schema: &serde_json::Map<String, Value>,
+    value: &Value,
+    path: &str,
+    out: &mut Vec<SchemaViolation>,
+) {
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for sub in all {
+            validate_at(sub, value, path, out);
+        }
+    }
+    if let Some(Value::Array(any)) = schema.get("anyOf") {
+        if !any.iter().any(|sub| validate_json_schema(sub, value).is_empty()) {
+            out.push(SchemaViolation::new(path, "does not match any allowed schema"));
+        }
+    }
+    if let Some(Value::Array(one)) = schema.get("oneOf") {
+        let matched = one
+            .iter()
+            .filter(|sub| validate_json_schema(sub, value).is_empty())
+            .count();
+        if matched != 1 {
+            out.push(SchemaViolation::new(
+                path,
+                format!("must match exactly one schema, matched {matched}"),
+            ));
+        }
+    }
+}
+
+fn check_bound(
+    schema: &serde_json::Map<String, Value>,
+    keyword: &str,
+    path: &str,
+    actual: usize,
+    out: &mut Vec<SchemaViolation>,
+    message: impl Fn(u64) -> String,
+) {
+    if let Some(min) = schema.get(keyword).and_then(Value::as_u64) {
+        if (actual as u64) < min {
+            out.push(SchemaViolation::new(path, message(min)));
+        }
+    }
+}
+
+fn check_upper(
+    schema: &serde_json::Map<String, Value>,
+    keyword: &str,
+    path: &str,
+    actual: usize,
+    out: &mut Vec<SchemaViolation>,
+    message: impl Fn(u64) -> String,
+) {
+    if let Some(max) = schema.get(keyword).and_then(Value::as_u64) {
+        if (actual as u64) > max {
+            out.push(SchemaViolation::new(path, message(max)));
+        }
+    }
+}
+
+fn check_number(
+    schema: &serde_json::Map<String, Value>,
+    path: &str,
+    n: f64,
+    out: &mut Vec<SchemaViolation>,
+) {
+    let limit = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+
+    if let Some(min) = limit("minimum") {
+        if n < min {
+            out.push(SchemaViolation::new(path, format!("must be >= {min}")));
+        }
+    }
+    if let Some(max) = limit("maximum") {
+        if n > max {
+            out.push(SchemaViolation::new(path, format!("must be <= {max}")));
+        }
+    }
+    if let Some(min) = limit("exclusiveMinimum") {
+        if n <= min {
+            out.push(SchemaViolation::new(path, format!("must be > {min}")));
+        }
+    }
+    if let Some(max) = limit("exclusiveMaximum") {
+        if n >= max {
+            out.push(SchemaViolation::new(path, format!("must be < {max}")));
+        }
+    }
+}
+
+fn matches_type(expected: &Value, value: &Value) -> bool {
+    match expected {
+        Value::String(name) => is_type(name, value),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| is_type(name, value)),
+        _ => true,
+    }
+}
+
+fn is_type(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64()
+            || value.is_u64()
+            || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        // Unknown type names do not constrain the value
+        _ => true,
+    }
+}
+
+fn describe_type(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        other => other.as_str().unwrap_or("any").to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn email_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["to", "subject"],
+            "additionalProperties": false,
+            "properties": {
+                "to": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {"type": "string", "pattern": "^[^@]+@[^@]+$"}
+                },
+                "subject": {"type": "string", "maxLength": 10},
+                "priority": {"type": "integer", "minimum": 1, "maximum": 5},
+                "format": {"enum": ["text", "html"]}
+            }
+        })
+    }
+
+    #[test]
+    fn test_valid_payload() {
+        let payload = json!({"to": ["a@b.c"], "subject": "Hi", "priority": 3, "format": "html"});
+        assert!(validate_json_schema(&email_schema(), &payload).is_empty());
+        assert!(validate_json_schema(&json!({}), &payload).is_empty());
+    }
+
+    #[test]
+    fn test_reports_violations_with_paths() {
+        let payload = json!({
+            "to": ["a@b.c", "nope"],
+            "priority": 9,
+            "format": "pdf",
+            "cc": "x@y.z"
+        });
+        let violations = validate_json_schema(&email_schema(), &payload);
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+
+        assert_eq!(violations.len(), 5, "{violations:?}");
+        assert!(violations
+            .iter()
+            .any(|v| v.path == "$" && v.message.contains("'subject'")));
+        assert!(paths.contains(&"$.to[1]"));
+        assert!(paths.contains(&"$.priority"));
+        assert!(paths.contains(&"$.format"));
+        assert!(paths.contains(&"$.cc"));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let violations = validate_json_schema(&email_schema(), &json!({"to": "a@b.c", "subject": 1}));
+        assert_eq!(
+            violations,
+            vec![
+                SchemaViolation::new("$.subject", "expected string, got integer"),
+                SchemaViolation::new("$.to", "expected array, got string"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({"anyOf": [{"type": "string"}, {"type": "integer"}]});
+        assert!(validate_json_schema(&schema, &json!(1)).is_empty());
+        assert_eq!(validate_json_schema(&schema, &json!(1.5)).len(), 1);
+
+        let schema = json!({"oneOf": [{"type": "number"}, {"type": "integer"}]});
+        assert_eq!(validate_json_schema(&schema, &json!(2)).len(), 1);
+        assert!(validate_json_schema(&schema, &json!(2.5)).is_empty());
+    }
+}
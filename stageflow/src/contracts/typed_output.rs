@@ -3,14 +3,14 @@
 //! Provides compile-time and runtime validation for stage output data
 //! using serde for serialization/deserialization.
 
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
 use std::marker::PhantomData;
 
 use crate::core::StageOutput;
 
 /// Error during typed output validation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationError {
     /// Error message.
     pub message: String,
@@ -50,6 +50,12 @@ impl std::fmt::Display for ValidationError {
 
 impl std::error::Error for ValidationError {}
 
+impl From<super::SchemaViolation> for ValidationError {
+    fn from(violation: super::SchemaViolation) -> Self {
+        Self::for_field(violation.path, violation.message)
+    }
+}
+
 /// Configuration for typed output validation.
 #[derive(Debug, Clone, Default)]
 pub struct TypedOutputConfig {
@@ -0,0 +1,106 @@
+//! Shared stage output data.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// The data of a [`StageOutput`](super::StageOutput), shared by reference.
+///
+/// Cloning is an `Arc` clone, so executors can hand one output to the run
+/// result and to every consumer without copying its values. Reads go
+/// through `Deref` to the map; writes through `DerefMut` copy the map first
+/// if it is shared.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StageData(Arc<HashMap<String, serde_json::Value>>);
+
+impl StageData {
+    /// Creates empty data.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the shared map.
+    #[must_use]
+    pub fn as_arc(&self) -> &Arc<HashMap<String, serde_json::Value>> {
+        &self.0
+    }
+
+    /// Converts into the shared map.
+    #[must_use]
+    pub fn into_arc(self) -> Arc<HashMap<String, serde_json::Value>> {
+        self.0
+    }
+
+    /// Converts into an owned map, copying it only if it is shared.
+    #[must_use]
+    pub fn into_inner(self) -> HashMap<String, serde_json::Value> {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    /// Returns true if both point to the same map.
+    #[must_use]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for StageData {
+    type Target = HashMap<String, serde_json::Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for StageData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl Serialize for StageData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StageData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HashMap::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl From<HashMap<String, serde_json::Value>> for StageData {
+    fn from(data: HashMap<String, serde_json::Value>) -> Self {
+        Self(Arc::new(data))
+    }
+}
+
+impl From<Arc<HashMap<String, serde_json::Value>>> for StageData {
+    fn from(data: Arc<HashMap<String, serde_json::Value>>) -> Self {
+        Self(data)
+    }
+}
+
+impl FromIterator<(String, serde_json::Value)> for StageData {
+    fn from_iter<I: IntoIterator<Item = (String, serde_json::Value)>>(iter: I) -> Self {
+        Self(Arc::new(iter.into_iter().collect()))
+    }
+}
+
+impl<'a> IntoIterator for &'a StageData {
+    type Item = (&'a String, &'a serde_json::Value);
+    type IntoIter = std::collections::hash_map::Iter<'a, String, serde_json::Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl PartialEq<HashMap<String, serde_json::Value>> for StageData {
+    fn eq(&self, other: &HashMap<String, serde_json::Value>) -> bool {
+        *self.0 == *other
+    }
+}
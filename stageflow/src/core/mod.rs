@@ -6,6 +6,7 @@
 //! - Stage artifacts and events
 
 mod artifact;
+mod data;
 mod event;
 mod output;
 #[cfg(test)]
@@ -13,6 +14,7 @@ mod output_tests;
 mod status;
 
 pub use artifact::StageArtifact;
+pub use data::StageData;
 pub use event::StageEvent;
 pub use output::StageOutput;
 pub use status::{StageKind, StageStatus};
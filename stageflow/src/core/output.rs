@@ -1,6 +1,7 @@
 //! Stage output type with factory methods matching Python semantics.
 
-use super::{StageArtifact, StageEvent, StageStatus};
+use super::{StageArtifact, StageData, StageEvent, StageStatus};
+use crate::errors::{is_transient_io, StageflowError, ToolError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -13,9 +14,9 @@ pub struct StageOutput {
     /// The status of the stage execution.
     pub status: StageStatus,
 
-    /// The output data (for successful executions).
+    /// The output data (for successful executions), shared on clone.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub data: Option<HashMap<String, serde_json::Value>>,
+    pub data: Option<StageData>,
 
     /// Artifacts produced by the stage.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -58,7 +59,7 @@ impl StageOutput {
     pub fn ok(data: HashMap<String, serde_json::Value>) -> Self {
         Self {
             status: StageStatus::Ok,
-            data: Some(data),
+            data: Some(data.into()),
             artifacts: Vec::new(),
             events: Vec::new(),
             metadata: HashMap::new(),
@@ -69,6 +70,13 @@ impl StageOutput {
         }
     }
 
+    /// Creates a successful output with data that is already shared, such
+    /// as another stage's [`StageData`], without copying it.
+    #[must_use]
+    pub fn ok_shared(data: impl Into<StageData>) -> Self {
+        Self::ok_empty().with_shared_data(data)
+    }
+
     /// Creates a successful output with no data.
     #[must_use]
     pub fn ok_empty() -> Self {
@@ -206,11 +214,19 @@ impl StageOutput {
     pub fn with_data(mut self, data: HashMap<String, serde_json::Value>) -> Self {
         match &mut self.data {
             Some(existing) => existing.extend(data),
-            None => self.data = Some(data),
+            None => self.data = Some(data.into()),
         }
         self
     }
 
+    /// Sets data that is already shared, replacing any existing data
+    /// without copying it.
+    #[must_use]
+    pub fn with_shared_data(mut self, data: impl Into<StageData>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
     /// Returns true if the output indicates success.
     #[must_use]
     pub fn is_success(&self) -> bool {
@@ -232,6 +248,12 @@ impl StageOutput {
     /// Returns the data, or an empty HashMap if none.
     #[must_use]
     pub fn data_or_empty(&self) -> HashMap<String, serde_json::Value> {
+        self.data.clone().map(StageData::into_inner).unwrap_or_default()
+    }
+
+    /// Returns the data, or empty data if none, without copying it.
+    #[must_use]
+    pub fn shared_data_or_empty(&self) -> StageData {
         self.data.clone().unwrap_or_default()
     }
 
@@ -249,7 +271,7 @@ impl StageOutput {
 
         if let Some(ref data) = self.data {
             let data_map: serde_json::Map<String, serde_json::Value> =
-                data.clone().into_iter().collect();
+                data.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
             map.insert("data".to_string(), serde_json::Value::Object(data_map));
         }
 
@@ -293,6 +315,92 @@ impl StageOutput {
     }
 }
 
+impl StageOutput {
+    /// Converts an error into a failure output.
+    ///
+    /// The output is retryable if any error in the source chain is a
+    /// retryable [`StageflowError`] or [`ToolError`] or a transient IO
+    /// error, and a [`StageflowError::Cancelled`] becomes a cancel output.
+    /// When the error has causes, every message in the chain is kept in
+    /// the `error_chain` metadata entry.
+    #[must_use]
+    pub fn from_error(error: impl Into<anyhow::Error>) -> Self {
+        let error = error.into();
+        if let Some(StageflowError::Cancelled(reason)) = error.downcast_ref::<StageflowError>() {
+            return Self::cancel(reason.clone());
+        }
+
+        let retryable = error.chain().any(is_retryable_cause);
+        let mut output = if retryable {
+            Self::fail_retryable(error.to_string())
+        } else {
+            Self::fail(error.to_string())
+        };
+        let chain: Vec<String> = error.chain().map(ToString::to_string).collect();
+        if chain.len() > 1 {
+            output
+                .metadata
+                .insert("error_chain".to_string(), serde_json::json!(chain));
+        }
+        output
+    }
+}
+
+fn is_retryable_cause(cause: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(err) = cause.downcast_ref::<StageflowError>() {
+        err.is_retryable()
+    } else if let Some(err) = cause.downcast_ref::<ToolError>() {
+        err.is_retryable()
+    } else if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+        is_transient_io(err)
+    } else {
+        false
+    }
+}
+
+impl From<anyhow::Error> for StageOutput {
+    fn from(error: anyhow::Error) -> Self {
+        Self::from_error(error)
+    }
+}
+
+impl From<StageflowError> for StageOutput {
+    fn from(error: StageflowError) -> Self {
+        Self::from_error(error)
+    }
+}
+
+impl From<ToolError> for StageOutput {
+    fn from(error: ToolError) -> Self {
+        Self::from_error(error)
+    }
+}
+
+/// Unwraps a `Result` inside a stage, returning a failure output on error.
+///
+/// Works like `?` for stage `execute` bodies, which return [`StageOutput`]
+/// rather than a `Result`. The error is converted with
+/// [`StageOutput::from_error`], so any `std::error::Error + Send + Sync`
+/// or `anyhow::Error` is accepted.
+///
+/// ```ignore
+/// async fn execute(&self, ctx: &StageContext) -> StageOutput {
+///     let body = try_stage!(ctx.read_file("prompt.txt").await);
+///     StageOutput::ok_value("length", serde_json::json!(body.len()))
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_stage {
+    ($result:expr $(,)?) => {
+        match $result {
+            ::core::result::Result::Ok(value) => value,
+            ::core::result::Result::Err(error) => {
+                return $crate::core::StageOutput::from_error(error);
+            }
+        }
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
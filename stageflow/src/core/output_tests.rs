@@ -210,4 +210,78 @@ mod tests {
         let output = StageOutput::ok_value("nested", nested.clone());
         assert_eq!(output.get("nested"), Some(&nested));
     }
+
+    #[test]
+    fn test_output_from_errors_infers_retryability() {
+        use crate::errors::{StageflowError, ToolError};
+
+        let output = StageOutput::from(ToolError::execution_failed("search", "503"));
+        assert!(output.is_failure());
+        assert!(output.is_retryable());
+
+        let output = StageOutput::from(ToolError::not_found("search"));
+        assert!(!output.is_retryable());
+
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "slow upstream");
+        assert!(StageOutput::from(StageflowError::from(io)).is_retryable());
+
+        let output = StageOutput::from(StageflowError::Cancelled("shutdown".to_string()));
+        assert_eq!(output.status, StageStatus::Cancel);
+        assert_eq!(output.cancel_reason.as_deref(), Some("shutdown"));
+    }
+
+    #[test]
+    fn test_output_from_anyhow_keeps_chain() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset by peer");
+        let error = anyhow::Error::new(io).context("fetching profile");
+
+        let output = StageOutput::from(error);
+        assert_eq!(output.error.as_deref(), Some("fetching profile"));
+        assert!(output.is_retryable());
+        assert_eq!(
+            output.metadata["error_chain"],
+            serde_json::json!(["fetching profile", "reset by peer"])
+        );
+    }
+
+    #[test]
+    fn test_try_stage_macro() {
+        fn parse(raw: &str) -> StageOutput {
+            let n: i64 = crate::try_stage!(raw.parse::<i64>());
+            StageOutput::ok_value("n", serde_json::json!(n))
+        }
+
+        assert_eq!(parse("7").get("n"), Some(&serde_json::json!(7)));
+        let failed = parse("seven");
+        assert!(failed.is_failure());
+        assert!(!failed.is_retryable());
+    }
+
+    #[test]
+    fn test_output_data_is_shared_on_clone() {
+        let payload = HashMap::from([("blob".to_string(), serde_json::json!("x".repeat(1024)))]);
+        let output = StageOutput::ok(payload.clone());
+        let copy = output.clone();
+        assert!(output.data.as_ref().unwrap().ptr_eq(copy.data.as_ref().unwrap()));
+        assert!(output.shared_data_or_empty().ptr_eq(output.data.as_ref().unwrap()));
+
+        let forwarded = StageOutput::ok_shared(output.shared_data_or_empty());
+        assert!(forwarded.data.as_ref().unwrap().ptr_eq(output.data.as_ref().unwrap()));
+
+        let mut changed = copy;
+        changed.data.as_mut().unwrap().insert("extra".to_string(), serde_json::json!(1));
+        assert_eq!(changed.data.as_ref().unwrap().len(), 2);
+        assert_eq!(*output.data.as_ref().unwrap(), payload);
+        assert_eq!(output.data_or_empty(), payload);
+    }
+
+    #[test]
+    fn test_shared_data_serializes_as_map() {
+        let output = StageOutput::ok_value("key", serde_json::json!("value"));
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["data"], serde_json::json!({"key": "value"}));
+
+        let restored: StageOutput = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.get("key"), Some(&serde_json::json!("value")));
+    }
 }
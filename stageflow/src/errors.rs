@@ -3,6 +3,7 @@
 //! This module provides a comprehensive error taxonomy matching the Python
 //! implementation's error types and behaviors.
 
+use crate::contracts::SchemaViolation;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
@@ -11,8 +12,12 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum StageflowError {
     /// A pipeline validation error occurred.
+    ///
+    /// Boxed, like [`StageflowError::CycleDetected`], to keep the error
+    /// type small. Matches bind a `Box<PipelineValidationError>`; `?` and
+    /// `into()` still convert from the unboxed error.
     #[error("{0}")]
-    Validation(#[from] PipelineValidationError),
+    Validation(#[from] Box<PipelineValidationError>),
 
     /// A data conflict occurred in a context bag.
     #[error("{0}")]
@@ -27,8 +32,10 @@ pub enum StageflowError {
     UndeclaredDependency(#[from] UndeclaredDependencyError),
 
     /// A cycle was detected in the pipeline.
+    ///
+    /// Matches bind a `Box<CycleDetectedError>`.
     #[error("{0}")]
-    CycleDetected(#[from] CycleDetectedError),
+    CycleDetected(#[from] Box<CycleDetectedError>),
 
     /// A stage execution error.
     #[error("Stage execution error: {0}")]
@@ -38,10 +45,26 @@ pub enum StageflowError {
     #[error("Pipeline cancelled: {0}")]
     Cancelled(String),
 
+    /// A stage accessed the context in a way its consistency mode forbids.
+    #[error("Context consistency violation: {0}")]
+    ConsistencyViolation(String),
+
+    /// A stage accessed a resource its sandbox policy denies.
+    #[error("{0}")]
+    AccessDenied(#[from] AccessDeniedError),
+
+    /// A stage with a read-only context wrote to it.
+    #[error("{0}")]
+    ReadOnlyContext(#[from] ReadOnlyContextError),
+
     /// A tool-related error.
     #[error("{0}")]
     Tool(#[from] ToolError),
 
+    /// A number could not be represented under the active number policy.
+    #[error("{0}")]
+    NumberPrecision(#[from] NumberPrecisionError),
+
     /// A generic internal error.
     #[error("Internal error: {0}")]
     Internal(String),
@@ -53,6 +76,119 @@ pub enum StageflowError {
     /// IO error.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+
+    /// Several independent errors, such as every failing stage of a
+    /// best-effort run or every failed preflight check.
+    ///
+    /// Build with [`StageflowError::from_errors`], which flattens nested
+    /// aggregates.
+    #[error("{}", display_multiple(.0))]
+    Multiple(Vec<StageflowError>),
+}
+
+impl From<PipelineValidationError> for StageflowError {
+    fn from(err: PipelineValidationError) -> Self {
+        Self::Validation(Box::new(err))
+    }
+}
+
+impl From<CycleDetectedError> for StageflowError {
+    fn from(err: CycleDetectedError) -> Self {
+        Self::CycleDetected(Box::new(err))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for StageflowError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Internal(format!("SQLite error: {err}"))
+    }
+}
+
+impl StageflowError {
+    /// Returns true if retrying the failed operation may succeed.
+    ///
+    /// Transient IO failures and retryable tool errors qualify; validation,
+    /// conflicts and other deterministic errors do not.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Io(err) => is_transient_io(err),
+            Self::Tool(err) => err.is_retryable(),
+            Self::Multiple(errors) => errors.iter().all(Self::is_retryable),
+            _ => false,
+        }
+    }
+
+    /// Combines errors into one.
+    ///
+    /// Returns `None` for no errors and the error itself for a single one;
+    /// otherwise returns [`StageflowError::Multiple`] with nested aggregates
+    /// flattened.
+    #[must_use]
+    pub fn from_errors<E: Into<Self>>(errors: impl IntoIterator<Item = E>) -> Option<Self> {
+        let mut flat = Vec::new();
+        for error in errors {
+            match error.into() {
+                Self::Multiple(inner) => flat.extend(inner),
+                error => flat.push(error),
+            }
+        }
+        match flat.len() {
+            0 => None,
+            1 => flat.pop(),
+            _ => Some(Self::Multiple(flat)),
+        }
+    }
+
+    /// Collects every value, or every error if any result failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns all errors combined with [`from_errors`](Self::from_errors).
+    pub fn collect<T, E: Into<Self>>(
+        results: impl IntoIterator<Item = Result<T, E>>,
+    ) -> Result<Vec<T>, Self> {
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(value) => values.push(value),
+                Err(error) => errors.push(error),
+            }
+        }
+        Self::from_errors(errors).map_or(Ok(values), Err)
+    }
+
+    /// Returns the individual errors: the aggregated ones for
+    /// [`StageflowError::Multiple`], otherwise just this error.
+    #[must_use]
+    pub fn errors(&self) -> Vec<&Self> {
+        match self {
+            Self::Multiple(errors) => errors.iter().collect(),
+            error => vec![error],
+        }
+    }
+}
+
+fn display_multiple(errors: &[StageflowError]) -> String {
+    let details: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    format!("{} errors occurred: {}", errors.len(), details.join("; "))
+}
+
+/// Returns true for IO errors that usually clear up on their own.
+pub(crate) fn is_transient_io(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        err.kind(),
+        ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+    )
 }
 
 /// Metadata about a contract error for better diagnostics.
@@ -251,6 +387,78 @@ impl DataConflictError {
     }
 }
 
+/// Error raised when a sandbox policy denies a stage's access.
+#[derive(Debug, Clone, Error)]
+#[error("Access denied: {kind} '{target}' is not allowed for stage '{stage}'")]
+pub struct AccessDeniedError {
+    /// The stage attempting access.
+    pub stage: String,
+    /// The kind of access, e.g. `env_var` or `file_write`.
+    pub kind: String,
+    /// The variable name or path.
+    pub target: String,
+}
+
+impl AccessDeniedError {
+    /// Creates a new access denied error.
+    #[must_use]
+    pub fn new(stage: impl Into<String>, kind: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            stage: stage.into(),
+            kind: kind.into(),
+            target: target.into(),
+        }
+    }
+}
+
+/// Error raised when a stage with a read-only context writes to it.
+#[derive(Debug, Clone, Error)]
+#[error("Stage '{stage}' has a read-only context but wrote {}", describe_writes(.data_keys, .output_stages))]
+pub struct ReadOnlyContextError {
+    /// The stage that wrote.
+    pub stage: String,
+    /// Context data keys that were added, changed or removed.
+    pub data_keys: Vec<String>,
+    /// Stages whose outputs were added, changed or removed.
+    pub output_stages: Vec<String>,
+}
+
+impl ReadOnlyContextError {
+    /// Creates an error for `stage` with no recorded writes.
+    #[must_use]
+    pub fn new(stage: impl Into<String>) -> Self {
+        Self {
+            stage: stage.into(),
+            data_keys: Vec::new(),
+            output_stages: Vec::new(),
+        }
+    }
+
+    /// Records a written context data key.
+    #[must_use]
+    pub fn with_data_key(mut self, key: impl Into<String>) -> Self {
+        self.data_keys.push(key.into());
+        self
+    }
+
+    /// Records a stage whose output was written.
+    #[must_use]
+    pub fn with_output_stage(mut self, stage: impl Into<String>) -> Self {
+        self.output_stages.push(stage.into());
+        self
+    }
+}
+
+fn describe_writes(data_keys: &[String], output_stages: &[String]) -> String {
+    let quoted = |items: &[String]| items.iter().map(|item| format!("'{item}'")).collect::<Vec<_>>().join(", ");
+    match (data_keys.is_empty(), output_stages.is_empty()) {
+        (false, true) => format!("data {}", quoted(data_keys)),
+        (true, false) => format!("outputs of {}", quoted(output_stages)),
+        (false, false) => format!("data {} and outputs of {}", quoted(data_keys), quoted(output_stages)),
+        (true, true) => "to it".to_string(),
+    }
+}
+
 /// Error raised when writing to an existing output in an output bag.
 #[derive(Debug, Clone, Error)]
 #[error("Output conflict for stage '{stage}': {message}")]
@@ -270,6 +478,13 @@ impl OutputConflictError {
             message: message.into(),
         }
     }
+
+    /// Creates an error for output keys the stage did not declare.
+    #[must_use]
+    pub fn undeclared_keys<K: AsRef<str>>(stage: impl Into<String>, keys: impl IntoIterator<Item = K>) -> Self {
+        let keys: Vec<String> = keys.into_iter().map(|key| key.as_ref().to_string()).collect();
+        Self::new(stage, format!("undeclared output keys: {}", keys.join(", ")))
+    }
 }
 
 /// Error raised when accessing an undeclared dependency.
@@ -293,6 +508,193 @@ impl UndeclaredDependencyError {
     }
 }
 
+/// An error from encoding or decoding a persisted context snapshot.
+#[derive(Debug, Error)]
+pub enum SnapshotFormatError {
+    /// The bytes are not a valid snapshot envelope.
+    #[error("Malformed snapshot: {0}")]
+    Malformed(#[from] JsonParseError),
+
+    /// The compressed snapshot could not be decoded.
+    #[error("{0}")]
+    Codec(#[from] CodecError),
+
+    /// The snapshot was written by a newer schema version.
+    #[error("Unsupported snapshot schema version {found} (supported up to {supported})")]
+    UnsupportedVersion {
+        /// The version found in the envelope.
+        found: u64,
+        /// The newest version this build reads.
+        supported: u64,
+    },
+}
+
+impl From<SnapshotFormatError> for StageflowError {
+    fn from(err: SnapshotFormatError) -> Self {
+        Self::Serialization(err.to_string())
+    }
+}
+
+/// An error from reading a typed value out of [`StageInputs`](crate::context::StageInputs).
+#[derive(Debug, Error)]
+pub enum InputError {
+    /// The stage is not a declared dependency.
+    #[error("{0}")]
+    Undeclared(#[from] UndeclaredDependencyError),
+
+    /// The upstream stage did not produce the key.
+    #[error("Missing input '{stage}.{key}'")]
+    Missing {
+        /// The upstream stage.
+        stage: String,
+        /// The missing key.
+        key: String,
+    },
+
+    /// The value does not match the requested type.
+    #[error("Invalid input '{stage}.{key}': {source}")]
+    Invalid {
+        /// The upstream stage.
+        stage: String,
+        /// The key that failed to deserialize.
+        key: String,
+        /// The deserialization error.
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl From<InputError> for StageflowError {
+    fn from(err: InputError) -> Self {
+        match err {
+            InputError::Undeclared(e) => Self::UndeclaredDependency(e),
+            other => Self::StageExecution(other.to_string()),
+        }
+    }
+}
+
+/// A number that could not be converted to JSON without losing precision.
+#[derive(Debug, Clone, Error)]
+#[error("Cannot represent number '{value}': {reason}")]
+pub struct NumberPrecisionError {
+    /// The offending number as written.
+    pub value: String,
+    /// Why it was rejected.
+    pub reason: String,
+}
+
+impl NumberPrecisionError {
+    /// Creates a new number precision error.
+    #[must_use]
+    pub fn new(value: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A JSON Patch operation that could not be applied.
+#[derive(Debug, Clone, Error)]
+#[error("Cannot apply patch operation {index} at '{path}': {reason}")]
+pub struct PatchError {
+    /// Position of the failing operation in the patch.
+    pub index: usize,
+    /// The JSON Pointer the operation targets.
+    pub path: String,
+    /// Why the operation failed.
+    pub reason: String,
+}
+
+impl PatchError {
+    /// Creates a new patch error.
+    #[must_use]
+    pub fn new(index: usize, path: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            index,
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A payload could not be compressed or decompressed.
+#[derive(Debug, Error)]
+#[error("{codec} codec failed: {source}")]
+pub struct CodecError {
+    /// Name of the codec.
+    pub codec: String,
+    /// The underlying failure.
+    #[source]
+    pub source: std::io::Error,
+}
+
+impl CodecError {
+    /// Creates a new codec error.
+    #[must_use]
+    pub fn new(codec: impl Into<String>, source: std::io::Error) -> Self {
+        Self {
+            codec: codec.into(),
+            source,
+        }
+    }
+}
+
+impl From<CodecError> for StageflowError {
+    fn from(err: CodecError) -> Self {
+        Self::Serialization(err.to_string())
+    }
+}
+
+/// An event could not be published to a stream broker.
+#[derive(Debug, Clone, Error)]
+pub enum PublishError {
+    /// The event could not be encoded.
+    #[error("Failed to serialize event: {0}")]
+    Serialization(String),
+
+    /// The broker rejected the message or could not be reached.
+    #[error("{broker} publish failed: {message}")]
+    Broker {
+        /// Name of the broker, e.g. `kafka`.
+        broker: String,
+        /// What went wrong.
+        message: String,
+    },
+}
+
+impl PublishError {
+    /// Creates a broker error.
+    #[must_use]
+    pub fn broker(broker: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Broker {
+            broker: broker.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// An error from parsing JSON under a [`NumberPolicy`](crate::utils::NumberPolicy).
+#[derive(Debug, Error)]
+pub enum JsonParseError {
+    /// The text is not valid JSON or does not match the target type.
+    #[error("Invalid JSON: {0}")]
+    Invalid(#[from] serde_json::Error),
+
+    /// The text holds a number the policy rejects.
+    #[error("{0}")]
+    Precision(#[from] NumberPrecisionError),
+}
+
+impl From<JsonParseError> for StageflowError {
+    fn from(err: JsonParseError) -> Self {
+        match err {
+            JsonParseError::Invalid(e) => Self::Serialization(e.to_string()),
+            JsonParseError::Precision(e) => Self::NumberPrecision(e),
+        }
+    }
+}
+
 /// Errors related to tool execution.
 #[derive(Debug, Clone, Error)]
 pub enum ToolError {
@@ -330,6 +732,15 @@ pub enum ToolError {
         timeout_seconds: f64,
     },
 
+    /// Tool input did not match the tool's parameter schema.
+    #[error("Invalid input for tool: {name} - {}", format_violations(.violations))]
+    InvalidInput {
+        /// The tool name.
+        name: String,
+        /// Every schema violation found in the input.
+        violations: Vec<SchemaViolation>,
+    },
+
     /// Tool undo failed.
     #[error("Undo failed for tool: {name} - {reason}")]
     UndoFailed {
@@ -385,6 +796,15 @@ impl ToolError {
         }
     }
 
+    /// Creates an invalid input error.
+    #[must_use]
+    pub fn invalid_input(name: impl Into<String>, violations: Vec<SchemaViolation>) -> Self {
+        Self::InvalidInput {
+            name: name.into(),
+            violations,
+        }
+    }
+
     /// Creates an undo failed error.
     #[must_use]
     pub fn undo_failed(name: impl Into<String>, reason: impl Into<String>) -> Self {
@@ -403,6 +823,15 @@ impl ToolError {
         }
     }
 
+    /// Returns true if retrying the call may succeed.
+    ///
+    /// Execution failures and approval timeouts are treated as transient;
+    /// missing tools, denials and invalid input are not.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::ExecutionFailed { .. } | Self::ApprovalTimeout { .. })
+    }
+
     /// Converts to a dictionary representation.
     #[must_use]
     pub fn to_dict(&self) -> HashMap<String, serde_json::Value> {
@@ -428,6 +857,11 @@ impl ToolError {
                 map.insert("request_id".to_string(), serde_json::json!(request_id));
                 map.insert("timeout_seconds".to_string(), serde_json::json!(timeout_seconds));
             }
+            Self::InvalidInput { name, violations } => {
+                map.insert("type".to_string(), serde_json::json!("ToolInvalidInput"));
+                map.insert("name".to_string(), serde_json::json!(name));
+                map.insert("violations".to_string(), serde_json::json!(violations));
+            }
             Self::UndoFailed { name, reason } => {
                 map.insert("type".to_string(), serde_json::json!("ToolUndoError"));
                 map.insert("name".to_string(), serde_json::json!(name));
@@ -445,6 +879,15 @@ impl ToolError {
     }
 }
 
+/// Joins schema violations into a single line for error messages.
+fn format_violations(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 /// Provides default suggestions for common contract error codes.
 pub struct ContractSuggestions;
 
@@ -477,6 +920,30 @@ impl ContractSuggestions {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_from_errors_flattens_aggregates() {
+        assert!(StageflowError::from_errors(Vec::<StageflowError>::new()).is_none());
+        let single = StageflowError::from_errors([StageflowError::Internal("a".into())]).unwrap();
+        assert!(matches!(single, StageflowError::Internal(_)));
+
+        let nested = StageflowError::from_errors([
+            StageflowError::Internal("a".into()),
+            StageflowError::Cancelled("b".into()),
+        ])
+        .unwrap();
+        let err = StageflowError::from_errors([nested, StageflowError::Internal("c".into())]).unwrap();
+        assert_eq!(err.errors().len(), 3);
+        assert_eq!(
+            err.to_string(),
+            "3 errors occurred: Internal error: a; Pipeline cancelled: b; Internal error: c"
+        );
+        assert!(!err.is_retryable());
+
+        let results = vec![Ok(1), Err(PipelineValidationError::new("x")), Ok(2)];
+        assert_eq!(StageflowError::collect(results).unwrap_err().errors().len(), 1);
+        assert_eq!(StageflowError::collect([Ok::<_, StageflowError>(1), Ok(2)]).unwrap(), vec![1, 2]);
+    }
+
     #[test]
     fn test_contract_error_info_creation() {
         let info = ContractErrorInfo::new("TEST-001", "Test error")
@@ -0,0 +1,329 @@
+//! Run export bundles for support tickets and offline debugging.
+//!
+//! [`export_run`] gathers everything a [`RunStateStore`] knows about a run
+//! into a [`RunBundle`], which [`to_archive`](RunBundle::to_archive) writes as
+//! a single compressed file. [`import_run`] loads a bundle into another store
+//! so the run can be inspected locally.
+
+use super::{EventFilter, RecordedEvent, RunRecord, RunStateStore, DEFAULT_EVENT_PAGE_SIZE};
+use crate::compression::{decompress, Codec};
+use crate::context::ContextSnapshot;
+use crate::core::{StageArtifact, StageOutput};
+use crate::errors::StageflowError;
+use crate::pipeline::PipelineSpec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Version of the [`RunBundle`] archive format.
+pub const BUNDLE_FORMAT_VERSION: u64 = 1;
+
+/// Default size above which artifacts are exported as references.
+pub const DEFAULT_MAX_INLINE_ARTIFACT_BYTES: usize = 64 * 1024;
+
+/// An artifact in a [`RunBundle`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BundledArtifact {
+    /// The artifact with its data.
+    Inline {
+        /// The stage that produced it.
+        stage: String,
+        /// The artifact.
+        artifact: StageArtifact,
+    },
+    /// An artifact whose data exceeded the inline limit.
+    Reference {
+        /// The stage that produced it.
+        stage: String,
+        /// The artifact id.
+        id: String,
+        /// The artifact name.
+        name: String,
+        /// The artifact type.
+        artifact_type: String,
+        /// Size of the serialized data that was left out.
+        size_bytes: usize,
+    },
+}
+
+impl BundledArtifact {
+    /// Returns the stage that produced the artifact.
+    #[must_use]
+    pub fn stage(&self) -> &str {
+        match self {
+            Self::Inline { stage, .. } | Self::Reference { stage, .. } => stage,
+        }
+    }
+
+    /// Returns true if the artifact data is included.
+    #[must_use]
+    pub fn is_inline(&self) -> bool {
+        matches!(self, Self::Inline { .. })
+    }
+}
+
+/// Everything recorded about one run, as exported by [`export_run`].
+///
+/// Stage outputs are stored without their artifacts, which are listed
+/// separately in [`artifacts`](Self::artifacts).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunBundle {
+    /// The archive format version.
+    pub format_version: u64,
+    /// The exported run.
+    pub run_id: String,
+    /// When the bundle was created (ISO 8601).
+    pub exported_at: String,
+    /// The pipeline the run executed.
+    pub pipeline: Option<PipelineSpec>,
+    /// The context at the end of the run.
+    pub snapshot: Option<ContextSnapshot>,
+    /// Stage outputs keyed by stage name.
+    #[serde(default)]
+    pub outputs: HashMap<String, StageOutput>,
+    /// The run's events, oldest first.
+    #[serde(default)]
+    pub events: Vec<RecordedEvent>,
+    /// Artifacts produced by the stages.
+    #[serde(default)]
+    pub artifacts: Vec<BundledArtifact>,
+}
+
+impl RunBundle {
+    /// Serializes the bundle as JSON and compresses it with `codec`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or compression fails.
+    pub fn to_archive(&self, codec: &dyn Codec) -> Result<Vec<u8>, StageflowError> {
+        let json = serde_json::to_vec(self).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        Ok(codec.encode(&json)?)
+    }
+
+    /// Reads a bundle written by [`to_archive`](Self::to_archive).
+    ///
+    /// The codec is detected from the archive's magic bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive cannot be decompressed, is not a
+    /// bundle, or was written by a newer format version.
+    pub fn from_archive(bytes: &[u8]) -> Result<Self, StageflowError> {
+        let json = decompress(bytes)?;
+        let bundle: Self =
+            serde_json::from_slice(&json).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        if bundle.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(StageflowError::Serialization(format!(
+                "Unsupported run bundle format version {} (supported up to {BUNDLE_FORMAT_VERSION})",
+                bundle.format_version
+            )));
+        }
+        Ok(bundle)
+    }
+
+    /// Returns the stage outputs with their inlined artifacts restored.
+    #[must_use]
+    pub fn outputs_with_artifacts(&self) -> HashMap<String, StageOutput> {
+        let mut outputs = self.outputs.clone();
+        for artifact in &self.artifacts {
+            if let BundledArtifact::Inline { stage, artifact } = artifact {
+                if let Some(output) = outputs.get_mut(stage) {
+                    output.artifacts.push(artifact.clone());
+                }
+            }
+        }
+        outputs
+    }
+}
+
+/// Options for [`export_run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Artifacts whose serialized data is larger than this are exported as
+    /// references.
+    pub max_inline_artifact_bytes: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            max_inline_artifact_bytes: DEFAULT_MAX_INLINE_ARTIFACT_BYTES,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Creates options with the default inline limit.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the inline artifact size limit.
+    #[must_use]
+    pub fn with_max_inline_artifact_bytes(mut self, max: usize) -> Self {
+        self.max_inline_artifact_bytes = max;
+        self
+    }
+}
+
+/// Gathers the record and every event of `run_id` into a bundle.
+///
+/// # Errors
+///
+/// Returns an error if the store fails, or if it has neither events nor a
+/// record for the run.
+pub async fn export_run(
+    store: &dyn RunStateStore,
+    run_id: &str,
+    options: &ExportOptions,
+) -> Result<RunBundle, StageflowError> {
+    let record = store.get_run(run_id).await?;
+    let filter = EventFilter::new();
+    let mut events = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = store
+            .list_events(run_id, &filter, cursor, DEFAULT_EVENT_PAGE_SIZE)
+            .await?;
+        events.extend(page.events);
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    if record.is_none() && events.is_empty() {
+        return Err(StageflowError::Internal(format!("No recorded run '{run_id}'")));
+    }
+
+    let RunRecord {
+        pipeline,
+        snapshot,
+        mut outputs,
+        ..
+    } = record.unwrap_or_default();
+    let mut artifacts = Vec::new();
+    let mut stages: Vec<(&String, &mut StageOutput)> = outputs.iter_mut().collect();
+    stages.sort_by(|a, b| a.0.cmp(b.0));
+    for (stage, output) in stages {
+        for artifact in std::mem::take(&mut output.artifacts) {
+            let size_bytes = serde_json::to_vec(&artifact.data).map_or(0, |data| data.len());
+            artifacts.push(if size_bytes <= options.max_inline_artifact_bytes {
+                BundledArtifact::Inline {
+                    stage: stage.clone(),
+                    artifact,
+                }
+            } else {
+                BundledArtifact::Reference {
+                    stage: stage.clone(),
+                    id: artifact.id,
+                    name: artifact.name,
+                    artifact_type: artifact.artifact_type,
+                    size_bytes,
+                }
+            });
+        }
+    }
+
+    Ok(RunBundle {
+        format_version: BUNDLE_FORMAT_VERSION,
+        run_id: run_id.to_string(),
+        exported_at: crate::utils::iso_timestamp(),
+        pipeline,
+        snapshot,
+        outputs,
+        events,
+        artifacts,
+    })
+}
+
+/// Loads a bundle's events and record into `store`.
+///
+/// Only inlined artifacts are restored onto their stage outputs.
+///
+/// # Errors
+///
+/// Returns an error if the store fails.
+pub async fn import_run(store: &dyn RunStateStore, bundle: &RunBundle) -> Result<(), StageflowError> {
+    store.import_events(&bundle.run_id, bundle.events.clone()).await?;
+    store
+        .save_run(RunRecord {
+            run_id: bundle.run_id.clone(),
+            pipeline: bundle.pipeline.clone(),
+            snapshot: bundle.snapshot.clone(),
+            outputs: bundle.outputs_with_artifacts(),
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::InMemoryRunStateStore;
+    use serde_json::json;
+
+    fn artifact(id: &str, data: serde_json::Value) -> StageArtifact {
+        StageArtifact::new("report", id, id, data)
+    }
+
+    #[tokio::test]
+    async fn test_export_archive_and_import_round_trip() {
+        let source = InMemoryRunStateStore::new();
+        source.append("run-1", "stage.started", Some(json!({"stage": "a"}))).await.unwrap();
+        source.append("run-1", "stage.completed", Some(json!({"stage": "a"}))).await.unwrap();
+        let output = StageOutput::ok_value("answer", json!(42)).with_artifacts(vec![
+            artifact("small", json!("ok")),
+            artifact("large", json!("x".repeat(100))),
+        ]);
+        source
+            .save_run(
+                RunRecord::new("run-1")
+                    .with_pipeline(PipelineSpec::new("demo").unwrap())
+                    .with_output("a", output),
+            )
+            .await
+            .unwrap();
+
+        let options = ExportOptions::new().with_max_inline_artifact_bytes(32);
+        let bundle = export_run(&source, "run-1", &options).await.unwrap();
+        assert_eq!(bundle.events.len(), 2);
+        assert!(bundle.outputs["a"].artifacts.is_empty());
+        let inline: Vec<bool> = bundle.artifacts.iter().map(BundledArtifact::is_inline).collect();
+        assert_eq!(inline, vec![true, false]);
+
+        let archive = bundle.to_archive(&crate::compression::IdentityCodec).unwrap();
+        let restored = RunBundle::from_archive(&archive).unwrap();
+
+        let target = InMemoryRunStateStore::new();
+        import_run(&target, &restored).await.unwrap();
+        let page = target.list_events("run-1", &EventFilter::new(), None, 10).await.unwrap();
+        let summary = |events: &[RecordedEvent]| -> Vec<(u64, String)> {
+            events.iter().map(|e| (e.seq, e.event_type.clone())).collect()
+        };
+        assert_eq!(summary(&page.events), summary(&bundle.events));
+        let record = target.get_run("run-1").await.unwrap().unwrap();
+        assert_eq!(record.pipeline.unwrap().name, "demo");
+        assert_eq!(record.outputs["a"].artifacts.len(), 1);
+        assert_eq!(record.outputs["a"].get("answer"), Some(&json!(42)));
+
+        // Appends after an import continue the original sequence
+        let next = target.append("run-1", "stage.started", None).await.unwrap();
+        assert_eq!(next.seq, 3);
+    }
+
+    #[tokio::test]
+    async fn test_export_unknown_run_and_newer_format_fail() {
+        let store = InMemoryRunStateStore::new();
+        assert!(export_run(&store, "missing", &ExportOptions::new()).await.is_err());
+
+        let newer = json!({
+            "format_version": BUNDLE_FORMAT_VERSION + 1,
+            "run_id": "r",
+            "exported_at": "",
+            "pipeline": null,
+            "snapshot": null,
+        });
+        let err = RunBundle::from_archive(newer.to_string().as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("Unsupported run bundle format"));
+    }
+}
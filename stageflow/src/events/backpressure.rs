@@ -5,7 +5,7 @@ use async_trait::async_trait;
 use parking_lot::RwLock;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::warn;
 
@@ -81,6 +81,22 @@ impl BackpressureMetrics {
     }
 }
 
+/// What happens to an event that arrives while the queue is full.
+///
+/// `try_emit` can never wait, so it always drops when the queue is full;
+/// the policy only changes the behavior of the async `emit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// `emit` waits for space in the queue.
+    #[default]
+    Block,
+    /// `emit` drops the event immediately, like `try_emit`.
+    DropNewest,
+}
+
+/// Callback invoked with the type and data of a dropped event.
+pub type DropCallback = Arc<dyn Fn(&str, &Option<serde_json::Value>) + Send + Sync>;
+
 /// Event message for the internal queue.
 struct EventMessage {
     event_type: String,
@@ -103,11 +119,13 @@ pub struct BackpressureAwareEventSink {
     /// Maximum queue size.
     max_queue_size: usize,
     /// Whether the worker is running.
-    running: AtomicBool,
+    running: Arc<AtomicBool>,
+    /// Behavior of `emit` when the queue is full.
+    drop_policy: RwLock<DropPolicy>,
     /// Backpressure metrics.
     metrics: Arc<BackpressureMetrics>,
     /// Optional callback when events are dropped.
-    on_drop: RwLock<Option<Arc<dyn Fn(&str, &Option<serde_json::Value>) + Send + Sync>>>,
+    on_drop: RwLock<Option<DropCallback>>,
     /// Worker task handle.
     worker_handle: RwLock<Option<tokio::task::JoinHandle<()>>>,
 }
@@ -123,7 +141,8 @@ impl BackpressureAwareEventSink {
             tx,
             rx: RwLock::new(Some(rx)),
             max_queue_size,
-            running: AtomicBool::new(false),
+            running: Arc::new(AtomicBool::new(false)),
+            drop_policy: RwLock::new(DropPolicy::default()),
             metrics: Arc::new(BackpressureMetrics::default()),
             on_drop: RwLock::new(None),
             worker_handle: RwLock::new(None),
@@ -144,6 +163,17 @@ impl BackpressureAwareEventSink {
         *self.on_drop.write() = Some(Arc::new(callback));
     }
 
+    /// Sets the behavior of `emit` when the queue is full.
+    pub fn set_drop_policy(&self, policy: DropPolicy) {
+        *self.drop_policy.write() = policy;
+    }
+
+    /// Returns the current drop policy.
+    #[must_use]
+    pub fn drop_policy(&self) -> DropPolicy {
+        *self.drop_policy.read()
+    }
+
     /// Starts the background worker.
     pub async fn start(self: &Arc<Self>) {
         if self.running.swap(true, Ordering::SeqCst) {
@@ -156,8 +186,7 @@ impl BackpressureAwareEventSink {
         }
 
         let downstream = self.downstream.clone();
-        let running = Arc::new(AtomicBool::new(true));
-        let running_clone = running.clone();
+        let running_clone = self.running.clone();
 
         let handle = tokio::spawn(async move {
             let mut receiver = rx.take().unwrap();
@@ -188,21 +217,40 @@ impl BackpressureAwareEventSink {
     }
 
     /// Stops the background worker.
+    ///
+    /// With `drain`, waits up to `timeout_secs` for queued events to reach
+    /// the downstream sink before the worker is cancelled. Negative or NaN
+    /// timeouts count as zero; infinite or overflowing ones wait without a
+    /// deadline.
     pub async fn stop(&self, drain: bool, timeout_secs: f64) {
-        if !self.running.swap(false, Ordering::SeqCst) {
+        if !self.running.load(Ordering::SeqCst) {
             return; // Not running
         }
 
+        let deadline = Duration::try_from_secs_f64(timeout_secs.max(0.0))
+            .ok()
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        let before_deadline = || deadline.map_or(true, |deadline| Instant::now() < deadline);
         if drain {
             // Wait for queue to drain with timeout
-            let deadline = Instant::now() + std::time::Duration::from_secs_f64(timeout_secs);
-            while Instant::now() < deadline && !self.tx.is_closed() {
+            while before_deadline() && self.queue_size() > 0 {
                 tokio::time::sleep(std::time::Duration::from_millis(10)).await;
             }
         }
-
-        // Cancel worker task
-        if let Some(handle) = self.worker_handle.write().take() {
+        self.running.store(false, Ordering::SeqCst);
+
+        let handle = self.worker_handle.write().take();
+        if let Some(mut handle) = handle {
+            if drain {
+                // Let the worker finish the event it is emitting
+                let remaining = deadline.map_or(Duration::MAX, |deadline| {
+                    deadline.saturating_duration_since(Instant::now())
+                });
+                if tokio::time::timeout(remaining, &mut handle).await.is_ok() {
+                    return;
+                }
+            }
+            // Cancel worker task
             handle.abort();
             let _ = handle.await;
         }
@@ -225,49 +273,60 @@ impl BackpressureAwareEventSink {
     pub fn metrics(&self) -> &BackpressureMetrics {
         &self.metrics
     }
+
+    /// Records, logs, and reports an event dropped due to backpressure.
+    fn drop_event(&self, event_type: &str, data: &Option<serde_json::Value>) {
+        self.metrics.record_drop();
+
+        let queue_size = self.queue_size();
+        let dropped_total = self.metrics.dropped();
+
+        warn!(
+            event_type = %event_type,
+            queue_size = %queue_size,
+            dropped_total = %dropped_total,
+            "Event dropped due to backpressure"
+        );
+
+        if let Some(ref callback) = *self.on_drop.read() {
+            callback(event_type, data);
+        }
+    }
 }
 
 #[async_trait]
 impl EventSink for BackpressureAwareEventSink {
     async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        if self.drop_policy() == DropPolicy::DropNewest {
+            self.try_emit(event_type, data);
+            return;
+        }
+
         let msg = EventMessage {
             event_type: event_type.to_string(),
             data,
         };
 
-        if self.tx.send(msg).await.is_ok() {
-            self.metrics.record_emit();
-        } else {
-            self.metrics.record_drop();
+        match self.tx.send(msg).await {
+            Ok(()) => self.metrics.record_emit(),
+            Err(mpsc::error::SendError(msg)) => self.drop_event(event_type, &msg.data),
         }
     }
 
     fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
         let msg = EventMessage {
             event_type: event_type.to_string(),
-            data: data.clone(),
+            data,
         };
 
         match self.tx.try_send(msg) {
             Ok(()) => {
                 self.metrics.record_emit();
             }
-            Err(_) => {
-                self.metrics.record_drop();
-
-                let queue_size = self.queue_size();
-                let dropped_total = self.metrics.dropped();
-
-                warn!(
-                    event_type = %event_type,
-                    queue_size = %queue_size,
-                    dropped_total = %dropped_total,
-                    "Event dropped due to backpressure"
-                );
-
-                if let Some(ref callback) = *self.on_drop.read() {
-                    callback(event_type, &data);
-                }
+            Err(
+                mpsc::error::TrySendError::Full(msg) | mpsc::error::TrySendError::Closed(msg),
+            ) => {
+                self.drop_event(event_type, &msg.data);
             }
         }
     }
@@ -327,4 +386,55 @@ mod tests {
         
         assert_eq!(sink.metrics().emitted(), 1);
     }
+
+    #[tokio::test]
+    async fn test_drop_newest_policy() {
+        let downstream = Arc::new(CollectingEventSink::new());
+        let sink = BackpressureAwareEventSink::new(downstream, 1);
+        sink.set_drop_policy(DropPolicy::DropNewest);
+
+        let dropped = Arc::new(AtomicU64::new(0));
+        let counter = dropped.clone();
+        sink.set_on_drop(move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        // The worker is not running, so only the first event fits
+        sink.emit("a", None).await;
+        sink.emit("b", None).await;
+        sink.try_emit("c", None);
+
+        assert_eq!(sink.metrics().emitted(), 1);
+        assert_eq!(sink.metrics().dropped(), 2);
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stop_drains_queue() {
+        let downstream = Arc::new(CollectingEventSink::new());
+        let sink = BackpressureAwareEventSink::new(downstream.clone(), 10);
+        for i in 0..5 {
+            sink.try_emit("test.event", Some(serde_json::json!({"i": i})));
+        }
+
+        sink.start().await;
+        sink.stop(true, 1.0).await;
+
+        assert!(!sink.is_running());
+        assert_eq!(sink.queue_size(), 0);
+        assert_eq!(downstream.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_stop_accepts_unusable_timeouts() {
+        for timeout in [f64::INFINITY, 1e30, f64::NAN, -1.0] {
+            let downstream = Arc::new(CollectingEventSink::new());
+            let sink = BackpressureAwareEventSink::new(downstream.clone(), 10);
+            sink.try_emit("test.event", None);
+
+            sink.start().await;
+            sink.stop(true, timeout).await;
+            assert!(!sink.is_running());
+        }
+    }
 }
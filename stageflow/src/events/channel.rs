@@ -0,0 +1,388 @@
+//! Event sink backed by a bounded queue.
+
+use super::{BackpressureMetrics, EventSink};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// What a [`ChannelEventSink`] does with an event that arrives while its
+/// queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowStrategy {
+    /// Evicts the oldest queued event to make room.
+    DropOldest,
+    /// Drops the arriving event.
+    #[default]
+    DropNewest,
+    /// `emit` waits up to the timeout for room, then drops the event.
+    /// `try_emit` cannot wait and drops immediately.
+    BlockWithTimeout(Duration),
+}
+
+struct QueuedEvent {
+    event_type: String,
+    data: Option<serde_json::Value>,
+}
+
+/// Bounded queue shared by the sink and its consumer task.
+///
+/// The lock is only held to push or pop, never across an await, so the
+/// sink can always evict the oldest event.
+struct EventQueue {
+    events: Mutex<VecDeque<QueuedEvent>>,
+    capacity: usize,
+    closed: AtomicBool,
+    ready: Notify,
+    space: Notify,
+}
+
+impl EventQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            closed: AtomicBool::new(false),
+            ready: Notify::new(),
+            space: Notify::new(),
+        }
+    }
+
+    /// Queues an event, returning the event it evicted, or the event
+    /// itself if there was no room or the queue is closed.
+    fn push(&self, event: QueuedEvent, evict_oldest: bool) -> Result<Option<QueuedEvent>, QueuedEvent> {
+        if self.is_closed() {
+            return Err(event);
+        }
+        let mut events = self.events.lock();
+        let evicted = if events.len() < self.capacity {
+            None
+        } else if evict_oldest {
+            events.pop_front()
+        } else {
+            return Err(event);
+        };
+        events.push_back(event);
+        drop(events);
+        self.ready.notify_one();
+        Ok(evicted)
+    }
+
+    fn pop(&self) -> Option<QueuedEvent> {
+        let event = self.events.lock().pop_front();
+        if event.is_some() {
+            self.space.notify_one();
+        }
+        event
+    }
+
+    /// Waits for the next event, or `None` once the queue is closed.
+    async fn next(&self) -> Option<QueuedEvent> {
+        loop {
+            if let Some(event) = self.pop() {
+                return Some(event);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.events.lock().len()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.ready.notify_one();
+        self.space.notify_waiters();
+    }
+}
+
+/// Event sink that hands events to a downstream sink through a bounded
+/// queue.
+///
+/// A background task feeds the downstream sink one event at a time, so an
+/// expensive consumer never slows down the stages emitting events; when it
+/// falls behind, the [`OverflowStrategy`] decides what is dropped and
+/// [`metrics`](Self::metrics) counts it. The task stops when the sink is
+/// dropped or [`shutdown`](Self::shutdown) is called.
+pub struct ChannelEventSink {
+    queue: Arc<EventQueue>,
+    overflow: OverflowStrategy,
+    metrics: Arc<BackpressureMetrics>,
+    stop: Arc<Notify>,
+    worker: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl ChannelEventSink {
+    /// Creates the sink and spawns the task feeding `downstream`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or if called outside a tokio runtime.
+    #[must_use]
+    pub fn spawn(downstream: Arc<dyn EventSink>, capacity: usize, overflow: OverflowStrategy) -> Arc<Self> {
+        assert!(capacity > 0, "channel event sink capacity must be positive");
+        let queue = Arc::new(EventQueue::new(capacity));
+        let stop = Arc::new(Notify::new());
+        let worker_queue = Arc::clone(&queue);
+        let worker_stop = Arc::clone(&stop);
+        let worker = tokio::spawn(async move {
+            loop {
+                let next = tokio::select! {
+                    event = worker_queue.next() => event,
+                    () = worker_stop.notified() => None,
+                };
+                let Some(event) = next else { break };
+                downstream.emit(&event.event_type, event.data).await;
+            }
+        });
+        Arc::new(Self {
+            queue,
+            overflow,
+            metrics: Arc::new(BackpressureMetrics::default()),
+            stop,
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+
+    /// Returns the overflow strategy.
+    #[must_use]
+    pub fn overflow(&self) -> OverflowStrategy {
+        self.overflow
+    }
+
+    /// Returns the queue capacity.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity
+    }
+
+    /// Returns the number of queued events.
+    #[must_use]
+    pub fn queue_size(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the queued and dropped event counters.
+    #[must_use]
+    pub fn metrics(&self) -> &BackpressureMetrics {
+        &self.metrics
+    }
+
+    /// Waits up to `timeout` for queued events to reach the downstream sink,
+    /// then stops the background task.
+    ///
+    /// Returns true if the queue drained in time. Events emitted afterwards
+    /// are dropped.
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.queue_size() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let drained = self.queue_size() == 0;
+        let worker = self.worker.lock().take();
+        if let Some(mut worker) = worker {
+            // Let the consumer finish the event it is handling
+            self.stop.notify_one();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if tokio::time::timeout(remaining, &mut worker).await.is_err() {
+                worker.abort();
+                let _ = worker.await;
+            }
+        }
+        self.queue.close();
+        drained
+    }
+
+    fn enqueue(&self, event: QueuedEvent) {
+        match self.queue.push(event, self.overflow == OverflowStrategy::DropOldest) {
+            Ok(evicted) => {
+                if let Some(evicted) = evicted {
+                    self.record_drop(&evicted.event_type);
+                }
+                self.metrics.record_emit();
+            }
+            Err(event) => self.record_drop(&event.event_type),
+        }
+    }
+
+    fn record_drop(&self, event_type: &str) {
+        self.metrics.record_drop();
+        warn!(
+            event_type = %event_type,
+            overflow = ?self.overflow,
+            dropped_total = self.metrics.dropped(),
+            "Event dropped by channel sink"
+        );
+    }
+}
+
+impl Drop for ChannelEventSink {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+impl std::fmt::Debug for ChannelEventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelEventSink")
+            .field("capacity", &self.capacity())
+            .field("overflow", &self.overflow)
+            .field("queue_size", &self.queue_size())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl EventSink for ChannelEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let event = QueuedEvent {
+            event_type: event_type.to_string(),
+            data,
+        };
+        let OverflowStrategy::BlockWithTimeout(timeout) = self.overflow else {
+            return self.enqueue(event);
+        };
+        let queued = tokio::time::timeout(timeout, async {
+            let mut event = event;
+            loop {
+                match self.queue.push(event, false) {
+                    Ok(_) => return true,
+                    Err(_) if self.queue.is_closed() => return false,
+                    Err(rejected) => event = rejected,
+                }
+                self.queue.space.notified().await;
+            }
+        })
+        .await;
+        if matches!(queued, Ok(true)) {
+            self.metrics.record_emit();
+        } else {
+            self.record_drop(event_type);
+        }
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.enqueue(QueuedEvent {
+            event_type: event_type.to_string(),
+            data,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::CollectingEventSink;
+    use tokio::sync::Semaphore;
+
+    /// Collects events, but only once a permit is released for each.
+    struct GatedSink {
+        gate: Arc<Semaphore>,
+        inner: Arc<CollectingEventSink>,
+    }
+
+    #[async_trait]
+    impl EventSink for GatedSink {
+        async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+            self.gate.acquire().await.unwrap().forget();
+            self.inner.emit(event_type, data).await;
+        }
+
+        fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+            self.inner.try_emit(event_type, data);
+        }
+    }
+
+    async fn stalled(overflow: OverflowStrategy) -> (Arc<ChannelEventSink>, Arc<Semaphore>, Arc<CollectingEventSink>) {
+        let gate = Arc::new(Semaphore::new(0));
+        let collected = Arc::new(CollectingEventSink::new());
+        let downstream = Arc::new(GatedSink {
+            gate: Arc::clone(&gate),
+            inner: Arc::clone(&collected),
+        });
+        let sink = ChannelEventSink::spawn(downstream, 2, overflow);
+        // The consumer takes the first event and waits on the gate
+        sink.try_emit("e0", None);
+        while sink.queue_size() > 0 {
+            tokio::task::yield_now().await;
+        }
+        (sink, gate, collected)
+    }
+
+    fn types(sink: &CollectingEventSink) -> Vec<String> {
+        sink.events().into_iter().map(|(t, _)| t).collect()
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_latest_events() {
+        let (sink, gate, collected) = stalled(OverflowStrategy::DropOldest).await;
+        for event in ["e1", "e2", "e3", "e4"] {
+            sink.try_emit(event, None);
+        }
+        assert_eq!(sink.metrics().dropped(), 2);
+
+        gate.add_permits(10);
+        assert!(sink.shutdown(Duration::from_secs(1)).await);
+        assert_eq!(types(&collected), vec!["e0", "e3", "e4"]);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_while_consumer_waits_for_events() {
+        let collected = Arc::new(CollectingEventSink::new());
+        let sink = ChannelEventSink::spawn(collected.clone(), 2, OverflowStrategy::DropOldest);
+        // Park the consumer on the empty queue; it cannot run again until
+        // this task yields, so the events below pile up behind it.
+        tokio::task::yield_now().await;
+        for event in ["e1", "e2", "e3", "e4"] {
+            sink.try_emit(event, None);
+        }
+        assert_eq!(sink.metrics().dropped(), 2);
+        assert_eq!(sink.metrics().emitted(), 4);
+
+        assert!(sink.shutdown(Duration::from_secs(1)).await);
+        assert_eq!(types(&collected), vec!["e3", "e4"]);
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_and_block_with_timeout() {
+        let (sink, gate, collected) = stalled(OverflowStrategy::DropNewest).await;
+        for event in ["e1", "e2", "e3"] {
+            sink.emit(event, None).await;
+        }
+        assert_eq!(sink.metrics().dropped(), 1);
+        gate.add_permits(10);
+        assert!(sink.shutdown(Duration::from_secs(1)).await);
+        assert_eq!(types(&collected), vec!["e0", "e1", "e2"]);
+
+        let (sink, gate, collected) =
+            stalled(OverflowStrategy::BlockWithTimeout(Duration::from_millis(200))).await;
+        sink.emit("e1", None).await;
+        sink.emit("e2", None).await;
+        sink.emit("e3", None).await;
+        assert_eq!(sink.metrics().dropped(), 1);
+
+        // Room frees up while `emit` waits
+        let waiting = {
+            let sink = Arc::clone(&sink);
+            tokio::spawn(async move { sink.emit("e4", None).await })
+        };
+        gate.add_permits(1);
+        waiting.await.unwrap();
+        gate.add_permits(10);
+        assert!(sink.shutdown(Duration::from_secs(1)).await);
+        assert_eq!(types(&collected), vec!["e0", "e1", "e2", "e4"]);
+        assert_eq!(sink.metrics().emitted(), 4);
+    }
+}
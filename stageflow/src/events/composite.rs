@@ -0,0 +1,315 @@
+//! Fan-out event sink with per-sink routing.
+//!
+//! A [`CompositeEventSink`] forwards each event to every child sink whose
+//! route matches the event type, so a run can send everything to a file
+//! while only `stage.*` events reach analytics. Children are isolated from
+//! each other: a panicking sink is contained, and in [`EventSink::emit`]
+//! children run concurrently with a per-sink timeout.
+
+use super::{EmittedEvent, EventSink};
+use async_trait::async_trait;
+use futures::future::join_all;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Default time a child sink gets to handle an event in [`EventSink::emit`].
+pub const DEFAULT_SINK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns true if `event_type` matches `pattern`.
+///
+/// `*` matches any run of characters, dots included, so `stage.*` matches
+/// `stage.completed` and `*.failed` matches `stage.failed`.
+#[must_use]
+pub fn event_type_matches(pattern: &str, event_type: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let text = event_type.as_bytes();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// A child sink and the events it receives.
+///
+/// With no include patterns every event is included; exclude patterns are
+/// applied after includes.
+pub struct SinkRoute {
+    name: String,
+    sink: Arc<dyn EventSink>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    timeout: Duration,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl SinkRoute {
+    /// Creates a route sending every event to `sink`.
+    #[must_use]
+    pub fn new(name: impl Into<String>, sink: Arc<dyn EventSink>) -> Self {
+        Self {
+            name: name.into(),
+            sink,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            timeout: DEFAULT_SINK_TIMEOUT,
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    /// Only forwards events matching `pattern`, or any other include pattern.
+    #[must_use]
+    pub fn with_include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Never forwards events matching `pattern`.
+    #[must_use]
+    pub fn with_exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Sets how long the sink gets to handle an event in
+    /// [`EventSink::emit`] before it is abandoned.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the route name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if the route forwards events of `event_type`.
+    #[must_use]
+    pub fn accepts(&self, event_type: &str) -> bool {
+        let included = self.include.is_empty()
+            || self.include.iter().any(|p| event_type_matches(p, event_type));
+        included && !self.exclude.iter().any(|p| event_type_matches(p, event_type))
+    }
+
+    /// Returns the route's delivery counters.
+    #[must_use]
+    pub fn metrics(&self) -> SinkRouteMetrics {
+        SinkRouteMetrics {
+            delivered: self.delivered.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record(&self, ok: bool) {
+        let counter = if ok { &self.delivered } else { &self.failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let delivery = AssertUnwindSafe(self.sink.emit(event_type, data)).catch_unwind();
+        let ok = match tokio::time::timeout(self.timeout, delivery).await {
+            Ok(Ok(())) => true,
+            Ok(Err(_)) => {
+                warn!(sink = %self.name, event_type, "Event sink panicked");
+                false
+            }
+            Err(_) => {
+                warn!(
+                    sink = %self.name,
+                    event_type,
+                    timeout_ms = self.timeout.as_millis(),
+                    "Event sink timed out"
+                );
+                false
+            }
+        };
+        self.record(ok);
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let delivered =
+            std::panic::catch_unwind(AssertUnwindSafe(|| self.sink.try_emit(event_type, data)));
+        if delivered.is_err() {
+            warn!(sink = %self.name, event_type, "Event sink panicked");
+        }
+        self.record(delivered.is_ok());
+    }
+
+    fn try_emit_event(&self, event: &Arc<EmittedEvent>) {
+        let delivered = std::panic::catch_unwind(AssertUnwindSafe(|| self.sink.try_emit_event(Arc::clone(event))));
+        if delivered.is_err() {
+            warn!(sink = %self.name, event_type = event.event_type(), "Event sink panicked");
+        }
+        self.record(delivered.is_ok());
+    }
+}
+
+impl std::fmt::Debug for SinkRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SinkRoute")
+            .field("name", &self.name)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Delivery counters of a [`SinkRoute`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SinkRouteMetrics {
+    /// Events the sink accepted.
+    pub delivered: u64,
+    /// Events the sink panicked on or timed out on.
+    pub failed: u64,
+}
+
+/// Event sink that fans events out to several child sinks.
+///
+/// Install it as the global sink, or on a context, to feed more than one
+/// destination. [`try_emit`](EventSink::try_emit) calls children in order
+/// on the caller's thread, so children doing slow work there should be
+/// wrapped in a [`BackpressureAwareEventSink`](super::BackpressureAwareEventSink).
+#[derive(Debug, Default)]
+pub struct CompositeEventSink {
+    routes: Vec<SinkRoute>,
+}
+
+impl CompositeEventSink {
+    /// Creates a sink with no children.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a child receiving every event.
+    #[must_use]
+    pub fn with_sink(self, name: impl Into<String>, sink: Arc<dyn EventSink>) -> Self {
+        self.with_route(SinkRoute::new(name, sink))
+    }
+
+    /// Adds a routed child.
+    #[must_use]
+    pub fn with_route(mut self, route: SinkRoute) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Returns the routes, in the order they were added.
+    #[must_use]
+    pub fn routes(&self) -> &[SinkRoute] {
+        &self.routes
+    }
+
+    /// Returns the delivery counters of the route named `name`.
+    #[must_use]
+    pub fn metrics(&self, name: &str) -> Option<SinkRouteMetrics> {
+        self.routes
+            .iter()
+            .find(|route| route.name == name)
+            .map(SinkRoute::metrics)
+    }
+
+    fn matching<'a>(&'a self, event_type: &'a str) -> impl Iterator<Item = &'a SinkRoute> + 'a {
+        self.routes.iter().filter(move |route| route.accepts(event_type))
+    }
+}
+
+#[async_trait]
+impl EventSink for CompositeEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        join_all(
+            self.matching(event_type)
+                .map(|route| route.emit(event_type, data.clone())),
+        )
+        .await;
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        for route in self.matching(event_type) {
+            route.try_emit(event_type, data.clone());
+        }
+    }
+
+    fn try_emit_event(&self, event: Arc<EmittedEvent>) {
+        // Children share the event, so its payload is built at most once
+        for route in self.matching(event.event_type()) {
+            route.try_emit_event(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::CollectingEventSink;
+
+    struct StuckSink;
+
+    #[async_trait]
+    impl EventSink for StuckSink {
+        async fn emit(&self, _event_type: &str, _data: Option<serde_json::Value>) {
+            std::future::pending::<()>().await;
+        }
+
+        fn try_emit(&self, _event_type: &str, _data: Option<serde_json::Value>) {
+            panic!("sink is broken");
+        }
+    }
+
+    #[test]
+    fn test_event_type_patterns() {
+        assert!(event_type_matches("*", "stage.completed"));
+        assert!(event_type_matches("stage.*", "stage.completed"));
+        assert!(event_type_matches("*.failed", "tool.call.failed"));
+        assert!(event_type_matches("pipeline.completed", "pipeline.completed"));
+        assert!(!event_type_matches("stage.*", "pipeline.completed"));
+        assert!(!event_type_matches("stage.*.x", "stage.completed"));
+    }
+
+    #[tokio::test]
+    async fn test_routes_filter_and_isolate_sinks() {
+        let everything = Arc::new(CollectingEventSink::new());
+        let analytics = Arc::new(CollectingEventSink::new());
+        let sink = CompositeEventSink::new()
+            .with_sink("file", everything.clone())
+            .with_route(
+                SinkRoute::new("analytics", analytics.clone())
+                    .with_include("stage.*")
+                    .with_exclude("stage.started"),
+            )
+            .with_route(SinkRoute::new("stuck", Arc::new(StuckSink)).with_timeout(Duration::from_millis(10)));
+
+        sink.try_emit("stage.started", None);
+        sink.try_emit("stage.completed", None);
+        sink.emit("pipeline.completed", None).await;
+
+        assert_eq!(everything.len(), 3);
+        let analytics_types: Vec<String> = analytics.events().into_iter().map(|(t, _)| t).collect();
+        assert_eq!(analytics_types, vec!["stage.completed"]);
+        assert_eq!(sink.metrics("analytics"), Some(SinkRouteMetrics { delivered: 1, failed: 0 }));
+        assert_eq!(sink.metrics("stuck"), Some(SinkRouteMetrics { delivered: 0, failed: 3 }));
+    }
+}
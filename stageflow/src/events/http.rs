@@ -0,0 +1,369 @@
+//! Event sink that ships batches of events to an HTTP endpoint.
+
+use super::{register_buffered_sink, track_pending_task, BackpressureMetrics, BufferedEventSink, EventSink};
+use crate::pipeline::{with_retry, RetryConfig};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tracing::warn;
+
+/// Default number of events per request.
+pub const DEFAULT_HTTP_BATCH_SIZE: usize = 100;
+
+/// Default time after which a partial batch is sent.
+pub const DEFAULT_HTTP_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default number of events held while the endpoint is unreachable.
+pub const DEFAULT_HTTP_MAX_BUFFERED: usize = 10_000;
+
+/// Event sink that POSTs batches of events as JSON.
+///
+/// Each request body is `{"events": [{"event_type", "data", "timestamp"}]}`.
+/// A batch is sent once it is full or after the flush interval, and
+/// failed requests are retried with the sink's [`RetryConfig`]; batches
+/// that still fail are dropped and counted in [`metrics`](Self::metrics).
+/// Batches are sent one at a time, in order.
+///
+/// [`wait_for_event_sink_tasks`](super::wait_for_event_sink_tasks) sends
+/// whatever is buffered and waits for all requests in flight, so call it
+/// before the process exits.
+pub struct HttpEventSink {
+    client: reqwest::Client,
+    url: String,
+    headers: HashMap<String, String>,
+    batch_size: usize,
+    flush_interval: Duration,
+    max_buffered: usize,
+    retry: RetryConfig,
+    buffer: Mutex<Vec<serde_json::Value>>,
+    sending: tokio::sync::Mutex<()>,
+    metrics: BackpressureMetrics,
+    self_ref: Weak<Self>,
+}
+
+impl HttpEventSink {
+    /// Creates a sink posting to `url`. Call [`spawn`](Self::spawn) to
+    /// start it.
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            headers: HashMap::new(),
+            batch_size: DEFAULT_HTTP_BATCH_SIZE,
+            flush_interval: DEFAULT_HTTP_FLUSH_INTERVAL,
+            max_buffered: DEFAULT_HTTP_MAX_BUFFERED,
+            retry: RetryConfig::default(),
+            buffer: Mutex::new(Vec::new()),
+            sending: tokio::sync::Mutex::new(()),
+            metrics: BackpressureMetrics::default(),
+            self_ref: Weak::new(),
+        }
+    }
+
+    /// Uses `client` for requests, e.g. to set timeouts or TLS options.
+    #[must_use]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Adds a header to every request.
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Authenticates requests with a bearer token.
+    #[must_use]
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        let value = format!("Bearer {}", token.as_ref());
+        self.with_header("Authorization", value)
+    }
+
+    /// Sets the number of events per request.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets how long a partial batch waits before it is sent.
+    #[must_use]
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Sets how many unsent events are held before new ones are dropped.
+    #[must_use]
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered;
+        self
+    }
+
+    /// Sets how failed requests are retried.
+    #[must_use]
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Starts the sink and its flush timer.
+    ///
+    /// The timer stops once the returned sink is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a tokio runtime.
+    #[must_use]
+    pub fn spawn(self) -> Arc<Self> {
+        let sink = Arc::new_cyclic(|self_ref| Self {
+            self_ref: self_ref.clone(),
+            ..self
+        });
+        let buffered: Weak<dyn BufferedEventSink> = Arc::downgrade(&sink) as Weak<dyn BufferedEventSink>;
+        register_buffered_sink(buffered);
+
+        let timer_ref = Arc::downgrade(&sink);
+        let interval = sink.flush_interval;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(sink) = timer_ref.upgrade() else { break };
+                if sink.buffered() > 0 {
+                    track_pending_task(sink.spawn_flush());
+                }
+            }
+        });
+        sink
+    }
+
+    /// Returns the endpoint URL.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the number of events waiting to be sent.
+    #[must_use]
+    pub fn buffered(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    /// Returns the sent (`emitted`) and lost (`dropped`) event counters.
+    #[must_use]
+    pub fn metrics(&self) -> &BackpressureMetrics {
+        &self.metrics
+    }
+
+    /// Sends every buffered event.
+    pub async fn flush(&self) {
+        let _sending = self.sending.lock().await;
+        loop {
+            let batch: Vec<serde_json::Value> = {
+                let mut buffer = self.buffer.lock();
+                let take = buffer.len().min(self.batch_size);
+                buffer.drain(..take).collect()
+            };
+            if batch.is_empty() {
+                return;
+            }
+            self.send(batch).await;
+        }
+    }
+
+    /// Buffers an event, returning true if a full batch is ready.
+    fn push(&self, event_type: &str, data: Option<serde_json::Value>) -> bool {
+        let mut buffer = self.buffer.lock();
+        if buffer.len() >= self.max_buffered {
+            drop(buffer);
+            self.metrics.record_drop();
+            warn!(url = %self.url, event_type, "HTTP event sink buffer full; event dropped");
+            return false;
+        }
+        let mut event = serde_json::Map::new();
+        event.insert("event_type".to_string(), serde_json::json!(event_type));
+        event.insert("data".to_string(), data.unwrap_or_default());
+        event.insert("timestamp".to_string(), serde_json::json!(crate::utils::iso_timestamp()));
+        buffer.push(serde_json::Value::Object(event));
+        buffer.len() >= self.batch_size
+    }
+
+    async fn send(&self, batch: Vec<serde_json::Value>) {
+        let count = batch.len();
+        let body = serde_json::json!({ "events": batch });
+        let result = with_retry(&self.retry, &self.url, || async {
+            let mut builder = self.client.post(&self.url).json(&body);
+            for (name, value) in &self.headers {
+                builder = builder.header(name, value);
+            }
+            builder
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map(drop)
+        })
+        .await;
+
+        match result {
+            Ok(()) => (0..count).for_each(|_| self.metrics.record_emit()),
+            Err(e) => {
+                warn!(url = %self.url, events = count, error = %e, "Failed to send event batch");
+                (0..count).for_each(|_| self.metrics.record_drop());
+            }
+        }
+    }
+}
+
+impl BufferedEventSink for HttpEventSink {
+    fn spawn_flush(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move { self.flush().await })
+    }
+}
+
+impl std::fmt::Debug for HttpEventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpEventSink")
+            .field("url", &self.url)
+            .field("batch_size", &self.batch_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("buffered", &self.buffered())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl EventSink for HttpEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        if self.push(event_type, data) {
+            self.flush().await;
+        }
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        if !self.push(event_type, data) {
+            return;
+        }
+        let Some(sink) = self.self_ref.upgrade() else {
+            return;
+        };
+        if tokio::runtime::Handle::try_current().is_ok() {
+            track_pending_task(sink.spawn_flush());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::wait_for_event_sink_tasks;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `status(n)` for the n-th request and records request heads
+    /// and bodies.
+    async fn collector<F>(status: F) -> (String, Arc<Mutex<Vec<(String, serde_json::Value)>>>)
+    where
+        F: Fn(usize) -> u16 + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&received);
+        let count = Arc::new(AtomicUsize::new(0));
+        let status = Arc::new(status);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let n = count.fetch_add(1, Ordering::SeqCst);
+                let code = status(n);
+                let recorded = Arc::clone(&recorded);
+                tokio::spawn(async move {
+                    let mut raw = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let (head, body) = loop {
+                        let read = socket.read(&mut buf).await.unwrap_or(0);
+                        raw.extend_from_slice(&buf[..read]);
+                        let text = String::from_utf8_lossy(&raw).to_string();
+                        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                            let length = head
+                                .lines()
+                                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                                .and_then(|v| v.parse::<usize>().ok())
+                                .unwrap_or(0);
+                            if body.len() >= length || read == 0 {
+                                break (head.to_lowercase(), body.to_string());
+                            }
+                        } else if read == 0 {
+                            break (text, String::new());
+                        }
+                    };
+                    if code == 200 {
+                        recorded.lock().push((head, serde_json::from_str(&body).unwrap_or_default()));
+                    }
+                    let response = format!("HTTP/1.1 {code} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (url, received)
+    }
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig::new().with_base_delay_ms(1).with_max_delay_ms(1)
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_posted_with_auth_and_flushed_on_shutdown() {
+        let (url, received) = collector(|_| 200).await;
+        let sink = HttpEventSink::new(url)
+            .with_bearer_token("secret")
+            .with_batch_size(2)
+            .with_flush_interval(Duration::from_secs(3600))
+            .with_retry(fast_retry())
+            .spawn();
+
+        for i in 0..3 {
+            sink.try_emit("stage.completed", Some(serde_json::json!({ "i": i })));
+        }
+        wait_for_event_sink_tasks().await;
+
+        let received = received.lock();
+        let sizes: Vec<usize> = received
+            .iter()
+            .map(|(_, body)| body["events"].as_array().map_or(0, Vec::len))
+            .collect();
+        assert_eq!(sizes, vec![2, 1]);
+        assert!(received[0].0.contains("authorization: bearer secret"));
+        assert_eq!(received[1].1["events"][0]["data"]["i"], 2);
+        assert_eq!(sink.metrics().emitted(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_requests_are_retried() {
+        let (url, received) = collector(|n| if n == 0 { 503 } else { 200 }).await;
+        let sink = HttpEventSink::new(url).with_retry(fast_retry()).spawn();
+
+        sink.emit("pipeline.completed", None).await;
+        sink.flush().await;
+
+        assert_eq!(received.lock().len(), 1);
+        assert_eq!(sink.metrics().emitted(), 1);
+        assert_eq!(sink.metrics().dropped(), 0);
+
+        let failing = HttpEventSink::new(sink.url())
+            .with_retry(fast_retry().with_max_attempts(0))
+            .with_max_buffered(1)
+            .spawn();
+        failing.try_emit("a", None);
+        failing.try_emit("b", None);
+        assert_eq!(failing.metrics().dropped(), 1);
+    }
+}
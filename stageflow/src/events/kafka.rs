@@ -0,0 +1,88 @@
+//! Kafka publisher for [`StreamEventSink`](super::StreamEventSink).
+
+use super::{StreamMessage, StreamPublisher};
+use crate::errors::PublishError;
+use async_trait::async_trait;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use std::time::Duration;
+
+/// Default time a message may wait in the producer queue.
+pub const DEFAULT_KAFKA_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Publishes stream messages to Kafka topics.
+///
+/// The run ID is the record key, so events of a run land on one partition
+/// in order. An acknowledged publish waits for the delivery report, whose
+/// strength follows the producer's `acks` setting.
+pub struct KafkaPublisher {
+    producer: FutureProducer,
+    queue_timeout: Duration,
+}
+
+impl KafkaPublisher {
+    /// Publishes through `producer`.
+    #[must_use]
+    pub fn new(producer: FutureProducer) -> Self {
+        Self {
+            producer,
+            queue_timeout: DEFAULT_KAFKA_QUEUE_TIMEOUT,
+        }
+    }
+
+    /// Sets how long a message may wait for room in the producer queue.
+    #[must_use]
+    pub fn with_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+}
+
+impl std::fmt::Debug for KafkaPublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaPublisher")
+            .field("queue_timeout", &self.queue_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl StreamPublisher for KafkaPublisher {
+    fn broker(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn publish(&self, message: StreamMessage, ack: bool) -> Result<(), PublishError> {
+        let headers = message
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain([("event-id", message.id.as_str())])
+            .fold(OwnedHeaders::new(), |headers, (key, value)| {
+                headers.insert(Header {
+                    key,
+                    value: Some(value),
+                })
+            });
+        let mut record: FutureRecord<'_, str, [u8]> = FutureRecord::to(&message.topic)
+            .payload(message.payload.as_slice())
+            .headers(headers);
+        if let Some(key) = &message.key {
+            record = record.key(key.as_str());
+        }
+
+        if ack {
+            self.producer
+                .send(record, self.queue_timeout)
+                .await
+                .map(drop)
+                .map_err(|(e, _)| PublishError::broker("kafka", e.to_string()))
+        } else {
+            // Dropping the delivery future does not cancel the send
+            self.producer
+                .send_result(record)
+                .map(drop)
+                .map_err(|(e, _)| PublishError::broker("kafka", e.to_string()))
+        }
+    }
+}
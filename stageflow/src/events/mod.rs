@@ -3,19 +3,80 @@
 //! This module provides the event emission infrastructure used throughout
 //! the stageflow framework for logging, monitoring, and analytics.
 
+mod archive;
 mod backpressure;
+mod channel;
+mod composite;
+#[cfg(feature = "webhooks")]
+mod http;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+mod redaction;
 mod sink;
-
-pub use backpressure::{BackpressureAwareEventSink, BackpressureMetrics};
-pub use sink::{EventSink, LoggingEventSink, NoOpEventSink};
-
-use parking_lot::RwLock;
-use std::sync::Arc;
+mod store;
+mod stream;
+mod typed;
+
+pub use archive::{
+    export_run, import_run, BundledArtifact, ExportOptions, RunBundle, BUNDLE_FORMAT_VERSION,
+    DEFAULT_MAX_INLINE_ARTIFACT_BYTES,
+};
+pub use backpressure::{BackpressureAwareEventSink, BackpressureMetrics, DropCallback, DropPolicy};
+pub use channel::{ChannelEventSink, OverflowStrategy};
+pub use composite::{
+    event_type_matches, CompositeEventSink, SinkRoute, SinkRouteMetrics, DEFAULT_SINK_TIMEOUT,
+};
+#[cfg(feature = "webhooks")]
+pub use http::{
+    HttpEventSink, DEFAULT_HTTP_BATCH_SIZE, DEFAULT_HTTP_FLUSH_INTERVAL, DEFAULT_HTTP_MAX_BUFFERED,
+};
+#[cfg(feature = "kafka")]
+pub use kafka::{KafkaPublisher, DEFAULT_KAFKA_QUEUE_TIMEOUT};
+#[cfg(feature = "nats")]
+pub use nats::NatsPublisher;
+pub use redaction::{RedactingEventSink, RedactionMetrics, RedactionRule, DEFAULT_PRESERVED_KEYS};
+pub use sink::{CollectingEventSink, EventSink, LoggingEventSink, NoOpEventSink};
+pub use store::{
+    EventFilter, EventPage, EventRetention, FileRunStateStore, InMemoryRunStateStore, RecordedEvent, RunRecord,
+    RunStateEventSink, RunStateStore, DEFAULT_EVENT_PAGE_SIZE,
+};
+pub use stream::{
+    DeliveryGuarantee, EnvelopeSerializer, InMemoryStreamPublisher, JsonEnvelopeSerializer, StreamEnvelope,
+    StreamEventSink, StreamMessage, StreamPublisher, ENVELOPE_AVRO_SCHEMA, ENVELOPE_SCHEMA_VERSION,
+};
+pub use typed::{EmittedEvent, EventMetadata, PipelineEvent};
+
+use parking_lot::{Mutex, RwLock};
+use std::future::Future;
+use std::sync::{Arc, Weak};
 use tokio::sync::RwLock as TokioRwLock;
 
-// Global event sink stored in a task-local-like context
+// Process-wide fallback sink
 static GLOBAL_EVENT_SINK: RwLock<Option<Arc<dyn EventSink>>> = RwLock::new(None);
 
+tokio::task_local! {
+    static SCOPED_EVENT_SINK: Arc<dyn EventSink>;
+}
+
+/// Runs a future with `sink` as the current event sink.
+///
+/// Pipeline contexts created inside the future pick up `sink` instead of
+/// the global one, so concurrent pipelines in one process can report to
+/// different sinks. The scope is task-local: tasks spawned from the future
+/// see the global sink unless they are scoped too.
+pub async fn with_event_sink<F: Future>(sink: Arc<dyn EventSink>, future: F) -> F::Output {
+    SCOPED_EVENT_SINK.scope(sink, future).await
+}
+
+/// Returns the sink scoped with [`with_event_sink`] for the current task, if
+/// any.
+#[must_use]
+pub fn scoped_event_sink() -> Option<Arc<dyn EventSink>> {
+    SCOPED_EVENT_SINK.try_with(Arc::clone).ok()
+}
+
 /// Sets the current global event sink.
 pub fn set_event_sink(sink: Arc<dyn EventSink>) {
     *GLOBAL_EVENT_SINK.write() = Some(sink);
@@ -26,13 +87,13 @@ pub fn clear_event_sink() {
     *GLOBAL_EVENT_SINK.write() = None;
 }
 
-/// Gets the current global event sink.
+/// Gets the current event sink.
 ///
-/// Returns a `NoOpEventSink` if no sink is set.
+/// Returns the sink scoped with [`with_event_sink`] if there is one, else
+/// the global sink, else a `NoOpEventSink`.
 pub fn get_event_sink() -> Arc<dyn EventSink> {
-    GLOBAL_EVENT_SINK
-        .read()
-        .clone()
+    scoped_event_sink()
+        .or_else(|| GLOBAL_EVENT_SINK.read().clone())
         .unwrap_or_else(|| Arc::new(NoOpEventSink))
 }
 
@@ -44,8 +105,49 @@ pub async fn register_pending_task(handle: tokio::task::JoinHandle<()>) {
     PENDING_TASKS.write().await.push(handle);
 }
 
+/// Tracks a pending task from synchronous code.
+pub(crate) fn track_pending_task(handle: tokio::task::JoinHandle<()>) {
+    match PENDING_TASKS.try_write() {
+        Ok(mut tasks) => {
+            tasks.retain(|task| !task.is_finished());
+            tasks.push(handle);
+        }
+        Err(_) => {
+            tokio::spawn(register_pending_task(handle));
+        }
+    }
+}
+
+/// A sink that holds events back and delivers them in the background.
+pub(crate) trait BufferedEventSink: Send + Sync {
+    /// Starts delivering the buffered events.
+    fn spawn_flush(self: Arc<Self>) -> tokio::task::JoinHandle<()>;
+}
+
+/// Buffered sinks flushed by [`wait_for_event_sink_tasks`].
+static BUFFERED_SINKS: Mutex<Vec<Weak<dyn BufferedEventSink>>> = Mutex::new(Vec::new());
+
+/// Registers a buffered sink to flush on [`wait_for_event_sink_tasks`].
+#[cfg(feature = "webhooks")]
+pub(crate) fn register_buffered_sink(sink: Weak<dyn BufferedEventSink>) {
+    let mut sinks = BUFFERED_SINKS.lock();
+    sinks.retain(|sink| sink.strong_count() > 0);
+    sinks.push(sink);
+}
+
 /// Waits for all pending event sink tasks to complete.
+///
+/// Sinks that buffer events, such as the HTTP sink, are flushed first.
 pub async fn wait_for_event_sink_tasks() {
+    let buffered: Vec<Arc<dyn BufferedEventSink>> = {
+        let mut sinks = BUFFERED_SINKS.lock();
+        sinks.retain(|sink| sink.strong_count() > 0);
+        sinks.iter().filter_map(Weak::upgrade).collect()
+    };
+    for sink in buffered {
+        register_pending_task(sink.spawn_flush()).await;
+    }
+
     let mut tasks = PENDING_TASKS.write().await;
     if tasks.is_empty() {
         return;
@@ -81,4 +183,24 @@ mod tests {
 
         clear_event_sink();
     }
+
+    #[tokio::test]
+    async fn test_scoped_sinks_are_isolated_per_task() {
+        let run = |sink: Arc<CollectingEventSink>, event: &'static str| {
+            tokio::spawn(with_event_sink(sink, async move {
+                let ctx = crate::context::PipelineContext::new(crate::context::RunIdentity::new());
+                tokio::task::yield_now().await;
+                crate::context::ExecutionContext::try_emit_event(&ctx, event, None);
+            }))
+        };
+        let first = Arc::new(CollectingEventSink::new());
+        let second = Arc::new(CollectingEventSink::new());
+        let (a, b) = tokio::join!(run(first.clone(), "first.event"), run(second.clone(), "second.event"));
+        a.unwrap();
+        b.unwrap();
+
+        assert_eq!(first.events().into_iter().map(|(t, _)| t).collect::<Vec<_>>(), vec!["first.event"]);
+        assert_eq!(second.events().into_iter().map(|(t, _)| t).collect::<Vec<_>>(), vec!["second.event"]);
+        assert!(scoped_event_sink().is_none());
+    }
 }
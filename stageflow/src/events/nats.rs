@@ -0,0 +1,88 @@
+//! NATS publisher for [`StreamEventSink`](super::StreamEventSink).
+
+use super::{StreamMessage, StreamPublisher};
+use crate::errors::PublishError;
+use async_trait::async_trait;
+
+enum Connection {
+    Core(async_nats::Client),
+    JetStream(async_nats::jetstream::Context),
+}
+
+/// Publishes stream messages to NATS subjects.
+///
+/// Core NATS has no broker acknowledgment, so an acknowledged publish only
+/// waits for the client to flush. Use [`jetstream`](Self::jetstream) for
+/// stored, acknowledged delivery; it sets `Nats-Msg-Id` to the event ID so
+/// the server drops retried duplicates.
+pub struct NatsPublisher {
+    connection: Connection,
+}
+
+impl NatsPublisher {
+    /// Publishes through core NATS.
+    #[must_use]
+    pub fn new(client: async_nats::Client) -> Self {
+        Self {
+            connection: Connection::Core(client),
+        }
+    }
+
+    /// Publishes through `JetStream`.
+    #[must_use]
+    pub fn jetstream(context: async_nats::jetstream::Context) -> Self {
+        Self {
+            connection: Connection::JetStream(context),
+        }
+    }
+}
+
+impl std::fmt::Debug for NatsPublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match self.connection {
+            Connection::Core(_) => "core",
+            Connection::JetStream(_) => "jetstream",
+        };
+        f.debug_struct("NatsPublisher").field("mode", &mode).finish()
+    }
+}
+
+#[async_trait]
+impl StreamPublisher for NatsPublisher {
+    fn broker(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn publish(&self, message: StreamMessage, ack: bool) -> Result<(), PublishError> {
+        let mut headers = async_nats::HeaderMap::new();
+        for (name, value) in &message.headers {
+            headers.insert(name.as_str(), value.as_str());
+        }
+        if let Some(key) = &message.key {
+            headers.insert("pipeline-run-id", key.as_str());
+        }
+        let error = |e: &dyn std::fmt::Display| PublishError::broker("nats", e.to_string());
+        match &self.connection {
+            Connection::Core(client) => {
+                client
+                    .publish_with_headers(message.topic, headers, message.payload.into())
+                    .await
+                    .map_err(|e| error(&e))?;
+                if ack {
+                    client.flush().await.map_err(|e| error(&e))?;
+                }
+            }
+            Connection::JetStream(context) => {
+                headers.insert("Nats-Msg-Id", message.id.as_str());
+                let pending = context
+                    .publish_with_headers(message.topic, headers, message.payload.into())
+                    .await
+                    .map_err(|e| error(&e))?;
+                if ack {
+                    pending.await.map_err(|e| error(&e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
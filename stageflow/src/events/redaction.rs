@@ -0,0 +1,252 @@
+//! Event sink that scrubs sensitive content before forwarding events.
+
+use super::EventSink;
+use crate::helpers::PIIDetector;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Top-level event fields left untouched by default: identifiers and
+/// timestamps added by the context, which patterns could otherwise mangle.
+pub const DEFAULT_PRESERVED_KEYS: &[&str] = &["pipeline_run_id", "request_id", "emitted_at", "sequence"];
+
+/// A named pattern whose matches are replaced in event strings.
+#[derive(Debug, Clone)]
+pub struct RedactionRule {
+    name: String,
+    pattern: Regex,
+    replacement: String,
+}
+
+impl RedactionRule {
+    /// Creates a rule replacing matches of `pattern` with `[REDACTED:<name>]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the regex error if `pattern` does not compile.
+    pub fn new(name: impl Into<String>, pattern: &str) -> Result<Self, regex::Error> {
+        let name = name.into();
+        Ok(Self {
+            replacement: format!("[REDACTED:{name}]"),
+            pattern: Regex::new(pattern)?,
+            name,
+        })
+    }
+
+    /// Replaces matches with `replacement`, which may refer to capture
+    /// groups as `$1` or `$name`.
+    #[must_use]
+    pub fn with_replacement(mut self, replacement: impl Into<String>) -> Self {
+        self.replacement = replacement.into();
+        self
+    }
+
+    /// Returns the rule name used in metrics.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Redaction counters of a [`RedactingEventSink`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionMetrics {
+    /// Events received.
+    pub events: u64,
+    /// Events forwarded with at least one value redacted.
+    pub redacted_events: u64,
+    /// Values redacted, keyed by rule name or PII type.
+    pub redactions: BTreeMap<String, u64>,
+}
+
+/// Event sink that redacts event data before forwarding it.
+///
+/// Every string in the event data, at any depth, is passed through the
+/// [`RedactionRule`]s in order and then through the [`PIIDetector`], if one
+/// is set. Object keys and the [`DEFAULT_PRESERVED_KEYS`] at the top level
+/// are left as they are.
+pub struct RedactingEventSink {
+    inner: Arc<dyn EventSink>,
+    rules: Vec<RedactionRule>,
+    detector: Option<PIIDetector>,
+    preserved_keys: HashSet<String>,
+    events: AtomicU64,
+    redacted_events: AtomicU64,
+    redactions: Mutex<BTreeMap<String, u64>>,
+}
+
+impl RedactingEventSink {
+    /// Wraps `inner`. Add rules or a detector; with neither, events pass
+    /// through unchanged.
+    #[must_use]
+    pub fn new(inner: Arc<dyn EventSink>) -> Self {
+        Self {
+            inner,
+            rules: Vec::new(),
+            detector: None,
+            preserved_keys: DEFAULT_PRESERVED_KEYS.iter().map(ToString::to_string).collect(),
+            events: AtomicU64::new(0),
+            redacted_events: AtomicU64::new(0),
+            redactions: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Adds a redaction rule, applied after those added before it.
+    #[must_use]
+    pub fn with_rule(mut self, rule: RedactionRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Redacts the PII `detector` finds, after the rules ran.
+    #[must_use]
+    pub fn with_pii_detector(mut self, detector: PIIDetector) -> Self {
+        self.detector = Some(detector);
+        self
+    }
+
+    /// Leaves the top-level field `key` untouched.
+    #[must_use]
+    pub fn with_preserved_key(mut self, key: impl Into<String>) -> Self {
+        self.preserved_keys.insert(key.into());
+        self
+    }
+
+    /// Returns the redaction counters.
+    #[must_use]
+    pub fn metrics(&self) -> RedactionMetrics {
+        RedactionMetrics {
+            events: self.events.load(Ordering::Relaxed),
+            redacted_events: self.redacted_events.load(Ordering::Relaxed),
+            redactions: self.redactions.lock().clone(),
+        }
+    }
+
+    /// Returns `data` redacted, recording what was replaced.
+    fn redact(&self, data: Option<serde_json::Value>) -> Option<serde_json::Value> {
+        self.events.fetch_add(1, Ordering::Relaxed);
+        let mut data = data?;
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        match &mut data {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if !self.preserved_keys.contains(key) {
+                        self.redact_value(value, &mut counts);
+                    }
+                }
+            }
+            other => self.redact_value(other, &mut counts),
+        }
+        if !counts.is_empty() {
+            self.redacted_events.fetch_add(1, Ordering::Relaxed);
+            let mut totals = self.redactions.lock();
+            for (name, count) in counts {
+                *totals.entry(name).or_insert(0) += count;
+            }
+        }
+        Some(data)
+    }
+
+    fn redact_value(&self, value: &mut serde_json::Value, counts: &mut BTreeMap<String, u64>) {
+        match value {
+            serde_json::Value::String(text) => {
+                if let Some(redacted) = self.redact_text(text, counts) {
+                    *text = redacted;
+                }
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item, counts)),
+            serde_json::Value::Object(map) => map.values_mut().for_each(|item| self.redact_value(item, counts)),
+            _ => {}
+        }
+    }
+
+    /// Returns the redacted text, or `None` if nothing matched.
+    fn redact_text(&self, text: &str, counts: &mut BTreeMap<String, u64>) -> Option<String> {
+        let mut current: Option<String> = None;
+        for rule in &self.rules {
+            let source = current.as_deref().unwrap_or(text);
+            let matches = rule.pattern.find_iter(source).count();
+            if matches > 0 {
+                *counts.entry(rule.name.clone()).or_insert(0) += matches as u64;
+                current = Some(rule.pattern.replace_all(source, rule.replacement.as_str()).into_owned());
+            }
+        }
+        if let Some(detector) = &self.detector {
+            let source = current.as_deref().unwrap_or(text);
+            let found = detector.find(source);
+            if !found.is_empty() {
+                for m in &found {
+                    *counts.entry(m.pii_type.to_string()).or_insert(0) += 1;
+                }
+                current = Some(detector.redact(source));
+            }
+        }
+        current
+    }
+}
+
+impl std::fmt::Debug for RedactingEventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedactingEventSink")
+            .field("rules", &self.rules.iter().map(RedactionRule::name).collect::<Vec<_>>())
+            .field("pii_detector", &self.detector.is_some())
+            .field("metrics", &self.metrics())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl EventSink for RedactingEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.inner.emit(event_type, self.redact(data)).await;
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.inner.try_emit(event_type, self.redact(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::CollectingEventSink;
+
+    #[tokio::test]
+    async fn test_redacts_rules_and_pii_with_metrics() {
+        let inner = Arc::new(CollectingEventSink::new());
+        let sink = RedactingEventSink::new(inner.clone())
+            .with_rule(RedactionRule::new("api_key", r"sk-[A-Za-z0-9]{8,}").unwrap())
+            .with_rule(RedactionRule::new("account", r"acct-(\d+)").unwrap().with_replacement("acct-***"))
+            .with_pii_detector(PIIDetector::default());
+
+        sink.emit(
+            "stage.completed",
+            Some(serde_json::json!({
+                "pipeline_run_id": "555-12-3456",
+                "input": "I am bob@example.com, key sk-abcdef123456",
+                "turns": [{ "text": "acct-991 from 10.0.0.7" }, 42],
+            })),
+        )
+        .await;
+        sink.try_emit("stage.started", Some(serde_json::json!({ "stage": "fetch" })));
+        sink.try_emit("stage.skipped", None);
+
+        let events = inner.events();
+        let data = events[0].1.as_ref().unwrap();
+        assert_eq!(data["pipeline_run_id"], "555-12-3456");
+        assert_eq!(data["input"], "I am [REDACTED:email], key [REDACTED:api_key]");
+        assert_eq!(data["turns"][0]["text"], "acct-*** from [REDACTED:ip_address]");
+        assert_eq!(data["turns"][1], 42);
+        assert_eq!(events[1].1.as_ref().unwrap()["stage"], "fetch");
+
+        let metrics = sink.metrics();
+        assert_eq!(metrics.events, 3);
+        assert_eq!(metrics.redacted_events, 1);
+        let redactions: Vec<(&str, u64)> = metrics.redactions.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(redactions, vec![("account", 1), ("api_key", 1), ("email", 1), ("ip_address", 1)]);
+    }
+}
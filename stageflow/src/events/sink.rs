@@ -1,7 +1,9 @@
 //! Event sink trait and implementations.
 
+use super::EmittedEvent;
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{debug, info, Level};
 
 /// Trait for event sinks that can receive events.
@@ -23,6 +25,17 @@ pub trait EventSink: Send + Sync {
     /// This method should never raise an exception. Errors are logged
     /// but suppressed.
     fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>);
+
+    /// Tries to emit a typed event without blocking.
+    ///
+    /// The default forwards the event's JSON payload to
+    /// [`try_emit`](Self::try_emit). Sinks that drop events or can use the
+    /// typed [`PipelineEvent`](super::PipelineEvent) override it to skip
+    /// serialization.
+    fn try_emit_event(&self, event: Arc<EmittedEvent>) {
+        let (event_type, data) = event.into_payload();
+        self.try_emit(&event_type, Some(data));
+    }
 }
 
 /// A no-op event sink that discards all events.
@@ -40,6 +53,10 @@ impl EventSink for NoOpEventSink {
     fn try_emit(&self, _event_type: &str, _data: Option<serde_json::Value>) {
         // Intentionally empty - discards all events
     }
+
+    fn try_emit_event(&self, _event: Arc<EmittedEvent>) {
+        // Intentionally empty - the payload is never built
+    }
 }
 
 /// An event sink that logs events using the tracing framework.
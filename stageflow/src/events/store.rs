@@ -0,0 +1,819 @@
+//! Persisted per-run event log with filtering, pagination and retention.
+//!
+//! A [`RunStateStore`] keeps the events of each pipeline run so UIs can page
+//! through them after the fact. Events reach the store through
+//! [`RunStateEventSink`], which records every event carrying a
+//! `pipeline_run_id`. [`FileRunStateStore`] keeps the logs on disk so they
+//! survive a restart.
+
+use super::EventSink;
+use crate::context::ContextSnapshot;
+use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::pipeline::PipelineSpec;
+use crate::utils::{decode_path_component, encode_path_component, TtlClock};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default page size for [`RunStateStore::list_events`].
+pub const DEFAULT_EVENT_PAGE_SIZE: usize = 100;
+
+/// An event recorded for a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Position in the run's log, increasing from 1.
+    pub seq: u64,
+    /// The run the event belongs to.
+    pub run_id: String,
+    /// The event type, e.g. `stage.completed`.
+    pub event_type: String,
+    /// Unix timestamp in seconds.
+    pub timestamp: f64,
+    /// The event payload.
+    pub data: Option<serde_json::Value>,
+}
+
+/// What a store keeps about a run besides its events.
+///
+/// Saved with [`RunStateStore::save_run`] and gathered into a
+/// [`RunBundle`](super::RunBundle) by [`export_run`](super::export_run).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// The run id.
+    pub run_id: String,
+    /// The pipeline the run executed.
+    pub pipeline: Option<PipelineSpec>,
+    /// The context at the end of the run.
+    pub snapshot: Option<ContextSnapshot>,
+    /// Stage outputs keyed by stage name.
+    #[serde(default)]
+    pub outputs: HashMap<String, StageOutput>,
+}
+
+impl RunRecord {
+    /// Creates an empty record for `run_id`.
+    #[must_use]
+    pub fn new(run_id: impl Into<String>) -> Self {
+        Self {
+            run_id: run_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the pipeline spec.
+    #[must_use]
+    pub fn with_pipeline(mut self, pipeline: PipelineSpec) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    /// Sets the final context snapshot.
+    #[must_use]
+    pub fn with_snapshot(mut self, snapshot: ContextSnapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Adds a stage output.
+    #[must_use]
+    pub fn with_output(mut self, stage: impl Into<String>, output: StageOutput) -> Self {
+        self.outputs.insert(stage.into(), output);
+        self
+    }
+}
+
+/// Criteria for selecting events.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Event type prefixes to include; empty includes every type.
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    /// Only events at or after this Unix timestamp.
+    pub since: Option<f64>,
+    /// Only events before this Unix timestamp.
+    pub until: Option<f64>,
+}
+
+impl EventFilter {
+    /// Creates a filter matching every event.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Includes events whose type starts with `prefix`.
+    #[must_use]
+    pub fn with_event_type(mut self, prefix: impl Into<String>) -> Self {
+        self.event_types.push(prefix.into());
+        self
+    }
+
+    /// Limits events to the half-open range `[since, until)`.
+    #[must_use]
+    pub fn with_time_range(mut self, since: Option<f64>, until: Option<f64>) -> Self {
+        self.since = since;
+        self.until = until;
+        self
+    }
+
+    /// Returns true if the event matches.
+    #[must_use]
+    pub fn matches(&self, event: &RecordedEvent) -> bool {
+        (self.event_types.is_empty()
+            || self
+                .event_types
+                .iter()
+                .any(|prefix| event.event_type.starts_with(prefix.as_str())))
+            && self.since.map_or(true, |since| event.timestamp >= since)
+            && self.until.map_or(true, |until| event.timestamp < until)
+    }
+}
+
+/// A page of events.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventPage {
+    /// The matching events, oldest first.
+    pub events: Vec<RecordedEvent>,
+    /// Cursor for the next page, or `None` if this is the last one.
+    pub next_cursor: Option<u64>,
+}
+
+/// Limits on how many events a store keeps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventRetention {
+    /// Most events kept per run; older events are dropped first.
+    pub max_events_per_run: Option<usize>,
+    /// How long events are kept after being recorded.
+    pub ttl: Option<Duration>,
+}
+
+impl EventRetention {
+    /// Keeps every event.
+    #[must_use]
+    pub fn unbounded() -> Self {
+        Self::default()
+    }
+
+    /// Sets the per-run event limit.
+    #[must_use]
+    pub fn with_max_events_per_run(mut self, max: usize) -> Self {
+        self.max_events_per_run = Some(max);
+        self
+    }
+
+    /// Sets how long events are kept.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn cutoff(&self, clock: &TtlClock) -> Option<f64> {
+        self.ttl.map(|ttl| clock.cutoff(ttl))
+    }
+}
+
+/// Storage for per-run event logs.
+#[async_trait]
+pub trait RunStateStore: Send + Sync {
+    /// Appends an event to a run's log and returns it as recorded.
+    async fn append(
+        &self,
+        run_id: &str,
+        event_type: &str,
+        data: Option<serde_json::Value>,
+    ) -> Result<RecordedEvent, StageflowError>;
+
+    /// Lists events of a run matching `filter`, oldest first.
+    ///
+    /// `cursor` is the `next_cursor` of the previous page, or `None` for the
+    /// first page. At most `limit` events are returned.
+    async fn list_events(
+        &self,
+        run_id: &str,
+        filter: &EventFilter,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EventPage, StageflowError>;
+
+    /// Removes a run's log.
+    async fn delete_run(&self, run_id: &str) -> Result<(), StageflowError>;
+
+    /// Drops events outside the retention policy, returning how many were removed.
+    async fn apply_retention(&self) -> Result<usize, StageflowError>;
+
+    /// Saves the pipeline, snapshot and outputs of a run, replacing any
+    /// previous record.
+    ///
+    /// Stores that only keep events reject records.
+    async fn save_run(&self, record: RunRecord) -> Result<(), StageflowError> {
+        Err(StageflowError::Internal(format!(
+            "run state store does not keep run records (run {})",
+            record.run_id
+        )))
+    }
+
+    /// Returns the record saved for a run, if any.
+    async fn get_run(&self, _run_id: &str) -> Result<Option<RunRecord>, StageflowError> {
+        Ok(None)
+    }
+
+    /// Adds previously recorded events to a run's log, e.g. when importing
+    /// an exported run.
+    ///
+    /// The default appends each event, so the store assigns new sequence
+    /// numbers and timestamps; stores should keep the originals if they can.
+    async fn import_events(&self, run_id: &str, events: Vec<RecordedEvent>) -> Result<(), StageflowError> {
+        for event in events {
+            self.append(run_id, &event.event_type, event.data).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct RunLog {
+    events: VecDeque<RecordedEvent>,
+    next_seq: u64,
+}
+
+/// In-memory run state store.
+///
+/// Retention is enforced on every append and again by
+/// [`apply_retention`](RunStateStore::apply_retention).
+#[derive(Debug, Default)]
+pub struct InMemoryRunStateStore {
+    runs: Mutex<HashMap<String, RunLog>>,
+    records: Mutex<HashMap<String, RunRecord>>,
+    retention: EventRetention,
+    clock: TtlClock,
+}
+
+impl InMemoryRunStateStore {
+    /// Creates a store that keeps every event.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the retention policy.
+    #[must_use]
+    pub fn with_retention(mut self, retention: EventRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Sets the clock used to timestamp events and apply the retention TTL.
+    #[must_use]
+    pub fn with_clock(mut self, clock: TtlClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the ids of runs with recorded events.
+    #[must_use]
+    pub fn run_ids(&self) -> Vec<String> {
+        self.runs.lock().keys().cloned().collect()
+    }
+
+    /// Returns the number of events kept for a run.
+    #[must_use]
+    pub fn event_count(&self, run_id: &str) -> usize {
+        self.runs.lock().get(run_id).map_or(0, |log| log.events.len())
+    }
+
+    fn trim(&self, log: &mut RunLog) -> usize {
+        let before = log.events.len();
+        if let Some(cutoff) = self.retention.cutoff(&self.clock) {
+            while log.events.front().is_some_and(|e| e.timestamp < cutoff) {
+                log.events.pop_front();
+            }
+        }
+        if let Some(max) = self.retention.max_events_per_run {
+            while log.events.len() > max {
+                log.events.pop_front();
+            }
+        }
+        before - log.events.len()
+    }
+}
+
+#[async_trait]
+impl RunStateStore for InMemoryRunStateStore {
+    async fn append(
+        &self,
+        run_id: &str,
+        event_type: &str,
+        data: Option<serde_json::Value>,
+    ) -> Result<RecordedEvent, StageflowError> {
+        let now = self.clock.now_unix_secs();
+        let mut runs = self.runs.lock();
+        let log = runs.entry(run_id.to_string()).or_default();
+        log.next_seq += 1;
+        let event = RecordedEvent {
+            seq: log.next_seq,
+            run_id: run_id.to_string(),
+            event_type: event_type.to_string(),
+            timestamp: now,
+            data,
+        };
+        log.events.push_back(event.clone());
+        self.trim(log);
+        Ok(event)
+    }
+
+    async fn list_events(
+        &self,
+        run_id: &str,
+        filter: &EventFilter,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EventPage, StageflowError> {
+        let runs = self.runs.lock();
+        let Some(log) = runs.get(run_id) else {
+            return Ok(EventPage::default());
+        };
+
+        let cutoff = self.retention.cutoff(&self.clock);
+        let after = cursor.unwrap_or(0);
+        let mut matching = log
+            .events
+            .iter()
+            .filter(|e| e.seq > after)
+            .filter(|e| cutoff.map_or(true, |cutoff| e.timestamp >= cutoff))
+            .filter(|e| filter.matches(e));
+
+        let events: Vec<RecordedEvent> = matching.by_ref().take(limit).cloned().collect();
+        let next_cursor = match (events.last(), matching.next()) {
+            (Some(last), Some(_)) => Some(last.seq),
+            _ => None,
+        };
+        Ok(EventPage { events, next_cursor })
+    }
+
+    async fn delete_run(&self, run_id: &str) -> Result<(), StageflowError> {
+        self.runs.lock().remove(run_id);
+        self.records.lock().remove(run_id);
+        Ok(())
+    }
+
+    async fn apply_retention(&self) -> Result<usize, StageflowError> {
+        let mut runs = self.runs.lock();
+        let removed = runs.values_mut().map(|log| self.trim(log)).sum();
+        runs.retain(|_, log| !log.events.is_empty());
+        Ok(removed)
+    }
+
+    async fn save_run(&self, record: RunRecord) -> Result<(), StageflowError> {
+        self.records.lock().insert(record.run_id.clone(), record);
+        Ok(())
+    }
+
+    async fn get_run(&self, run_id: &str) -> Result<Option<RunRecord>, StageflowError> {
+        Ok(self.records.lock().get(run_id).cloned())
+    }
+
+    /// Keeps the original sequence numbers and timestamps, merging with any
+    /// events already logged for the run.
+    async fn import_events(&self, run_id: &str, events: Vec<RecordedEvent>) -> Result<(), StageflowError> {
+        let mut runs = self.runs.lock();
+        let log = runs.entry(run_id.to_string()).or_default();
+        let mut merged: Vec<RecordedEvent> = log.events.drain(..).collect();
+        merged.extend(events.into_iter().map(|event| RecordedEvent {
+            run_id: run_id.to_string(),
+            ..event
+        }));
+        merged.sort_by_key(|event| event.seq);
+        merged.dedup_by_key(|event| event.seq);
+        log.next_seq = log.next_seq.max(merged.last().map_or(0, |event| event.seq));
+        log.events = merged.into();
+        self.trim(log);
+        Ok(())
+    }
+}
+
+/// File-backed run state store.
+///
+/// Each run is kept as a JSON-lines event log, `<run>.events.jsonl`, next to
+/// a `<run>.run.json` record. Events are appended as they arrive; retention
+/// hides expired and excess events from reads and
+/// [`apply_retention`](RunStateStore::apply_retention) rewrites the logs
+/// without them.
+#[derive(Debug)]
+pub struct FileRunStateStore {
+    directory: PathBuf,
+    retention: EventRetention,
+    clock: TtlClock,
+    // Last sequence number per run, loaded from disk on first use. The lock
+    // also serializes writes to the logs.
+    next_seq: tokio::sync::Mutex<HashMap<String, u64>>,
+}
+
+impl FileRunStateStore {
+    /// Creates a store rooted at the given directory that keeps every event.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            retention: EventRetention::default(),
+            clock: TtlClock::default(),
+            next_seq: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the retention policy.
+    #[must_use]
+    pub fn with_retention(mut self, retention: EventRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Sets the clock used to timestamp events and apply the retention TTL.
+    #[must_use]
+    pub fn with_clock(mut self, clock: TtlClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the store directory.
+    #[must_use]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn path_for(&self, run_id: &str, suffix: &str) -> PathBuf {
+        self.directory.join(format!("{}.{suffix}", encode_path_component(run_id)))
+    }
+
+    async fn read_log(&self, run_id: &str) -> Result<Vec<RecordedEvent>, StageflowError> {
+        let bytes = match tokio::fs::read(self.path_for(run_id, "events.jsonl")).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = bytes.split(|b| *b == b'\n').filter(|line| !line.is_empty()).peekable();
+        let mut events = Vec::new();
+        while let Some(line) = lines.next() {
+            match serde_json::from_slice(line) {
+                Ok(event) => events.push(event),
+                // A crash mid-append leaves a torn last line, which is dropped.
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => return Err(StageflowError::Serialization(e.to_string())),
+            }
+        }
+        Ok(events)
+    }
+
+    async fn write_log(&self, run_id: &str, events: &[RecordedEvent]) -> Result<(), StageflowError> {
+        let path = self.path_for(run_id, "events.jsonl");
+        if events.is_empty() {
+            return match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let mut bytes = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut bytes, event).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+            bytes.push(b'\n');
+        }
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let tmp = path.with_extension("jsonl.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn last_seq(&self, seqs: &mut HashMap<String, u64>, run_id: &str) -> Result<u64, StageflowError> {
+        if let Some(seq) = seqs.get(run_id) {
+            return Ok(*seq);
+        }
+        let seq = self.read_log(run_id).await?.last().map_or(0, |event| event.seq);
+        seqs.insert(run_id.to_string(), seq);
+        Ok(seq)
+    }
+
+    fn retained(&self, mut events: Vec<RecordedEvent>) -> Vec<RecordedEvent> {
+        if let Some(cutoff) = self.retention.cutoff(&self.clock) {
+            events.retain(|e| e.timestamp >= cutoff);
+        }
+        if let Some(max) = self.retention.max_events_per_run {
+            let excess = events.len().saturating_sub(max);
+            events.drain(..excess);
+        }
+        events
+    }
+}
+
+#[async_trait]
+impl RunStateStore for FileRunStateStore {
+    async fn append(
+        &self,
+        run_id: &str,
+        event_type: &str,
+        data: Option<serde_json::Value>,
+    ) -> Result<RecordedEvent, StageflowError> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut seqs = self.next_seq.lock().await;
+        let seq = self.last_seq(&mut seqs, run_id).await? + 1;
+        let event = RecordedEvent {
+            seq,
+            run_id: run_id.to_string(),
+            event_type: event_type.to_string(),
+            timestamp: self.clock.now_unix_secs(),
+            data,
+        };
+        let mut line = serde_json::to_vec(&event).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        line.push(b'\n');
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path_for(run_id, "events.jsonl"))
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        seqs.insert(run_id.to_string(), seq);
+        Ok(event)
+    }
+
+    async fn list_events(
+        &self,
+        run_id: &str,
+        filter: &EventFilter,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EventPage, StageflowError> {
+        let after = cursor.unwrap_or(0);
+        let events = self.retained(self.read_log(run_id).await?);
+        let mut matching = events.into_iter().filter(|e| e.seq > after && filter.matches(e));
+
+        let events: Vec<RecordedEvent> = matching.by_ref().take(limit).collect();
+        let next_cursor = match (events.last(), matching.next()) {
+            (Some(last), Some(_)) => Some(last.seq),
+            _ => None,
+        };
+        Ok(EventPage { events, next_cursor })
+    }
+
+    async fn delete_run(&self, run_id: &str) -> Result<(), StageflowError> {
+        let mut seqs = self.next_seq.lock().await;
+        for suffix in ["events.jsonl", "run.json"] {
+            match tokio::fs::remove_file(self.path_for(run_id, suffix)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        seqs.remove(run_id);
+        Ok(())
+    }
+
+    async fn apply_retention(&self) -> Result<usize, StageflowError> {
+        let _seqs = self.next_seq.lock().await;
+        let mut entries = match tokio::fs::read_dir(&self.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(run) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".events.jsonl"))
+                .and_then(decode_path_component)
+            else {
+                continue;
+            };
+            let events = self.read_log(&run).await?;
+            let before = events.len();
+            let kept = self.retained(events);
+            if kept.len() < before {
+                removed += before - kept.len();
+                self.write_log(&run, &kept).await?;
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn save_run(&self, record: RunRecord) -> Result<(), StageflowError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let bytes = serde_json::to_vec_pretty(&record).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+
+        // Write then rename so a crash mid-write never leaves a torn record.
+        let path = self.path_for(&record.run_id, "run.json");
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get_run(&self, run_id: &str) -> Result<Option<RunRecord>, StageflowError> {
+        match tokio::fs::read(self.path_for(run_id, "run.json")).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| StageflowError::Serialization(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Keeps the original sequence numbers and timestamps, merging with any
+    /// events already logged for the run.
+    async fn import_events(&self, run_id: &str, events: Vec<RecordedEvent>) -> Result<(), StageflowError> {
+        let mut seqs = self.next_seq.lock().await;
+        let mut merged = self.read_log(run_id).await?;
+        merged.extend(events.into_iter().map(|event| RecordedEvent {
+            run_id: run_id.to_string(),
+            ..event
+        }));
+        merged.sort_by_key(|event| event.seq);
+        merged.dedup_by_key(|event| event.seq);
+        let last = merged.last().map_or(0, |event| event.seq);
+        self.write_log(run_id, &merged).await?;
+        let next = seqs.entry(run_id.to_string()).or_default();
+        *next = (*next).max(last);
+        Ok(())
+    }
+}
+
+/// Event sink that records events into a [`RunStateStore`].
+///
+/// Events are keyed by their `pipeline_run_id` field; events without one are
+/// not recorded. Failures to record are logged and otherwise ignored so
+/// storage problems never fail a run.
+pub struct RunStateEventSink {
+    store: Arc<dyn RunStateStore>,
+}
+
+impl RunStateEventSink {
+    /// Creates a sink writing to `store`.
+    #[must_use]
+    pub fn new(store: Arc<dyn RunStateStore>) -> Self {
+        Self { store }
+    }
+
+    fn run_id(data: Option<&serde_json::Value>) -> Option<String> {
+        data?
+            .get("pipeline_run_id")?
+            .as_str()
+            .map(ToString::to_string)
+    }
+}
+
+impl std::fmt::Debug for RunStateEventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunStateEventSink").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl EventSink for RunStateEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let Some(run_id) = Self::run_id(data.as_ref()) else {
+            return;
+        };
+        if let Err(e) = self.store.append(&run_id, event_type, data).await {
+            tracing::warn!(run_id = %run_id, event_type, error = %e, "Failed to record run event");
+        }
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let Some(run_id) = Self::run_id(data.as_ref()) else {
+            return;
+        };
+        let store = Arc::clone(&self.store);
+        let event_type = event_type.to_string();
+        handle.spawn(async move {
+            if let Err(e) = store.append(&run_id, &event_type, data).await {
+                tracing::warn!(run_id = %run_id, event_type, error = %e, "Failed to record run event");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn seeded_store(store: InMemoryRunStateStore) -> InMemoryRunStateStore {
+        for i in 0..5 {
+            store.append("run-1", "stage.started", Some(json!({"i": i}))).await.unwrap();
+            store.append("run-1", "stage.completed", Some(json!({"i": i}))).await.unwrap();
+        }
+        store
+    }
+
+    #[tokio::test]
+    async fn test_list_events_paginates_and_filters() {
+        let store = seeded_store(InMemoryRunStateStore::new()).await;
+        let filter = EventFilter::new().with_event_type("stage.completed");
+
+        let first = store.list_events("run-1", &filter, None, 3).await.unwrap();
+        assert_eq!(first.events.len(), 3);
+        assert!(first.events.iter().all(|e| e.event_type == "stage.completed"));
+
+        let second = store
+            .list_events("run-1", &filter, first.next_cursor, 3)
+            .await
+            .unwrap();
+        assert_eq!(second.events.len(), 2);
+        assert_eq!(second.next_cursor, None);
+        assert_eq!(second.events[0].data, Some(json!({"i": 3})));
+
+        let future = EventFilter::new().with_time_range(Some(TtlClock::default().now_unix_secs() + 60.0), None);
+        assert!(store.list_events("run-1", &future, None, 10).await.unwrap().events.is_empty());
+        assert!(store.list_events("run-2", &filter, None, 10).await.unwrap().events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retention_bounds_storage() {
+        let store = seeded_store(
+            InMemoryRunStateStore::new()
+                .with_retention(EventRetention::unbounded().with_max_events_per_run(4)),
+        )
+        .await;
+        let page = store.list_events("run-1", &EventFilter::new(), None, 10).await.unwrap();
+        assert_eq!(page.events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![7, 8, 9, 10]);
+
+        let manual = Arc::new(crate::utils::ManualClock::new(
+            chrono::Utc::now(),
+            chrono::Duration::zero(),
+        ));
+        let store = seeded_store(
+            InMemoryRunStateStore::new()
+                .with_retention(EventRetention::unbounded().with_ttl(Duration::from_secs(60)))
+                .with_clock(TtlClock::new(manual.clone())),
+        )
+        .await;
+        assert_eq!(store.apply_retention().await.unwrap(), 0);
+        manual.advance(chrono::Duration::seconds(61));
+        assert_eq!(store.apply_retention().await.unwrap(), 10);
+        assert!(store.run_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileRunStateStore::new(dir.path());
+        for i in 0..3 {
+            store.append("run/1", "stage.started", Some(json!({"i": i}))).await.unwrap();
+            store.append("run/1", "stage.completed", Some(json!({"i": i}))).await.unwrap();
+        }
+        store.save_run(RunRecord::new("run/1").with_output("a", StageOutput::ok_value("answer", json!(42)))).await.unwrap();
+        drop(store);
+
+        let store = FileRunStateStore::new(dir.path());
+        let filter = EventFilter::new().with_event_type("stage.completed");
+        let first = store.list_events("run/1", &filter, None, 2).await.unwrap();
+        assert_eq!(first.events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 4]);
+        let second = store.list_events("run/1", &filter, first.next_cursor, 2).await.unwrap();
+        assert_eq!(second.events[0].data, Some(json!({"i": 2})));
+        assert_eq!(second.next_cursor, None);
+        assert_eq!(store.append("run/1", "stage.started", None).await.unwrap().seq, 7);
+        assert!(store.get_run("run/1").await.unwrap().unwrap().outputs.contains_key("a"));
+
+        store.delete_run("run/1").await.unwrap();
+        assert!(store.list_events("run/1", &filter, None, 10).await.unwrap().events.is_empty());
+        assert!(store.get_run("run/1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_retention_rewrites_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileRunStateStore::new(dir.path())
+            .with_retention(EventRetention::unbounded().with_max_events_per_run(4));
+        for i in 0..10 {
+            store.append("run-1", "stage.started", Some(json!({"i": i}))).await.unwrap();
+        }
+        let page = store.list_events("run-1", &EventFilter::new(), None, 10).await.unwrap();
+        assert_eq!(page.events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![7, 8, 9, 10]);
+
+        assert_eq!(store.apply_retention().await.unwrap(), 6);
+        let reopened = FileRunStateStore::new(dir.path());
+        assert_eq!(reopened.list_events("run-1", &EventFilter::new(), None, 10).await.unwrap().events.len(), 4);
+        assert_eq!(reopened.append("run-1", "stage.started", None).await.unwrap().seq, 11);
+    }
+
+    #[tokio::test]
+    async fn test_sink_records_events_by_run_id() {
+        let store = Arc::new(InMemoryRunStateStore::new());
+        let sink = RunStateEventSink::new(store.clone());
+        sink.emit("stage.started", Some(json!({"pipeline_run_id": "abc"}))).await;
+        sink.emit("stage.started", Some(json!({"stage": "orphan"}))).await;
+
+        assert_eq!(store.run_ids(), vec!["abc".to_string()]);
+        assert_eq!(store.event_count("abc"), 1);
+    }
+}
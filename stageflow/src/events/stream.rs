@@ -0,0 +1,437 @@
+//! Event sink publishing to stream brokers such as Kafka or NATS.
+//!
+//! [`StreamEventSink`] wraps each event in a [`StreamEnvelope`], encodes it
+//! with an [`EnvelopeSerializer`] and hands it to a [`StreamPublisher`].
+//! Messages are keyed by `pipeline_run_id`, so a partitioned broker keeps
+//! the events of a run in order. Broker clients live behind the `kafka` and
+//! `nats` features.
+
+use super::composite::event_type_matches;
+use super::{track_pending_task, BackpressureMetrics, EventSink};
+use crate::errors::PublishError;
+use crate::pipeline::{with_retry, RetryConfig};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Version of the [`StreamEnvelope`] layout.
+pub const ENVELOPE_SCHEMA_VERSION: u32 = 1;
+
+/// Avro schema of a [`StreamEnvelope`] whose `data` is carried as a JSON
+/// string, for serializers writing Avro.
+pub const ENVELOPE_AVRO_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "StreamEnvelope",
+  "namespace": "stageflow.events",
+  "fields": [
+    {"name": "schema_version", "type": "int"},
+    {"name": "event_id", "type": "string"},
+    {"name": "event_type", "type": "string"},
+    {"name": "pipeline_run_id", "type": ["null", "string"], "default": null},
+    {"name": "timestamp", "type": "string"},
+    {"name": "data", "type": ["null", "string"], "default": null}
+  ]
+}"#;
+
+/// An event as published to a stream.
+///
+/// The layout is flat and stable so it maps onto [`ENVELOPE_AVRO_SCHEMA`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamEnvelope {
+    /// Layout version, [`ENVELOPE_SCHEMA_VERSION`] when created here.
+    pub schema_version: u32,
+    /// Unique ID, usable by consumers to drop redelivered events.
+    pub event_id: String,
+    /// The event type.
+    pub event_type: String,
+    /// The run the event belongs to, taken from the event data.
+    pub pipeline_run_id: Option<String>,
+    /// When the event was published, as ISO 8601.
+    pub timestamp: String,
+    /// The event data.
+    pub data: Option<serde_json::Value>,
+}
+
+impl StreamEnvelope {
+    /// Wraps an event.
+    #[must_use]
+    pub fn new(event_type: &str, data: Option<serde_json::Value>) -> Self {
+        let pipeline_run_id = data
+            .as_ref()
+            .and_then(|data| data.get("pipeline_run_id"))
+            .and_then(serde_json::Value::as_str)
+            .map(String::from);
+        Self {
+            schema_version: ENVELOPE_SCHEMA_VERSION,
+            event_id: crate::utils::generate_uuid().to_string(),
+            event_type: event_type.to_string(),
+            pipeline_run_id,
+            timestamp: crate::utils::iso_timestamp(),
+            data,
+        }
+    }
+
+    /// Returns the data as a JSON string, the form the Avro schema uses.
+    #[must_use]
+    pub fn data_json(&self) -> Option<String> {
+        self.data.as_ref().map(ToString::to_string)
+    }
+}
+
+/// Encodes envelopes into message payloads.
+pub trait EnvelopeSerializer: Send + Sync {
+    /// MIME type of the payload, sent as the `content-type` header.
+    fn content_type(&self) -> &str;
+
+    /// Encodes `envelope`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PublishError::Serialization`] if it cannot be encoded.
+    fn serialize(&self, envelope: &StreamEnvelope) -> Result<Vec<u8>, PublishError>;
+}
+
+/// Encodes envelopes as JSON objects.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonEnvelopeSerializer;
+
+impl EnvelopeSerializer for JsonEnvelopeSerializer {
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn serialize(&self, envelope: &StreamEnvelope) -> Result<Vec<u8>, PublishError> {
+        serde_json::to_vec(envelope).map_err(|e| PublishError::Serialization(e.to_string()))
+    }
+}
+
+/// A message ready for a broker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMessage {
+    /// Topic, or subject for NATS.
+    pub topic: String,
+    /// Partition key: the run ID, when the event has one.
+    pub key: Option<String>,
+    /// Unique message ID, the envelope's `event_id`.
+    pub id: String,
+    /// Encoded envelope.
+    pub payload: Vec<u8>,
+    /// Message headers.
+    pub headers: Vec<(String, String)>,
+}
+
+/// A broker client.
+#[async_trait]
+pub trait StreamPublisher: Send + Sync {
+    /// Name of the broker, used in logs.
+    fn broker(&self) -> &str;
+
+    /// Publishes `message`, waiting for the broker to acknowledge it when
+    /// `ack` is true.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PublishError::Broker`] if the message was not accepted.
+    async fn publish(&self, message: StreamMessage, ack: bool) -> Result<(), PublishError>;
+}
+
+/// How hard a [`StreamEventSink`] tries to deliver an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryGuarantee {
+    /// Publishes once without waiting for an acknowledgment.
+    AtMostOnce,
+    /// Waits for an acknowledgment and retries until the retry config is
+    /// exhausted. Consumers may see an event twice and can deduplicate on
+    /// `event_id`.
+    #[default]
+    AtLeastOnce,
+}
+
+/// Event sink publishing envelopes to a stream broker.
+///
+/// Events go to the default topic unless a topic route matches their type.
+/// `emit` publishes before returning; `try_emit` publishes in a background
+/// task tracked by [`wait_for_event_sink_tasks`](super::wait_for_event_sink_tasks),
+/// so events emitted that way may overtake each other. Wrap the sink in a
+/// [`ChannelEventSink`](super::ChannelEventSink) to publish in order
+/// without blocking the emitter.
+pub struct StreamEventSink {
+    publisher: Arc<dyn StreamPublisher>,
+    topic: String,
+    routes: Vec<(String, String)>,
+    serializer: Arc<dyn EnvelopeSerializer>,
+    guarantee: DeliveryGuarantee,
+    retry: RetryConfig,
+    metrics: Arc<BackpressureMetrics>,
+}
+
+impl StreamEventSink {
+    /// Creates a sink publishing every event to `topic`.
+    #[must_use]
+    pub fn new(publisher: Arc<dyn StreamPublisher>, topic: impl Into<String>) -> Self {
+        Self {
+            publisher,
+            topic: topic.into(),
+            routes: Vec::new(),
+            serializer: Arc::new(JsonEnvelopeSerializer),
+            guarantee: DeliveryGuarantee::default(),
+            retry: RetryConfig::default(),
+            metrics: Arc::new(BackpressureMetrics::default()),
+        }
+    }
+
+    /// Publishes events whose type matches `pattern` to `topic`.
+    ///
+    /// Patterns use `*` wildcards and are tried in the order added.
+    #[must_use]
+    pub fn with_topic_route(mut self, pattern: impl Into<String>, topic: impl Into<String>) -> Self {
+        self.routes.push((pattern.into(), topic.into()));
+        self
+    }
+
+    /// Sets how envelopes are encoded.
+    #[must_use]
+    pub fn with_serializer(mut self, serializer: Arc<dyn EnvelopeSerializer>) -> Self {
+        self.serializer = serializer;
+        self
+    }
+
+    /// Sets the delivery guarantee.
+    #[must_use]
+    pub fn with_delivery(mut self, guarantee: DeliveryGuarantee) -> Self {
+        self.guarantee = guarantee;
+        self
+    }
+
+    /// Sets how failed publishes are retried under
+    /// [`DeliveryGuarantee::AtLeastOnce`].
+    #[must_use]
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Returns the delivery guarantee.
+    #[must_use]
+    pub fn delivery(&self) -> DeliveryGuarantee {
+        self.guarantee
+    }
+
+    /// Returns the topic events of `event_type` are published to.
+    #[must_use]
+    pub fn topic_for(&self, event_type: &str) -> &str {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| event_type_matches(pattern, event_type))
+            .map_or(&self.topic, |(_, topic)| topic)
+    }
+
+    /// Returns the published (`emitted`) and lost (`dropped`) event counters.
+    #[must_use]
+    pub fn metrics(&self) -> &BackpressureMetrics {
+        &self.metrics
+    }
+
+    /// Builds the message for an event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PublishError::Serialization`] if the envelope cannot be
+    /// encoded.
+    pub fn message(&self, event_type: &str, data: Option<serde_json::Value>) -> Result<StreamMessage, PublishError> {
+        let envelope = StreamEnvelope::new(event_type, data);
+        let payload = self.serializer.serialize(&envelope)?;
+        Ok(StreamMessage {
+            topic: self.topic_for(event_type).to_string(),
+            key: envelope.pipeline_run_id,
+            id: envelope.event_id,
+            payload,
+            headers: vec![
+                ("content-type".to_string(), self.serializer.content_type().to_string()),
+                ("event-type".to_string(), envelope.event_type),
+            ],
+        })
+    }
+
+    async fn publish(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let result = match self.message(event_type, data) {
+            Ok(message) => match self.guarantee {
+                DeliveryGuarantee::AtMostOnce => self.publisher.publish(message, false).await,
+                DeliveryGuarantee::AtLeastOnce => {
+                    with_retry(&self.retry, &message.id, || {
+                        self.publisher.publish(message.clone(), true)
+                    })
+                    .await
+                }
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => self.metrics.record_emit(),
+            Err(e) => {
+                self.metrics.record_drop();
+                warn!(
+                    broker = self.publisher.broker(),
+                    topic = self.topic_for(event_type),
+                    event_type,
+                    error = %e,
+                    "Failed to publish event"
+                );
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for StreamEventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamEventSink")
+            .field("broker", &self.publisher.broker())
+            .field("topic", &self.topic)
+            .field("routes", &self.routes)
+            .field("guarantee", &self.guarantee)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl EventSink for StreamEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.publish(event_type, data).await;
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.metrics.record_drop();
+            warn!(event_type, "No tokio runtime; stream event dropped");
+            return;
+        };
+        // The task gets its own handle on the shared publisher and counters
+        let sink = Self {
+            publisher: Arc::clone(&self.publisher),
+            topic: self.topic.clone(),
+            routes: self.routes.clone(),
+            serializer: Arc::clone(&self.serializer),
+            guarantee: self.guarantee,
+            retry: self.retry.clone(),
+            metrics: Arc::clone(&self.metrics),
+        };
+        let event_type = event_type.to_string();
+        track_pending_task(runtime.spawn(async move { sink.publish(&event_type, data).await }));
+    }
+}
+
+/// Publisher keeping messages in memory, for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryStreamPublisher {
+    messages: Mutex<Vec<StreamMessage>>,
+    failures: Mutex<usize>,
+}
+
+impl InMemoryStreamPublisher {
+    /// Creates an empty publisher.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the next `count` publishes.
+    pub fn fail_next(&self, count: usize) {
+        *self.failures.lock() = count;
+    }
+
+    /// Returns the published messages.
+    #[must_use]
+    pub fn messages(&self) -> Vec<StreamMessage> {
+        self.messages.lock().clone()
+    }
+
+    /// Returns the published messages with partition key `key`.
+    #[must_use]
+    pub fn messages_for_key(&self, key: &str) -> Vec<StreamMessage> {
+        self.messages
+            .lock()
+            .iter()
+            .filter(|message| message.key.as_deref() == Some(key))
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl StreamPublisher for InMemoryStreamPublisher {
+    fn broker(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn publish(&self, message: StreamMessage, _ack: bool) -> Result<(), PublishError> {
+        let mut failures = self.failures.lock();
+        if *failures > 0 {
+            *failures -= 1;
+            return Err(PublishError::broker("memory", "injected failure"));
+        }
+        drop(failures);
+        self.messages.lock().push(message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn retry() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            base_delay_ms: 1,
+            ..RetryConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publishes_keyed_envelopes_to_routed_topics() {
+        let publisher = Arc::new(InMemoryStreamPublisher::new());
+        let sink = StreamEventSink::new(publisher.clone(), "stageflow.events")
+            .with_topic_route("stage.*", "stageflow.stages");
+
+        sink.emit("stage.completed", Some(json!({"pipeline_run_id": "run-1", "stage": "a"})))
+            .await;
+        sink.emit("pipeline.completed", Some(json!({"pipeline_run_id": "run-1"})))
+            .await;
+        sink.try_emit("pipeline.completed", None);
+        crate::events::wait_for_event_sink_tasks().await;
+
+        let messages = publisher.messages_for_key("run-1");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].topic, "stageflow.stages");
+        assert_eq!(messages[1].topic, "stageflow.events");
+        let envelope: StreamEnvelope = serde_json::from_slice(&messages[0].payload).unwrap();
+        assert_eq!(envelope.event_type, "stage.completed");
+        assert_eq!(envelope.event_id, messages[0].id);
+        assert_eq!(envelope.data_json().unwrap(), r#"{"pipeline_run_id":"run-1","stage":"a"}"#);
+        assert!(messages[0]
+            .headers
+            .contains(&("content-type".to_string(), "application/json".to_string())));
+
+        assert_eq!(publisher.messages().len(), 3);
+        assert_eq!(sink.metrics().emitted(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_guarantees() {
+        let publisher = Arc::new(InMemoryStreamPublisher::new());
+        let sink = StreamEventSink::new(publisher.clone(), "events").with_retry(retry());
+        publisher.fail_next(2);
+        sink.emit("a", None).await;
+        assert_eq!(publisher.messages().len(), 1);
+
+        let sink = sink.with_delivery(DeliveryGuarantee::AtMostOnce);
+        publisher.fail_next(1);
+        sink.emit("b", None).await;
+        assert_eq!(publisher.messages().len(), 1);
+        assert_eq!(sink.metrics().dropped(), 1);
+    }
+}
@@ -0,0 +1,403 @@
+//! Typed pipeline events with lazily built JSON payloads.
+//!
+//! Hot-path events such as `stage.started` are emitted as [`PipelineEvent`]
+//! variants inside an [`EmittedEvent`], which sinks receive by `Arc` through
+//! [`EventSink::try_emit_event`](super::EventSink::try_emit_event). The JSON
+//! payload is built at most once, when a sink first asks for it, so sinks
+//! that drop events or read the typed variant never pay for it.
+
+use std::borrow::Cow;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+/// A structured pipeline event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineEvent {
+    /// `stage.started`: a stage began executing.
+    StageStarted {
+        /// The stage name.
+        stage: String,
+    },
+    /// `stage.completed`: a stage finished successfully.
+    StageCompleted {
+        /// The stage name.
+        stage: String,
+        /// Wall-clock time of the stage.
+        duration_ms: f64,
+    },
+    /// `stage.failed`: a stage failed.
+    StageFailed {
+        /// The stage name.
+        stage: String,
+        /// The stage's error message.
+        error: Option<String>,
+        /// Wall-clock time of the stage.
+        duration_ms: f64,
+    },
+    /// `stage.skipped`: a stage was skipped.
+    StageSkipped {
+        /// The stage name.
+        stage: String,
+        /// Why the stage was skipped.
+        reason: Option<String>,
+    },
+    /// `stage.cancelled`: a stage cancelled the pipeline.
+    StageCancelled {
+        /// The stage name.
+        stage: String,
+        /// Why the stage cancelled.
+        reason: Option<String>,
+    },
+    /// `guard_retry.scheduled`: a guard failed and its retry stage was
+    /// queued again.
+    GuardRetryScheduled {
+        /// The guard stage.
+        guard: String,
+        /// The attempt number just made.
+        attempt: usize,
+        /// The stage that will run again.
+        retry_stage: String,
+        /// Consecutive attempts with an unchanged output.
+        stagnation_hits: usize,
+        /// The retry policy's overall timeout.
+        timeout_seconds: Option<f64>,
+    },
+    /// Any other event, with its JSON data.
+    Custom {
+        /// The event type.
+        event_type: String,
+        /// The event data.
+        data: Option<serde_json::Value>,
+    },
+}
+
+impl PipelineEvent {
+    /// Creates an untyped event.
+    #[must_use]
+    pub fn custom(event_type: impl Into<String>, data: Option<serde_json::Value>) -> Self {
+        Self::Custom {
+            event_type: event_type.into(),
+            data,
+        }
+    }
+
+    /// Returns the event type, such as `stage.completed`.
+    #[must_use]
+    pub fn event_type(&self) -> &str {
+        match self {
+            Self::Custom { event_type, .. } => event_type,
+            typed => typed.static_event_type().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the stage the event is about, if any.
+    #[must_use]
+    pub fn stage(&self) -> Option<&str> {
+        match self {
+            Self::StageStarted { stage }
+            | Self::StageCompleted { stage, .. }
+            | Self::StageFailed { stage, .. }
+            | Self::StageSkipped { stage, .. }
+            | Self::StageCancelled { stage, .. } => Some(stage),
+            Self::GuardRetryScheduled { guard, .. } => Some(guard),
+            Self::Custom { data, .. } => data.as_ref()?.get("stage")?.as_str(),
+        }
+    }
+
+    /// Builds the event's JSON data, without the envelope fields.
+    #[must_use]
+    pub fn to_data(&self) -> Option<serde_json::Value> {
+        let data = match self {
+            Self::StageStarted { stage } => serde_json::json!({ "stage": stage }),
+            Self::StageCompleted { stage, duration_ms } => serde_json::json!({
+                "stage": stage,
+                "duration_ms": duration_ms,
+            }),
+            Self::StageFailed {
+                stage,
+                error,
+                duration_ms,
+            } => serde_json::json!({
+                "stage": stage,
+                "error": error,
+                "duration_ms": duration_ms,
+            }),
+            Self::StageSkipped { stage, reason } | Self::StageCancelled { stage, reason } => serde_json::json!({
+                "stage": stage,
+                "reason": reason,
+            }),
+            Self::GuardRetryScheduled {
+                guard,
+                attempt,
+                retry_stage,
+                stagnation_hits,
+                timeout_seconds,
+            } => serde_json::json!({
+                "guard": guard,
+                "attempt": attempt,
+                "retry_stage": retry_stage,
+                "stagnation_hits": stagnation_hits,
+                "timeout_seconds": timeout_seconds,
+            }),
+            Self::Custom { data, .. } => return data.clone(),
+        };
+        Some(data)
+    }
+
+    fn static_event_type(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::StageStarted { .. } => "stage.started",
+            Self::StageCompleted { .. } => "stage.completed",
+            Self::StageFailed { .. } => "stage.failed",
+            Self::StageSkipped { .. } => "stage.skipped",
+            Self::StageCancelled { .. } => "stage.cancelled",
+            Self::GuardRetryScheduled { .. } => "guard_retry.scheduled",
+            Self::Custom { .. } => return None,
+        })
+    }
+
+    /// Converts the event into its JSON data, without the envelope fields.
+    #[must_use]
+    pub fn into_data(self) -> Option<serde_json::Value> {
+        match self {
+            Self::Custom { data, .. } => data,
+            typed => typed.to_data(),
+        }
+    }
+}
+
+/// Envelope fields a pipeline context stamps on every event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventMetadata {
+    /// The pipeline run.
+    pub pipeline_run_id: Option<Uuid>,
+    /// When the event was emitted, as an ISO 8601 timestamp.
+    pub emitted_at: Option<String>,
+    /// The event's position in the run.
+    pub sequence: Option<u64>,
+    /// The request that started the run.
+    pub request_id: Option<Uuid>,
+    /// The context's execution mode.
+    pub execution_mode: Option<String>,
+    /// The pipeline topology.
+    pub topology: Option<String>,
+    /// Whether the run simulates side effects.
+    pub dry_run: bool,
+}
+
+impl EventMetadata {
+    fn stamp(&self, map: &mut serde_json::Map<String, serde_json::Value>) {
+        if let Some(id) = self.pipeline_run_id {
+            map.insert("pipeline_run_id".to_string(), serde_json::json!(id.to_string()));
+        }
+        if let Some(emitted_at) = &self.emitted_at {
+            map.insert("emitted_at".to_string(), serde_json::json!(emitted_at));
+        }
+        if let Some(sequence) = self.sequence {
+            map.insert("sequence".to_string(), serde_json::json!(sequence));
+        }
+        if let Some(id) = self.request_id {
+            map.insert("request_id".to_string(), serde_json::json!(id.to_string()));
+        }
+        if let Some(mode) = &self.execution_mode {
+            map.insert("execution_mode".to_string(), serde_json::json!(mode));
+        }
+        if let Some(topology) = &self.topology {
+            map.insert("topology".to_string(), serde_json::json!(topology));
+        }
+        if self.dry_run {
+            map.insert("dry_run".to_string(), serde_json::json!(true));
+        }
+    }
+}
+
+/// A [`PipelineEvent`] with its envelope, as handed to sinks.
+///
+/// [`json`](Self::json) builds the enriched JSON payload on first use and
+/// caches it, so sinks sharing the event serialize it once between them.
+#[derive(Debug)]
+pub struct EmittedEvent {
+    event: PipelineEvent,
+    metadata: EventMetadata,
+    json: OnceLock<serde_json::Value>,
+}
+
+impl EmittedEvent {
+    /// Wraps an event and its envelope.
+    #[must_use]
+    pub fn new(event: PipelineEvent, metadata: EventMetadata) -> Self {
+        Self {
+            event,
+            metadata,
+            json: OnceLock::new(),
+        }
+    }
+
+    /// Returns the typed event.
+    #[must_use]
+    pub fn event(&self) -> &PipelineEvent {
+        &self.event
+    }
+
+    /// Returns the envelope fields.
+    #[must_use]
+    pub fn metadata(&self) -> &EventMetadata {
+        &self.metadata
+    }
+
+    /// Returns the event type.
+    #[must_use]
+    pub fn event_type(&self) -> &str {
+        self.event.event_type()
+    }
+
+    /// Returns true once the JSON payload has been built.
+    #[must_use]
+    pub fn is_serialized(&self) -> bool {
+        self.json.get().is_some()
+    }
+
+    /// Returns the JSON payload: the event data with the envelope fields
+    /// added, built on first call.
+    pub fn json(&self) -> &serde_json::Value {
+        self.json.get_or_init(|| Self::stamp(&self.metadata, self.event.to_data()))
+    }
+
+    /// Returns the event type and JSON payload, moving them out instead of
+    /// cloning when this is the last reference.
+    #[must_use]
+    pub fn into_payload(self: Arc<Self>) -> (Cow<'static, str>, serde_json::Value) {
+        match Arc::try_unwrap(self) {
+            Ok(Self { event, metadata, json }) => {
+                let event_type = event
+                    .static_event_type()
+                    .map_or_else(|| Cow::Owned(event.event_type().to_string()), Cow::Borrowed);
+                let json = json.into_inner().unwrap_or_else(|| Self::stamp(&metadata, event.into_data()));
+                (event_type, json)
+            }
+            Err(shared) => (Cow::Owned(shared.event_type().to_string()), shared.json().clone()),
+        }
+    }
+
+    fn stamp(metadata: &EventMetadata, data: Option<serde_json::Value>) -> serde_json::Value {
+        let mut json = data.unwrap_or_else(|| serde_json::json!({}));
+        if let serde_json::Value::Object(map) = &mut json {
+            metadata.stamp(map);
+        }
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_is_built_lazily_with_envelope() {
+        let run_id = Uuid::new_v4();
+        let event = EmittedEvent::new(
+            PipelineEvent::StageCompleted {
+                stage: "fetch".to_string(),
+                duration_ms: 1.5,
+            },
+            EventMetadata {
+                pipeline_run_id: Some(run_id),
+                sequence: Some(3),
+                ..EventMetadata::default()
+            },
+        );
+        assert!(!event.is_serialized());
+        assert_eq!(event.event_type(), "stage.completed");
+
+        let json = event.json();
+        assert_eq!(json["stage"], "fetch");
+        assert_eq!(json["duration_ms"], 1.5);
+        assert_eq!(json["pipeline_run_id"], run_id.to_string());
+        assert_eq!(json["sequence"], 3);
+        assert!(json.get("dry_run").is_none());
+        assert!(event.is_serialized());
+    }
+
+    #[test]
+    fn test_into_payload_moves_or_clones() {
+        let event = PipelineEvent::custom("custom.event", Some(serde_json::json!({"stage": "s", "n": 1})));
+        assert_eq!(event.stage(), Some("s"));
+        let (event_type, json) = Arc::new(EmittedEvent::new(event.clone(), EventMetadata::default())).into_payload();
+        assert_eq!(event_type, "custom.event");
+        assert_eq!(json, serde_json::json!({"stage": "s", "n": 1}));
+
+        let shared = Arc::new(EmittedEvent::new(event, EventMetadata::default()));
+        let (_, cloned) = Arc::clone(&shared).into_payload();
+        assert_eq!(&cloned, shared.json());
+
+        let non_object = PipelineEvent::custom("raw", Some(serde_json::json!([1, 2])));
+        let metadata = EventMetadata {
+            sequence: Some(1),
+            ..EventMetadata::default()
+        };
+        assert_eq!(EmittedEvent::new(non_object, metadata).json(), &serde_json::json!([1, 2]));
+    }
+
+    #[derive(Default)]
+    struct TypedSink {
+        events: parking_lot::Mutex<Vec<Arc<EmittedEvent>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::events::EventSink for TypedSink {
+        async fn emit(&self, _event_type: &str, _data: Option<serde_json::Value>) {}
+
+        fn try_emit(&self, _event_type: &str, _data: Option<serde_json::Value>) {}
+
+        fn try_emit_event(&self, event: Arc<EmittedEvent>) {
+            self.events.lock().push(event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_events_reach_sinks_unserialized() {
+        use crate::context::{ContextSnapshot, ExecutionContext, PipelineContext, RunIdentity};
+        use crate::events::{CollectingEventSink, CompositeEventSink};
+        use crate::pipeline::{PipelineBuilder, UnifiedStageGraph};
+        use crate::stages::NoOpStage;
+
+        let typed_sink = Arc::new(TypedSink::default());
+        let collecting = Arc::new(CollectingEventSink::new());
+        let graph = PipelineBuilder::new("typed")
+            .stage("a", Arc::new(NoOpStage::new("a")), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(typed_sink.clone()));
+        UnifiedStageGraph::new(graph)
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        {
+            let events = typed_sink.events.lock();
+            let event_types: Vec<&str> = events.iter().map(|e| e.event_type()).collect();
+            assert_eq!(event_types, vec!["stage.started", "stage.completed"]);
+            assert!(events.iter().all(|e| !e.is_serialized()));
+            assert_eq!(events[1].event().stage(), Some("a"));
+        }
+
+        let composite = CompositeEventSink::new()
+            .with_sink("typed", typed_sink.clone())
+            .with_sink("json", collecting.clone());
+        let ctx = PipelineContext::new(RunIdentity::new()).with_event_sink(Arc::new(composite));
+        ctx.emit_pipeline_event(PipelineEvent::StageStarted { stage: "b".to_string() });
+        ctx.try_emit_event("stage.started", Some(serde_json::json!({"stage": "b"})));
+
+        let shared = typed_sink.events.lock().last().cloned().unwrap();
+        assert!(shared.is_serialized());
+        let events = collecting.events();
+        let strip = |data: &Option<serde_json::Value>| {
+            let mut data = data.clone().unwrap();
+            data.as_object_mut().unwrap().retain(|key, _| key != "emitted_at" && key != "sequence");
+            data
+        };
+        assert_eq!(strip(&events[0].1), strip(&events[1].1));
+        assert_eq!(events[0].1.as_ref(), Some(shared.json()));
+    }
+}
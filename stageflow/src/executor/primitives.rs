@@ -0,0 +1,980 @@
+//! Scheduling primitives shared by the built-in DAG engines.
+//!
+//! These are the same building blocks `StageGraph` and `UnifiedStageGraph`
+//! use internally: dependency tracking to compute the ready set, input
+//! assembly from completed outputs, and the standard `stage.*` lifecycle
+//! events. Custom executors that use them emit the same event stream as
+//! the built-in engines.
+
+use crate::context::{ContextAccess, ContextSnapshot, ExecutionContext, PipelineContext, StageContext, StageInputs};
+use crate::artifacts::{artifact_reference, reference_uri, ArtifactStore};
+use crate::core::{StageArtifact, StageOutput, StageStatus};
+use crate::events::PipelineEvent;
+use crate::interceptors::{ImmutabilityInterceptor, Interceptor};
+use crate::pipeline::{
+    catch_stage_panic, watch_for_stall, CleanupRegistry, KeyedExecution, ResourceLimitExceeded, StageGraph,
+    StageSpec,
+};
+use crate::utils::Stopwatch;
+use futures::future::OptionFuture;
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Time budget for a stage's cancel cleanup callbacks.
+pub const STAGE_CANCEL_CLEANUP_TIMEOUT_SECS: f64 = 5.0;
+
+/// Tracks unsatisfied dependencies and the set of stages ready to run.
+///
+/// Ready stages are handed out in the graph's topological execution order,
+/// so executors that launch one stage at a time get a stable schedule.
+#[derive(Debug, Clone)]
+pub struct DependencyTracker {
+    in_degree: HashMap<String, usize>,
+    dependents: HashMap<String, Vec<String>>,
+    order: HashMap<String, usize>,
+    ready: Vec<String>,
+    finished: HashSet<String>,
+}
+
+impl DependencyTracker {
+    /// Creates a tracker for a built stage graph.
+    #[must_use]
+    pub fn new(graph: &StageGraph) -> Self {
+        Self::from_specs(graph.stage_specs(), graph.execution_order())
+    }
+
+    /// Creates a tracker from stage specs and an execution order.
+    ///
+    /// Stages missing from `execution_order` are ordered after those present.
+    #[must_use]
+    pub fn from_specs(specs: &HashMap<String, StageSpec>, execution_order: &[String]) -> Self {
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, spec) in specs {
+            for dep in &spec.dependencies {
+                dependents.entry(dep.clone()).or_default().push(name.clone());
+            }
+        }
+
+        let in_degree: HashMap<String, usize> = specs
+            .iter()
+            .map(|(name, spec)| (name.clone(), spec.dependencies.len()))
+            .collect();
+        let ready = in_degree
+            .iter()
+            .filter(|(_, &count)| count == 0)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        Self {
+            in_degree,
+            dependents,
+            order: execution_order
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.clone(), i))
+                .collect(),
+            ready,
+            finished: HashSet::new(),
+        }
+    }
+
+    /// Returns true if any stage is ready to launch.
+    #[must_use]
+    pub fn has_ready(&self) -> bool {
+        !self.ready.is_empty()
+    }
+
+    /// Removes and returns all ready stages in execution order.
+    pub fn take_ready(&mut self) -> Vec<String> {
+        let mut ready = std::mem::take(&mut self.ready);
+        ready.sort_by_key(|name| self.position(name));
+        ready
+    }
+
+    /// Removes and returns the ready stage that comes first in execution order.
+    pub fn take_next_ready(&mut self) -> Option<String> {
+        let index = self
+            .ready
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, name)| self.position(name))
+            .map(|(i, _)| i)?;
+        Some(self.ready.remove(index))
+    }
+
+    /// Marks a stage as finished and returns the dependents it unblocked.
+    ///
+    /// Unblocked stages are also queued as ready. Finishing a stage twice
+    /// is a no-op.
+    pub fn mark_complete(&mut self, stage: &str) -> Vec<String> {
+        if !self.finished.insert(stage.to_string()) {
+            return Vec::new();
+        }
+
+        let mut unblocked = Vec::new();
+        for child in self.dependents.get(stage).into_iter().flatten() {
+            if let Some(count) = self.in_degree.get_mut(child) {
+                *count = count.saturating_sub(1);
+                if *count == 0 && !self.finished.contains(child) {
+                    unblocked.push(child.clone());
+                }
+            }
+        }
+        self.ready.extend(unblocked.iter().cloned());
+        unblocked
+    }
+
+    /// Marks a stage as finished without running it.
+    ///
+    /// Used when a stage's output is restored from a checkpoint.
+    pub fn restore_complete(&mut self, stage: &str) -> Vec<String> {
+        self.ready.retain(|name| name != stage);
+        self.mark_complete(stage)
+    }
+
+    /// Queues a stage to run again regardless of its dependency state.
+    ///
+    /// Used for re-execution such as guard retries.
+    pub fn requeue(&mut self, stage: impl Into<String>) {
+        self.ready.push(stage.into());
+    }
+
+    /// Returns true if the stage has been marked complete.
+    #[must_use]
+    pub fn is_finished(&self, stage: &str) -> bool {
+        self.finished.contains(stage)
+    }
+
+    /// Returns the number of unsatisfied dependencies for a stage.
+    #[must_use]
+    pub fn in_degree(&self, stage: &str) -> Option<usize> {
+        self.in_degree.get(stage).copied()
+    }
+
+    /// Returns stages that have not finished, in execution order.
+    #[must_use]
+    pub fn remaining(&self) -> Vec<String> {
+        let mut remaining: Vec<String> = self
+            .in_degree
+            .keys()
+            .filter(|name| !self.finished.contains(*name))
+            .cloned()
+            .collect();
+        remaining.sort_by_key(|name| self.position(name));
+        remaining
+    }
+
+    /// Returns true once every stage has finished.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.finished.len() >= self.in_degree.len()
+    }
+
+    fn position(&self, stage: &str) -> usize {
+        self.order.get(stage).copied().unwrap_or(usize::MAX)
+    }
+}
+
+/// A completed stage's output data, keyed with the default hasher that
+/// [`StageInputs`] uses.
+type OutputData = HashMap<String, serde_json::Value>;
+
+/// Builds strict inputs for a stage from the outputs of its completed
+/// dependencies, limited to the stage's declared input keys.
+#[must_use]
+pub fn build_stage_inputs<S: BuildHasher>(spec: &StageSpec, completed: &HashMap<String, OutputData, S>) -> StageInputs {
+    let outputs = spec
+        .dependencies
+        .iter()
+        .filter_map(|dep| completed.get(dep).map(|output| (dep.clone(), Arc::new(output.clone()))))
+        .collect();
+    StageInputs::from_shared(outputs, spec.dependencies.clone(), spec.name.clone(), true)
+        .with_declared_keys(spec.input_keys.clone())
+}
+
+/// Builds strict inputs like [`build_stage_inputs`] from shared outputs,
+/// cloning only the `Arc`s of the stage's dependencies.
+#[must_use]
+pub fn build_shared_stage_inputs<S: BuildHasher>(
+    spec: &StageSpec,
+    completed: &HashMap<String, Arc<OutputData>, S>,
+) -> StageInputs {
+    let outputs = spec
+        .dependencies
+        .iter()
+        .filter_map(|dep| completed.get(dep).map(|output| (dep.clone(), Arc::clone(output))))
+        .collect();
+    StageInputs::from_shared(outputs, spec.dependencies.clone(), spec.name.clone(), true)
+        .with_declared_keys(spec.input_keys.clone())
+}
+
+/// Emits `stage.started` for a stage.
+pub fn emit_stage_started(ctx: &dyn ExecutionContext, stage: &str) {
+    if !ctx.profile().emits("stage.started") {
+        return;
+    }
+    ctx.emit_pipeline_event(PipelineEvent::StageStarted { stage: stage.to_string() });
+}
+
+/// Stores each artifact in `store` and replaces its data with a reference.
+///
+/// Artifacts that already hold a reference are left alone; ones that fail
+/// to store keep their data and emit `artifact.persist_failed`.
+async fn persist_artifacts(
+    ctx: &PipelineContext,
+    store: &dyn ArtifactStore,
+    stage: &str,
+    artifacts: &mut [StageArtifact],
+) {
+    let run_id = ctx
+        .pipeline_run_id()
+        .map_or_else(|| "unknown".to_string(), |id| id.to_string());
+    for artifact in artifacts.iter_mut().filter(|artifact| reference_uri(&artifact.data).is_none()) {
+        match store.put(&run_id, stage, artifact).await {
+            Ok(stored) => artifact.data = artifact_reference(&stored),
+            Err(e) => {
+                warn!(stage, artifact = %artifact.id, error = %e, "Failed to persist artifact");
+                ctx.try_emit_event(
+                    "artifact.persist_failed",
+                    Some(serde_json::json!({
+                        "stage": stage,
+                        "artifact_id": artifact.id,
+                        "error": e.to_string(),
+                    })),
+                );
+            }
+        }
+    }
+}
+
+/// Emits `stage.budget_exceeded` for a stage that went over one of its
+/// resource limits.
+pub fn emit_budget_exceeded(ctx: &dyn ExecutionContext, stage: &str, exceeded: &ResourceLimitExceeded) {
+    ctx.try_emit_event(
+        "stage.budget_exceeded",
+        Some(serde_json::json!({
+            "stage": stage,
+            "limit": exceeded.limit.as_str(),
+            "code": exceeded.limit.code(),
+            "max": exceeded.max,
+            "actual": exceeded.actual,
+        })),
+    );
+}
+
+/// Emits the lifecycle event matching the output's status.
+///
+/// `Ok`, `Skip`, `Fail` and `Cancel` map to `stage.completed`,
+/// `stage.skipped`, `stage.failed` and `stage.cancelled`; other statuses
+/// emit nothing. Events the context's profile suppresses are not built.
+pub fn emit_stage_outcome(ctx: &dyn ExecutionContext, stage: &str, output: &StageOutput, duration_ms: f64) {
+    let event_type = match output.status {
+        StageStatus::Ok => "stage.completed",
+        StageStatus::Skip => "stage.skipped",
+        StageStatus::Fail => "stage.failed",
+        StageStatus::Cancel => "stage.cancelled",
+        _ => return,
+    };
+    if !ctx.profile().emits(event_type) {
+        return;
+    }
+    let stage = stage.to_string();
+    let event = match output.status {
+        StageStatus::Ok => PipelineEvent::StageCompleted { stage, duration_ms },
+        StageStatus::Skip => PipelineEvent::StageSkipped {
+            stage,
+            reason: output.skip_reason.clone(),
+        },
+        StageStatus::Fail => PipelineEvent::StageFailed {
+            stage,
+            error: output.error.clone(),
+            duration_ms,
+        },
+        _ => PipelineEvent::StageCancelled {
+            stage,
+            reason: output.cancel_reason.clone(),
+        },
+    };
+    ctx.emit_pipeline_event(event);
+}
+
+/// Runs one stage with the standard lifecycle.
+///
+/// Emits `stage.started`, executes the runner, commits copy-on-write
+/// context writes for successful stages, and emits the outcome event.
+/// With an artifact store attached to the context, returned artifacts are
+/// persisted and their data replaced by a reference.
+/// Stages that output keys their spec does not
+/// [declare](StageSpec::with_output_key) fail with an
+/// [`OutputConflictError`](crate::errors::OutputConflictError).
+/// With a [`SecretResolver`](crate::secrets::SecretResolver) attached,
+/// resolved secret values are redacted from the output's error.
+/// Stages that exceed their [`ResourceLimits`](crate::pipeline::ResourceLimits)
+/// fail with the limit's error info and emit `stage.budget_exceeded`; a
+/// stage stopped at its time limit is treated as aborted.
+/// Specs marked [`with_read_only`](StageSpec::with_read_only) get a read-only
+/// context, checked by an enforcing [`ImmutabilityInterceptor`]: a stage
+/// that writes to it fails with a [`ReadOnlyContextError`](crate::errors::ReadOnlyContextError).
+/// Stages that yielded through [`CoopYield`](crate::cancellation::CoopYield)
+/// get their yield counters in the output metadata under `coop`, and every
+/// output records its wall-clock time under `duration_ms`, except under the
+/// fast-path profile.
+/// Callbacks registered with [`StageContext::on_cancel`] run if the stage
+/// returns `Cancel`, finishes after the pipeline was cancelled, or is
+/// dropped before finishing; otherwise they are discarded.
+/// Runs with a [`WideEventEmitter`](crate::observability::WideEventEmitter)
+/// attached also emit a wide event once the outcome is known.
+/// A panicking stage fails with the panic recorded as a
+/// [`StagePanic`](crate::pipeline::StagePanic) in its metadata.
+/// Specs with a [`StallPolicy`](crate::pipeline::StallPolicy) are watched
+/// for heartbeats and emit `stage.stalled` when they go quiet; a cancelling
+/// policy stops the stage, which is treated as aborted.
+/// Specs with an [idempotency key](StageSpec::with_idempotency_key), run
+/// with an idempotency store attached, return the output cached under the
+/// rendered key without running and cache their successful outputs.
+/// The snapshot may be the run's shared `Arc`, which is not cloned.
+pub async fn run_stage(
+    spec: &StageSpec,
+    ctx: Arc<PipelineContext>,
+    inputs: StageInputs,
+    snapshot: impl Into<Arc<ContextSnapshot>>,
+) -> StageOutput {
+    let mut stage_ctx = StageContext::new(ctx.clone(), spec.name.clone(), inputs, snapshot);
+    let immutability = (spec.context_access == ContextAccess::ReadOnly).then(ImmutabilityInterceptor::enforcing);
+    if immutability.is_some() {
+        stage_ctx = stage_ctx.with_read_only();
+    }
+    let mut abort_guard = CancelCleanupGuard {
+        stage: spec.name.clone(),
+        registry: Some(stage_ctx.cancel_cleanup().clone()),
+    };
+
+    emit_stage_started(ctx.as_ref(), &spec.name);
+
+    let started_at = ctx
+        .deterministic_source()
+        .map_or_else(chrono::Utc::now, |source| source.now());
+    let stage_start = Stopwatch::start();
+    let keyed = KeyedExecution::for_stage(spec.idempotency_key.as_ref(), &stage_ctx);
+    if let Some(output) = OptionFuture::from(keyed.as_ref().map(|keyed| keyed.replay(&stage_ctx))).await.flatten() {
+        emit_stage_outcome(ctx.as_ref(), &spec.name, &output, stage_start.elapsed_ms());
+        return output;
+    }
+    if let Some(immutability) = &immutability {
+        immutability.before(&stage_ctx).await;
+    }
+    // Resolves with the output and whether the stage was stopped early
+    let execution = async {
+        let run = catch_stage_panic(spec.runner.execute(&stage_ctx));
+        let result = match spec.stall_policy {
+            Some(policy) => tokio::select! {
+                result = run => result,
+                quiet = watch_for_stall(&stage_ctx, policy) => {
+                    stage_ctx.cancellation_token().cancel("stalled");
+                    return (policy.to_output(&spec.name, quiet), true);
+                }
+            },
+            None => run.await,
+        };
+        (result.unwrap_or_else(|panic| panic.to_output(&spec.name)), false)
+    };
+    let (mut output, aborted) = match spec.resource_limits.max_duration {
+        Some(max) => {
+            if let Ok(result) = tokio::time::timeout(max, execution).await {
+                result
+            } else {
+                let exceeded = ResourceLimitExceeded::duration(max, stage_start.elapsed());
+                emit_budget_exceeded(ctx.as_ref(), &spec.name, &exceeded);
+                (exceeded.to_output(&spec.name), true)
+            }
+        }
+        None => execution.await,
+    };
+    if let Some(immutability) = &immutability {
+        output = immutability.after(&stage_ctx, output).await;
+    }
+    output = check_output(spec, ctx.as_ref(), output);
+    if let Some(store) = ctx.artifact_store() {
+        persist_artifacts(ctx.as_ref(), store.as_ref(), &spec.name, &mut output.artifacts).await;
+    }
+    abort_guard.registry = None;
+    if let Some(stats) = stage_ctx.coop_stats().filter(|_| !ctx.profile().is_fast_path()) {
+        output
+            .metadata
+            .insert("coop".to_string(), serde_json::to_value(stats).unwrap_or_default());
+    }
+    if output.status == StageStatus::Ok {
+        stage_ctx.commit_writes();
+        if let Some(keyed) = &keyed {
+            keyed.record(&output).await;
+        }
+    } else {
+        stage_ctx.discard_writes();
+    }
+    if aborted || output.status == StageStatus::Cancel || ctx.is_cancelled() {
+        run_cancel_cleanup(&spec.name, stage_ctx.cancel_cleanup()).await;
+    } else {
+        stage_ctx.cancel_cleanup().clear();
+    }
+    let duration_ms = stage_start.elapsed_ms();
+    if !ctx.profile().is_fast_path() {
+        output
+            .metadata
+            .entry("duration_ms".to_string())
+            .or_insert_with(|| serde_json::json!(duration_ms));
+    }
+
+    emit_stage_outcome(ctx.as_ref(), &spec.name, &output, duration_ms);
+    if let Some(emitter) = ctx.wide_events() {
+        emitter.emit_stage_execution(&stage_ctx, &output, started_at, duration_ms);
+    }
+    output
+}
+
+/// Fails an output over the spec's output limits or with undeclared keys,
+/// and redacts resolved secrets from its error.
+fn check_output(spec: &StageSpec, ctx: &PipelineContext, mut output: StageOutput) -> StageOutput {
+    if let Some(exceeded) = spec.resource_limits.check_output(&output) {
+        emit_budget_exceeded(ctx, &spec.name, &exceeded);
+        output = exceeded.to_output(&spec.name);
+    }
+    if let Some(conflict) = output.data.as_ref().and_then(|data| spec.check_output_keys(data)) {
+        output = StageOutput::fail(conflict.to_string());
+    }
+    if let (Some(secrets), Some(error)) = (ctx.secrets(), output.error.as_mut()) {
+        *error = secrets.redactor().redact(error);
+    }
+    output
+}
+
+/// Runs a stage's cancel cleanup callbacks and logs the results.
+async fn run_cancel_cleanup(stage: &str, registry: &CleanupRegistry) {
+    if registry.pending_count() == 0 {
+        return;
+    }
+    let (completed, failed) = registry.run_all(STAGE_CANCEL_CLEANUP_TIMEOUT_SECS).await;
+    for name in &completed {
+        info!(stage = %stage, cleanup = %name, "Stage cancel cleanup completed");
+    }
+    for (name, error) in &failed {
+        warn!(stage = %stage, cleanup = %name, error = %error, "Stage cancel cleanup failed");
+    }
+}
+
+/// Runs the cancel cleanup of a stage whose task is dropped mid-execution.
+struct CancelCleanupGuard {
+    stage: String,
+    registry: Option<Arc<CleanupRegistry>>,
+}
+
+impl Drop for CancelCleanupGuard {
+    fn drop(&mut self) {
+        let Some(registry) = self.registry.take() else {
+            return;
+        };
+        if registry.pending_count() == 0 {
+            return;
+        }
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let stage = std::mem::take(&mut self.stage);
+            handle.spawn(async move { run_cancel_cleanup(&stage, &registry).await });
+        } else {
+            warn!(
+                stage = %self.stage,
+                pending = registry.pending_count(),
+                "Stage aborted outside a runtime; cancel cleanup skipped"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ExecutionProfile, RunIdentity};
+    use crate::events::CollectingEventSink;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::{NoOpStage, Stage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn noop(name: &str) -> Arc<dyn Stage> {
+        Arc::new(NoOpStage::new(name))
+    }
+
+    fn diamond() -> StageGraph {
+        PipelineBuilder::new("diamond")
+            .stage("a", noop("a"), &[])
+            .unwrap()
+            .stage("b", noop("b"), &["a"])
+            .unwrap()
+            .stage("c", noop("c"), &["a"])
+            .unwrap()
+            .stage("d", noop("d"), &["b", "c"])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_tracker_ready_set_progression() {
+        let mut tracker = DependencyTracker::new(&diamond());
+        assert_eq!(tracker.take_ready(), vec!["a".to_string()]);
+        assert!(!tracker.has_ready());
+
+        assert_eq!(tracker.mark_complete("a").len(), 2);
+        assert_eq!(tracker.take_next_ready().as_deref(), Some("b"));
+        assert!(tracker.mark_complete("b").is_empty());
+        assert_eq!(tracker.in_degree("d"), Some(1));
+
+        assert_eq!(tracker.take_next_ready().as_deref(), Some("c"));
+        assert_eq!(tracker.mark_complete("c"), vec!["d".to_string()]);
+        assert_eq!(tracker.remaining(), vec!["d".to_string()]);
+
+        tracker.mark_complete("d");
+        assert!(tracker.is_complete());
+    }
+
+    #[test]
+    fn test_tracker_mark_complete_is_idempotent() {
+        let mut tracker = DependencyTracker::new(&diamond());
+        tracker.take_ready();
+        tracker.mark_complete("a");
+        assert!(tracker.mark_complete("a").is_empty());
+        assert_eq!(tracker.in_degree("b"), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_emits_lifecycle() {
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+        let graph = diamond();
+        let spec = graph.stage_spec("a").unwrap();
+
+        let inputs = build_stage_inputs(spec, &HashMap::new());
+        let output = run_stage(spec, ctx, inputs, ContextSnapshot::new()).await;
+
+        assert!(output.is_success());
+        let types: Vec<String> = sink.events().into_iter().map(|(t, _)| t).collect();
+        assert_eq!(types, vec!["stage.started", "stage.completed"]);
+    }
+
+    #[tokio::test]
+    async fn test_fast_path_skips_routine_events() {
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_profile(ExecutionProfile::FastPath),
+        );
+        let graph = diamond();
+        let spec = graph.stage_spec("a").unwrap();
+
+        let inputs = build_stage_inputs(spec, &HashMap::new());
+        let output = run_stage(spec, ctx.clone(), inputs, ContextSnapshot::new()).await;
+        assert!(output.is_success());
+        assert!(output.metadata.is_empty());
+        assert!(sink.events().is_empty());
+
+        ctx.try_emit_event("custom.event", Some(serde_json::json!({"n": 1})));
+        let (_, data) = sink.events().pop().unwrap();
+        let data = data.unwrap();
+        assert_eq!(data.as_object().unwrap().len(), 4);
+        assert!(data.get("pipeline_run_id").is_some());
+        assert_eq!(data["sequence"], 1);
+    }
+
+    /// Registers a cleanup, then waits until released or cancelled.
+    #[derive(Debug)]
+    struct CleanupStage {
+        cleaned: Arc<AtomicUsize>,
+        started: Arc<tokio::sync::Notify>,
+        cancel: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Stage for CleanupStage {
+        fn name(&self) -> &'static str {
+            "cleanup"
+        }
+
+        async fn execute(&self, ctx: &StageContext) -> StageOutput {
+            let cleaned = self.cleaned.clone();
+            ctx.on_cancel("release_lock", move || async move {
+                cleaned.fetch_add(1, Ordering::SeqCst);
+            });
+            self.started.notify_one();
+            if self.cancel {
+                return StageOutput::cancel("stop");
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            StageOutput::ok_empty()
+        }
+    }
+
+    fn cleanup_graph(stage: CleanupStage) -> StageGraph {
+        PipelineBuilder::new("cleanup")
+            .stage("cleanup", Arc::new(stage), &[])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_persists_artifacts_to_store() {
+        use crate::artifacts::{reference_uri, ArtifactStore, InMemoryArtifactStore};
+        use crate::pipeline::StageSpec;
+        use crate::stages::FnStage;
+
+        let store = Arc::new(InMemoryArtifactStore::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_artifact_store(store.clone()));
+        let stage = FnStage::new("render", |_ctx| {
+            StageOutput::ok_empty().with_artifacts(vec![StageArtifact::new("chart", "c1", "chart", serde_json::json!([1, 2, 3]))])
+        });
+        let spec = StageSpec::new("render", Arc::new(stage));
+
+        let output = run_stage(&spec, ctx, StageInputs::default(), ContextSnapshot::new()).await;
+        let uri = reference_uri(&output.artifacts[0].data).unwrap();
+        assert_eq!(store.get(uri).await.unwrap().unwrap().data, serde_json::json!([1, 2, 3]));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_redacts_resolved_secrets() {
+        use crate::pipeline::StageSpec;
+        use crate::secrets::{InMemorySecretsProvider, SecretResolver};
+
+        #[derive(Debug)]
+        struct LoginStage;
+
+        #[async_trait::async_trait]
+        impl Stage for LoginStage {
+            fn name(&self) -> &'static str {
+                "login"
+            }
+
+            async fn execute(&self, ctx: &StageContext) -> StageOutput {
+                match ctx.resolve_secrets(&serde_json::json!({ "password": "secret://mem/DB" })).await {
+                    Ok(config) => {
+                        ctx.try_emit_event("login.attempted", Some(config.clone()));
+                        StageOutput::fail(format!("login with {} refused", config["password"]))
+                    }
+                    Err(e) => StageOutput::fail(e.to_string()),
+                }
+            }
+        }
+
+        let sink = Arc::new(CollectingEventSink::new());
+        let resolver = SecretResolver::new().with_provider("mem", Arc::new(InMemorySecretsProvider::new().with_secret("DB", "hunter2")));
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_secrets(Arc::new(resolver)),
+        );
+        let spec = StageSpec::new("login", Arc::new(LoginStage));
+
+        let output = run_stage(&spec, ctx, StageInputs::default(), ContextSnapshot::new()).await;
+        assert_eq!(output.error.as_deref(), Some("login with \"[REDACTED]\" refused"));
+        let attempted = sink.events_of_type("login.attempted");
+        assert_eq!(attempted[0].1.as_ref().unwrap()["password"], "[REDACTED]");
+        let failed = sink.events_of_type("stage.failed");
+        assert!(!failed[0].1.as_ref().unwrap().to_string().contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_enforces_time_limit() {
+        use crate::pipeline::{ResourceLimits, StageSpec};
+
+        let cleaned = Arc::new(AtomicUsize::new(0));
+        let stage = CleanupStage {
+            cleaned: cleaned.clone(),
+            started: Arc::new(tokio::sync::Notify::new()),
+            cancel: false,
+        };
+        let spec = StageSpec::new("cleanup", Arc::new(stage))
+            .with_resource_limits(ResourceLimits::new().with_max_duration(std::time::Duration::from_millis(20)));
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+
+        let output = run_stage(&spec, ctx, StageInputs::default(), ContextSnapshot::new()).await;
+        assert!(!output.is_success());
+        assert_eq!(output.metadata["error_info"]["code"], crate::contracts::codes::STAGE_DURATION);
+        assert_eq!(cleaned.load(Ordering::SeqCst), 1);
+        let exceeded = sink.events_of_type("stage.budget_exceeded");
+        assert_eq!(exceeded.len(), 1);
+        assert_eq!(exceeded[0].1.as_ref().unwrap()["limit"], "duration");
+    }
+
+    /// Sleeps `rounds` times, reporting a heartbeat after each if asked.
+    #[derive(Debug)]
+    struct PollingStage {
+        rounds: u32,
+        gap: std::time::Duration,
+        heartbeat: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Stage for PollingStage {
+        fn name(&self) -> &'static str {
+            "poll"
+        }
+
+        async fn execute(&self, ctx: &StageContext) -> StageOutput {
+            for _ in 0..self.rounds {
+                tokio::time::sleep(self.gap).await;
+                if self.heartbeat {
+                    ctx.heartbeat();
+                }
+            }
+            StageOutput::ok_empty()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_reports_stalls() {
+        use crate::pipeline::{StageSpec, StallPolicy};
+        use std::time::Duration;
+
+        let policy = StallPolicy::new(Duration::from_millis(40));
+        let run = |heartbeat: bool| async move {
+            let stage = PollingStage {
+                rounds: 8,
+                gap: Duration::from_millis(10),
+                heartbeat,
+            };
+            let spec = StageSpec::new("poll", Arc::new(stage)).with_stall_policy(policy);
+            let sink = Arc::new(CollectingEventSink::new());
+            let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+            let output = run_stage(&spec, ctx, StageInputs::default(), ContextSnapshot::new()).await;
+            (output, sink.events_of_type("stage.stalled"))
+        };
+
+        let (output, stalled) = run(true).await;
+        assert!(output.is_success());
+        assert!(stalled.is_empty());
+
+        // A quiet stage is reported once but keeps running
+        let (output, stalled) = run(false).await;
+        assert!(output.is_success());
+        assert_eq!(stalled.len(), 1);
+        let data = stalled[0].1.as_ref().unwrap();
+        assert_eq!(data["stage"], "poll");
+        assert_eq!(data["action"], "report");
+        assert_eq!(data["heartbeats"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_stops_stalled_stage() {
+        use crate::pipeline::{StageSpec, StallPolicy};
+
+        let cleaned = Arc::new(AtomicUsize::new(0));
+        let stage = CleanupStage {
+            cleaned: cleaned.clone(),
+            started: Arc::new(tokio::sync::Notify::new()),
+            cancel: false,
+        };
+        let spec = StageSpec::new("cleanup", Arc::new(stage))
+            .with_stall_policy(StallPolicy::new(std::time::Duration::from_millis(20)).cancelling());
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+
+        let output = run_stage(&spec, ctx.clone(), StageInputs::default(), ContextSnapshot::new()).await;
+        assert_eq!(output.status, StageStatus::Fail);
+        assert!(output.error.unwrap().starts_with("Stage 'cleanup' stalled"));
+        assert_eq!(cleaned.load(Ordering::SeqCst), 1);
+        assert!(!ctx.is_cancelled());
+        let stalled = sink.events_of_type("stage.stalled");
+        assert_eq!(stalled.len(), 1);
+        assert_eq!(stalled[0].1.as_ref().unwrap()["action"], "cancel");
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_runs_cancel_cleanup_on_cancel_output() {
+        let cleaned = Arc::new(AtomicUsize::new(0));
+        let graph = cleanup_graph(CleanupStage {
+            cleaned: cleaned.clone(),
+            started: Arc::new(tokio::sync::Notify::new()),
+            cancel: true,
+        });
+        let spec = graph.stage_spec("cleanup").unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let output = run_stage(spec, ctx, StageInputs::default(), ContextSnapshot::new()).await;
+
+        assert_eq!(output.status, StageStatus::Cancel);
+        assert_eq!(cleaned.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_runs_cancel_cleanup_when_aborted() {
+        let cleaned = Arc::new(AtomicUsize::new(0));
+        let started = Arc::new(tokio::sync::Notify::new());
+        let graph = cleanup_graph(CleanupStage {
+            cleaned: cleaned.clone(),
+            started: started.clone(),
+            cancel: false,
+        });
+        let spec = graph.stage_spec("cleanup").unwrap().clone();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let task = tokio::spawn(async move {
+            run_stage(&spec, ctx, StageInputs::default(), ContextSnapshot::new()).await
+        });
+        started.notified().await;
+        task.abort();
+        assert!(task.await.unwrap_err().is_cancelled());
+
+        for _ in 0..50 {
+            if cleaned.load(Ordering::SeqCst) == 1 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cleaned.load(Ordering::SeqCst), 1);
+    }
+
+    /// Sums in a tight loop, yielding every iteration.
+    #[derive(Debug)]
+    struct CrunchStage;
+
+    #[async_trait::async_trait]
+    impl Stage for CrunchStage {
+        fn name(&self) -> &'static str {
+            "crunch"
+        }
+
+        async fn execute(&self, ctx: &StageContext) -> StageOutput {
+            let mut coop = ctx.coop().with_slice(std::time::Duration::ZERO);
+            let mut total = 0_u64;
+            for n in 0..3 {
+                if let Err(interrupt) = coop.tick().await {
+                    return interrupt.into_output();
+                }
+                total += n;
+            }
+            StageOutput::ok_value("total", serde_json::json!(total))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_attaches_coop_stats() {
+        let graph = PipelineBuilder::new("crunch")
+            .stage("crunch", Arc::new(CrunchStage), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let spec = graph.stage_spec("crunch").unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let output = run_stage(spec, ctx, StageInputs::default(), ContextSnapshot::new()).await;
+
+        assert_eq!(output.metadata["coop"]["ticks"], 3);
+        assert_eq!(output.metadata["coop"]["yields"], 3);
+
+        let noop = PipelineBuilder::new("noop").stage("a", noop("a"), &[]).unwrap().build().unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let output = run_stage(
+            noop.stage_spec("a").unwrap(),
+            ctx,
+            StageInputs::default(),
+            ContextSnapshot::new(),
+        )
+        .await;
+        assert!(!output.metadata.contains_key("coop"));
+        assert!(output.metadata["duration_ms"].as_f64().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_declared_keys_are_enforced() {
+        use crate::core::StageOutput;
+        use crate::pipeline::StageSpec;
+        use crate::stages::FnStage;
+
+        let fetch = Arc::new(FnStage::new("fetch", |_: &StageContext| {
+            StageOutput::ok(HashMap::from([
+                ("body".to_string(), serde_json::json!("<html>")),
+                ("headers".to_string(), serde_json::json!({})),
+            ]))
+        }));
+        let read = |key: &'static str| {
+            Arc::new(FnStage::new("read", move |ctx: &StageContext| match ctx.inputs().get_value("fetch", key) {
+                Ok(value) => StageOutput::ok_value("value", value.cloned().unwrap_or_default()),
+                Err(e) => StageOutput::fail(e.to_string()),
+            }))
+        };
+        let run = |fetch_spec: StageSpec, key: &'static str| {
+            let mut builder = PipelineBuilder::new("keys");
+            builder.add_stage_spec(fetch_spec).unwrap();
+            builder
+                .add_stage_spec(StageSpec::new("read", read(key)).with_dependency("fetch").with_input_key("fetch", "body"))
+                .unwrap();
+            let graph = builder.build().unwrap();
+            async move { graph.execute(Arc::new(PipelineContext::new(RunIdentity::new())), ContextSnapshot::new()).await }
+        };
+
+        let result = run(StageSpec::new("fetch", fetch.clone()), "body").await.unwrap();
+        assert_eq!(result.outputs["read"].get("value"), Some(&serde_json::json!("<html>")));
+
+        let result = run(StageSpec::new("fetch", fetch.clone()), "headers").await.unwrap();
+        assert!(result.outputs["read"].error.as_deref().unwrap().contains("fetch.headers"));
+
+        let result = run(StageSpec::new("fetch", fetch).with_output_key("body"), "body").await.unwrap();
+        assert!(result.outputs["fetch"].error.as_deref().unwrap().contains("undeclared output keys: headers"));
+    }
+
+    #[tokio::test]
+    async fn test_run_stage_replays_keyed_outputs() {
+        use crate::pipeline::{IdempotencyKeyTemplate, InMemoryIdempotencyStore, StageSpec};
+        use crate::stages::FnStage;
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let charge = {
+            let runs = Arc::clone(&runs);
+            Arc::new(FnStage::new("charge", move |_ctx| {
+                StageOutput::ok_value("receipt", serde_json::json!(runs.fetch_add(1, Ordering::SeqCst)))
+            }))
+        };
+        let template = IdempotencyKeyTemplate::parse("{user_id}:{stage}:{quote.order}").unwrap();
+        let spec = StageSpec::new("charge", charge).with_dependency("quote").with_idempotency_key(template);
+        assert!(spec.idempotent);
+
+        let store = Arc::new(InMemoryIdempotencyStore::new());
+        let sink = Arc::new(CollectingEventSink::new());
+        let mut identity = RunIdentity::new();
+        identity.user_id = Some(uuid::Uuid::new_v4());
+        let run = |total: i64| {
+            let ctx = Arc::new(
+                PipelineContext::new(identity.clone())
+                    .with_event_sink(sink.clone())
+                    .with_idempotency_store(store.clone()),
+            );
+            let quote = HashMap::from([
+                ("order".to_string(), serde_json::json!("o-1")),
+                ("total".to_string(), serde_json::json!(total)),
+            ]);
+            let inputs = StageInputs::new(
+                HashMap::from([("quote".to_string(), quote)]),
+                HashSet::from(["quote".to_string()]),
+                "charge",
+                true,
+            );
+            run_stage(&spec, ctx, inputs, ContextSnapshot::new())
+        };
+
+        let first = run(42).await;
+        let replayed = run(42).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(replayed.get("receipt"), first.get("receipt"));
+        let hits = sink.events_of_type("stage.idempotency_hit");
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].1.as_ref().unwrap()["key"].as_str().unwrap().ends_with(":charge:o-1"));
+
+        // The same key with different inputs is refused
+        let conflict = run(43).await;
+        assert!(conflict.is_failure());
+        assert!(conflict.error.as_deref().unwrap().contains("parameter mismatch"));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(sink.events_of_type("stage.idempotency_conflict").len(), 1);
+    }
+}
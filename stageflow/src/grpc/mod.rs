@@ -0,0 +1,17 @@
+//! gRPC control plane for driving pipelines remotely.
+//!
+//! [`ControlPlaneService`] runs registered pipelines on request through a
+//! [`RunManager`](crate::pipeline::RunManager) and keeps their status and
+//! events; [`ControlPlaneServer`] serves it as the
+//! `stageflow.v1.ControlPlane` service defined in
+//! `proto/stageflow/v1/control.proto`, with the `RunPipeline`,
+//! `GetRunStatus`, `CancelRun` and `StreamEvents` RPCs.
+//!
+//! Enabled by the `grpc` feature.
+
+pub mod proto;
+mod server;
+mod service;
+
+pub use server::ControlPlaneServer;
+pub use service::{ControlPlaneService, EventStream};
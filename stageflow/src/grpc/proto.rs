@@ -0,0 +1,114 @@
+//! Messages of the `stageflow.v1.ControlPlane` service.
+//!
+//! These mirror `proto/stageflow/v1/control.proto`, which non-Rust clients
+//! generate their stubs from.
+
+/// State of a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum RunState {
+    /// Not set.
+    Unspecified = 0,
+    /// The run has not finished.
+    Running = 1,
+    /// The run finished successfully.
+    Succeeded = 2,
+    /// A stage failed or the engine returned an error.
+    Failed = 3,
+    /// The run was cancelled.
+    Cancelled = 4,
+}
+
+/// Request for `RunPipeline`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RunPipelineRequest {
+    /// Name of a pipeline registered with the service.
+    #[prost(string, tag = "1")]
+    pub pipeline: String,
+    /// JSON-encoded [`ContextSnapshot`](crate::context::ContextSnapshot);
+    /// empty for an empty snapshot.
+    #[prost(string, tag = "2")]
+    pub snapshot_json: String,
+    /// Respond once the run finishes instead of as soon as it starts.
+    #[prost(bool, tag = "3")]
+    pub wait: bool,
+}
+
+/// Response of `RunPipeline` and `GetRunStatus`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RunStatus {
+    /// The pipeline run id.
+    #[prost(string, tag = "1")]
+    pub run_id: String,
+    /// The pipeline name.
+    #[prost(string, tag = "2")]
+    pub pipeline: String,
+    /// The run's state.
+    #[prost(enumeration = "RunState", tag = "3")]
+    pub state: i32,
+    /// Run error or cancellation reason.
+    #[prost(string, optional, tag = "4")]
+    pub error: Option<String>,
+    /// Total run time in milliseconds, set once the run finishes.
+    #[prost(double, tag = "5")]
+    pub duration_ms: f64,
+    /// JSON object of stage outputs keyed by stage name, set once the run
+    /// finishes.
+    #[prost(string, tag = "6")]
+    pub outputs_json: String,
+}
+
+/// Request for `GetRunStatus`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetRunStatusRequest {
+    /// The pipeline run id.
+    #[prost(string, tag = "1")]
+    pub run_id: String,
+}
+
+/// Request for `CancelRun`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CancelRunRequest {
+    /// The pipeline run id.
+    #[prost(string, tag = "1")]
+    pub run_id: String,
+    /// Why the run is cancelled.
+    #[prost(string, tag = "2")]
+    pub reason: String,
+}
+
+/// Response of `CancelRun`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CancelRunResponse {
+    /// False if the run had already finished.
+    #[prost(bool, tag = "1")]
+    pub cancelled: bool,
+}
+
+/// Request for `StreamEvents`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamEventsRequest {
+    /// The pipeline run id.
+    #[prost(string, tag = "1")]
+    pub run_id: String,
+}
+
+/// An event emitted during a run, as streamed by `StreamEvents`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PipelineEvent {
+    /// The pipeline run id.
+    #[prost(string, tag = "1")]
+    pub run_id: String,
+    /// Position in the run's event log, increasing from 1.
+    #[prost(uint64, tag = "2")]
+    pub seq: u64,
+    /// The event type, e.g. `stage.completed`.
+    #[prost(string, tag = "3")]
+    pub event_type: String,
+    /// Unix timestamp in seconds.
+    #[prost(double, tag = "4")]
+    pub timestamp: f64,
+    /// JSON-encoded event data; empty if the event has none.
+    #[prost(string, tag = "5")]
+    pub data_json: String,
+}
@@ -0,0 +1,206 @@
+//! gRPC transport for [`ControlPlaneService`].
+
+use super::proto::{
+    CancelRunRequest, CancelRunResponse, GetRunStatusRequest, PipelineEvent, RunPipelineRequest,
+    StreamEventsRequest,
+};
+use super::{ControlPlaneService, EventStream};
+use crate::errors::StageflowError;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::{empty_body, BoxBody};
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, Body, BoxFuture, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::{Request, Response, Status};
+
+/// Serves a [`ControlPlaneService`] as `stageflow.v1.ControlPlane`.
+///
+/// The wire format is defined in `proto/stageflow/v1/control.proto`. Add
+/// the server to a [`tonic::transport::Server`] alongside other services,
+/// or run it alone with [`serve`](Self::serve).
+#[derive(Debug, Clone)]
+pub struct ControlPlaneServer {
+    service: Arc<ControlPlaneService>,
+}
+
+impl ControlPlaneServer {
+    /// Creates a server for `service`.
+    #[must_use]
+    pub fn new(service: Arc<ControlPlaneService>) -> Self {
+        Self { service }
+    }
+
+    /// Serves on `addr` until the process exits.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), StageflowError> {
+        self.serve_with_shutdown(addr, std::future::pending()).await
+    }
+
+    /// Serves on `addr` until `signal` completes.
+    pub async fn serve_with_shutdown(
+        self,
+        addr: SocketAddr,
+        signal: impl Future<Output = ()> + Send,
+    ) -> Result<(), StageflowError> {
+        tonic::transport::Server::builder()
+            .add_service(self)
+            .serve_with_shutdown(addr, signal)
+            .await
+            .map_err(|e| StageflowError::Internal(format!("gRPC server failed: {e}")))
+    }
+}
+
+impl NamedService for ControlPlaneServer {
+    const NAME: &'static str = "stageflow.v1.ControlPlane";
+}
+
+/// Adapts an async fn to [`UnaryService`].
+struct Unary<F>(F);
+
+impl<F, Fut, Req, Resp> UnaryService<Req> for Unary<F>
+where
+    F: FnMut(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<Resp>, Status>>,
+{
+    type Response = Resp;
+    type Future = Fut;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        (self.0)(request)
+    }
+}
+
+/// Serves `StreamEvents`.
+struct StreamEvents(Arc<ControlPlaneService>);
+
+impl ServerStreamingService<StreamEventsRequest> for StreamEvents {
+    type Response = PipelineEvent;
+    type ResponseStream = EventStream;
+    type Future = std::future::Ready<Result<Response<EventStream>, Status>>;
+
+    fn call(&mut self, request: Request<StreamEventsRequest>) -> Self::Future {
+        std::future::ready(self.0.stream_events(&request.into_inner().run_id).map(Response::new))
+    }
+}
+
+impl<B> Service<http::Request<B>> for ControlPlaneServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = Arc::clone(&self.service);
+        match request.uri().path() {
+            "/stageflow.v1.ControlPlane/RunPipeline" => Box::pin(async move {
+                let method = Unary(move |request: Request<RunPipelineRequest>| {
+                    let service = Arc::clone(&service);
+                    async move { service.run_pipeline(request.into_inner()).await.map(Response::new) }
+                });
+                Ok(Grpc::new(ProstCodec::default()).unary(method, request).await)
+            }),
+            "/stageflow.v1.ControlPlane/GetRunStatus" => Box::pin(async move {
+                let method = Unary(move |request: Request<GetRunStatusRequest>| {
+                    let service = Arc::clone(&service);
+                    async move { service.get_run_status(&request.into_inner().run_id).await.map(Response::new) }
+                });
+                Ok(Grpc::new(ProstCodec::default()).unary(method, request).await)
+            }),
+            "/stageflow.v1.ControlPlane/CancelRun" => Box::pin(async move {
+                let method = Unary(move |request: Request<CancelRunRequest>| {
+                    let request = request.into_inner();
+                    std::future::ready(
+                        service
+                            .cancel_run(&request.run_id, &request.reason)
+                            .map(|cancelled| Response::new(CancelRunResponse { cancelled })),
+                    )
+                });
+                Ok(Grpc::new(ProstCodec::default()).unary(method, request).await)
+            }),
+            "/stageflow.v1.ControlPlane/StreamEvents" => Box::pin(async move {
+                let method = StreamEvents(service);
+                Ok(Grpc::new(ProstCodec::default()).server_streaming(method, request).await)
+            }),
+            _ => Box::pin(async move {
+                let mut response = http::Response::new(empty_body());
+                let headers = response.headers_mut();
+                headers.insert("grpc-status", http::HeaderValue::from(tonic::Code::Unimplemented as i32));
+                headers.insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static("application/grpc"));
+                Ok(response)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::proto::{RunState, RunStatus};
+    use crate::context::ContextSnapshot;
+    use crate::pipeline::{PipelineBuilder, RunManager, UnifiedStageGraph};
+    use futures::StreamExt;
+    use crate::testing::SlowStage;
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::transport::{Channel, Server};
+
+    #[tokio::test]
+    async fn test_round_trip_over_grpc() {
+        let stage = Arc::new(SlowStage::with_delay_ms("work", 0));
+        let graph = PipelineBuilder::new("jobs").stage("work", stage, &[]).unwrap().build().unwrap();
+        let runs = RunManager::new().with_pipeline(Arc::new(UnifiedStageGraph::new(graph)));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(ControlPlaneServer::new(Arc::new(ControlPlaneService::from_run_manager(Arc::new(runs)))))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+        let channel = Channel::from_shared(format!("http://{addr}")).unwrap().connect().await.unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+
+        client.ready().await.unwrap();
+        let run = RunPipelineRequest {
+            pipeline: "jobs".to_string(),
+            snapshot_json: serde_json::to_string(&ContextSnapshot::new().with_input_text("hi")).unwrap(),
+            wait: true,
+        };
+        let path = PathAndQuery::from_static("/stageflow.v1.ControlPlane/RunPipeline");
+        let status: RunStatus =
+            client.unary(Request::new(run), path, ProstCodec::default()).await.unwrap().into_inner();
+        assert_eq!(status.state(), RunState::Succeeded);
+
+        client.ready().await.unwrap();
+        let stream = StreamEventsRequest { run_id: status.run_id.clone() };
+        let path = PathAndQuery::from_static("/stageflow.v1.ControlPlane/StreamEvents");
+        let events: Vec<PipelineEvent> = client
+            .server_streaming(Request::new(stream), path, ProstCodec::default())
+            .await
+            .unwrap()
+            .into_inner()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert!(events.iter().all(|e| e.run_id == status.run_id));
+        assert!(!events.is_empty());
+
+        client.ready().await.unwrap();
+        let cancel = CancelRunRequest { run_id: "missing".to_string(), reason: String::new() };
+        let path = PathAndQuery::from_static("/stageflow.v1.ControlPlane/CancelRun");
+        let error = client
+            .unary::<_, CancelRunResponse, _>(Request::new(cancel), path, ProstCodec::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::NotFound);
+    }
+}
@@ -0,0 +1,277 @@
+//! Control plane operations over a [`RunManager`].
+
+use super::proto::{PipelineEvent, RunPipelineRequest, RunState, RunStatus};
+use crate::context::ContextSnapshot;
+use crate::events::{EventSink, RecordedEvent};
+use crate::pipeline::{self, RunInfo, RunManager, RunManagerError, UnifiedStageGraph};
+use futures::StreamExt;
+use std::sync::Arc;
+use tonic::Status;
+
+/// Stream of a run's events.
+pub type EventStream = tonic::codegen::BoxStream<PipelineEvent>;
+
+/// Runs registered pipelines on request and tracks their status and events.
+///
+/// This is the transport-independent half of the control plane;
+/// [`ControlPlaneServer`](super::ControlPlaneServer) exposes it over gRPC.
+/// Runs are kept by a [`RunManager`], which the HTTP `router` of the
+/// `server` feature can share through
+/// [`from_run_manager`](Self::from_run_manager).
+#[derive(Debug, Clone)]
+pub struct ControlPlaneService {
+    runs: Arc<RunManager>,
+}
+
+// Handlers return tonic's `Status`, however large
+#[allow(clippy::result_large_err)]
+impl ControlPlaneService {
+    /// Creates a service with no pipelines.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::from_run_manager(Arc::new(RunManager::new()))
+    }
+
+    /// Creates a service over an existing manager.
+    #[must_use]
+    pub fn from_run_manager(runs: Arc<RunManager>) -> Self {
+        Self { runs }
+    }
+
+    /// Registers a pipeline under its name.
+    ///
+    /// On a manager shared with other front ends this registers it on a
+    /// copy, which still tracks runs alongside the original.
+    #[must_use]
+    pub fn with_pipeline(mut self, graph: Arc<UnifiedStageGraph>) -> Self {
+        self.configure(|runs| runs.with_pipeline(graph));
+        self
+    }
+
+    /// Sets a sink that also receives every run's events.
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.configure(|runs| runs.with_event_sink(sink));
+        self
+    }
+
+    /// Sets how many finished runs are kept.
+    #[must_use]
+    pub fn with_max_retained_runs(mut self, max: usize) -> Self {
+        self.configure(|runs| runs.with_max_retained_runs(max));
+        self
+    }
+
+    /// Returns the manager running the pipelines.
+    #[must_use]
+    pub fn run_manager(&self) -> &Arc<RunManager> {
+        &self.runs
+    }
+
+    /// Returns the names of the registered pipelines.
+    #[must_use]
+    pub fn pipeline_names(&self) -> Vec<&str> {
+        self.runs.pipeline_names()
+    }
+
+    /// Starts a run, returning its status once started or, if the request
+    /// asks to wait, once finished.
+    ///
+    /// # Errors
+    ///
+    /// Returns `not_found` for an unknown pipeline and `invalid_argument`
+    /// for a malformed snapshot.
+    pub async fn run_pipeline(&self, request: RunPipelineRequest) -> Result<RunStatus, Status> {
+        let snapshot = if request.snapshot_json.trim().is_empty() {
+            ContextSnapshot::new()
+        } else {
+            serde_json::from_str(&request.snapshot_json)
+                .map_err(|e| Status::invalid_argument(format!("Invalid snapshot_json: {e}")))?
+        };
+        let run = self.runs.start(&request.pipeline, snapshot).map_err(|e| to_status(&e))?;
+        if !request.wait {
+            return Ok(run.into());
+        }
+        self.wait_for_run(&run.run_id).await
+    }
+
+    /// Returns the status of a run.
+    ///
+    /// # Errors
+    ///
+    /// Returns `not_found` for an unknown run.
+    pub async fn get_run_status(&self, run_id: &str) -> Result<RunStatus, Status> {
+        self.runs.status(run_id).await.map(Into::into).map_err(|e| to_status(&e))
+    }
+
+    /// Cancels a run, returning false if it had already finished.
+    ///
+    /// # Errors
+    ///
+    /// Returns `not_found` for an unknown run.
+    pub fn cancel_run(&self, run_id: &str, reason: &str) -> Result<bool, Status> {
+        self.runs.cancel(run_id, reason).map_err(|e| to_status(&e))
+    }
+
+    /// Streams a run's events from the first, ending when the run
+    /// finishes.
+    ///
+    /// A subscriber that falls more than
+    /// [`RUN_EVENT_BUFFER`](crate::pipeline::RUN_EVENT_BUFFER) events behind
+    /// receives a `resource_exhausted` error.
+    ///
+    /// # Errors
+    ///
+    /// Returns `not_found` for an unknown run.
+    pub fn stream_events(&self, run_id: &str) -> Result<EventStream, Status> {
+        let events = self.runs.events(run_id).map_err(|e| to_status(&e))?;
+        Ok(events.map(|event| event.map(Into::into).map_err(|e| to_status(&e))).boxed())
+    }
+
+    /// Blocks until a run finishes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `not_found` for an unknown run.
+    pub async fn wait_for_run(&self, run_id: &str) -> Result<RunStatus, Status> {
+        self.runs.wait(run_id).await.map(Into::into).map_err(|e| to_status(&e))
+    }
+
+    fn configure(&mut self, change: impl FnOnce(RunManager) -> RunManager) {
+        let runs = Arc::make_mut(&mut self.runs);
+        *runs = change(std::mem::take(runs));
+    }
+}
+
+impl Default for ControlPlaneService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_status(error: &RunManagerError) -> Status {
+    match error {
+        RunManagerError::UnknownPipeline(_) | RunManagerError::UnknownRun(_) => Status::not_found(error.to_string()),
+        RunManagerError::Lagged(_) => Status::resource_exhausted(error.to_string()),
+        RunManagerError::Store(_) => Status::unavailable(error.to_string()),
+    }
+}
+
+impl From<RunInfo> for RunStatus {
+    fn from(run: RunInfo) -> Self {
+        let state = match run.state {
+            pipeline::RunState::Running => RunState::Running,
+            pipeline::RunState::Succeeded => RunState::Succeeded,
+            pipeline::RunState::Failed => RunState::Failed,
+            pipeline::RunState::Cancelled => RunState::Cancelled,
+        };
+        Self {
+            outputs_json: if run.state.is_finished() {
+                serde_json::to_string(&run.outputs).unwrap_or_default()
+            } else {
+                String::new()
+            },
+            run_id: run.run_id,
+            pipeline: run.pipeline,
+            state: state.into(),
+            error: run.error,
+            duration_ms: run.duration_ms.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<RecordedEvent> for PipelineEvent {
+    fn from(event: RecordedEvent) -> Self {
+        Self {
+            run_id: event.run_id,
+            seq: event.seq,
+            event_type: event.event_type,
+            timestamp: event.timestamp,
+            data_json: event.data.map(|data| data.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::PipelineBuilder;
+    use crate::testing::SlowStage;
+
+    fn graph(delay_ms: u64) -> Arc<UnifiedStageGraph> {
+        let stage = Arc::new(SlowStage::with_delay_ms("work", delay_ms));
+        let graph = PipelineBuilder::new("jobs").stage("work", stage, &[]).unwrap().build().unwrap();
+        Arc::new(UnifiedStageGraph::new(graph))
+    }
+
+    fn service(delay_ms: u64) -> ControlPlaneService {
+        ControlPlaneService::new().with_pipeline(graph(delay_ms))
+    }
+
+    fn request(wait: bool) -> RunPipelineRequest {
+        RunPipelineRequest {
+            pipeline: "jobs".to_string(),
+            snapshot_json: String::new(),
+            wait,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_status_and_event_replay() {
+        let service = service(0);
+        let status = service.run_pipeline(request(true)).await.unwrap();
+        assert_eq!(status.state(), RunState::Succeeded);
+        assert!(status.outputs_json.contains("work"));
+        assert_eq!(service.get_run_status(&status.run_id).await.unwrap(), status);
+
+        // A finished run's stream replays its events and ends
+        let events: Vec<PipelineEvent> = service
+            .stream_events(&status.run_id)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert!(!events.is_empty());
+        assert!(events.iter().enumerate().all(|(i, e)| e.seq == i as u64 + 1));
+        assert!(events.iter().any(|e| e.event_type.starts_with("stage.")));
+
+        let missing = service.run_pipeline(RunPipelineRequest {
+            pipeline: "nope".to_string(),
+            ..request(false)
+        });
+        assert_eq!(missing.await.unwrap_err().code(), tonic::Code::NotFound);
+        assert_eq!(service.get_run_status("nope").await.unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_run() {
+        let service = service(5_000).with_max_retained_runs(1);
+        let status = service.run_pipeline(request(false)).await.unwrap();
+        assert_eq!(status.state(), RunState::Running);
+        let mut events = service.stream_events(&status.run_id).unwrap();
+
+        assert!(service.cancel_run(&status.run_id, "operator").unwrap());
+        let finished = service.wait_for_run(&status.run_id).await.unwrap();
+        assert_eq!(finished.state(), RunState::Cancelled);
+        assert!(finished.error.unwrap().contains("operator"));
+        assert!(!service.cancel_run(&status.run_id, "again").unwrap());
+        // The live stream ends with the run
+        while events.next().await.is_some() {}
+
+        // Only the latest finished run is kept
+        let next = service.run_pipeline(request(false)).await.unwrap();
+        service.cancel_run(&next.run_id, "").unwrap();
+        service.wait_for_run(&next.run_id).await.unwrap();
+        assert!(service.get_run_status(&status.run_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shares_runs_with_the_manager() {
+        let runs = Arc::new(RunManager::new());
+        let service = ControlPlaneService::from_run_manager(Arc::clone(&runs)).with_pipeline(graph(0));
+
+        let status = service.run_pipeline(request(true)).await.unwrap();
+        assert_eq!(runs.status(&status.run_id).await.unwrap().state, pipeline::RunState::Succeeded);
+        assert!(runs.pipeline_names().is_empty());
+    }
+}
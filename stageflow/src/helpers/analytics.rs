@@ -1,8 +1,17 @@
 //! Analytics event types and exporters.
 
+use crate::compression::{Codec, IdentityCodec};
+use crate::events::{BackpressureAwareEventSink, BackpressureMetrics, DropPolicy, EventSink};
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
 use uuid::Uuid;
 
 /// An analytics event.
@@ -44,6 +53,40 @@ impl AnalyticsEvent {
         }
     }
 
+    /// Creates an analytics event from a pipeline event.
+    ///
+    /// Well-known fields (`pipeline_run_id`, `stage`/`stage_name`,
+    /// `duration_ms`) are lifted out of the payload; everything else is
+    /// kept in `data`.
+    #[must_use]
+    pub fn from_event(event_type: &str, data: Option<serde_json::Value>) -> Self {
+        let mut event = Self::new(event_type);
+        let fields = match data {
+            Some(serde_json::Value::Object(fields)) => fields,
+            Some(other) => {
+                event.data.insert("value".to_string(), other);
+                return event;
+            }
+            None => return event,
+        };
+
+        for (key, value) in fields {
+            match key.as_str() {
+                "pipeline_run_id" => {
+                    event.pipeline_run_id = value.as_str().and_then(|s| Uuid::parse_str(s).ok());
+                }
+                "stage" | "stage_name" if value.is_string() => {
+                    event.stage_name = value.as_str().map(String::from);
+                }
+                "duration_ms" if value.is_number() => event.duration_ms = value.as_f64(),
+                _ => {
+                    event.data.insert(key, value);
+                }
+            }
+        }
+        event
+    }
+
     /// Converts to a dictionary.
     #[must_use]
     pub fn to_dict(&self) -> HashMap<String, serde_json::Value> {
@@ -69,28 +112,97 @@ impl AnalyticsEvent {
     }
 }
 
+/// Destination for analytics events.
+#[async_trait]
+pub trait AnalyticsExporter: Send + Sync {
+    /// Exports a single event.
+    async fn export(&self, event: &AnalyticsEvent);
+
+    /// Exports a batch of events.
+    async fn export_batch(&self, events: &[AnalyticsEvent]) {
+        for event in events {
+            self.export(event).await;
+        }
+    }
+
+    /// Flushes anything buffered by the exporter.
+    async fn flush(&self) {}
+}
+
 /// JSON file exporter for analytics events.
 pub struct JSONFileExporter {
     path: std::path::PathBuf,
     append: bool,
-    event_count: std::sync::atomic::AtomicUsize,
+    codec: Arc<dyn Codec>,
+    event_count: AtomicUsize,
+    write_lock: Mutex<()>,
 }
 
 impl JSONFileExporter {
     /// Creates a new file exporter.
+    ///
+    /// Without `append`, the file is truncated on the first export.
     #[must_use]
     pub fn new(path: impl Into<std::path::PathBuf>, append: bool) -> Self {
         Self {
             path: path.into(),
             append,
-            event_count: std::sync::atomic::AtomicUsize::new(0),
+            codec: Arc::new(IdentityCodec),
+            event_count: AtomicUsize::new(0),
+            write_lock: Mutex::new(()),
         }
     }
 
+    /// Compresses each exported batch with `codec`.
+    ///
+    /// Batches are appended as separate gzip members or zstd frames, which
+    /// [`decompress`](crate::compression::decompress) reads back as one
+    /// stream of JSON lines.
+    #[must_use]
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
     /// Returns the event count.
     #[must_use]
     pub fn event_count(&self) -> usize {
-        self.event_count.load(std::sync::atomic::Ordering::SeqCst)
+        self.event_count.load(Ordering::SeqCst)
+    }
+
+    /// Writes events as JSON lines.
+    fn write_lines(&self, events: &[AnalyticsEvent]) -> std::io::Result<()> {
+        let _guard = self.write_lock.lock();
+        let truncate = !self.append && self.event_count() == 0;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(!truncate)
+            .truncate(truncate)
+            .open(&self.path)?;
+
+        let mut lines = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut lines, &event.to_dict())?;
+            lines.push(b'\n');
+        }
+        let payload = self.codec.encode(&lines).map_err(std::io::Error::other)?;
+        file.write_all(&payload)?;
+        self.event_count.fetch_add(events.len(), Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AnalyticsExporter for JSONFileExporter {
+    async fn export(&self, event: &AnalyticsEvent) {
+        self.export_batch(std::slice::from_ref(event)).await;
+    }
+
+    async fn export_batch(&self, events: &[AnalyticsEvent]) {
+        if let Err(e) = self.write_lines(events) {
+            warn!(path = %self.path.display(), error = %e, "Failed to export analytics events");
+        }
     }
 }
 
@@ -98,7 +210,7 @@ impl JSONFileExporter {
 pub struct ConsoleExporter {
     colorize: bool,
     verbose: bool,
-    event_count: std::sync::atomic::AtomicUsize,
+    event_count: AtomicUsize,
 }
 
 impl ConsoleExporter {
@@ -108,33 +220,205 @@ impl ConsoleExporter {
         Self {
             colorize,
             verbose,
-            event_count: std::sync::atomic::AtomicUsize::new(0),
+            event_count: AtomicUsize::new(0),
         }
     }
 
     /// Returns the event count.
     #[must_use]
     pub fn event_count(&self) -> usize {
-        self.event_count.load(std::sync::atomic::Ordering::SeqCst)
+        self.event_count.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl AnalyticsExporter for ConsoleExporter {
+    async fn export(&self, event: &AnalyticsEvent) {
+        let event_type = if self.colorize {
+            format!("\x1b[36m{}\x1b[0m", event.event_type)
+        } else {
+            event.event_type.clone()
+        };
+
+        if self.verbose {
+            println!(
+                "[{}] {} {}",
+                event.timestamp.to_rfc3339(),
+                event_type,
+                serde_json::json!(event.to_dict())
+            );
+        } else {
+            println!("[{}] {}", event.timestamp.to_rfc3339(), event_type);
+        }
+        self.event_count.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Event sink that groups events into batches for an analytics exporter.
+///
+/// A batch is exported once it reaches `batch_size` events, or on the next
+/// `emit` after `flush_interval` has passed since the previous export.
+/// `try_emit` only buffers, since exporting requires awaiting.
+pub struct BatchingEventSink {
+    exporter: Arc<dyn AnalyticsExporter>,
+    batch_size: usize,
+    flush_interval: Duration,
+    buffer: Mutex<Vec<AnalyticsEvent>>,
+    last_flush: Mutex<Instant>,
+}
+
+impl BatchingEventSink {
+    /// Creates a new batching sink.
+    #[must_use]
+    pub fn new(exporter: Arc<dyn AnalyticsExporter>, batch_size: usize, flush_interval: Duration) -> Self {
+        Self {
+            exporter,
+            batch_size: batch_size.max(1),
+            flush_interval,
+            buffer: Mutex::new(Vec::new()),
+            last_flush: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Returns the number of buffered events.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    /// Exports all buffered events.
+    pub async fn flush(&self) {
+        let batch = std::mem::take(&mut *self.buffer.lock());
+        *self.last_flush.lock() = Instant::now();
+        if !batch.is_empty() {
+            self.exporter.export_batch(&batch).await;
+        }
+        self.exporter.flush().await;
+    }
+
+    /// Buffers an event and returns whether a flush is due.
+    fn push(&self, event_type: &str, data: Option<serde_json::Value>) -> bool {
+        let mut buffer = self.buffer.lock();
+        buffer.push(AnalyticsEvent::from_event(event_type, data));
+        buffer.len() >= self.batch_size || self.last_flush.lock().elapsed() >= self.flush_interval
     }
 }
 
+#[async_trait]
+impl EventSink for BatchingEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        if self.push(event_type, data) {
+            self.flush().await;
+        }
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.push(event_type, data);
+    }
+}
+
+/// Exporter that drops every event.
+struct DiscardExporter;
+
+#[async_trait]
+impl AnalyticsExporter for DiscardExporter {
+    async fn export(&self, _event: &AnalyticsEvent) {}
+}
+
 /// Buffered exporter with batching.
+///
+/// Events go through a [`BackpressureAwareEventSink`] queue of
+/// `max_buffer_size` events in front of a [`BatchingEventSink`], so the
+/// analytics path shares the queue metrics, [`DropPolicy`], and drop
+/// callback of the event system instead of buffering without bound.
 pub struct BufferedExporter {
-    batch_size: usize,
-    flush_interval_seconds: f64,
-    max_buffer_size: usize,
+    batcher: Arc<BatchingEventSink>,
+    queue: Arc<BackpressureAwareEventSink>,
 }
 
 impl BufferedExporter {
-    /// Creates a new buffered exporter.
+    /// Creates a new buffered exporter that discards its batches.
+    ///
+    /// Use [`BufferedExporter::with_exporter`] to send them somewhere.
     #[must_use]
     pub fn new(batch_size: usize, flush_interval_seconds: f64, max_buffer_size: usize) -> Self {
-        Self {
-            batch_size,
-            flush_interval_seconds,
-            max_buffer_size,
-        }
+        Self::with_exporter(Arc::new(DiscardExporter), batch_size, flush_interval_seconds, max_buffer_size)
+    }
+
+    /// Creates a new buffered exporter feeding `exporter`.
+    ///
+    /// A negative or NaN `flush_interval_seconds` flushes on every emit; an
+    /// infinite one only flushes full batches.
+    #[must_use]
+    pub fn with_exporter(
+        exporter: Arc<dyn AnalyticsExporter>,
+        batch_size: usize,
+        flush_interval_seconds: f64,
+        max_buffer_size: usize,
+    ) -> Self {
+        let flush_interval = Duration::try_from_secs_f64(flush_interval_seconds.max(0.0)).unwrap_or(Duration::MAX);
+        let batcher = Arc::new(BatchingEventSink::new(exporter, batch_size, flush_interval));
+        let queue = BackpressureAwareEventSink::new(batcher.clone(), max_buffer_size.max(1));
+        Self { batcher, queue }
+    }
+
+    /// Starts the queue worker that feeds the batching sink.
+    pub async fn start(&self) {
+        self.queue.start().await;
+    }
+
+    /// Drains the queue within `timeout_secs`, stops the worker, and
+    /// exports the remaining events.
+    pub async fn shutdown(&self, timeout_secs: f64) {
+        self.queue.stop(true, timeout_secs).await;
+        self.batcher.flush().await;
+    }
+
+    /// Exports the events that already left the queue.
+    pub async fn flush(&self) {
+        self.batcher.flush().await;
+    }
+
+    /// Sets the behavior of `emit` when the queue is full.
+    pub fn set_drop_policy(&self, policy: DropPolicy) {
+        self.queue.set_drop_policy(policy);
+    }
+
+    /// Sets the callback invoked for dropped events.
+    pub fn set_on_drop<F>(&self, callback: F)
+    where
+        F: Fn(&str, &Option<serde_json::Value>) + Send + Sync + 'static,
+    {
+        self.queue.set_on_drop(callback);
+    }
+
+    /// Returns the queue metrics.
+    #[must_use]
+    pub fn metrics(&self) -> &BackpressureMetrics {
+        self.queue.metrics()
+    }
+
+    /// Returns the number of events waiting in the queue.
+    #[must_use]
+    pub fn queue_size(&self) -> usize {
+        self.queue.queue_size()
+    }
+
+    /// Returns the underlying queue.
+    #[must_use]
+    pub fn queue(&self) -> &Arc<BackpressureAwareEventSink> {
+        &self.queue
+    }
+}
+
+#[async_trait]
+impl EventSink for BufferedExporter {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.queue.emit(event_type, data).await;
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.queue.try_emit(event_type, data);
     }
 }
 
@@ -183,4 +467,144 @@ mod tests {
         assert!(dict.contains_key("pipeline_run_id"));
         assert!(dict.contains_key("duration_ms"));
     }
+
+    #[derive(Default)]
+    struct CollectingExporter {
+        batches: Mutex<Vec<Vec<AnalyticsEvent>>>,
+    }
+
+    #[async_trait]
+    impl AnalyticsExporter for CollectingExporter {
+        async fn export(&self, event: &AnalyticsEvent) {
+            self.batches.lock().push(vec![event.clone()]);
+        }
+
+        async fn export_batch(&self, events: &[AnalyticsEvent]) {
+            self.batches.lock().push(events.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_analytics_event_from_event() {
+        let run_id = Uuid::new_v4();
+        let event = AnalyticsEvent::from_event(
+            "stage.completed",
+            Some(serde_json::json!({
+                "pipeline_run_id": run_id.to_string(),
+                "stage": "fetch",
+                "duration_ms": 12.5,
+                "status": "ok",
+            })),
+        );
+
+        assert_eq!(event.pipeline_run_id, Some(run_id));
+        assert_eq!(event.stage_name.as_deref(), Some("fetch"));
+        assert_eq!(event.duration_ms, Some(12.5));
+        assert_eq!(event.data.len(), 1);
+        assert_eq!(event.data["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_batching_sink_exports_full_batches() {
+        let exporter = Arc::new(CollectingExporter::default());
+        let sink = BatchingEventSink::new(exporter.clone(), 2, Duration::from_secs(60));
+
+        for _ in 0..3 {
+            sink.emit("test.event", None).await;
+        }
+        assert_eq!(exporter.batches.lock().len(), 1);
+        assert_eq!(sink.pending(), 1);
+
+        sink.flush().await;
+        let sizes: Vec<usize> = exporter.batches.lock().iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_exporter_shutdown_exports_everything() {
+        let exporter = Arc::new(CollectingExporter::default());
+        let buffered = BufferedExporter::with_exporter(exporter.clone(), 2, 60.0, 16);
+        buffered.start().await;
+
+        for i in 0..5 {
+            buffered.try_emit("test.event", Some(serde_json::json!({"i": i})));
+        }
+        buffered.shutdown(1.0).await;
+
+        let total: usize = exporter.batches.lock().iter().map(Vec::len).sum();
+        assert_eq!(total, 5);
+        assert_eq!(buffered.metrics().emitted(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_exporter_accepts_unusable_intervals() {
+        let exporter = Arc::new(CollectingExporter::default());
+        let never = BufferedExporter::with_exporter(exporter.clone(), 2, f64::INFINITY, 16);
+        never.start().await;
+        never.try_emit("test.event", None);
+        never.shutdown(f64::INFINITY).await;
+        assert_eq!(exporter.batches.lock().len(), 1);
+
+        let discarding = BufferedExporter::new(2, f64::NAN, 16);
+        discarding.emit("test.event", None).await;
+        assert_eq!(discarding.queue_size(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_exporter_shares_drop_behavior() {
+        let exporter = Arc::new(CollectingExporter::default());
+        let buffered = BufferedExporter::with_exporter(exporter, 1, 60.0, 2);
+        buffered.set_drop_policy(DropPolicy::DropNewest);
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let counter = dropped.clone();
+        buffered.set_on_drop(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Not started, so the queue fills up after two events
+        for _ in 0..4 {
+            buffered.emit("test.event", None).await;
+        }
+
+        assert_eq!(buffered.queue_size(), 2);
+        assert_eq!(buffered.metrics().dropped(), 2);
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_json_file_exporter_writes_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let exporter = JSONFileExporter::new(&path, false);
+
+        exporter
+            .export_batch(&[AnalyticsEvent::new("a"), AnalyticsEvent::new("b")])
+            .await;
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert_eq!(exporter.event_count(), 2);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_json_file_exporter_compresses_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl.gz");
+        let exporter = JSONFileExporter::new(&path, false)
+            .with_codec(Arc::new(crate::compression::GzipCodec::new()));
+
+        exporter.export(&AnalyticsEvent::new("a")).await;
+        exporter.export(&AnalyticsEvent::new("b")).await;
+
+        let raw = std::fs::read(&path).unwrap();
+        let contents = crate::compression::decompress(&raw).unwrap();
+        let types: Vec<String> = std::str::from_utf8(&contents)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["event_type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(types, vec!["a", "b"]);
+    }
 }
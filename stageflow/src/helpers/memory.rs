@@ -1,8 +1,20 @@
 //! Memory helpers for conversation history.
+//!
+//! [`MemoryStore`] abstracts where entries live. [`InMemoryStore`] keeps a
+//! plain per-session history, while [`VectorMemoryStore`] also embeds each
+//! entry so that [`MemoryFetchStage`] can retrieve the entries most similar
+//! to the current query instead of only the most recent ones.
 
+use crate::context::StageContext;
+use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::stages::Stage;
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// A memory entry.
@@ -15,9 +27,26 @@ pub struct MemoryEntry {
     pub timestamp: DateTime<Utc>,
     #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
+    /// Embedding of `content`, filled in by embedding-aware stores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
 }
 
 impl MemoryEntry {
+    /// Creates an entry with a fresh ID and the current time.
+    #[must_use]
+    pub fn new(session_id: Uuid, role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            session_id,
+            role: role.into(),
+            content: content.into(),
+            timestamp: Utc::now(),
+            metadata: HashMap::new(),
+            embedding: None,
+        }
+    }
+
     /// Converts to a dictionary.
     #[must_use]
     pub fn to_dict(&self) -> HashMap<String, serde_json::Value> {
@@ -31,6 +60,15 @@ impl MemoryEntry {
     }
 }
 
+/// A memory entry ranked by similarity to a query.
+#[derive(Debug, Clone)]
+pub struct ScoredMemory {
+    /// The matching entry.
+    pub entry: MemoryEntry,
+    /// Cosine similarity to the query, in `[-1, 1]`.
+    pub score: f32,
+}
+
 /// Memory configuration.
 #[derive(Debug, Clone)]
 pub struct MemoryConfig {
@@ -46,6 +84,108 @@ impl Default for MemoryConfig {
     }
 }
 
+/// Turns text into embedding vectors.
+///
+/// Implement this over an embeddings API; [`HashingEmbedder`] is a
+/// dependency-free fallback for tests and local runs.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Embeds `text`.
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, StageflowError>;
+}
+
+/// Embeds text by hashing its lowercased words into a fixed-size vector.
+///
+/// Similarity reflects shared vocabulary, not meaning.
+#[derive(Debug, Clone, Copy)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    /// Creates an embedder producing vectors of `dimensions` (at least 1).
+    #[must_use]
+    pub fn new(dimensions: usize) -> Self {
+        Self { dimensions: dimensions.max(1) }
+    }
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashingEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, StageflowError> {
+        let mut vector = vec![0.0; self.dimensions];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            word.to_lowercase().hash(&mut hasher);
+            let bucket = usize::try_from(hasher.finish() % self.dimensions as u64).unwrap_or_default();
+            vector[bucket] += 1.0;
+        }
+        Ok(vector)
+    }
+}
+
+/// Cosine similarity of two vectors; zero if either is empty or all zeros
+/// or their lengths differ.
+#[must_use]
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// Storage for conversation memory.
+#[async_trait]
+pub trait MemoryStore: Send + Sync {
+    /// Stores an entry.
+    async fn store(&self, entry: MemoryEntry) -> Result<(), StageflowError>;
+
+    /// Fetches the most recent entries for a session, oldest first.
+    async fn fetch(&self, session_id: Uuid, config: &MemoryConfig) -> Result<Vec<MemoryEntry>, StageflowError>;
+
+    /// Returns up to `top_k` entries of a session most similar to `query`,
+    /// best first.
+    ///
+    /// Stores without embeddings return an error, which is the default.
+    async fn search(
+        &self,
+        session_id: Uuid,
+        query: &str,
+        top_k: usize,
+        config: &MemoryConfig,
+    ) -> Result<Vec<ScoredMemory>, StageflowError> {
+        let _ = (session_id, top_k, config);
+        Err(StageflowError::Internal(format!("Memory store cannot search for '{query}'")))
+    }
+}
+
+fn recent(entries: &[MemoryEntry], config: &MemoryConfig) -> Vec<MemoryEntry> {
+    let mut recent: Vec<MemoryEntry> = entries
+        .iter()
+        .rev()
+        .filter(|e| config.include_system || e.role != "system")
+        .take(config.max_entries)
+        .cloned()
+        .collect();
+    recent.reverse();
+    recent
+}
+
 /// In-memory store for memory entries.
 #[derive(Default)]
 pub struct InMemoryStore {
@@ -67,28 +207,228 @@ impl InMemoryStore {
     /// Fetches entries for a session.
     #[must_use]
     pub fn fetch(&self, session_id: Uuid, config: &MemoryConfig) -> Vec<MemoryEntry> {
-        self.entries.read().get(&session_id).cloned().unwrap_or_default()
+        self.entries.read().get(&session_id).map(|entries| recent(entries, config)).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl MemoryStore for InMemoryStore {
+    async fn store(&self, entry: MemoryEntry) -> Result<(), StageflowError> {
+        InMemoryStore::store(self, entry);
+        Ok(())
+    }
+
+    async fn fetch(&self, session_id: Uuid, config: &MemoryConfig) -> Result<Vec<MemoryEntry>, StageflowError> {
+        Ok(InMemoryStore::fetch(self, session_id, config))
+    }
+}
+
+/// In-process store that embeds entries and searches them by brute-force
+/// cosine similarity.
+///
+/// Search cost grows linearly with a session's history, which is fine for
+/// conversation-sized memories; larger corpora belong in a vector database
+/// behind [`MemoryStore`].
+pub struct VectorMemoryStore {
+    embedder: Arc<dyn Embedder>,
+    entries: parking_lot::RwLock<HashMap<Uuid, Vec<MemoryEntry>>>,
+}
+
+impl VectorMemoryStore {
+    /// Creates a store embedding entries with `embedder`.
+    #[must_use]
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        Self { embedder, entries: parking_lot::RwLock::default() }
+    }
+
+    /// Returns the number of entries stored for a session.
+    #[must_use]
+    pub fn len(&self, session_id: Uuid) -> usize {
+        self.entries.read().get(&session_id).map_or(0, Vec::len)
+    }
+}
+
+#[async_trait]
+impl MemoryStore for VectorMemoryStore {
+    async fn store(&self, mut entry: MemoryEntry) -> Result<(), StageflowError> {
+        if entry.embedding.is_none() {
+            entry.embedding = Some(self.embedder.embed(&entry.content).await?);
+        }
+        self.entries.write().entry(entry.session_id).or_default().push(entry);
+        Ok(())
+    }
+
+    async fn fetch(&self, session_id: Uuid, config: &MemoryConfig) -> Result<Vec<MemoryEntry>, StageflowError> {
+        Ok(self.entries.read().get(&session_id).map(|entries| recent(entries, config)).unwrap_or_default())
+    }
+
+    async fn search(
+        &self,
+        session_id: Uuid,
+        query: &str,
+        top_k: usize,
+        config: &MemoryConfig,
+    ) -> Result<Vec<ScoredMemory>, StageflowError> {
+        let query = self.embedder.embed(query).await?;
+        let entries = self.entries.read();
+        let mut scored: Vec<ScoredMemory> = entries
+            .get(&session_id)
             .into_iter()
+            .flatten()
             .filter(|e| config.include_system || e.role != "system")
-            .rev()
-            .take(config.max_entries)
-            .collect::<Vec<_>>()
-            .into_iter()
-            .rev()
-            .collect()
+            .filter_map(|e| {
+                let score = cosine_similarity(&query, e.embedding.as_deref()?);
+                Some(ScoredMemory { entry: e.clone(), score })
+            })
+            .collect();
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(top_k);
+        Ok(scored)
     }
 }
 
 /// Memory fetch stage.
+///
+/// Loads the session's memory into `Enrichments.memory` and the stage
+/// output. By default that is the most recent history; with
+/// [`with_top_k`](Self::with_top_k) it is the entries most similar to the
+/// query text, taken from `query_key` or the snapshot's input text.
 pub struct MemoryFetchStage {
-    store: std::sync::Arc<InMemoryStore>,
+    store: Arc<dyn MemoryStore>,
     config: MemoryConfig,
+    top_k: Option<usize>,
+    query_key: Option<String>,
 }
 
 impl MemoryFetchStage {
     /// Creates a new fetch stage.
     #[must_use]
-    pub fn new(store: std::sync::Arc<InMemoryStore>, config: MemoryConfig) -> Self {
-        Self { store, config }
+    pub fn new(store: Arc<dyn MemoryStore>, config: MemoryConfig) -> Self {
+        Self { store, config, top_k: None, query_key: None }
+    }
+
+    /// Retrieves the `top_k` entries most similar to the query.
+    #[must_use]
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Reads the query text from an upstream output instead of the input text.
+    #[must_use]
+    pub fn with_query_key(mut self, key: impl Into<String>) -> Self {
+        self.query_key = Some(key.into());
+        self
+    }
+}
+
+impl std::fmt::Debug for MemoryFetchStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryFetchStage")
+            .field("config", &self.config)
+            .field("top_k", &self.top_k)
+            .field("query_key", &self.query_key)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Stage for MemoryFetchStage {
+    fn name(&self) -> &'static str {
+        "memory_fetch"
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let Some(session_id) = ctx.pipeline_ctx().run_id().session_id else {
+            return StageOutput::skip("No session to fetch memory for");
+        };
+        let query = match &self.query_key {
+            Some(key) => ctx.inputs().find_value(key).and_then(serde_json::Value::as_str).map(str::to_string),
+            None => ctx.snapshot().input_text.clone(),
+        };
+
+        let (entries, query) = match (self.top_k, query) {
+            (Some(top_k), Some(query)) => match self.store.search(session_id, &query, top_k, &self.config).await {
+                Ok(scored) => {
+                    let entries = scored
+                        .into_iter()
+                        .map(|s| {
+                            let mut entry = s.entry.to_dict();
+                            entry.insert("score".to_string(), serde_json::json!(s.score));
+                            entry
+                        })
+                        .collect::<Vec<_>>();
+                    (entries, Some(query))
+                }
+                Err(e) => return StageOutput::fail(format!("Memory search failed: {e}")),
+            },
+            _ => match self.store.fetch(session_id, &self.config).await {
+                Ok(entries) => (entries.iter().map(MemoryEntry::to_dict).collect(), None),
+                Err(e) => return StageOutput::fail(format!("Memory fetch failed: {e}")),
+            },
+        };
+
+        let memory = serde_json::json!({ "entries": entries, "query": query });
+        {
+            let mut enrichments = ctx.pipeline_ctx().enrichments.write();
+            if let Some(object) = enrichments.as_object_mut() {
+                object.insert("memory".to_string(), memory.clone());
+            }
+        }
+        StageOutput::ok_value("memory", memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageInputs};
+
+    async fn seeded(session_id: Uuid) -> Arc<VectorMemoryStore> {
+        let store = Arc::new(VectorMemoryStore::new(Arc::new(HashingEmbedder::default())));
+        for (role, content) in [
+            ("user", "My dog is called Rex"),
+            ("assistant", "Rex is a great name for a dog"),
+            ("user", "I live in Lisbon"),
+            ("system", "Be concise"),
+        ] {
+            MemoryStore::store(store.as_ref(), MemoryEntry::new(session_id, role, content)).await.unwrap();
+        }
+        store
+    }
+
+    #[tokio::test]
+    async fn test_vector_search_ranks_by_similarity() {
+        let session_id = Uuid::new_v4();
+        let store = seeded(session_id).await;
+        let config = MemoryConfig { include_system: false, ..MemoryConfig::default() };
+
+        let hits = store.search(session_id, "what is my dog called", 2, &config).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].entry.content, "My dog is called Rex");
+        assert!(hits[0].score > hits[1].score);
+        assert!(store.search(Uuid::new_v4(), "dog", 2, &config).await.unwrap().is_empty());
+        assert!(InMemoryStore::new().search(session_id, "dog", 2, &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stage_writes_top_k_into_enrichments() {
+        let session_id = Uuid::new_v4();
+        let pipeline = Arc::new(PipelineContext::new(RunIdentity::new().with_session_id(session_id)));
+        let stage = MemoryFetchStage::new(seeded(session_id).await, MemoryConfig::default()).with_top_k(1);
+        let snapshot = ContextSnapshot::new().with_input_text("where do I live?");
+        let ctx = StageContext::new(pipeline.clone(), "memory_fetch", StageInputs::default(), snapshot);
+
+        let output = stage.execute(&ctx).await;
+        assert!(output.is_success());
+        let memory = pipeline.enrichments.read()["memory"].clone();
+        assert_eq!(memory["query"], "where do I live?");
+        assert_eq!(memory["entries"].as_array().map(Vec::len), Some(1));
+        assert_eq!(memory["entries"][0]["content"], "I live in Lisbon");
+        assert_eq!(output.get("memory"), Some(&memory));
+
+        let recent = MemoryFetchStage::new(Arc::new(InMemoryStore::new()), MemoryConfig::default());
+        let output = recent.execute(&StageContext::new(pipeline, "memory_fetch", StageInputs::default(), ContextSnapshot::new())).await;
+        assert_eq!(output.get("memory").map(|m| m["entries"].clone()), Some(serde_json::json!([])));
     }
 }
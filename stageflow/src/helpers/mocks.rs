@@ -1,9 +1,32 @@
 //! Mock providers for testing.
+//!
+//! Besides the scripted mocks, a [`Cassette`] records the calls a real
+//! provider answers and plays them back later, so integration tests of
+//! pipelines that call external services run without the network.
+//! [`CassetteLLMProvider`] and [`CassetteTool`] wrap an LLM provider and a
+//! tool; other providers, such as STT and TTS, can use
+//! [`Cassette::record`] and [`Cassette::replay`] directly.
 
+use super::providers::{LLMChunk, LLMError, LLMProvider, LLMRequest, LLMResponse, LLMStream};
+use crate::errors::{StageflowError, ToolError};
+use crate::tools::{Tool, ToolDefinition, ToolInput, ToolOutput, UndoMetadata};
+use async_trait::async_trait;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Mock LLM provider.
+///
+/// Replies with the response of the longest pattern found in the prompt,
+/// the prompt itself in echo mode, or the configured responses in turn.
+/// Streams split the reply into words, one chunk each, and token counts
+/// are word counts.
 pub struct MockLLMProvider {
     responses: Vec<String>,
     patterns: HashMap<String, String>,
@@ -27,6 +50,34 @@ impl MockLLMProvider {
         }
     }
 
+    /// Replies with `response` to prompts containing `pattern`.
+    #[must_use]
+    pub fn with_pattern(mut self, pattern: impl Into<String>, response: impl Into<String>) -> Self {
+        self.patterns.insert(pattern.into(), response.into());
+        self
+    }
+
+    /// Replies with the prompt when no pattern matches.
+    #[must_use]
+    pub fn with_echo(mut self) -> Self {
+        self.echo_mode = true;
+        self
+    }
+
+    /// Waits `latency_ms` before a completion and before each streamed chunk.
+    #[must_use]
+    pub fn with_latency_ms(mut self, latency_ms: u64) -> Self {
+        self.latency_ms = latency_ms;
+        self
+    }
+
+    /// Fails this fraction of calls, spread evenly and deterministically.
+    #[must_use]
+    pub fn with_fail_rate(mut self, fail_rate: f64) -> Self {
+        self.fail_rate = fail_rate.clamp(0.0, 1.0);
+        self
+    }
+
     /// Returns the call count.
     #[must_use]
     pub fn call_count(&self) -> usize {
@@ -37,6 +88,81 @@ impl MockLLMProvider {
     pub fn reset(&self) {
         self.call_count.store(0, Ordering::SeqCst);
     }
+
+    fn reply(&self, request: &LLMRequest) -> Result<String, LLMError> {
+        let call = self.call_count.fetch_add(1, Ordering::SeqCst);
+        let calls = f64::from(u32::try_from(call).unwrap_or(u32::MAX));
+        if ((calls + 1.0) * self.fail_rate).floor() > (calls * self.fail_rate).floor() {
+            return Err(LLMError::Provider {
+                provider: "mock".to_string(),
+                message: "Simulated failure".to_string(),
+                retryable: true,
+            });
+        }
+        let prompt = request.last_content();
+        let matched = self
+            .patterns
+            .iter()
+            .filter(|(pattern, _)| prompt.contains(pattern.as_str()))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, response)| response.clone());
+        Ok(match matched {
+            Some(response) => response,
+            None if self.echo_mode => prompt.to_string(),
+            None if self.responses.is_empty() => String::new(),
+            None => self.responses[call % self.responses.len()].clone(),
+        })
+    }
+}
+
+fn word_count(text: &str) -> u32 {
+    u32::try_from(text.split_whitespace().count()).unwrap_or(u32::MAX)
+}
+
+#[async_trait]
+impl LLMProvider for MockLLMProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn complete(&self, request: &LLMRequest) -> Result<LLMResponse, LLMError> {
+        if self.latency_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(self.latency_ms)).await;
+        }
+        let content = self.reply(request)?;
+        Ok(LLMResponse {
+            model: request.model.clone().unwrap_or_else(|| "mock".to_string()),
+            provider: "mock".to_string(),
+            input_tokens: Some(word_count(request.last_content())),
+            output_tokens: Some(word_count(&content)),
+            latency_ms: None,
+            finish_reason: Some("stop".to_string()),
+            tool_calls: None,
+            cached_tokens: None,
+            content,
+        })
+    }
+
+    async fn stream(&self, request: &LLMRequest) -> Result<LLMStream, LLMError> {
+        let content = self.reply(request)?;
+        let words: Vec<String> = content.split_inclusive(char::is_whitespace).map(str::to_string).collect();
+        let count = words.len();
+        let (input_tokens, output_tokens) = (word_count(request.last_content()), word_count(&content));
+        let latency = Duration::from_millis(self.latency_ms);
+        let chunks = futures::stream::iter(words.into_iter().enumerate()).then(move |(i, word)| async move {
+            if !latency.is_zero() {
+                tokio::time::sleep(latency).await;
+            }
+            let mut chunk = LLMChunk::text(word);
+            if i + 1 == count {
+                chunk.finish_reason = Some("stop".to_string());
+                chunk.input_tokens = Some(input_tokens);
+                chunk.output_tokens = Some(output_tokens);
+            }
+            Ok(chunk)
+        });
+        Ok(Box::pin(chunks))
+    }
 }
 
 /// Mock STT provider.
@@ -122,3 +248,444 @@ impl Default for MockToolExecutor {
         Self::new()
     }
 }
+
+/// Whether a [`Cassette`] captures new interactions or replays saved ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Calls go to the real provider and are captured.
+    Record,
+    /// Calls are answered from the saved interactions.
+    Playback,
+}
+
+/// A captured request and the response it got.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The kind of call, such as `llm` or `tool`.
+    pub kind: String,
+    /// The request, as JSON.
+    pub request: serde_json::Value,
+    /// The response, as JSON.
+    pub response: serde_json::Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// Request/response pairs captured from real providers, saved as a JSON file.
+///
+/// In playback, each call is answered by the first interaction not yet
+/// played whose kind and request equal the call's, so repeated identical
+/// calls replay their responses in recorded order. Only successful calls
+/// are captured, and fields a response type skips when serializing, such
+/// as [`TTSResponse::audio`](super::TTSResponse::audio), come back empty.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    interactions: Mutex<Vec<Interaction>>,
+    played: Mutex<Vec<bool>>,
+}
+
+impl Cassette {
+    /// Creates an empty cassette that records into `path` when saved.
+    #[must_use]
+    pub fn recording(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: CassetteMode::Record,
+            interactions: Mutex::new(Vec::new()),
+            played: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads a saved cassette for playback.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, StageflowError> {
+        let path = path.into();
+        let bytes = std::fs::read(&path)?;
+        let file: CassetteFile =
+            serde_json::from_slice(&bytes).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        Ok(Self {
+            path,
+            mode: CassetteMode::Playback,
+            played: Mutex::new(vec![false; file.interactions.len()]),
+            interactions: Mutex::new(file.interactions),
+        })
+    }
+
+    /// Plays back `path` if it exists, and records into it otherwise, so the
+    /// first run of a test captures the cassette the later runs use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an existing file cannot be read or parsed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, StageflowError> {
+        let path = path.into();
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::recording(path))
+        }
+    }
+
+    /// Returns the cassette file path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the mode.
+    #[must_use]
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Returns true if calls go to the real provider.
+    #[must_use]
+    pub fn is_recording(&self) -> bool {
+        self.mode == CassetteMode::Record
+    }
+
+    /// Returns the captured or loaded interactions.
+    #[must_use]
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions.lock().clone()
+    }
+
+    /// Returns how many loaded interactions have not been played yet.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.played.lock().iter().filter(|played| !**played).count()
+    }
+
+    /// Captures a call and its response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either does not serialize to JSON.
+    pub fn record<Req: Serialize, Resp: Serialize>(
+        &self,
+        kind: &str,
+        request: &Req,
+        response: &Resp,
+    ) -> Result<(), StageflowError> {
+        let interaction = Interaction {
+            kind: kind.to_string(),
+            request: to_json(request)?,
+            response: to_json(response)?,
+        };
+        self.interactions.lock().push(interaction);
+        self.played.lock().push(true);
+        Ok(())
+    }
+
+    /// Answers a call from the saved interactions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no unplayed interaction matches the call, or its
+    /// response does not deserialize into `Resp`.
+    pub fn replay<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        kind: &str,
+        request: &Req,
+    ) -> Result<Resp, StageflowError> {
+        let request = to_json(request)?;
+        let interactions = self.interactions.lock();
+        let mut played = self.played.lock();
+        let index = interactions
+            .iter()
+            .zip(played.iter())
+            .position(|(i, played)| !played && i.kind == kind && i.request == request)
+            .ok_or_else(|| {
+                StageflowError::Internal(format!(
+                    "No recorded {kind} interaction in {} matches request {request}",
+                    self.path.display()
+                ))
+            })?;
+        played[index] = true;
+        serde_json::from_value(interactions[index].response.clone())
+            .map_err(|e| StageflowError::Serialization(e.to_string()))
+    }
+
+    /// Writes the interactions to the cassette file, creating its directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self) -> Result<(), StageflowError> {
+        let file = CassetteFile { interactions: self.interactions() };
+        let bytes = serde_json::to_vec_pretty(&file).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, bytes)?;
+        Ok(())
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<serde_json::Value, StageflowError> {
+    serde_json::to_value(value).map_err(|e| StageflowError::Serialization(e.to_string()))
+}
+
+fn cassette_llm_error(error: &StageflowError) -> LLMError {
+    LLMError::Provider {
+        provider: "cassette".to_string(),
+        message: error.to_string(),
+        retryable: false,
+    }
+}
+
+/// LLM provider that records a real provider's completions into a
+/// [`Cassette`], or plays them back without one.
+///
+/// Streams are recorded and replayed as whole completions, yielded as a
+/// single chunk.
+pub struct CassetteLLMProvider {
+    inner: Option<Arc<dyn LLMProvider>>,
+    cassette: Arc<Cassette>,
+}
+
+impl CassetteLLMProvider {
+    /// Records the completions of `inner`.
+    #[must_use]
+    pub fn recording(inner: Arc<dyn LLMProvider>, cassette: Arc<Cassette>) -> Self {
+        Self { inner: Some(inner), cassette }
+    }
+
+    /// Plays back recorded completions.
+    #[must_use]
+    pub fn playback(cassette: Arc<Cassette>) -> Self {
+        Self { inner: None, cassette }
+    }
+
+    /// Records through `inner` or plays back, following the cassette's mode.
+    #[must_use]
+    pub fn new(inner: Arc<dyn LLMProvider>, cassette: Arc<Cassette>) -> Self {
+        if cassette.is_recording() {
+            Self::recording(inner, cassette)
+        } else {
+            Self::playback(cassette)
+        }
+    }
+
+    /// Returns the cassette.
+    #[must_use]
+    pub fn cassette(&self) -> &Arc<Cassette> {
+        &self.cassette
+    }
+}
+
+#[async_trait]
+impl LLMProvider for CassetteLLMProvider {
+    fn name(&self) -> &str {
+        self.inner.as_ref().map_or("cassette", |inner| inner.name())
+    }
+
+    async fn complete(&self, request: &LLMRequest) -> Result<LLMResponse, LLMError> {
+        let Some(inner) = &self.inner else {
+            return self.cassette.replay("llm", request).map_err(|e| cassette_llm_error(&e));
+        };
+        let response = inner.complete(request).await?;
+        self.cassette.record("llm", request, &response).map_err(|e| cassette_llm_error(&e))?;
+        Ok(response)
+    }
+}
+
+/// Request key of a recorded tool call; the action and run ids change
+/// between runs, so only the tool, payload and behavior are matched.
+#[derive(Serialize)]
+struct ToolCallKey<'a> {
+    tool_name: &'a str,
+    payload: &'a serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    behavior: Option<&'a str>,
+}
+
+impl<'a> From<&'a ToolInput> for ToolCallKey<'a> {
+    fn from(input: &'a ToolInput) -> Self {
+        Self {
+            tool_name: &input.tool_name,
+            payload: &input.payload,
+            behavior: input.behavior.as_deref(),
+        }
+    }
+}
+
+/// Tool that records a real tool's outputs into a [`Cassette`], or plays
+/// them back without one.
+pub struct CassetteTool {
+    inner: Option<Arc<dyn Tool>>,
+    definition: ToolDefinition,
+    cassette: Arc<Cassette>,
+}
+
+impl CassetteTool {
+    /// Records the outputs of `inner`.
+    #[must_use]
+    pub fn recording(inner: Arc<dyn Tool>, cassette: Arc<Cassette>) -> Self {
+        Self { definition: inner.definition(), inner: Some(inner), cassette }
+    }
+
+    /// Plays back recorded outputs for the tool `definition` describes.
+    #[must_use]
+    pub fn playback(definition: ToolDefinition, cassette: Arc<Cassette>) -> Self {
+        Self { inner: None, definition, cassette }
+    }
+
+    /// Records through `inner` or plays back, following the cassette's mode.
+    #[must_use]
+    pub fn new(inner: Arc<dyn Tool>, cassette: Arc<Cassette>) -> Self {
+        if cassette.is_recording() {
+            Self::recording(inner, cassette)
+        } else {
+            Self::playback(inner.definition(), cassette)
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for CassetteTool {
+    fn action_type(&self) -> &str {
+        &self.definition.action_type
+    }
+
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    async fn execute(&self, input: ToolInput) -> Result<ToolOutput, ToolError> {
+        let to_tool_error = |e: StageflowError| ToolError::execution_failed(&self.definition.name, e.to_string());
+        let Some(inner) = &self.inner else {
+            return self.cassette.replay("tool", &ToolCallKey::from(&input)).map_err(to_tool_error);
+        };
+        let key = serde_json::to_value(ToolCallKey::from(&input))
+            .map_err(|e| ToolError::execution_failed(&self.definition.name, e.to_string()))?;
+        let output = inner.execute(input).await?;
+        self.cassette.record("tool", &key, &output).map_err(to_tool_error)?;
+        Ok(output)
+    }
+
+    async fn undo(&self, metadata: &UndoMetadata) -> Result<(), ToolError> {
+        match &self.inner {
+            Some(inner) => inner.undo(metadata).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::STTResponse;
+
+    #[tokio::test]
+    async fn test_cassette_records_and_plays_back_llm_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassettes/llm.json");
+        let real = Arc::new(MockLLMProvider::new(vec!["first".to_string(), "second".to_string()]));
+
+        let cassette = Arc::new(Cassette::open(&path).unwrap());
+        assert!(cassette.is_recording());
+        let provider = CassetteLLMProvider::new(real.clone(), Arc::clone(&cassette));
+        let request = LLMRequest::user("hello");
+        assert_eq!(provider.complete(&request).await.unwrap().content, "first");
+        assert_eq!(provider.complete(&request).await.unwrap().content, "second");
+        cassette.save().unwrap();
+
+        let cassette = Arc::new(Cassette::open(&path).unwrap());
+        assert_eq!(cassette.mode(), CassetteMode::Playback);
+        let provider = CassetteLLMProvider::new(real.clone(), Arc::clone(&cassette));
+        let response = stream_text(&provider, &request).await;
+        assert_eq!(response, "first");
+        assert_eq!(provider.complete(&request).await.unwrap().content, "second");
+        assert_eq!(real.call_count(), 2);
+        assert_eq!(cassette.remaining(), 0);
+
+        let err = provider.complete(&request).await.unwrap_err();
+        assert!(!err.is_retryable());
+        assert!(provider.complete(&LLMRequest::user("other")).await.is_err());
+    }
+
+    async fn stream_text(provider: &dyn LLMProvider, request: &LLMRequest) -> String {
+        let mut stream = provider.stream(request).await.unwrap();
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            text.push_str(&chunk.unwrap().delta);
+        }
+        text
+    }
+
+    struct UpperTool {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Tool for UpperTool {
+        fn action_type(&self) -> &'static str {
+            "UPPER"
+        }
+
+        fn name(&self) -> &'static str {
+            "upper"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition::new("upper", "UPPER")
+        }
+
+        async fn execute(&self, input: ToolInput) -> Result<ToolOutput, ToolError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let text = input.payload["text"].as_str().unwrap_or_default().to_uppercase();
+            Ok(ToolOutput::ok(Some(serde_json::json!({ "text": text }))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cassette_tool_matches_on_payload_not_action_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tool.json");
+        let real = Arc::new(UpperTool { calls: AtomicUsize::new(0) });
+
+        let cassette = Arc::new(Cassette::recording(&path));
+        let tool = CassetteTool::new(real.clone(), Arc::clone(&cassette));
+        let input = ToolInput::new("upper", serde_json::json!({ "text": "hi" }));
+        tool.execute(input).await.unwrap();
+        cassette.save().unwrap();
+
+        let cassette = Arc::new(Cassette::load(&path).unwrap());
+        let tool = CassetteTool::playback(real.definition(), cassette);
+        let output = tool.execute(ToolInput::new("upper", serde_json::json!({ "text": "hi" }))).await.unwrap();
+        assert_eq!(output.data.unwrap()["text"], "HI");
+        assert_eq!(real.calls.load(Ordering::SeqCst), 1);
+
+        let err = tool.execute(ToolInput::new("upper", serde_json::json!({ "text": "bye" }))).await;
+        assert!(matches!(err, Err(ToolError::ExecutionFailed { .. })));
+    }
+
+    #[test]
+    fn test_cassette_records_other_providers_directly() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stt.json");
+        let cassette = Cassette::recording(&path);
+        let audio = serde_json::json!({ "audio_sha256": "abc" });
+        let transcript = STTResponse { text: "hello".to_string(), ..STTResponse::default() };
+        cassette.record("stt", &audio, &transcript).unwrap();
+        cassette.save().unwrap();
+
+        let cassette = Cassette::load(&path).unwrap();
+        let replayed: STTResponse = cassette.replay("stt", &audio).unwrap();
+        assert_eq!(replayed.text, "hello");
+        assert!(cassette.replay::<_, STTResponse>("tts", &audio).is_err());
+    }
+}
@@ -4,17 +4,36 @@ pub mod analytics;
 pub mod guardrails;
 pub mod memory;
 pub mod mocks;
+#[cfg(feature = "openai")]
+pub mod openai;
 pub mod providers;
 pub mod runtime;
 pub mod streaming;
 pub mod timestamps;
 pub mod uuid_utils;
 
-pub use analytics::{AnalyticsEvent, AnalyticsSink, BufferedExporter, ConsoleExporter, JSONFileExporter};
-pub use guardrails::{ContentFilter, GuardrailResult, GuardrailStage, InjectionDetector, PIIDetector, PolicyViolation};
-pub use memory::{InMemoryStore, MemoryConfig, MemoryEntry, MemoryFetchStage};
-pub use mocks::{MockAuthProvider, MockLLMProvider, MockSTTProvider, MockToolExecutor, MockTTSProvider};
-pub use providers::{LLMResponse, STTResponse, TTSResponse};
+pub use analytics::{
+    AnalyticsEvent, AnalyticsExporter, AnalyticsSink, BatchingEventSink, BufferedExporter, ConsoleExporter,
+    JSONFileExporter,
+};
+pub use guardrails::{
+    ContentFilter, CustomRule, CustomRuleFn, GuardrailAction, GuardrailCheck, GuardrailResult, GuardrailStage,
+    InjectionDetector, PIIDetector, PiiMatch, PolicySet, PolicyViolation, ViolationType, PII_TYPES,
+};
+pub use memory::{
+    cosine_similarity, Embedder, HashingEmbedder, InMemoryStore, MemoryConfig, MemoryEntry, MemoryFetchStage,
+    MemoryStore, ScoredMemory, VectorMemoryStore,
+};
+pub use mocks::{
+    Cassette, CassetteLLMProvider, CassetteMode, CassetteTool, Interaction, MockAuthProvider, MockLLMProvider,
+    MockSTTProvider, MockToolExecutor, MockTTSProvider,
+};
+#[cfg(feature = "openai")]
+pub use openai::{OpenAIProvider, DEFAULT_LLM_TIMEOUT, DEFAULT_OPENAI_BASE_URL, DEFAULT_OPENAI_MODEL};
+pub use providers::{
+    stream_completion, LLMChunk, LLMError, LLMProvider, LLMRequest, LLMResponse, LLMStream, STTResponse, TTSResponse,
+    LLM_USAGE_METADATA_KEY,
+};
 pub use runtime::{RetryPolicy, TimeoutConfig, TimedResult, run_with_retry, run_with_timeout, run_cleanup_with_timeout};
 pub use streaming::{AudioChunk, BackpressureMonitor, ChunkQueue, StreamingBuffer};
 pub use timestamps::{detect_unix_precision, normalize_to_utc, parse_timestamp as parse_ts};
@@ -0,0 +1,488 @@
+//! OpenAI-compatible chat completions provider.
+
+use super::providers::{LLMChunk, LLMError, LLMProvider, LLMRequest, LLMResponse, LLMStream};
+use crate::pipeline::{with_retry_if, RetryConfig};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Default API base URL.
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Default model when a request does not name one.
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+
+/// Default time allowed per attempt, and between streamed chunks.
+pub const DEFAULT_LLM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// LLM provider speaking the `OpenAI` chat completions API.
+///
+/// Any server implementing `POST {base_url}/chat/completions` works,
+/// including local servers such as vLLM, Ollama or llama.cpp and
+/// Anthropic's `OpenAI` compatibility endpoint at
+/// `https://api.anthropic.com/v1`. Rate limits (HTTP 429), server errors,
+/// timeouts and connection failures are retried with the provider's
+/// [`RetryConfig`]; a streamed call is retried only until the server
+/// starts responding.
+#[derive(Debug, Clone)]
+pub struct OpenAIProvider {
+    client: reqwest::Client,
+    name: String,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    timeout: Duration,
+    retry: RetryConfig,
+    headers: Vec<(String, String)>,
+}
+
+impl OpenAIProvider {
+    /// Creates a provider for the `OpenAI` API. An empty key sends no
+    /// `Authorization` header, as local servers expect.
+    #[must_use]
+    pub fn new(api_key: impl Into<String>) -> Self {
+        let api_key = api_key.into();
+        Self {
+            client: reqwest::Client::new(),
+            name: "openai".to_string(),
+            base_url: DEFAULT_OPENAI_BASE_URL.to_string(),
+            api_key: (!api_key.is_empty()).then_some(api_key),
+            model: DEFAULT_OPENAI_MODEL.to_string(),
+            timeout: DEFAULT_LLM_TIMEOUT,
+            retry: RetryConfig::default(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Sends requests to `base_url`, e.g. `http://localhost:11434/v1`.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the model used when a request does not name one.
+    #[must_use]
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Sets the provider name reported in responses and errors.
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the time allowed per attempt and between streamed chunks.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how failed attempts are retried.
+    #[must_use]
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Adds a header to every request.
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Uses `client` for requests, e.g. to set TLS options or a proxy.
+    #[must_use]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn failed(&self, message: impl Into<String>, retryable: bool) -> LLMError {
+        LLMError::Provider {
+            provider: self.name.clone(),
+            message: message.into(),
+            retryable,
+        }
+    }
+
+    fn body(&self, request: &LLMRequest, stream: bool) -> serde_json::Value {
+        let messages: Vec<serde_json::Value> = request
+            .messages
+            .iter()
+            .map(|m| serde_json::json!({ "role": m.role, "content": m.content }))
+            .collect();
+        let mut body = serde_json::json!({
+            "model": request.model.as_deref().unwrap_or(&self.model),
+            "messages": messages,
+            "stream": stream,
+        });
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        if !request.stop.is_empty() {
+            body["stop"] = serde_json::json!(request.stop);
+        }
+        if stream {
+            body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        body
+    }
+
+    /// Sends one request, mapping unsuccessful statuses to errors.
+    async fn send(&self, body: &serde_json::Value) -> Result<reqwest::Response, LLMError> {
+        let mut request = self.client.post(format!("{}/chat/completions", self.base_url)).json(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.map_err(|e| self.failed(e.to_string(), true))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let text = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| v.pointer("/error/message").and_then(serde_json::Value::as_str).map(str::to_string))
+            .unwrap_or_else(|| if text.is_empty() { status.to_string() } else { text });
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(LLMError::RateLimited {
+                provider: self.name.clone(),
+                message,
+                retry_after,
+            });
+        }
+        let retryable = status.is_server_error() || status == reqwest::StatusCode::REQUEST_TIMEOUT;
+        Err(self.failed(format!("HTTP {status}: {message}"), retryable))
+    }
+
+    fn parse_response(&self, request: &LLMRequest, json: &serde_json::Value) -> Result<LLMResponse, LLMError> {
+        let choice = json
+            .pointer("/choices/0")
+            .ok_or_else(|| self.failed("response has no choices", false))?;
+        Ok(LLMResponse {
+            content: choice
+                .pointer("/message/content")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            model: json
+                .get("model")
+                .and_then(serde_json::Value::as_str)
+                .map_or_else(|| request.model.clone().unwrap_or_else(|| self.model.clone()), str::to_string),
+            provider: self.name.clone(),
+            input_tokens: token_count(json, "/usage/prompt_tokens"),
+            output_tokens: token_count(json, "/usage/completion_tokens"),
+            latency_ms: None,
+            finish_reason: choice
+                .get("finish_reason")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string),
+            tool_calls: choice
+                .pointer("/message/tool_calls")
+                .and_then(serde_json::Value::as_array)
+                .cloned(),
+            cached_tokens: token_count(json, "/usage/prompt_tokens_details/cached_tokens"),
+        })
+    }
+}
+
+fn token_count(json: &serde_json::Value, pointer: &str) -> Option<u32> {
+    json.pointer(pointer)
+        .and_then(serde_json::Value::as_u64)
+        .and_then(|n| u32::try_from(n).ok())
+}
+
+#[async_trait]
+impl LLMProvider for OpenAIProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn complete(&self, request: &LLMRequest) -> Result<LLMResponse, LLMError> {
+        let body = self.body(request, false);
+        let start = Instant::now();
+        let attempt = || async {
+            let call = async {
+                let response = self.send(&body).await?;
+                response
+                    .json::<serde_json::Value>()
+                    .await
+                    .map_err(|e| self.failed(format!("invalid response: {e}"), false))
+            };
+            tokio::time::timeout(self.timeout, call)
+                .await
+                .map_err(|_| LLMError::Timeout(self.timeout))?
+        };
+        let json = with_retry_if(&self.retry, &self.name, attempt, LLMError::is_retryable).await?;
+        let mut response = self.parse_response(request, &json)?;
+        response.latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+        Ok(response)
+    }
+
+    async fn stream(&self, request: &LLMRequest) -> Result<LLMStream, LLMError> {
+        let body = self.body(request, true);
+        let attempt = || async {
+            tokio::time::timeout(self.timeout, self.send(&body))
+                .await
+                .map_err(|_| LLMError::Timeout(self.timeout))?
+        };
+        let response = with_retry_if(&self.retry, &self.name, attempt, LLMError::is_retryable).await?;
+        let events = ServerSentEvents {
+            response,
+            buffer: Vec::new(),
+            pending: VecDeque::new(),
+            done: false,
+            timeout: self.timeout,
+            provider: self.name.clone(),
+        };
+        Ok(Box::pin(futures::stream::unfold(events, ServerSentEvents::next)))
+    }
+}
+
+/// Parser for a streamed chat completion's `data:` lines.
+struct ServerSentEvents {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+    pending: VecDeque<Result<LLMChunk, LLMError>>,
+    done: bool,
+    timeout: Duration,
+    provider: String,
+}
+
+impl ServerSentEvents {
+    async fn next(mut self) -> Option<(Result<LLMChunk, LLMError>, Self)> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some((item, self));
+            }
+            if self.done {
+                return None;
+            }
+            match tokio::time::timeout(self.timeout, self.response.chunk()).await {
+                Err(_) => {
+                    self.done = true;
+                    self.pending.push_back(Err(LLMError::Timeout(self.timeout)));
+                }
+                Ok(Err(e)) => {
+                    self.done = true;
+                    self.pending.push_back(Err(self.failed(e.to_string())));
+                }
+                Ok(Ok(None)) => {
+                    let rest = std::mem::take(&mut self.buffer);
+                    self.parse_line(&rest);
+                    self.done = true;
+                }
+                Ok(Ok(Some(bytes))) => {
+                    self.buffer.extend_from_slice(&bytes);
+                    while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                        self.parse_line(&line);
+                    }
+                }
+            }
+        }
+    }
+
+    fn failed(&self, message: impl Into<String>) -> LLMError {
+        LLMError::Provider {
+            provider: self.provider.clone(),
+            message: message.into(),
+            retryable: false,
+        }
+    }
+
+    fn parse_line(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+            return;
+        };
+        if data == "[DONE]" {
+            self.done = true;
+            return;
+        }
+        let event: serde_json::Value = match serde_json::from_str(data) {
+            Ok(event) => event,
+            Err(e) => {
+                self.pending.push_back(Err(self.failed(format!("invalid stream event: {e}"))));
+                return;
+            }
+        };
+        if let Some(message) = event.pointer("/error/message").and_then(serde_json::Value::as_str) {
+            self.pending.push_back(Err(self.failed(message)));
+            return;
+        }
+        let chunk = LLMChunk {
+            delta: event
+                .pointer("/choices/0/delta/content")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            model: event.get("model").and_then(serde_json::Value::as_str).map(str::to_string),
+            finish_reason: event
+                .pointer("/choices/0/finish_reason")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string),
+            input_tokens: token_count(&event, "/usage/prompt_tokens"),
+            output_tokens: token_count(&event, "/usage/completion_tokens"),
+        };
+        let informative = !chunk.delta.is_empty()
+            || chunk.finish_reason.is_some()
+            || chunk.input_tokens.is_some()
+            || chunk.output_tokens.is_some();
+        if informative {
+            self.pending.push_back(Ok(chunk));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancellation::CancellationToken;
+    use crate::helpers::stream_completion;
+    use crate::pipeline::JitterStrategy;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves the given raw HTTP responses in turn, repeating the last, and
+    /// records each request.
+    async fn server(responses: Vec<String>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let read = socket.read(&mut buf).await.unwrap_or(0);
+                    raw.extend_from_slice(&buf[..read]);
+                    let text = String::from_utf8_lossy(&raw).to_string();
+                    let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                        let length = head
+                            .lines()
+                            .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                            .and_then(|v| v.parse::<usize>().ok())
+                            .unwrap_or(0);
+                        body.len() >= length
+                    });
+                    if complete || read == 0 {
+                        break;
+                    }
+                }
+                let index = {
+                    let mut requests = recorded.lock();
+                    requests.push(String::from_utf8_lossy(&raw).to_string());
+                    requests.len() - 1
+                };
+                let response = &responses[index.min(responses.len() - 1)];
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        (url, requests)
+    }
+
+    fn http(status: &str, headers: &str, body: &str) -> String {
+        format!("HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+    }
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig::new().with_max_attempts(3).with_base_delay_ms(1).with_jitter(JitterStrategy::None)
+    }
+
+    #[tokio::test]
+    async fn test_complete_retries_rate_limit() {
+        let body = r#"{"model":"gpt-test","choices":[{"message":{"content":"Hi!"},"finish_reason":"stop"}],
+            "usage":{"prompt_tokens":7,"completion_tokens":2,"prompt_tokens_details":{"cached_tokens":4}}}"#;
+        let (url, requests) = server(vec![
+            http("429 Too Many Requests", "Retry-After: 0\r\n", r#"{"error":{"message":"slow down"}}"#),
+            http("200 OK", "Content-Type: application/json\r\n", body),
+        ])
+        .await;
+        let provider = OpenAIProvider::new("sk-test").with_base_url(url).with_retry(fast_retry());
+
+        let response = provider.complete(&LLMRequest::user("hello").with_max_tokens(5)).await.unwrap();
+        assert_eq!(response.content, "Hi!");
+        assert_eq!(response.model, "gpt-test");
+        assert_eq!((response.input_tokens, response.output_tokens, response.cached_tokens), (Some(7), Some(2), Some(4)));
+
+        let requests = requests.lock();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].to_lowercase().contains("authorization: bearer sk-test"));
+        assert!(requests[1].contains(r#""max_tokens":5"#));
+        assert!(requests[1].contains(r#""model":"gpt-4o-mini""#));
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_stage_outputs() {
+        let (url, _) = server(vec![http("429 Too Many Requests", "Retry-After: 2\r\n", "")]).await;
+        let limited = OpenAIProvider::new("").with_base_url(url).with_retry(fast_retry());
+        let err = limited.complete(&LLMRequest::user("hello")).await.unwrap_err();
+        assert!(matches!(err, LLMError::RateLimited { retry_after: Some(wait), .. } if wait == Duration::from_secs(2)));
+        let output = err.to_stage_output();
+        assert!(output.is_retryable());
+        assert_eq!(output.metadata["retry_after_ms"], 2000);
+
+        let (url, requests) = server(vec![http("400 Bad Request", "", r#"{"error":{"message":"bad model"}}"#)]).await;
+        let rejected = OpenAIProvider::new("").with_base_url(url).with_retry(fast_retry());
+        let err = rejected.complete(&LLMRequest::user("hello")).await.unwrap_err();
+        assert_eq!(requests.lock().len(), 1);
+        assert!(!err.is_retryable());
+        assert!(err.to_string().contains("bad model"));
+        assert!(!err.to_stage_output().is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_stream_parses_server_sent_events() {
+        let events = [
+            r#"{"model":"gpt-test","choices":[{"delta":{"role":"assistant"}}]}"#,
+            r#"{"model":"gpt-test","choices":[{"delta":{"content":"Hel"}}]}"#,
+            r#"{"model":"gpt-test","choices":[{"delta":{"content":"lo ✓"},"finish_reason":"stop"}]}"#,
+            r#"{"model":"gpt-test","choices":[],"usage":{"prompt_tokens":3,"completion_tokens":2}}"#,
+            "[DONE]",
+        ];
+        let mut body = String::new();
+        for event in events {
+            body.push_str("data: ");
+            body.push_str(event);
+            body.push_str("\n\n");
+        }
+        let (url, requests) = server(vec![http("200 OK", "Content-Type: text/event-stream\r\n", &body)]).await;
+        let provider = OpenAIProvider::new("").with_base_url(url).with_model("local");
+
+        let mut deltas = Vec::new();
+        let request = LLMRequest::user("hi");
+        let response = stream_completion(&provider, &request, &CancellationToken::new(), |chunk| deltas.push(chunk.delta.clone()))
+            .await
+            .unwrap();
+        assert_eq!(deltas, vec!["Hel", "lo ✓", ""]);
+        assert_eq!(response.content, "Hello ✓");
+        assert_eq!(response.model, "gpt-test");
+        assert_eq!((response.input_tokens, response.output_tokens), (Some(3), Some(2)));
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+        assert!(requests.lock()[0].contains(r#""stream":true"#));
+        assert!(!requests.lock()[0].to_lowercase().contains("authorization"));
+    }
+}
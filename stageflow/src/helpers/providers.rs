@@ -1,7 +1,25 @@
-//! Provider response types.
+//! Provider request and response types, and the LLM provider trait.
+//!
+//! [`LLMProvider::stream`] yields [`LLMChunk`]s as the model produces them;
+//! [`stream_completion`] drains such a stream into an [`LLMResponse`],
+//! passing each chunk to a callback (for example to feed a
+//! [`StreamingBuffer`](super::StreamingBuffer)) and stopping early when a
+//! [`CancellationToken`] is cancelled.
 
+use crate::cancellation::CancellationToken;
+use crate::context::Message;
+use crate::core::StageOutput;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Stage output metadata key holding an LLM call's usage.
+pub const LLM_USAGE_METADATA_KEY: &str = "llm_usage";
 
 /// LLM response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +60,292 @@ impl LLMResponse {
         if let Some(l) = self.latency_ms { map.insert("llm.latency_ms".to_string(), serde_json::json!(l)); }
         map
     }
+
+    /// Returns the provider, model, token counts, latency and finish reason
+    /// of the call, omitting those that are unknown.
+    #[must_use]
+    pub fn usage_metadata(&self) -> serde_json::Value {
+        let mut usage = serde_json::Map::new();
+        usage.insert("provider".to_string(), serde_json::json!(self.provider));
+        usage.insert("model".to_string(), serde_json::json!(self.model));
+        if let Some(t) = self.input_tokens { usage.insert("input_tokens".to_string(), serde_json::json!(t)); }
+        if let Some(t) = self.output_tokens { usage.insert("output_tokens".to_string(), serde_json::json!(t)); }
+        usage.insert("total_tokens".to_string(), serde_json::json!(self.total_tokens()));
+        if let Some(t) = self.cached_tokens { usage.insert("cached_tokens".to_string(), serde_json::json!(t)); }
+        if let Some(l) = self.latency_ms { usage.insert("latency_ms".to_string(), serde_json::json!(l)); }
+        if let Some(ref r) = self.finish_reason { usage.insert("finish_reason".to_string(), serde_json::json!(r)); }
+        serde_json::Value::Object(usage)
+    }
+
+    /// Converts into a successful stage output with the text under
+    /// `content`, any tool calls under `tool_calls`, and the
+    /// [`usage_metadata`](Self::usage_metadata) under [`LLM_USAGE_METADATA_KEY`].
+    #[must_use]
+    pub fn into_stage_output(self) -> StageOutput {
+        let usage = self.usage_metadata();
+        let mut data = HashMap::from([("content".to_string(), serde_json::json!(self.content))]);
+        if let Some(calls) = self.tool_calls {
+            data.insert("tool_calls".to_string(), serde_json::json!(calls));
+        }
+        StageOutput::ok(data).add_metadata(LLM_USAGE_METADATA_KEY, usage)
+    }
+}
+
+/// A chat completion request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LLMRequest {
+    /// The conversation so far.
+    pub messages: Vec<Message>,
+    /// Model to use; the provider's default if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Sampling temperature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Maximum number of tokens to generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Sequences that stop generation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl LLMRequest {
+    /// Creates a request for `messages`.
+    #[must_use]
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self { messages, ..Self::default() }
+    }
+
+    /// Creates a request with a single user message.
+    #[must_use]
+    pub fn user(prompt: impl Into<String>) -> Self {
+        Self::new(vec![Message::user(prompt)])
+    }
+
+    /// Sets the model.
+    #[must_use]
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Sets the sampling temperature.
+    #[must_use]
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Sets the maximum number of tokens to generate.
+    #[must_use]
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Adds a stop sequence.
+    #[must_use]
+    pub fn with_stop(mut self, stop: impl Into<String>) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    /// Returns the content of the last message, usually the prompt.
+    #[must_use]
+    pub fn last_content(&self) -> &str {
+        self.messages.last().map_or("", |m| m.content.as_str())
+    }
+}
+
+/// A piece of a streamed completion.
+///
+/// Token counts and the finish reason usually arrive on the last chunk;
+/// when several chunks carry them, the last value wins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LLMChunk {
+    /// Text generated since the previous chunk.
+    pub delta: String,
+    /// Model that produced the chunk, if reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Why generation stopped, on the final chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    /// Prompt tokens, if reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u32>,
+    /// Generated tokens, if reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u32>,
+}
+
+impl LLMChunk {
+    /// Creates a chunk carrying only text.
+    #[must_use]
+    pub fn text(delta: impl Into<String>) -> Self {
+        Self { delta: delta.into(), ..Self::default() }
+    }
+}
+
+/// Error raised by an [`LLMProvider`].
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LLMError {
+    /// The provider rejected the call for exceeding a rate limit.
+    #[error("Rate limited by {provider}: {message}")]
+    RateLimited {
+        /// The provider name.
+        provider: String,
+        /// The provider's message.
+        message: String,
+        /// How long the provider asked to wait, if it said.
+        retry_after: Option<Duration>,
+    },
+
+    /// The call did not finish in time.
+    #[error("LLM call timed out after {0:?}")]
+    Timeout(Duration),
+
+    /// The call was cancelled through its token.
+    #[error("LLM call cancelled: {0}")]
+    Cancelled(String),
+
+    /// The provider failed or returned something unusable.
+    #[error("LLM provider {provider} failed: {message}")]
+    Provider {
+        /// The provider name.
+        provider: String,
+        /// What went wrong.
+        message: String,
+        /// Whether repeating the call may succeed.
+        retryable: bool,
+    },
+}
+
+impl LLMError {
+    /// Returns true if repeating the call may succeed.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } | Self::Timeout(_) => true,
+            Self::Cancelled(_) => false,
+            Self::Provider { retryable, .. } => *retryable,
+        }
+    }
+
+    /// Converts into the output of a stage whose LLM call failed.
+    ///
+    /// Retryable errors become retryable failures, so the stage's retry
+    /// policy applies; a rate limit's requested wait is recorded in the
+    /// metadata under `retry_after_ms`. Cancellation becomes a cancel output.
+    #[must_use]
+    pub fn to_stage_output(&self) -> StageOutput {
+        match self {
+            Self::Cancelled(reason) => StageOutput::cancel(reason.clone()),
+            Self::RateLimited { retry_after: Some(wait), .. } => StageOutput::fail_retryable(self.to_string())
+                .add_metadata("retry_after_ms", serde_json::json!(wait.as_millis())),
+            _ if self.is_retryable() => StageOutput::fail_retryable(self.to_string()),
+            _ => StageOutput::fail(self.to_string()),
+        }
+    }
+}
+
+/// A stream of completion chunks.
+pub type LLMStream = Pin<Box<dyn Stream<Item = Result<LLMChunk, LLMError>> + Send>>;
+
+/// A chat completion model.
+#[async_trait]
+pub trait LLMProvider: Send + Sync {
+    /// Returns the provider name reported in responses.
+    fn name(&self) -> &str;
+
+    /// Runs a completion and returns the whole response.
+    async fn complete(&self, request: &LLMRequest) -> Result<LLMResponse, LLMError>;
+
+    /// Runs a completion, yielding text as it is generated.
+    ///
+    /// The default runs [`complete`](Self::complete) and yields its response
+    /// as a single chunk, for providers that cannot stream.
+    async fn stream(&self, request: &LLMRequest) -> Result<LLMStream, LLMError> {
+        let response = self.complete(request).await?;
+        let chunk = LLMChunk {
+            delta: response.content,
+            model: Some(response.model),
+            finish_reason: response.finish_reason,
+            input_tokens: response.input_tokens,
+            output_tokens: response.output_tokens,
+        };
+        Ok(Box::pin(futures::stream::once(async move { Ok(chunk) })))
+    }
+}
+
+/// Resolves once `token` is cancelled.
+async fn cancelled(token: &CancellationToken) {
+    let notify = Arc::new(tokio::sync::Notify::new());
+    let waker = Arc::clone(&notify);
+    token.on_cancel(move || waker.notify_one());
+    notify.notified().await;
+}
+
+/// Streams a completion from `provider`, calling `on_token` with each chunk,
+/// and returns the assembled response.
+///
+/// The call stops with [`LLMError::Cancelled`] as soon as `cancel` is
+/// cancelled, even while waiting for the next chunk. The response's
+/// latency covers the whole stream.
+///
+/// # Errors
+///
+/// Returns the provider's error, or [`LLMError::Cancelled`].
+pub async fn stream_completion<F>(
+    provider: &dyn LLMProvider,
+    request: &LLMRequest,
+    cancel: &CancellationToken,
+    mut on_token: F,
+) -> Result<LLMResponse, LLMError>
+where
+    F: FnMut(&LLMChunk) + Send,
+{
+    let start = Instant::now();
+    let cancellation = cancelled(cancel);
+    tokio::pin!(cancellation);
+    let cancelled_error = || LLMError::Cancelled(cancel.reason().unwrap_or_default());
+
+    let mut stream = tokio::select! {
+        biased;
+        () = &mut cancellation => return Err(cancelled_error()),
+        stream = provider.stream(request) => stream?,
+    };
+    let mut response = LLMResponse {
+        content: String::new(),
+        model: request.model.clone().unwrap_or_default(),
+        provider: provider.name().to_string(),
+        input_tokens: None,
+        output_tokens: None,
+        latency_ms: None,
+        finish_reason: None,
+        tool_calls: None,
+        cached_tokens: None,
+    };
+    loop {
+        let next = tokio::select! {
+            biased;
+            () = &mut cancellation => return Err(cancelled_error()),
+            next = stream.next() => next,
+        };
+        let Some(chunk) = next else { break };
+        let chunk = chunk?;
+        on_token(&chunk);
+        response.content.push_str(&chunk.delta);
+        if let Some(model) = chunk.model {
+            response.model = model;
+        }
+        response.finish_reason = chunk.finish_reason.or(response.finish_reason);
+        response.input_tokens = chunk.input_tokens.or(response.input_tokens);
+        response.output_tokens = chunk.output_tokens.or(response.output_tokens);
+    }
+    response.latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+    Ok(response)
 }
 
 /// STT response.
@@ -102,3 +406,52 @@ impl TTSResponse {
         self.audio.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{MockLLMProvider, StreamingBuffer};
+
+    #[tokio::test]
+    async fn test_stream_completion_feeds_buffer_and_reports_usage() {
+        let provider = MockLLMProvider::new(vec!["Hello there friend".to_string()]);
+        let buffer = StreamingBuffer::new(1000.0, 16_000);
+        let mut tokens = 0;
+        let request = LLMRequest::user("say hi").with_model("mock-1");
+        let response = stream_completion(&provider, &request, &CancellationToken::new(), |chunk| {
+            tokens += 1;
+            buffer.push_text(&chunk.delta);
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(tokens, 3);
+        assert_eq!(buffer.text(), "Hello there friend");
+        assert_eq!(response.content, "Hello there friend");
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+
+        let output = response.into_stage_output();
+        let usage = &output.metadata[LLM_USAGE_METADATA_KEY];
+        assert_eq!(usage["model"], "mock-1");
+        assert_eq!(usage["input_tokens"], 2);
+        assert_eq!(usage["output_tokens"], 3);
+        assert_eq!(usage["total_tokens"], 5);
+    }
+
+    #[tokio::test]
+    async fn test_stream_completion_stops_when_cancelled() {
+        let provider = Arc::new(MockLLMProvider::new(vec!["one two three four".to_string()]).with_latency_ms(20));
+        let cancel = Arc::new(CancellationToken::new());
+        let canceller = Arc::clone(&cancel);
+        let request = LLMRequest::user("count");
+        let mut received = Vec::new();
+        let result = stream_completion(provider.as_ref(), &request, &cancel, |chunk| {
+            received.push(chunk.delta.clone());
+            canceller.cancel("user stopped");
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err(), LLMError::Cancelled("user stopped".to_string()));
+        assert_eq!(received, vec!["one ".to_string()]);
+    }
+}
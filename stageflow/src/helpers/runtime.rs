@@ -186,7 +186,7 @@ impl RetryPolicy {
         
         let final_delay = if self.jitter {
             // Add up to 25% jitter
-            let jitter = capped * 0.25 * rand::random::<f64>();
+            let jitter = capped * 0.25 * crate::utils::random_f64();
             capped + jitter
         } else {
             capped
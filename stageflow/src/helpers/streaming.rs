@@ -130,16 +130,34 @@ impl ChunkQueue {
     }
 }
 
-/// Streaming buffer for audio.
+/// Streaming buffer for audio, and for text streamed token by token.
 pub struct StreamingBuffer {
     max_duration_ms: f64,
     sample_rate: u32,
+    text: parking_lot::Mutex<String>,
 }
 
 impl StreamingBuffer {
     /// Creates a new buffer.
     #[must_use]
     pub fn new(max_duration_ms: f64, sample_rate: u32) -> Self {
-        Self { max_duration_ms, sample_rate }
+        Self { max_duration_ms, sample_rate, text: parking_lot::Mutex::new(String::new()) }
+    }
+
+    /// Appends streamed text, such as an LLM token.
+    pub fn push_text(&self, text: &str) {
+        self.text.lock().push_str(text);
+    }
+
+    /// Returns the text received so far.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.text.lock().clone()
+    }
+
+    /// Returns the text received so far and clears it.
+    #[must_use]
+    pub fn take_text(&self) -> String {
+        std::mem::take(&mut *self.text.lock())
     }
 }
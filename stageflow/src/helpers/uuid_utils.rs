@@ -192,13 +192,13 @@ impl UuidCollisionMonitor {
 /// Generate a new UUIDv4.
 #[must_use]
 pub fn generate_uuid4() -> Uuid {
-    Uuid::new_v4()
+    crate::utils::generate_uuid()
 }
 
 /// Generate a new UUIDv7 (time-ordered) if available.
 #[must_use]
 pub fn generate_uuid7() -> Uuid {
-    Uuid::now_v7()
+    crate::utils::generate_uuid_v7()
 }
 
 #[cfg(test)]
@@ -0,0 +1,218 @@
+//! Runtime validation of stage outputs against registered contracts.
+
+use super::Interceptor;
+use crate::context::{ExecutionContext, StageContext};
+use crate::contracts::{validate_json_schema, ContractRegistry, ValidationError, REGISTRY};
+use crate::core::StageOutput;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// What to do when an output violates its contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContractValidationMode {
+    /// Log and record the violations but keep the output.
+    #[default]
+    Warn,
+    /// Replace the output with a failure.
+    Fail,
+}
+
+/// Interceptor that validates successful outputs against the stage's
+/// registered JSON schema.
+///
+/// The contract is looked up by stage name, using the version bound with
+/// [`with_contract`](Self::with_contract) or otherwise the most recently
+/// registered one. Stages without a registered contract pass through.
+/// Violations are attached to the output metadata under
+/// `contract_validation` as a list of [`ValidationError`]s.
+pub struct ContractValidationInterceptor {
+    registry: Arc<ContractRegistry>,
+    mode: ContractValidationMode,
+    versions: HashMap<String, String>,
+}
+
+impl ContractValidationInterceptor {
+    /// Creates an interceptor that warns on violations of contracts in the
+    /// global registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            registry: Arc::clone(&REGISTRY),
+            mode: ContractValidationMode::Warn,
+            versions: HashMap::new(),
+        }
+    }
+
+    /// Uses a specific registry instead of the global one.
+    #[must_use]
+    pub fn with_registry(mut self, registry: Arc<ContractRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets the validation mode.
+    #[must_use]
+    pub fn with_mode(mut self, mode: ContractValidationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Pins the contract version validated for a stage.
+    #[must_use]
+    pub fn with_contract(mut self, stage: impl Into<String>, version: impl Into<String>) -> Self {
+        self.versions.insert(stage.into(), version.into());
+        self
+    }
+
+    /// Validates output data against the stage's contract.
+    ///
+    /// Returns the contract version and the errors found, or `None` if the
+    /// stage has no registered contract.
+    #[must_use]
+    pub fn validate(
+        &self,
+        stage: &str,
+        output: &StageOutput,
+    ) -> Option<(String, Vec<ValidationError>)> {
+        let contract = match self.versions.get(stage) {
+            Some(version) => self.registry.get(stage, version)?,
+            None => self
+                .registry
+                .list(Some(stage))
+                .into_iter()
+                .max_by_key(|m| m.created_at)?,
+        };
+
+        let data = serde_json::Value::Object(output.data_or_empty().into_iter().collect());
+        let errors = validate_json_schema(&contract.schema, &data)
+            .into_iter()
+            .map(ValidationError::from)
+            .collect();
+        Some((contract.version, errors))
+    }
+}
+
+impl Default for ContractValidationInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Interceptor for ContractValidationInterceptor {
+    fn priority(&self) -> i32 {
+        -60 // Run last after execution, on the final output
+    }
+
+    async fn after(&self, ctx: &StageContext, output: StageOutput) -> StageOutput {
+        if !output.is_success() {
+            return output;
+        }
+        let stage = ctx.stage_name();
+        let Some((version, errors)) = self.validate(stage, &output) else {
+            return output;
+        };
+        if errors.is_empty() {
+            return output;
+        }
+
+        let contract = format!("{stage}@{version}");
+        let summary = errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        warn!(
+            stage = %stage,
+            contract = %contract,
+            errors = %summary,
+            "Stage output violates contract"
+        );
+        ctx.try_emit_event(
+            "contract.validation_failed",
+            Some(serde_json::json!({
+                "stage": stage,
+                "contract": contract,
+                "errors": errors,
+                "mode": if self.mode == ContractValidationMode::Fail { "fail" } else { "warn" },
+            })),
+        );
+
+        let details = serde_json::json!({
+            "contract": contract,
+            "valid": false,
+            "errors": errors,
+        });
+        match self.mode {
+            ContractValidationMode::Warn => output.add_metadata("contract_validation", details),
+            ContractValidationMode::Fail => {
+                StageOutput::fail(format!("Output violates contract {contract}: {summary}"))
+                    .with_metadata(output.metadata)
+                    .add_metadata("contract_validation", details)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageInputs};
+    use serde_json::json;
+
+    fn test_stage_context() -> StageContext {
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        StageContext::new(
+            pipeline_ctx,
+            "summarize",
+            StageInputs::default(),
+            ContextSnapshot::new(),
+        )
+    }
+
+    fn registry() -> Arc<ContractRegistry> {
+        let registry = ContractRegistry::new();
+        let schema = json!({
+            "type": "object",
+            "required": ["summary"],
+            "properties": {"summary": {"type": "string"}}
+        });
+        registry.register("summarize", "1.0", schema, None).unwrap();
+        Arc::new(registry)
+    }
+
+    #[tokio::test]
+    async fn test_warn_mode_attaches_errors() {
+        let interceptor = ContractValidationInterceptor::new().with_registry(registry());
+        let ctx = test_stage_context();
+
+        let valid = StageOutput::ok_value("summary", json!("short"));
+        let output = interceptor.after(&ctx, valid).await;
+        assert!(!output.metadata.contains_key("contract_validation"));
+
+        let output = interceptor.after(&ctx, StageOutput::ok_value("summary", json!(3))).await;
+        assert!(output.is_success());
+        let details = &output.metadata["contract_validation"];
+        assert_eq!(details["contract"], "summarize@1.0");
+        assert_eq!(details["errors"][0]["field"], "$.summary");
+    }
+
+    #[tokio::test]
+    async fn test_fail_mode_and_unregistered_stages() {
+        let interceptor = ContractValidationInterceptor::new()
+            .with_registry(registry())
+            .with_mode(ContractValidationMode::Fail);
+        let ctx = test_stage_context();
+
+        let output = interceptor.after(&ctx, StageOutput::ok_empty()).await;
+        assert!(output.is_failure());
+        assert!(output.error.unwrap().contains("missing required property 'summary'"));
+
+        let pinned = ContractValidationInterceptor::new()
+            .with_registry(registry())
+            .with_contract("summarize", "2.0");
+        assert!(pinned.validate("summarize", &StageOutput::ok_empty()).is_none());
+    }
+}
@@ -2,31 +2,77 @@
 
 use super::Interceptor;
 use crate::context::{ExecutionContext, StageContext};
-use crate::core::StageOutput;
+use crate::artifacts::{artifact_reference, ArtifactStore, StoredArtifact};
+use crate::core::{StageArtifact, StageOutput};
+use crate::errors::ReadOnlyContextError;
+use crate::pipeline::{ResourceLimit, ResourceLimitExceeded};
 use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tracing::warn;
+use uuid::Uuid;
 
-/// Interceptor that detects snapshot mutations.
+type DataDict = HashMap<String, serde_json::Value>;
+type OutputDict = HashMap<String, HashMap<String, serde_json::Value>>;
+
+/// Interceptor that detects writes to a stage's context.
+///
+/// `before` copies what [`StageContext::data`] and
+/// [`StageContext::outputs`] hold and `after` compares them; any added,
+/// changed or removed entry counts as a violation and emits
+/// `context.immutability_violation`. An enforcing interceptor also fails
+/// the stage with a [`ReadOnlyContextError`].
+///
+/// For read-only stages both bags are private copies, so only the stage's
+/// own writes show up. Other stages see the shared bags, where writes by
+/// stages running in parallel are reported too.
 pub struct ImmutabilityInterceptor {
     /// Number of violations detected.
     violations: AtomicUsize,
+    /// Whether violations fail the stage.
+    enforce: bool,
+    /// Bag contents recorded by `before`, keyed by run and stage.
+    baselines: Mutex<HashMap<(Option<Uuid>, String), (DataDict, OutputDict)>>,
 }
 
 impl ImmutabilityInterceptor {
-    /// Creates a new immutability interceptor.
+    /// Creates a new immutability interceptor that only reports violations.
     #[must_use]
     pub fn new() -> Self {
         Self {
             violations: AtomicUsize::new(0),
+            enforce: false,
+            baselines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates an interceptor that fails stages that wrote to their context.
+    #[must_use]
+    pub fn enforcing() -> Self {
+        Self {
+            enforce: true,
+            ..Self::new()
         }
     }
 
+    /// Returns true if violations fail the stage.
+    #[must_use]
+    pub fn is_enforcing(&self) -> bool {
+        self.enforce
+    }
+
     /// Returns the number of violations detected.
     #[must_use]
     pub fn violation_count(&self) -> usize {
         self.violations.load(Ordering::SeqCst)
     }
+
+    fn baseline_key(ctx: &StageContext) -> (Option<Uuid>, String) {
+        (ctx.pipeline_run_id(), ctx.stage_name().to_string())
+    }
 }
 
 impl Default for ImmutabilityInterceptor {
@@ -35,6 +81,19 @@ impl Default for ImmutabilityInterceptor {
     }
 }
 
+/// Returns the keys whose values differ between `before` and `after`, sorted.
+fn changed_keys<V: PartialEq>(before: &HashMap<String, V>, after: &HashMap<String, V>) -> Vec<String> {
+    let mut keys: Vec<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
 #[async_trait]
 impl Interceptor for ImmutabilityInterceptor {
     fn priority(&self) -> i32 {
@@ -42,24 +101,110 @@ impl Interceptor for ImmutabilityInterceptor {
     }
 
     async fn before(&self, ctx: &StageContext) -> Option<StageOutput> {
-        // Store snapshot hash for later comparison
-        // In a real implementation, we'd compute a hash of the snapshot
+        let baseline = (ctx.data().to_dict(), ctx.outputs().to_dict());
+        self.baselines.lock().insert(Self::baseline_key(ctx), baseline);
         None
     }
 
     async fn after(&self, ctx: &StageContext, output: StageOutput) -> StageOutput {
-        // Compare snapshot hash - if different, log violation
-        // For now, just pass through
-        output
+        let Some((data, outputs)) = self.baselines.lock().remove(&Self::baseline_key(ctx)) else {
+            return output;
+        };
+        let data_keys = changed_keys(&data, &ctx.data().to_dict());
+        let output_stages = changed_keys(&outputs, &ctx.outputs().to_dict());
+        if data_keys.is_empty() && output_stages.is_empty() {
+            return output;
+        }
+
+        self.violations.fetch_add(1, Ordering::SeqCst);
+        warn!(
+            stage = ctx.stage_name(),
+            data_keys = ?data_keys,
+            output_stages = ?output_stages,
+            "Stage wrote to an immutable context"
+        );
+        ctx.try_emit_event(
+            "context.immutability_violation",
+            Some(serde_json::json!({
+                "stage": ctx.stage_name(),
+                "data_keys": data_keys,
+                "output_stages": output_stages,
+                "enforced": self.enforce,
+            })),
+        );
+        if !self.enforce {
+            return output;
+        }
+        let error = ReadOnlyContextError {
+            stage: ctx.stage_name().to_string(),
+            data_keys,
+            output_stages,
+        };
+        StageOutput::fail(error.to_string())
+    }
+}
+
+/// What [`ContextSizeInterceptor`] does with an output value over its size
+/// limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizeAction {
+    /// Keep the value and log a warning.
+    #[default]
+    Warn,
+    /// Fail the stage with an output size error.
+    Fail,
+    /// Cut strings down to the limit and replace other values with a
+    /// preview of their JSON.
+    Truncate,
+    /// Move the value to the artifact store and leave a reference to it.
+    /// Without a store, or if storing fails, the value is truncated.
+    Spill,
+}
+
+/// Size limit on individual stage output values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputSizePolicy {
+    /// Largest allowed value, serialized as JSON.
+    pub max_value_bytes: usize,
+    /// What to do with larger values.
+    pub action: OversizeAction,
+}
+
+impl OutputSizePolicy {
+    /// Creates a policy.
+    #[must_use]
+    pub fn new(max_value_bytes: usize, action: OversizeAction) -> Self {
+        Self {
+            max_value_bytes,
+            action,
+        }
     }
 }
 
 /// Interceptor that warns on large or growing contexts.
+///
+/// With an [`OutputSizePolicy`] it also guards stage outputs: values over
+/// the limit are handled per [`OversizeAction`], each emitting
+/// `output.size_exceeded` and counting towards
+/// [`truncation_count`](Self::truncation_count) or
+/// [`spill_count`](Self::spill_count). Spilled values are replaced by
+/// `{"artifact_uri": ..., "size_bytes": ...}`.
 pub struct ContextSizeInterceptor {
     /// Maximum allowed size in bytes.
     max_size_bytes: usize,
     /// Warning threshold as a fraction of max size.
     warning_threshold: f64,
+    /// Output policy for stages without their own.
+    output_policy: Option<OutputSizePolicy>,
+    /// Output policies by stage name.
+    stage_output_policies: HashMap<String, OutputSizePolicy>,
+    /// Where spilled values are stored.
+    artifact_store: Option<Arc<dyn ArtifactStore>>,
+    /// Number of truncated values.
+    truncations: AtomicUsize,
+    /// Number of spilled values.
+    spills: AtomicUsize,
 }
 
 impl ContextSizeInterceptor {
@@ -69,7 +214,134 @@ impl ContextSizeInterceptor {
         Self {
             max_size_bytes,
             warning_threshold: warning_threshold.clamp(0.0, 1.0),
+            output_policy: None,
+            stage_output_policies: HashMap::new(),
+            artifact_store: None,
+            truncations: AtomicUsize::new(0),
+            spills: AtomicUsize::new(0),
+        }
+    }
+
+    /// Guards the outputs of every stage without its own policy.
+    #[must_use]
+    pub fn with_output_policy(mut self, policy: OutputSizePolicy) -> Self {
+        self.output_policy = Some(policy);
+        self
+    }
+
+    /// Guards the outputs of one stage.
+    #[must_use]
+    pub fn with_stage_output_policy(mut self, stage: impl Into<String>, policy: OutputSizePolicy) -> Self {
+        self.stage_output_policies.insert(stage.into(), policy);
+        self
+    }
+
+    /// Sets the store that spilled values are written to.
+    #[must_use]
+    pub fn with_artifact_store(mut self, store: Arc<dyn ArtifactStore>) -> Self {
+        self.artifact_store = Some(store);
+        self
+    }
+
+    /// Returns the output policy applied to `stage`.
+    #[must_use]
+    pub fn output_policy_for(&self, stage: &str) -> Option<OutputSizePolicy> {
+        self.stage_output_policies.get(stage).copied().or(self.output_policy)
+    }
+
+    /// Returns the number of output values truncated so far.
+    #[must_use]
+    pub fn truncation_count(&self) -> usize {
+        self.truncations.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of output values spilled so far.
+    #[must_use]
+    pub fn spill_count(&self) -> usize {
+        self.spills.load(Ordering::SeqCst)
+    }
+
+    /// Applies `policy` to each value of the output.
+    async fn guard_output(&self, ctx: &StageContext, mut output: StageOutput, policy: OutputSizePolicy) -> StageOutput {
+        let stage = ctx.stage_name();
+        let mut oversized: Vec<(String, usize)> = output
+            .data
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), json_size(value)))
+            .filter(|(_, size)| *size > policy.max_value_bytes)
+            .collect();
+        oversized.sort();
+
+        for (key, size) in oversized {
+            let action = match policy.action {
+                OversizeAction::Warn => {
+                    warn!(stage, key = %key, size_bytes = size, "Output value exceeds size limit");
+                    OversizeAction::Warn
+                }
+                OversizeAction::Fail => {
+                    self.emit_size_exceeded(ctx, &key, size, policy, OversizeAction::Fail);
+                    return ResourceLimitExceeded::new(ResourceLimit::OutputBytes, policy.max_value_bytes, size)
+                        .to_output(stage);
+                }
+                OversizeAction::Truncate | OversizeAction::Spill => {
+                    let Some(value) = output.data.as_mut().and_then(|data| data.remove(&key)) else {
+                        continue;
+                    };
+                    let (replacement, action) = match self.spill(ctx, &key, &value, policy.action).await {
+                        Some(stored) => (artifact_reference(&stored), OversizeAction::Spill),
+                        None => (truncate_value(value, size, policy.max_value_bytes), OversizeAction::Truncate),
+                    };
+                    if action == OversizeAction::Spill {
+                        self.spills.fetch_add(1, Ordering::SeqCst);
+                    } else {
+                        self.truncations.fetch_add(1, Ordering::SeqCst);
+                    }
+                    if let Some(data) = output.data.as_mut() {
+                        data.insert(key.clone(), replacement);
+                    }
+                    action
+                }
+            };
+            self.emit_size_exceeded(ctx, &key, size, policy, action);
         }
+        output
+    }
+
+    /// Stores `value` as an artifact when spilling is requested and a store
+    /// is configured.
+    async fn spill(&self, ctx: &StageContext, key: &str, value: &serde_json::Value, action: OversizeAction) -> Option<StoredArtifact> {
+        if action != OversizeAction::Spill {
+            return None;
+        }
+        let Some(store) = &self.artifact_store else {
+            warn!(stage = ctx.stage_name(), key, "No artifact store to spill to; truncating");
+            return None;
+        };
+        let run_id = ctx.pipeline_run_id().map_or_else(|| "unknown".to_string(), |id| id.to_string());
+        let artifact = StageArtifact::new("spilled_output", format!("{}.{key}", ctx.stage_name()), key, value.clone());
+        match store.put(&run_id, ctx.stage_name(), &artifact).await {
+            Ok(stored) => Some(stored),
+            Err(e) => {
+                warn!(stage = ctx.stage_name(), key, error = %e, "Failed to spill output value; truncating");
+                None
+            }
+        }
+    }
+
+    fn emit_size_exceeded(&self, ctx: &StageContext, key: &str, size: usize, policy: OutputSizePolicy, action: OversizeAction) {
+        ctx.try_emit_event(
+            "output.size_exceeded",
+            Some(serde_json::json!({
+                "stage": ctx.stage_name(),
+                "key": key,
+                "size_bytes": size,
+                "max_bytes": policy.max_value_bytes,
+                "action": action,
+                "truncations": self.truncation_count(),
+                "spills": self.spill_count(),
+            })),
+        );
     }
 
     /// Estimates the size of the context data.
@@ -118,6 +390,11 @@ impl Interceptor for ContextSizeInterceptor {
     }
 
     async fn after(&self, ctx: &StageContext, output: StageOutput) -> StageOutput {
+        let output = match self.output_policy_for(ctx.stage_name()) {
+            Some(policy) => self.guard_output(ctx, output, policy).await,
+            None => output,
+        };
+
         // Record metrics about context size growth
         let size = self.estimate_size(ctx);
 
@@ -133,6 +410,30 @@ impl Interceptor for ContextSizeInterceptor {
     }
 }
 
+fn json_size(value: &serde_json::Value) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+/// Shrinks a value of `size` bytes to roughly `max` bytes.
+fn truncate_value(value: serde_json::Value, size: usize, max: usize) -> serde_json::Value {
+    let cut = |text: &str, max: usize| {
+        let mut end = max.min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text[..end].to_string()
+    };
+    match value {
+        // Leave room for the quotes
+        serde_json::Value::String(text) => serde_json::Value::String(cut(&text, max.saturating_sub(2))),
+        other => serde_json::json!({
+            "truncated": true,
+            "size_bytes": size,
+            "preview": cut(&other.to_string(), max / 2),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,6 +463,29 @@ mod tests {
         assert!(after_result.is_success());
     }
 
+    #[tokio::test]
+    async fn test_enforcing_immutability_fails_read_only_writes() {
+        let interceptor = ImmutabilityInterceptor::enforcing();
+        let ctx = test_stage_context().with_read_only();
+        ctx.pipeline_ctx().data.set("shared", serde_json::json!(1)).unwrap();
+
+        assert!(interceptor.before(&ctx).await.is_none());
+        let err = ctx.write_data("rejected", serde_json::json!(true)).unwrap_err();
+        assert!(matches!(err, crate::errors::StageflowError::ReadOnlyContext(_)));
+        ctx.data().set("verdict", serde_json::json!("pass")).unwrap();
+        ctx.outputs().set("test", HashMap::new(), 1, true).unwrap();
+
+        let output = interceptor.after(&ctx, StageOutput::ok_empty()).await;
+        assert!(!output.is_success());
+        assert_eq!(
+            output.error.as_deref(),
+            Some("Stage 'test' has a read-only context but wrote data 'verdict' and outputs of 'test'")
+        );
+        assert_eq!(interceptor.violation_count(), 1);
+        assert!(!ctx.pipeline_ctx().data.contains_key("verdict"));
+        assert!(ctx.pipeline_ctx().outputs.is_empty());
+    }
+
     #[tokio::test]
     async fn test_context_size_interceptor() {
         let interceptor = ContextSizeInterceptor::new(10000, 0.8);
@@ -174,4 +498,40 @@ mod tests {
         let after_result = interceptor.after(&ctx, output).await;
         assert!(after_result.is_success());
     }
+    #[tokio::test]
+    async fn test_output_size_policy_truncates_and_spills() {
+        use crate::artifacts::InMemoryArtifactStore;
+        use serde_json::json;
+
+        let store = Arc::new(InMemoryArtifactStore::new());
+        let interceptor = ContextSizeInterceptor::default()
+            .with_output_policy(OutputSizePolicy::new(8, OversizeAction::Truncate))
+            .with_stage_output_policy("spiller", OutputSizePolicy::new(8, OversizeAction::Spill))
+            .with_artifact_store(store.clone());
+        let output = || {
+            StageOutput::ok(HashMap::from([
+                ("short".to_string(), json!("ok")),
+                ("long".to_string(), json!("abcdefghijklmnop")),
+                ("list".to_string(), json!([1, 2, 3, 4, 5])),
+            ]))
+        };
+
+        let truncated = interceptor.after(&test_stage_context(), output()).await;
+        assert_eq!(truncated.get("short"), Some(&json!("ok")));
+        assert_eq!(truncated.get("long"), Some(&json!("abcdef")));
+        assert_eq!(truncated.get("list").unwrap()["truncated"], json!(true));
+        assert_eq!(interceptor.truncation_count(), 2);
+
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let ctx = StageContext::new(pipeline_ctx, "spiller", StageInputs::default(), ContextSnapshot::new());
+        let spilled = interceptor.after(&ctx, output()).await;
+        let uri = spilled.get("long").unwrap()["artifact_uri"].as_str().unwrap().to_string();
+        assert_eq!(store.get(&uri).await.unwrap().unwrap().data, json!("abcdefghijklmnop"));
+        assert_eq!(interceptor.spill_count(), 2);
+        assert_eq!(store.len(), 2);
+
+        let strict = ContextSizeInterceptor::default().with_output_policy(OutputSizePolicy::new(8, OversizeAction::Fail));
+        let failed = strict.after(&test_stage_context(), output()).await;
+        assert_eq!(failed.metadata["error_info"]["code"], crate::contracts::codes::OUTPUT_SIZE);
+    }
 }
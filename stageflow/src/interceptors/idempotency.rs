@@ -3,21 +3,24 @@
 use super::Interceptor;
 use crate::context::{ExecutionContext, StageContext};
 use crate::core::StageOutput;
+use crate::pipeline::IdempotencyKeyTemplate;
+use crate::utils::{Expiry, TtlClock};
 use async_trait::async_trait;
 use dashmap::DashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 /// Entry in the idempotency store.
 struct IdempotencyEntry {
     output: StageOutput,
-    created_at: Instant,
+    expiry: Expiry,
 }
 
 /// Store for idempotency keys.
 pub struct IdempotencyStore {
     entries: DashMap<String, IdempotencyEntry>,
     ttl: Duration,
+    clock: TtlClock,
 }
 
 impl IdempotencyStore {
@@ -27,13 +30,21 @@ impl IdempotencyStore {
         Self {
             entries: DashMap::new(),
             ttl,
+            clock: TtlClock::default(),
         }
     }
 
+    /// Sets the clock used to expire entries.
+    #[must_use]
+    pub fn with_clock(mut self, clock: TtlClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Gets a cached result.
     pub fn get(&self, key: &str) -> Option<StageOutput> {
         if let Some(entry) = self.entries.get(key) {
-            if entry.created_at.elapsed() < self.ttl {
+            if !self.clock.is_expired(&entry.expiry) {
                 return Some(entry.output.clone());
             }
             // Expired
@@ -49,7 +60,7 @@ impl IdempotencyStore {
             key,
             IdempotencyEntry {
                 output,
-                created_at: Instant::now(),
+                expiry: self.clock.expiry_after(self.ttl),
             },
         );
     }
@@ -69,17 +80,28 @@ impl Default for IdempotencyStore {
 /// Interceptor that enforces idempotent execution of WORK stages.
 pub struct IdempotencyInterceptor {
     store: Arc<IdempotencyStore>,
+    template: Option<IdempotencyKeyTemplate>,
 }
 
 impl IdempotencyInterceptor {
     /// Creates a new idempotency interceptor.
     #[must_use]
     pub fn new(store: Arc<IdempotencyStore>) -> Self {
-        Self { store }
+        Self { store, template: None }
+    }
+
+    /// Renders keys from `template` instead of `{pipeline_run_id}:{stage}`.
+    #[must_use]
+    pub fn with_key_template(mut self, template: IdempotencyKeyTemplate) -> Self {
+        self.template = Some(template);
+        self
     }
 
     /// Generates an idempotency key for a stage execution.
     fn generate_key(&self, ctx: &StageContext) -> String {
+        if let Some(template) = &self.template {
+            return template.render(ctx);
+        }
         let pipeline_run_id = ctx
             .pipeline_run_id()
             .map(|id| id.to_string())
@@ -177,4 +199,16 @@ mod tests {
         let before_result = interceptor.before(&ctx).await;
         assert!(before_result.is_some());
     }
+
+    #[tokio::test]
+    async fn test_interceptor_renders_key_template() {
+        let store = Arc::new(IdempotencyStore::new(Duration::from_secs(60)));
+        let template = IdempotencyKeyTemplate::parse("orders:{stage}").unwrap();
+        let interceptor = IdempotencyInterceptor::new(store.clone()).with_key_template(template);
+
+        // Keys ignore the run, so a later run hits the cache
+        interceptor.after(&test_stage_context(), StageOutput::ok_empty()).await;
+        assert!(store.get("orders:test").is_some());
+        assert!(interceptor.before(&test_stage_context()).await.is_some());
+    }
 }
@@ -1,11 +1,13 @@
 //! Interceptors (middleware) for stage execution.
 
 mod chain;
+mod contract;
 mod hardening;
 mod idempotency;
 mod retry;
 
 pub use chain::{Interceptor, InterceptorChain};
-pub use hardening::{ContextSizeInterceptor, ImmutabilityInterceptor};
+pub use contract::{ContractValidationInterceptor, ContractValidationMode};
+pub use hardening::{ContextSizeInterceptor, ImmutabilityInterceptor, OutputSizePolicy, OversizeAction};
 pub use idempotency::IdempotencyInterceptor;
 pub use retry::{BackoffStrategy, JitterStrategy, RetryInterceptor};
@@ -4,7 +4,7 @@ use super::Interceptor;
 use crate::context::{ExecutionContext, StageContext};
 use crate::core::StageOutput;
 use async_trait::async_trait;
-use rand::Rng;
+use crate::utils::random_in_range;
 use std::time::Duration;
 
 /// Backoff strategy for retries.
@@ -47,28 +47,30 @@ impl JitterStrategy {
     /// Applies jitter to a delay.
     #[must_use]
     pub fn apply(&self, delay: Duration) -> Duration {
-        let mut rng = rand::thread_rng();
-
         match self {
             Self::None => delay,
             Self::Full => {
                 let millis = delay.as_millis() as u64;
-                Duration::from_millis(rng.gen_range(0..=millis))
+                Duration::from_millis(random_in_range(0..=millis))
             }
             Self::Equal => {
                 let millis = delay.as_millis() as u64;
                 let half = millis / 2;
-                Duration::from_millis(half + rng.gen_range(0..=half))
+                Duration::from_millis(half + random_in_range(0..=half))
             }
             Self::Decorrelated => {
                 let millis = delay.as_millis() as u64;
-                Duration::from_millis(rng.gen_range(millis..=millis * 3))
+                Duration::from_millis(random_in_range(millis..=millis * 3))
             }
         }
     }
 }
 
 /// Interceptor that retries failed stages.
+///
+/// Retries are charged to the run's
+/// [`RetryBudget`](crate::pipeline::RetryBudget); none is scheduled once it
+/// is spent.
 pub struct RetryInterceptor {
     /// Maximum number of retry attempts.
     max_attempts: u32,
@@ -123,6 +125,11 @@ impl Interceptor for RetryInterceptor {
             return output;
         }
 
+        let delay = self.calculate_delay(1);
+        if ctx.pipeline_ctx().reserve_retry_budget(ctx.stage_name(), delay).is_err() {
+            return output;
+        }
+
         // In a real implementation, we'd track attempts and retry
         // For now, just emit the event and return
         ctx.try_emit_event(
@@ -130,6 +137,7 @@ impl Interceptor for RetryInterceptor {
             Some(serde_json::json!({
                 "stage": ctx.stage_name(),
                 "max_attempts": self.max_attempts,
+                "delay_ms": u64::try_from(delay.as_millis()).unwrap_or(u64::MAX),
             })),
         );
 
@@ -188,4 +196,36 @@ mod tests {
         let interceptor = RetryInterceptor::exponential(3, Duration::from_millis(100));
         assert_eq!(interceptor.max_attempts, 3);
     }
+
+    #[tokio::test]
+    async fn test_retries_are_charged_to_retry_budget() {
+        use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageInputs};
+        use crate::events::CollectingEventSink;
+        use crate::pipeline::RetryBudget;
+        use std::sync::Arc;
+
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_retry_budget(RetryBudget::new().with_max_retries(1)),
+        );
+        let interceptor = RetryInterceptor::constant(3, Duration::from_millis(10));
+        for name in ["a", "b"] {
+            let stage_ctx = StageContext::new(ctx.clone(), name, StageInputs::default(), ContextSnapshot::new());
+            let output = interceptor.after(&stage_ctx, StageOutput::retry("flaky")).await;
+            assert!(output.is_retryable());
+        }
+
+        let scheduled = sink.events_of_type("stage.retry_scheduled");
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].1.as_ref().unwrap()["delay_ms"], 10);
+        let exhausted = sink.events_of_type("retry.budget_exhausted");
+        assert_eq!(exhausted.len(), 1);
+        assert_eq!(exhausted[0].1.as_ref().unwrap()["stage"], "b");
+        assert_eq!(exhausted[0].1.as_ref().unwrap()["limit"], "retries");
+        let usage = ctx.retry_budget_usage().unwrap();
+        assert_eq!((usage.retries, usage.refused), (1, 1));
+        assert_eq!(usage.retry_time, Duration::from_millis(10));
+    }
 }
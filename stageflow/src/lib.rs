@@ -41,6 +41,7 @@
     clippy::missing_panics_doc
 )]
 
+pub mod artifacts;
 pub mod cancellation;
 pub mod compression;
 pub mod context;
@@ -48,15 +49,29 @@ pub mod contracts;
 pub mod core;
 pub mod errors;
 pub mod events;
+pub mod executor;
 pub mod helpers;
 pub mod interceptors;
 pub mod observability;
 pub mod pipeline;
+pub mod replay;
+pub mod scheduler;
+pub mod secrets;
 pub mod stages;
 pub mod subpipeline;
 pub mod testing;
 pub mod tools;
 pub mod utils;
+pub mod worker;
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
+#[cfg(feature = "wasm")]
+pub mod plugins;
+
+#[cfg(feature = "server")]
+pub mod server;
 
 #[cfg(feature = "websearch")]
 pub mod websearch;
@@ -74,9 +89,9 @@ pub mod prelude {
         StageArtifact, StageEvent, StageKind, StageOutput, StageStatus,
     };
     pub use crate::errors::{
-        ContractErrorInfo, CycleDetectedError, DataConflictError,
-        OutputConflictError, PipelineValidationError, StageflowError,
-        UndeclaredDependencyError,
+        AccessDeniedError, ContractErrorInfo, CycleDetectedError, DataConflictError,
+        InputError, JsonParseError, NumberPrecisionError, OutputConflictError, PipelineValidationError, ReadOnlyContextError,
+        StageflowError, UndeclaredDependencyError,
     };
     pub use crate::events::{EventSink, LoggingEventSink, NoOpEventSink};
     pub use crate::pipeline::{
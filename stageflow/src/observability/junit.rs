@@ -0,0 +1,395 @@
+//! `JUnit` XML export of run results.
+//!
+//! CI systems render `JUnit` reports natively, so pipelines run as test or
+//! eval jobs can publish one test case per stage. Suites are built from a
+//! [`UnifiedExecutionResult`] or case by case, for harnesses with their own
+//! notion of a case.
+
+use crate::core::{StageOutput, StageStatus};
+use crate::pipeline::UnifiedExecutionResult;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Outcome of a `JUnit` test case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JUnitOutcome {
+    /// The case passed.
+    Passed,
+    /// The case ran and its check failed.
+    Failed {
+        /// Failure message.
+        message: String,
+    },
+    /// The case could not run to completion.
+    Errored {
+        /// Error message.
+        message: String,
+    },
+    /// The case was not run.
+    Skipped {
+        /// Why the case was skipped, if known.
+        message: Option<String>,
+    },
+}
+
+/// A single `JUnit` test case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JUnitTestCase {
+    /// Case name, such as the stage name.
+    pub name: String,
+    /// Grouping shown by CI systems, such as the pipeline name.
+    pub classname: String,
+    /// Duration in milliseconds.
+    pub duration_ms: f64,
+    /// How the case ended.
+    pub outcome: JUnitOutcome,
+}
+
+impl JUnitTestCase {
+    /// Creates a passing case with no duration.
+    #[must_use]
+    pub fn new(classname: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            classname: classname.into(),
+            duration_ms: 0.0,
+            outcome: JUnitOutcome::Passed,
+        }
+    }
+
+    /// Creates a case from a stage output.
+    ///
+    /// Failed stages become failures, cancelled and skipped stages are
+    /// skipped, and stages that never finished are errors. The duration is
+    /// read from the output's `duration_ms` metadata when present.
+    #[must_use]
+    pub fn from_stage_output(classname: impl Into<String>, stage: impl Into<String>, output: &StageOutput) -> Self {
+        let outcome = match output.status {
+            StageStatus::Ok => JUnitOutcome::Passed,
+            StageStatus::Fail => JUnitOutcome::Failed {
+                message: output.error.clone().unwrap_or_else(|| "Stage failed".to_string()),
+            },
+            StageStatus::Skip => JUnitOutcome::Skipped {
+                message: output.skip_reason.clone(),
+            },
+            StageStatus::Cancel => JUnitOutcome::Skipped {
+                message: output.cancel_reason.clone(),
+            },
+            StageStatus::Retry | StageStatus::Pending | StageStatus::Running => JUnitOutcome::Errored {
+                message: format!("Stage ended with status {}", output.status),
+            },
+        };
+        let duration_ms = output
+            .metadata
+            .get("duration_ms")
+            .and_then(serde_json::Value::as_f64)
+            .unwrap_or(0.0);
+        Self::new(classname, stage)
+            .with_duration_ms(duration_ms)
+            .with_outcome(outcome)
+    }
+
+    /// Sets the duration.
+    #[must_use]
+    pub fn with_duration_ms(mut self, duration_ms: f64) -> Self {
+        self.duration_ms = duration_ms;
+        self
+    }
+
+    /// Sets the outcome.
+    #[must_use]
+    pub fn with_outcome(mut self, outcome: JUnitOutcome) -> Self {
+        self.outcome = outcome;
+        self
+    }
+}
+
+/// A `JUnit` test suite, usually one pipeline run.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JUnitTestSuite {
+    /// Suite name.
+    pub name: String,
+    /// Total duration in milliseconds.
+    pub duration_ms: f64,
+    /// ISO 8601 start time, if known.
+    pub timestamp: Option<String>,
+    /// The cases in report order.
+    pub cases: Vec<JUnitTestCase>,
+}
+
+impl JUnitTestSuite {
+    /// Creates an empty suite.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Creates a suite with one case per stage, sorted by stage name.
+    ///
+    /// A run that failed or was cancelled without any failing stage gets an
+    /// extra errored case named `pipeline`, so the run never reports green.
+    #[must_use]
+    pub fn from_unified_result(name: impl Into<String>, result: &UnifiedExecutionResult) -> Self {
+        let name = name.into();
+        let mut stages: Vec<_> = result.outputs.iter().collect();
+        stages.sort_by(|a, b| a.0.cmp(b.0));
+        let mut suite = Self::new(name.clone()).with_duration_ms(result.duration_ms);
+        for (stage, output) in stages {
+            suite.push(JUnitTestCase::from_stage_output(&name, stage, output));
+        }
+
+        if !result.success && suite.failures() + suite.errors() == 0 {
+            let message = result
+                .error
+                .clone()
+                .or_else(|| result.cancel_reason.clone())
+                .unwrap_or_else(|| "Pipeline failed".to_string());
+            suite.push(
+                JUnitTestCase::new(&name, "pipeline")
+                    .with_duration_ms(result.duration_ms)
+                    .with_outcome(JUnitOutcome::Errored { message }),
+            );
+        }
+        suite
+    }
+
+    /// Sets the total duration.
+    #[must_use]
+    pub fn with_duration_ms(mut self, duration_ms: f64) -> Self {
+        self.duration_ms = duration_ms;
+        self
+    }
+
+    /// Sets the start time.
+    #[must_use]
+    pub fn with_timestamp(mut self, timestamp: impl Into<String>) -> Self {
+        self.timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Adds a case.
+    pub fn push(&mut self, case: JUnitTestCase) {
+        self.cases.push(case);
+    }
+
+    /// Returns the number of failed cases.
+    #[must_use]
+    pub fn failures(&self) -> usize {
+        self.count(|outcome| matches!(outcome, JUnitOutcome::Failed { .. }))
+    }
+
+    /// Returns the number of errored cases.
+    #[must_use]
+    pub fn errors(&self) -> usize {
+        self.count(|outcome| matches!(outcome, JUnitOutcome::Errored { .. }))
+    }
+
+    /// Returns the number of skipped cases.
+    #[must_use]
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, JUnitOutcome::Skipped { .. }))
+    }
+
+    fn count(&self, matches: impl Fn(&JUnitOutcome) -> bool) -> usize {
+        self.cases.iter().filter(|case| matches(&case.outcome)).count()
+    }
+
+    fn write_xml(&self, out: &mut String) {
+        let _ = write!(
+            out,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{}""#,
+            escape(&self.name),
+            self.cases.len(),
+            self.failures(),
+            self.errors(),
+            self.skipped(),
+            seconds(self.duration_ms),
+        );
+        if let Some(timestamp) = &self.timestamp {
+            let _ = write!(out, r#" timestamp="{}""#, escape(timestamp));
+        }
+        out.push_str(">\n");
+
+        for case in &self.cases {
+            let _ = write!(
+                out,
+                r#"    <testcase name="{}" classname="{}" time="{}""#,
+                escape(&case.name),
+                escape(&case.classname),
+                seconds(case.duration_ms),
+            );
+            let (tag, message) = match &case.outcome {
+                JUnitOutcome::Passed => {
+                    out.push_str("/>\n");
+                    continue;
+                }
+                JUnitOutcome::Failed { message } => ("failure", Some(message)),
+                JUnitOutcome::Errored { message } => ("error", Some(message)),
+                JUnitOutcome::Skipped { message } => ("skipped", message.as_ref()),
+            };
+            out.push_str(">\n");
+            match message {
+                Some(message) => {
+                    let message = escape(message);
+                    let _ = writeln!(out, r#"      <{tag} message="{message}">{message}</{tag}>"#);
+                }
+                None => {
+                    let _ = writeln!(out, "      <{tag}/>");
+                }
+            }
+            out.push_str("    </testcase>\n");
+        }
+        out.push_str("  </testsuite>\n");
+    }
+}
+
+/// A `JUnit` report holding one or more suites.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JUnitReport {
+    /// Report name.
+    pub name: String,
+    /// The suites in report order.
+    pub suites: Vec<JUnitTestSuite>,
+}
+
+impl JUnitReport {
+    /// Creates an empty report.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            suites: Vec::new(),
+        }
+    }
+
+    /// Adds a suite.
+    #[must_use]
+    pub fn with_suite(mut self, suite: JUnitTestSuite) -> Self {
+        self.suites.push(suite);
+        self
+    }
+
+    /// Returns true if no suite has failures or errors.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.suites.iter().all(|suite| suite.failures() + suite.errors() == 0)
+    }
+
+    /// Renders the report as `JUnit` XML.
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let sum = |f: fn(&JUnitTestSuite) -> usize| self.suites.iter().map(f).sum::<usize>();
+        let duration_ms: f64 = self.suites.iter().map(|suite| suite.duration_ms).sum();
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            out,
+            r#"<testsuites name="{}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{}">"#,
+            escape(&self.name),
+            sum(|suite| suite.cases.len()),
+            sum(JUnitTestSuite::failures),
+            sum(JUnitTestSuite::errors),
+            sum(JUnitTestSuite::skipped),
+            seconds(duration_ms),
+        );
+        for suite in &self.suites {
+            suite.write_xml(&mut out);
+        }
+        out.push_str("</testsuites>\n");
+        out
+    }
+
+    /// Writes the report to a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_xml())
+    }
+}
+
+/// Formats milliseconds as `JUnit` seconds.
+fn seconds(duration_ms: f64) -> String {
+    format!("{:.3}", duration_ms / 1000.0)
+}
+
+/// Escapes text for use in XML attributes and content.
+pub(super) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            // Other control characters are not allowed in XML 1.0
+            c if c.is_control() && c != '\t' && c != '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_unified_result_to_junit_xml() {
+        let outputs = HashMap::from([
+            (
+                "fetch".to_string(),
+                StageOutput::ok_empty().add_metadata("duration_ms", serde_json::json!(1500.0)),
+            ),
+            ("judge".to_string(), StageOutput::fail("score < 0.8 for \"q1\"")),
+            ("notify".to_string(), StageOutput::skip("dry run")),
+        ]);
+        let result = UnifiedExecutionResult {
+            outputs,
+            duration_ms: 2000.0,
+            success: false,
+            error: Some("judge failed".to_string()),
+            cancelled: false,
+            cancel_reason: None,
+            tool_transcript: crate::tools::ToolTranscript::default(),
+            enrichment_cache_stats: None,
+        };
+
+        let suite = JUnitTestSuite::from_unified_result("eval", &result);
+        assert_eq!((suite.failures(), suite.errors(), suite.skipped()), (1, 0, 1));
+        let report = JUnitReport::new("ci").with_suite(suite);
+        assert!(!report.is_success());
+
+        let xml = report.to_xml();
+        assert!(xml.contains(r#"<testsuites name="ci" tests="3" failures="1" errors="0" skipped="1" time="2.000">"#));
+        assert!(xml.contains(r#"<testcase name="fetch" classname="eval" time="1.500"/>"#));
+        assert!(xml.contains(r#"<failure message="score &lt; 0.8 for &quot;q1&quot;">"#));
+        assert!(xml.contains(r#"<skipped message="dry run">"#));
+    }
+
+    #[test]
+    fn test_cancelled_run_without_failing_stage_reports_error() {
+        let result = UnifiedExecutionResult {
+            outputs: HashMap::from([("a".to_string(), StageOutput::ok_empty())]),
+            duration_ms: 5.0,
+            success: false,
+            error: None,
+            cancelled: true,
+            cancel_reason: Some("user stop".to_string()),
+            tool_transcript: crate::tools::ToolTranscript::default(),
+            enrichment_cache_stats: None,
+        };
+        let suite = JUnitTestSuite::from_unified_result("run", &result);
+        assert_eq!(suite.errors(), 1);
+        assert_eq!(
+            suite.cases.last().unwrap().outcome,
+            JUnitOutcome::Errored { message: "user stop".to_string() }
+        );
+    }
+}
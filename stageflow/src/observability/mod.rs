@@ -1,10 +1,17 @@
 //! Observability utilities.
 
+mod junit;
+mod report;
 mod tracing;
 mod wide_events;
 
+pub use junit::{JUnitOutcome, JUnitReport, JUnitTestCase, JUnitTestSuite};
+pub use report::{FailureChain, PipelineRunReport, StageRunReport};
+
 pub use tracing::{
     LoggingTracingEmitter, NoOpTracingEmitter, PipelineSpanAttributes, SpanTimer,
     StageSpanAttributes, TracingEmitter,
 };
-pub use wide_events::WideEventEmitter;
+pub use wide_events::{
+    StageFailureInfo, StageResourceMetrics, StageWideEvent, WideEventEmitter, WideEventEnricher,
+};
@@ -0,0 +1,550 @@
+//! Structured reports of finished pipeline runs.
+//!
+//! A [`PipelineRunReport`] combines a [`UnifiedExecutionResult`] with the
+//! run's `stage.*` and `guard_retry.*` events into what an operator wants
+//! to see after a run: when each stage ran and for how long, which chain of
+//! stages bounded the run's duration, how often stages were retried, why
+//! stages were skipped, and what a failure prevented from running. Reports
+//! render as JSON, Markdown or HTML.
+
+use super::junit::escape;
+use crate::context::EnrichmentCacheStats;
+use crate::core::{StageOutput, StageStatus};
+use crate::errors::StageflowError;
+use crate::pipeline::{StageGraph, UnifiedExecutionResult};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+
+/// What one stage did during a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageRunReport {
+    /// The stage name.
+    pub stage: String,
+    /// Final status; `None` if the stage never finished.
+    pub status: Option<StageStatus>,
+    /// When the stage last started (ISO 8601), from its `stage.started` event.
+    pub started_at: Option<String>,
+    /// Milliseconds from the first stage start to this stage's last start.
+    pub offset_ms: Option<f64>,
+    /// Wall-clock duration of the last execution in milliseconds.
+    pub duration_ms: Option<f64>,
+    /// How many times the stage started.
+    pub executions: u32,
+    /// Re-executions plus retries the stage reported under the
+    /// `retry_count` output metadata key.
+    pub retries: u32,
+    /// Why the stage was skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<String>,
+    /// Error of a failed stage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Reason given by a cancelled stage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+    /// Whether the stage is on the critical path.
+    pub on_critical_path: bool,
+}
+
+/// The stage that ended a run and what it kept from running.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailureChain {
+    /// The failed or cancelled stage.
+    pub origin: String,
+    /// Its status.
+    pub status: StageStatus,
+    /// Its error or cancel reason.
+    pub message: Option<String>,
+    /// Stages downstream of the origin that never finished, in execution
+    /// order.
+    pub blocked: Vec<String>,
+}
+
+/// A structured report of one pipeline run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineRunReport {
+    /// The pipeline name.
+    pub pipeline: String,
+    /// Whether the run succeeded.
+    pub success: bool,
+    /// Whether the run was cancelled.
+    pub cancelled: bool,
+    /// The run's error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Why the run was cancelled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+    /// Total duration in milliseconds.
+    pub duration_ms: f64,
+    /// One entry per stage, in execution order.
+    pub stages: Vec<StageRunReport>,
+    /// The chain of dependent stages with the longest total duration.
+    pub critical_path: Vec<String>,
+    /// Sum of the durations on the critical path.
+    pub critical_path_ms: f64,
+    /// Total retries across all stages.
+    pub total_retries: u32,
+    /// Set if a stage failed or cancelled the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureChain>,
+    /// Enrichment cache hits and misses, if the run had a cache attached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enrichment_cache: Option<EnrichmentCacheStats>,
+}
+
+impl PipelineRunReport {
+    /// Builds the report for a run of `graph`.
+    ///
+    /// `events` are the events the run emitted, as collected by
+    /// [`CollectingEventSink`](crate::events::CollectingEventSink). Durations
+    /// come from the `duration_ms` output metadata, falling back to the
+    /// outcome events; start times come from the `emitted_at` stamp of
+    /// `stage.started` events. Guard failures that triggered a retry count as
+    /// retries of the guard.
+    #[must_use]
+    pub fn from_run(
+        graph: &StageGraph,
+        result: &UnifiedExecutionResult,
+        events: &[(String, Option<serde_json::Value>)],
+    ) -> Self {
+        let observed = ObservedEvents::collect(events);
+        let first_start = observed.started_at.values().map(|(_, at)| *at).min();
+
+        let mut stages: Vec<StageRunReport> = graph
+            .execution_order()
+            .iter()
+            .map(|stage| {
+                let output = result.outputs.get(stage);
+                let executions = observed.starts.get(stage).copied().unwrap_or(0);
+                let reported = output
+                    .and_then(|o| o.metadata.get("retry_count"))
+                    .and_then(serde_json::Value::as_u64)
+                    .and_then(|count| u32::try_from(count).ok())
+                    .unwrap_or(0);
+                let guard_retries = observed.guard_retries.get(stage).copied().unwrap_or(0);
+                let retries = executions.saturating_sub(1).max(guard_retries) + reported;
+                let started = observed.started_at.get(stage);
+                StageRunReport {
+                    stage: stage.clone(),
+                    status: output.map(|o| o.status),
+                    started_at: started.map(|(text, _)| text.clone()),
+                    offset_ms: started.zip(first_start).map(|((_, at), first)| millis_between(first, *at)),
+                    duration_ms: output
+                        .and_then(|o| o.metadata.get("duration_ms"))
+                        .and_then(serde_json::Value::as_f64)
+                        .or_else(|| observed.durations.get(stage).copied()),
+                    executions,
+                    retries,
+                    skip_reason: output
+                        .and_then(|o| o.skip_reason.clone())
+                        .or_else(|| observed.skip_reasons.get(stage).cloned()),
+                    error: output.and_then(|o| o.error.clone()),
+                    cancel_reason: output.and_then(|o| o.cancel_reason.clone()),
+                    on_critical_path: false,
+                }
+            })
+            .collect();
+
+        let durations = stages
+            .iter()
+            .filter(|s| s.status.is_some())
+            .map(|s| (s.stage.clone(), s.duration_ms.unwrap_or(0.0)))
+            .collect();
+        let analysis = graph.analyze(&durations);
+        for stage in &mut stages {
+            stage.on_critical_path = analysis.is_critical(&stage.stage);
+        }
+
+        Self {
+            pipeline: graph.name().to_string(),
+            success: result.success,
+            cancelled: result.cancelled,
+            error: result.error.clone(),
+            cancel_reason: result.cancel_reason.clone(),
+            duration_ms: result.duration_ms,
+            total_retries: stages.iter().map(|s| s.retries).sum(),
+            failure: failure_chain(graph, &result.outputs),
+            stages,
+            critical_path: analysis.critical_path,
+            critical_path_ms: analysis.critical_path_ms,
+            enrichment_cache: result.enrichment_cache_stats,
+        }
+    }
+
+    /// Returns the report for a stage.
+    #[must_use]
+    pub fn stage(&self, name: &str) -> Option<&StageRunReport> {
+        self.stages.iter().find(|s| s.stage == name)
+    }
+
+    /// Serializes the report as pretty-printed JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, StageflowError> {
+        serde_json::to_string_pretty(self).map_err(|e| StageflowError::Serialization(e.to_string()))
+    }
+
+    /// Renders the report as Markdown.
+    #[must_use]
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Pipeline run: {}\n", self.pipeline);
+        let _ = writeln!(out, "- **Status:** {}", self.status_label());
+        let _ = writeln!(out, "- **Duration:** {:.1} ms", self.duration_ms);
+        if let Some(message) = self.error.as_ref().or(self.cancel_reason.as_ref()) {
+            let _ = writeln!(out, "- **Reason:** {}", markdown_line(message));
+        }
+        let _ = writeln!(
+            out,
+            "- **Critical path:** {} ({:.1} ms)",
+            self.critical_path.join(" → "),
+            self.critical_path_ms
+        );
+        let _ = writeln!(out, "- **Retries:** {}", self.total_retries);
+        if let Some(cache) = self.enrichment_cache_text() {
+            let _ = writeln!(out, "- **Enrichment cache:** {cache}");
+        }
+        out.push('\n');
+
+        out.push_str("## Stages\n\n");
+        out.push_str("| Stage | Status | Start (ms) | Duration (ms) | Retries | Notes |\n");
+        out.push_str("|---|---|---:|---:|---:|---|\n");
+        for stage in &self.stages {
+            let name = if stage.on_critical_path {
+                format!("**{}**", markdown_cell(&stage.stage))
+            } else {
+                markdown_cell(&stage.stage)
+            };
+            let _ = writeln!(
+                out,
+                "| {name} | {} | {} | {} | {} | {} |",
+                status_text(stage.status),
+                millis_text(stage.offset_ms),
+                millis_text(stage.duration_ms),
+                stage.retries,
+                markdown_cell(&stage.note().unwrap_or_default()),
+            );
+        }
+
+        if let Some(failure) = &self.failure {
+            out.push_str("\n## Failure\n\n");
+            let _ = writeln!(
+                out,
+                "`{}` ended with `{}`: {}",
+                failure.origin,
+                failure.status,
+                markdown_line(failure.message.as_deref().unwrap_or("no message")),
+            );
+            if !failure.blocked.is_empty() {
+                let _ = writeln!(out, "\nDid not run: {}", failure.blocked.join(", "));
+            }
+        }
+        out
+    }
+
+    /// Renders the report as a standalone HTML page.
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(out, "<title>Pipeline run: {}</title>", escape(&self.pipeline));
+        out.push_str(
+            "<style>body{font-family:sans-serif}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:4px 8px}tr.critical{font-weight:bold}\
+             .fail{color:#b00}.skip{color:#888}</style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(out, "<h1>Pipeline run: {}</h1>", escape(&self.pipeline));
+        out.push_str("<ul>\n");
+        let _ = writeln!(out, "<li>Status: {}</li>", self.status_label());
+        let _ = writeln!(out, "<li>Duration: {:.1} ms</li>", self.duration_ms);
+        if let Some(message) = self.error.as_ref().or(self.cancel_reason.as_ref()) {
+            let _ = writeln!(out, "<li>Reason: {}</li>", escape(message));
+        }
+        let _ = writeln!(
+            out,
+            "<li>Critical path: {} ({:.1} ms)</li>",
+            escape(&self.critical_path.join(" → ")),
+            self.critical_path_ms
+        );
+        let _ = writeln!(out, "<li>Retries: {}</li>", self.total_retries);
+        if let Some(cache) = self.enrichment_cache_text() {
+            let _ = writeln!(out, "<li>Enrichment cache: {cache}</li>");
+        }
+        out.push_str("</ul>\n");
+
+        out.push_str("<table>\n<tr><th>Stage</th><th>Status</th><th>Start (ms)</th>");
+        out.push_str("<th>Duration (ms)</th><th>Retries</th><th>Notes</th></tr>\n");
+        for stage in &self.stages {
+            let class = if stage.on_critical_path { " class=\"critical\"" } else { "" };
+            let status_class = match stage.status {
+                Some(StageStatus::Fail | StageStatus::Cancel) => "fail",
+                Some(StageStatus::Skip) | None => "skip",
+                _ => "",
+            };
+            let _ = writeln!(
+                out,
+                "<tr{class}><td>{}</td><td class=\"{status_class}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&stage.stage),
+                status_text(stage.status),
+                millis_text(stage.offset_ms),
+                millis_text(stage.duration_ms),
+                stage.retries,
+                escape(&stage.note().unwrap_or_default()),
+            );
+        }
+        out.push_str("</table>\n");
+
+        if let Some(failure) = &self.failure {
+            out.push_str("<h2>Failure</h2>\n");
+            let _ = writeln!(
+                out,
+                "<p><code>{}</code> ended with <code>{}</code>: {}</p>",
+                escape(&failure.origin),
+                failure.status,
+                escape(failure.message.as_deref().unwrap_or("no message")),
+            );
+            if !failure.blocked.is_empty() {
+                let _ = writeln!(out, "<p>Did not run: {}</p>", escape(&failure.blocked.join(", ")));
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn status_label(&self) -> &'static str {
+        if self.success {
+            "succeeded"
+        } else if self.cancelled {
+            "cancelled"
+        } else {
+            "failed"
+        }
+    }
+
+    fn enrichment_cache_text(&self) -> Option<String> {
+        self.enrichment_cache.map(|stats| {
+            format!(
+                "{} hits, {} misses ({:.0}% hit rate)",
+                stats.hits,
+                stats.misses,
+                stats.hit_rate() * 100.0
+            )
+        })
+    }
+}
+
+impl StageRunReport {
+    fn note(&self) -> Option<String> {
+        self.error
+            .as_ref()
+            .or(self.skip_reason.as_ref())
+            .or(self.cancel_reason.as_ref())
+            .cloned()
+    }
+}
+
+/// What the run's events say about each stage.
+#[derive(Default)]
+struct ObservedEvents {
+    starts: HashMap<String, u32>,
+    started_at: HashMap<String, (String, DateTime<FixedOffset>)>,
+    durations: HashMap<String, f64>,
+    skip_reasons: HashMap<String, String>,
+    guard_retries: HashMap<String, u32>,
+}
+
+impl ObservedEvents {
+    fn collect(events: &[(String, Option<serde_json::Value>)]) -> Self {
+        let mut observed = Self::default();
+        for (event_type, data) in events {
+            let Some(data) = data else { continue };
+            let field = |key: &str| data.get(key).and_then(serde_json::Value::as_str);
+            if event_type == "guard_retry.scheduled" {
+                if let Some(guard) = field("guard") {
+                    *observed.guard_retries.entry(guard.to_string()).or_insert(0) += 1;
+                }
+                continue;
+            }
+            let Some(stage) = field("stage") else { continue };
+            match event_type.as_str() {
+                "stage.started" => {
+                    *observed.starts.entry(stage.to_string()).or_insert(0) += 1;
+                    let stamp = field("emitted_at")
+                        .and_then(|text| DateTime::parse_from_rfc3339(text).ok().map(|at| (text.to_string(), at)));
+                    if let Some(stamp) = stamp {
+                        observed.started_at.insert(stage.to_string(), stamp);
+                    }
+                }
+                "stage.completed" | "stage.failed" => {
+                    if let Some(duration) = data.get("duration_ms").and_then(serde_json::Value::as_f64) {
+                        observed.durations.insert(stage.to_string(), duration);
+                    }
+                }
+                "stage.skipped" => {
+                    if let Some(reason) = field("reason") {
+                        observed.skip_reasons.insert(stage.to_string(), reason.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        observed
+    }
+}
+
+/// Finds the first failed or cancelled stage and the stages it blocked.
+fn failure_chain(graph: &StageGraph, outputs: &HashMap<String, StageOutput>) -> Option<FailureChain> {
+    let (origin, output) = graph.execution_order().iter().find_map(|stage| {
+        outputs
+            .get(stage)
+            .filter(|o| matches!(o.status, StageStatus::Fail | StageStatus::Cancel))
+            .map(|o| (stage, o))
+    })?;
+
+    let mut downstream = BTreeSet::from([origin.as_str()]);
+    let mut blocked = Vec::new();
+    for stage in graph.execution_order() {
+        let Some(spec) = graph.stage_spec(stage) else { continue };
+        if spec.dependencies.iter().any(|dep| downstream.contains(dep.as_str())) {
+            downstream.insert(stage.as_str());
+            if !outputs.contains_key(stage) {
+                blocked.push(stage.clone());
+            }
+        }
+    }
+
+    Some(FailureChain {
+        origin: origin.clone(),
+        status: output.status,
+        message: output.error.clone().or_else(|| output.cancel_reason.clone()),
+        blocked,
+    })
+}
+
+fn millis_between(from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) -> f64 {
+    (to - from).to_std().map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0)
+}
+
+fn status_text(status: Option<StageStatus>) -> String {
+    status.map_or_else(|| "not run".to_string(), |s| s.to_string())
+}
+
+fn millis_text(ms: Option<f64>) -> String {
+    ms.map_or_else(|| "-".to_string(), |ms| format!("{ms:.1}"))
+}
+
+/// Escapes text for a Markdown table cell, keeping line breaks as `<br>`.
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace("\r\n", "<br>").replace(['\n', '\r'], "<br>")
+}
+
+/// Joins multi-line text onto one Markdown line.
+fn markdown_line(text: &str) -> String {
+    text.replace("\r\n", " ").replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::NoOpStage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn graph() -> StageGraph {
+        let noop = |name: &str| -> Arc<dyn crate::stages::Stage> { Arc::new(NoOpStage::new(name)) };
+        PipelineBuilder::new("report")
+            .stage("fetch", noop("fetch"), &[])
+            .unwrap()
+            .stage("quick", noop("quick"), &["fetch"])
+            .unwrap()
+            .stage("slow", noop("slow"), &["fetch"])
+            .unwrap()
+            .stage("merge", noop("merge"), &["quick", "slow"])
+            .unwrap()
+            .stage("publish", noop("publish"), &["merge"])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn timed(ms: f64) -> StageOutput {
+        StageOutput::ok_empty().add_metadata("duration_ms", json!(ms))
+    }
+
+    fn started(stage: &str, at: &str) -> (String, Option<serde_json::Value>) {
+        ("stage.started".to_string(), Some(json!({"stage": stage, "emitted_at": at})))
+    }
+
+    #[test]
+    fn test_report_critical_path_retries_and_failure_chain() {
+        let result = UnifiedExecutionResult {
+            outputs: HashMap::from([
+                ("fetch".to_string(), timed(10.0)),
+                ("quick".to_string(), timed(5.0)),
+                ("slow".to_string(), timed(40.0)),
+                ("merge".to_string(), StageOutput::fail("bad | merge\nsee logs\r\nfor details")),
+            ]),
+            duration_ms: 60.0,
+            success: false,
+            error: Some("Stage 'merge' failed".to_string()),
+            cancelled: false,
+            cancel_reason: None,
+            tool_transcript: crate::tools::ToolTranscript::default(),
+            enrichment_cache_stats: Some(EnrichmentCacheStats { hits: 3, misses: 1 }),
+        };
+        let events = vec![
+            started("fetch", "2026-01-01T00:00:00.000000+00:00"),
+            started("quick", "2026-01-01T00:00:00.010000+00:00"),
+            started("slow", "2026-01-01T00:00:00.010000+00:00"),
+            started("slow", "2026-01-01T00:00:00.012000+00:00"),
+            started("merge", "2026-01-01T00:00:00.052000+00:00"),
+            ("stage.failed".to_string(), Some(json!({"stage": "merge", "duration_ms": 3.0}))),
+        ];
+
+        let report = PipelineRunReport::from_run(&graph(), &result, &events);
+        assert_eq!(report.critical_path, vec!["fetch", "slow", "merge"]);
+        assert!((report.critical_path_ms - 53.0).abs() < f64::EPSILON);
+        assert_eq!(report.stage("slow").unwrap().retries, 1);
+        assert_eq!(report.stage("merge").unwrap().offset_ms, Some(52.0));
+        assert_eq!(report.stage("publish").unwrap().status, None);
+        let failure = report.failure.as_ref().unwrap();
+        assert_eq!((failure.origin.as_str(), failure.blocked.clone()), ("merge", vec!["publish".to_string()]));
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("fetch → slow → merge"));
+        assert!(markdown.contains("| **slow** | ok | 12.0 | 40.0 | 1 |"));
+        assert!(markdown.contains("bad \\| merge<br>see logs<br>for details"));
+        assert_eq!(markdown.lines().filter(|line| line.starts_with("| ")).count(), 6);
+        assert!(markdown.contains("- **Enrichment cache:** 3 hits, 1 misses (75% hit rate)\n\n## Stages"));
+        let html = report.to_html();
+        assert!(html.contains("<tr class=\"critical\"><td>merge</td><td class=\"fail\">fail</td>"));
+        assert!(html.contains("<li>Enrichment cache: 3 hits, 1 misses (75% hit rate)</li>\n</ul>"));
+
+        let parsed: PipelineRunReport = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, report);
+    }
+
+    #[tokio::test]
+    async fn test_report_from_executed_run() {
+        let graph = graph();
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(
+            crate::context::PipelineContext::new(crate::context::RunIdentity::new())
+                .with_event_sink(sink.clone()),
+        );
+        let unified = crate::pipeline::UnifiedStageGraph::new(graph);
+        let result = unified.execute(ctx, crate::context::ContextSnapshot::new()).await.unwrap();
+
+        let report = PipelineRunReport::from_run(unified.graph(), &result, &sink.events());
+        assert!(report.success && report.failure.is_none());
+        assert_eq!(report.stages.len(), 5);
+        assert!(report.stages.iter().all(|s| s.executions == 1 && s.started_at.is_some()));
+        assert_eq!(report.critical_path.first().map(String::as_str), Some("fetch"));
+        assert_eq!(report.critical_path.last().map(String::as_str), Some("publish"));
+    }
+}
@@ -1,8 +1,186 @@
 //! Wide event emitter for comprehensive observability.
+//!
+//! A wide event is one self-contained record per stage execution, carrying
+//! everything needed to analyse it without joining other events: timing,
+//! a hash of the inputs, retries, failure details and resource use. Attach
+//! a [`WideEventEmitter`] to a run with
+//! [`PipelineContext::with_wide_events`] and both DAG engines emit a
+//! [`StageWideEvent`] through the run's event sink as each stage finishes.
 
-use crate::context::ExecutionContext;
-use crate::core::StageStatus;
+use crate::cancellation::CoopStats;
+use crate::context::{ExecutionContext, PipelineContext, StageContext};
+use crate::core::{StageOutput, StageStatus};
+use crate::pipeline::hash_parameters;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Why a stage execution did not succeed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageFailureInfo {
+    /// Error message of a failed stage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Reason given by a cancelled stage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+    /// Whether the stage marked the error as retryable.
+    #[serde(default)]
+    pub retryable: bool,
+}
+
+/// Resources used by a stage execution.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageResourceMetrics {
+    /// Size of the serialized output data in bytes.
+    pub output_bytes: usize,
+    /// Number of artifacts produced.
+    pub artifacts: usize,
+    /// Tool calls the stage made.
+    pub tool_calls: usize,
+    /// Time spent in those tool calls, in milliseconds.
+    pub tool_duration_ms: f64,
+    /// Cooperative yielding counters, if the stage yielded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coop: Option<CoopStats>,
+}
+
+/// Canonical wide event for one stage execution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageWideEvent {
+    /// The pipeline run.
+    pub pipeline_run_id: Option<String>,
+    /// The request that started the run.
+    pub request_id: Option<String>,
+    /// Execution mode of the run.
+    pub execution_mode: String,
+    /// Topology of the run.
+    pub topology: Option<String>,
+    /// The stage name.
+    pub stage: String,
+    /// Final status of the execution.
+    pub status: StageStatus,
+    /// When the stage started, as RFC 3339.
+    pub started_at: String,
+    /// When the stage finished, as RFC 3339.
+    pub ended_at: String,
+    /// Wall-clock duration in milliseconds.
+    pub duration_ms: f64,
+    /// Hash of the inputs the stage received.
+    pub inputs_hash: String,
+    /// Earlier executions of the stage in this run, plus any retries the
+    /// stage reported under the `retry_count` output metadata key.
+    pub retry_count: u32,
+    /// Failure details, for failed and cancelled stages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<StageFailureInfo>,
+    /// Resource use.
+    pub resources: StageResourceMetrics,
+    /// Output data keys, sorted.
+    pub data_keys: Vec<String>,
+    /// Fields added by enrichers.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+impl StageWideEvent {
+    /// Builds the event for a finished stage execution.
+    ///
+    /// `prior_runs` is how many times the stage already ran in this run.
+    #[must_use]
+    pub fn from_execution(
+        stage_ctx: &StageContext,
+        output: &StageOutput,
+        started_at: DateTime<Utc>,
+        duration_ms: f64,
+        prior_runs: u32,
+    ) -> Self {
+        let ctx = stage_ctx.pipeline_ctx();
+        let stage = stage_ctx.stage_name().to_string();
+        let ended_at = started_at
+            + chrono::Duration::from_std(std::time::Duration::from_secs_f64(duration_ms.max(0.0) / 1000.0))
+                .unwrap_or_else(|_| chrono::Duration::zero());
+
+        let inputs = serde_json::to_value(stage_ctx.inputs().to_flat_dict()).unwrap_or_default();
+        let reported_retries = output
+            .metadata
+            .get("retry_count")
+            .and_then(serde_json::Value::as_u64)
+            .and_then(|count| u32::try_from(count).ok())
+            .unwrap_or(0);
+
+        let failure = matches!(output.status, StageStatus::Fail | StageStatus::Cancel).then(|| {
+            StageFailureInfo {
+                error: output.error.clone(),
+                cancel_reason: output.cancel_reason.clone(),
+                retryable: output.retryable,
+            }
+        });
+
+        let transcript = ctx.tool_transcript();
+        let stage_calls = transcript
+            .calls
+            .iter()
+            .filter(|call| call.stage.as_deref() == Some(stage.as_str()));
+        let (tool_calls, tool_duration_ms) =
+            stage_calls.fold((0, 0.0), |(count, ms), call| (count + 1, ms + call.duration_ms));
+        let resources = StageResourceMetrics {
+            output_bytes: output
+                .data
+                .as_ref()
+                .and_then(|data| serde_json::to_vec(data).ok())
+                .map_or(0, |bytes| bytes.len()),
+            artifacts: output.artifacts.len(),
+            tool_calls,
+            tool_duration_ms,
+            coop: stage_ctx.coop_stats(),
+        };
+
+        let mut data_keys: Vec<String> = output
+            .data
+            .as_ref()
+            .map(|data| data.keys().cloned().collect())
+            .unwrap_or_default();
+        data_keys.sort();
+
+        Self {
+            pipeline_run_id: ctx.pipeline_run_id().map(|id| id.to_string()),
+            request_id: ctx.request_id().map(|id| id.to_string()),
+            execution_mode: ctx.execution_mode().to_string(),
+            topology: ctx.topology().map(ToString::to_string),
+            stage,
+            status: output.status,
+            started_at: started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            ended_at: ended_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            duration_ms,
+            inputs_hash: hash_parameters(&inputs, None),
+            retry_count: prior_runs.saturating_add(reported_retries),
+            failure,
+            resources,
+            data_keys,
+            attributes: serde_json::Map::new(),
+        }
+    }
+}
+
+/// Adds fields to stage wide events before they are emitted.
+///
+/// Closures taking the same arguments as [`enrich`](Self::enrich) are
+/// enrichers.
+pub trait WideEventEnricher: Send + Sync {
+    /// Adds to or adjusts `event`, usually through its `attributes`.
+    fn enrich(&self, event: &mut StageWideEvent, stage_ctx: &StageContext, output: &StageOutput);
+}
+
+impl<F> WideEventEnricher for F
+where
+    F: Fn(&mut StageWideEvent, &StageContext, &StageOutput) + Send + Sync,
+{
+    fn enrich(&self, event: &mut StageWideEvent, stage_ctx: &StageContext, output: &StageOutput) {
+        self(event, stage_ctx, output);
+    }
+}
 
 /// Emitter for wide events (comprehensive event payloads).
 pub struct WideEventEmitter {
@@ -10,6 +188,8 @@ pub struct WideEventEmitter {
     pub stage_event_type: String,
     /// Default event type for pipeline events.
     pub pipeline_event_type: String,
+    /// Enrichers applied to stage wide events, in order.
+    enrichers: Vec<Arc<dyn WideEventEnricher>>,
 }
 
 impl Default for WideEventEmitter {
@@ -17,10 +197,21 @@ impl Default for WideEventEmitter {
         Self {
             stage_event_type: "stage.wide".to_string(),
             pipeline_event_type: "pipeline.wide".to_string(),
+            enrichers: Vec::new(),
         }
     }
 }
 
+impl std::fmt::Debug for WideEventEmitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WideEventEmitter")
+            .field("stage_event_type", &self.stage_event_type)
+            .field("pipeline_event_type", &self.pipeline_event_type)
+            .field("enrichers", &self.enrichers.len())
+            .finish()
+    }
+}
+
 impl WideEventEmitter {
     /// Creates a new wide event emitter.
     #[must_use]
@@ -28,6 +219,54 @@ impl WideEventEmitter {
         Self::default()
     }
 
+    /// Adds an enricher, run after those already added.
+    #[must_use]
+    pub fn with_enricher(mut self, enricher: impl WideEventEnricher + 'static) -> Self {
+        self.enrichers.push(Arc::new(enricher));
+        self
+    }
+
+    /// Builds the enriched wide event for a finished stage execution.
+    #[must_use]
+    pub fn build_stage_event(
+        &self,
+        stage_ctx: &StageContext,
+        output: &StageOutput,
+        started_at: DateTime<Utc>,
+        duration_ms: f64,
+        prior_runs: u32,
+    ) -> StageWideEvent {
+        let mut event =
+            StageWideEvent::from_execution(stage_ctx, output, started_at, duration_ms, prior_runs);
+        for enricher in &self.enrichers {
+            enricher.enrich(&mut event, stage_ctx, output);
+        }
+        event
+    }
+
+    /// Emits the wide event for a finished stage execution through the
+    /// run's event sink.
+    ///
+    /// Called by the DAG engines as each stage is finalized.
+    pub fn emit_stage_execution(
+        &self,
+        stage_ctx: &StageContext,
+        output: &StageOutput,
+        started_at: DateTime<Utc>,
+        duration_ms: f64,
+    ) {
+        let ctx: &PipelineContext = stage_ctx.pipeline_ctx();
+        if !ctx.profile().emits(&self.stage_event_type) {
+            return;
+        }
+        let prior_runs = ctx.record_stage_run(stage_ctx.stage_name());
+        let event = self.build_stage_event(stage_ctx, output, started_at, duration_ms, prior_runs);
+        match serde_json::to_value(&event) {
+            Ok(payload) => ctx.try_emit_event(&self.stage_event_type, Some(payload)),
+            Err(e) => tracing::warn!(stage = %event.stage, error = %e, "Failed to serialize wide event"),
+        }
+    }
+
     /// Builds a stage payload.
     #[must_use]
     pub fn build_stage_payload<C: ExecutionContext>(
@@ -151,6 +390,80 @@ mod tests {
     use crate::context::DictContextAdapter;
     use uuid::Uuid;
 
+    fn wide_pipeline() -> crate::pipeline::StageGraph {
+        use crate::stages::FnStage;
+        crate::pipeline::PipelineBuilder::new("wide")
+            .stage(
+                "fetch",
+                Arc::new(FnStage::new("fetch", |_| StageOutput::ok_value("rows", serde_json::json!([1, 2])))),
+                &[],
+            )
+            .unwrap()
+            .stage(
+                "store",
+                Arc::new(FnStage::new("store", |_| StageOutput::fail_retryable("disk full"))),
+                &["fetch"],
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn wide_context(sink: Arc<crate::events::CollectingEventSink>) -> Arc<PipelineContext> {
+        let emitter = WideEventEmitter::new().with_enricher(
+            |event: &mut StageWideEvent, _: &StageContext, _: &StageOutput| {
+                event.attributes.insert("team".to_string(), serde_json::json!("data"));
+            },
+        );
+        Arc::new(
+            PipelineContext::new(crate::context::RunIdentity::new())
+                .with_event_sink(sink)
+                .with_wide_events(Arc::new(emitter)),
+        )
+    }
+
+    fn wide_events(sink: &crate::events::CollectingEventSink) -> Vec<StageWideEvent> {
+        sink.events_of_type("stage.wide")
+            .into_iter()
+            .map(|(_, data)| serde_json::from_value(data.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_both_engines_emit_enriched_stage_events() {
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let result = wide_pipeline()
+            .execute(wide_context(sink.clone()), crate::context::ContextSnapshot::new())
+            .await
+            .unwrap();
+        assert!(!result.success);
+
+        let events = wide_events(&sink);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].stage, "fetch");
+        assert_eq!(events[0].status, StageStatus::Ok);
+        assert_eq!(events[0].data_keys, vec!["rows".to_string()]);
+        assert!(events[0].resources.output_bytes > 0);
+        assert!(events[0].failure.is_none());
+        assert_eq!(events[0].attributes["team"], "data");
+
+        let failure = events[1].failure.as_ref().unwrap();
+        assert_eq!(failure.error.as_deref(), Some("disk full"));
+        assert!(failure.retryable);
+        assert_ne!(events[0].inputs_hash, events[1].inputs_hash);
+
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = wide_context(sink.clone());
+        let unified = crate::pipeline::UnifiedStageGraph::new(wide_pipeline());
+        unified.execute(ctx.clone(), crate::context::ContextSnapshot::new()).await.unwrap();
+        unified.execute(ctx, crate::context::ContextSnapshot::new()).await.unwrap();
+
+        let events = wide_events(&sink);
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[3].stage, "store");
+        assert_eq!(events[3].retry_count, 1);
+    }
+
     #[test]
     fn test_emitter_creation() {
         let emitter = WideEventEmitter::new();
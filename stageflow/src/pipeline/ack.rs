@@ -0,0 +1,133 @@
+//! Manual stage acknowledgment for at-least-once delivery.
+//!
+//! A stage declared with [`StageSpec::with_manual_ack`](super::StageSpec::with_manual_ack)
+//! is not considered complete when it returns. The executor waits until an
+//! external system, typically a transactional outbox consumer, confirms the
+//! stage's side effects through [`StageAckRegistry::ack`]. Unacknowledged
+//! stages are not recorded in the run checkpoint, so a resumed run delivers
+//! them again; acknowledged ones are restored from the checkpoint instead.
+
+use crate::context::ExecutionContext;
+use crate::errors::StageflowError;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Default time the executor waits for an acknowledgment.
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Acknowledgments received for manual-ack stages, keyed by run and stage.
+#[derive(Debug, Default)]
+pub struct StageAckRegistry {
+    acked: Mutex<HashSet<(String, String)>>,
+    notify: Notify,
+}
+
+impl StageAckRegistry {
+    /// Creates an empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acknowledges a stage of a run, releasing an executor waiting on it.
+    ///
+    /// Acknowledgments that arrive before the stage returns are kept until
+    /// the executor asks for them.
+    pub fn ack(&self, run_id: &str, stage: &str) {
+        self.acked
+            .lock()
+            .insert((run_id.to_string(), stage.to_string()));
+        self.notify.notify_waiters();
+    }
+
+    /// Returns true if a pending acknowledgment exists.
+    #[must_use]
+    pub fn is_acked(&self, run_id: &str, stage: &str) -> bool {
+        self.acked
+            .lock()
+            .contains(&(run_id.to_string(), stage.to_string()))
+    }
+
+    /// Drops pending acknowledgments for a run.
+    pub fn forget_run(&self, run_id: &str) {
+        self.acked.lock().retain(|(run, _)| run != run_id);
+    }
+
+    /// Waits up to `timeout` for a stage to be acknowledged.
+    ///
+    /// Consumes the acknowledgment, so a stage that runs again, for example
+    /// through a guard retry, must be acknowledged again. Returns false on
+    /// timeout.
+    pub async fn wait(&self, run_id: &str, stage: &str, timeout: Duration) -> bool {
+        let key = (run_id.to_string(), stage.to_string());
+        let acked = async {
+            loop {
+                // Register before checking so an ack between the two is not missed.
+                let notified = self.notify.notified();
+                if self.acked.lock().remove(&key) {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, acked).await.is_ok()
+    }
+
+    /// Waits for a stage that returned successfully to be acknowledged,
+    /// emitting `stage.awaiting_ack`, then `stage.acknowledged` or
+    /// `stage.ack_timeout`.
+    pub(crate) async fn await_stage(
+        &self,
+        ctx: &dyn ExecutionContext,
+        run_id: &str,
+        stage: &str,
+        timeout: Duration,
+    ) -> Result<(), StageflowError> {
+        let timeout_ms = timeout.as_secs_f64() * 1000.0;
+        ctx.try_emit_event(
+            "stage.awaiting_ack",
+            Some(serde_json::json!({
+                "stage": stage,
+                "timeout_ms": timeout_ms,
+            })),
+        );
+        if !self.wait(run_id, stage, timeout).await {
+            ctx.try_emit_event(
+                "stage.ack_timeout",
+                Some(serde_json::json!({
+                    "stage": stage,
+                    "timeout_ms": timeout_ms,
+                })),
+            );
+            return Err(StageflowError::StageExecution(format!(
+                "Stage '{stage}' was not acknowledged within {timeout_ms} ms"
+            )));
+        }
+        ctx.try_emit_event("stage.acknowledged", Some(serde_json::json!({ "stage": stage })));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_wait_consumes_acks() {
+        let registry = Arc::new(StageAckRegistry::new());
+        registry.ack("run", "early");
+        assert!(registry.wait("run", "early", Duration::ZERO).await);
+        assert!(!registry.is_acked("run", "early"));
+
+        let acker = registry.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            acker.ack("run", "late");
+        });
+        assert!(registry.wait("run", "late", Duration::from_secs(5)).await);
+        assert!(!registry.wait("other", "late", Duration::from_millis(5)).await);
+    }
+}
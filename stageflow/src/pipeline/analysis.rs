@@ -0,0 +1,312 @@
+//! Critical path and bottleneck analysis of stage graphs.
+//!
+//! The same analysis runs before a run, on the duration hints set with
+//! [`StageSpec::with_estimated_duration`](super::StageSpec::with_estimated_duration),
+//! and after it, on the durations recorded in a [`UnifiedExecutionResult`].
+//! It reports the chain of stages that bounds the run's duration, how many
+//! stages could ever run at once, and how much each stage could be delayed
+//! without delaying the run.
+
+use super::{StageGraph, UnifiedExecutionResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Schedule of one stage when every stage starts as soon as its
+/// dependencies finish.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    /// Duration used for the stage, in milliseconds.
+    pub duration_ms: f64,
+    /// Earliest time the stage can start, relative to the run start.
+    pub earliest_start_ms: f64,
+    /// Latest time the stage can start without delaying the run.
+    pub latest_start_ms: f64,
+    /// How long the stage can be delayed without delaying the run.
+    pub slack_ms: f64,
+}
+
+/// Result of analysing a stage graph with a set of stage durations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphAnalysis {
+    /// Stages on the longest chain of dependencies, first to last.
+    pub critical_path: Vec<String>,
+    /// Summed duration of the critical path, the shortest possible run time.
+    pub critical_path_ms: f64,
+    /// Largest number of stages with no dependency between them, i.e. the
+    /// most stages that can ever run at once.
+    pub max_parallelism: usize,
+    /// Schedule and slack per stage.
+    pub stages: BTreeMap<String, StageTiming>,
+}
+
+impl GraphAnalysis {
+    /// Returns the slack of `stage` in milliseconds.
+    #[must_use]
+    pub fn slack_ms(&self, stage: &str) -> Option<f64> {
+        self.stages.get(stage).map(|timing| timing.slack_ms)
+    }
+
+    /// Returns true if `stage` is on the critical path.
+    #[must_use]
+    pub fn is_critical(&self, stage: &str) -> bool {
+        self.critical_path.iter().any(|s| s == stage)
+    }
+}
+
+impl StageGraph {
+    /// Returns each stage's duration hint in milliseconds; stages without a
+    /// hint count as zero.
+    #[must_use]
+    pub fn estimated_durations(&self) -> HashMap<String, f64> {
+        self.stage_specs()
+            .iter()
+            .map(|(name, spec)| {
+                let ms = spec.estimated_duration.map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+                (name.clone(), ms)
+            })
+            .collect()
+    }
+
+    /// Analyses the graph using the stages' duration hints.
+    #[must_use]
+    pub fn analyze_estimates(&self) -> GraphAnalysis {
+        self.analyze(&self.estimated_durations())
+    }
+
+    /// Analyses the graph using the durations a run recorded under the
+    /// `duration_ms` output metadata key.
+    ///
+    /// Stages that did not run, or ran without recording a duration, count
+    /// as zero, so the critical path ends at the last stage that ran.
+    #[must_use]
+    pub fn analyze_result(&self, result: &UnifiedExecutionResult) -> GraphAnalysis {
+        let durations = result
+            .outputs
+            .iter()
+            .filter_map(|(name, output)| {
+                let ms = output.metadata.get("duration_ms")?.as_f64()?;
+                Some((name.clone(), ms))
+            })
+            .collect();
+        self.analyze(&durations)
+    }
+
+    /// Returns the critical path for the given durations in milliseconds.
+    #[must_use]
+    pub fn critical_path(&self, durations: &HashMap<String, f64>) -> Vec<String> {
+        self.analyze(durations).critical_path
+    }
+
+    /// Analyses the graph with the given durations in milliseconds.
+    ///
+    /// Stages missing from `durations` count as zero. When several chains
+    /// are equally long, the one ending earliest in execution order wins.
+    #[must_use]
+    pub fn analyze(&self, durations: &HashMap<String, f64>) -> GraphAnalysis {
+        let order = self.execution_order();
+        let position: HashMap<&str, usize> = order.iter().enumerate().map(|(i, s)| (s.as_str(), i)).collect();
+        let duration = |stage: &str| durations.get(stage).copied().unwrap_or(0.0).max(0.0);
+        let dependencies = |stage: &str| -> Vec<&str> {
+            let mut deps: Vec<&str> = self
+                .stage_spec(stage)
+                .into_iter()
+                .flat_map(|spec| spec.dependencies.iter().map(String::as_str))
+                .filter(|dep| position.contains_key(dep))
+                .collect();
+            deps.sort_by_key(|dep| position[dep]);
+            deps
+        };
+
+        // Forward pass: earliest start and the dependency that sets it
+        let mut earliest: HashMap<&str, f64> = HashMap::new();
+        let mut blocker: HashMap<&str, &str> = HashMap::new();
+        for stage in order {
+            let mut start = 0.0;
+            for dep in dependencies(stage) {
+                let end = earliest[dep] + duration(dep);
+                if end > start || (!blocker.contains_key(stage.as_str()) && end >= start) {
+                    start = end;
+                    blocker.insert(stage.as_str(), dep);
+                }
+            }
+            earliest.insert(stage.as_str(), start);
+        }
+
+        let mut end_stage: Option<(&str, f64)> = None;
+        for stage in order {
+            let end = earliest[stage.as_str()] + duration(stage);
+            if end_stage.map_or(true, |(_, best)| end > best) {
+                end_stage = Some((stage.as_str(), end));
+            }
+        }
+        let critical_path_ms = end_stage.map_or(0.0, |(_, end)| end);
+        let mut critical_path = Vec::new();
+        let mut current = end_stage.map(|(stage, _)| stage);
+        while let Some(stage) = current {
+            critical_path.push(stage.to_string());
+            current = blocker.get(stage).copied();
+        }
+        critical_path.reverse();
+
+        // Backward pass: latest finish that keeps the run at its minimum
+        let mut latest_finish: HashMap<&str, f64> = HashMap::new();
+        for stage in order.iter().rev() {
+            let finish = latest_finish.get(stage.as_str()).copied().unwrap_or(critical_path_ms);
+            let latest_start = finish - duration(stage);
+            for dep in dependencies(stage) {
+                let entry = latest_finish.entry(dep).or_insert(latest_start);
+                *entry = entry.min(latest_start);
+            }
+            latest_finish.insert(stage.as_str(), finish);
+        }
+
+        let stages = order
+            .iter()
+            .map(|stage| {
+                let duration_ms = duration(stage);
+                let earliest_start_ms = earliest[stage.as_str()];
+                let latest_start_ms = latest_finish[stage.as_str()] - duration_ms;
+                let timing = StageTiming {
+                    duration_ms,
+                    earliest_start_ms,
+                    latest_start_ms,
+                    slack_ms: (latest_start_ms - earliest_start_ms).max(0.0),
+                };
+                (stage.clone(), timing)
+            })
+            .collect();
+
+        GraphAnalysis {
+            critical_path,
+            critical_path_ms,
+            max_parallelism: self.max_parallelism(),
+            stages,
+        }
+    }
+
+    /// Returns the largest number of stages with no dependency path between
+    /// any two of them.
+    ///
+    /// This is the graph's width: the most stages that can be in flight at
+    /// once, whatever their durations.
+    #[must_use]
+    pub fn max_parallelism(&self) -> usize {
+        let order = self.execution_order();
+        let index: HashMap<&str, usize> = order.iter().enumerate().map(|(i, s)| (s.as_str(), i)).collect();
+
+        // ancestors[i] holds every stage stage i transitively depends on
+        let mut ancestors: Vec<HashSet<usize>> = vec![HashSet::new(); order.len()];
+        for (i, stage) in order.iter().enumerate() {
+            let deps: Vec<usize> = self
+                .stage_spec(stage)
+                .into_iter()
+                .flat_map(|spec| spec.dependencies.iter())
+                .filter_map(|dep| index.get(dep.as_str()).copied())
+                .collect();
+            for dep in deps {
+                let inherited: Vec<usize> = ancestors[dep].iter().copied().collect();
+                ancestors[i].insert(dep);
+                ancestors[i].extend(inherited);
+            }
+        }
+
+        // Dilworth: width = stages - maximum matching in the reachability graph
+        let mut matched_to: Vec<Option<usize>> = vec![None; order.len()];
+        let mut matching = 0;
+        for stage in 0..order.len() {
+            let mut seen = vec![false; order.len()];
+            if augment(stage, &ancestors, &mut matched_to, &mut seen) {
+                matching += 1;
+            }
+        }
+        order.len() - matching
+    }
+}
+
+/// Tries to match `stage` to one of its ancestors, re-matching others along
+/// an augmenting path.
+fn augment(
+    stage: usize,
+    ancestors: &[HashSet<usize>],
+    matched_to: &mut [Option<usize>],
+    seen: &mut [bool],
+) -> bool {
+    for &ancestor in &ancestors[stage] {
+        if seen[ancestor] {
+            continue;
+        }
+        seen[ancestor] = true;
+        if matched_to[ancestor].map_or(true, |other| augment(other, ancestors, matched_to, seen)) {
+            matched_to[ancestor] = Some(stage);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StageOutput;
+    use crate::pipeline::{PipelineBuilder, StageSpec};
+    use crate::stages::NoOpStage;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn spec(name: &str, deps: &[&str], estimate_ms: u64) -> StageSpec {
+        StageSpec::new(name, Arc::new(NoOpStage::new(name)))
+            .with_dependencies(deps.iter().copied())
+            .with_estimated_duration(Duration::from_millis(estimate_ms))
+    }
+
+    fn graph() -> StageGraph {
+        let mut builder = PipelineBuilder::new("analysis");
+        for stage in [
+            spec("fetch", &[], 10),
+            spec("quick", &["fetch"], 5),
+            spec("slow", &["fetch"], 40),
+            spec("side", &[], 20),
+            spec("merge", &["quick", "slow"], 3),
+        ] {
+            builder.add_stage_spec(stage).unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_estimated_critical_path_slack_and_parallelism() {
+        let analysis = graph().analyze_estimates();
+        assert_eq!(analysis.critical_path, vec!["fetch", "slow", "merge"]);
+        assert!((analysis.critical_path_ms - 53.0).abs() < 1e-9);
+        assert_eq!(analysis.max_parallelism, 3);
+        assert_eq!(analysis.slack_ms("quick"), Some(35.0));
+        assert_eq!(analysis.slack_ms("side"), Some(33.0));
+        assert_eq!(analysis.slack_ms("slow"), Some(0.0));
+        assert!(!analysis.is_critical("side"));
+    }
+
+    #[test]
+    fn test_result_analysis_uses_recorded_durations() {
+        let graph = graph();
+        let timed = |ms: f64| StageOutput::ok_empty().add_metadata("duration_ms", serde_json::json!(ms));
+        let result = UnifiedExecutionResult {
+            outputs: HashMap::from([
+                ("fetch".to_string(), timed(10.0)),
+                ("quick".to_string(), timed(90.0)),
+                ("slow".to_string(), timed(40.0)),
+                ("side".to_string(), timed(1.0)),
+            ]),
+            duration_ms: 100.0,
+            success: false,
+            error: None,
+            cancelled: true,
+            cancel_reason: Some("stop".to_string()),
+            tool_transcript: crate::tools::ToolTranscript::default(),
+            enrichment_cache_stats: None,
+        };
+        let analysis = graph.analyze_result(&result);
+        assert_eq!(analysis.critical_path, vec!["fetch", "quick"]);
+        assert!((analysis.critical_path_ms - 100.0).abs() < 1e-9);
+        assert_eq!(graph.critical_path(&HashMap::new()), vec!["fetch"]);
+    }
+}
@@ -0,0 +1,475 @@
+//! Run-level safety limits.
+//!
+//! A [`RunBudget`] caps what a whole run may consume, including its
+//! subpipelines: guard retries, tool calls, subpipeline nesting and wall-clock
+//! time. Limits are checked by the executor, the tool executor and the
+//! subpipeline spawner; exceeding one cancels the run with
+//! [`CancelReason::BudgetExceeded`].
+//!
+//! A [`RetryBudget`] caps the retries of every stage of a run together,
+//! by count and by time spent retrying. Unlike a [`RunBudget`] it does not
+//! cancel the run: once it is spent, further retries are refused and the
+//! failed outputs stand.
+
+use crate::context::PipelineContext;
+use serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+/// Limits applied to a run and all of its subpipelines.
+///
+/// Every limit is optional; an empty budget enforces nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunBudget {
+    /// Maximum number of stage retries across the run.
+    pub max_stage_retries: Option<u32>,
+    /// Maximum number of tool calls across the run.
+    pub max_tool_calls: Option<u32>,
+    /// Maximum subpipeline nesting depth.
+    pub max_subpipeline_depth: Option<u32>,
+    /// Maximum wall-clock time for the run.
+    pub max_wall_clock: Option<Duration>,
+}
+
+impl RunBudget {
+    /// Creates a budget with no limits.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the total number of stage retries.
+    #[must_use]
+    pub fn with_max_stage_retries(mut self, max: u32) -> Self {
+        self.max_stage_retries = Some(max);
+        self
+    }
+
+    /// Limits the total number of tool calls.
+    #[must_use]
+    pub fn with_max_tool_calls(mut self, max: u32) -> Self {
+        self.max_tool_calls = Some(max);
+        self
+    }
+
+    /// Limits how deeply subpipelines may nest.
+    #[must_use]
+    pub fn with_max_subpipeline_depth(mut self, max: u32) -> Self {
+        self.max_subpipeline_depth = Some(max);
+        self
+    }
+
+    /// Limits the run's wall-clock time.
+    #[must_use]
+    pub fn with_max_wall_clock(mut self, max: Duration) -> Self {
+        self.max_wall_clock = Some(max);
+        self
+    }
+
+    /// Returns true if no limit is set.
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A limit of a [`RunBudget`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    /// [`RunBudget::max_stage_retries`].
+    StageRetries,
+    /// [`RunBudget::max_tool_calls`].
+    ToolCalls,
+    /// [`RunBudget::max_subpipeline_depth`].
+    SubpipelineDepth,
+    /// [`RunBudget::max_wall_clock`], measured in milliseconds.
+    WallClock,
+}
+
+impl BudgetLimit {
+    /// Returns the limit name.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::StageRetries => "stage_retries",
+            Self::ToolCalls => "tool_calls",
+            Self::SubpipelineDepth => "subpipeline_depth",
+            Self::WallClock => "wall_clock",
+        }
+    }
+}
+
+/// Why a run was cancelled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CancelReason {
+    /// A stage or caller cancelled the run.
+    Requested {
+        /// The reason given.
+        message: String,
+    },
+    /// The run exceeded a limit of its [`RunBudget`].
+    BudgetExceeded {
+        /// The limit that was exceeded.
+        limit: BudgetLimit,
+        /// The configured maximum.
+        max: u64,
+        /// The amount the run attempted to use.
+        attempted: u64,
+    },
+}
+
+impl CancelReason {
+    /// Creates a reason for a requested cancellation.
+    #[must_use]
+    pub fn requested(message: impl Into<String>) -> Self {
+        Self::Requested {
+            message: message.into(),
+        }
+    }
+
+    /// Returns the exceeded limit, if the budget caused the cancellation.
+    #[must_use]
+    pub fn budget_limit(&self) -> Option<BudgetLimit> {
+        match self {
+            Self::BudgetExceeded { limit, .. } => Some(*limit),
+            Self::Requested { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Requested { message } => f.write_str(message),
+            Self::BudgetExceeded {
+                limit,
+                max,
+                attempted,
+            } => write!(
+                f,
+                "Run budget exceeded: {} limit is {max}, attempted {attempted}",
+                limit.as_str()
+            ),
+        }
+    }
+}
+
+/// Resources a run has used against its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    /// Stage retries scheduled.
+    pub stage_retries: u32,
+    /// Tool calls admitted.
+    pub tool_calls: u32,
+    /// Deepest subpipeline level reached.
+    pub subpipeline_depth: u32,
+    /// Time since the run started.
+    pub elapsed: Duration,
+}
+
+/// Counts usage against a budget; shared by a run and its subpipelines.
+#[derive(Debug)]
+pub(crate) struct BudgetTracker {
+    budget: RunBudget,
+    started_at: Instant,
+    stage_retries: AtomicU32,
+    tool_calls: AtomicU32,
+    subpipeline_depth: AtomicU32,
+}
+
+impl BudgetTracker {
+    pub(crate) fn new(budget: RunBudget) -> Self {
+        Self {
+            budget,
+            started_at: Instant::now(),
+            stage_retries: AtomicU32::new(0),
+            tool_calls: AtomicU32::new(0),
+            subpipeline_depth: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn budget(&self) -> &RunBudget {
+        &self.budget
+    }
+
+    /// Counts a stage retry, refusing it if the limit would be exceeded.
+    pub(crate) fn reserve_retry(&self) -> Result<(), CancelReason> {
+        reserve(&self.stage_retries, self.budget.max_stage_retries, BudgetLimit::StageRetries)
+    }
+
+    /// Counts a tool call, refusing it if the limit would be exceeded.
+    pub(crate) fn reserve_tool_call(&self) -> Result<(), CancelReason> {
+        reserve(&self.tool_calls, self.budget.max_tool_calls, BudgetLimit::ToolCalls)
+    }
+
+    /// Records entering a subpipeline at `depth`.
+    pub(crate) fn enter_depth(&self, depth: u32) -> Result<(), CancelReason> {
+        if let Some(max) = self.budget.max_subpipeline_depth {
+            if depth > max {
+                return Err(exceeded(BudgetLimit::SubpipelineDepth, max, depth));
+            }
+        }
+        self.subpipeline_depth.fetch_max(depth, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns when the wall-clock limit runs out, if one is set.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.budget.max_wall_clock.map(|max| self.started_at + max)
+    }
+
+    /// Returns the wall-clock reason once the deadline has passed.
+    pub(crate) fn check_wall_clock(&self) -> Result<(), CancelReason> {
+        match self.budget.max_wall_clock {
+            Some(max) if self.started_at.elapsed() >= max => Err(CancelReason::BudgetExceeded {
+                limit: BudgetLimit::WallClock,
+                max: millis(max),
+                attempted: millis(self.started_at.elapsed()),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn usage(&self) -> BudgetUsage {
+        BudgetUsage {
+            stage_retries: self.stage_retries.load(Ordering::SeqCst),
+            tool_calls: self.tool_calls.load(Ordering::SeqCst),
+            subpipeline_depth: self.subpipeline_depth.load(Ordering::SeqCst),
+            elapsed: self.started_at.elapsed(),
+        }
+    }
+}
+
+/// Limits on the retries of all stages of a run together.
+///
+/// Each retry policy still decides whether a stage is worth retrying; the
+/// budget decides whether the run can afford it. Every limit is optional.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryBudget {
+    /// Maximum number of retries across the run.
+    pub max_retries: Option<u32>,
+    /// Maximum time spent retrying across the run: backoff delays plus the
+    /// retried attempts themselves.
+    pub max_retry_time: Option<Duration>,
+}
+
+impl RetryBudget {
+    /// Creates a budget with no limits.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the total number of retries.
+    #[must_use]
+    pub fn with_max_retries(mut self, max: u32) -> Self {
+        self.max_retries = Some(max);
+        self
+    }
+
+    /// Limits the total time spent retrying.
+    #[must_use]
+    pub fn with_max_retry_time(mut self, max: Duration) -> Self {
+        self.max_retry_time = Some(max);
+        self
+    }
+
+    /// Returns true if no limit is set.
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Why a [`RetryBudget`] refused a retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RetryBudgetExhausted {
+    /// The run already used all its retries.
+    #[error("Retry budget exhausted: {max} retries used")]
+    Retries {
+        /// The configured maximum.
+        max: u32,
+    },
+    /// The retry would take the run past its retry time.
+    #[error("Retry budget exhausted: {} ms of {} ms retry time used", millis(*spent), millis(*max))]
+    RetryTime {
+        /// The configured maximum.
+        max: Duration,
+        /// Time already spent retrying.
+        spent: Duration,
+    },
+}
+
+impl RetryBudgetExhausted {
+    /// Returns the name of the exhausted limit.
+    #[must_use]
+    pub fn limit(&self) -> &'static str {
+        match self {
+            Self::Retries { .. } => "retries",
+            Self::RetryTime { .. } => "retry_time",
+        }
+    }
+}
+
+/// Retries a run has used against its [`RetryBudget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryBudgetUsage {
+    /// Retries granted.
+    pub retries: u32,
+    /// Retries refused because the budget was spent.
+    pub refused: u32,
+    /// Time spent retrying.
+    pub retry_time: Duration,
+}
+
+/// Counts retries against a [`RetryBudget`]; shared by a run and its
+/// subpipelines.
+#[derive(Debug)]
+pub(crate) struct RetryBudgetTracker {
+    budget: RetryBudget,
+    usage: Mutex<RetryBudgetUsage>,
+}
+
+impl RetryBudgetTracker {
+    pub(crate) fn new(budget: RetryBudget) -> Self {
+        Self {
+            budget,
+            usage: Mutex::new(RetryBudgetUsage::default()),
+        }
+    }
+
+    pub(crate) fn budget(&self) -> &RetryBudget {
+        &self.budget
+    }
+
+    /// Grants a retry starting after `delay`, charging the delay, or
+    /// refuses it if the budget is spent.
+    pub(crate) fn reserve(&self, delay: Duration) -> Result<(), RetryBudgetExhausted> {
+        let mut usage = self.usage.lock();
+        let refused = match (self.budget.max_retries, self.budget.max_retry_time) {
+            (Some(max), _) if usage.retries >= max => Some(RetryBudgetExhausted::Retries { max }),
+            (_, Some(max)) if usage.retry_time + delay > max => Some(RetryBudgetExhausted::RetryTime {
+                max,
+                spent: usage.retry_time,
+            }),
+            _ => None,
+        };
+        if let Some(refused) = refused {
+            usage.refused += 1;
+            return Err(refused);
+        }
+        usage.retries += 1;
+        usage.retry_time += delay;
+        Ok(())
+    }
+
+    /// Charges time spent running a retried attempt.
+    pub(crate) fn record(&self, elapsed: Duration) {
+        self.usage.lock().retry_time += elapsed;
+    }
+
+    pub(crate) fn usage(&self) -> RetryBudgetUsage {
+        *self.usage.lock()
+    }
+}
+
+/// Awaits `fut`, giving up when the run's wall-clock limit runs out.
+///
+/// Returns `None` on timeout, after cancelling the run.
+pub(crate) async fn until_deadline<F: std::future::Future>(
+    ctx: &PipelineContext,
+    fut: F,
+) -> Option<F::Output> {
+    let Some(deadline) = ctx.budget_deadline() else {
+        return Some(fut.await);
+    };
+    if let Ok(output) = tokio::time::timeout_at(deadline.into(), fut).await {
+        return Some(output);
+    }
+    let _ = ctx.check_wall_clock();
+    None
+}
+
+fn reserve(counter: &AtomicU32, max: Option<u32>, limit: BudgetLimit) -> Result<(), CancelReason> {
+    let Some(max) = max else {
+        counter.fetch_add(1, Ordering::SeqCst);
+        return Ok(());
+    };
+    counter
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+            (used < max).then_some(used + 1)
+        })
+        .map(drop)
+        .map_err(|used| exceeded(limit, max, used.saturating_add(1)))
+}
+
+fn exceeded(limit: BudgetLimit, max: u32, attempted: u32) -> CancelReason {
+    CancelReason::BudgetExceeded {
+        limit,
+        max: u64::from(max),
+        attempted: u64::from(attempted),
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_refuses_past_limits() {
+        let tracker = BudgetTracker::new(
+            RunBudget::new()
+                .with_max_tool_calls(2)
+                .with_max_subpipeline_depth(1),
+        );
+        assert!(tracker.reserve_tool_call().is_ok());
+        assert!(tracker.reserve_tool_call().is_ok());
+        let reason = tracker.reserve_tool_call().unwrap_err();
+        assert_eq!(
+            reason,
+            CancelReason::BudgetExceeded {
+                limit: BudgetLimit::ToolCalls,
+                max: 2,
+                attempted: 3
+            }
+        );
+        assert!(reason.to_string().contains("tool_calls limit is 2"));
+
+        assert!(tracker.reserve_retry().is_ok());
+        assert!(tracker.enter_depth(1).is_ok());
+        assert_eq!(tracker.enter_depth(2).unwrap_err().budget_limit(), Some(BudgetLimit::SubpipelineDepth));
+        assert!(tracker.check_wall_clock().is_ok());
+
+        let usage = tracker.usage();
+        assert_eq!((usage.tool_calls, usage.stage_retries, usage.subpipeline_depth), (2, 1, 1));
+    }
+
+    #[test]
+    fn test_retry_budget_refuses_past_limits() {
+        let tracker = RetryBudgetTracker::new(RetryBudget::new().with_max_retries(2));
+        assert!(tracker.reserve(Duration::ZERO).is_ok());
+        assert!(tracker.reserve(Duration::ZERO).is_ok());
+        let refused = tracker.reserve(Duration::ZERO).unwrap_err();
+        assert_eq!(refused, RetryBudgetExhausted::Retries { max: 2 });
+        assert_eq!(refused.limit(), "retries");
+
+        let tracker = RetryBudgetTracker::new(RetryBudget::new().with_max_retry_time(Duration::from_millis(100)));
+        assert!(tracker.reserve(Duration::from_millis(40)).is_ok());
+        tracker.record(Duration::from_millis(30));
+        let refused = tracker.reserve(Duration::from_millis(40)).unwrap_err();
+        assert_eq!(refused.limit(), "retry_time");
+        assert!(refused.to_string().contains("70 ms of 100 ms"));
+        assert!(tracker.reserve(Duration::from_millis(30)).is_ok());
+
+        let usage = tracker.usage();
+        assert_eq!((usage.retries, usage.refused), (2, 1));
+        assert_eq!(usage.retry_time, Duration::from_millis(100));
+    }
+}
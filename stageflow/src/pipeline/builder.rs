@@ -1,8 +1,9 @@
 //! Pipeline builder with validation.
 
-use super::{StageGraph, StageSpec};
+use super::{FanOut, LoopGroup, PipelineManifest, RunBudget, StageGraph, StageManifest, StageSpec};
+use crate::contracts::{codes, ContractRef, ContractRegistry, REGISTRY};
 use crate::core::StageKind;
-use crate::errors::{ContractErrorInfo, CycleDetectedError, PipelineValidationError};
+use crate::errors::{ContractErrorInfo, CycleDetectedError, PipelineValidationError, StageflowError};
 use crate::stages::Stage;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -16,6 +17,12 @@ pub struct PipelineBuilder {
     stages: HashMap<String, StageSpec>,
     /// Insertion order for stages.
     stage_order: Vec<String>,
+    /// Body stage names of loop groups, reserved so events stay unambiguous.
+    loop_members: HashSet<String>,
+    /// Registry used to check declared contracts; the global one if unset.
+    contract_registry: Option<Arc<ContractRegistry>>,
+    /// Run-level limits applied when the pipeline executes.
+    budget: RunBudget,
 }
 
 impl PipelineBuilder {
@@ -26,9 +33,29 @@ impl PipelineBuilder {
             name: name.into(),
             stages: HashMap::new(),
             stage_order: Vec::new(),
+            loop_members: HashSet::new(),
+            contract_registry: None,
+            budget: RunBudget::default(),
         }
     }
 
+    /// Sets the registry used to check stage contracts at build time.
+    #[must_use]
+    pub fn with_contract_registry(mut self, registry: Arc<ContractRegistry>) -> Self {
+        self.contract_registry = Some(registry);
+        self
+    }
+
+    /// Sets run-level limits enforced whenever the pipeline executes.
+    ///
+    /// A budget set on the [`PipelineContext`](crate::context::PipelineContext)
+    /// takes precedence.
+    #[must_use]
+    pub fn with_budget(mut self, budget: RunBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
     /// Adds a stage to the pipeline.
     ///
     /// # Errors
@@ -58,6 +85,21 @@ impl PipelineBuilder {
         // Validate stage itself
         spec.validate()?;
 
+        if self.loop_members.contains(&spec.name) {
+            return Err(PipelineValidationError::new(format!(
+                "Stage '{}' is already a body stage of a loop group",
+                spec.name
+            ))
+            .with_stages(vec![spec.name.clone()])
+            .with_error_info(
+                ContractErrorInfo::new(
+                    "CONTRACT-004-LOOP_NAME",
+                    format!("Stage name '{}' is used inside a loop group", spec.name),
+                )
+                .with_fix_hint("Give loop body stages names that are unique across the pipeline."),
+            ));
+        }
+
         // Check for missing dependencies
         for dep in &spec.dependencies {
             if !self.stages.contains_key(dep) {
@@ -85,6 +127,67 @@ impl PipelineBuilder {
         Ok(())
     }
 
+    /// Adds a loop group as a single stage.
+    ///
+    /// The loop body repeats inside the stage, so the outer graph stays
+    /// acyclic. Body stage names must be unique across the pipeline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the loop is invalid, a body stage name is already
+    /// in use, or a dependency is missing.
+    pub fn loop_group(
+        mut self,
+        group: LoopGroup,
+        dependencies: &[&str],
+    ) -> Result<Self, PipelineValidationError> {
+        let members = group.body_stage_names();
+        let taken: Vec<String> = members
+            .iter()
+            .filter(|name| self.stages.contains_key(*name) || self.loop_members.contains(*name))
+            .cloned()
+            .collect();
+        if !taken.is_empty() {
+            return Err(PipelineValidationError::new(format!(
+                "Loop '{}' body stages are already defined: {}",
+                group.name(),
+                taken.join(", ")
+            ))
+            .with_stages(taken)
+            .with_error_info(
+                ContractErrorInfo::new(
+                    "CONTRACT-004-LOOP_NAME",
+                    format!("Loop '{}' reuses existing stage names", group.name()),
+                )
+                .with_fix_hint("Give loop body stages names that are unique across the pipeline."),
+            ));
+        }
+
+        let name = group.name().to_string();
+        let runner: Arc<dyn Stage> = Arc::new(group.build()?);
+        let spec = StageSpec::new(name, runner)
+            .with_dependencies(dependencies.iter().map(|s| (*s).to_string()));
+        self.add_stage_spec(spec)?;
+        self.loop_members.extend(members);
+        Ok(self)
+    }
+
+    /// Adds a fan-out as a single stage.
+    ///
+    /// The fan-out expands into one template copy per item while it runs,
+    /// so the outer graph is unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fan-out is invalid or a dependency is missing.
+    pub fn fan_out(mut self, fan_out: FanOut, dependencies: &[&str]) -> Result<Self, PipelineValidationError> {
+        let name = fan_out.name().to_string();
+        let runner: Arc<dyn Stage> = Arc::new(fan_out.build()?);
+        let spec = StageSpec::new(name, runner).with_dependencies(dependencies.iter().map(|s| (*s).to_string()));
+        self.add_stage_spec(spec)?;
+        Ok(self)
+    }
+
     /// Composes this builder with another.
     ///
     /// # Errors
@@ -92,6 +195,13 @@ impl PipelineBuilder {
     /// Returns an error if there are conflicting stage definitions.
     pub fn compose(mut self, other: Self) -> Result<Self, PipelineValidationError> {
         self.name = format!("{}+{}", self.name, other.name);
+        self.loop_members.extend(other.loop_members);
+        if self.contract_registry.is_none() {
+            self.contract_registry = other.contract_registry;
+        }
+        if self.budget.is_unlimited() {
+            self.budget = other.budget;
+        }
 
         for (name, other_spec) in other.stages {
             if let Some(existing) = self.stages.get(&name) {
@@ -122,19 +232,41 @@ impl PipelineBuilder {
 
     /// Builds the pipeline.
     ///
+    /// Stages that consume a contract are checked against the stage that
+    /// produces it: both versions must be registered, and the produced
+    /// version must have no breaking changes relative to the consumed one.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the builder has no stages.
+    /// Returns an error if the builder has no stages or a consumed contract
+    /// is missing, unregistered, or incompatible.
     pub fn build(self) -> Result<StageGraph, PipelineValidationError> {
         if self.stages.is_empty() {
-            return Err(PipelineValidationError::new("Pipeline has no stages")
-                .with_error_info(
-                    ContractErrorInfo::new("CONTRACT-004-EMPTY", "Cannot build an empty pipeline")
-                        .with_fix_hint("Add at least one stage to the pipeline before building."),
-                ));
+            return Err(empty_pipeline_error());
         }
 
-        Ok(StageGraph::new(self.name, self.stages, self.stage_order))
+        if let Some(err) = self.contract_violations().into_iter().next() {
+            return Err(err);
+        }
+
+        Ok(StageGraph::new(self.name, self.stages, self.stage_order).with_budget(self.budget))
+    }
+
+    /// Runs every check [`build`](Self::build) performs without building.
+    ///
+    /// Unlike `build`, which stops at the first problem, this reports all of
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the failed checks, combined into
+    /// [`StageflowError::Multiple`] when there is more than one.
+    pub fn preflight(&self) -> Result<(), StageflowError> {
+        let mut errors = self.contract_violations();
+        if self.stages.is_empty() {
+            errors.push(empty_pipeline_error());
+        }
+        StageflowError::from_errors(errors).map_or(Ok(()), Err)
     }
 
     /// Returns the pipeline name.
@@ -149,6 +281,102 @@ impl PipelineBuilder {
         self.stages.len()
     }
 
+    /// Returns the stage names in insertion order.
+    #[must_use]
+    pub fn stage_names(&self) -> Vec<String> {
+        self.stage_order.clone()
+    }
+
+    /// Describes the stages added so far, for
+    /// [`PipelineLinter`](super::PipelineLinter).
+    #[must_use]
+    pub fn manifest(&self) -> PipelineManifest {
+        PipelineManifest {
+            name: self.name.clone(),
+            stages: self
+                .stage_order
+                .iter()
+                .filter_map(|name| self.stages.get(name))
+                .map(StageManifest::from)
+                .collect(),
+            guard_retries: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// Checks every consumed contract against its producer, returning all
+    /// violations.
+    fn contract_violations(&self) -> Vec<PipelineValidationError> {
+        let mut violations = Vec::new();
+        let registry = self.contract_registry.as_ref().unwrap_or(&*REGISTRY);
+
+        for consumer in self.stage_order.iter().filter_map(|name| self.stages.get(name)) {
+            for expected in &consumer.consumes {
+                let producers: Vec<(&String, &ContractRef)> = self
+                    .stage_order
+                    .iter()
+                    .filter_map(|name| Some((name, self.stages.get(name)?.produces.as_ref()?)))
+                    .filter(|(_, produced)| produced.name == expected.name)
+                    .collect();
+                if producers.is_empty() {
+                    violations.push(contract_error(
+                        format!(
+                            "Stage '{}' consumes contract {expected} but no stage produces it",
+                            consumer.name
+                        ),
+                        vec![consumer.name.clone()],
+                        codes::MISSING_PRODUCER,
+                        "Declare the contract on the producing stage with StageSpec::with_produces.",
+                    ));
+                    continue;
+                }
+
+                for (producer, produced) in producers {
+                    let stages = vec![producer.clone(), consumer.name.clone()];
+                    let unregistered: Vec<PipelineValidationError> = [expected, produced]
+                        .into_iter()
+                        .filter(|contract| registry.get(&contract.name, &contract.version).is_none())
+                        .map(|contract| {
+                            contract_error(
+                                format!("Contract {contract} is not registered"),
+                                stages.clone(),
+                                codes::UNREGISTERED,
+                                "Register the contract schema with ContractRegistry::register before building.",
+                            )
+                        })
+                        .collect();
+                    if !unregistered.is_empty() {
+                        violations.extend(unregistered);
+                        continue;
+                    }
+                    if produced.version == expected.version {
+                        continue;
+                    }
+
+                    // Both versions are registered, so the diff cannot fail
+                    let Ok(report) =
+                        registry.diff(&expected.name, &expected.version, &produced.version)
+                    else {
+                        continue;
+                    };
+                    if !report.is_compatible() {
+                        violations.push(contract_error(
+                            format!(
+                                "Stage '{producer}' produces {produced} which is incompatible with {expected} consumed by '{}': {}",
+                                consumer.name,
+                                report.breaking_changes.join("; ")
+                            ),
+                            stages,
+                            codes::SCHEMA_MISMATCH,
+                            "Update the consumer to the produced contract version or restore the removed fields.",
+                        ));
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+
     /// Detects cycles in the dependency graph.
     fn detect_cycles(&self) -> Result<(), CycleDetectedError> {
         let mut visited = HashSet::new();
@@ -203,6 +431,34 @@ fn specs_compatible(a: &StageSpec, b: &StageSpec) -> bool {
     a.dependencies == b.dependencies
         && a.conditional == b.conditional
         && a.kind == b.kind
+        && a.produces == b.produces
+        && a.consumes == b.consumes
+        && a.idempotent == b.idempotent
+        && a.idempotency_key == b.idempotency_key
+        && a.context_access == b.context_access
+        && a.resource_limits == b.resource_limits
+        && a.stall_policy == b.stall_policy
+}
+
+/// Builds the error for a pipeline without stages.
+fn empty_pipeline_error() -> PipelineValidationError {
+    PipelineValidationError::new("Pipeline has no stages").with_error_info(
+        ContractErrorInfo::new("CONTRACT-004-EMPTY", "Cannot build an empty pipeline")
+            .with_fix_hint("Add at least one stage to the pipeline before building."),
+    )
+}
+
+/// Builds a contract validation error.
+fn contract_error(
+    message: String,
+    stages: Vec<String>,
+    code: &str,
+    fix_hint: &str,
+) -> PipelineValidationError {
+    let info = ContractErrorInfo::new(code, message.clone()).with_fix_hint(fix_hint);
+    PipelineValidationError::new(message)
+        .with_stages(stages)
+        .with_error_info(info)
 }
 
 #[cfg(test)]
@@ -322,4 +578,84 @@ mod tests {
 
         assert_eq!(graph.name(), "test");
     }
+
+    fn contract_pipeline(registry: Arc<ContractRegistry>, consumed: &str) -> PipelineBuilder {
+        let mut builder = PipelineBuilder::new("contracts").with_contract_registry(registry);
+        builder
+            .add_stage_spec(
+                StageSpec::new("fetch", noop("fetch"))
+                    .with_produces("user@2.0".parse().unwrap()),
+            )
+            .unwrap();
+        builder
+            .add_stage_spec(
+                StageSpec::new("render", noop("render"))
+                    .with_dependency("fetch")
+                    .with_consumes(consumed.parse().unwrap()),
+            )
+            .unwrap();
+        builder
+    }
+
+    fn user_registry() -> Arc<ContractRegistry> {
+        let registry = ContractRegistry::new();
+        let v1 = serde_json::json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}, "email": {"type": "string"}}
+        });
+        let v2 = serde_json::json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}}
+        });
+        registry.register("user", "1.0", v1, None).unwrap();
+        registry.register("user", "2.0", v2, None).unwrap();
+        Arc::new(registry)
+    }
+
+    #[test]
+    fn test_build_accepts_compatible_contracts() {
+        assert!(contract_pipeline(user_registry(), "user@2.0").build().is_ok());
+    }
+
+    #[test]
+    fn test_build_rejects_incompatible_contract() {
+        let err = contract_pipeline(user_registry(), "user@1.0").build().unwrap_err();
+        let info = err.error_info.unwrap();
+
+        assert_eq!(info.code, codes::SCHEMA_MISMATCH);
+        assert!(err.message.contains("email"), "{}", err.message);
+        assert_eq!(err.stages, vec!["fetch", "render"]);
+    }
+
+    #[test]
+    fn test_build_rejects_unregistered_and_unproduced_contracts() {
+        let err = contract_pipeline(user_registry(), "user@9.0").build().unwrap_err();
+        assert_eq!(err.error_info.unwrap().code, codes::UNREGISTERED);
+
+        let err = contract_pipeline(user_registry(), "order@1.0").build().unwrap_err();
+        assert_eq!(err.error_info.unwrap().code, codes::MISSING_PRODUCER);
+    }
+
+    #[test]
+    fn test_preflight_reports_every_violation() {
+        let mut builder = contract_pipeline(user_registry(), "user@1.0");
+        builder
+            .add_stage_spec(
+                StageSpec::new("bill", noop("bill")).with_consumes("order@1.0".parse().unwrap()),
+            )
+            .unwrap();
+
+        let err = builder.preflight().unwrap_err();
+        let found: Vec<String> = err
+            .errors()
+            .into_iter()
+            .filter_map(|error| match error {
+                StageflowError::Validation(e) => e.error_info.as_ref().map(|info| info.code.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(found, vec![codes::SCHEMA_MISMATCH, codes::MISSING_PRODUCER]);
+        assert!(err.to_string().starts_with("2 errors occurred: "));
+        assert!(contract_pipeline(user_registry(), "user@2.0").preflight().is_ok());
+    }
 }
@@ -55,6 +55,11 @@ impl CleanupRegistry {
         self.callbacks.lock().len()
     }
 
+    /// Discards all pending callbacks without running them.
+    pub fn clear(&self) {
+        self.callbacks.lock().clear();
+    }
+
     /// Runs all cleanup callbacks in LIFO order.
     ///
     /// Returns lists of completed and failed callback names.
@@ -0,0 +1,346 @@
+//! Retry-state checkpoints for resuming runs across process restarts.
+//!
+//! A checkpoint records how many retry attempts each stage has consumed so a
+//! resumed run keeps counting against the same budgets instead of starting
+//! over, which would otherwise allow a crash-looping run to retry forever.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{GuardRetryRuntimeState, RetryState};
+use crate::context::SnapshotFingerprint;
+use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::utils::encode_path_component;
+
+/// Persisted retry bookkeeping for a single pipeline run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetryCheckpoint {
+    /// The pipeline run this checkpoint belongs to.
+    pub run_id: String,
+    /// Guard-retry runtime state keyed by guard stage name.
+    #[serde(default)]
+    pub guard_retry_state: HashMap<String, GuardRetryRuntimeState>,
+    /// Stage-level retry state keyed by stage name.
+    #[serde(default)]
+    pub retry_state: HashMap<String, RetryState>,
+    /// Outputs of manual-ack stages that were acknowledged, keyed by stage name.
+    #[serde(default)]
+    pub acknowledged: HashMap<String, StageOutput>,
+    /// Fingerprint of the snapshot the run started from, for noticing a
+    /// resume from a different snapshot.
+    #[serde(default)]
+    pub snapshot: Option<SnapshotFingerprint>,
+    /// Unix timestamp of the last update.
+    pub updated_at: f64,
+}
+
+impl RetryCheckpoint {
+    /// Creates an empty checkpoint for a run.
+    #[must_use]
+    pub fn new(run_id: impl Into<String>) -> Self {
+        Self {
+            run_id: run_id.into(),
+            guard_retry_state: HashMap::new(),
+            retry_state: HashMap::new(),
+            acknowledged: HashMap::new(),
+            snapshot: None,
+            updated_at: now_seconds(),
+        }
+    }
+
+    /// Records guard-retry state, capturing elapsed time for later resumption.
+    pub fn set_guard_state(&mut self, guard: impl Into<String>, state: &GuardRetryRuntimeState) {
+        self.guard_retry_state.insert(guard.into(), state.checkpoint());
+        self.updated_at = now_seconds();
+    }
+
+    /// Removes guard-retry state once the guard has recovered.
+    pub fn clear_guard_state(&mut self, guard: &str) {
+        self.guard_retry_state.remove(guard);
+        self.updated_at = now_seconds();
+    }
+
+    /// Records stage-level retry state.
+    pub fn set_retry_state(&mut self, stage_name: impl Into<String>, state: &RetryState) {
+        self.retry_state.insert(stage_name.into(), state.clone());
+        self.updated_at = now_seconds();
+    }
+
+    /// Removes stage-level retry state once the stage has succeeded.
+    pub fn clear_retry_state(&mut self, stage_name: &str) {
+        self.retry_state.remove(stage_name);
+        self.updated_at = now_seconds();
+    }
+
+    /// Records the output of an acknowledged manual-ack stage.
+    pub fn set_acknowledged(&mut self, stage_name: impl Into<String>, output: &StageOutput) {
+        self.acknowledged.insert(stage_name.into(), output.clone());
+        self.updated_at = now_seconds();
+    }
+
+    /// Returns restored guard-retry state with monotonic clocks rebuilt.
+    #[must_use]
+    pub fn restored_guard_state(&self) -> HashMap<String, GuardRetryRuntimeState> {
+        self.guard_retry_state
+            .iter()
+            .map(|(name, state)| {
+                let mut state = state.clone();
+                state.restore_clock();
+                (name.clone(), state)
+            })
+            .collect()
+    }
+
+    /// Returns true if no retry or acknowledgment state is recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.guard_retry_state.is_empty()
+            && self.retry_state.is_empty()
+            && self.acknowledged.is_empty()
+    }
+}
+
+/// Storage backend for retry checkpoints.
+#[async_trait]
+pub trait RetryCheckpointStore: Send + Sync {
+    /// Loads the checkpoint for a run, if one exists.
+    async fn load(&self, run_id: &str) -> Result<Option<RetryCheckpoint>, StageflowError>;
+
+    /// Saves a checkpoint, replacing any previous one for the same run.
+    async fn save(&self, checkpoint: &RetryCheckpoint) -> Result<(), StageflowError>;
+
+    /// Deletes the checkpoint for a run.
+    async fn delete(&self, run_id: &str) -> Result<(), StageflowError>;
+}
+
+/// In-memory retry checkpoint store.
+#[derive(Debug, Default)]
+pub struct InMemoryRetryCheckpointStore {
+    entries: Arc<Mutex<HashMap<String, RetryCheckpoint>>>,
+}
+
+impl InMemoryRetryCheckpointStore {
+    /// Creates a new in-memory store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of stored checkpoints.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns true if the store is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
+#[async_trait]
+impl RetryCheckpointStore for InMemoryRetryCheckpointStore {
+    async fn load(&self, run_id: &str) -> Result<Option<RetryCheckpoint>, StageflowError> {
+        Ok(self.entries.lock().get(run_id).cloned())
+    }
+
+    async fn save(&self, checkpoint: &RetryCheckpoint) -> Result<(), StageflowError> {
+        self.entries
+            .lock()
+            .insert(checkpoint.run_id.clone(), checkpoint.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, run_id: &str) -> Result<(), StageflowError> {
+        self.entries.lock().remove(run_id);
+        Ok(())
+    }
+}
+
+/// File-backed retry checkpoint store writing one JSON file per run.
+#[derive(Debug, Clone)]
+pub struct FileRetryCheckpointStore {
+    directory: PathBuf,
+}
+
+impl FileRetryCheckpointStore {
+    /// Creates a store rooted at the given directory.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Returns the checkpoint directory.
+    #[must_use]
+    pub fn directory(&self) -> &std::path::Path {
+        &self.directory
+    }
+
+    fn path_for(&self, run_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.retry.json", encode_path_component(run_id)))
+    }
+}
+
+#[async_trait]
+impl RetryCheckpointStore for FileRetryCheckpointStore {
+    async fn load(&self, run_id: &str) -> Result<Option<RetryCheckpoint>, StageflowError> {
+        let path = self.path_for(run_id);
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| StageflowError::Serialization(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, checkpoint: &RetryCheckpoint) -> Result<(), StageflowError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let bytes = serde_json::to_vec_pretty(checkpoint)
+            .map_err(|e| StageflowError::Serialization(e.to_string()))?;
+
+        // Write then rename so a crash mid-write never leaves a torn checkpoint.
+        let path = self.path_for(&checkpoint.run_id);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, run_id: &str) -> Result<(), StageflowError> {
+        match tokio::fs::remove_file(self.path_for(run_id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A run's checkpoint shared by the executor and its stage tasks.
+///
+/// Each update is applied and saved under one lock, so a writer never
+/// persists a stale copy over a newer one.
+#[derive(Clone)]
+pub(crate) struct SharedCheckpoint {
+    store: Arc<dyn RetryCheckpointStore>,
+    checkpoint: Arc<tokio::sync::Mutex<RetryCheckpoint>>,
+}
+
+impl SharedCheckpoint {
+    /// Loads the checkpoint for a run, starting an empty one if none exists.
+    pub(crate) async fn open(
+        store: Arc<dyn RetryCheckpointStore>,
+        run_id: &str,
+    ) -> Result<Self, StageflowError> {
+        let checkpoint = store
+            .load(run_id)
+            .await?
+            .unwrap_or_else(|| RetryCheckpoint::new(run_id));
+        Ok(Self {
+            store,
+            checkpoint: Arc::new(tokio::sync::Mutex::new(checkpoint)),
+        })
+    }
+
+    /// Returns a copy of the current checkpoint.
+    pub(crate) async fn current(&self) -> RetryCheckpoint {
+        self.checkpoint.lock().await.clone()
+    }
+
+    /// Records the fingerprint of the snapshot this run started from,
+    /// returning the sections that differ from the run being resumed.
+    ///
+    /// The fingerprint is saved with the next update.
+    pub(crate) async fn stamp_snapshot(&self, fingerprint: SnapshotFingerprint) -> Vec<&'static str> {
+        let previous = self.checkpoint.lock().await.snapshot.replace(fingerprint);
+        previous.map(|previous| previous.changed_sections(&fingerprint)).unwrap_or_default()
+    }
+
+    /// Applies `update` to the checkpoint and saves it.
+    pub(crate) async fn update(
+        &self,
+        update: impl FnOnce(&mut RetryCheckpoint),
+    ) -> Result<(), StageflowError> {
+        let mut checkpoint = self.checkpoint.lock().await;
+        update(&mut checkpoint);
+        self.store.save(&checkpoint).await
+    }
+
+    /// Deletes the saved checkpoint once the run has completed.
+    pub(crate) async fn delete(&self) -> Result<(), StageflowError> {
+        let checkpoint = self.checkpoint.lock().await;
+        self.store.delete(&checkpoint.run_id).await
+    }
+}
+
+fn now_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_store_roundtrip() {
+        let store = InMemoryRetryCheckpointStore::new();
+        let mut checkpoint = RetryCheckpoint::new("run-1");
+        let mut state = GuardRetryRuntimeState::new();
+        state.attempts = 3;
+        checkpoint.set_guard_state("guard", &state);
+
+        store.save(&checkpoint).await.unwrap();
+        assert_eq!(store.len(), 1);
+
+        let loaded = store.load("run-1").await.unwrap().unwrap();
+        assert_eq!(loaded.guard_retry_state["guard"].attempts, 3);
+
+        store.delete("run-1").await.unwrap();
+        assert!(store.load("run-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_survives_new_instance() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checkpoint = RetryCheckpoint::new("run/2");
+        let mut retry = RetryState::new();
+        retry.attempt = 2;
+        checkpoint.set_retry_state("work", &retry);
+
+        FileRetryCheckpointStore::new(dir.path())
+            .save(&checkpoint)
+            .await
+            .unwrap();
+
+        let reopened = FileRetryCheckpointStore::new(dir.path());
+        let loaded = reopened.load("run/2").await.unwrap().unwrap();
+        assert_eq!(loaded.retry_state["work"].attempt, 2);
+        assert!(reopened.load("run_2").await.unwrap().is_none());
+
+        reopened.delete("run/2").await.unwrap();
+        assert!(reopened.load("run/2").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_restored_guard_state_rebuilds_clock() {
+        let mut checkpoint = RetryCheckpoint::new("run");
+        let mut state = GuardRetryRuntimeState::new();
+        state.attempts = 1;
+        state.elapsed_seconds = 2.0;
+        checkpoint.guard_retry_state.insert("guard".to_string(), state);
+
+        let restored = checkpoint.restored_guard_state();
+        assert!(restored["guard"].started_at.is_some());
+    }
+}
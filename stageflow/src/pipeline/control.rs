@@ -0,0 +1,124 @@
+//! Live control of a running pipeline: pause, resume and single-step.
+//!
+//! A [`PipelineController`] comes from
+//! [`UnifiedStageGraph::execute_controlled`](super::UnifiedStageGraph::execute_controlled).
+//! Pausing stops the executor from starting new stages; stages already in
+//! flight run to completion. While paused, [`step`](PipelineController::step)
+//! lets exactly one more stage start, which makes it possible to walk a
+//! pipeline stage by stage when debugging.
+
+use crate::context::{ExecutionContext, PipelineContext};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Scheduling state shared between a controller and the executor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ControlMode {
+    Running,
+    /// Paused, with the number of stages still allowed to start.
+    Paused { steps: u32 },
+}
+
+/// Handle that pauses, resumes and single-steps a running pipeline.
+///
+/// Clones control the same run. Calls made before the run starts take
+/// effect from its first stage, so a run can be started paused.
+#[derive(Clone)]
+pub struct PipelineController {
+    mode: Arc<watch::Sender<ControlMode>>,
+    ctx: Arc<PipelineContext>,
+    pipeline: String,
+}
+
+impl PipelineController {
+    pub(crate) fn new(ctx: Arc<PipelineContext>, pipeline: impl Into<String>) -> Self {
+        Self {
+            mode: Arc::new(watch::Sender::new(ControlMode::Running)),
+            ctx,
+            pipeline: pipeline.into(),
+        }
+    }
+
+    /// Stops new stages from starting. Returns false if already paused.
+    pub fn pause(&self) -> bool {
+        let paused = self.mode.send_if_modified(|mode| {
+            let was_running = *mode == ControlMode::Running;
+            if was_running {
+                *mode = ControlMode::Paused { steps: 0 };
+            }
+            was_running
+        });
+        if paused {
+            self.emit("pipeline.paused");
+        }
+        paused
+    }
+
+    /// Lets stages start again. Returns false if not paused.
+    pub fn resume(&self) -> bool {
+        let resumed = self.mode.send_if_modified(|mode| {
+            let was_paused = *mode != ControlMode::Running;
+            *mode = ControlMode::Running;
+            was_paused
+        });
+        if resumed {
+            self.emit("pipeline.resumed");
+        }
+        resumed
+    }
+
+    /// Lets one more stage start, pausing the run first if it is running.
+    ///
+    /// Steps accumulate: stepping twice before the executor picks up the
+    /// first lets two stages start.
+    pub fn step(&self) {
+        self.pause();
+        self.mode.send_modify(|mode| {
+            if let ControlMode::Paused { steps } = mode {
+                *steps += 1;
+            }
+        });
+    }
+
+    /// Returns true if the run is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        *self.mode.borrow() != ControlMode::Running
+    }
+
+    /// Returns a receiver that observes pause, resume and step calls.
+    pub(crate) fn subscribe(&self) -> watch::Receiver<ControlMode> {
+        self.mode.subscribe()
+    }
+
+    /// Takes one allowed step while paused, if `next` yields a stage to
+    /// start.
+    pub(crate) fn take_step<T>(&self, next: impl FnOnce() -> Option<T>) -> Option<T> {
+        let mut taken = None;
+        // Consuming a step is not a change the executor needs waking for
+        self.mode.send_if_modified(|mode| {
+            if let ControlMode::Paused { steps } = mode {
+                if *steps > 0 {
+                    taken = next();
+                    *steps -= u32::from(taken.is_some());
+                }
+            }
+            false
+        });
+        taken
+    }
+
+    fn emit(&self, event_type: &str) {
+        self.ctx
+            .try_emit_event(event_type, Some(serde_json::json!({ "pipeline": self.pipeline })));
+    }
+}
+
+impl std::fmt::Debug for PipelineController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineController")
+            .field("pipeline", &self.pipeline)
+            .field("mode", &*self.mode.borrow())
+            .finish_non_exhaustive()
+    }
+}
@@ -2,15 +2,20 @@
 //!
 //! Executes stages as soon as their dependencies are met, allowing for maximum parallelism.
 
-use super::StageSpec;
-use crate::context::{ContextSnapshot, ExecutionContext, PipelineContext, StageContext, StageInputs};
+use super::{until_deadline, RunBudget, StageAckRegistry, StageSpec, DEFAULT_ACK_TIMEOUT};
+use crate::context::{ContextSnapshot, ExecutionContext, PipelineContext};
 use crate::core::{StageOutput, StageStatus};
 use crate::errors::StageflowError;
+use crate::executor::{DependencyTracker, Executor, build_shared_stage_inputs, run_stage};
 use futures::stream::{FuturesUnordered, StreamExt};
 use parking_lot::RwLock;
+use crate::utils::{with_deterministic_source, Stopwatch};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::Duration;
+
+/// A completed stage's output data, shared with the stages that depend on it.
+type SharedOutput = Arc<HashMap<String, serde_json::Value>>;
 
 /// Result of executing a stage graph.
 #[derive(Debug)]
@@ -34,6 +39,12 @@ pub struct StageGraph {
     stages: HashMap<String, StageSpec>,
     /// Execution order (topologically sorted).
     execution_order: Vec<String>,
+    /// Run-level limits applied on execution.
+    budget: RunBudget,
+    /// Acknowledgments received for manual-ack stages.
+    ack_registry: Arc<StageAckRegistry>,
+    /// How long to wait for a manual-ack stage to be acknowledged.
+    ack_timeout: Duration,
 }
 
 impl StageGraph {
@@ -51,9 +62,54 @@ impl StageGraph {
             name,
             stages,
             execution_order,
+            budget: RunBudget::default(),
+            ack_registry: Arc::new(StageAckRegistry::new()),
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
         }
     }
 
+    /// Sets run-level limits enforced on execution.
+    #[must_use]
+    pub fn with_budget(mut self, budget: RunBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the run-level limits enforced on execution.
+    #[must_use]
+    pub fn budget(&self) -> &RunBudget {
+        &self.budget
+    }
+
+    /// Sets the registry that receives acknowledgments for manual-ack stages.
+    #[must_use]
+    pub fn with_ack_registry(mut self, registry: Arc<StageAckRegistry>) -> Self {
+        self.ack_registry = registry;
+        self
+    }
+
+    /// Sets how long to wait for a manual-ack stage to be acknowledged.
+    ///
+    /// A stage not acknowledged in time fails the run before its dependents
+    /// start.
+    #[must_use]
+    pub fn with_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = timeout;
+        self
+    }
+
+    /// Returns the registry that receives acknowledgments for manual-ack stages.
+    #[must_use]
+    pub fn ack_registry(&self) -> &Arc<StageAckRegistry> {
+        &self.ack_registry
+    }
+
+    /// Returns how long to wait for a manual-ack stage to be acknowledged.
+    #[must_use]
+    pub fn ack_timeout(&self) -> Duration {
+        self.ack_timeout
+    }
+
     /// Returns the pipeline name.
     #[must_use]
     pub fn name(&self) -> &str {
@@ -88,72 +144,95 @@ impl StageGraph {
     ///
     /// Stages are executed as soon as their dependencies are satisfied,
     /// allowing for maximum parallelism. This matches Python's StageGraph behavior.
+    /// When the context is deterministic, stages instead run one at a time
+    /// in topological order.
     pub async fn execute(
         &self,
         ctx: Arc<PipelineContext>,
         snapshot: ContextSnapshot,
     ) -> Result<GraphExecutionResult, StageflowError> {
-        let start = Instant::now();
+        match ctx.deterministic_source().cloned() {
+            Some(source) => with_deterministic_source(source, self.execute_inner(ctx, snapshot)).await,
+            None => self.execute_inner(ctx, snapshot).await,
+        }
+    }
+
+    /// Executes the graph with a custom executor.
+    pub async fn execute_with(
+        &self,
+        executor: &dyn Executor,
+        ctx: Arc<PipelineContext>,
+        snapshot: ContextSnapshot,
+    ) -> Result<GraphExecutionResult, StageflowError> {
+        executor.execute(self, ctx, snapshot).await
+    }
+
+    async fn execute_inner(
+        &self,
+        ctx: Arc<PipelineContext>,
+        snapshot: ContextSnapshot,
+    ) -> Result<GraphExecutionResult, StageflowError> {
+        let start = Stopwatch::start();
+        let deterministic = ctx.is_deterministic();
+        ctx.install_budget(self.budget);
+        let snapshot = Arc::new(snapshot);
         
         // Shared state for parallel execution
         let outputs: Arc<RwLock<HashMap<String, StageOutput>>> = Arc::new(RwLock::new(HashMap::new()));
-        let completed_outputs: Arc<RwLock<HashMap<String, HashMap<String, serde_json::Value>>>> = 
-            Arc::new(RwLock::new(HashMap::new()));
+        let completed_outputs: Arc<RwLock<HashMap<String, SharedOutput>>> = Arc::new(RwLock::new(HashMap::new()));
         
-        // Track in-degree (number of unsatisfied dependencies) for each stage
-        let mut in_degree: HashMap<String, usize> = self.stages.iter()
-            .map(|(name, spec)| (name.clone(), spec.dependencies.len()))
-            .collect();
+        // Track unsatisfied dependencies and the ready set
+        let mut tracker = DependencyTracker::new(self);
         
         // Active tasks being executed
         let mut active_tasks: FuturesUnordered<tokio::task::JoinHandle<Result<(String, StageOutput), StageflowError>>> = 
             FuturesUnordered::new();
         
-        // Schedule stages with no dependencies (in_degree == 0)
-        let ready_stages: Vec<String> = in_degree.iter()
-            .filter(|(_, &count)| count == 0)
-            .map(|(name, _)| name.clone())
-            .collect();
-        
-        for stage_name in ready_stages {
-            let task = self.spawn_stage_task(
-                stage_name.clone(),
-                ctx.clone(),
-                snapshot.clone(),
-                completed_outputs.clone(),
-            );
-            active_tasks.push(task);
-        }
-        
-        let mut completed_count = 0;
-        let total_stages = self.stages.len();
-        
-        while completed_count < total_stages {
-            // Check for cancellation
+        while !tracker.is_complete() {
+            // Launch ready stages; deterministic runs keep a single stage in flight
+            let launch = if deterministic {
+                if active_tasks.is_empty() {
+                    tracker.take_next_ready().into_iter().collect()
+                } else {
+                    Vec::new()
+                }
+            } else {
+                tracker.take_ready()
+            };
+            for stage_name in launch {
+                active_tasks.push(self.spawn_stage_task(
+                    stage_name,
+                    ctx.clone(),
+                    snapshot.clone(),
+                    completed_outputs.clone(),
+                ));
+            }
+
+            // Check for cancellation, including by the run's budget
+            let _ = ctx.check_wall_clock();
             if (*ctx).is_cancelled() {
                 // Cancel all active tasks
                 // Note: In Rust we can't easily cancel JoinHandles, but we check cancellation in each stage
                 let current_outputs = outputs.read().clone();
                 return Ok(GraphExecutionResult {
                     outputs: current_outputs,
-                    duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                    duration_ms: start.elapsed_ms(),
                     success: false,
                     error: Some("Pipeline cancelled".to_string()),
                 });
             }
             
             if active_tasks.is_empty() {
-                let pending: Vec<_> = self.stages.keys()
-                    .filter(|name| !outputs.read().contains_key(*name))
-                    .cloned()
-                    .collect();
                 return Err(StageflowError::Internal(
-                    format!("Deadlocked stage graph; remaining stages: {:?}", pending)
+                    format!("Deadlocked stage graph; remaining stages: {:?}", tracker.remaining())
                 ));
             }
             
             // Wait for the first task to complete (parallel execution!)
-            if let Some(result) = active_tasks.next().await {
+            let Some(next) = until_deadline(&ctx, active_tasks.next()).await else {
+                continue;
+            };
+            if let Some(result) = next {
                 match result {
                     Ok(Ok((stage_name, output))) => {
                         // Handle stage failure
@@ -162,7 +241,7 @@ impl StageGraph {
                             outs.insert(stage_name.clone(), output);
                             return Ok(GraphExecutionResult {
                                 outputs: outs.clone(),
-                                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                                duration_ms: start.elapsed_ms(),
                                 success: false,
                                 error: Some(format!("Stage '{}' failed", stage_name)),
                             });
@@ -174,42 +253,21 @@ impl StageGraph {
                             outs.insert(stage_name.clone(), output);
                             return Ok(GraphExecutionResult {
                                 outputs: outs.clone(),
-                                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                                duration_ms: start.elapsed_ms(),
                                 success: false,
                                 error: Some(format!("Stage '{}' cancelled pipeline", stage_name)),
                             });
                         }
                         
                         // Store output for downstream stages
-                        {
-                            let mut comp_outs = completed_outputs.write();
-                            if let Some(data) = output.data.clone() {
-                                comp_outs.insert(stage_name.clone(), data);
-                            } else {
-                                comp_outs.insert(stage_name.clone(), HashMap::new());
-                            }
-                        }
+                        completed_outputs
+                            .write()
+                            .insert(stage_name.clone(), output.shared_data_or_empty().into_arc());
                         
                         outputs.write().insert(stage_name.clone(), output);
-                        completed_count += 1;
                         
-                        // Schedule newly ready stages (dependencies satisfied)
-                        for (child_name, spec) in &self.stages {
-                            if spec.dependencies.contains(&stage_name) {
-                                if let Some(count) = in_degree.get_mut(child_name) {
-                                    *count = count.saturating_sub(1);
-                                    if *count == 0 && !outputs.read().contains_key(child_name) {
-                                        let task = self.spawn_stage_task(
-                                            child_name.clone(),
-                                            ctx.clone(),
-                                            snapshot.clone(),
-                                            completed_outputs.clone(),
-                                        );
-                                        active_tasks.push(task);
-                                    }
-                                }
-                            }
-                        }
+                        // Queue newly ready stages (dependencies satisfied)
+                        tracker.mark_complete(&stage_name);
                     }
                     Ok(Err(e)) => {
                         return Err(e);
@@ -224,97 +282,45 @@ impl StageGraph {
         let final_outputs = outputs.read().clone();
         Ok(GraphExecutionResult {
             outputs: final_outputs,
-            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+            duration_ms: start.elapsed_ms(),
             success: true,
             error: None,
         })
     }
-    
+
     /// Spawns a task to execute a single stage.
     fn spawn_stage_task(
         &self,
         stage_name: String,
         ctx: Arc<PipelineContext>,
-        snapshot: ContextSnapshot,
-        completed_outputs: Arc<RwLock<HashMap<String, HashMap<String, serde_json::Value>>>>,
+        snapshot: Arc<ContextSnapshot>,
+        completed_outputs: Arc<RwLock<HashMap<String, SharedOutput>>>,
     ) -> tokio::task::JoinHandle<Result<(String, StageOutput), StageflowError>> {
         let spec = self.stages.get(&stage_name).unwrap().clone();
+        let source = ctx.deterministic_source().cloned();
+        let acks = Arc::clone(&self.ack_registry);
+        let ack_timeout = self.ack_timeout;
         
-        tokio::spawn(async move {
+        let task = async move {
             // Build inputs from completed outputs
-            let prior_outputs = completed_outputs.read().clone();
-            let inputs = StageInputs::new(
-                prior_outputs,
-                spec.dependencies.clone(),
-                &stage_name,
-                true,
-            );
-            
-            // Create stage context
-            let stage_ctx = StageContext::new(
-                ctx.clone(),
-                &stage_name,
-                inputs,
-                snapshot,
-            );
-            
-            // Emit stage.started
-            (*ctx).try_emit_event(
-                "stage.started",
-                Some(serde_json::json!({
-                    "stage": &stage_name,
-                })),
-            );
-            
-            let stage_start = Instant::now();
-            
-            // Execute stage
-            let output = spec.runner.execute(&stage_ctx).await;
-            let stage_duration_ms = stage_start.elapsed().as_secs_f64() * 1000.0;
-            
-            // Emit appropriate event based on status
-            match output.status {
-                StageStatus::Ok => {
-                    (*ctx).try_emit_event(
-                        "stage.completed",
-                        Some(serde_json::json!({
-                            "stage": &stage_name,
-                            "duration_ms": stage_duration_ms,
-                        })),
-                    );
-                }
-                StageStatus::Skip => {
-                    (*ctx).try_emit_event(
-                        "stage.skipped",
-                        Some(serde_json::json!({
-                            "stage": &stage_name,
-                            "reason": output.skip_reason,
-                        })),
-                    );
-                }
-                StageStatus::Fail => {
-                    (*ctx).try_emit_event(
-                        "stage.failed",
-                        Some(serde_json::json!({
-                            "stage": &stage_name,
-                            "error": output.error,
-                            "duration_ms": stage_duration_ms,
-                        })),
-                    );
-                }
-                StageStatus::Cancel => {
-                    (*ctx).try_emit_event(
-                        "stage.cancelled",
-                        Some(serde_json::json!({
-                            "stage": &stage_name,
-                            "reason": output.cancel_reason,
-                        })),
-                    );
-                }
-                _ => {}
+            // Only the dependencies' outputs are handed over, as shared `Arc`s
+            let inputs = build_shared_stage_inputs(&spec, &completed_outputs.read());
+            let run_key = ctx.pipeline_run_id().map(|id| id.to_string()).unwrap_or_default();
+            let output = run_stage(&spec, ctx.clone(), inputs, snapshot).await;
+
+            // Dependents only start once a manual-ack stage is acknowledged
+            if spec.manual_ack && output.is_success() {
+                acks.await_stage(ctx.as_ref(), &run_key, &stage_name, ack_timeout).await?;
             }
-            
             Ok((stage_name, output))
+        };
+
+        // Task-locals do not cross `tokio::spawn`, so re-scope the source
+        tokio::spawn(async move {
+            match source {
+                Some(source) => with_deterministic_source(source, task).await,
+                None => task.await,
+            }
         })
     }
 }
@@ -367,7 +373,7 @@ fn topological_sort(
 mod tests {
     use super::*;
     use crate::context::RunIdentity;
-    use crate::stages::NoOpStage;
+    use crate::stages::{FnStage, NoOpStage};
 
     fn noop(name: &str) -> Arc<dyn crate::stages::Stage> {
         Arc::new(NoOpStage::new(name))
@@ -418,4 +424,69 @@ mod tests {
         assert!(result.success);
         assert_eq!(result.outputs.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_stage_inputs_hold_only_dependencies() {
+        let producer = |name: &'static str| -> Arc<dyn crate::stages::Stage> {
+            Arc::new(FnStage::new(name, move |_ctx| {
+                StageOutput::ok(HashMap::from([("from".to_string(), serde_json::json!(name))]))
+            }))
+        };
+        let consumer = Arc::new(FnStage::new("consumer", |ctx| {
+            let inputs = ctx.inputs();
+            StageOutput::ok(HashMap::from([
+                ("a".to_string(), serde_json::json!(inputs.get_unchecked("a").is_some())),
+                ("b".to_string(), serde_json::json!(inputs.get_unchecked("b").is_some())),
+            ]))
+        }));
+        let stages = HashMap::from([
+            ("a".to_string(), StageSpec::new("a", producer("a")).with_dependency("b")),
+            ("b".to_string(), StageSpec::new("b", producer("b"))),
+            ("consumer".to_string(), StageSpec::new("consumer", consumer).with_dependency("a")),
+        ]);
+        let order = vec!["b".to_string(), "a".to_string(), "consumer".to_string()];
+        let graph = StageGraph::new("test".to_string(), stages, order);
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+
+        assert!(result.success);
+        let seen = &result.outputs["consumer"];
+        assert_eq!(seen.get("a"), Some(&serde_json::json!(true)));
+        assert_eq!(seen.get("b"), Some(&serde_json::json!(false)));
+    }
+
+    #[tokio::test]
+    async fn test_manual_ack_gates_dependents() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let notify_runs = Arc::new(AtomicUsize::new(0));
+        let counter = notify_runs.clone();
+        let notify = Arc::new(FnStage::new("notify", move |_ctx| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            StageOutput::ok_empty()
+        }));
+        let stages = HashMap::from([
+            ("outbox".to_string(), StageSpec::new("outbox", noop("outbox")).with_manual_ack()),
+            ("notify".to_string(), StageSpec::new("notify", notify).with_dependency("outbox")),
+        ]);
+        let order = vec!["outbox".to_string(), "notify".to_string()];
+        let graph = StageGraph::new("test".to_string(), stages, order)
+            .with_ack_timeout(Duration::from_millis(20));
+
+        // No acknowledgment: the run fails before the dependent runs
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let err = graph.execute(ctx, ContextSnapshot::new()).await.unwrap_err();
+        assert!(err.to_string().contains("not acknowledged"));
+        assert_eq!(notify_runs.load(Ordering::SeqCst), 0);
+
+        let identity = RunIdentity::new();
+        graph
+            .ack_registry()
+            .ack(&identity.pipeline_run_id.unwrap().to_string(), "outbox");
+        let ctx = Arc::new(PipelineContext::new(identity));
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert!(result.success);
+        assert_eq!(notify_runs.load(Ordering::SeqCst), 1);
+    }
 }
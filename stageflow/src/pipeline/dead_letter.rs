@@ -0,0 +1,327 @@
+//! Dead letters for stage work that failed permanently.
+//!
+//! When a stage with a retry config gives up, the executor can record a
+//! [`DeadLetter`] holding its last output, the outputs it was given and the
+//! run's context snapshot, and emit `stage.dead_lettered`. A letter is
+//! re-injected into a new run with
+//! [`UnifiedStageGraph::redrive`](super::UnifiedStageGraph::redrive).
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::context::ContextSnapshot;
+use crate::core::{StageOutput, StageStatus};
+use crate::errors::StageflowError;
+use crate::utils::encode_path_component;
+
+/// A stage that exhausted its retries, with what is needed to run it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// Unique ID of the letter.
+    pub id: String,
+    /// The pipeline the stage belongs to.
+    pub pipeline: String,
+    /// The pipeline run the stage failed in, if it had an ID.
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// The stage that failed.
+    pub stage: String,
+    /// Retry attempts made before giving up.
+    pub attempts: usize,
+    /// The stage's last output.
+    pub output: StageOutput,
+    /// The stage's dependencies, whose outputs were its inputs.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Outputs of the stages that had finished when the stage gave up,
+    /// keyed by stage name.
+    #[serde(default)]
+    pub outputs: HashMap<String, StageOutput>,
+    /// The context snapshot the run started from.
+    #[serde(default)]
+    pub snapshot: ContextSnapshot,
+    /// Unix timestamp of when the letter was written.
+    pub created_at: f64,
+}
+
+impl DeadLetter {
+    /// Creates a letter for `stage` with no recorded outputs.
+    #[must_use]
+    pub fn new(
+        pipeline: impl Into<String>,
+        stage: impl Into<String>,
+        attempts: usize,
+        output: StageOutput,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            pipeline: pipeline.into(),
+            run_id: None,
+            stage: stage.into(),
+            attempts,
+            output,
+            dependencies: Vec::new(),
+            outputs: HashMap::new(),
+            snapshot: ContextSnapshot::new(),
+            created_at: now_seconds(),
+        }
+    }
+
+    /// Returns the error of the stage's last output.
+    #[must_use]
+    pub fn error(&self) -> Option<&str> {
+        self.output.error.as_deref()
+    }
+
+    /// Returns the dependency outputs the stage was given, keyed by stage
+    /// name.
+    #[must_use]
+    pub fn inputs(&self) -> HashMap<&str, &StageOutput> {
+        self.dependencies
+            .iter()
+            .filter_map(|dep| self.outputs.get(dep).map(|output| (dep.as_str(), output)))
+            .collect()
+    }
+
+    /// Returns the outputs a redrive restores instead of running again:
+    /// every recorded output that did not fail, other than the stage's own.
+    #[must_use]
+    pub fn restorable_outputs(&self) -> HashMap<String, StageOutput> {
+        self.outputs
+            .iter()
+            .filter(|(name, output)| {
+                **name != self.stage && matches!(output.status, StageStatus::Ok | StageStatus::Skip)
+            })
+            .map(|(name, output)| (name.clone(), output.clone()))
+            .collect()
+    }
+}
+
+/// Storage backend for dead letters.
+#[async_trait]
+pub trait DeadLetterStore: Send + Sync {
+    /// Stores a letter, replacing any previous one with the same ID.
+    async fn put(&self, letter: &DeadLetter) -> Result<(), StageflowError>;
+
+    /// Loads a letter by ID, if it exists.
+    async fn get(&self, id: &str) -> Result<Option<DeadLetter>, StageflowError>;
+
+    /// Returns every stored letter, oldest first.
+    async fn list(&self) -> Result<Vec<DeadLetter>, StageflowError>;
+
+    /// Deletes a letter, returning true if it existed.
+    async fn remove(&self, id: &str) -> Result<bool, StageflowError>;
+}
+
+/// In-memory dead-letter store.
+#[derive(Debug, Default)]
+pub struct InMemoryDeadLetterStore {
+    entries: Arc<Mutex<HashMap<String, DeadLetter>>>,
+}
+
+impl InMemoryDeadLetterStore {
+    /// Creates a new in-memory store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of stored letters.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns true if the store is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for InMemoryDeadLetterStore {
+    async fn put(&self, letter: &DeadLetter) -> Result<(), StageflowError> {
+        self.entries.lock().insert(letter.id.clone(), letter.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<DeadLetter>, StageflowError> {
+        Ok(self.entries.lock().get(id).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<DeadLetter>, StageflowError> {
+        let mut letters: Vec<DeadLetter> = self.entries.lock().values().cloned().collect();
+        sort_oldest_first(&mut letters);
+        Ok(letters)
+    }
+
+    async fn remove(&self, id: &str) -> Result<bool, StageflowError> {
+        Ok(self.entries.lock().remove(id).is_some())
+    }
+}
+
+/// File-backed dead-letter store writing one JSON file per letter.
+#[derive(Debug, Clone)]
+pub struct FileDeadLetterStore {
+    directory: PathBuf,
+}
+
+impl FileDeadLetterStore {
+    /// Creates a store rooted at the given directory.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Returns the dead-letter directory.
+    #[must_use]
+    pub fn directory(&self) -> &std::path::Path {
+        &self.directory
+    }
+
+    fn path_for(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{}.dead.json", encode_path_component(id)))
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for FileDeadLetterStore {
+    async fn put(&self, letter: &DeadLetter) -> Result<(), StageflowError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let bytes = serde_json::to_vec_pretty(letter)
+            .map_err(|e| StageflowError::Serialization(e.to_string()))?;
+
+        // Write then rename so a crash mid-write never leaves a torn letter.
+        let path = self.path_for(&letter.id);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<DeadLetter>, StageflowError> {
+        match tokio::fs::read(self.path_for(id)).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| StageflowError::Serialization(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<DeadLetter>, StageflowError> {
+        let mut entries = match tokio::fs::read_dir(&self.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut letters = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let is_letter = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.ends_with(".dead.json"));
+            if !is_letter {
+                continue;
+            }
+            let bytes = tokio::fs::read(entry.path()).await?;
+            letters.push(
+                serde_json::from_slice(&bytes).map_err(|e| StageflowError::Serialization(e.to_string()))?,
+            );
+        }
+        sort_oldest_first(&mut letters);
+        Ok(letters)
+    }
+
+    async fn remove(&self, id: &str) -> Result<bool, StageflowError> {
+        match tokio::fs::remove_file(self.path_for(id)).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn sort_oldest_first(letters: &mut [DeadLetter]) {
+    letters.sort_by(|a, b| a.created_at.total_cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+}
+
+fn now_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letter() -> DeadLetter {
+        let mut letter = DeadLetter::new("orders", "charge", 3, StageOutput::fail("card declined"));
+        letter.dependencies = vec!["quote".to_string()];
+        letter
+            .outputs
+            .insert("quote".to_string(), StageOutput::ok_value("total", serde_json::json!(42)));
+        letter.outputs.insert("audit".to_string(), StageOutput::fail("audit down"));
+        letter
+    }
+
+    #[test]
+    fn test_letter_inputs_and_restorable_outputs() {
+        let letter = letter();
+        assert_eq!(letter.error(), Some("card declined"));
+
+        let inputs = letter.inputs();
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs["quote"].get("total"), Some(&serde_json::json!(42)));
+
+        // Failed outputs run again rather than being restored
+        let restored = letter.restorable_outputs();
+        assert_eq!(restored.keys().collect::<Vec<_>>(), vec!["quote"]);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_roundtrip() {
+        let store = InMemoryDeadLetterStore::new();
+        let first = letter();
+        let mut second = letter();
+        second.created_at = first.created_at + 1.0;
+
+        store.put(&second).await.unwrap();
+        store.put(&first).await.unwrap();
+        assert_eq!(store.len(), 2);
+
+        let listed: Vec<String> = store.list().await.unwrap().into_iter().map(|l| l.id).collect();
+        assert_eq!(listed, vec![first.id.clone(), second.id.clone()]);
+        assert_eq!(store.get(&first.id).await.unwrap().unwrap().stage, "charge");
+
+        assert!(store.remove(&first.id).await.unwrap());
+        assert!(!store.remove(&first.id).await.unwrap());
+        assert!(store.get(&first.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_survives_new_instance() {
+        let dir = tempfile::tempdir().unwrap();
+        let letter = letter();
+        FileDeadLetterStore::new(dir.path()).put(&letter).await.unwrap();
+
+        let reopened = FileDeadLetterStore::new(dir.path());
+        let loaded = reopened.get(&letter.id).await.unwrap().unwrap();
+        assert_eq!(loaded.attempts, 3);
+        assert_eq!(loaded.inputs()["quote"].get("total"), Some(&serde_json::json!(42)));
+        assert_eq!(reopened.list().await.unwrap().len(), 1);
+
+        assert!(reopened.remove(&letter.id).await.unwrap());
+        assert!(reopened.list().await.unwrap().is_empty());
+        assert!(FileDeadLetterStore::new(dir.path().join("missing")).list().await.unwrap().is_empty());
+    }
+}
@@ -3,6 +3,7 @@
 //! Provides continue-on-failure mode that records failures but continues
 //! executing unrelated branches, and backpressure management for burst loads.
 
+use crate::errors::StageflowError;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -227,6 +228,15 @@ impl FailureCollector {
     pub fn failures(&self) -> &[FailureRecord] {
         &self.failures
     }
+
+    /// Returns the recorded failures as one error, each prefixed with its
+    /// stage name, or `None` if nothing failed.
+    #[must_use]
+    pub fn error(&self) -> Option<StageflowError> {
+        StageflowError::from_errors(self.failures.iter().map(|failure| {
+            StageflowError::StageExecution(format!("{}: {}", failure.stage, failure.error))
+        }))
+    }
 }
 
 impl Default for FailureCollector {
@@ -390,6 +400,12 @@ mod tests {
         
         // All stages can run in best effort mode
         assert!(collector.can_run("stage2", &["stage1".to_string()]));
+
+        collector.record_failure(FailureRecord::new("stage2", "timeout"));
+        let err = collector.error().unwrap();
+        assert_eq!(err.errors().len(), 2);
+        assert!(err.to_string().contains("stage2: timeout"), "{err}");
+        assert!(FailureCollector::new(FailureMode::BestEffort).error().is_none());
     }
 
     #[test]
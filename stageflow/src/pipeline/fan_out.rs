@@ -0,0 +1,351 @@
+//! Per-item fan-out over a list produced at runtime.
+//!
+//! A static graph cannot hold one stage per item of a list it only sees
+//! while running. A [`FanOut`] runs as a single stage of the outer
+//! pipeline: its mapper stage produces the list, one copy of the template
+//! stage runs per item, and a join stage aggregates their outputs.
+//!
+//! Template copies are named `<template>[<index>]` and read their item
+//! through `inputs.get(<fan-out name>)`, which holds `item`, `index` and
+//! `count`. The join reads `results`, the copies' output data in item
+//! order, and `count` the same way. All three also see the fan-out's own
+//! inputs, and the copies and join see the mapper's output.
+
+use crate::context::{ExecutionContext, StageContext, StageInputs};
+use crate::core::{StageOutput, StageStatus};
+use crate::errors::{ContractErrorInfo, PipelineValidationError};
+use crate::executor::run_stage;
+use crate::stages::Stage;
+use super::StageSpec;
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+type Outputs = HashMap<String, HashMap<String, serde_json::Value>>;
+
+/// Declares a mapper, a per-item template and an optional join.
+#[derive(Debug, Clone)]
+pub struct FanOut {
+    name: String,
+    mapper: Arc<dyn Stage>,
+    items_key: String,
+    template: Arc<dyn Stage>,
+    join: Option<Arc<dyn Stage>>,
+    max_items: Option<usize>,
+    max_concurrency: Option<usize>,
+}
+
+impl FanOut {
+    /// Creates a fan-out running `template` once per item of the list
+    /// `mapper` outputs under `items`.
+    #[must_use]
+    pub fn new(name: impl Into<String>, mapper: Arc<dyn Stage>, template: Arc<dyn Stage>) -> Self {
+        Self {
+            name: name.into(),
+            mapper,
+            items_key: "items".to_string(),
+            template,
+            join: None,
+            max_items: None,
+            max_concurrency: None,
+        }
+    }
+
+    /// Reads the item list from `key` of the mapper output instead of `items`.
+    #[must_use]
+    pub fn with_items_key(mut self, key: impl Into<String>) -> Self {
+        self.items_key = key.into();
+        self
+    }
+
+    /// Aggregates the copies' outputs with `join`.
+    ///
+    /// Without a join, the fan-out outputs `results` and `count` itself.
+    #[must_use]
+    pub fn with_join(mut self, join: Arc<dyn Stage>) -> Self {
+        self.join = Some(join);
+        self
+    }
+
+    /// Fails the fan-out if the mapper produces more than `max_items` items.
+    #[must_use]
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    /// Runs at most `max_concurrency` template copies at once.
+    #[must_use]
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    /// Returns the fan-out name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Validates the fan-out and builds the stage that runs it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a limit is zero or an inner stage shares the
+    /// fan-out's name.
+    pub fn build(self) -> Result<FanOutStage, PipelineValidationError> {
+        if self.max_items == Some(0) || self.max_concurrency == Some(0) {
+            return Err(fan_out_error(
+                &self.name,
+                "CONTRACT-004-FAN_OUT_LIMIT",
+                format!("Fan-out '{}' has a zero limit", self.name),
+                "Leave max_items and max_concurrency unset or give them positive values.",
+            ));
+        }
+        let inner = [Some(&self.mapper), Some(&self.template), self.join.as_ref()];
+        if inner.into_iter().flatten().any(|stage| stage.name() == self.name) {
+            return Err(fan_out_error(
+                &self.name,
+                "CONTRACT-004-FAN_OUT_NAME",
+                format!("Fan-out '{}' has an inner stage with the same name", self.name),
+                "Inner stages read fan-out state under the fan-out name; rename the stage.",
+            ));
+        }
+
+        let spec = |stage: &Arc<dyn Stage>| StageSpec::new(stage.name(), stage.clone());
+        Ok(FanOutStage {
+            mapper: spec(&self.mapper),
+            template: self.template,
+            join: self.join.as_ref().map(spec),
+            name: self.name,
+            items_key: self.items_key,
+            max_items: self.max_items,
+            max_concurrency: self.max_concurrency,
+        })
+    }
+}
+
+fn fan_out_error(name: &str, code: &str, message: String, hint: &str) -> PipelineValidationError {
+    PipelineValidationError::new(message.clone())
+        .with_stages(vec![name.to_string()])
+        .with_error_info(ContractErrorInfo::new(code, message).with_fix_hint(hint))
+}
+
+/// Stage that runs a [`FanOut`].
+///
+/// The output is the join's output, or without a join, `results` and
+/// `count`. Any mapper, copy or join failure fails the stage.
+#[derive(Debug)]
+pub struct FanOutStage {
+    name: String,
+    mapper: StageSpec,
+    items_key: String,
+    template: Arc<dyn Stage>,
+    join: Option<StageSpec>,
+    max_items: Option<usize>,
+    max_concurrency: Option<usize>,
+}
+
+impl FanOutStage {
+    async fn run(&self, ctx: &StageContext, spec: &StageSpec, visible: Outputs) -> StageOutput {
+        let declared: HashSet<String> = visible.keys().cloned().collect();
+        let inputs = StageInputs::new(visible, declared, spec.name.clone(), true);
+        run_stage(spec, ctx.pipeline_ctx().clone(), inputs, ctx.shared_snapshot().clone()).await
+    }
+
+    /// Wraps an inner stage's failure; cancellations pass through unchanged.
+    fn failed(&self, stage: &str, output: StageOutput) -> StageOutput {
+        if output.status == StageStatus::Cancel {
+            return output;
+        }
+        StageOutput::fail(format!(
+            "Fan-out '{}' stage '{stage}' failed: {}",
+            self.name,
+            output.error.as_deref().unwrap_or("unknown error")
+        ))
+    }
+}
+
+fn is_failure(output: &StageOutput) -> bool {
+    matches!(output.status, StageStatus::Fail | StageStatus::Cancel)
+}
+
+#[async_trait]
+impl Stage for FanOutStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let pipeline_ctx = ctx.pipeline_ctx();
+        let mut visible: Outputs = ctx
+            .inputs()
+            .stages()
+            .into_iter()
+            .filter_map(|stage| {
+                ctx.inputs()
+                    .get_unchecked(stage)
+                    .map(|data| (stage.clone(), data.clone()))
+            })
+            .collect();
+
+        let mapped = self.run(ctx, &self.mapper, visible.clone()).await;
+        if is_failure(&mapped) {
+            return self.failed(&self.mapper.name, mapped);
+        }
+        let data = mapped.data_or_empty();
+        let Some(items) = data.get(&self.items_key).and_then(serde_json::Value::as_array).cloned() else {
+            return StageOutput::fail(format!(
+                "Fan-out '{}' mapper '{}' did not output a list under '{}'",
+                self.name, self.mapper.name, self.items_key
+            ));
+        };
+        if let Some(max) = self.max_items.filter(|max| items.len() > *max) {
+            return StageOutput::fail(format!(
+                "Fan-out '{}' mapper produced {} items, more than the limit of {max}",
+                self.name,
+                items.len()
+            ));
+        }
+        visible.insert(self.mapper.name.clone(), data);
+
+        let count = items.len();
+        pipeline_ctx.try_emit_event(
+            "fan_out.started",
+            Some(serde_json::json!({ "fan_out": self.name, "count": count })),
+        );
+
+        let copies = items.into_iter().enumerate().map(|(index, item)| {
+            let spec = StageSpec::new(format!("{}[{index}]", self.template.name()), self.template.clone());
+            let mut visible = visible.clone();
+            visible.insert(
+                self.name.clone(),
+                HashMap::from([
+                    ("item".to_string(), item),
+                    ("index".to_string(), serde_json::json!(index)),
+                    ("count".to_string(), serde_json::json!(count)),
+                ]),
+            );
+            async move {
+                let output = self.run(ctx, &spec, visible).await;
+                (spec.name, output)
+            }
+        });
+        let limit = self.max_concurrency.unwrap_or(count).max(1);
+        let outputs: Vec<(String, StageOutput)> = futures::stream::iter(copies).buffered(limit).collect().await;
+
+        let mut results = Vec::with_capacity(count);
+        for (copy, output) in outputs {
+            if is_failure(&output) {
+                pipeline_ctx.try_emit_event(
+                    "fan_out.failed",
+                    Some(serde_json::json!({ "fan_out": self.name, "stage": copy, "status": output.status })),
+                );
+                return self.failed(&copy, output);
+            }
+            results.push(serde_json::json!(output.data_or_empty()));
+        }
+        pipeline_ctx.try_emit_event(
+            "fan_out.completed",
+            Some(serde_json::json!({ "fan_out": self.name, "count": count })),
+        );
+
+        let aggregate = HashMap::from([
+            ("results".to_string(), serde_json::Value::Array(results)),
+            ("count".to_string(), serde_json::json!(count)),
+        ]);
+        let Some(join) = &self.join else {
+            return StageOutput::ok(aggregate);
+        };
+        visible.insert(self.name.clone(), aggregate);
+        let joined = self.run(ctx, join, visible).await;
+        if is_failure(&joined) {
+            return self.failed(&join.name, joined);
+        }
+        joined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+    use crate::events::CollectingEventSink;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::FnStage;
+
+    fn mapper(items: serde_json::Value) -> Arc<dyn Stage> {
+        Arc::new(FnStage::new("split", move |_: &StageContext| StageOutput::ok_value("items", items.clone())))
+    }
+
+    /// Doubles the item, failing on negative ones.
+    fn double() -> Arc<dyn Stage> {
+        Arc::new(FnStage::new("double", |ctx: &StageContext| {
+            let item = ctx.inputs().get_value("docs", "item").ok().flatten().and_then(serde_json::Value::as_i64);
+            match item {
+                Some(n) if n >= 0 => StageOutput::ok_value("value", serde_json::json!(n * 2)),
+                _ => StageOutput::fail("negative item"),
+            }
+        }))
+    }
+
+    fn sum() -> Arc<dyn Stage> {
+        Arc::new(FnStage::new("sum", |ctx: &StageContext| {
+            let results = ctx.inputs().get_value("docs", "results").ok().flatten().cloned().unwrap_or_default();
+            let total: i64 = results
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|r| r["value"].as_i64())
+                .sum();
+            StageOutput::ok_value("total", serde_json::json!(total))
+        }))
+    }
+
+    async fn run(fan_out: FanOut) -> (crate::pipeline::GraphExecutionResult, Arc<CollectingEventSink>) {
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+        let graph = PipelineBuilder::new("batch").fan_out(fan_out, &[]).unwrap().build().unwrap();
+        (graph.execute(ctx, ContextSnapshot::new()).await.unwrap(), sink)
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_and_join() {
+        let (result, sink) = run(FanOut::new("docs", mapper(serde_json::json!([1, 2, 3])), double())).await;
+        assert!(result.success);
+        let output = &result.outputs["docs"];
+        assert_eq!(output.get("count"), Some(&serde_json::json!(3)));
+        assert_eq!(
+            output.get("results"),
+            Some(&serde_json::json!([{ "value": 2 }, { "value": 4 }, { "value": 6 }]))
+        );
+        assert_eq!(sink.events_of_type("fan_out.completed").len(), 1);
+
+        let fan_out = FanOut::new("docs", mapper(serde_json::json!([1, 2, 3, 4])), double())
+            .with_join(sum())
+            .with_max_concurrency(2);
+        let (result, _) = run(fan_out).await;
+        assert_eq!(result.outputs["docs"].get("total"), Some(&serde_json::json!(20)));
+
+        let (result, _) = run(FanOut::new("docs", mapper(serde_json::json!([])), double()).with_join(sum())).await;
+        assert_eq!(result.outputs["docs"].get("total"), Some(&serde_json::json!(0)));
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_failures() {
+        let (result, _) = run(FanOut::new("docs", mapper(serde_json::json!([1, -1])), double())).await;
+        let error = result.outputs["docs"].error.clone().unwrap();
+        assert!(error.contains("double[1]"));
+        assert!(error.contains("negative item"));
+
+        let (result, _) = run(FanOut::new("docs", mapper(serde_json::json!("nope")), double())).await;
+        assert!(result.outputs["docs"].error.clone().unwrap().contains("did not output a list"));
+
+        let (result, _) = run(FanOut::new("docs", mapper(serde_json::json!([1, 2])), double()).with_max_items(1)).await;
+        assert!(!result.success);
+
+        let err = FanOut::new("split", mapper(serde_json::json!([])), double()).build().unwrap_err();
+        assert_eq!(err.error_info.unwrap().code, "CONTRACT-004-FAN_OUT_NAME");
+    }
+}
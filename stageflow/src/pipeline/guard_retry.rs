@@ -160,7 +160,11 @@ pub trait StageSpecLike {
 }
 
 /// Runtime state for guard retry tracking.
-#[derive(Debug, Clone, Default)]
+///
+/// `started_at` is a monotonic instant and cannot be persisted; checkpoints
+/// carry `elapsed_seconds` instead and [`Self::restore_clock`] rebuilds the
+/// instant after a restart so timeouts keep counting from the original start.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GuardRetryRuntimeState {
     /// Number of retry attempts made.
     pub attempts: usize,
@@ -169,7 +173,11 @@ pub struct GuardRetryRuntimeState {
     /// Hash of the last output for stagnation detection.
     pub last_hash: Option<String>,
     /// Timestamp when retrying started.
+    #[serde(skip)]
     pub started_at: Option<Instant>,
+    /// Seconds spent retrying, captured when the state is checkpointed.
+    #[serde(default)]
+    pub elapsed_seconds: f64,
 }
 
 impl GuardRetryRuntimeState {
@@ -178,6 +186,28 @@ impl GuardRetryRuntimeState {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns a copy suitable for persisting, with `elapsed_seconds` updated.
+    #[must_use]
+    pub fn checkpoint(&self) -> Self {
+        let mut state = self.clone();
+        if let Some(started_at) = self.started_at {
+            state.elapsed_seconds = started_at.elapsed().as_secs_f64();
+        }
+        state
+    }
+
+    /// Rebuilds `started_at` from `elapsed_seconds` after deserialization.
+    pub fn restore_clock(&mut self) {
+        if self.started_at.is_none() && (self.attempts > 0 || self.elapsed_seconds > 0.0) {
+            let elapsed = std::time::Duration::from_secs_f64(self.elapsed_seconds.max(0.0));
+            self.started_at = Some(
+                Instant::now()
+                    .checked_sub(elapsed)
+                    .unwrap_or_else(Instant::now),
+            );
+        }
+    }
 }
 
 impl StageSpecLike for StageSpec {
@@ -290,4 +320,24 @@ mod tests {
         assert!(hash_a.is_some());
         assert_ne!(hash_all, hash_a);
     }
+
+    #[test]
+    fn test_runtime_state_checkpoint_roundtrip() {
+        let mut state = GuardRetryRuntimeState::new();
+        state.attempts = 2;
+        state.stagnation_hits = 1;
+        state.last_hash = Some("abc".to_string());
+        state.started_at = Instant::now().checked_sub(std::time::Duration::from_secs(5));
+
+        let json = serde_json::to_string(&state.checkpoint()).unwrap();
+        let mut restored: GuardRetryRuntimeState = serde_json::from_str(&json).unwrap();
+        assert!(restored.started_at.is_none());
+        assert!(restored.elapsed_seconds >= 5.0);
+
+        restored.restore_clock();
+        assert_eq!(restored.attempts, 2);
+        assert_eq!(restored.stagnation_hits, 1);
+        assert_eq!(restored.last_hash.as_deref(), Some("abc"));
+        assert!(restored.started_at.unwrap().elapsed().as_secs_f64() >= 5.0);
+    }
 }
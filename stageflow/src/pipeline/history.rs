@@ -0,0 +1,533 @@
+//! Pipeline run history.
+//!
+//! A [`RunStore`] attached with
+//! [`UnifiedStageGraph::with_run_store`](super::UnifiedStageGraph::with_run_store)
+//! receives a [`RunSummary`] for every run: which pipeline and spec version
+//! ran, when, how it ended and what each stage did. [`RunQuery`] filters
+//! the history by pipeline, status and start time.
+//!
+//! With the `sqlite` feature, `SqliteRunStore` keeps the history in a
+//! `SQLite` database.
+
+use super::{StageGraph, UnifiedExecutionResult};
+use crate::core::StageStatus;
+use crate::errors::StageflowError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// Every stage finished without failing the run.
+    Succeeded,
+    /// A stage failed or the engine returned an error.
+    Failed,
+    /// The run was cancelled.
+    Cancelled,
+}
+
+impl RunStatus {
+    /// Returns the status name.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Outcome of one stage in a [`RunSummary`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageSummary {
+    /// The stage name.
+    pub stage: String,
+    /// Final status, or `None` if the stage never ran.
+    pub status: Option<StageStatus>,
+    /// Wall-clock time of the last execution, in milliseconds.
+    pub duration_ms: Option<f64>,
+    /// Error message, for failed stages.
+    pub error: Option<String>,
+}
+
+/// One recorded pipeline run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// The pipeline run id.
+    pub run_id: String,
+    /// The pipeline name.
+    pub pipeline: String,
+    /// Hash of the pipeline's structure, see [`StageGraph::spec_hash`].
+    pub spec_hash: String,
+    /// When the run started.
+    pub started_at: DateTime<Utc>,
+    /// When the run finished.
+    pub finished_at: DateTime<Utc>,
+    /// Total run time in milliseconds.
+    pub duration_ms: f64,
+    /// How the run ended.
+    pub status: RunStatus,
+    /// Run error or cancellation reason.
+    pub error: Option<String>,
+    /// Stages in execution order.
+    pub stages: Vec<StageSummary>,
+}
+
+impl RunSummary {
+    /// Summarizes a finished run.
+    #[must_use]
+    pub fn from_result(
+        graph: &StageGraph,
+        run_id: impl Into<String>,
+        started_at: DateTime<Utc>,
+        result: &UnifiedExecutionResult,
+    ) -> Self {
+        let status = if result.cancelled {
+            RunStatus::Cancelled
+        } else if result.success {
+            RunStatus::Succeeded
+        } else {
+            RunStatus::Failed
+        };
+        let stages = graph
+            .execution_order()
+            .iter()
+            .map(|stage| {
+                let output = result.outputs.get(stage);
+                StageSummary {
+                    stage: stage.clone(),
+                    status: output.map(|o| o.status),
+                    duration_ms: output
+                        .and_then(|o| o.metadata.get("duration_ms"))
+                        .and_then(serde_json::Value::as_f64),
+                    error: output.and_then(|o| o.error.clone()),
+                }
+            })
+            .collect();
+        Self {
+            run_id: run_id.into(),
+            pipeline: graph.name().to_string(),
+            spec_hash: graph.spec_hash(),
+            started_at,
+            finished_at: started_at + duration_from_ms(result.duration_ms),
+            duration_ms: result.duration_ms,
+            status,
+            error: result.error.clone().or_else(|| result.cancel_reason.clone()),
+            stages,
+        }
+    }
+
+    /// Summarizes a run the engine aborted with an error.
+    #[must_use]
+    pub fn from_error(
+        graph: &StageGraph,
+        run_id: impl Into<String>,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+        error: &StageflowError,
+    ) -> Self {
+        Self {
+            run_id: run_id.into(),
+            pipeline: graph.name().to_string(),
+            spec_hash: graph.spec_hash(),
+            started_at,
+            finished_at,
+            duration_ms: (finished_at - started_at)
+                .to_std()
+                .map_or(0.0, |d| d.as_secs_f64() * 1000.0),
+            status: RunStatus::Failed,
+            error: Some(error.to_string()),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Returns the summary of `stage`.
+    #[must_use]
+    pub fn stage(&self, stage: &str) -> Option<&StageSummary> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+}
+
+fn duration_from_ms(ms: f64) -> chrono::Duration {
+    std::time::Duration::try_from_secs_f64(ms / 1000.0)
+        .ok()
+        .and_then(|d| chrono::Duration::from_std(d).ok())
+        .unwrap_or_else(chrono::Duration::zero)
+}
+
+impl StageGraph {
+    /// Returns a hash of the pipeline's structure: stage names, kinds,
+    /// dependencies and execution flags.
+    ///
+    /// Runs of the same pipeline name with different hashes ran different
+    /// versions of the pipeline.
+    #[must_use]
+    pub fn spec_hash(&self) -> String {
+        let mut names: Vec<&String> = self.stage_specs().keys().collect();
+        names.sort();
+        let mut hasher = Sha256::new();
+        hasher.update(self.name().as_bytes());
+        for name in names {
+            let spec = &self.stage_specs()[name];
+            let mut deps: Vec<&String> = spec.dependencies.iter().collect();
+            deps.sort();
+            hasher.update(format!(
+                "\n{name}|{}|{deps:?}|{}|{}|{:?}",
+                spec.kind, spec.conditional, spec.idempotent, spec.context_access
+            ));
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Filters for [`RunStore::query`]; an empty query matches every run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunQuery {
+    /// Only runs of this pipeline.
+    pub pipeline: Option<String>,
+    /// Only runs that ended this way.
+    pub status: Option<RunStatus>,
+    /// Only runs started at or after this time.
+    pub started_after: Option<DateTime<Utc>>,
+    /// Only runs started before this time.
+    pub started_before: Option<DateTime<Utc>>,
+    /// Maximum number of runs returned.
+    pub limit: Option<usize>,
+}
+
+impl RunQuery {
+    /// Creates a query matching every run.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches runs of one pipeline.
+    #[must_use]
+    pub fn with_pipeline(mut self, pipeline: impl Into<String>) -> Self {
+        self.pipeline = Some(pipeline.into());
+        self
+    }
+
+    /// Matches runs with one status.
+    #[must_use]
+    pub fn with_status(mut self, status: RunStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Matches runs started in `[from, to)`.
+    #[must_use]
+    pub fn with_time_range(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        self.started_after = Some(from);
+        self.started_before = Some(to);
+        self
+    }
+
+    /// Returns at most `limit` runs.
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns true if `run` passes every filter.
+    #[must_use]
+    pub fn matches(&self, run: &RunSummary) -> bool {
+        self.pipeline.as_ref().map_or(true, |p| *p == run.pipeline)
+            && self.status.map_or(true, |s| s == run.status)
+            && self.started_after.map_or(true, |t| run.started_at >= t)
+            && self.started_before.map_or(true, |t| run.started_at < t)
+    }
+}
+
+/// Storage for pipeline run history.
+#[async_trait]
+pub trait RunStore: Send + Sync {
+    /// Records a run, replacing any previous record with the same run id.
+    async fn record(&self, run: RunSummary) -> Result<(), StageflowError>;
+
+    /// Returns the run with the given id.
+    async fn get(&self, run_id: &str) -> Result<Option<RunSummary>, StageflowError>;
+
+    /// Returns the runs matching `query`, most recently started first.
+    async fn query(&self, query: &RunQuery) -> Result<Vec<RunSummary>, StageflowError>;
+}
+
+/// In-memory [`RunStore`].
+#[derive(Debug, Default)]
+pub struct InMemoryRunStore {
+    runs: RwLock<HashMap<String, RunSummary>>,
+}
+
+impl InMemoryRunStore {
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of recorded runs.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.runs.read().len()
+    }
+
+    /// Returns true if no run is recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.runs.read().is_empty()
+    }
+}
+
+#[async_trait]
+impl RunStore for InMemoryRunStore {
+    async fn record(&self, run: RunSummary) -> Result<(), StageflowError> {
+        self.runs.write().insert(run.run_id.clone(), run);
+        Ok(())
+    }
+
+    async fn get(&self, run_id: &str) -> Result<Option<RunSummary>, StageflowError> {
+        Ok(self.runs.read().get(run_id).cloned())
+    }
+
+    async fn query(&self, query: &RunQuery) -> Result<Vec<RunSummary>, StageflowError> {
+        let mut runs: Vec<RunSummary> = self.runs.read().values().filter(|run| query.matches(run)).cloned().collect();
+        runs.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.run_id.cmp(&b.run_id)));
+        runs.truncate(query.limit.unwrap_or(usize::MAX));
+        Ok(runs)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+const RUN_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS stageflow_runs (
+        run_id TEXT PRIMARY KEY,
+        pipeline TEXT NOT NULL,
+        status TEXT NOT NULL,
+        started_at_us INTEGER NOT NULL,
+        summary TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS stageflow_runs_by_pipeline ON stageflow_runs (pipeline, started_at_us);
+    CREATE INDEX IF NOT EXISTS stageflow_runs_by_start ON stageflow_runs (started_at_us);
+";
+
+/// [`RunStore`] backed by a `SQLite` database.
+///
+/// Each run is a row holding its summary as JSON, with the pipeline, status
+/// and start time in indexed columns so queries are answered by `SQLite`.
+#[cfg(feature = "sqlite")]
+#[derive(Debug, Clone)]
+pub struct SqliteRunStore {
+    db: crate::utils::sqlite::SqliteDb,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteRunStore {
+    /// Opens or creates the database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or its schema
+    /// cannot be created.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, StageflowError> {
+        Ok(Self {
+            db: crate::utils::sqlite::SqliteDb::open(path, RUN_SCHEMA)?,
+        })
+    }
+
+    /// Creates a store in a private in-memory database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema cannot be created.
+    pub fn in_memory() -> Result<Self, StageflowError> {
+        Ok(Self {
+            db: crate::utils::sqlite::SqliteDb::open_in_memory(RUN_SCHEMA)?,
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn parse_summary(json: &str) -> Result<RunSummary, StageflowError> {
+    serde_json::from_str(json).map_err(|e| StageflowError::Serialization(e.to_string()))
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait]
+impl RunStore for SqliteRunStore {
+    async fn record(&self, run: RunSummary) -> Result<(), StageflowError> {
+        let summary = serde_json::to_string(&run).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO stageflow_runs (run_id, pipeline, status, started_at_us, summary)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    rusqlite::params![
+                        run.run_id,
+                        run.pipeline,
+                        run.status.as_str(),
+                        run.started_at.timestamp_micros(),
+                        summary
+                    ],
+                )
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, run_id: &str) -> Result<Option<RunSummary>, StageflowError> {
+        let run_id = run_id.to_string();
+        let summary: Option<String> = self
+            .db
+            .call(move |conn| {
+                use rusqlite::OptionalExtension;
+                conn.query_row("SELECT summary FROM stageflow_runs WHERE run_id = ?1", [run_id], |row| row.get(0))
+                    .optional()
+            })
+            .await?;
+        summary.as_deref().map(parse_summary).transpose()
+    }
+
+    async fn query(&self, query: &RunQuery) -> Result<Vec<RunSummary>, StageflowError> {
+        use rusqlite::types::Value;
+
+        let mut clauses = Vec::new();
+        let mut params: Vec<Value> = Vec::new();
+        if let Some(pipeline) = &query.pipeline {
+            clauses.push("pipeline = ?");
+            params.push(Value::Text(pipeline.clone()));
+        }
+        if let Some(status) = query.status {
+            clauses.push("status = ?");
+            params.push(Value::Text(status.as_str().to_string()));
+        }
+        if let Some(after) = query.started_after {
+            clauses.push("started_at_us >= ?");
+            params.push(Value::Integer(after.timestamp_micros()));
+        }
+        if let Some(before) = query.started_before {
+            clauses.push("started_at_us < ?");
+            params.push(Value::Integer(before.timestamp_micros()));
+        }
+        let filter = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", clauses.join(" AND "))
+        };
+        // SQLite treats a negative limit as no limit
+        params.push(Value::Integer(
+            query.limit.map_or(-1, |limit| i64::try_from(limit).unwrap_or(i64::MAX)),
+        ));
+        let sql = format!("SELECT summary FROM stageflow_runs {filter} ORDER BY started_at_us DESC, run_id LIMIT ?");
+
+        let rows: Vec<String> = self
+            .db
+            .call(move |conn| {
+                let mut statement = conn.prepare(&sql)?;
+                let rows = statement.query_map(rusqlite::params_from_iter(params), |row| row.get(0))?;
+                rows.collect()
+            })
+            .await?;
+        rows.iter().map(|json| parse_summary(json)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StageOutput;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::NoOpStage;
+    use std::sync::Arc;
+
+    fn graph(name: &str) -> StageGraph {
+        PipelineBuilder::new(name)
+            .stage("a", Arc::new(NoOpStage::new("a")), &[])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn result(success: bool) -> UnifiedExecutionResult {
+        let output = if success { StageOutput::ok_empty() } else { StageOutput::fail("boom") };
+        UnifiedExecutionResult {
+            outputs: HashMap::from([("a".to_string(), output.add_metadata("duration_ms", serde_json::json!(5.0)))]),
+            duration_ms: 1500.0,
+            success,
+            error: (!success).then(|| "Stage 'a' failed".to_string()),
+            cancelled: false,
+            cancel_reason: None,
+            tool_transcript: crate::tools::ToolTranscript::default(),
+            enrichment_cache_stats: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_by_pipeline_status_and_time() {
+        check_queries(&InMemoryRunStore::new()).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_store_queries_and_survives_reopen() {
+        check_queries(&SqliteRunStore::in_memory().unwrap()).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("runs.db");
+        let t0 = Utc::now();
+        let run = RunSummary::from_result(&graph("ingest"), "r1", t0, &result(false));
+        SqliteRunStore::open(&path).unwrap().record(run.clone()).await.unwrap();
+
+        let reopened = SqliteRunStore::open(&path).unwrap();
+        assert_eq!(reopened.get("r1").await.unwrap(), Some(run));
+        assert!(reopened.get("r2").await.unwrap().is_none());
+        let failed = RunQuery::new().with_pipeline("ingest").with_status(RunStatus::Failed);
+        assert_eq!(reopened.query(&failed).await.unwrap().len(), 1);
+    }
+
+    async fn check_queries(store: &dyn RunStore) {
+        let t0 = DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let hour = chrono::Duration::hours(1);
+        store.record(RunSummary::from_result(&graph("ingest"), "r1", t0, &result(true))).await.unwrap();
+        store.record(RunSummary::from_result(&graph("ingest"), "r2", t0 + hour, &result(false))).await.unwrap();
+        store.record(RunSummary::from_result(&graph("report"), "r3", t0 + hour * 2, &result(true))).await.unwrap();
+
+        let r2 = store.get("r2").await.unwrap().unwrap();
+        assert_eq!(r2.status, RunStatus::Failed);
+        assert_eq!(r2.finished_at - r2.started_at, chrono::Duration::milliseconds(1500));
+        assert_eq!(r2.stage("a").unwrap().error.as_deref(), Some("boom"));
+        assert_eq!(r2.spec_hash, graph("ingest").spec_hash());
+        assert_ne!(r2.spec_hash, graph("report").spec_hash());
+
+        let ids = |runs: Vec<RunSummary>| runs.into_iter().map(|r| r.run_id).collect::<Vec<_>>();
+        assert_eq!(ids(store.query(&RunQuery::new()).await.unwrap()), vec!["r3", "r2", "r1"]);
+        assert_eq!(ids(store.query(&RunQuery::new().with_pipeline("ingest")).await.unwrap()), vec!["r2", "r1"]);
+        let succeeded = RunQuery::new().with_status(RunStatus::Succeeded).with_limit(1);
+        assert_eq!(ids(store.query(&succeeded).await.unwrap()), vec!["r3"]);
+        let window = RunQuery::new().with_time_range(t0, t0 + hour * 2);
+        assert_eq!(ids(store.query(&window).await.unwrap()), vec!["r2", "r1"]);
+    }
+
+    #[tokio::test]
+    async fn test_engine_records_runs() {
+        use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+        use crate::pipeline::UnifiedStageGraph;
+
+        let store = Arc::new(InMemoryRunStore::new());
+        let unified = UnifiedStageGraph::new(graph("ingest")).with_run_store(store.clone());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let run_id = ctx.run_id().pipeline_run_id.unwrap().to_string();
+        unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+
+        let run = store.get(&run_id).await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Succeeded);
+        assert_eq!(run.stage("a").unwrap().status, Some(StageStatus::Ok));
+    }
+}
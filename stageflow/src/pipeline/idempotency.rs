@@ -10,9 +10,11 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
+use crate::context::{ExecutionContext, StageContext, StageInputs};
 use crate::core::StageOutput;
+use crate::utils::{Expiry, TtlClock};
 
 /// Cached stage result with metadata.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,22 +27,27 @@ pub struct CachedResult {
     pub expires_at: Option<f64>,
     /// Unix timestamp when the entry was created.
     pub created_at: f64,
+    /// Expiry computed in this process, with its monotonic deadline.
+    #[serde(skip)]
+    expiry: Option<Expiry>,
 }
 
 impl CachedResult {
     /// Creates a new cached result.
     #[must_use]
     pub fn new(output: StageOutput) -> Self {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map(|d| d.as_secs_f64())
-            .unwrap_or(0.0);
+        Self::new_at(output, &TtlClock::default())
+    }
 
+    /// Creates a new cached result stamped with `clock`.
+    #[must_use]
+    pub fn new_at(output: StageOutput, clock: &TtlClock) -> Self {
         Self {
             output,
             params_hash: None,
             expires_at: None,
-            created_at: now,
+            created_at: clock.now_unix_secs(),
+            expiry: None,
         }
     }
 
@@ -53,23 +60,40 @@ impl CachedResult {
 
     /// Sets the expiration time.
     #[must_use]
-    pub fn with_ttl_seconds(mut self, ttl: f64) -> Self {
-        self.expires_at = Some(self.created_at + ttl);
+    pub fn with_ttl_seconds(self, ttl: f64) -> Self {
+        self.with_ttl_at(ttl, &TtlClock::default())
+    }
+
+    /// Sets the expiration time, measured with `clock`.
+    #[must_use]
+    pub fn with_ttl_at(mut self, ttl: f64, clock: &TtlClock) -> Self {
+        let expiry = clock.expiry_from(self.created_at, Duration::from_secs_f64(ttl.max(0.0)));
+        self.expires_at = Some(expiry.expires_at);
+        self.expiry = Some(expiry);
         self
     }
 
     /// Returns true if the entry has expired.
     #[must_use]
     pub fn is_expired(&self) -> bool {
-        if let Some(expires_at) = self.expires_at {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map(|d| d.as_secs_f64())
-                .unwrap_or(0.0);
-            now >= expires_at
-        } else {
-            false
-        }
+        self.is_expired_at(&TtlClock::default())
+    }
+
+    /// Returns true if the entry has expired according to `clock`.
+    ///
+    /// Entries whose TTL was set in this process use its monotonic deadline;
+    /// entries loaded from storage, or whose `expires_at` was changed, are
+    /// checked against the wall clock with the clock's skew tolerance.
+    #[must_use]
+    pub fn is_expired_at(&self, clock: &TtlClock) -> bool {
+        let Some(expires_at) = self.expires_at else {
+            return false;
+        };
+        let expiry = self
+            .expiry
+            .filter(|expiry| expiry.expires_at.to_bits() == expires_at.to_bits())
+            .unwrap_or_else(|| Expiry::at(expires_at));
+        clock.is_expired(&expiry)
     }
 }
 
@@ -93,6 +117,7 @@ pub trait IdempotencyStore: Send + Sync {
 #[derive(Debug, Default)]
 pub struct InMemoryIdempotencyStore {
     entries: Arc<Mutex<HashMap<String, CachedResult>>>,
+    clock: TtlClock,
 }
 
 impl InMemoryIdempotencyStore {
@@ -102,6 +127,13 @@ impl InMemoryIdempotencyStore {
         Self::default()
     }
 
+    /// Sets the clock used to expire entries.
+    #[must_use]
+    pub fn with_clock(mut self, clock: TtlClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Returns the number of entries.
     #[must_use]
     pub fn len(&self) -> usize {
@@ -121,7 +153,7 @@ impl IdempotencyStore for InMemoryIdempotencyStore {
         let mut entries = self.entries.lock();
         
         if let Some(entry) = entries.get(key) {
-            if entry.is_expired() {
+            if entry.is_expired_at(&self.clock) {
                 entries.remove(key);
                 return None;
             }
@@ -133,7 +165,7 @@ impl IdempotencyStore for InMemoryIdempotencyStore {
 
     async fn set(&self, key: &str, mut entry: CachedResult, ttl_seconds: Option<f64>) {
         if let Some(ttl) = ttl_seconds {
-            entry = entry.with_ttl_seconds(ttl);
+            entry = entry.with_ttl_at(ttl, &self.clock);
         }
         self.entries.lock().insert(key.to_string(), entry);
     }
@@ -268,10 +300,319 @@ pub async fn check_idempotency(
     }
 }
 
+/// Error parsing an [`IdempotencyKeyTemplate`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IdempotencyTemplateError {
+    /// A `{` has no matching `}`.
+    #[error("Idempotency key template '{template}' has an unclosed placeholder")]
+    Unclosed {
+        /// The template.
+        template: String,
+    },
+    /// A placeholder is neither a known field nor `dependency.key`.
+    #[error("Idempotency key template '{template}' has unknown placeholder '{name}'")]
+    UnknownPlaceholder {
+        /// The template.
+        template: String,
+        /// The placeholder's name.
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum KeyPart {
+    Literal(String),
+    Stage,
+    PipelineRunId,
+    RequestId,
+    SessionId,
+    UserId,
+    OrgId,
+    InteractionId,
+    InputHash,
+    SnapshotHash,
+    Input { stage: String, key: String },
+}
+
+impl KeyPart {
+    fn placeholder(name: &str) -> Option<Self> {
+        Some(match name {
+            "stage" => Self::Stage,
+            "pipeline_run_id" => Self::PipelineRunId,
+            "request_id" => Self::RequestId,
+            "session_id" => Self::SessionId,
+            "user_id" => Self::UserId,
+            "org_id" => Self::OrgId,
+            "interaction_id" => Self::InteractionId,
+            "input_hash" => Self::InputHash,
+            "snapshot_hash" => Self::SnapshotHash,
+            _ => {
+                let (stage, key) = name.split_once('.').filter(|(stage, key)| !stage.is_empty() && !key.is_empty())?;
+                Self::Input {
+                    stage: stage.to_string(),
+                    key: key.to_string(),
+                }
+            }
+        })
+    }
+}
+
+/// Template for the idempotency key of a stage, such as
+/// `"{user_id}:{stage}:{input_hash}"`.
+///
+/// Placeholders are `{stage}`, the run identity fields `{pipeline_run_id}`,
+/// `{request_id}`, `{session_id}`, `{user_id}`, `{org_id}` and
+/// `{interaction_id}`, `{input_hash}` for a hash of the stage's inputs,
+/// `{snapshot_hash}` for the [fingerprint](crate::context::ContextSnapshot::fingerprint)
+/// of the run's snapshot, and `{dependency.key}` for one input value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IdempotencyKeyTemplate {
+    source: String,
+    parts: Vec<KeyPart>,
+}
+
+impl IdempotencyKeyTemplate {
+    /// Parses a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a placeholder is unclosed or unknown.
+    pub fn parse(template: impl Into<String>) -> Result<Self, IdempotencyTemplateError> {
+        let source = template.into();
+        let mut parts = Vec::new();
+        let mut rest = source.as_str();
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(KeyPart::Literal(rest[..open].to_string()));
+            }
+            let after = &rest[open + 1..];
+            let close = after.find('}').ok_or_else(|| IdempotencyTemplateError::Unclosed {
+                template: source.clone(),
+            })?;
+            let name = &after[..close];
+            parts.push(KeyPart::placeholder(name).ok_or_else(|| IdempotencyTemplateError::UnknownPlaceholder {
+                template: source.clone(),
+                name: name.to_string(),
+            })?);
+            rest = &after[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(KeyPart::Literal(rest.to_string()));
+        }
+        Ok(Self { source, parts })
+    }
+
+    /// Returns the template text.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns true if the key depends on the stage's inputs.
+    #[must_use]
+    pub fn uses_inputs(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, KeyPart::InputHash | KeyPart::Input { .. }))
+    }
+
+    /// Renders the key for a stage execution.
+    ///
+    /// Unset identity fields and missing input values render as empty text.
+    #[must_use]
+    pub fn render(&self, ctx: &StageContext) -> String {
+        let identity = ctx.pipeline_ctx().run_id();
+        let id = |id: Option<uuid::Uuid>| id.map(|id| id.to_string()).unwrap_or_default();
+        let mut key = String::new();
+        for part in &self.parts {
+            match part {
+                KeyPart::Literal(text) => key.push_str(text),
+                KeyPart::Stage => key.push_str(ctx.stage_name()),
+                KeyPart::PipelineRunId => key.push_str(&id(identity.pipeline_run_id)),
+                KeyPart::RequestId => key.push_str(&id(identity.request_id)),
+                KeyPart::SessionId => key.push_str(&id(identity.session_id)),
+                KeyPart::UserId => key.push_str(&id(identity.user_id)),
+                KeyPart::OrgId => key.push_str(&id(identity.org_id)),
+                KeyPart::InteractionId => key.push_str(&id(identity.interaction_id)),
+                KeyPart::InputHash => key.push_str(&hash_inputs(ctx.inputs(), None)),
+                KeyPart::SnapshotHash => key.push_str(&ctx.snapshot().fingerprint().combined().to_string()),
+                KeyPart::Input { stage, key: name } => {
+                    match ctx.inputs().get_unchecked(stage).and_then(|output| output.get(name)) {
+                        Some(serde_json::Value::String(text)) => key.push_str(text),
+                        Some(value) => key.push_str(&value.to_string()),
+                        None => {}
+                    }
+                }
+            }
+        }
+        key
+    }
+}
+
+impl std::str::FromStr for IdempotencyKeyTemplate {
+    type Err = IdempotencyTemplateError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Self::parse(template)
+    }
+}
+
+impl TryFrom<String> for IdempotencyKeyTemplate {
+    type Error = IdempotencyTemplateError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        Self::parse(template)
+    }
+}
+
+impl From<IdempotencyKeyTemplate> for String {
+    fn from(template: IdempotencyKeyTemplate) -> Self {
+        template.source
+    }
+}
+
+impl std::fmt::Display for IdempotencyKeyTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Returns a stage's visible inputs as parameters, keyed `dependency.key`.
+#[must_use]
+pub fn input_params(inputs: &StageInputs) -> serde_json::Value {
+    serde_json::Value::Object(inputs.to_flat_dict().into_iter().collect())
+}
+
+/// Hashes a stage's visible inputs, optionally only the `dependency.key`
+/// fields listed.
+#[must_use]
+pub fn hash_inputs(inputs: &StageInputs, fields: Option<&[String]>) -> String {
+    hash_parameters(&input_params(inputs), fields)
+}
+
+/// A stage execution deduplicated through an idempotency store.
+pub(crate) struct KeyedExecution {
+    key: String,
+    params: serde_json::Value,
+    store: Arc<dyn IdempotencyStore>,
+    config: IdempotencyConfig,
+}
+
+impl KeyedExecution {
+    /// Keys an execution by `template`, if its run has an idempotency store.
+    pub(crate) fn for_stage(template: Option<&IdempotencyKeyTemplate>, ctx: &StageContext) -> Option<Self> {
+        let pipeline = ctx.pipeline_ctx();
+        Some(Self {
+            store: pipeline.idempotency_store()?.clone(),
+            key: template?.render(ctx),
+            params: input_params(ctx.inputs()),
+            config: pipeline.idempotency_config().clone(),
+        })
+    }
+
+    /// Returns the output to use instead of running the stage: the cached
+    /// output of an earlier execution with the same key, or a failure if
+    /// that execution had different inputs.
+    pub(crate) async fn replay(&self, ctx: &StageContext) -> Option<StageOutput> {
+        match check_idempotency(self.store.as_ref(), &self.key, &self.params, &self.config).await {
+            IdempotencyCheckResult::NotFound => None,
+            IdempotencyCheckResult::Found(entry) => {
+                ctx.try_emit_event(
+                    "stage.idempotency_hit",
+                    Some(serde_json::json!({
+                        "stage": ctx.stage_name(),
+                        "key": self.key,
+                    })),
+                );
+                Some(entry.output)
+            }
+            IdempotencyCheckResult::ParamMismatch(mismatch) => {
+                ctx.try_emit_event(
+                    "stage.idempotency_conflict",
+                    Some(serde_json::json!({
+                        "stage": ctx.stage_name(),
+                        "key": self.key,
+                        "expected": mismatch.expected,
+                        "actual": mismatch.actual,
+                    })),
+                );
+                Some(StageOutput::fail(mismatch.to_string()))
+            }
+        }
+    }
+
+    /// Caches an output under the key.
+    pub(crate) async fn record(&self, output: &StageOutput) {
+        let hash = hash_parameters(&self.params, self.config.hash_fields.as_deref());
+        let entry = CachedResult::new(output.clone()).with_params_hash(hash);
+        self.store.set(&self.key, entry, self.config.default_ttl_seconds).await;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn stage_context(identity: crate::context::RunIdentity) -> StageContext {
+        let quote = HashMap::from([
+            ("order".to_string(), serde_json::json!("o-1")),
+            ("total".to_string(), serde_json::json!(42)),
+        ]);
+        let inputs = StageInputs::new(
+            HashMap::from([("quote".to_string(), quote)]),
+            std::collections::HashSet::from(["quote".to_string()]),
+            "charge",
+            true,
+        );
+        let ctx = Arc::new(crate::context::PipelineContext::new(identity));
+        StageContext::new(ctx, "charge", inputs, crate::context::ContextSnapshot::new())
+    }
+
+    #[test]
+    fn test_key_template_renders_identity_and_inputs() {
+        let mut identity = crate::context::RunIdentity::new();
+        let user = uuid::Uuid::new_v4();
+        identity.user_id = Some(user);
+        let ctx = stage_context(identity);
+
+        let template: IdempotencyKeyTemplate = "{user_id}:{stage}:{quote.order}:{quote.total}:{org_id}".parse().unwrap();
+        assert_eq!(template.render(&ctx), format!("{user}:charge:o-1:42:"));
+        assert!(template.uses_inputs());
+        assert!(!IdempotencyKeyTemplate::parse("run-{pipeline_run_id}").unwrap().uses_inputs());
+
+        let hashed = IdempotencyKeyTemplate::parse("{stage}:{input_hash}").unwrap();
+        assert_eq!(hashed.render(&ctx), format!("charge:{}", hash_inputs(ctx.inputs(), None)));
+        let memoized = IdempotencyKeyTemplate::parse("{stage}:{snapshot_hash}").unwrap();
+        assert_eq!(
+            memoized.render(&ctx),
+            format!("charge:{}", crate::context::ContextSnapshot::new().fingerprint().combined())
+        );
+        assert_eq!(
+            input_params(ctx.inputs()),
+            serde_json::json!({ "quote.order": "o-1", "quote.total": 42 })
+        );
+    }
+
+    #[test]
+    fn test_key_template_rejects_bad_placeholders() {
+        assert_eq!(
+            IdempotencyKeyTemplate::parse("{stage").unwrap_err(),
+            IdempotencyTemplateError::Unclosed {
+                template: "{stage".to_string()
+            }
+        );
+        assert!(matches!(
+            IdempotencyKeyTemplate::parse("{tenant}:{stage}"),
+            Err(IdempotencyTemplateError::UnknownPlaceholder { name, .. }) if name == "tenant"
+        ));
+        assert!(IdempotencyKeyTemplate::parse("{.total}").is_err());
+
+        let template: IdempotencyKeyTemplate = serde_json::from_value(serde_json::json!("{stage}:{input_hash}")).unwrap();
+        assert_eq!(serde_json::to_value(&template).unwrap(), "{stage}:{input_hash}");
+        assert!(serde_json::from_value::<IdempotencyKeyTemplate>(serde_json::json!("{nope}")).is_err());
+    }
+
     #[test]
     fn test_cached_result_creation() {
         let output = StageOutput::ok_empty();
@@ -293,6 +634,20 @@ mod tests {
         assert!(cached.is_expired());
     }
 
+    #[tokio::test]
+    async fn test_in_memory_store_expires_on_injected_clock() {
+        let manual = Arc::new(crate::utils::ManualClock::new(
+            chrono::Utc::now(),
+            chrono::Duration::zero(),
+        ));
+        let store = InMemoryIdempotencyStore::new().with_clock(TtlClock::new(manual.clone()));
+        store.set("key1", CachedResult::new(StageOutput::ok_empty()), Some(60.0)).await;
+
+        assert!(store.get("key1").await.is_some());
+        manual.advance(chrono::Duration::seconds(61));
+        assert!(store.get("key1").await.is_none());
+    }
+
     #[tokio::test]
     async fn test_in_memory_store_basic() {
         let store = InMemoryIdempotencyStore::new();
@@ -5,7 +5,8 @@ mod tests {
     use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageContext, StageInputs};
     use crate::core::StageOutput;
     use crate::pipeline::{
-        BackoffStrategy, FailureMode, JitterStrategy, PipelineBuilder, RetryConfig, StageSpec,
+        BackoffStrategy, FailureMode, InMemoryRunStore, JitterStrategy, PipelineBuilder, RetryConfig, RunQuery,
+        RunStore, RunSummary, StageSpec, UnifiedStageGraph,
     };
     use crate::stages::{NoOpStage, Stage};
     use async_trait::async_trait;
@@ -335,4 +336,76 @@ mod tests {
         assert!(output.is_success());
         assert_eq!(output.get("answer"), Some(&serde_json::json!(42)));
     }
+
+    #[derive(Debug)]
+    struct StampingStage {
+        name: String,
+    }
+
+    #[async_trait]
+    impl Stage for StampingStage {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn execute(&self, _ctx: &StageContext) -> StageOutput {
+            let mut data = HashMap::new();
+            data.insert("id".to_string(), serde_json::json!(crate::utils::generate_uuid().to_string()));
+            data.insert("at".to_string(), serde_json::json!(crate::utils::iso_timestamp()));
+            StageOutput::ok(data)
+        }
+    }
+
+    type RecordedEvents = Vec<(String, Option<serde_json::Value>)>;
+
+    async fn deterministic_run(seed: u64) -> (RecordedEvents, HashMap<String, StageOutput>, RunSummary) {
+        let stamp = |name: &str| -> Arc<dyn Stage> {
+            Arc::new(StampingStage { name: name.to_string() })
+        };
+        let graph = PipelineBuilder::new("deterministic")
+            .stage("root", stamp("root"), &[])
+            .unwrap()
+            .stage("left", stamp("left"), &["root"])
+            .unwrap()
+            .stage("right", stamp("right"), &["root"])
+            .unwrap()
+            .stage("join", stamp("join"), &["left", "right"])
+            .unwrap()
+            .build()
+            .unwrap();
+        let runs = Arc::new(InMemoryRunStore::new());
+        let graph = UnifiedStageGraph::new(graph).with_run_store(runs.clone());
+
+        // Without a run id the engine draws one for the run store
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let source = Arc::new(crate::utils::DeterministicSource::new(seed));
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::default())
+                .with_event_sink(sink.clone())
+                .with_deterministic_source(source),
+        );
+
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert!(result.success);
+        let summary = runs.query(&RunQuery::new()).await.unwrap().remove(0);
+        (sink.events(), result.outputs, summary)
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_mode_reproduces_runs() {
+        let (events_a, outputs_a, summary_a) = deterministic_run(42).await;
+        let (events_b, outputs_b, summary_b) = deterministic_run(42).await;
+
+        assert_eq!(events_a, events_b);
+        assert!(events_a.iter().any(|(event_type, _)| event_type == "stage.completed"));
+        for stage in ["root", "left", "right", "join"] {
+            let (a, b) = (serde_json::to_value(&outputs_a[stage]), serde_json::to_value(&outputs_b[stage]));
+            assert_eq!(a.unwrap(), b.unwrap());
+        }
+        assert_eq!(summary_a, summary_b);
+
+        let (_, outputs_c, summary_c) = deterministic_run(7).await;
+        assert_ne!(outputs_a["root"].get("id"), outputs_c["root"].get("id"));
+        assert_ne!(summary_a.run_id, summary_c.run_id);
+    }
 }
@@ -0,0 +1,157 @@
+//! Panic isolation for stage execution.
+//!
+//! [`run_stage`](crate::executor::run_stage) runs each stage under
+//! [`catch_stage_panic`], so a panicking stage becomes a failed
+//! [`StageOutput`] carrying the panic message and backtrace under the
+//! `panic` metadata key instead of an opaque task join error. What the
+//! executor does with that failure is set by its [`PanicPolicy`].
+
+use crate::core::StageOutput;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Once;
+use std::task::{Context, Poll};
+
+/// Metadata key holding a [`StagePanic`] on the output of a panicked stage.
+pub const PANIC_METADATA_KEY: &str = "panic";
+
+/// What an executor does when a stage panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum PanicPolicy {
+    /// Treat the panic as a stage failure, handled by the
+    /// [`FailureMode`](super::FailureMode) like any other (default).
+    #[default]
+    Isolate,
+    /// Abort the run with a [`StageExecution`](crate::errors::StageflowError::StageExecution)
+    /// error naming the stage and the panic message.
+    Abort,
+}
+
+/// A panic caught while running a stage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagePanic {
+    /// The panic message, or a placeholder for non-string payloads.
+    pub message: String,
+    /// The backtrace at the panic site, if one was captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+impl StagePanic {
+    /// Builds a panic from the payload returned by `catch_unwind` or a
+    /// panicked task's `JoinError`.
+    #[must_use]
+    pub fn from_payload(payload: &(dyn Any + Send), backtrace: Option<String>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        Self { message, backtrace }
+    }
+
+    /// Returns the panic recorded on a stage output, if it panicked.
+    #[must_use]
+    pub fn from_output(output: &StageOutput) -> Option<Self> {
+        serde_json::from_value(output.metadata.get(PANIC_METADATA_KEY)?.clone()).ok()
+    }
+
+    /// Converts the panic into a failed output for `stage`.
+    #[must_use]
+    pub fn to_output(&self, stage: &str) -> StageOutput {
+        StageOutput::fail(format!("Stage '{stage}' panicked: {}", self.message))
+            .add_metadata(PANIC_METADATA_KEY, serde_json::to_value(self).unwrap_or_default())
+    }
+}
+
+thread_local! {
+    /// Set while a stage future is polled under [`catch_stage_panic`].
+    static CAPTURING: Cell<bool> = const { Cell::new(false) };
+    /// Backtrace recorded by the panic hook for the catching poll.
+    static LAST_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Chains a panic hook that records a backtrace for panics inside stages.
+///
+/// Other panics, and the output of the previous hook, are unaffected.
+fn install_backtrace_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if CAPTURING.with(Cell::get) {
+                let backtrace = Backtrace::force_capture().to_string();
+                LAST_BACKTRACE.with(|last| *last.borrow_mut() = Some(backtrace));
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Future returned by [`catch_stage_panic`].
+pub struct CatchStagePanic<F> {
+    inner: F,
+}
+
+impl<F: Future + Unpin> Future for CatchStagePanic<F> {
+    type Output = Result<F::Output, StagePanic>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let was_capturing = CAPTURING.with(|capturing| capturing.replace(true));
+        let polled = catch_unwind(AssertUnwindSafe(|| Pin::new(&mut self.inner).poll(cx)));
+        CAPTURING.with(|capturing| capturing.set(was_capturing));
+        match polled {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => {
+                let backtrace = LAST_BACKTRACE.with(|last| last.borrow_mut().take());
+                Poll::Ready(Err(StagePanic::from_payload(payload.as_ref(), backtrace)))
+            }
+        }
+    }
+}
+
+/// Runs a stage future, turning a panic while it is polled into a
+/// [`StagePanic`] with the backtrace at the panic site.
+pub fn catch_stage_panic<F: Future + Unpin>(future: F) -> CatchStagePanic<F> {
+    install_backtrace_hook();
+    CatchStagePanic { inner: future }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_panic_is_caught_with_backtrace() {
+        let caught = catch_stage_panic(Box::pin(async {
+            tokio::task::yield_now().await;
+            panic!("boom");
+        }))
+        .await
+        .unwrap_err();
+
+        assert_eq!(caught.message, "boom");
+        assert!(caught.backtrace.is_some());
+
+        let output = caught.to_output("explode");
+        assert!(output.is_failure());
+        assert_eq!(output.error.as_deref(), Some("Stage 'explode' panicked: boom"));
+        assert_eq!(StagePanic::from_output(&output), Some(caught));
+        assert!(StagePanic::from_output(&StageOutput::fail("plain")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_completed_future_passes_through() {
+        let value = catch_stage_panic(Box::pin(async { 7 })).await.unwrap();
+        assert_eq!(value, 7);
+
+        let formatted = catch_stage_panic(Box::pin(async { panic!("code {}", 42) })).await.unwrap_err();
+        assert_eq!(formatted.message, "code 42");
+    }
+}
@@ -0,0 +1,101 @@
+//! Execution policies keyed by stage kind.
+//!
+//! Kinds describe what a stage is for; a [`KindPolicies`] table on the
+//! [`UnifiedStageGraph`](super::UnifiedStageGraph) turns that into runtime
+//! behaviour. [`KindPolicies::standard`] refuses to re-run non-idempotent
+//! work stages, skips enrichment stages whose inputs were skipped, and runs
+//! guards with a read-only context.
+
+use crate::core::StageKind;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What the graph enforces for stages of one kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindPolicy {
+    /// Stages not declared idempotent fail instead of running a second time,
+    /// e.g. when a guard retry re-queues them.
+    #[serde(default)]
+    pub require_idempotency: bool,
+    /// Stages are skipped when any dependency was skipped.
+    #[serde(default)]
+    pub propagate_skip: bool,
+    /// Stages run with a read-only context.
+    #[serde(default)]
+    pub read_only_context: bool,
+}
+
+impl KindPolicy {
+    /// Creates a policy that enforces nothing.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires stages to be idempotent before they run again.
+    #[must_use]
+    pub fn with_require_idempotency(mut self) -> Self {
+        self.require_idempotency = true;
+        self
+    }
+
+    /// Skips stages whose dependencies were skipped.
+    #[must_use]
+    pub fn with_propagate_skip(mut self) -> Self {
+        self.propagate_skip = true;
+        self
+    }
+
+    /// Runs stages with a read-only context.
+    #[must_use]
+    pub fn with_read_only_context(mut self) -> Self {
+        self.read_only_context = true;
+        self
+    }
+}
+
+/// Per-kind execution policies.
+///
+/// Kinds without an entry get [`KindPolicy::default`], which enforces
+/// nothing; an empty table keeps the engine's behaviour unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindPolicies {
+    policies: HashMap<StageKind, KindPolicy>,
+}
+
+impl KindPolicies {
+    /// Creates an empty table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the recommended table: idempotency for `Work`, skip
+    /// propagation for `Enrich`, and a read-only context for `Guard`.
+    #[must_use]
+    pub fn standard() -> Self {
+        Self::new()
+            .with_policy(StageKind::Work, KindPolicy::new().with_require_idempotency())
+            .with_policy(StageKind::Enrich, KindPolicy::new().with_propagate_skip())
+            .with_policy(StageKind::Guard, KindPolicy::new().with_read_only_context())
+    }
+
+    /// Sets the policy for a kind.
+    #[must_use]
+    pub fn with_policy(mut self, kind: StageKind, policy: KindPolicy) -> Self {
+        self.policies.insert(kind, policy);
+        self
+    }
+
+    /// Returns the policy for a kind.
+    #[must_use]
+    pub fn policy_for(&self, kind: StageKind) -> KindPolicy {
+        self.policies.get(&kind).copied().unwrap_or_default()
+    }
+
+    /// Returns true if no kind has a policy.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.policies.values().all(|policy| *policy == KindPolicy::default())
+    }
+}
@@ -0,0 +1,651 @@
+//! Lint rules for pipeline definitions.
+//!
+//! Linting catches pipelines that build fine but are likely wrong, such as
+//! an enrich stage whose output nobody reads. Rules run over a
+//! [`PipelineManifest`], either loaded from a file or describing a pipeline
+//! built in code, and report [`LintFinding`]s that can carry a [`SpecEdit`]
+//! fixing the problem.
+
+use super::{PipelineManifest, StageManifest};
+use crate::core::StageKind;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+impl PipelineManifest {
+    /// Applies `edit`, returning false if it refers to an unknown stage or
+    /// would remove a stage that others depend on.
+    pub fn apply(&mut self, edit: &SpecEdit) -> bool {
+        match edit {
+            SpecEdit::AddDependency { stage, dependency } => {
+                if self.stage(dependency).is_none() {
+                    return false;
+                }
+                self.stage_mut(stage)
+                    .map(|entry| {
+                        if !entry.depends_on(dependency) {
+                            entry.depends_on.push(dependency.clone());
+                        }
+                    })
+                    .is_some()
+            }
+            SpecEdit::MarkIdempotent { stage } => self
+                .stage_mut(stage)
+                .map(|entry| entry.idempotent = true)
+                .is_some(),
+            SpecEdit::MakeUnconditional { stage } => self
+                .stage_mut(stage)
+                .map(|entry| entry.conditional = false)
+                .is_some(),
+            SpecEdit::AddGuardRetry { guard, retry_stage } => {
+                if self.stage(guard).is_none() || self.stage(retry_stage).is_none() {
+                    return false;
+                }
+                self.guard_retries.insert(guard.clone(), retry_stage.clone());
+                true
+            }
+            SpecEdit::RemoveStage { stage } => {
+                if self.consumers(stage).next().is_some() {
+                    return false;
+                }
+                let before = self.stages.len();
+                self.stages.retain(|entry| entry.name != *stage);
+                self.guard_retries.remove(stage);
+                before != self.stages.len()
+            }
+        }
+    }
+}
+
+/// A change to a pipeline definition that resolves a finding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SpecEdit {
+    /// Makes `stage` depend on `dependency`.
+    AddDependency {
+        /// The stage gaining the dependency.
+        stage: String,
+        /// The stage depended on.
+        dependency: String,
+    },
+    /// Declares `stage` idempotent.
+    MarkIdempotent {
+        /// The stage.
+        stage: String,
+    },
+    /// Clears the conditional flag of `stage`.
+    MakeUnconditional {
+        /// The stage.
+        stage: String,
+    },
+    /// Adds a guard retry policy re-running `retry_stage` when `guard` fails.
+    AddGuardRetry {
+        /// The guard stage.
+        guard: String,
+        /// The stage to re-run.
+        retry_stage: String,
+    },
+    /// Removes `stage` from the pipeline.
+    RemoveStage {
+        /// The stage.
+        stage: String,
+    },
+}
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintSeverity {
+    /// Worth a look, often intentional.
+    Info,
+    /// Probably a mistake.
+    Warning,
+    /// Almost certainly a mistake.
+    Error,
+}
+
+impl std::fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Info => write!(f, "info"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem reported by a lint rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintFinding {
+    /// Code of the rule that reported it.
+    pub rule: String,
+    /// How serious it is.
+    pub severity: LintSeverity,
+    /// Human-readable description.
+    pub message: String,
+    /// Stages involved.
+    pub stages: Vec<String>,
+    /// Edit that resolves it, when one can be derived.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<SpecEdit>,
+}
+
+impl LintFinding {
+    /// Creates a finding about `stage`.
+    #[must_use]
+    pub fn new(
+        rule: impl Into<String>,
+        severity: LintSeverity,
+        stage: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            rule: rule.into(),
+            severity,
+            message: message.into(),
+            stages: vec![stage.into()],
+            suggestion: None,
+        }
+    }
+
+    /// Attaches a suggested fix.
+    #[must_use]
+    pub fn with_suggestion(mut self, edit: SpecEdit) -> Self {
+        self.suggestion = Some(edit);
+        self
+    }
+}
+
+impl std::fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+/// A check over a pipeline manifest.
+pub trait LintRule: Send + Sync {
+    /// Stable code identifying the rule, e.g. `unconsumed-stage`.
+    fn code(&self) -> &'static str;
+
+    /// Returns the problems found in `pipeline`.
+    fn check(&self, pipeline: &PipelineManifest) -> Vec<LintFinding>;
+}
+
+/// Flags enrich, route and guard stages that no other stage depends on.
+///
+/// These kinds exist to feed or gate downstream stages, so without
+/// consumers their work is wasted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnconsumedStageRule;
+
+impl LintRule for UnconsumedStageRule {
+    fn code(&self) -> &'static str {
+        "unconsumed-stage"
+    }
+
+    fn check(&self, pipeline: &PipelineManifest) -> Vec<LintFinding> {
+        pipeline
+            .stages
+            .iter()
+            .filter(|stage| matches!(stage.kind, StageKind::Enrich | StageKind::Route | StageKind::Guard))
+            .filter(|stage| pipeline.consumers(&stage.name).next().is_none())
+            .map(|stage| {
+                LintFinding::new(
+                    self.code(),
+                    LintSeverity::Warning,
+                    &stage.name,
+                    format!("{} stage '{}' has no consumers", stage.kind, stage.name),
+                )
+                .with_suggestion(SpecEdit::RemoveStage {
+                    stage: stage.name.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Flags guard stages without a guard retry policy.
+///
+/// The suggested retry stage is one of the guard's dependencies, which the
+/// retry policy requires.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuardWithoutRetryRule;
+
+impl LintRule for GuardWithoutRetryRule {
+    fn code(&self) -> &'static str {
+        "guard-without-retry"
+    }
+
+    fn check(&self, pipeline: &PipelineManifest) -> Vec<LintFinding> {
+        pipeline
+            .stages
+            .iter()
+            .filter(|stage| stage.kind == StageKind::Guard)
+            .filter(|stage| !pipeline.guard_retries.contains_key(&stage.name))
+            .map(|stage| {
+                let finding = LintFinding::new(
+                    self.code(),
+                    LintSeverity::Info,
+                    &stage.name,
+                    format!("guard '{}' fails the run without retrying", stage.name),
+                );
+                match stage.depends_on.first() {
+                    Some(retry_stage) => finding.with_suggestion(SpecEdit::AddGuardRetry {
+                        guard: stage.name.clone(),
+                        retry_stage: retry_stage.clone(),
+                    }),
+                    None => finding,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Flags work stages that are not declared idempotent.
+///
+/// Retries, resumes and redelivery re-run stages, so side effects that
+/// are not deduplicated may happen twice.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonIdempotentWorkRule;
+
+impl LintRule for NonIdempotentWorkRule {
+    fn code(&self) -> &'static str {
+        "non-idempotent-work"
+    }
+
+    fn check(&self, pipeline: &PipelineManifest) -> Vec<LintFinding> {
+        pipeline
+            .stages
+            .iter()
+            .filter(|stage| stage.kind == StageKind::Work && !stage.idempotent)
+            .map(|stage| {
+                LintFinding::new(
+                    self.code(),
+                    LintSeverity::Warning,
+                    &stage.name,
+                    format!(
+                        "work stage '{}' is not idempotent; deduplicate its side effects before marking it",
+                        stage.name
+                    ),
+                )
+                .with_suggestion(SpecEdit::MarkIdempotent {
+                    stage: stage.name.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Flags conditional stages with no route stage upstream.
+///
+/// A conditional stage is skipped when an upstream output carries a
+/// `skip_reason`, which route stages produce. Without one the stage always
+/// runs, so the suggestion is either a dependency on a route stage that is
+/// not downstream of it, or dropping the conditional flag.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnreachableSkipRule;
+
+impl LintRule for UnreachableSkipRule {
+    fn code(&self) -> &'static str {
+        "unreachable-skip"
+    }
+
+    fn check(&self, pipeline: &PipelineManifest) -> Vec<LintFinding> {
+        pipeline
+            .stages
+            .iter()
+            .filter(|stage| stage.conditional)
+            .filter_map(|stage| {
+                let upstream = pipeline.upstream(&stage.name);
+                let has_router = upstream
+                    .iter()
+                    .filter_map(|name| pipeline.stage(name))
+                    .any(|dep| dep.kind == StageKind::Route);
+                if has_router {
+                    return None;
+                }
+                let router = pipeline.stages.iter().find(|candidate| {
+                    candidate.kind == StageKind::Route
+                        && !pipeline.upstream(&candidate.name).contains(&stage.name)
+                });
+                let suggestion = match router {
+                    Some(router) => SpecEdit::AddDependency {
+                        stage: stage.name.clone(),
+                        dependency: router.name.clone(),
+                    },
+                    None => SpecEdit::MakeUnconditional {
+                        stage: stage.name.clone(),
+                    },
+                };
+                Some(
+                    LintFinding::new(
+                        self.code(),
+                        LintSeverity::Warning,
+                        &stage.name,
+                        format!(
+                            "conditional stage '{}' has no upstream stage that can skip it",
+                            stage.name
+                        ),
+                    )
+                    .with_suggestion(suggestion),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Flags declared keys that cannot be used.
+///
+/// An output key is unused when every consumer of the stage declares the
+/// keys it reads from it and none reads that key. An input key that its
+/// producer declares it does not output can never be read, which is an
+/// error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnusedDeclaredKeyRule;
+
+impl LintRule for UnusedDeclaredKeyRule {
+    fn code(&self) -> &'static str {
+        "unused-declared-key"
+    }
+
+    fn check(&self, pipeline: &PipelineManifest) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        for stage in &pipeline.stages {
+            for (producer, keys) in &stage.inputs {
+                let Some(produced) = pipeline.stage(producer).map(|p| &p.outputs).filter(|o| !o.is_empty()) else {
+                    continue;
+                };
+                for key in keys.iter().filter(|key| !produced.contains(*key)) {
+                    let mut finding = LintFinding::new(
+                        self.code(),
+                        LintSeverity::Error,
+                        &stage.name,
+                        format!("stage '{}' reads '{producer}.{key}', which '{producer}' does not output", stage.name),
+                    );
+                    finding.stages.push(producer.clone());
+                    findings.push(finding);
+                }
+            }
+
+            let consumers: Vec<&StageManifest> = pipeline.consumers(&stage.name).collect();
+            let reads: Option<BTreeSet<&String>> = consumers
+                .iter()
+                .map(|consumer| consumer.inputs.get(&stage.name))
+                .collect::<Option<Vec<_>>>()
+                .filter(|_| !consumers.is_empty())
+                .map(|keys| keys.into_iter().flatten().collect());
+            let Some(reads) = reads else { continue };
+            for key in stage.outputs.iter().filter(|key| !reads.contains(key)) {
+                findings.push(LintFinding::new(
+                    self.code(),
+                    LintSeverity::Warning,
+                    &stage.name,
+                    format!("stage '{}' declares output '{key}', which no consumer reads", stage.name),
+                ));
+            }
+        }
+        findings
+    }
+}
+
+/// Findings of a lint run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintReport {
+    /// Findings in rule order.
+    pub findings: Vec<LintFinding>,
+}
+
+impl LintReport {
+    /// Returns true if nothing was found.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Returns the most serious severity found.
+    #[must_use]
+    pub fn max_severity(&self) -> Option<LintSeverity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    /// Returns the findings reported by `rule`.
+    pub fn by_rule<'a>(&'a self, rule: &'a str) -> impl Iterator<Item = &'a LintFinding> + 'a {
+        self.findings.iter().filter(move |finding| finding.rule == rule)
+    }
+
+    /// Returns `pipeline` with the suggestions applied.
+    ///
+    /// Removals go last and are skipped for stages that another fix gave a
+    /// consumer. Suggestions are derived independently, so lint the result
+    /// again.
+    #[must_use]
+    pub fn fix(&self, pipeline: &PipelineManifest) -> PipelineManifest {
+        let mut edits: Vec<&SpecEdit> = self
+            .findings
+            .iter()
+            .filter_map(|finding| finding.suggestion.as_ref())
+            .collect();
+        edits.sort_by_key(|edit| matches!(edit, SpecEdit::RemoveStage { .. }));
+        let mut fixed = pipeline.clone();
+        for edit in edits {
+            fixed.apply(edit);
+        }
+        fixed
+    }
+}
+
+/// Runs a set of lint rules.
+pub struct PipelineLinter {
+    rules: Vec<Box<dyn LintRule>>,
+    severities: HashMap<String, LintSeverity>,
+}
+
+impl Default for PipelineLinter {
+    fn default() -> Self {
+        Self::empty()
+            .with_rule(UnconsumedStageRule)
+            .with_rule(GuardWithoutRetryRule)
+            .with_rule(NonIdempotentWorkRule)
+            .with_rule(UnreachableSkipRule)
+            .with_rule(UnusedDeclaredKeyRule)
+    }
+}
+
+impl PipelineLinter {
+    /// Creates a linter with the built-in rules.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a linter without rules.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            severities: HashMap::new(),
+        }
+    }
+
+    /// Adds a rule.
+    #[must_use]
+    pub fn with_rule(mut self, rule: impl LintRule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Removes the rule with code `code`.
+    #[must_use]
+    pub fn without_rule(mut self, code: &str) -> Self {
+        self.rules.retain(|rule| rule.code() != code);
+        self
+    }
+
+    /// Reports findings of rule `code` at `severity` instead of the rule's
+    /// own.
+    #[must_use]
+    pub fn with_severity(mut self, code: impl Into<String>, severity: LintSeverity) -> Self {
+        self.severities.insert(code.into(), severity);
+        self
+    }
+
+    /// Returns the codes of the rules, in run order.
+    #[must_use]
+    pub fn rule_codes(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|rule| rule.code()).collect()
+    }
+
+    /// Runs every rule over `pipeline`.
+    #[must_use]
+    pub fn lint(&self, pipeline: &PipelineManifest) -> LintReport {
+        let findings = self
+            .rules
+            .iter()
+            .flat_map(|rule| rule.check(pipeline))
+            .map(|mut finding| {
+                if let Some(severity) = self.severities.get(&finding.rule) {
+                    finding.severity = *severity;
+                }
+                finding
+            })
+            .collect();
+        LintReport { findings }
+    }
+}
+
+impl std::fmt::Debug for PipelineLinter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineLinter")
+            .field("rules", &self.rule_codes())
+            .field("severities", &self.severities)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{GuardRetryPolicy, GuardRetryStrategy, PipelineBuilder, StageSpec};
+    use crate::stages::NoOpStage;
+    use std::sync::Arc;
+
+    fn spec(name: &str, kind: StageKind, deps: &[&str]) -> StageSpec {
+        StageSpec::new(name, Arc::new(NoOpStage::new(name)))
+            .with_kind(kind)
+            .with_dependencies(deps.iter().copied())
+    }
+
+    fn builder() -> PipelineBuilder {
+        let mut builder = PipelineBuilder::new("lint");
+        for spec in [
+            spec("input", StageKind::Transform, &[]),
+            spec("profile", StageKind::Enrich, &[]),
+            spec("router", StageKind::Route, &["input"]),
+            spec("llm", StageKind::Agent, &["input"]),
+            spec("policy", StageKind::Guard, &["llm"]),
+            spec("notify", StageKind::Work, &["policy"]).conditional(),
+            spec("persist", StageKind::Work, &["policy"]).with_idempotent(),
+        ] {
+            builder.add_stage_spec(spec).unwrap();
+        }
+        builder
+    }
+
+    #[test]
+    fn test_builtin_rules_report_findings_with_fixes() {
+        let manifest = builder().manifest();
+        let report = PipelineLinter::new().lint(&manifest);
+
+        let unconsumed: Vec<&str> = report
+            .by_rule("unconsumed-stage")
+            .map(|finding| finding.stages[0].as_str())
+            .collect();
+        assert_eq!(unconsumed, vec!["profile", "router"]);
+
+        let guard = report.by_rule("guard-without-retry").next().unwrap();
+        assert_eq!(
+            guard.suggestion,
+            Some(SpecEdit::AddGuardRetry {
+                guard: "policy".into(),
+                retry_stage: "llm".into(),
+            })
+        );
+
+        let work: Vec<&str> = report
+            .by_rule("non-idempotent-work")
+            .map(|finding| finding.stages[0].as_str())
+            .collect();
+        assert_eq!(work, vec!["notify"]);
+
+        let skip = report.by_rule("unreachable-skip").next().unwrap();
+        assert_eq!(
+            skip.suggestion,
+            Some(SpecEdit::AddDependency {
+                stage: "notify".into(),
+                dependency: "router".into(),
+            })
+        );
+        assert_eq!(report.max_severity(), Some(LintSeverity::Warning));
+
+        let fixed = report.fix(&manifest);
+        assert!(fixed.stage("profile").is_none());
+        assert!(fixed.stage("router").is_some());
+        let remaining = PipelineLinter::new().lint(&fixed);
+        assert!(remaining.is_clean(), "{:?}", remaining.findings);
+    }
+
+    #[test]
+    fn test_linter_configuration_and_serialized_manifest() {
+        let strategy = GuardRetryStrategy::new().with_policy("policy", GuardRetryPolicy::new("llm"));
+        let manifest = builder().manifest().with_guard_retry_strategy(&strategy);
+        let linter = PipelineLinter::new()
+            .without_rule("unconsumed-stage")
+            .with_severity("non-idempotent-work", LintSeverity::Error);
+
+        let report = linter.lint(&manifest);
+        assert_eq!(report.by_rule("guard-without-retry").count(), 0);
+        assert_eq!(report.by_rule("unconsumed-stage").count(), 0);
+        assert_eq!(report.max_severity(), Some(LintSeverity::Error));
+
+        let json = serde_json::json!({
+            "name": "from-json",
+            "stages": [
+                {"name": "fetch", "kind": "transform"},
+                {"name": "maybe", "kind": "transform", "depends_on": ["fetch"], "conditional": true}
+            ]
+        });
+        let manifest: PipelineManifest = serde_json::from_value(json).unwrap();
+        let report = PipelineLinter::new().lint(&manifest);
+        assert_eq!(
+            report.findings[0].suggestion,
+            Some(SpecEdit::MakeUnconditional { stage: "maybe".into() })
+        );
+        let value = serde_json::to_value(&report.findings[0]).unwrap();
+        assert_eq!(value["suggestion"]["op"], "make_unconditional");
+    }
+
+    #[test]
+    fn test_unused_declared_keys() {
+        let mut builder = PipelineBuilder::new("keys");
+        for spec in [
+            spec("fetch", StageKind::Transform, &[]).with_output_key("body").with_output_key("headers"),
+            spec("parse", StageKind::Transform, &["fetch"]).with_input_key("fetch", "body").with_input_key("fetch", "status"),
+        ] {
+            builder.add_stage_spec(spec).unwrap();
+        }
+        let report = PipelineLinter::empty().with_rule(UnusedDeclaredKeyRule).lint(&builder.manifest());
+        let messages: Vec<&str> = report.findings.iter().map(|finding| finding.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "stage 'fetch' declares output 'headers', which no consumer reads",
+                "stage 'parse' reads 'fetch.status', which 'fetch' does not output",
+            ]
+        );
+        assert_eq!(report.max_severity(), Some(LintSeverity::Error));
+
+        // A consumer that does not declare its inputs may read any key
+        builder.add_stage_spec(spec("store", StageKind::Work, &["fetch"])).unwrap();
+        let report = PipelineLinter::empty().with_rule(UnusedDeclaredKeyRule).lint(&builder.manifest());
+        assert_eq!(report.findings.len(), 1);
+    }
+}
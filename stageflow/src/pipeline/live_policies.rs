@@ -0,0 +1,291 @@
+//! Retry policies that can be replaced while a pipeline runs.
+//!
+//! [`UnifiedStageGraph`](super::UnifiedStageGraph) reads its guard-retry
+//! strategy and per-stage [`RetryConfig`]s through a [`PolicyHandle`] each
+//! time it decides whether to retry, so a new policy applies from the next
+//! decision on: raising `max_attempts` gives a struggling guard-retry loop
+//! more room, and removing its policy lets the next failure end the run.
+
+use super::{
+    hash_retry_payload, should_retry, GuardRetryPolicy, GuardRetryRuntimeState, GuardRetryStrategy, RetryConfig,
+    RetryDecision, RetryState, SharedCheckpoint, StageSpec,
+};
+use crate::context::{ExecutionContext, PipelineContext};
+use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::events::PipelineEvent;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Policies {
+    guard_retry: RwLock<Option<Arc<GuardRetryStrategy>>>,
+    stage_retry: RwLock<HashMap<String, Arc<RetryConfig>>>,
+    /// Stage names and kinds that new policies are validated against.
+    stages: HashMap<String, StageSpec>,
+}
+
+/// Shared handle to the retry policies of a [`UnifiedStageGraph`](super::UnifiedStageGraph).
+///
+/// Clones share the same policies. Readers take the current policy as an
+/// `Arc`, so a swap never blocks on, or changes, a decision in progress.
+#[derive(Debug, Clone)]
+pub struct PolicyHandle {
+    inner: Arc<Policies>,
+}
+
+impl PolicyHandle {
+    pub(crate) fn new(stages: HashMap<String, StageSpec>) -> Self {
+        Self {
+            inner: Arc::new(Policies {
+                guard_retry: RwLock::new(None),
+                stage_retry: RwLock::new(HashMap::new()),
+                stages,
+            }),
+        }
+    }
+
+    /// Returns the current guard-retry strategy.
+    #[must_use]
+    pub fn guard_retry_strategy(&self) -> Option<Arc<GuardRetryStrategy>> {
+        self.inner.guard_retry.read().clone()
+    }
+
+    /// Replaces the guard-retry strategy, or disables guard retries with
+    /// `None`.
+    ///
+    /// Attempt and stagnation counts already recorded are kept and judged
+    /// against the new limits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the strategy references unknown stages or
+    /// non-guard stages.
+    pub fn set_guard_retry_strategy(&self, strategy: Option<GuardRetryStrategy>) -> Result<(), StageflowError> {
+        if let Some(strategy) = &strategy {
+            strategy.validate(&self.inner.stages).map_err(StageflowError::Internal)?;
+        }
+        tracing::info!(enabled = strategy.is_some(), "Guard-retry strategy replaced");
+        *self.inner.guard_retry.write() = strategy.map(Arc::new);
+        Ok(())
+    }
+
+    /// Returns the retry config of a stage.
+    #[must_use]
+    pub fn stage_retry(&self, stage: &str) -> Option<Arc<RetryConfig>> {
+        self.inner.stage_retry.read().get(stage).cloned()
+    }
+
+    /// Sets the retry config of a stage, or stops retrying it with `None`.
+    ///
+    /// A stage is retried while its output status is listed in
+    /// [`RetryConfig::retry_on_status`]. Attempts already made count
+    /// against the new `max_attempts`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stage does not exist.
+    pub fn set_stage_retry(&self, stage: &str, config: Option<RetryConfig>) -> Result<(), StageflowError> {
+        if !self.inner.stages.contains_key(stage) {
+            return Err(StageflowError::Internal(format!(
+                "Retry config references unknown stage '{stage}'"
+            )));
+        }
+        tracing::info!(stage, enabled = config.is_some(), "Stage retry config replaced");
+        let mut stage_retry = self.inner.stage_retry.write();
+        match config {
+            Some(config) => stage_retry.insert(stage.to_string(), Arc::new(config)),
+            None => stage_retry.remove(stage),
+        };
+        Ok(())
+    }
+
+    /// Runs a stage again for as long as its retry config asks for it.
+    ///
+    /// The config is read on every attempt so a swap applies to the next one,
+    /// and each scheduled attempt is saved to the checkpoint before it runs.
+    /// Returns true if the stage gave up retrying for good.
+    pub(crate) async fn retry_stage<F, Fut>(
+        &self,
+        stage_name: &str,
+        ctx: &PipelineContext,
+        checkpoint: Option<&SharedCheckpoint>,
+        retry_state: &mut RetryState,
+        output: &mut StageOutput,
+        run_again: F,
+    ) -> Result<bool, StageflowError>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = StageOutput>,
+    {
+        while let Some(config) = self
+            .stage_retry(stage_name)
+            .filter(|config| config.retry_on_status.contains(&output.status.to_string()))
+        {
+            let RetryDecision::Retry(delay) = should_retry(retry_state, &config, stage_name) else {
+                return Ok(true);
+            };
+            if ctx.reserve_retry_budget(stage_name, delay).is_err() {
+                return Ok(true);
+            }
+            // The run budget cancels the run, which is not the stage's fault
+            if ctx.reserve_retry().is_err() {
+                break;
+            }
+            ctx.try_emit_event(
+                "stage.retry_scheduled",
+                Some(serde_json::json!({
+                    "stage": stage_name,
+                    "attempt": retry_state.attempt,
+                    "max_attempts": config.max_attempts,
+                    "delay_ms": u64::try_from(delay.as_millis()).unwrap_or(u64::MAX),
+                })),
+            );
+            if let Some(cp) = checkpoint {
+                cp.update(|cp| cp.set_retry_state(stage_name, retry_state)).await?;
+            }
+            tokio::time::sleep(delay).await;
+            let attempt_start = Instant::now();
+            *output = run_again().await;
+            ctx.record_retry_time(attempt_start.elapsed());
+        }
+        Ok(false)
+    }
+}
+
+/// What to do after a guard with a retry policy fails.
+pub(crate) enum GuardRetryVerdict {
+    /// Run the guard's retry stage, then the guard, again.
+    Retry,
+    /// Keep the failure: the policy is exhausted or the run cannot afford
+    /// another attempt.
+    Stand,
+    /// The run budget ran out and cancelled the run.
+    Cancelled,
+}
+
+/// Records a guard failure against its retry state and decides whether to
+/// retry it.
+pub(crate) async fn decide_guard_retry(
+    ctx: &PipelineContext,
+    guard: &str,
+    output: &StageOutput,
+    policy: &GuardRetryPolicy,
+    state: &mut GuardRetryRuntimeState,
+    checkpoint: Option<&SharedCheckpoint>,
+) -> Result<GuardRetryVerdict, StageflowError> {
+    if state.started_at.is_none() {
+        state.started_at = Some(Instant::now());
+    }
+
+    state.attempts += 1;
+
+    let retry_hash = hash_retry_payload(Some(output), policy.hash_fields.as_deref());
+    if retry_hash.is_some() && retry_hash == state.last_hash {
+        state.stagnation_hits += 1;
+    } else {
+        state.stagnation_hits = 0;
+    }
+    state.last_hash = retry_hash;
+
+    if let Some(cp) = checkpoint {
+        cp.update(|cp| cp.set_guard_state(guard, state)).await?;
+    }
+
+    ctx.try_emit_event(
+        "guard_retry.attempt",
+        Some(serde_json::json!({
+            "guard": guard,
+            "attempt": state.attempts,
+            "retry_stage": policy.retry_stage,
+            "max_attempts": policy.max_attempts,
+            "stagnation_hits": state.stagnation_hits,
+            "timeout_seconds": policy.timeout_seconds,
+        })),
+    );
+
+    let exceeded_attempts = state.attempts >= policy.max_attempts;
+    let exceeded_stagnation = state.stagnation_hits >= policy.stagnation_limit;
+    let exceeded_timeout = policy
+        .timeout_seconds
+        .and_then(|timeout| state.started_at.map(|t| t.elapsed().as_secs_f64() >= timeout))
+        .unwrap_or(false);
+
+    if exceeded_attempts || exceeded_stagnation || exceeded_timeout {
+        ctx.try_emit_event(
+            "guard_retry.exhausted",
+            Some(serde_json::json!({
+                "guard": guard,
+                "attempts": state.attempts,
+                "stagnation_hits": state.stagnation_hits,
+                "retry_stage": policy.retry_stage,
+                "timeout_seconds": policy.timeout_seconds,
+                "reason": if exceeded_timeout { "timeout" } else if exceeded_stagnation { "stagnation" } else { "max_attempts" },
+            })),
+        );
+        return Ok(GuardRetryVerdict::Stand);
+    }
+    if ctx.reserve_retry_budget(guard, Duration::ZERO).is_err() {
+        return Ok(GuardRetryVerdict::Stand);
+    }
+    if ctx.reserve_retry().is_err() {
+        return Ok(GuardRetryVerdict::Cancelled);
+    }
+    ctx.emit_pipeline_event(PipelineEvent::GuardRetryScheduled {
+        guard: guard.to_string(),
+        attempt: state.attempts,
+        retry_stage: policy.retry_stage.clone(),
+        stagnation_hits: state.stagnation_hits,
+        timeout_seconds: policy.timeout_seconds,
+    });
+    Ok(GuardRetryVerdict::Retry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StageKind;
+    use crate::pipeline::GuardRetryPolicy;
+    use crate::stages::NoOpStage;
+
+    fn handle() -> PolicyHandle {
+        let specs = [
+            StageSpec::new("draft", Arc::new(NoOpStage::new("draft"))),
+            StageSpec::new("check", Arc::new(NoOpStage::new("check")))
+                .with_kind(StageKind::Guard)
+                .with_dependency("draft"),
+        ];
+        PolicyHandle::new(specs.into_iter().map(|spec| (spec.name.clone(), spec)).collect())
+    }
+
+    #[test]
+    fn test_swaps_are_validated_and_visible_to_clones() {
+        let handle = handle();
+        let reader = handle.clone();
+
+        let strategy = GuardRetryStrategy::new().with_policy("check", GuardRetryPolicy::new("draft"));
+        handle.set_guard_retry_strategy(Some(strategy)).unwrap();
+        let before = reader.guard_retry_strategy().unwrap();
+        assert_eq!(before.get_policy("check").unwrap().max_attempts, 2);
+
+        let raised = GuardRetryStrategy::new()
+            .with_policy("check", GuardRetryPolicy::new("draft").with_max_attempts(5));
+        handle.set_guard_retry_strategy(Some(raised)).unwrap();
+        assert_eq!(reader.guard_retry_strategy().unwrap().get_policy("check").unwrap().max_attempts, 5);
+        // Policies taken before the swap are unchanged
+        assert_eq!(before.get_policy("check").unwrap().max_attempts, 2);
+
+        let bad = GuardRetryStrategy::new().with_policy("draft", GuardRetryPolicy::new("check"));
+        assert!(handle.set_guard_retry_strategy(Some(bad)).is_err());
+        handle.set_guard_retry_strategy(None).unwrap();
+        assert!(reader.guard_retry_strategy().is_none());
+
+        handle.set_stage_retry("draft", Some(RetryConfig::new().with_max_attempts(4))).unwrap();
+        assert_eq!(reader.stage_retry("draft").unwrap().max_attempts, 4);
+        assert!(handle.set_stage_retry("missing", Some(RetryConfig::new())).is_err());
+        handle.set_stage_retry("draft", None).unwrap();
+        assert!(reader.stage_retry("draft").is_none());
+    }
+}
@@ -0,0 +1,599 @@
+//! Bounded, intentional loops over a subgraph of stages.
+//!
+//! Agent workflows often repeat a plan → act → observe cycle until a goal is
+//! met. Expressing that as graph edges would be rejected as a cycle, so a
+//! [`LoopGroup`] instead wraps the body in its own acyclic graph and runs it
+//! repeatedly as a single stage of the outer pipeline.
+//!
+//! Each iteration, body stages can read the loop state through
+//! `inputs.get(<loop name>)`, which holds the carried keys plus the current
+//! `iteration` number. Besides it they see the inputs of the loop stage and
+//! the outputs of the body stages they depend on. After an iteration, the carried keys are taken from
+//! the body outputs (later stages in execution order win) and handed to the
+//! next iteration and to the termination predicate.
+
+use super::{PipelineBuilder, StageGraph};
+use crate::context::{ExecutionContext, StageContext, StageInputs};
+use crate::core::{StageOutput, StageStatus};
+use crate::errors::{ContractErrorInfo, PipelineValidationError};
+use crate::executor::{DependencyTracker, run_stage};
+use crate::stages::Stage;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Predicate deciding whether a loop should stop after an iteration.
+pub type LoopPredicate = Arc<dyn Fn(&LoopIteration) -> bool + Send + Sync>;
+
+/// State observed at the end of one loop iteration.
+#[derive(Debug, Clone)]
+pub struct LoopIteration {
+    /// One-based iteration number.
+    pub iteration: usize,
+    /// Loop-carried state after this iteration.
+    pub state: HashMap<String, serde_json::Value>,
+    /// Output data of each body stage in this iteration.
+    pub outputs: HashMap<String, HashMap<String, serde_json::Value>>,
+}
+
+impl LoopIteration {
+    /// Returns a carried state value.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&serde_json::Value> {
+        self.state.get(key)
+    }
+}
+
+/// Why a loop stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopTermination {
+    /// The termination predicate returned true.
+    Predicate,
+    /// The iteration limit was reached.
+    MaxIterations,
+}
+
+impl LoopTermination {
+    /// Returns the name used in events and output data.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Predicate => "predicate",
+            Self::MaxIterations => "max_iterations",
+        }
+    }
+}
+
+/// Declares a repeating subgraph with an iteration limit.
+#[derive(Clone)]
+pub struct LoopGroup {
+    name: String,
+    body: PipelineBuilder,
+    max_iterations: usize,
+    carried_keys: Vec<String>,
+    initial_state: HashMap<String, serde_json::Value>,
+    until: Option<LoopPredicate>,
+    fail_on_exhaustion: bool,
+}
+
+impl std::fmt::Debug for LoopGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoopGroup")
+            .field("name", &self.name)
+            .field("max_iterations", &self.max_iterations)
+            .field("carried_keys", &self.carried_keys)
+            .field("has_predicate", &self.until.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoopGroup {
+    /// Creates a loop that runs its body at most `max_iterations` times.
+    #[must_use]
+    pub fn new(name: impl Into<String>, max_iterations: usize) -> Self {
+        let name = name.into();
+        Self {
+            body: PipelineBuilder::new(name.clone()),
+            name,
+            max_iterations,
+            carried_keys: Vec::new(),
+            initial_state: HashMap::new(),
+            until: None,
+            fail_on_exhaustion: false,
+        }
+    }
+
+    /// Adds a body stage. Dependencies refer to other body stages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a dependency is unknown or forms a cycle within the body.
+    pub fn stage(
+        mut self,
+        name: impl Into<String>,
+        runner: Arc<dyn Stage>,
+        dependencies: &[&str],
+    ) -> Result<Self, PipelineValidationError> {
+        self.body = self.body.stage(name, runner, dependencies)?;
+        Ok(self)
+    }
+
+    /// Carries an output key from one iteration to the next.
+    #[must_use]
+    pub fn carry(mut self, key: impl Into<String>) -> Self {
+        self.carried_keys.push(key.into());
+        self
+    }
+
+    /// Sets the value of a carried key before the first iteration.
+    #[must_use]
+    pub fn with_initial_state(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        let key = key.into();
+        if !self.carried_keys.contains(&key) {
+            self.carried_keys.push(key.clone());
+        }
+        self.initial_state.insert(key, value);
+        self
+    }
+
+    /// Stops the loop once the predicate returns true.
+    #[must_use]
+    pub fn until(mut self, predicate: impl Fn(&LoopIteration) -> bool + Send + Sync + 'static) -> Self {
+        self.until = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Fails the loop stage if the iteration limit is hit before the predicate holds.
+    #[must_use]
+    pub fn fail_on_exhaustion(mut self) -> Self {
+        self.fail_on_exhaustion = true;
+        self
+    }
+
+    /// Returns the loop name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the names of the body stages.
+    #[must_use]
+    pub fn body_stage_names(&self) -> Vec<String> {
+        self.body.stage_names()
+    }
+
+    /// Validates the loop and builds the stage that runs it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the iteration limit is zero, the body is empty,
+    /// or a body stage shadows the loop name.
+    pub fn build(self) -> Result<LoopStage, PipelineValidationError> {
+        if self.max_iterations == 0 {
+            return Err(loop_error(
+                &self.name,
+                "CONTRACT-004-LOOP_LIMIT",
+                format!("Loop '{}' must allow at least one iteration", self.name),
+                "Set max_iterations to the largest number of repetitions the loop may take.",
+            ));
+        }
+        if self.body.stage_count() == 0 {
+            return Err(loop_error(
+                &self.name,
+                "CONTRACT-004-LOOP_EMPTY",
+                format!("Loop '{}' has no body stages", self.name),
+                "Add the stages that make up one iteration with LoopGroup::stage.",
+            ));
+        }
+        if self.body.stage_names().contains(&self.name) {
+            return Err(loop_error(
+                &self.name,
+                "CONTRACT-004-LOOP_NAME",
+                format!("Loop '{}' has a body stage with the same name", self.name),
+                "Body stages read loop state under the loop name; rename the stage.",
+            ));
+        }
+
+        Ok(LoopStage {
+            name: self.name,
+            body: self.body.build()?,
+            max_iterations: self.max_iterations,
+            carried_keys: self.carried_keys,
+            initial_state: self.initial_state,
+            until: self.until,
+            fail_on_exhaustion: self.fail_on_exhaustion,
+        })
+    }
+}
+
+fn loop_error(
+    name: &str,
+    code: &str,
+    message: String,
+    hint: &str,
+) -> PipelineValidationError {
+    PipelineValidationError::new(message.clone())
+        .with_stages(vec![name.to_string()])
+        .with_error_info(ContractErrorInfo::new(code, message).with_fix_hint(hint))
+}
+
+/// Stage that runs a [`LoopGroup`] body until it terminates.
+///
+/// On success the output data contains the final carried state, the
+/// number of `iterations`, the `termination` reason, and the last
+/// iteration's body `outputs`.
+pub struct LoopStage {
+    name: String,
+    body: StageGraph,
+    max_iterations: usize,
+    carried_keys: Vec<String>,
+    initial_state: HashMap<String, serde_json::Value>,
+    until: Option<LoopPredicate>,
+    fail_on_exhaustion: bool,
+}
+
+impl std::fmt::Debug for LoopStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoopStage")
+            .field("name", &self.name)
+            .field("body", &self.body.execution_order())
+            .field("max_iterations", &self.max_iterations)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoopStage {
+    /// Returns the body stage graph.
+    #[must_use]
+    pub fn body(&self) -> &StageGraph {
+        &self.body
+    }
+
+    /// Runs one iteration of the body.
+    ///
+    /// Returns the body outputs, or the failing or cancelling stage and its output.
+    async fn run_iteration(
+        &self,
+        ctx: &StageContext,
+        outer: &HashMap<String, HashMap<String, serde_json::Value>>,
+        loop_state: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, HashMap<String, serde_json::Value>>, (String, StageOutput)> {
+        let pipeline_ctx = ctx.pipeline_ctx();
+        let mut visible = outer.clone();
+        visible.insert(self.name.clone(), loop_state);
+
+        let mut outputs = HashMap::new();
+        let mut tracker = DependencyTracker::new(&self.body);
+        while tracker.has_ready() {
+            let batch = if pipeline_ctx.is_deterministic() {
+                tracker.take_next_ready().into_iter().collect()
+            } else {
+                tracker.take_ready()
+            };
+
+            let runs = batch.iter().filter_map(|name| self.body.stage_spec(name)).map(|spec| {
+                let declared: HashSet<String> =
+                    spec.dependencies.iter().chain(outer.keys()).chain([&self.name]).cloned().collect();
+                let available = declared
+                    .iter()
+                    .filter_map(|stage| visible.get(stage).map(|data| (stage.clone(), data.clone())))
+                    .collect();
+                let inputs = StageInputs::new(available, declared, spec.name.clone(), true);
+                async move {
+                    let output = run_stage(spec, pipeline_ctx.clone(), inputs, ctx.shared_snapshot().clone()).await;
+                    (spec.name.clone(), output)
+                }
+            });
+
+            for (name, output) in futures::future::join_all(runs).await {
+                if matches!(output.status, StageStatus::Fail | StageStatus::Cancel) {
+                    return Err((name, output));
+                }
+                let data = output.data_or_empty();
+                visible.insert(name.clone(), data.clone());
+                outputs.insert(name.clone(), data);
+                tracker.mark_complete(&name);
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    /// Updates carried state from an iteration's outputs.
+    fn carry_state(
+        &self,
+        state: &mut HashMap<String, serde_json::Value>,
+        outputs: &HashMap<String, HashMap<String, serde_json::Value>>,
+    ) {
+        for stage in self.body.execution_order() {
+            let Some(data) = outputs.get(stage) else { continue };
+            for key in &self.carried_keys {
+                if let Some(value) = data.get(key) {
+                    state.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Stage for LoopStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let pipeline_ctx = ctx.pipeline_ctx();
+        let outer: HashMap<String, HashMap<String, serde_json::Value>> = ctx
+            .inputs()
+            .stages()
+            .into_iter()
+            .filter_map(|stage| {
+                ctx.inputs()
+                    .get_unchecked(stage)
+                    .map(|data| (stage.clone(), data.clone()))
+            })
+            .collect();
+
+        let mut state = self.initial_state.clone();
+        let mut last_outputs = HashMap::new();
+        let mut termination = LoopTermination::MaxIterations;
+        let mut iterations = 0;
+
+        for iteration in 1..=self.max_iterations {
+            if pipeline_ctx.is_cancelled() {
+                return StageOutput::cancel(format!(
+                    "Loop '{}' cancelled before iteration {iteration}",
+                    self.name
+                ));
+            }
+
+            iterations = iteration;
+            let iteration_start = Instant::now();
+            pipeline_ctx.try_emit_event(
+                "loop.iteration_started",
+                Some(serde_json::json!({
+                    "loop": self.name,
+                    "iteration": iteration,
+                })),
+            );
+
+            let mut loop_state = state.clone();
+            loop_state.insert("iteration".to_string(), serde_json::json!(iteration));
+            let outputs = match self.run_iteration(ctx, &outer, loop_state).await {
+                Ok(outputs) => outputs,
+                Err((failed_stage, failed)) => {
+                    pipeline_ctx.try_emit_event(
+                        "loop.failed",
+                        Some(serde_json::json!({
+                            "loop": self.name,
+                            "iteration": iteration,
+                            "stage": failed_stage,
+                            "status": failed.status,
+                        })),
+                    );
+                    if failed.status == StageStatus::Cancel {
+                        return failed;
+                    }
+                    return StageOutput::fail(format!(
+                        "Loop '{}' stage '{failed_stage}' failed in iteration {iteration}: {}",
+                        self.name,
+                        failed.error.as_deref().unwrap_or("unknown error")
+                    ));
+                }
+            };
+
+            self.carry_state(&mut state, &outputs);
+            pipeline_ctx.try_emit_event(
+                "loop.iteration_completed",
+                Some(serde_json::json!({
+                    "loop": self.name,
+                    "iteration": iteration,
+                    "duration_ms": iteration_start.elapsed().as_secs_f64() * 1000.0,
+                    "state": state,
+                })),
+            );
+
+            let view = LoopIteration {
+                iteration,
+                state: state.clone(),
+                outputs,
+            };
+            let done = self.until.as_ref().is_some_and(|until| until(&view));
+            last_outputs = view.outputs;
+            if done {
+                termination = LoopTermination::Predicate;
+                break;
+            }
+        }
+
+        pipeline_ctx.try_emit_event(
+            "loop.completed",
+            Some(serde_json::json!({
+                "loop": self.name,
+                "iterations": iterations,
+                "termination": termination.as_str(),
+            })),
+        );
+
+        if termination == LoopTermination::MaxIterations && self.fail_on_exhaustion {
+            return StageOutput::fail(format!(
+                "Loop '{}' reached max_iterations ({}) without terminating",
+                self.name, self.max_iterations
+            ));
+        }
+
+        let mut data = state;
+        data.insert("iterations".to_string(), serde_json::json!(iterations));
+        data.insert("termination".to_string(), serde_json::json!(termination.as_str()));
+        data.insert("outputs".to_string(), serde_json::json!(last_outputs));
+        StageOutput::ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+    use crate::events::CollectingEventSink;
+    use crate::stages::FnStage;
+    use crate::utils::DeterministicSource;
+
+    /// Adds one to the carried `count`.
+    fn increment() -> Arc<dyn Stage> {
+        Arc::new(FnStage::new("act", |ctx: &StageContext| {
+            let count = ctx
+                .inputs()
+                .get_value("counter", "count")
+                .ok()
+                .flatten()
+                .and_then(serde_json::Value::as_i64)
+                .unwrap_or(0);
+            StageOutput::ok_value("count", serde_json::json!(count + 1))
+        }))
+    }
+
+    fn counter_loop(max_iterations: usize) -> LoopGroup {
+        LoopGroup::new("counter", max_iterations)
+            .stage("act", increment(), &[])
+            .unwrap()
+            .with_initial_state("count", serde_json::json!(0))
+    }
+
+    #[tokio::test]
+    async fn test_loop_stops_on_predicate() {
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+        let graph = PipelineBuilder::new("agent")
+            .loop_group(
+                counter_loop(10).until(|it| it.get("count") == Some(&serde_json::json!(3))),
+                &[],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        let output = &result.outputs["counter"];
+
+        assert!(result.success);
+        assert_eq!(output.get("count"), Some(&serde_json::json!(3)));
+        assert_eq!(output.get("iterations"), Some(&serde_json::json!(3)));
+        assert_eq!(output.get("termination"), Some(&serde_json::json!("predicate")));
+        assert_eq!(sink.events_of_type("loop.iteration_completed").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_loop_exhaustion() {
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let graph = PipelineBuilder::new("agent")
+            .loop_group(counter_loop(2), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let result = graph.execute(ctx.clone(), ContextSnapshot::new()).await.unwrap();
+        assert_eq!(
+            result.outputs["counter"].get("termination"),
+            Some(&serde_json::json!("max_iterations"))
+        );
+
+        let strict = PipelineBuilder::new("agent")
+            .loop_group(counter_loop(2).fail_on_exhaustion(), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let result = strict.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_body_reads_outer_inputs_and_runs_in_order() {
+        let seed = Arc::new(FnStage::new("seed", |_: &StageContext| {
+            StageOutput::ok_value("goal", serde_json::json!(2))
+        }));
+        let plan = Arc::new(FnStage::new("plan", |ctx: &StageContext| {
+            let goal = ctx.inputs().get_value("seed", "goal").ok().flatten().cloned();
+            StageOutput::ok_value("goal", goal.unwrap_or_default())
+        }));
+        let group = LoopGroup::new("counter", 5)
+            .stage("plan", plan, &[])
+            .unwrap()
+            .stage("act", increment(), &["plan"])
+            .unwrap()
+            .carry("count")
+            .until(|it| {
+                let goal = it.outputs["plan"].get("goal").cloned();
+                it.get("count") == goal.as_ref()
+            });
+
+        let graph = PipelineBuilder::new("agent")
+            .stage("seed", seed, &[])
+            .unwrap()
+            .loop_group(group, &["seed"])
+            .unwrap()
+            .build()
+            .unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert_eq!(result.outputs["counter"].get("iterations"), Some(&serde_json::json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_body_stages_read_only_declared_inputs() {
+        fn sees_act(key: &'static str) -> Arc<dyn Stage> {
+            Arc::new(FnStage::new(key, move |ctx: &StageContext| {
+                StageOutput::ok_value(key, serde_json::json!(ctx.inputs().get("act").is_ok()))
+            }))
+        }
+        let group = LoopGroup::new("counter", 2)
+            .stage("act", increment(), &[])
+            .unwrap()
+            .stage("peek", sees_act("peeked"), &[])
+            .unwrap()
+            .stage("check", sees_act("checked"), &["act"])
+            .unwrap()
+            .carry("peeked")
+            .carry("checked");
+        let graph = PipelineBuilder::new("agent").loop_group(group, &[]).unwrap().build().unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_deterministic_source(Arc::new(DeterministicSource::new(7))));
+
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        let output = &result.outputs["counter"];
+        assert_eq!(output.get("peeked"), Some(&serde_json::json!(false)));
+        assert_eq!(output.get("checked"), Some(&serde_json::json!(true)));
+    }
+
+    #[tokio::test]
+    async fn test_body_failure_fails_loop() {
+        let failing = Arc::new(FnStage::new("act", |_: &StageContext| StageOutput::fail("boom")));
+        let graph = PipelineBuilder::new("agent")
+            .loop_group(LoopGroup::new("counter", 3).stage("act", failing, &[]).unwrap(), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let result = graph.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        let error = result.outputs["counter"].error.clone().unwrap();
+        assert!(error.contains("iteration 1"));
+        assert!(error.contains("boom"));
+    }
+
+    #[test]
+    fn test_loop_validation() {
+        let err = LoopGroup::new("empty", 3).build().unwrap_err();
+        assert_eq!(err.error_info.unwrap().code, "CONTRACT-004-LOOP_EMPTY");
+
+        let err = counter_loop(0).build().unwrap_err();
+        assert_eq!(err.error_info.unwrap().code, "CONTRACT-004-LOOP_LIMIT");
+
+        let err = PipelineBuilder::new("agent")
+            .stage("act", increment(), &[])
+            .unwrap()
+            .loop_group(counter_loop(2), &[])
+            .unwrap_err();
+        assert!(err.message.contains("act"));
+    }
+}
@@ -0,0 +1,317 @@
+//! Pipelines declared as data and built from registered stage factories.
+//!
+//! A [`PipelineManifest`] is also the description that
+//! [`PipelineLinter`](super::PipelineLinter) checks; one that describes a
+//! pipeline built in code comes from [`PipelineManifest::from_graph`] or
+//! [`PipelineBuilder::manifest`].
+
+use super::{GuardRetryPolicy, GuardRetryStrategy, PipelineBuilder, StageGraph, StageSpec};
+use crate::core::StageKind;
+use crate::errors::PipelineValidationError;
+use crate::stages::{StageConfig, StageFactoryError, StageRegistry};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// Error raised when a manifest cannot be turned into a pipeline.
+#[derive(Debug, Clone, Error)]
+pub enum ManifestError {
+    /// The manifest is not valid JSON or does not have the manifest shape.
+    #[error("Invalid manifest: {0}")]
+    Parse(String),
+
+    /// A stage could not be built.
+    #[error("{0}")]
+    Stage(#[from] StageFactoryError),
+
+    /// The stages do not form a valid pipeline.
+    #[error("{0}")]
+    Validation(#[from] PipelineValidationError),
+}
+
+/// A stage entry in a [`PipelineManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageManifest {
+    /// Name of the stage in the pipeline.
+    pub name: String,
+    /// Stage type, the key its factory is registered under. Empty when the
+    /// manifest describes a stage built in code.
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub stage_type: String,
+    /// Configuration passed to the factory.
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub config: serde_json::Value,
+    /// Names of stages this stage depends on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// The kind of stage.
+    #[serde(default)]
+    pub kind: StageKind,
+    /// Whether the stage is conditional.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub conditional: bool,
+    /// Whether the stage is safe to re-run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub idempotent: bool,
+    /// Declared input keys, keyed by the stage that outputs them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, BTreeSet<String>>,
+    /// Declared output keys.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub outputs: BTreeSet<String>,
+}
+
+impl StageManifest {
+    /// Returns true if the stage depends directly on `stage`.
+    #[must_use]
+    pub fn depends_on(&self, stage: &str) -> bool {
+        self.depends_on.iter().any(|dep| dep == stage)
+    }
+
+    fn apply_to(&self, spec: StageSpec) -> StageSpec {
+        let mut spec = spec.with_dependencies(self.depends_on.iter()).with_kind(self.kind);
+        if self.conditional {
+            spec = spec.conditional();
+        }
+        if self.idempotent {
+            spec = spec.with_idempotent();
+        }
+        for (stage, keys) in &self.inputs {
+            for key in keys {
+                spec = spec.with_input_key(stage, key);
+            }
+        }
+        self.outputs.iter().fold(spec, StageSpec::with_output_key)
+    }
+}
+
+impl From<&StageSpec> for StageManifest {
+    fn from(spec: &StageSpec) -> Self {
+        let mut depends_on: Vec<String> = spec.dependencies.iter().cloned().collect();
+        depends_on.sort();
+        Self {
+            name: spec.name.clone(),
+            stage_type: String::new(),
+            config: serde_json::Value::Null,
+            depends_on,
+            kind: spec.kind,
+            conditional: spec.conditional,
+            idempotent: spec.idempotent,
+            inputs: spec
+                .input_keys
+                .iter()
+                .map(|(stage, keys)| (stage.clone(), keys.iter().cloned().collect()))
+                .collect(),
+            outputs: spec.output_keys.iter().cloned().collect(),
+        }
+    }
+}
+
+/// A pipeline declared as data.
+///
+/// ```json
+/// {
+///   "name": "ingest",
+///   "stages": [
+///     { "name": "fetch", "type": "http_fetch", "config": { "url": "https://example.com" } },
+///     { "name": "parse", "type": "html_parse", "kind": "transform", "depends_on": ["fetch"] }
+///   ]
+/// }
+/// ```
+///
+/// Each stage is built by the factory registered under its `type`, given
+/// its name and `config`. Stages are added in order, so dependencies must
+/// be listed before their dependents. The manifest is a plain serde type,
+/// so it can be read from any format serde supports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineManifest {
+    /// The pipeline name.
+    pub name: String,
+    /// The stages, in the order they are added.
+    pub stages: Vec<StageManifest>,
+    /// Retry stage of each guard with a guard retry policy, keyed by guard.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub guard_retries: BTreeMap<String, String>,
+}
+
+impl PipelineManifest {
+    /// Parses a JSON manifest.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ManifestError::Parse`] if the JSON is malformed or not a manifest.
+    pub fn from_json(json: &str) -> Result<Self, ManifestError> {
+        serde_json::from_str(json).map_err(|e| ManifestError::Parse(e.to_string()))
+    }
+
+    /// Builds each stage with `registry` and adds it to a builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a stage type is unknown, a factory rejects its
+    /// configuration, or a stage fails validation.
+    pub fn to_builder(&self, registry: &StageRegistry) -> Result<PipelineBuilder, ManifestError> {
+        let mut builder = PipelineBuilder::new(&self.name);
+        for stage in &self.stages {
+            let config = StageConfig::new(&stage.name, stage.config.clone());
+            let runner = registry.create(&stage.stage_type, &config)?;
+            builder.add_stage_spec(stage.apply_to(StageSpec::new(&stage.name, runner)))?;
+        }
+        Ok(builder)
+    }
+
+    /// Builds the pipeline with `registry`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`to_builder`](Self::to_builder) or
+    /// [`PipelineBuilder::build`] fails.
+    pub fn build(&self, registry: &StageRegistry) -> Result<StageGraph, ManifestError> {
+        Ok(self.to_builder(registry)?.build()?)
+    }
+
+    /// Describes a built graph. Stage types and configuration are left
+    /// empty, so the result can be linted but not built.
+    #[must_use]
+    pub fn from_graph(graph: &StageGraph) -> Self {
+        let specs = graph.stage_specs();
+        Self {
+            name: graph.name().to_string(),
+            stages: graph
+                .execution_order()
+                .iter()
+                .filter_map(|name| specs.get(name))
+                .map(StageManifest::from)
+                .collect(),
+            guard_retries: BTreeMap::new(),
+        }
+    }
+
+    /// Records the guard retry policies the pipeline runs with.
+    #[must_use]
+    pub fn with_guard_retry_strategy(mut self, strategy: &GuardRetryStrategy) -> Self {
+        self.guard_retries.extend(
+            strategy
+                .policies
+                .iter()
+                .map(|(guard, policy)| (guard.clone(), policy.retry_stage.clone())),
+        );
+        self
+    }
+
+    /// Returns a guard retry strategy with a default policy for each entry
+    /// of [`guard_retries`](Self::guard_retries).
+    #[must_use]
+    pub fn guard_retry_strategy(&self) -> GuardRetryStrategy {
+        self.guard_retries
+            .iter()
+            .fold(GuardRetryStrategy::new(), |strategy, (guard, retry_stage)| {
+                strategy.with_policy(guard, GuardRetryPolicy::new(retry_stage))
+            })
+    }
+
+    /// Returns the stage named `name`.
+    #[must_use]
+    pub fn stage(&self, name: &str) -> Option<&StageManifest> {
+        self.stages.iter().find(|stage| stage.name == name)
+    }
+
+    /// Returns the stages that depend directly on `name`.
+    pub fn consumers<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a StageManifest> + 'a {
+        self.stages.iter().filter(move |stage| stage.depends_on(name))
+    }
+
+    /// Returns every stage `name` depends on, directly or transitively.
+    #[must_use]
+    pub fn upstream(&self, name: &str) -> BTreeSet<String> {
+        let mut seen = BTreeSet::new();
+        let mut pending: Vec<&str> = vec![name];
+        while let Some(current) = pending.pop() {
+            for dep in self.stage(current).into_iter().flat_map(|stage| &stage.depends_on) {
+                if seen.insert(dep.clone()) {
+                    pending.push(dep);
+                }
+            }
+        }
+        seen
+    }
+
+    pub(crate) fn stage_mut(&mut self, name: &str) -> Option<&mut StageManifest> {
+        self.stages.iter_mut().find(|stage| stage.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stages::NoOpStage;
+    use std::sync::Arc;
+
+    fn registry() -> StageRegistry {
+        let registry = StageRegistry::new();
+        registry.register("noop", |cfg: &StageConfig| {
+            if cfg.config.get("fail").is_some() {
+                return Err(cfg.invalid("asked to fail"));
+            }
+            Ok(Arc::new(NoOpStage::new(&cfg.name)) as _)
+        });
+        registry
+    }
+
+    #[test]
+    fn test_build_from_json() {
+        let manifest = PipelineManifest::from_json(
+            r#"{"name": "ingest", "stages": [
+                {"name": "fetch", "type": "noop"},
+                {"name": "parse", "type": "noop", "config": {"mode": "html"}, "depends_on": ["fetch"]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.stages[1].config["mode"], "html");
+
+        let builder = manifest.to_builder(&registry()).unwrap();
+        assert_eq!(builder.name(), "ingest");
+        assert_eq!(builder.stage_names(), vec!["fetch".to_string(), "parse".to_string()]);
+        assert!(manifest.build(&registry()).is_ok());
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let parse = |json: &str| PipelineManifest::from_json(json).unwrap().build(&registry()).unwrap_err();
+
+        let err = parse(r#"{"name": "p", "stages": [{"name": "a", "type": "fetch"}]}"#);
+        assert!(matches!(err, ManifestError::Stage(StageFactoryError::UnknownType(ref t)) if t == "fetch"));
+        let err = parse(r#"{"name": "p", "stages": [{"name": "a", "type": "noop", "config": {"fail": true}}]}"#);
+        assert!(matches!(err, ManifestError::Stage(StageFactoryError::InvalidConfig { .. })));
+        let err = parse(r#"{"name": "p", "stages": [{"name": "a", "type": "noop", "depends_on": ["b"]}]}"#);
+        assert!(matches!(err, ManifestError::Validation(_)));
+
+        assert!(matches!(PipelineManifest::from_json("{}"), Err(ManifestError::Parse(_))));
+    }
+
+    #[test]
+    fn test_stage_settings_reach_the_built_pipeline() {
+        let manifest = PipelineManifest::from_json(
+            r#"{"name": "ingest", "guard_retries": {"check": "fetch"}, "stages": [
+                {"name": "fetch", "type": "noop", "kind": "transform", "outputs": ["body"]},
+                {"name": "check", "type": "noop", "kind": "guard", "depends_on": ["fetch"],
+                 "conditional": true, "idempotent": true, "inputs": {"fetch": ["body"]}}
+            ]}"#,
+        )
+        .unwrap();
+        let graph = manifest.build(&registry()).unwrap();
+        let specs = graph.stage_specs();
+        let check = &specs["check"];
+        assert_eq!(check.kind, StageKind::Guard);
+        assert!(check.conditional && check.idempotent);
+        assert!(check.input_keys["fetch"].contains("body"));
+        assert!(specs["fetch"].output_keys.contains("body"));
+        assert_eq!(manifest.guard_retry_strategy().policies["check"].retry_stage, "fetch");
+
+        // Describing the graph again gives back everything but the factories
+        let described = PipelineManifest::from_graph(&graph).with_guard_retry_strategy(&manifest.guard_retry_strategy());
+        assert_eq!(described.stage("check").unwrap().inputs, manifest.stages[1].inputs);
+        assert_eq!(described.guard_retries, manifest.guard_retries);
+        assert!(described.stages.iter().all(|stage| stage.stage_type.is_empty()));
+    }
+}
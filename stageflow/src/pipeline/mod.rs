@@ -3,48 +3,129 @@
 //! This module provides:
 //! - Pipeline specifications
 //! - Pipeline builder with validation
+//! - Lint rules with suggested fixes
+//! - Critical path, parallelism and slack analysis
 //! - DAG execution engines
-//! - Failure tolerance modes
+//! - Execution policies by stage kind
+//! - Failure tolerance modes and panic isolation
+//! - Retry policies that can be swapped mid-run
+//! - Bounded loop groups for iterative agent workflows
+//! - Per-item fan-out over lists produced at runtime
+//! - Live pause, resume and single-step control of runs
+//! - Manual stage acknowledgment for at-least-once delivery
+//! - Run-level budgets for retries, tool calls, nesting and wall-clock time
+//! - Retry budgets shared by every stage of a run
+//! - Dead letters for stages that exhaust their retries, with redrive
+//! - Idempotency keys templated per stage, replaying cached results
+//! - Per-stage limits on duration, output size and artifact size
+//! - Heartbeats and stuck-stage detection
+//! - Run history with queries by pipeline, status and time
+//! - Pipelines declared as manifests and built from stage factories
+//! - Starting, polling, streaming and cancelling runs by id
+//! - Conversation sessions shared by multi-turn runs
+//! - Graceful shutdown draining every run in flight
+//! - Latency and cost simulation
 
+mod ack;
+mod analysis;
+mod budget;
 mod builder;
 mod builder_helpers;
 mod cancellation;
+mod checkpoint;
+mod control;
 mod dag;
+mod dead_letter;
 mod failure_tolerance;
+mod fan_out;
 mod guard_retry;
+mod history;
 mod idempotency;
 #[cfg(test)]
 mod integration_tests;
 mod interfaces;
+mod isolation;
+mod kind_policy;
+mod lint;
+mod live_policies;
+mod loop_group;
+mod manifest;
+mod resource_limits;
 mod retry;
+mod run_manager;
+mod session;
+mod shutdown;
+mod simulation;
 mod spec;
 mod unified;
+mod watchdog;
 
+pub use ack::{StageAckRegistry, DEFAULT_ACK_TIMEOUT};
+pub use analysis::{GraphAnalysis, StageTiming};
+pub(crate) use budget::{until_deadline, BudgetTracker, RetryBudgetTracker};
+pub use budget::{
+    BudgetLimit, BudgetUsage, CancelReason, RetryBudget, RetryBudgetExhausted, RetryBudgetUsage, RunBudget,
+};
 pub use builder::PipelineBuilder;
 pub use builder_helpers::FluentPipelineBuilder;
 pub use cancellation::{
     CancellationToken, CleanupGuard, CleanupRegistry, run_with_cleanup,
 };
+pub(crate) use checkpoint::SharedCheckpoint;
+pub use checkpoint::{
+    FileRetryCheckpointStore, InMemoryRetryCheckpointStore, RetryCheckpoint, RetryCheckpointStore,
+};
+pub use control::PipelineController;
 pub use dag::{GraphExecutionResult, StageGraph};
+pub use dead_letter::{DeadLetter, DeadLetterStore, FileDeadLetterStore, InMemoryDeadLetterStore};
 pub use failure_tolerance::{
     BackpressureConfig, BackpressureTracker, FailureCollector, FailureMode,
     FailureRecord, FailureSummary,
 };
+pub use fan_out::{FanOut, FanOutStage};
 pub use guard_retry::{
     GuardRetryPolicy, GuardRetryRuntimeState, GuardRetryStrategy, hash_retry_payload,
 };
+pub use history::{InMemoryRunStore, RunQuery, RunStatus, RunStore, RunSummary, StageSummary};
+#[cfg(feature = "sqlite")]
+pub use history::SqliteRunStore;
+pub(crate) use idempotency::KeyedExecution;
 pub use idempotency::{
-    CachedResult, IdempotencyCheckResult, IdempotencyConfig, IdempotencyParamMismatch,
-    IdempotencyStore, InMemoryIdempotencyStore, check_idempotency, generate_idempotency_key,
-    hash_parameters,
+    CachedResult, IdempotencyCheckResult, IdempotencyConfig, IdempotencyKeyTemplate, IdempotencyParamMismatch,
+    IdempotencyStore, IdempotencyTemplateError, InMemoryIdempotencyStore, check_idempotency,
+    generate_idempotency_key, hash_inputs, hash_parameters, input_params,
+};
+pub use resource_limits::{ResourceLimit, ResourceLimitExceeded, ResourceLimits};
+pub use run_manager::{
+    RunEventStream, RunInfo, RunManager, RunManagerError, RunState, RUN_EVENT_BUFFER,
+};
+pub use session::{SessionManager, DEFAULT_SESSION_RESPONSE_KEY};
+pub use shutdown::{
+    Shutdown, ShutdownGuard, ShutdownReport, DEFAULT_SHUTDOWN_CLEANUP_TIMEOUT, SHUTDOWN_REASON,
 };
 pub use retry::{
     BackoffStrategy, JitterStrategy, RetryConfig, RetryDecision, RetryState,
-    should_retry, with_retry,
+    should_retry, with_retry, with_retry_if,
+};
+pub use isolation::{catch_stage_panic, CatchStagePanic, PanicPolicy, StagePanic, PANIC_METADATA_KEY};
+pub use kind_policy::{KindPolicies, KindPolicy};
+pub use lint::{
+    GuardWithoutRetryRule, LintFinding, LintReport, LintRule, LintSeverity, NonIdempotentWorkRule,
+    PipelineLinter, SpecEdit, UnconsumedStageRule, UnreachableSkipRule, UnusedDeclaredKeyRule,
 };
+pub(crate) use live_policies::{decide_guard_retry, GuardRetryVerdict};
+pub use live_policies::PolicyHandle;
+pub use loop_group::{LoopGroup, LoopIteration, LoopPredicate, LoopStage, LoopTermination};
+pub use manifest::{ManifestError, PipelineManifest, StageManifest};
 pub use interfaces::{
     ConditionalStage, ConfigurableStage, DependentStage, IdempotentStage,
     ObservableStage, ParallelSafeStage, RetryableStage, StageCapabilities,
 };
+pub use simulation::{
+    Distribution, HistoricalStats, Percentiles, SimulationConfig, SimulationReport, StageProfile,
+    simulate, simulate_concurrency_limits,
+};
 pub use spec::{PipelineSpec, StageSpec};
-pub use unified::UnifiedStageGraph;
+pub use unified::{UnifiedExecutionResult, UnifiedStageGraph};
+pub(crate) use watchdog::watch_for_stall;
+pub use watchdog::{Heartbeat, StallAction, StallPolicy};
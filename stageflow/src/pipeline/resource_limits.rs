@@ -0,0 +1,219 @@
+//! Per-stage resource limits.
+//!
+//! [`ResourceLimits`] on a [`StageSpec`](super::StageSpec) bound a single
+//! stage: how long it may run and how large its output data and artifacts
+//! may be. The executor stops a stage at its time limit and rejects
+//! oversized outputs, failing the stage with a [`ContractErrorInfo`] under
+//! the `error_info` metadata key. Every overrun emits
+//! `stage.budget_exceeded`.
+
+use crate::contracts::{codes, ContractErrorInfo};
+use crate::core::StageOutput;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Limits applied to one stage execution.
+///
+/// Every limit is optional; empty limits enforce nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum wall-clock time for one execution.
+    pub max_duration: Option<Duration>,
+    /// Maximum size of the output data, serialized as JSON.
+    pub max_output_bytes: Option<usize>,
+    /// Maximum total size of the artifacts' data, serialized as JSON.
+    pub max_artifact_bytes: Option<usize>,
+}
+
+impl ResourceLimits {
+    /// Creates limits that enforce nothing.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits how long one execution may run.
+    #[must_use]
+    pub fn with_max_duration(mut self, max: Duration) -> Self {
+        self.max_duration = Some(max);
+        self
+    }
+
+    /// Limits the size of the output data.
+    #[must_use]
+    pub fn with_max_output_bytes(mut self, max: usize) -> Self {
+        self.max_output_bytes = Some(max);
+        self
+    }
+
+    /// Limits the total size of the output's artifacts.
+    #[must_use]
+    pub fn with_max_artifact_bytes(mut self, max: usize) -> Self {
+        self.max_artifact_bytes = Some(max);
+        self
+    }
+
+    /// Returns true if no limit is set.
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the first size limit `output` exceeds.
+    #[must_use]
+    pub fn check_output(&self, output: &StageOutput) -> Option<ResourceLimitExceeded> {
+        if let Some(max) = self.max_output_bytes {
+            let size = output.data.as_ref().map_or(0, json_size);
+            if size > max {
+                return Some(ResourceLimitExceeded::new(ResourceLimit::OutputBytes, max, size));
+            }
+        }
+        if let Some(max) = self.max_artifact_bytes {
+            let size = output.artifacts.iter().map(|artifact| json_size(&artifact.data)).sum();
+            if size > max {
+                return Some(ResourceLimitExceeded::new(ResourceLimit::ArtifactBytes, max, size));
+            }
+        }
+        None
+    }
+}
+
+/// A limit of [`ResourceLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceLimit {
+    /// [`ResourceLimits::max_duration`], measured in milliseconds.
+    Duration,
+    /// [`ResourceLimits::max_output_bytes`].
+    OutputBytes,
+    /// [`ResourceLimits::max_artifact_bytes`].
+    ArtifactBytes,
+}
+
+impl ResourceLimit {
+    /// Returns the limit name.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Duration => "duration",
+            Self::OutputBytes => "output_bytes",
+            Self::ArtifactBytes => "artifact_bytes",
+        }
+    }
+
+    /// Returns the contract error code reported when the limit is exceeded.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Duration => codes::STAGE_DURATION,
+            Self::OutputBytes => codes::OUTPUT_SIZE,
+            Self::ArtifactBytes => codes::ARTIFACT_SIZE,
+        }
+    }
+}
+
+/// A stage execution that went over one of its [`ResourceLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimitExceeded {
+    /// The limit that was exceeded.
+    pub limit: ResourceLimit,
+    /// The configured maximum.
+    pub max: u64,
+    /// The measured amount.
+    pub actual: u64,
+}
+
+impl ResourceLimitExceeded {
+    /// Creates a record of an exceeded size limit.
+    #[must_use]
+    pub fn new(limit: ResourceLimit, max: usize, actual: usize) -> Self {
+        Self {
+            limit,
+            max: u64::try_from(max).unwrap_or(u64::MAX),
+            actual: u64::try_from(actual).unwrap_or(u64::MAX),
+        }
+    }
+
+    /// Creates a record of an exceeded duration limit.
+    #[must_use]
+    pub fn duration(max: Duration, actual: Duration) -> Self {
+        Self {
+            limit: ResourceLimit::Duration,
+            max: millis(max),
+            actual: millis(actual),
+        }
+    }
+
+    /// Returns the structured error for `stage`.
+    #[must_use]
+    pub fn error_info(&self, stage: &str) -> ContractErrorInfo {
+        let (summary, hint) = match self.limit {
+            ResourceLimit::Duration => (
+                format!("Stage '{stage}' exceeded its time limit of {}ms", self.max),
+                "Raise the stage's max_duration or make the stage faster",
+            ),
+            ResourceLimit::OutputBytes => (
+                format!(
+                    "Stage '{stage}' produced {} bytes of output data, over its limit of {}",
+                    self.actual, self.max
+                ),
+                "Store large values as artifacts or raise the stage's max_output_bytes",
+            ),
+            ResourceLimit::ArtifactBytes => (
+                format!(
+                    "Stage '{stage}' produced {} bytes of artifacts, over its limit of {}",
+                    self.actual, self.max
+                ),
+                "Store large artifacts externally or raise the stage's max_artifact_bytes",
+            ),
+        };
+        ContractErrorInfo::new(self.limit.code(), summary)
+            .with_fix_hint(hint)
+            .with_context("stage", serde_json::json!(stage))
+            .with_context("limit", serde_json::json!(self.limit.as_str()))
+            .with_context("max", serde_json::json!(self.max))
+            .with_context("actual", serde_json::json!(self.actual))
+    }
+
+    /// Returns the failed output that replaces the stage's own.
+    #[must_use]
+    pub fn to_output(&self, stage: &str) -> StageOutput {
+        let info = self.error_info(stage);
+        StageOutput::fail(info.summary.clone())
+            .add_metadata("error_info", serde_json::to_value(&info).unwrap_or_default())
+    }
+}
+
+fn json_size<T: Serialize>(value: &T) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StageArtifact;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_output_reports_first_exceeded_limit() {
+        let output = StageOutput::ok_value("text", json!("x".repeat(40)))
+            .with_artifacts(vec![StageArtifact::new("blob", "a", "a", json!("y".repeat(100)))]);
+
+        assert!(ResourceLimits::new().check_output(&output).is_none());
+        let limits = ResourceLimits::new().with_max_output_bytes(1024).with_max_artifact_bytes(64);
+        let exceeded = limits.check_output(&output).unwrap();
+        assert_eq!(exceeded.limit, ResourceLimit::ArtifactBytes);
+        assert_eq!(exceeded.actual, 102);
+
+        let exceeded = ResourceLimits::new().with_max_output_bytes(16).check_output(&output).unwrap();
+        assert_eq!(exceeded.limit, ResourceLimit::OutputBytes);
+        let failed = exceeded.to_output("summarize");
+        assert!(!failed.is_success());
+        assert_eq!(failed.metadata["error_info"]["code"], json!(codes::OUTPUT_SIZE));
+        assert_eq!(failed.metadata["error_info"]["context"]["max"], json!(16));
+    }
+}
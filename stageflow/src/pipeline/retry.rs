@@ -3,7 +3,7 @@
 //! Provides automatic retry handling for transient failures with
 //! exponential backoff, jitter, and configurable retry conditions.
 
-use rand::Rng;
+use crate::utils::random_in_range;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Duration;
@@ -108,11 +108,12 @@ impl RetryConfig {
 }
 
 /// State tracking for retry operations.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RetryState {
     /// Current attempt number (0-indexed).
     pub attempt: usize,
     /// Previous delays for decorrelated jitter.
+    #[serde(default)]
     previous_delays: HashMap<String, u64>,
 }
 
@@ -161,7 +162,7 @@ impl RetryState {
                 if delay == 0 {
                     0
                 } else {
-                    rand::thread_rng().gen_range(0..=delay)
+                    random_in_range(0..=delay)
                 }
             }
             JitterStrategy::Equal => {
@@ -169,7 +170,7 @@ impl RetryState {
                 if half == 0 {
                     delay
                 } else {
-                    half + rand::thread_rng().gen_range(0..=half)
+                    half + random_in_range(0..=half)
                 }
             }
             JitterStrategy::Decorrelated => {
@@ -178,7 +179,7 @@ impl RetryState {
                 let new_delay = if upper <= base {
                     base
                 } else {
-                    rand::thread_rng().gen_range(base..=upper)
+                    random_in_range(base..=upper)
                 };
                 self.previous_delays.insert(key.to_string(), new_delay);
                 new_delay
@@ -225,20 +226,40 @@ pub fn should_retry(
 
 /// Executes an operation with retry logic.
 pub async fn with_retry<T, E, F, Fut>(
+    config: &RetryConfig,
+    key: &str,
+    operation: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    with_retry_if(config, key, operation, |_| true).await
+}
+
+/// Executes an operation with retry logic, retrying only errors for which
+/// `is_retryable` returns true.
+///
+/// Other errors are returned immediately, without using up attempts.
+pub async fn with_retry_if<T, E, F, Fut, P>(
     config: &RetryConfig,
     key: &str,
     mut operation: F,
+    is_retryable: P,
 ) -> Result<T, E>
 where
     F: FnMut() -> Fut,
     Fut: std::future::Future<Output = Result<T, E>>,
     E: std::fmt::Display,
+    P: Fn(&E) -> bool,
 {
     let mut state = RetryState::new();
 
     loop {
         match operation().await {
             Ok(result) => return Ok(result),
+            Err(e) if !is_retryable(&e) => return Err(e),
             Err(e) => {
                 match should_retry(&mut state, config, key) {
                     RetryDecision::Retry(delay) => {
@@ -503,4 +524,28 @@ mod tests {
         let final_calls = calls.load(std::sync::atomic::Ordering::SeqCst);
         assert!(final_calls >= 1 && final_calls <= 4);
     }
+
+    #[tokio::test]
+    async fn test_with_retry_if_stops_on_permanent_error() {
+        let config = RetryConfig::new()
+            .with_max_attempts(5)
+            .with_base_delay_ms(1)
+            .with_jitter(JitterStrategy::None);
+
+        let mut calls = 0;
+        let result: Result<i32, String> = with_retry_if(
+            &config,
+            "test",
+            || {
+                calls += 1;
+                let error = if calls < 2 { "transient" } else { "permanent" };
+                async move { Err(error.to_string()) }
+            },
+            |e| e == "transient",
+        )
+        .await;
+
+        assert_eq!(result, Err("permanent".to_string()));
+        assert_eq!(calls, 2);
+    }
 }
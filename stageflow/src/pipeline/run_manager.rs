@@ -0,0 +1,493 @@
+//! Starting, tracking and cancelling runs on behalf of remote callers.
+//!
+//! A [`RunManager`] holds named pipelines, starts runs of them on request
+//! and keeps each run's state and event log so callers can poll, stream
+//! and cancel by run id. The gRPC and HTTP front ends are thin layers over
+//! it.
+
+use super::{RunStatus, RunStore, RunSummary, UnifiedExecutionResult, UnifiedStageGraph};
+use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::events::{EventSink, NoOpEventSink, RecordedEvent};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, oneshot, watch};
+
+/// Events buffered per [`RunManager::events`] subscriber before it lags.
+pub const RUN_EVENT_BUFFER: usize = 256;
+
+/// A run's events, oldest first, ending when the run finishes.
+pub type RunEventStream = BoxStream<'static, Result<RecordedEvent, RunManagerError>>;
+
+/// Error returned by [`RunManager`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RunManagerError {
+    /// No pipeline is registered under the name.
+    #[error("Unknown pipeline '{0}'")]
+    UnknownPipeline(String),
+    /// No run with the id is known.
+    #[error("Unknown run '{0}'")]
+    UnknownRun(String),
+    /// An event subscriber fell behind and missed events.
+    #[error("Event stream fell behind by {0} events")]
+    Lagged(u64),
+    /// The run store failed.
+    #[error("Run store failed: {0}")]
+    Store(String),
+}
+
+/// State of a run managed by a [`RunManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunState {
+    /// The run has not finished.
+    Running,
+    /// Every stage finished without failing the run.
+    Succeeded,
+    /// A stage failed or the engine returned an error.
+    Failed,
+    /// The run was cancelled.
+    Cancelled,
+}
+
+impl RunState {
+    /// Returns the state name.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// Returns true once the run has finished.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        *self != Self::Running
+    }
+}
+
+impl From<RunStatus> for RunState {
+    fn from(status: RunStatus) -> Self {
+        match status {
+            RunStatus::Succeeded => Self::Succeeded,
+            RunStatus::Failed => Self::Failed,
+            RunStatus::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+/// What a [`RunManager`] knows about a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunInfo {
+    /// The pipeline run id.
+    pub run_id: String,
+    /// The pipeline name.
+    pub pipeline: String,
+    /// The run's state.
+    pub state: RunState,
+    /// Run error or cancellation reason.
+    pub error: Option<String>,
+    /// Total run time in milliseconds, once finished.
+    pub duration_ms: Option<f64>,
+    /// Stage outputs keyed by stage name, once finished. Empty for runs
+    /// only found in the run store.
+    #[serde(default)]
+    pub outputs: HashMap<String, StageOutput>,
+}
+
+impl From<RunSummary> for RunInfo {
+    fn from(summary: RunSummary) -> Self {
+        Self {
+            run_id: summary.run_id,
+            pipeline: summary.pipeline,
+            state: summary.status.into(),
+            error: summary.error,
+            duration_ms: Some(summary.duration_ms),
+            outputs: HashMap::new(),
+        }
+    }
+}
+
+struct RunEntry {
+    info: RunInfo,
+    events: Vec<RecordedEvent>,
+    /// Present while the run is going.
+    live: Option<broadcast::Sender<RecordedEvent>>,
+    /// Present until the run finishes or is cancelled.
+    cancel: Option<(Arc<PipelineContext>, oneshot::Sender<String>)>,
+    finished: watch::Receiver<bool>,
+}
+
+#[derive(Default)]
+struct Runs {
+    entries: HashMap<String, RunEntry>,
+    /// Finished runs, oldest first, for pruning.
+    finished: VecDeque<String>,
+}
+
+/// Runs registered pipelines on request and tracks them by run id.
+///
+/// Finished runs stay in memory until more than
+/// [`max_retained_runs`](Self::with_max_retained_runs) have finished.
+/// With a [run store](Self::with_run_store), usually the one the graphs
+/// record to, [`status`](Self::status) falls back to the store for runs
+/// no longer in memory.
+///
+/// Clones share the runs, so a run started through one is visible through
+/// every other.
+#[derive(Clone)]
+pub struct RunManager {
+    pipelines: HashMap<String, Arc<UnifiedStageGraph>>,
+    runs: Arc<Mutex<Runs>>,
+    event_sink: Arc<dyn EventSink>,
+    run_store: Option<Arc<dyn RunStore>>,
+    max_retained_runs: usize,
+}
+
+impl RunManager {
+    /// Creates a manager with no pipelines.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            pipelines: HashMap::new(),
+            runs: Arc::default(),
+            event_sink: Arc::new(NoOpEventSink),
+            run_store: None,
+            max_retained_runs: 1000,
+        }
+    }
+
+    /// Registers a pipeline under its name.
+    #[must_use]
+    pub fn with_pipeline(mut self, graph: Arc<UnifiedStageGraph>) -> Self {
+        self.pipelines.insert(graph.name().to_string(), graph);
+        self
+    }
+
+    /// Sets a sink that also receives every run's events.
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.event_sink = sink;
+        self
+    }
+
+    /// Sets the store consulted for runs no longer held in memory.
+    #[must_use]
+    pub fn with_run_store(mut self, store: Arc<dyn RunStore>) -> Self {
+        self.run_store = Some(store);
+        self
+    }
+
+    /// Sets how many finished runs are kept in memory.
+    #[must_use]
+    pub fn with_max_retained_runs(mut self, max: usize) -> Self {
+        self.max_retained_runs = max;
+        self
+    }
+
+    /// Returns the names of the registered pipelines, sorted.
+    #[must_use]
+    pub fn pipeline_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.pipelines.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Starts a run of `pipeline` and returns it in the running state.
+    ///
+    /// The snapshot's run identity is replaced with the new run's.
+    pub fn start(&self, pipeline: &str, snapshot: ContextSnapshot) -> Result<RunInfo, RunManagerError> {
+        let graph = self
+            .pipelines
+            .get(pipeline)
+            .ok_or_else(|| RunManagerError::UnknownPipeline(pipeline.to_string()))?;
+
+        let run_uuid = crate::utils::generate_uuid();
+        let run_id = run_uuid.to_string();
+        let identity = RunIdentity::with_pipeline_run_id(run_uuid);
+        let sink = Arc::new(RunEventSink {
+            run_id: run_id.clone(),
+            runs: Arc::clone(&self.runs),
+            downstream: Arc::clone(&self.event_sink),
+        });
+        let ctx = Arc::new(PipelineContext::new(identity.clone()).with_event_sink(sink));
+        let snapshot = snapshot.with_run_id(identity);
+
+        let (cancel, cancelled) = oneshot::channel();
+        let (finish, finished) = watch::channel(false);
+        let info = RunInfo {
+            run_id: run_id.clone(),
+            pipeline: pipeline.to_string(),
+            state: RunState::Running,
+            error: None,
+            duration_ms: None,
+            outputs: HashMap::new(),
+        };
+        self.runs.lock().entries.insert(
+            run_id.clone(),
+            RunEntry {
+                info: info.clone(),
+                events: Vec::new(),
+                live: Some(broadcast::channel(RUN_EVENT_BUFFER).0),
+                cancel: Some((Arc::clone(&ctx), cancel)),
+                finished,
+            },
+        );
+
+        let graph = Arc::clone(graph);
+        let runs = Arc::clone(&self.runs);
+        let max_retained_runs = self.max_retained_runs;
+        tokio::spawn(async move {
+            // Cancellation is otherwise only seen between stages
+            let result = tokio::select! {
+                result = graph.execute(ctx, snapshot) => result,
+                Ok(reason) = cancelled => Err(StageflowError::Cancelled(reason)),
+            };
+            Self::finish(&runs, &run_id, &result, max_retained_runs);
+            let _ = finish.send(true);
+        });
+        Ok(info)
+    }
+
+    /// Returns what is known about a run.
+    pub async fn status(&self, run_id: &str) -> Result<RunInfo, RunManagerError> {
+        let info = self.runs.lock().entries.get(run_id).map(|entry| entry.info.clone());
+        if let Some(info) = info {
+            return Ok(info);
+        }
+        let Some(store) = &self.run_store else {
+            return Err(RunManagerError::UnknownRun(run_id.to_string()));
+        };
+        match store.get(run_id).await {
+            Ok(Some(summary)) => Ok(summary.into()),
+            Ok(None) => Err(RunManagerError::UnknownRun(run_id.to_string())),
+            Err(e) => Err(RunManagerError::Store(e.to_string())),
+        }
+    }
+
+    /// Waits for a run to finish and returns it.
+    pub async fn wait(&self, run_id: &str) -> Result<RunInfo, RunManagerError> {
+        let finished = self.runs.lock().entries.get(run_id).map(|entry| entry.finished.clone());
+        if let Some(mut finished) = finished {
+            let _ = finished.wait_for(|done| *done).await;
+        }
+        self.status(run_id).await
+    }
+
+    /// Cancels a run, returning false if it had already finished.
+    pub fn cancel(&self, run_id: &str, reason: &str) -> Result<bool, RunManagerError> {
+        let mut runs = self.runs.lock();
+        let entry = runs
+            .entries
+            .get_mut(run_id)
+            .ok_or_else(|| RunManagerError::UnknownRun(run_id.to_string()))?;
+        let Some((ctx, cancel)) = entry.cancel.take() else {
+            return Ok(false);
+        };
+        let reason = if reason.is_empty() { "Cancelled remotely" } else { reason };
+        ctx.mark_cancelled_with_reason(reason);
+        Ok(cancel.send(reason.to_string()).is_ok())
+    }
+
+    /// Streams a run's events from the first, ending when the run
+    /// finishes.
+    ///
+    /// A subscriber more than [`RUN_EVENT_BUFFER`] events behind receives
+    /// [`RunManagerError::Lagged`] and then continues with the oldest
+    /// event still buffered.
+    pub fn events(&self, run_id: &str) -> Result<RunEventStream, RunManagerError> {
+        let runs = self.runs.lock();
+        let entry = runs
+            .entries
+            .get(run_id)
+            .ok_or_else(|| RunManagerError::UnknownRun(run_id.to_string()))?;
+        let backlog = stream::iter(entry.events.clone()).map(Ok);
+        let Some(live) = &entry.live else {
+            return Ok(backlog.boxed());
+        };
+        // Subscribing under the lock means no event is missed or repeated
+        let live = stream::unfold(live.subscribe(), |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(event) => Some((Ok(event), receiver)),
+                Err(RecvError::Lagged(missed)) => Some((Err(RunManagerError::Lagged(missed)), receiver)),
+                Err(RecvError::Closed) => None,
+            }
+        });
+        Ok(backlog.chain(live).boxed())
+    }
+
+    fn finish(
+        runs: &Mutex<Runs>,
+        run_id: &str,
+        result: &Result<UnifiedExecutionResult, StageflowError>,
+        max_retained_runs: usize,
+    ) {
+        let mut runs = runs.lock();
+        let Some(entry) = runs.entries.get_mut(run_id) else {
+            return;
+        };
+        let info = &mut entry.info;
+        match result {
+            Ok(result) => {
+                info.state = if result.cancelled {
+                    RunState::Cancelled
+                } else if result.success {
+                    RunState::Succeeded
+                } else {
+                    RunState::Failed
+                };
+                info.error = result.error.clone().or_else(|| result.cancel_reason.clone());
+                info.duration_ms = Some(result.duration_ms);
+                info.outputs.clone_from(&result.outputs);
+            }
+            Err(e) => {
+                info.state = if matches!(e, StageflowError::Cancelled(_)) {
+                    RunState::Cancelled
+                } else {
+                    RunState::Failed
+                };
+                info.error = Some(e.to_string());
+            }
+        }
+        // Dropping the sender ends every subscriber's stream
+        entry.live = None;
+        entry.cancel = None;
+
+        runs.finished.push_back(run_id.to_string());
+        while runs.finished.len() > max_retained_runs {
+            if let Some(oldest) = runs.finished.pop_front() {
+                runs.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl Default for RunManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for RunManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunManager")
+            .field("pipelines", &self.pipeline_names())
+            .field("max_retained_runs", &self.max_retained_runs)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Records a run's events for [`RunManager::events`] and forwards them
+/// downstream.
+struct RunEventSink {
+    run_id: String,
+    runs: Arc<Mutex<Runs>>,
+    downstream: Arc<dyn EventSink>,
+}
+
+impl RunEventSink {
+    fn record(&self, event_type: &str, data: Option<&serde_json::Value>) {
+        let mut runs = self.runs.lock();
+        let Some(entry) = runs.entries.get_mut(&self.run_id) else {
+            return;
+        };
+        let event = RecordedEvent {
+            seq: entry.events.len() as u64 + 1,
+            run_id: self.run_id.clone(),
+            event_type: event_type.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |since_epoch| since_epoch.as_secs_f64()),
+            data: data.cloned(),
+        };
+        if let Some(live) = &entry.live {
+            // No subscribers is fine; the event is in the backlog
+            let _ = live.send(event.clone());
+        }
+        entry.events.push(event);
+    }
+}
+
+#[async_trait]
+impl EventSink for RunEventSink {
+    async fn emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.record(event_type, data.as_ref());
+        self.downstream.emit(event_type, data).await;
+    }
+
+    fn try_emit(&self, event_type: &str, data: Option<serde_json::Value>) {
+        self.record(event_type, data.as_ref());
+        self.downstream.try_emit(event_type, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{InMemoryRunStore, PipelineBuilder};
+    use crate::testing::SlowStage;
+
+    fn graph(delay_ms: u64) -> UnifiedStageGraph {
+        let stage = Arc::new(SlowStage::with_delay_ms("work", delay_ms));
+        let graph = PipelineBuilder::new("jobs").stage("work", stage, &[]).unwrap().build().unwrap();
+        UnifiedStageGraph::new(graph)
+    }
+
+    #[tokio::test]
+    async fn test_run_status_and_event_replay() {
+        let store = Arc::new(InMemoryRunStore::new());
+        let graph = graph(0).with_run_store(store.clone());
+        let manager = RunManager::new()
+            .with_pipeline(Arc::new(graph))
+            .with_run_store(store)
+            .with_max_retained_runs(0);
+        let started = manager.start("jobs", ContextSnapshot::new()).unwrap();
+        assert_eq!(started.state, RunState::Running);
+
+        let mut events = manager.events(&started.run_id).unwrap();
+        let finished = manager.wait(&started.run_id).await.unwrap();
+        assert_eq!(finished.state, RunState::Succeeded);
+
+        // The stream ends with the run, with events numbered from 1
+        let mut seqs = Vec::new();
+        while let Some(event) = events.next().await {
+            seqs.push(event.unwrap().seq);
+        }
+        assert!(!seqs.is_empty());
+        assert!(seqs.iter().enumerate().all(|(i, seq)| *seq == i as u64 + 1));
+
+        // Evicted from memory, the run is still found in the store
+        let stored = manager.status(&started.run_id).await.unwrap();
+        assert_eq!((stored.state, stored.outputs.len()), (RunState::Succeeded, 0));
+        assert_eq!(
+            manager.start("nope", ContextSnapshot::new()).unwrap_err(),
+            RunManagerError::UnknownPipeline("nope".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_run() {
+        let manager = RunManager::new().with_pipeline(Arc::new(graph(5_000)));
+        let run = manager.start("jobs", ContextSnapshot::new()).unwrap();
+
+        assert!(manager.cancel(&run.run_id, "operator").unwrap());
+        let finished = manager.wait(&run.run_id).await.unwrap();
+        assert_eq!(finished.state, RunState::Cancelled);
+        assert!(finished.error.unwrap().contains("operator"));
+        assert!(!manager.cancel(&run.run_id, "again").unwrap());
+        assert_eq!(
+            manager.cancel("missing", "").unwrap_err(),
+            RunManagerError::UnknownRun("missing".to_string())
+        );
+    }
+}
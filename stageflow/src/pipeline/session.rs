@@ -0,0 +1,239 @@
+//! Per-session conversation state for multi-turn pipelines.
+//!
+//! A [`SessionManager`] attached with
+//! [`UnifiedStageGraph::with_session_manager`](super::UnifiedStageGraph::with_session_manager)
+//! prepends the session's history to each run's snapshot and, after a
+//! successful run, appends the user's input and the run's responses to it.
+
+use crate::context::{ContextSnapshot, Conversation, Message};
+use crate::core::StageOutput;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Output key read as the assistant's reply by default.
+pub const DEFAULT_SESSION_RESPONSE_KEY: &str = "response";
+
+/// A stored session.
+#[derive(Debug)]
+struct Session {
+    snapshot: ContextSnapshot,
+    last_used: Instant,
+}
+
+/// Keeps a [`ContextSnapshot`] per session ID across pipeline runs.
+///
+/// Only the conversation carries over between runs; enrichments, input
+/// text and metadata come from each run's own snapshot. Sessions unused
+/// for longer than the TTL are dropped on access or by
+/// [`evict_expired`](Self::evict_expired).
+#[derive(Debug)]
+pub struct SessionManager {
+    sessions: RwLock<HashMap<Uuid, Session>>,
+    ttl: Option<Duration>,
+    response_key: String,
+    max_messages: Option<usize>,
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionManager {
+    /// Creates a manager whose sessions never expire.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+            ttl: None,
+            response_key: DEFAULT_SESSION_RESPONSE_KEY.to_string(),
+            max_messages: None,
+        }
+    }
+
+    /// Drops sessions unused for longer than `ttl`.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the output key whose string value is recorded as the reply.
+    #[must_use]
+    pub fn with_response_key(mut self, key: impl Into<String>) -> Self {
+        self.response_key = key.into();
+        self
+    }
+
+    /// Keeps at most `max` messages per session, dropping the oldest.
+    #[must_use]
+    pub fn with_max_messages(mut self, max: usize) -> Self {
+        self.max_messages = Some(max);
+        self
+    }
+
+    /// Returns the number of stored sessions, including expired ones not
+    /// yet evicted.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sessions.read().len()
+    }
+
+    /// Returns whether no sessions are stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sessions.read().is_empty()
+    }
+
+    fn is_expired(&self, session: &Session) -> bool {
+        self.ttl.is_some_and(|ttl| session.last_used.elapsed() > ttl)
+    }
+
+    /// Returns a session's snapshot, unless it is missing or expired.
+    #[must_use]
+    pub fn get(&self, session_id: Uuid) -> Option<ContextSnapshot> {
+        let mut sessions = self.sessions.write();
+        let session = sessions.get_mut(&session_id)?;
+        if self.is_expired(session) {
+            sessions.remove(&session_id);
+            return None;
+        }
+        session.last_used = Instant::now();
+        Some(session.snapshot.clone())
+    }
+
+    /// Stores a session's snapshot, replacing any existing one.
+    pub fn insert(&self, session_id: Uuid, snapshot: ContextSnapshot) {
+        self.sessions.write().insert(session_id, Session { snapshot, last_used: Instant::now() });
+    }
+
+    /// Removes a session, returning whether it existed.
+    pub fn remove(&self, session_id: Uuid) -> bool {
+        self.sessions.write().remove(&session_id).is_some()
+    }
+
+    /// Removes expired sessions, returning how many were removed.
+    pub fn evict_expired(&self) -> usize {
+        let mut sessions = self.sessions.write();
+        let before = sessions.len();
+        sessions.retain(|_, session| !self.is_expired(session));
+        before - sessions.len()
+    }
+
+    /// Prepares a run's snapshot by prepending the session's history to
+    /// its conversation.
+    #[must_use]
+    pub fn load_for_run(&self, session_id: Uuid, snapshot: ContextSnapshot) -> ContextSnapshot {
+        let Some(session) = self.get(session_id) else {
+            return snapshot;
+        };
+        let history = session.conversation;
+        let routing_decision = snapshot.conversation.routing_decision.clone().or(history.routing_decision);
+        let mut messages = history.messages;
+        messages.extend(snapshot.conversation.messages.iter().cloned());
+        let mut conversation = Conversation::with_messages(messages);
+        conversation.routing_decision = routing_decision;
+        snapshot.with_conversation(conversation)
+    }
+
+    /// Records a finished run in the session.
+    ///
+    /// The stored conversation is the run's conversation followed by its
+    /// input text as a user turn, unless that repeats the last message,
+    /// and by the response of every successful stage, ordered by stage
+    /// name, as assistant turns.
+    pub fn commit_after_run(&self, session_id: Uuid, snapshot: &ContextSnapshot, outputs: &HashMap<String, StageOutput>) {
+        let mut messages = snapshot.conversation.messages.clone();
+        if let Some(input) = &snapshot.input_text {
+            if messages.last().map_or(true, |m| m.content != *input) {
+                messages.push(Message::user(input.clone()));
+            }
+        }
+        let mut responses: Vec<(&String, &str)> = outputs
+            .iter()
+            .filter(|(_, output)| output.is_success())
+            .filter_map(|(stage, output)| Some((stage, output.get(&self.response_key)?.as_str()?)))
+            .collect();
+        responses.sort_unstable();
+        for (stage, response) in responses {
+            let mut message = Message::assistant(response);
+            message.metadata.insert("stage".to_string(), serde_json::json!(stage));
+            messages.push(message);
+        }
+        if let Some(max) = self.max_messages {
+            let excess = messages.len().saturating_sub(max);
+            messages.drain(..excess);
+        }
+
+        let mut conversation = Conversation::with_messages(messages);
+        conversation.routing_decision.clone_from(&snapshot.conversation.routing_decision);
+        self.insert(session_id, snapshot.clone().with_conversation(conversation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{PipelineContext, RunIdentity, StageContext};
+    use crate::pipeline::{PipelineBuilder, UnifiedStageGraph};
+    use crate::stages::Stage;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    /// Replies with the number of messages it saw.
+    #[derive(Debug)]
+    struct CountingReply;
+
+    #[async_trait]
+    impl Stage for CountingReply {
+        fn name(&self) -> &'static str {
+            "reply"
+        }
+
+        async fn execute(&self, ctx: &StageContext) -> StageOutput {
+            let seen = ctx.snapshot().conversation.messages.len();
+            StageOutput::ok_value("response", serde_json::json!(format!("seen {seen}")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_share_session_conversation() {
+        let sessions = Arc::new(SessionManager::new().with_max_messages(3));
+        let graph = PipelineBuilder::new("chat").stage("reply", Arc::new(CountingReply), &[]).unwrap().build().unwrap();
+        let unified = UnifiedStageGraph::new(graph).with_session_manager(sessions.clone());
+        let session_id = Uuid::new_v4();
+
+        for input in ["hi", "again"] {
+            let ctx = Arc::new(PipelineContext::new(RunIdentity::new().with_session_id(session_id)));
+            let result = unified.execute(ctx, ContextSnapshot::new().with_input_text(input)).await.unwrap();
+            assert!(result.success);
+        }
+
+        let history = sessions.get(session_id).unwrap().conversation.messages;
+        let turns: Vec<(&str, &str)> = history.iter().map(|m| (m.role.as_str(), m.content.as_str())).collect();
+        assert_eq!(turns, vec![("assistant", "seen 0"), ("user", "again"), ("assistant", "seen 2")]);
+        assert_eq!(history[2].metadata["stage"], "reply");
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        unified.execute(ctx, ContextSnapshot::new().with_input_text("anonymous")).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn test_ttl_eviction() {
+        let sessions = SessionManager::new().with_ttl(Duration::from_millis(20));
+        let (stale, fresh) = (Uuid::new_v4(), Uuid::new_v4());
+        sessions.insert(stale, ContextSnapshot::new());
+        std::thread::sleep(Duration::from_millis(30));
+        sessions.insert(fresh, ContextSnapshot::new());
+
+        assert_eq!(sessions.evict_expired(), 1);
+        assert!(sessions.get(stale).is_none());
+        let snapshot = ContextSnapshot::new().with_conversation(Conversation::with_messages(vec![Message::user("new")]));
+        assert_eq!(sessions.load_for_run(stale, snapshot).conversation.messages.len(), 1);
+        assert!(sessions.get(fresh).is_some());
+    }
+}
@@ -0,0 +1,308 @@
+//! Draining in-flight pipelines on process shutdown.
+//!
+//! A [`Shutdown`] tracks the runs an embedder starts. One call to
+//! [`Shutdown::shutdown`] cancels them all, waits up to a deadline for their
+//! stages to finish, runs the registered cleanup callbacks and flushes event
+//! sink tasks.
+
+use super::{CancellationToken, CleanupRegistry};
+use crate::context::PipelineContext;
+use crate::events::wait_for_event_sink_tasks;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Cancel reason given to runs cancelled by [`Shutdown::shutdown`].
+pub const SHUTDOWN_REASON: &str = "shutdown";
+
+/// Default time allowed for cleanup callbacks and the event sink flush.
+pub const DEFAULT_SHUTDOWN_CLEANUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct Runs {
+    tokens: Mutex<HashMap<u64, Arc<CancellationToken>>>,
+    next_id: AtomicU64,
+    drained: Notify,
+}
+
+/// Coordinates a graceful shutdown of the runs in flight.
+///
+/// Register each run with [`track`](Self::track), [`track_context`](Self::track_context)
+/// or [`run`](Self::run); runs started after shutdown begins are cancelled
+/// immediately.
+#[derive(Debug)]
+pub struct Shutdown {
+    token: Arc<CancellationToken>,
+    runs: Arc<Runs>,
+    cleanup: Arc<CleanupRegistry>,
+    cleanup_timeout: Duration,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Creates a coordinator with no runs in flight.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            runs: Arc::new(Runs::default()),
+            cleanup: Arc::new(CleanupRegistry::new()),
+            cleanup_timeout: DEFAULT_SHUTDOWN_CLEANUP_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for cleanup callbacks and, separately, for
+    /// flushing event sink tasks.
+    #[must_use]
+    pub fn with_cleanup_timeout(mut self, timeout: Duration) -> Self {
+        self.cleanup_timeout = timeout;
+        self
+    }
+
+    /// Returns the token cancelled when shutdown begins.
+    #[must_use]
+    pub fn token(&self) -> &Arc<CancellationToken> {
+        &self.token
+    }
+
+    /// Returns the registry of callbacks run once the runs have drained.
+    #[must_use]
+    pub fn cleanup_registry(&self) -> &Arc<CleanupRegistry> {
+        &self.cleanup
+    }
+
+    /// Returns true once shutdown has begun.
+    #[must_use]
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Returns the number of tracked runs still in flight.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.runs.tokens.lock().len()
+    }
+
+    /// Tracks a run cancelled through `token`.
+    ///
+    /// The run counts as in flight until the returned guard is dropped.
+    #[must_use]
+    pub fn track(&self, token: &Arc<CancellationToken>) -> ShutdownGuard {
+        let id = self.runs.next_id.fetch_add(1, Ordering::Relaxed);
+        self.runs.tokens.lock().insert(id, Arc::clone(token));
+        if let Some(reason) = self.token.reason() {
+            token.cancel(reason);
+        }
+        ShutdownGuard {
+            id,
+            runs: Arc::downgrade(&self.runs),
+        }
+    }
+
+    /// Tracks a run executing with `ctx`, cancelling the context on shutdown.
+    #[must_use]
+    pub fn track_context(&self, ctx: &Arc<PipelineContext>) -> ShutdownGuard {
+        let token = CancellationToken::new();
+        let ctx = Arc::downgrade(ctx);
+        token.on_cancel(move |reason| {
+            if let Some(ctx) = ctx.upgrade() {
+                ctx.mark_cancelled_with_reason(reason);
+            }
+        });
+        self.track(&token)
+    }
+
+    /// Runs `run` as a tracked run executing with `ctx`.
+    pub async fn run<F: Future>(&self, ctx: &Arc<PipelineContext>, run: F) -> F::Output {
+        let _guard = self.track_context(ctx);
+        run.await
+    }
+
+    /// Shuts down with [`SHUTDOWN_REASON`] as the cancel reason.
+    ///
+    /// See [`shutdown_with_reason`](Self::shutdown_with_reason).
+    pub async fn shutdown(&self, deadline: Duration) -> ShutdownReport {
+        self.shutdown_with_reason(deadline, SHUTDOWN_REASON).await
+    }
+
+    /// Cancels every tracked run, waits up to `deadline` for them to
+    /// finish, then runs the cleanup callbacks and flushes event sink tasks.
+    ///
+    /// Cleanup and the flush happen even if runs are still in flight at the
+    /// deadline; the report says how many were.
+    pub async fn shutdown_with_reason(&self, deadline: Duration, reason: &str) -> ShutdownReport {
+        let start = Instant::now();
+        self.token.cancel(reason);
+        let tokens: Vec<_> = self.runs.tokens.lock().values().cloned().collect();
+        for token in tokens {
+            token.cancel(reason);
+        }
+
+        let drained = tokio::time::timeout_at(start + deadline, self.drained()).await.is_ok();
+        let (cleanup_completed, cleanup_failed) =
+            self.cleanup.run_all(self.cleanup_timeout.as_secs_f64()).await;
+        let events_flushed = tokio::time::timeout(self.cleanup_timeout, wait_for_event_sink_tasks())
+            .await
+            .is_ok();
+
+        ShutdownReport {
+            drained,
+            abandoned: self.in_flight(),
+            cleanup_completed,
+            cleanup_failed,
+            events_flushed,
+            duration: start.elapsed(),
+        }
+    }
+
+    /// Resolves once no tracked run is in flight.
+    async fn drained(&self) {
+        loop {
+            let notified = self.runs.drained.notified();
+            if self.runs.tokens.lock().is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Keeps a run counted as in flight by its [`Shutdown`] until dropped.
+#[derive(Debug)]
+pub struct ShutdownGuard {
+    id: u64,
+    runs: Weak<Runs>,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        if let Some(runs) = self.runs.upgrade() {
+            let mut tokens = runs.tokens.lock();
+            tokens.remove(&self.id);
+            if tokens.is_empty() {
+                runs.drained.notify_waiters();
+            }
+        }
+    }
+}
+
+/// Outcome of [`Shutdown::shutdown`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Whether every run finished before the deadline.
+    pub drained: bool,
+    /// Runs still in flight when shutdown returned.
+    pub abandoned: usize,
+    /// Cleanup callbacks that completed.
+    pub cleanup_completed: Vec<String>,
+    /// Cleanup callbacks that failed, with the error.
+    pub cleanup_failed: Vec<(String, String)>,
+    /// Whether pending event sink tasks finished within the cleanup timeout.
+    pub events_flushed: bool,
+    /// Total time taken.
+    pub duration: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, ExecutionContext, RunIdentity, StageContext};
+    use crate::core::StageOutput;
+    use crate::pipeline::{PipelineBuilder, UnifiedStageGraph};
+    use crate::stages::Stage;
+    use async_trait::async_trait;
+
+    /// A stage that runs until its run is cancelled.
+    #[derive(Debug)]
+    struct WaitStage {
+        name: String,
+    }
+
+    #[async_trait]
+    impl Stage for WaitStage {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn execute(&self, ctx: &StageContext) -> StageOutput {
+            while !ctx.is_cancelled() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            StageOutput::ok_empty()
+        }
+    }
+
+    fn waiting_pipeline() -> UnifiedStageGraph {
+        let stage = Arc::new(WaitStage { name: "wait".into() });
+        let graph = PipelineBuilder::new("test").stage("wait", stage, &[]).unwrap().build().unwrap();
+        UnifiedStageGraph::new(graph)
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_runs_and_runs_cleanup() {
+        let shutdown = Arc::new(Shutdown::new());
+        let cleaned = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        for name in ["first", "second"] {
+            let cleaned = cleaned.clone();
+            shutdown.cleanup_registry().register(name, move || async move {
+                cleaned.lock().push(name);
+            });
+        }
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let run = {
+            let shutdown = shutdown.clone();
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                shutdown.run(&ctx, waiting_pipeline().execute(ctx.clone(), ContextSnapshot::new())).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(shutdown.in_flight(), 1);
+
+        let report = shutdown.shutdown(Duration::from_secs(5)).await;
+        assert!(report.drained);
+        assert_eq!(report.abandoned, 0);
+        assert_eq!(report.cleanup_completed, vec!["second", "first"]);
+        assert!(report.events_flushed);
+        assert_eq!(*cleaned.lock(), vec!["second", "first"]);
+        assert_eq!(ctx.cancel_reason().as_deref(), Some(SHUTDOWN_REASON));
+        assert!(run.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_gives_up_at_deadline() {
+        let shutdown = Shutdown::new();
+        let stuck = CancellationToken::new();
+        let guard = shutdown.track(&stuck);
+
+        let report = shutdown.shutdown_with_reason(Duration::from_millis(20), "redeploy").await;
+        assert!(!report.drained);
+        assert_eq!(report.abandoned, 1);
+        assert_eq!(stuck.reason().as_deref(), Some("redeploy"));
+
+        drop(guard);
+        assert_eq!(shutdown.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_runs_tracked_after_shutdown_are_cancelled() {
+        let shutdown = Shutdown::new();
+        assert!(shutdown.shutdown(Duration::from_millis(10)).await.drained);
+        assert!(shutdown.is_shutting_down());
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let _guard = shutdown.track_context(&ctx);
+        assert!(ctx.is_cancelled());
+    }
+}
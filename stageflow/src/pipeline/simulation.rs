@@ -0,0 +1,552 @@
+//! Offline latency and cost simulation for stage graphs.
+//!
+//! Given per-stage latency and cost distributions, the simulator replays the
+//! graph's dependency structure many times with sampled durations and reports
+//! percentiles for end-to-end latency, cost, and time spent waiting for a
+//! concurrency slot. Nothing is executed; stages are never invoked.
+
+use super::StageGraph;
+use crate::errors::{PipelineValidationError, StageflowError};
+use crate::executor::DependencyTracker;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+/// A distribution of non-negative values (milliseconds or cost units).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Distribution {
+    /// Always the same value.
+    Fixed {
+        /// The value.
+        value: f64,
+    },
+    /// Uniform between `min` and `max`.
+    Uniform {
+        /// Lower bound.
+        min: f64,
+        /// Upper bound.
+        max: f64,
+    },
+    /// Normal distribution, truncated at zero.
+    Normal {
+        /// Mean.
+        mean: f64,
+        /// Standard deviation.
+        std_dev: f64,
+    },
+    /// Resamples observed values, e.g. durations from past runs.
+    Empirical {
+        /// Observed values.
+        samples: Vec<f64>,
+    },
+}
+
+impl Distribution {
+    /// Creates a fixed distribution.
+    #[must_use]
+    pub fn fixed(value: f64) -> Self {
+        Self::Fixed { value }
+    }
+
+    /// Creates an empirical distribution from observed values.
+    #[must_use]
+    pub fn empirical(samples: Vec<f64>) -> Self {
+        Self::Empirical { samples }
+    }
+
+    /// Draws one value. Results are clamped to be non-negative.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        let value = match self {
+            Self::Fixed { value } => *value,
+            Self::Uniform { min, max } if max > min => rng.gen_range(*min..=*max),
+            Self::Uniform { min, .. } => *min,
+            Self::Normal { mean, std_dev } => {
+                // Box-Muller transform
+                let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                mean + z * std_dev
+            }
+            Self::Empirical { samples } if samples.is_empty() => 0.0,
+            Self::Empirical { samples } => samples[rng.gen_range(0..samples.len())],
+        };
+        value.max(0.0)
+    }
+}
+
+impl Default for Distribution {
+    fn default() -> Self {
+        Self::fixed(0.0)
+    }
+}
+
+/// Latency and cost distributions for one stage.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageProfile {
+    /// Latency in milliseconds.
+    pub latency_ms: Distribution,
+    /// Cost per execution.
+    #[serde(default)]
+    pub cost: Distribution,
+}
+
+impl StageProfile {
+    /// Creates a profile with the given latency and zero cost.
+    #[must_use]
+    pub fn new(latency_ms: Distribution) -> Self {
+        Self {
+            latency_ms,
+            cost: Distribution::default(),
+        }
+    }
+
+    /// Sets the cost distribution.
+    #[must_use]
+    pub fn with_cost(mut self, cost: Distribution) -> Self {
+        self.cost = cost;
+        self
+    }
+}
+
+/// Accumulates observed stage latencies and costs from past runs.
+#[derive(Debug, Clone, Default)]
+pub struct HistoricalStats {
+    latencies: HashMap<String, Vec<f64>>,
+    costs: HashMap<String, Vec<f64>>,
+}
+
+impl HistoricalStats {
+    /// Creates empty stats.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one stage execution.
+    pub fn record(&mut self, stage: &str, latency_ms: f64, cost: Option<f64>) {
+        self.latencies.entry(stage.to_string()).or_default().push(latency_ms);
+        if let Some(cost) = cost {
+            self.costs.entry(stage.to_string()).or_default().push(cost);
+        }
+    }
+
+    /// Records every `stage.completed` event carrying a `duration_ms`.
+    ///
+    /// A numeric `cost` field in the event data is recorded as well.
+    pub fn record_events(&mut self, events: &[(String, Option<serde_json::Value>)]) {
+        for (event_type, data) in events {
+            if event_type != "stage.completed" {
+                continue;
+            }
+            let Some(data) = data else { continue };
+            let stage = data.get("stage").and_then(serde_json::Value::as_str);
+            let duration = data.get("duration_ms").and_then(serde_json::Value::as_f64);
+            if let (Some(stage), Some(duration)) = (stage, duration) {
+                let cost = data.get("cost").and_then(serde_json::Value::as_f64);
+                self.record(stage, duration, cost);
+            }
+        }
+    }
+
+    /// Returns the number of recorded executions for a stage.
+    #[must_use]
+    pub fn sample_count(&self, stage: &str) -> usize {
+        self.latencies.get(stage).map_or(0, Vec::len)
+    }
+
+    /// Converts the observations into empirical stage profiles.
+    #[must_use]
+    pub fn into_profiles(self) -> HashMap<String, StageProfile> {
+        let mut costs = self.costs;
+        self.latencies
+            .into_iter()
+            .map(|(stage, latencies)| {
+                let cost = costs
+                    .remove(&stage)
+                    .map_or_else(Distribution::default, Distribution::empirical);
+                let profile = StageProfile::new(Distribution::empirical(latencies)).with_cost(cost);
+                (stage, profile)
+            })
+            .collect()
+    }
+}
+
+/// Options for a simulation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Number of simulated runs.
+    pub iterations: usize,
+    /// Maximum stages in flight at once; `None` is unbounded.
+    pub max_concurrency: Option<usize>,
+    /// Seed for sampling; `None` seeds from entropy.
+    pub seed: Option<u64>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 1000,
+            max_concurrency: None,
+            seed: None,
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// Creates a config with default values.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of simulated runs.
+    #[must_use]
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets the concurrency limit.
+    #[must_use]
+    pub fn with_max_concurrency(mut self, limit: usize) -> Self {
+        self.max_concurrency = Some(limit);
+        self
+    }
+
+    /// Sets the sampling seed.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// Summary statistics over simulated values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    /// Smallest value.
+    pub min: f64,
+    /// Arithmetic mean.
+    pub mean: f64,
+    /// Median.
+    pub p50: f64,
+    /// 90th percentile.
+    pub p90: f64,
+    /// 95th percentile.
+    pub p95: f64,
+    /// 99th percentile.
+    pub p99: f64,
+    /// Largest value.
+    pub max: f64,
+}
+
+impl Percentiles {
+    /// Computes nearest-rank percentiles. Empty input yields all zeros.
+    #[must_use]
+    pub fn from_samples(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(f64::total_cmp);
+
+        let rank = |percent: usize| {
+            let index = (percent * samples.len()).div_ceil(100).clamp(1, samples.len()) - 1;
+            samples[index]
+        };
+        let (sum, count) = samples
+            .iter()
+            .fold((0.0, 0.0), |(sum, count), value| (sum + value, count + 1.0));
+
+        Self {
+            min: samples[0],
+            mean: sum / count,
+            p50: rank(50),
+            p90: rank(90),
+            p95: rank(95),
+            p99: rank(99),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// Result of simulating a graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    /// Number of simulated runs.
+    pub iterations: usize,
+    /// Concurrency limit that was simulated.
+    pub max_concurrency: Option<usize>,
+    /// End-to-end pipeline latency in milliseconds.
+    pub latency_ms: Percentiles,
+    /// Total cost per run.
+    pub cost: Percentiles,
+    /// Total time per run that ready stages spent waiting for a slot.
+    pub queue_wait_ms: Percentiles,
+}
+
+/// Simulates a graph using the given stage profiles.
+///
+/// Every stage in the graph needs a profile. Ready stages start in the
+/// graph's execution order as slots free up, mirroring the DAG engine.
+pub fn simulate<S: BuildHasher>(
+    graph: &StageGraph,
+    profiles: &HashMap<String, StageProfile, S>,
+    config: &SimulationConfig,
+) -> Result<SimulationReport, StageflowError> {
+    let missing: Vec<String> = graph
+        .execution_order()
+        .iter()
+        .filter(|name| !profiles.contains_key(*name))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        return Err(PipelineValidationError::new(format!(
+            "Missing simulation profiles for stages: {}",
+            missing.join(", ")
+        ))
+        .with_stages(missing)
+        .into());
+    }
+    if config.max_concurrency == Some(0) {
+        return Err(PipelineValidationError::new("max_concurrency must be at least 1").into());
+    }
+
+    let mut rng = match config.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let mut latencies = Vec::with_capacity(config.iterations);
+    let mut costs = Vec::with_capacity(config.iterations);
+    let mut waits = Vec::with_capacity(config.iterations);
+    for _ in 0..config.iterations {
+        let run = simulate_once(graph, profiles, config.max_concurrency, &mut rng)?;
+        latencies.push(run.latency_ms);
+        costs.push(run.cost);
+        waits.push(run.queue_wait_ms);
+    }
+
+    Ok(SimulationReport {
+        iterations: config.iterations,
+        max_concurrency: config.max_concurrency,
+        latency_ms: Percentiles::from_samples(latencies),
+        cost: Percentiles::from_samples(costs),
+        queue_wait_ms: Percentiles::from_samples(waits),
+    })
+}
+
+/// Runs [`simulate`] once per concurrency limit for what-if comparison.
+///
+/// Each limit is simulated with the same seed, so differences between the
+/// reports come from the limit rather than from sampling noise.
+pub fn simulate_concurrency_limits<S: BuildHasher>(
+    graph: &StageGraph,
+    profiles: &HashMap<String, StageProfile, S>,
+    config: &SimulationConfig,
+    limits: &[Option<usize>],
+) -> Result<Vec<SimulationReport>, StageflowError> {
+    let seed = config.seed.unwrap_or_else(rand::random);
+    limits
+        .iter()
+        .map(|limit| {
+            let config = SimulationConfig {
+                max_concurrency: *limit,
+                seed: Some(seed),
+                ..config.clone()
+            };
+            simulate(graph, profiles, &config)
+        })
+        .collect()
+}
+
+struct SimulatedRun {
+    latency_ms: f64,
+    cost: f64,
+    queue_wait_ms: f64,
+}
+
+fn simulate_once<S: BuildHasher>(
+    graph: &StageGraph,
+    profiles: &HashMap<String, StageProfile, S>,
+    max_concurrency: Option<usize>,
+    rng: &mut StdRng,
+) -> Result<SimulatedRun, StageflowError> {
+    let limit = max_concurrency.unwrap_or(usize::MAX);
+    let mut tracker = DependencyTracker::new(graph);
+    let mut ready_at: HashMap<String, f64> = HashMap::new();
+    let mut running: Vec<(f64, String)> = Vec::new();
+    let mut now = 0.0_f64;
+    let mut cost = 0.0;
+    let mut queue_wait_ms = 0.0;
+
+    while !tracker.is_complete() {
+        while running.len() < limit {
+            let Some(name) = tracker.take_next_ready() else { break };
+            let profile = &profiles[&name];
+            queue_wait_ms += now - ready_at.get(&name).copied().unwrap_or(0.0);
+            cost += profile.cost.sample(rng);
+            running.push((now + profile.latency_ms.sample(rng), name));
+        }
+
+        let Some(index) = running
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))
+            .map(|(i, _)| i)
+        else {
+            return Err(StageflowError::Internal(format!(
+                "Deadlocked stage graph; remaining stages: {:?}",
+                tracker.remaining()
+            )));
+        };
+
+        let (finished_at, name) = running.swap_remove(index);
+        now = finished_at;
+        for child in tracker.mark_complete(&name) {
+            ready_at.insert(child, now);
+        }
+    }
+
+    Ok(SimulatedRun {
+        latency_ms: now,
+        cost,
+        queue_wait_ms,
+    })
+}
+
+impl StageGraph {
+    /// Estimates latency and cost percentiles without executing any stage.
+    ///
+    /// See [`simulate`].
+    pub fn simulate<S: BuildHasher>(
+        &self,
+        profiles: &HashMap<String, StageProfile, S>,
+        config: &SimulationConfig,
+    ) -> Result<SimulationReport, StageflowError> {
+        simulate(self, profiles, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::{NoOpStage, Stage};
+    use std::sync::Arc;
+
+    fn noop(name: &str) -> Arc<dyn Stage> {
+        Arc::new(NoOpStage::new(name))
+    }
+
+    /// Three independent stages feeding a final join.
+    fn fan_in() -> StageGraph {
+        PipelineBuilder::new("fan_in")
+            .stage("a", noop("a"), &[])
+            .unwrap()
+            .stage("b", noop("b"), &[])
+            .unwrap()
+            .stage("c", noop("c"), &[])
+            .unwrap()
+            .stage("join", noop("join"), &["a", "b", "c"])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn fixed_profiles() -> HashMap<String, StageProfile> {
+        ["a", "b", "c", "join"]
+            .iter()
+            .map(|name| {
+                let profile = StageProfile::new(Distribution::fixed(100.0))
+                    .with_cost(Distribution::fixed(0.5));
+                ((*name).to_string(), profile)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_latency_parallel() {
+        let config = SimulationConfig::new().with_iterations(10).with_seed(1);
+        let report = fan_in().simulate(&fixed_profiles(), &config).unwrap();
+
+        assert!((report.latency_ms.p50 - 200.0).abs() < f64::EPSILON);
+        assert!((report.cost.mean - 2.0).abs() < f64::EPSILON);
+        assert!(report.queue_wait_ms.max.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_concurrency_what_if() {
+        let config = SimulationConfig::new().with_iterations(5);
+        let reports =
+            simulate_concurrency_limits(&fan_in(), &fixed_profiles(), &config, &[None, Some(1)])
+                .unwrap();
+
+        assert!((reports[0].latency_ms.p99 - 200.0).abs() < f64::EPSILON);
+        assert!((reports[1].latency_ms.p99 - 400.0).abs() < f64::EPSILON);
+        // b waits 100ms and c waits 200ms behind a
+        assert!((reports[1].queue_wait_ms.mean - 300.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_missing_profile_is_rejected() {
+        let mut profiles = fixed_profiles();
+        profiles.remove("join");
+
+        let err = simulate(&fan_in(), &profiles, &SimulationConfig::new()).unwrap_err();
+        assert!(err.to_string().contains("join"));
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let mut profiles = fixed_profiles();
+        profiles.insert(
+            "a".to_string(),
+            StageProfile::new(Distribution::Normal {
+                mean: 100.0,
+                std_dev: 30.0,
+            }),
+        );
+        let config = SimulationConfig::new().with_iterations(200).with_seed(9);
+
+        let first = simulate(&fan_in(), &profiles, &config).unwrap();
+        let second = simulate(&fan_in(), &profiles, &config).unwrap();
+        assert_eq!(first, second);
+        assert!(first.latency_ms.p99 >= first.latency_ms.p50);
+    }
+
+    #[test]
+    fn test_historical_stats_from_events() {
+        let events = vec![
+            (
+                "stage.completed".to_string(),
+                Some(serde_json::json!({"stage": "a", "duration_ms": 12.0, "cost": 0.2})),
+            ),
+            (
+                "stage.completed".to_string(),
+                Some(serde_json::json!({"stage": "a", "duration_ms": 18.0})),
+            ),
+            ("stage.started".to_string(), Some(serde_json::json!({"stage": "a"}))),
+        ];
+        let mut stats = HistoricalStats::new();
+        stats.record_events(&events);
+        assert_eq!(stats.sample_count("a"), 2);
+
+        let profiles = stats.into_profiles();
+        assert_eq!(
+            profiles["a"].latency_ms,
+            Distribution::empirical(vec![12.0, 18.0])
+        );
+        assert_eq!(profiles["a"].cost, Distribution::empirical(vec![0.2]));
+    }
+
+    #[test]
+    fn test_percentiles_nearest_rank() {
+        let p = Percentiles::from_samples((1..=100).map(f64::from).collect());
+        assert!((p.p50 - 50.0).abs() < f64::EPSILON);
+        assert!((p.p95 - 95.0).abs() < f64::EPSILON);
+        assert!((p.max - 100.0).abs() < f64::EPSILON);
+    }
+}
@@ -1,11 +1,15 @@
 //! Pipeline and stage specifications.
 
+use crate::context::ContextAccess;
+use crate::contracts::ContractRef;
 use crate::core::StageKind;
-use crate::errors::PipelineValidationError;
+use crate::errors::{OutputConflictError, PipelineValidationError};
 use crate::stages::Stage;
+use super::{IdempotencyKeyTemplate, ResourceLimits, StallPolicy};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Specification for a single stage in a pipeline.
 #[derive(Debug, Clone)]
@@ -20,6 +24,29 @@ pub struct StageSpec {
     pub conditional: bool,
     /// The kind of stage.
     pub kind: StageKind,
+    /// Contract of the output this stage produces.
+    pub produces: Option<ContractRef>,
+    /// Contracts this stage expects its upstream producers to satisfy.
+    pub consumes: Vec<ContractRef>,
+    /// Whether the stage completes only once acknowledged externally.
+    pub manual_ack: bool,
+    /// Whether running the stage twice has the same effect as running it once.
+    pub idempotent: bool,
+    /// Key under which the stage's successful output is cached, if any.
+    pub idempotency_key: Option<IdempotencyKeyTemplate>,
+    /// Whether the stage may write to the context.
+    pub context_access: ContextAccess,
+    /// Expected duration, used to analyse the graph before it runs.
+    pub estimated_duration: Option<Duration>,
+    /// Limits enforced on each execution.
+    pub resource_limits: ResourceLimits,
+    /// How long the stage may go without progress, if it is watched.
+    pub stall_policy: Option<StallPolicy>,
+    /// Output keys the stage may read, keyed by dependency. Reads from a
+    /// dependency listed here are limited to its keys.
+    pub input_keys: HashMap<String, HashSet<String>>,
+    /// Keys the stage may output. Empty allows any key.
+    pub output_keys: HashSet<String>,
 }
 
 impl StageSpec {
@@ -32,6 +59,17 @@ impl StageSpec {
             dependencies: HashSet::new(),
             conditional: false,
             kind: StageKind::Work,
+            produces: None,
+            consumes: Vec::new(),
+            manual_ack: false,
+            idempotent: false,
+            idempotency_key: None,
+            context_access: ContextAccess::ReadWrite,
+            estimated_duration: None,
+            resource_limits: ResourceLimits::default(),
+            stall_policy: None,
+            input_keys: HashMap::new(),
+            output_keys: HashSet::new(),
         }
     }
 
@@ -63,11 +101,128 @@ impl StageSpec {
         self
     }
 
+    /// Declares the contract this stage's output satisfies.
+    #[must_use]
+    pub fn with_produces(mut self, contract: ContractRef) -> Self {
+        self.produces = Some(contract);
+        self
+    }
+
+    /// Declares a contract this stage expects from a producer.
+    #[must_use]
+    pub fn with_consumes(mut self, contract: ContractRef) -> Self {
+        self.consumes.push(contract);
+        self
+    }
+
+    /// Requires an external acknowledgment before the stage counts as complete.
+    ///
+    /// See [`StageAckRegistry`](super::StageAckRegistry).
+    #[must_use]
+    pub fn with_manual_ack(mut self) -> Self {
+        self.manual_ack = true;
+        self
+    }
+
+    /// Declares that the stage is safe to re-run, e.g. because its side
+    /// effects are deduplicated by an idempotency key.
+    #[must_use]
+    pub fn with_idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// Deduplicates executions of the stage by a key rendered from
+    /// `template`, and marks it idempotent.
+    ///
+    /// In runs with an [idempotency store](crate::context::PipelineContext::with_idempotency_store),
+    /// a successful output is cached under the key, and later executions
+    /// rendering the same key return it without running the stage. An
+    /// execution whose inputs differ from the cached one fails instead.
+    #[must_use]
+    pub fn with_idempotency_key(mut self, template: IdempotencyKeyTemplate) -> Self {
+        self.idempotency_key = Some(template);
+        self.idempotent = true;
+        self
+    }
+
+    /// Runs the stage with a read-only context, so its context writes fail.
+    ///
+    /// See [`StageContext::is_read_only`](crate::context::StageContext::is_read_only).
+    #[must_use]
+    pub fn with_read_only(mut self) -> Self {
+        self.context_access = ContextAccess::ReadOnly;
+        self
+    }
+
+    /// Sets the expected duration of the stage.
+    ///
+    /// See [`StageGraph::analyze_estimates`](super::StageGraph::analyze_estimates).
+    #[must_use]
+    pub fn with_estimated_duration(mut self, duration: Duration) -> Self {
+        self.estimated_duration = Some(duration);
+        self
+    }
+
+    /// Sets the limits enforced on each execution.
+    ///
+    /// See [`ResourceLimits`].
+    #[must_use]
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+
+    /// Watches the stage for stalls while it runs.
+    ///
+    /// See [`StallPolicy`].
+    #[must_use]
+    pub fn with_stall_policy(mut self, policy: StallPolicy) -> Self {
+        self.stall_policy = Some(policy);
+        self
+    }
+
+    /// Declares that the stage reads `key` from the output of `stage`.
+    ///
+    /// Once a key is declared for a dependency, reading any other key of
+    /// that dependency fails with an
+    /// [`UndeclaredDependencyError`](crate::errors::UndeclaredDependencyError).
+    #[must_use]
+    pub fn with_input_key(mut self, stage: impl Into<String>, key: impl Into<String>) -> Self {
+        self.input_keys.entry(stage.into()).or_default().insert(key.into());
+        self
+    }
+
+    /// Declares a key the stage outputs.
+    ///
+    /// Once any key is declared, an output carrying an undeclared key fails
+    /// the stage.
+    #[must_use]
+    pub fn with_output_key(mut self, key: impl Into<String>) -> Self {
+        self.output_keys.insert(key.into());
+        self
+    }
+
+    /// Returns the conflict if `data` has keys the stage does not declare.
+    #[must_use]
+    pub fn check_output_keys(&self, data: &HashMap<String, serde_json::Value>) -> Option<OutputConflictError> {
+        if self.output_keys.is_empty() {
+            return None;
+        }
+        let mut undeclared: Vec<&String> = data.keys().filter(|key| !self.output_keys.contains(*key)).collect();
+        if undeclared.is_empty() {
+            return None;
+        }
+        undeclared.sort();
+        Some(OutputConflictError::undeclared_keys(&self.name, undeclared))
+    }
+
     /// Validates the stage specification.
     ///
     /// # Errors
     ///
-    /// Returns an error if the stage depends on itself.
+    /// Returns an error if the stage depends on itself or declares input
+    /// keys from a stage it does not depend on.
     pub fn validate(&self) -> Result<(), PipelineValidationError> {
         if self.dependencies.contains(&self.name) {
             return Err(PipelineValidationError::new(format!(
@@ -76,6 +231,15 @@ impl StageSpec {
             ))
             .with_stages(vec![self.name.clone()]));
         }
+        let mut sources: Vec<&String> = self.input_keys.keys().filter(|stage| !self.dependencies.contains(*stage)).collect();
+        sources.sort();
+        if let Some(source) = sources.first() {
+            return Err(PipelineValidationError::new(format!(
+                "Stage '{}' declares input keys from '{source}', which is not a dependency",
+                self.name
+            ))
+            .with_stages(vec![self.name.clone(), (*source).clone()]));
+        }
         Ok(())
     }
 }
@@ -156,6 +320,26 @@ mod tests {
         assert!(spec.validate().is_err());
     }
 
+    #[test]
+    fn test_stage_spec_declared_keys() {
+        let runner = Arc::new(NoOpStage::new("test"));
+        let spec = StageSpec::new("test", runner)
+            .with_dependency("fetch")
+            .with_input_key("fetch", "body")
+            .with_output_key("summary");
+        assert!(spec.validate().is_ok());
+
+        let data = std::collections::HashMap::from([
+            ("summary".to_string(), serde_json::json!("ok")),
+            ("debug".to_string(), serde_json::json!(true)),
+        ]);
+        let conflict = spec.check_output_keys(&data).unwrap();
+        assert_eq!(conflict.message, "undeclared output keys: debug");
+
+        let undeclared_source = spec.with_input_key("other", "x");
+        assert!(undeclared_source.validate().unwrap_err().message.contains("'other'"));
+    }
+
     #[test]
     fn test_pipeline_spec_creation() {
         let spec = PipelineSpec::new("my-pipeline").unwrap();
@@ -1,15 +1,30 @@
 //! Unified stage graph with enhanced execution features.
 
 use super::StageGraph;
-use crate::context::{ContextSnapshot, ExecutionContext, PipelineContext, StageContext, StageInputs};
+use crate::context::{
+    ContextAccess, ContextSnapshot, EnrichmentCacheStats, ExecutionContext, PipelineContext, StageInputs,
+};
 use crate::core::{StageKind, StageOutput, StageStatus};
 use crate::errors::StageflowError;
-use crate::pipeline::{GuardRetryRuntimeState, GuardRetryStrategy, hash_retry_payload};
+use crate::executor::{DependencyTracker, emit_stage_outcome, run_stage};
+use crate::tools::ToolTranscript;
+use crate::utils::{with_deterministic_source, Stopwatch};
+use super::control::ControlMode;
+use crate::pipeline::{
+    DeadLetter, DeadLetterStore, FailureCollector, FailureMode, FailureRecord, GuardRetryRuntimeState,
+    GuardRetryStrategy, GuardRetryVerdict, KindPolicies, KindPolicy,
+    PanicPolicy, PipelineController, PolicyHandle, RetryCheckpoint, RetryCheckpointStore, RetryConfig,
+    RetryState, RunStore, RunSummary, SessionManager, SharedCheckpoint, StageAckRegistry, StagePanic,
+    decide_guard_retry, until_deadline,
+};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::task::JoinSet;
 
+/// How often a paused run with nothing in flight checks for cancellation.
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Cancellation error for unified pipeline.
 #[derive(Debug)]
 pub struct UnifiedPipelineCancelled {
@@ -42,13 +57,26 @@ pub struct UnifiedExecutionResult {
     pub cancelled: bool,
     /// Cancellation reason if cancelled.
     pub cancel_reason: Option<String>,
+    /// Tool invocations made by stages during the run.
+    pub tool_transcript: ToolTranscript,
+    /// Enrichment cache hits and misses of the run, if a cache was attached.
+    pub enrichment_cache_stats: Option<EnrichmentCacheStats>,
 }
 
 /// Enhanced stage graph with conditional execution and cancellation.
 pub struct UnifiedStageGraph {
     /// The underlying stage graph.
     inner: StageGraph,
-    guard_retry_strategy: Option<GuardRetryStrategy>,
+    policies: PolicyHandle,
+    checkpoint_store: Option<Arc<dyn RetryCheckpointStore>>,
+    ack_registry: Arc<StageAckRegistry>,
+    ack_timeout: Duration,
+    kind_policies: KindPolicies,
+    run_store: Option<Arc<dyn RunStore>>,
+    session_manager: Option<Arc<SessionManager>>,
+    failure_mode: FailureMode,
+    panic_policy: PanicPolicy,
+    dead_letter_store: Option<Arc<dyn DeadLetterStore>>,
 }
 
 impl UnifiedStageGraph {
@@ -56,21 +84,146 @@ impl UnifiedStageGraph {
     #[must_use]
     pub fn new(graph: StageGraph) -> Self {
         Self {
+            policies: PolicyHandle::new(graph.stage_specs().clone()),
+            checkpoint_store: None,
+            ack_registry: Arc::clone(graph.ack_registry()),
+            ack_timeout: graph.ack_timeout(),
             inner: graph,
-            guard_retry_strategy: None,
+            kind_policies: KindPolicies::new(),
+            run_store: None,
+            session_manager: None,
+            failure_mode: FailureMode::default(),
+            panic_policy: PanicPolicy::default(),
+            dead_letter_store: None,
         }
     }
 
+    /// Sets how stage failures affect the rest of the run.
+    ///
+    /// Under [`FailureMode::ContinueOnFailure`] stages depending on a
+    /// failed stage are skipped and unrelated branches keep running; under
+    /// [`FailureMode::BestEffort`] every stage runs. Either way the run is
+    /// unsuccessful if any stage failed.
+    #[must_use]
+    pub fn with_failure_mode(mut self, mode: FailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
+    /// Sets what happens when a stage panics.
+    #[must_use]
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Sets the execution policies applied by stage kind.
+    ///
+    /// See [`KindPolicies::standard`] for the recommended table.
+    #[must_use]
+    pub fn with_kind_policies(mut self, policies: KindPolicies) -> Self {
+        self.kind_policies = policies;
+        self
+    }
+
+    /// Returns the execution policies applied by stage kind.
+    #[must_use]
+    pub fn kind_policies(&self) -> &KindPolicies {
+        &self.kind_policies
+    }
+
+    /// Records a [`RunSummary`] of every run in `store`.
+    ///
+    /// Failing to record is logged and does not affect the run's result.
+    #[must_use]
+    pub fn with_run_store(mut self, store: Arc<dyn RunStore>) -> Self {
+        self.run_store = Some(store);
+        self
+    }
+
+    /// Carries conversations across runs that share a session ID.
+    ///
+    /// Runs without a session ID in their [`RunIdentity`] are unaffected;
+    /// failed runs leave their session unchanged.
+    ///
+    /// [`RunIdentity`]: crate::context::RunIdentity
+    #[must_use]
+    pub fn with_session_manager(mut self, sessions: Arc<SessionManager>) -> Self {
+        self.session_manager = Some(sessions);
+        self
+    }
+
+    /// Sets a store used to persist guard-retry state across restarts.
+    ///
+    /// When set, a run resumed with the same pipeline run ID continues from
+    /// the recorded attempt and stagnation counts instead of resetting them.
+    #[must_use]
+    pub fn with_checkpoint_store(mut self, store: Arc<dyn RetryCheckpointStore>) -> Self {
+        self.checkpoint_store = Some(store);
+        self
+    }
+
+    /// Records a [`DeadLetter`] in `store` for every stage that gives up
+    /// retrying, either after its last attempt or because the run's
+    /// [`RetryBudget`](super::RetryBudget) refused another.
+    ///
+    /// Failing to store a letter is logged and does not affect the run.
+    /// Re-inject a letter with [`redrive`](Self::redrive).
+    #[must_use]
+    pub fn with_dead_letter_store(mut self, store: Arc<dyn DeadLetterStore>) -> Self {
+        self.dead_letter_store = Some(store);
+        self
+    }
+
+    /// Sets the registry that receives acknowledgments for manual-ack stages.
+    #[must_use]
+    pub fn with_ack_registry(mut self, registry: Arc<StageAckRegistry>) -> Self {
+        self.ack_registry = registry;
+        self
+    }
+
+    /// Sets how long to wait for a manual-ack stage to be acknowledged.
+    ///
+    /// A stage not acknowledged in time fails the run without being recorded
+    /// as complete, so resuming the run with a checkpoint store delivers it
+    /// again.
+    #[must_use]
+    pub fn with_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = timeout;
+        self
+    }
+
+    /// Returns the registry that receives acknowledgments for manual-ack stages.
+    #[must_use]
+    pub fn ack_registry(&self) -> &Arc<StageAckRegistry> {
+        &self.ack_registry
+    }
+
     /// Sets a guard-retry strategy.
+    ///
+    /// It can be replaced mid-run through [`policies`](Self::policies).
     #[must_use]
-    pub fn with_guard_retry_strategy(mut self, strategy: GuardRetryStrategy) -> Result<Self, StageflowError> {
-        strategy
-            .validate(self.inner.stage_specs())
-            .map_err(StageflowError::Internal)?;
-        self.guard_retry_strategy = Some(strategy);
+    pub fn with_guard_retry_strategy(self, strategy: GuardRetryStrategy) -> Result<Self, StageflowError> {
+        self.policies.set_guard_retry_strategy(Some(strategy))?;
+        Ok(self)
+    }
+
+    /// Retries a stage with `config` while its output status is listed in
+    /// [`RetryConfig::retry_on_status`].
+    ///
+    /// It can be replaced mid-run through [`policies`](Self::policies).
+    pub fn with_stage_retry(self, stage: &str, config: RetryConfig) -> Result<Self, StageflowError> {
+        self.policies.set_stage_retry(stage, Some(config))?;
         Ok(self)
     }
 
+    /// Returns the handle to the retry policies, which swaps them on
+    /// running and future executions alike.
+    #[must_use]
+    pub fn policies(&self) -> &PolicyHandle {
+        &self.policies
+    }
+
     /// Returns the pipeline name.
     #[must_use]
     pub fn name(&self) -> &str {
@@ -83,420 +236,796 @@ impl UnifiedStageGraph {
         self.inner.stage_count()
     }
 
+    /// Returns the underlying stage graph.
+    #[must_use]
+    pub fn graph(&self) -> &StageGraph {
+        &self.inner
+    }
+
     /// Executes the unified stage graph.
     ///
     /// Supports:
     /// - Conditional stage execution (skip if inputs contain skip_reason)
+    /// - Policies by stage kind, see [`with_kind_policies`](Self::with_kind_policies)
     /// - Cancellation on StageStatus::Cancel
+    /// - Deterministic, one-stage-at-a-time scheduling when the context
+    ///   carries a deterministic source
+    ///
+    /// Use [`execute_controlled`](Self::execute_controlled) to pause,
+    /// resume or single-step the run.
     pub async fn execute(
         &self,
         ctx: Arc<PipelineContext>,
         snapshot: ContextSnapshot,
     ) -> Result<UnifiedExecutionResult, StageflowError> {
-        let start = Instant::now();
-        let specs = self.inner.stage_specs().clone();
-
-        let completed: Arc<parking_lot::RwLock<HashMap<String, StageOutput>>> =
-            Arc::new(parking_lot::RwLock::new(HashMap::new()));
-        let mut guard_retry_state: HashMap<String, GuardRetryRuntimeState> = HashMap::new();
-        let mut pending_guard_retries: HashMap<String, Vec<String>> = HashMap::new();
-        let mut finalized: HashSet<String> = HashSet::new();
-        let mut active_retry_targets: HashSet<String> = HashSet::new();
+        self.execute_with(ctx, snapshot, None, HashMap::new()).await
+    }
 
-        let mut in_degree: HashMap<String, usize> = specs
-            .iter()
-            .map(|(name, spec)| (name.clone(), spec.dependencies.len()))
+    /// Re-runs a dead-lettered stage in a new run executing with `ctx`.
+    ///
+    /// The run starts from the letter's context snapshot. Stages whose
+    /// outputs the letter restores are not run again, so the stage sees the
+    /// inputs it originally failed on; stages downstream of it and stages
+    /// that had not finished run as usual. Removing the letter from its
+    /// store once the redrive succeeds is left to the caller.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the letter is for another pipeline or a stage
+    /// this pipeline does not have, or if the run itself fails.
+    pub async fn redrive(
+        &self,
+        letter: &DeadLetter,
+        ctx: Arc<PipelineContext>,
+    ) -> Result<UnifiedExecutionResult, StageflowError> {
+        if letter.pipeline != self.name() || !self.inner.stage_specs().contains_key(&letter.stage) {
+            return Err(StageflowError::Internal(format!(
+                "Dead letter '{}' for stage '{}' of pipeline '{}' cannot be redriven on pipeline '{}'",
+                letter.id,
+                letter.stage,
+                letter.pipeline,
+                self.name()
+            )));
+        }
+        let restored: HashMap<String, StageOutput> = letter
+            .restorable_outputs()
+            .into_iter()
+            .filter(|(name, _)| self.inner.stage_specs().contains_key(name))
             .collect();
+        ctx.try_emit_event(
+            "stage.redriven",
+            Some(serde_json::json!({
+                "stage": letter.stage,
+                "dead_letter_id": letter.id,
+                "original_run_id": letter.run_id,
+                "restored": restored.keys().collect::<Vec<_>>(),
+            })),
+        );
+        self.execute_with(ctx, letter.snapshot.clone(), None, restored).await
+    }
+
+    /// Executes the unified stage graph under a [`PipelineController`].
+    ///
+    /// Returns the controller and the run, which does nothing until
+    /// awaited; pausing before awaiting starts the run paused. A paused run
+    /// is still cancelled by the context and by the wall-clock budget.
+    pub fn execute_controlled(
+        &self,
+        ctx: Arc<PipelineContext>,
+        snapshot: ContextSnapshot,
+    ) -> (
+        PipelineController,
+        impl std::future::Future<Output = Result<UnifiedExecutionResult, StageflowError>> + '_,
+    ) {
+        let controller = PipelineController::new(ctx.clone(), self.name());
+        let run = self.execute_with(ctx, snapshot, Some(controller.clone()), HashMap::new());
+        (controller, run)
+    }
 
-        let mut tasks: JoinSet<Result<(String, StageOutput), StageflowError>> = JoinSet::new();
-
-        let schedule_stage = |tasks: &mut JoinSet<Result<(String, StageOutput), StageflowError>>,
-                              stage_name: String,
-                              ctx: Arc<PipelineContext>,
-                              snapshot: ContextSnapshot,
-                              completed: Arc<parking_lot::RwLock<HashMap<String, StageOutput>>>,
-                              specs: HashMap<String, super::StageSpec>| {
-            let spec = specs.get(&stage_name).cloned();
-            if spec.is_none() {
-                return;
+    async fn execute_with(
+        &self,
+        ctx: Arc<PipelineContext>,
+        snapshot: ContextSnapshot,
+        controller: Option<PipelineController>,
+        restored: HashMap<String, StageOutput>,
+    ) -> Result<UnifiedExecutionResult, StageflowError> {
+        let now = || ctx.deterministic_source().map_or_else(chrono::Utc::now, |source| source.now());
+        let started_at = now();
+        let session = self.session_manager.as_deref().zip(ctx.run_id().session_id);
+        let snapshot = match session {
+            Some((sessions, session_id)) => sessions.load_for_run(session_id, snapshot),
+            None => snapshot,
+        };
+        let session_snapshot = session.map(|_| snapshot.clone());
+        let result = match ctx.deterministic_source().cloned() {
+            Some(source) => {
+                let run = self.execute_inner(ctx.clone(), snapshot, controller.as_ref(), restored);
+                with_deterministic_source(source, run).await
             }
-            let spec = spec.unwrap();
-            tasks.spawn(async move {
-                let prior_outputs: HashMap<String, StageOutput> = {
-                    let lock = completed.read();
-                    spec.dependencies
-                        .iter()
-                        .filter_map(|dep| lock.get(dep).cloned().map(|o| (dep.clone(), o)))
-                        .collect()
-                };
+            None => self.execute_inner(ctx.clone(), snapshot, controller.as_ref(), restored).await,
+        };
 
-                let mut prior_data: HashMap<String, HashMap<String, serde_json::Value>> = HashMap::new();
-                for (name, output) in &prior_outputs {
-                    prior_data.insert(name.clone(), output.data.clone().unwrap_or_default());
-                }
+        if let (Some((sessions, session_id)), Some(snapshot), Ok(result)) = (session, &session_snapshot, &result) {
+            if result.success {
+                sessions.commit_after_run(session_id, snapshot, &result.outputs);
+            }
+        }
+        if let Some(store) = &self.run_store {
+            let run_id = ctx.pipeline_run_id().map_or_else(
+                || {
+                    let id = ctx.deterministic_source().map_or_else(uuid::Uuid::new_v4, |source| source.uuid_v4());
+                    id.to_string()
+                },
+                |id| id.to_string(),
+            );
+            let summary = match &result {
+                Ok(result) => RunSummary::from_result(&self.inner, run_id, started_at, result),
+                Err(e) => RunSummary::from_error(&self.inner, run_id, started_at, now(), e),
+            };
+            if let Err(e) = store.record(summary).await {
+                tracing::warn!(pipeline = self.name(), error = %e, "Failed to record pipeline run");
+            }
+        }
+        result
+    }
 
-                let skip_reason = if spec.conditional {
-                    find_skip_reason(&prior_data)
-                } else {
-                    None
-                };
+    async fn execute_inner(
+        &self,
+        ctx: Arc<PipelineContext>,
+        snapshot: ContextSnapshot,
+        controller: Option<&PipelineController>,
+        restored: HashMap<String, StageOutput>,
+    ) -> Result<UnifiedExecutionResult, StageflowError> {
+        let start = Stopwatch::start();
+        ctx.install_budget(*self.inner.budget());
+        let (checkpoint, resumed) = self.open_checkpoint(&ctx, &snapshot).await?;
+        let mut run = RunLoop {
+            graph: self,
+            guards: GuardRetries::resume(&ctx, resumed.as_ref()),
+            deterministic: ctx.is_deterministic(),
+            run_key: ctx.pipeline_run_id().map(|id| id.to_string()).unwrap_or_default(),
+            ctx,
+            controller,
+            start,
+            snapshot: Arc::new(snapshot),
+            completed: Arc::default(),
+            checkpoint,
+            resumed_retry: HashMap::new(),
+            tracker: DependencyTracker::new(&self.inner),
+            tasks: JoinSet::new(),
+            task_stages: HashMap::new(),
+            failures: FailureCollector::new(self.failure_mode),
+            blocked: HashSet::new(),
+        };
+        // Outputs carried over by a redrive stand in for their stages
+        for (stage_name, output) in restored {
+            run.tracker.restore_complete(&stage_name);
+            run.completed.write().insert(stage_name, output);
+        }
+        if let Some(cp) = resumed {
+            for (stage_name, output) in cp.acknowledged {
+                run.tracker.restore_complete(&stage_name);
+                run.ctx
+                    .try_emit_event("stage.ack_restored", Some(serde_json::json!({ "stage": stage_name })));
+                run.completed.write().insert(stage_name, output);
+            }
+            run.resumed_retry = cp.retry_state;
+        }
+        run.run().await
+    }
 
-                if let Some(reason) = skip_reason {
-                    ctx.try_emit_event(
-                        "stage.skipped",
-                        Some(serde_json::json!({
-                            "stage": stage_name,
-                            "reason": reason,
-                        })),
-                    );
-                    return Ok((stage_name, StageOutput::skip(reason)));
-                }
+    /// Opens the run's retry checkpoint, if the graph has a store and the
+    /// run an id, and returns it with what it held when opened.
+    async fn open_checkpoint(
+        &self,
+        ctx: &PipelineContext,
+        snapshot: &ContextSnapshot,
+    ) -> Result<(Option<SharedCheckpoint>, Option<RetryCheckpoint>), StageflowError> {
+        let (Some(store), Some(run_id)) = (&self.checkpoint_store, ctx.pipeline_run_id()) else {
+            return Ok((None, None));
+        };
+        let checkpoint = SharedCheckpoint::open(Arc::clone(store), &run_id.to_string()).await?;
+        let resumed = checkpoint.current().await;
+        // A resumed run should pick up from the snapshot it crashed with
+        let changed = checkpoint.stamp_snapshot(snapshot.fingerprint()).await;
+        if !changed.is_empty() {
+            ctx.try_emit_event(
+                "checkpoint.snapshot_drift",
+                Some(serde_json::json!({ "changed_sections": changed })),
+            );
+        }
+        Ok((Some(checkpoint), Some(resumed)))
+    }
 
-                let inputs = StageInputs::new(
-                    prior_data,
-                    spec.dependencies.clone(),
-                    stage_name.clone(),
-                    true,
-                );
+    /// Returns why `stage` must be skipped under
+    /// [`FailureMode::ContinueOnFailure`], if a dependency failed or was
+    /// itself skipped for a failure.
+    fn blocked_by_failure(
+        &self,
+        stage: &str,
+        specs: &HashMap<String, super::StageSpec>,
+        failures: &FailureCollector,
+        blocked: &HashSet<String>,
+    ) -> Option<String> {
+        if self.failure_mode != FailureMode::ContinueOnFailure {
+            return None;
+        }
+        let mut failed: Vec<&String> = specs
+            .get(stage)?
+            .dependencies
+            .iter()
+            .filter(|dep| failures.has_failed(dep) || blocked.contains(*dep))
+            .collect();
+        failed.sort();
+        failed.first().map(|dep| format!("Upstream stage '{dep}' failed"))
+    }
+}
 
-                let stage_ctx = StageContext::new(
-                    ctx.clone(),
-                    stage_name.clone(),
-                    inputs,
-                    snapshot,
-                );
+type CompletedOutputs = Arc<parking_lot::RwLock<HashMap<String, StageOutput>>>;
+type StageResult = Result<(String, StageOutput), StageflowError>;
+
+/// Guard retries of one run.
+#[derive(Default)]
+struct GuardRetries {
+    /// Retry state of each guard that has failed, restored on resume.
+    state: HashMap<String, GuardRetryRuntimeState>,
+    /// Guards waiting for their retry stage, keyed by that stage.
+    pending: HashMap<String, Vec<String>>,
+    /// Retry stages requeued and not yet finished.
+    active_targets: HashSet<String>,
+    /// When each guard's pending retry was scheduled, charged to the
+    /// retry budget once the guard runs again.
+    started: HashMap<String, Instant>,
+}
 
-                ctx.try_emit_event(
-                    "stage.started",
-                    Some(serde_json::json!({
-                        "stage": stage_name,
-                    })),
-                );
+impl GuardRetries {
+    /// Picks up the guard retry state a previous process checkpointed.
+    fn resume(ctx: &PipelineContext, resumed: Option<&RetryCheckpoint>) -> Self {
+        let state = resumed.map(RetryCheckpoint::restored_guard_state).unwrap_or_default();
+        if !state.is_empty() {
+            ctx.try_emit_event(
+                "guard_retry.resumed",
+                Some(serde_json::json!({
+                    "guards": state
+                        .iter()
+                        .map(|(name, state)| (name.clone(), serde_json::json!(state.attempts)))
+                        .collect::<serde_json::Map<_, _>>(),
+                })),
+            );
+        }
+        Self {
+            state,
+            ..Self::default()
+        }
+    }
 
-                let stage_start = Instant::now();
-                let output = spec.runner.execute(&stage_ctx).await;
-                let stage_duration_ms = stage_start.elapsed().as_secs_f64() * 1000.0;
-
-                match output.status {
-                    StageStatus::Ok => {
-                        ctx.try_emit_event(
-                            "stage.completed",
-                            Some(serde_json::json!({
-                                "stage": stage_name,
-                                "duration_ms": stage_duration_ms,
-                            })),
-                        );
-                    }
-                    StageStatus::Skip => {
-                        ctx.try_emit_event(
-                            "stage.skipped",
-                            Some(serde_json::json!({
-                                "stage": stage_name,
-                                "reason": output.skip_reason,
-                            })),
-                        );
-                    }
-                    StageStatus::Fail => {
-                        ctx.try_emit_event(
-                            "stage.failed",
-                            Some(serde_json::json!({
-                                "stage": stage_name,
-                                "error": output.error,
-                                "duration_ms": stage_duration_ms,
-                            })),
-                        );
-                    }
-                    StageStatus::Cancel => {
-                        ctx.try_emit_event(
-                            "stage.cancelled",
-                            Some(serde_json::json!({
-                                "stage": stage_name,
-                                "reason": output.cancel_reason,
-                            })),
-                        );
-                    }
-                    _ => {}
-                }
+    /// Charges the time since a guard's retry was scheduled to the budget.
+    fn charge_retry_time(&mut self, ctx: &PipelineContext, guard: &str) {
+        if let Some(started) = self.started.remove(guard) {
+            ctx.record_retry_time(started.elapsed());
+        }
+    }
 
-                Ok((stage_name, output))
-            });
+    /// Clears a guard's retry state once it passes, reporting the recovery.
+    async fn recover(
+        &mut self,
+        ctx: &PipelineContext,
+        guard: &str,
+        checkpoint: Option<&SharedCheckpoint>,
+    ) -> Result<(), StageflowError> {
+        let Some(state) = self.state.remove(guard) else {
+            return Ok(());
         };
-
-        let ready_stages: Vec<String> = in_degree
-            .iter()
-            .filter(|(_, &count)| count == 0)
-            .map(|(name, _)| name.clone())
-            .collect();
-
-        for stage_name in ready_stages {
-            schedule_stage(
-                &mut tasks,
-                stage_name,
-                ctx.clone(),
-                snapshot.clone(),
-                completed.clone(),
-                specs.clone(),
+        if let Some(cp) = checkpoint {
+            cp.update(|cp| cp.clear_guard_state(guard)).await?;
+        }
+        if state.attempts > 0 {
+            ctx.try_emit_event(
+                "guard_retry.recovered",
+                Some(serde_json::json!({
+                    "guard": guard,
+                    "attempts": state.attempts,
+                })),
             );
         }
+        Ok(())
+    }
 
-        while finalized.len() < specs.len() {
-            if (*ctx).is_cancelled() {
-                let reason = ctx.cancel_reason().unwrap_or_else(|| "Pipeline cancelled".to_string());
-                ctx.try_emit_event(
-                    "pipeline_cancelled",
-                    Some(serde_json::json!({
-                        "reason": &reason,
-                    })),
-                );
-                tasks.abort_all();
-                let outputs = completed.read().clone();
-                return Ok(UnifiedExecutionResult {
-                    outputs,
-                    duration_ms: start.elapsed().as_secs_f64() * 1000.0,
-                    success: false,
-                    error: None,
-                    cancelled: true,
-                    cancel_reason: Some(reason),
-                });
-            }
+    /// Returns the guards waiting on `stage`, which has now run.
+    fn release(&mut self, stage: &str) -> Vec<String> {
+        self.active_targets.remove(stage);
+        self.pending.remove(stage).unwrap_or_default()
+    }
+}
 
-            if tasks.len() == 0 {
-                let pending: Vec<_> = specs
-                    .keys()
-                    .filter(|name| !finalized.contains(*name))
-                    .cloned()
-                    .collect();
-                return Err(StageflowError::Internal(format!(
-                    "Deadlocked stage graph; remaining stages: {:?}",
-                    pending
-                )));
+/// One run of a [`UnifiedStageGraph`], from the first stage launched to
+/// the result.
+struct RunLoop<'a> {
+    graph: &'a UnifiedStageGraph,
+    ctx: Arc<PipelineContext>,
+    controller: Option<&'a PipelineController>,
+    deterministic: bool,
+    start: Stopwatch,
+    snapshot: Arc<ContextSnapshot>,
+    completed: CompletedOutputs,
+    checkpoint: Option<SharedCheckpoint>,
+    run_key: String,
+    /// Taken by each stage's first run only; later runs in this process start afresh
+    resumed_retry: HashMap<String, RetryState>,
+    tracker: DependencyTracker,
+    tasks: JoinSet<StageResult>,
+    task_stages: HashMap<tokio::task::Id, String>,
+    failures: FailureCollector,
+    /// Stages skipped because an upstream stage failed
+    blocked: HashSet<String>,
+    guards: GuardRetries,
+}
+
+impl RunLoop<'_> {
+    async fn run(mut self) -> Result<UnifiedExecutionResult, StageflowError> {
+        let mut control_changes = self.controller.map(PipelineController::subscribe);
+        while !self.tracker.is_complete() {
+            let paused = control_changes
+                .as_mut()
+                .is_some_and(|changes| *changes.borrow_and_update() != ControlMode::Running);
+            if self.launch_ready(paused) && self.tasks.is_empty() {
+                continue;
             }
 
-            let next = tasks.join_next().await;
-            let result = match next {
-                Some(res) => res,
-                None => continue,
-            };
+            // Running out of wall-clock time cancels the run
+            if self.ctx.check_wall_clock().is_err() || self.ctx.is_cancelled() {
+                let reason = self.ctx.cancel_reason().unwrap_or_else(|| "Pipeline cancelled".to_string());
+                self.ctx
+                    .try_emit_event("pipeline_cancelled", Some(serde_json::json!({ "reason": &reason })));
+                self.tasks.abort_all();
+                return Ok(self.cancelled(reason));
+            }
 
-            let (stage_name, stage_output) = match result {
-                Ok(Ok(v)) => v,
-                Ok(Err(e)) => {
-                    tasks.abort_all();
-                    return Err(e);
+            let next = if let Some(changes) = control_changes.as_mut().filter(|_| paused) {
+                // Wake when a stage finishes or the controller resumes or
+                // steps, and now and then to notice cancellation
+                tokio::select! {
+                    next = self.tasks.join_next_with_id(), if !self.tasks.is_empty() => next,
+                    _ = changes.changed() => continue,
+                    () = tokio::time::sleep(PAUSED_POLL_INTERVAL) => continue,
                 }
-                Err(e) => {
-                    tasks.abort_all();
-                    return Err(StageflowError::Internal(format!("Task join error: {e}")));
+            } else {
+                if self.tasks.is_empty() {
+                    return Err(StageflowError::Internal(format!(
+                        "Deadlocked stage graph; remaining stages: {:?}",
+                        self.tracker.remaining()
+                    )));
                 }
+                let Some(next) = until_deadline(&self.ctx, self.tasks.join_next_with_id()).await else {
+                    continue;
+                };
+                next
             };
-
-            {
-                completed.write().insert(stage_name.clone(), stage_output.clone());
-            }
-
-            let spec = match specs.get(&stage_name) {
-                Some(s) => s,
-                None => continue,
+            let Some(joined) = next else {
+                continue;
             };
-
-            let mut policy = None;
-            if self.guard_retry_strategy.is_some() && spec.kind == StageKind::Guard {
-                policy = self
-                    .guard_retry_strategy
-                    .as_ref()
-                    .and_then(|s| s.get_policy(&stage_name));
+            let (stage_name, stage_output) = self.joined_output(joined)?;
+            if let Some(result) = self.on_stage_finished(stage_name, stage_output).await? {
+                return Ok(result);
             }
+        }
 
-            if let (Some(policy), StageStatus::Fail) = (policy, stage_output.status) {
-                let state = guard_retry_state
-                    .entry(stage_name.clone())
-                    .or_insert_with(GuardRetryRuntimeState::new);
+        if let Some(cp) = &self.checkpoint {
+            cp.delete().await?;
+        }
+        let error = self.failures.error().map(|error| error.to_string());
+        Ok(self.result(self.failures.failures().is_empty(), error))
+    }
 
-                if state.started_at.is_none() {
-                    state.started_at = Some(Instant::now());
-                }
+    /// Starts the ready stages, skipping those an upstream failure blocks.
+    /// Returns true if any stage was skipped.
+    fn launch_ready(&mut self, paused: bool) -> bool {
+        let specs = self.graph.inner.stage_specs();
+        let mut skipped_any = false;
+        for stage_name in self.stages_to_launch(paused) {
+            let Some(reason) = self.graph.blocked_by_failure(&stage_name, specs, &self.failures, &self.blocked) else {
+                self.spawn_stage(stage_name);
+                continue;
+            };
+            self.ctx.try_emit_event(
+                "stage.skipped",
+                Some(serde_json::json!({
+                    "stage": stage_name,
+                    "reason": reason,
+                })),
+            );
+            self.completed.write().insert(stage_name.clone(), StageOutput::skip(reason));
+            self.tracker.mark_complete(&stage_name);
+            self.blocked.insert(stage_name);
+            skipped_any = true;
+        }
+        skipped_any
+    }
 
-                state.attempts += 1;
+    /// Returns the stages to start now. Deterministic runs keep a single
+    /// stage in flight; paused runs start only the stages they are stepped
+    /// through.
+    fn stages_to_launch(&mut self, paused: bool) -> Vec<String> {
+        if self.deterministic && !self.tasks.is_empty() {
+            Vec::new()
+        } else if let Some(control) = self.controller.filter(|_| paused) {
+            let stepped = control.take_step(|| self.tracker.take_next_ready());
+            if let Some(stage_name) = &stepped {
+                self.ctx
+                    .try_emit_event("pipeline.stepped", Some(serde_json::json!({ "stage": stage_name })));
+            }
+            stepped.into_iter().collect()
+        } else if self.deterministic {
+            self.tracker.take_next_ready().into_iter().collect()
+        } else {
+            self.tracker.take_ready()
+        }
+    }
 
-                let retry_hash = hash_retry_payload(
-                    Some(&stage_output),
-                    policy.hash_fields.as_deref(),
-                );
-                if retry_hash.is_some() && retry_hash == state.last_hash {
-                    state.stagnation_hits += 1;
-                } else {
-                    state.stagnation_hits = 0;
-                }
-                state.last_hash = retry_hash;
+    fn spawn_stage(&mut self, stage_name: String) {
+        let graph = self.graph;
+        let Some(mut spec) = graph.inner.stage_specs().get(&stage_name).cloned() else {
+            return;
+        };
+        let policy = graph.kind_policies.policy_for(spec.kind);
+        if policy.read_only_context {
+            spec.context_access = ContextAccess::ReadOnly;
+        }
+        let source = self.ctx.deterministic_source().cloned();
+        let task = StageTask {
+            resumed_retry: self.resumed_retry.remove(&stage_name),
+            stage_name: stage_name.clone(),
+            spec,
+            policy,
+            ctx: Arc::clone(&self.ctx),
+            snapshot: Arc::clone(&self.snapshot),
+            completed: Arc::clone(&self.completed),
+            policies: graph.policies.clone(),
+            checkpoint: self.checkpoint.clone(),
+            acks: Arc::clone(&graph.ack_registry),
+            ack_timeout: graph.ack_timeout,
+            run_key: self.run_key.clone(),
+            dead_letters: graph.dead_letter_store.clone(),
+            pipeline: graph.name().to_string(),
+        }
+        .run();
+        let handle = self.tasks.spawn(async move {
+            match source {
+                Some(source) => with_deterministic_source(source, task).await,
+                None => task.await,
+            }
+        });
+        self.task_stages.insert(handle.id(), stage_name);
+    }
 
-                ctx.try_emit_event(
-                    "guard_retry.attempt",
-                    Some(serde_json::json!({
-                        "guard": stage_name,
-                        "attempt": state.attempts,
-                        "retry_stage": policy.retry_stage,
-                        "max_attempts": policy.max_attempts,
-                        "stagnation_hits": state.stagnation_hits,
-                        "timeout_seconds": policy.timeout_seconds,
-                    })),
-                );
+    /// Unpacks a finished task into its stage's output, or the error that
+    /// ends the run.
+    fn joined_output(
+        &mut self,
+        joined: Result<(tokio::task::Id, StageResult), tokio::task::JoinError>,
+    ) -> Result<(String, StageOutput), StageflowError> {
+        let (stage_name, output) = match joined {
+            Ok((id, Ok(finished))) => {
+                self.task_stages.remove(&id);
+                finished
+            }
+            Ok((_, Err(e))) => {
+                self.tasks.abort_all();
+                return Err(e);
+            }
+            Err(e) => {
+                // Panics outside the stage itself, e.g. in a retry
+                // policy, still fail only the stage whose task it was
+                let Some(stage_name) = self.task_stages.remove(&e.id()).filter(|_| e.is_panic()) else {
+                    self.tasks.abort_all();
+                    return Err(StageflowError::Internal(format!("Task join error: {e}")));
+                };
+                let output = StagePanic::from_payload(e.into_panic().as_ref(), None).to_output(&stage_name);
+                emit_stage_outcome(self.ctx.as_ref(), &stage_name, &output, 0.0);
+                (stage_name, output)
+            }
+        };
+        if self.graph.panic_policy == PanicPolicy::Abort {
+            if let Some(panic) = StagePanic::from_output(&output) {
+                self.tasks.abort_all();
+                return Err(StageflowError::StageExecution(format!(
+                    "Stage '{stage_name}' panicked: {}",
+                    panic.message
+                )));
+            }
+        }
+        Ok((stage_name, output))
+    }
 
-                let exceeded_attempts = state.attempts >= policy.max_attempts;
-                let exceeded_stagnation = state.stagnation_hits >= policy.stagnation_limit;
-                let exceeded_timeout = policy
-                    .timeout_seconds
-                    .and_then(|timeout| state.started_at.map(|t| t.elapsed().as_secs_f64() >= timeout))
-                    .unwrap_or(false);
-
-                if exceeded_attempts || exceeded_stagnation || exceeded_timeout {
-                    ctx.try_emit_event(
-                        "guard_retry.exhausted",
-                        Some(serde_json::json!({
-                            "guard": stage_name,
-                            "attempts": state.attempts,
-                            "stagnation_hits": state.stagnation_hits,
-                            "retry_stage": policy.retry_stage,
-                            "timeout_seconds": policy.timeout_seconds,
-                            "reason": if exceeded_timeout { "timeout" } else if exceeded_stagnation { "stagnation" } else { "max_attempts" },
-                        })),
-                    );
-                } else {
-                    ctx.try_emit_event(
-                        "guard_retry.scheduled",
-                        Some(serde_json::json!({
-                            "guard": stage_name,
-                            "attempt": state.attempts,
-                            "retry_stage": policy.retry_stage,
-                            "stagnation_hits": state.stagnation_hits,
-                            "timeout_seconds": policy.timeout_seconds,
-                        })),
-                    );
-
-                    pending_guard_retries
-                        .entry(policy.retry_stage.clone())
-                        .or_default()
-                        .push(stage_name.clone());
-
-                    if !active_retry_targets.contains(&policy.retry_stage) {
-                        active_retry_targets.insert(policy.retry_stage.clone());
-                        schedule_stage(
-                            &mut tasks,
-                            policy.retry_stage.clone(),
-                            ctx.clone(),
-                            snapshot.clone(),
-                            completed.clone(),
-                            specs.clone(),
-                        );
-                    }
+    /// Records a finished stage and returns the run's result if it ends
+    /// the run.
+    async fn on_stage_finished(
+        &mut self,
+        stage_name: String,
+        output: StageOutput,
+    ) -> Result<Option<UnifiedExecutionResult>, StageflowError> {
+        self.completed.write().insert(stage_name.clone(), output.clone());
+        self.guards.charge_retry_time(&self.ctx, &stage_name);
+        let Some(spec) = self.graph.inner.stage_specs().get(&stage_name) else {
+            return Ok(None);
+        };
 
-                    continue;
-                }
+        if spec.kind == StageKind::Guard && output.status == StageStatus::Fail {
+            match self.retry_guard(&stage_name, &output).await? {
+                // The guard's failure stands
+                GuardRetryVerdict::Stand => {}
+                // The run is now cancelled; the next iteration reports it
+                GuardRetryVerdict::Cancelled | GuardRetryVerdict::Retry => return Ok(None),
             }
+        }
 
-            if stage_output.status == StageStatus::Cancel {
-                let reason = stage_output
-                    .cancel_reason
-                    .clone()
-                    .unwrap_or_else(|| "Pipeline cancelled".to_string());
-
-                (*ctx).mark_cancelled_with_reason(&reason);
-                ctx.try_emit_event(
+        match output.status {
+            StageStatus::Cancel => {
+                let reason = output.cancel_reason.unwrap_or_else(|| "Pipeline cancelled".to_string());
+                self.ctx.mark_cancelled_with_reason(&reason);
+                self.ctx.try_emit_event(
                     "pipeline_cancelled",
                     Some(serde_json::json!({
                         "stage": stage_name,
                         "reason": &reason,
                     })),
                 );
-                tasks.abort_all();
-                let outputs = completed.read().clone();
-                return Ok(UnifiedExecutionResult {
-                    outputs,
-                    duration_ms: start.elapsed().as_secs_f64() * 1000.0,
-                    success: false,
-                    error: None,
-                    cancelled: true,
-                    cancel_reason: Some(reason),
-                });
+                self.tasks.abort_all();
+                return Ok(Some(self.cancelled(reason)));
             }
-
-            if stage_output.status == StageStatus::Fail {
-                tasks.abort_all();
-                let outputs = completed.read().clone();
-                return Ok(UnifiedExecutionResult {
-                    outputs,
-                    duration_ms: start.elapsed().as_secs_f64() * 1000.0,
-                    success: false,
-                    error: Some(format!("Stage '{}' failed", stage_name)),
-                    cancelled: false,
-                    cancel_reason: None,
-                });
+            StageStatus::Fail => {
+                let error_type = if StagePanic::from_output(&output).is_some() {
+                    "StagePanic"
+                } else {
+                    "StageFailure"
+                };
+                self.failures.record_failure(
+                    FailureRecord::new(&stage_name, output.error.clone().unwrap_or_default())
+                        .with_error_type(error_type),
+                );
+                if self.failures.should_stop() {
+                    self.tasks.abort_all();
+                    return Ok(Some(self.result(false, Some(format!("Stage '{stage_name}' failed")))));
+                }
             }
-
-            if guard_retry_state.contains_key(&stage_name) && stage_output.status != StageStatus::Fail {
-                if let Some(state) = guard_retry_state.remove(&stage_name) {
-                    if state.attempts > 0 {
-                        ctx.try_emit_event(
-                            "guard_retry.recovered",
-                            Some(serde_json::json!({
-                                "guard": stage_name,
-                                "attempts": state.attempts,
-                            })),
-                        );
+            _ => {
+                self.guards.recover(&self.ctx, &stage_name, self.checkpoint.as_ref()).await?;
+                if spec.manual_ack {
+                    if let Some(cp) = &self.checkpoint {
+                        cp.update(|cp| cp.set_acknowledged(stage_name.clone(), &output)).await?;
                     }
                 }
             }
+        }
 
-            let pending_guards = pending_guard_retries.remove(&stage_name).unwrap_or_default();
-            if active_retry_targets.contains(&stage_name) {
-                active_retry_targets.remove(&stage_name);
-            }
-            for guard_name in pending_guards {
-                schedule_stage(
-                    &mut tasks,
-                    guard_name,
-                    ctx.clone(),
-                    snapshot.clone(),
-                    completed.clone(),
-                    specs.clone(),
-                );
-            }
+        for guard_name in self.guards.release(&stage_name) {
+            self.tracker.requeue(guard_name);
+        }
+        self.tracker.mark_complete(&stage_name);
+        Ok(None)
+    }
 
-            if !finalized.contains(&stage_name) {
-                finalized.insert(stage_name.clone());
-                for (child_name, child_spec) in &specs {
-                    if child_spec.dependencies.contains(&stage_name) {
-                        if let Some(count) = in_degree.get_mut(child_name) {
-                            *count = count.saturating_sub(1);
-                            if *count == 0 && !finalized.contains(child_name) {
-                                schedule_stage(
-                                    &mut tasks,
-                                    child_name.clone(),
-                                    ctx.clone(),
-                                    snapshot.clone(),
-                                    completed.clone(),
-                                    specs.clone(),
-                                );
-                            }
-                        }
-                    }
-                }
+    /// Records a guard failure against its retry policy and, if the guard
+    /// is retried, requeues the policy's retry stage.
+    async fn retry_guard(&mut self, guard: &str, output: &StageOutput) -> Result<GuardRetryVerdict, StageflowError> {
+        let strategy = self.graph.policies.guard_retry_strategy();
+        let Some(policy) = strategy.as_deref().and_then(|s| s.get_policy(guard)) else {
+            return Ok(GuardRetryVerdict::Stand);
+        };
+        let state = self.guards.state.entry(guard.to_string()).or_default();
+        let verdict = decide_guard_retry(&self.ctx, guard, output, policy, state, self.checkpoint.as_ref()).await?;
+        if matches!(verdict, GuardRetryVerdict::Retry) {
+            self.guards.started.insert(guard.to_string(), Instant::now());
+            self.guards
+                .pending
+                .entry(policy.retry_stage.clone())
+                .or_default()
+                .push(guard.to_string());
+            if self.guards.active_targets.insert(policy.retry_stage.clone()) {
+                self.tracker.requeue(policy.retry_stage.clone());
             }
         }
+        Ok(verdict)
+    }
 
-        let outputs = completed.read().clone();
-        Ok(UnifiedExecutionResult {
-            outputs,
-            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
-            success: true,
-            error: None,
+    fn result(&self, success: bool, error: Option<String>) -> UnifiedExecutionResult {
+        UnifiedExecutionResult {
+            outputs: self.completed.read().clone(),
+            duration_ms: self.start.elapsed_ms(),
+            success,
+            error,
             cancelled: false,
             cancel_reason: None,
+            tool_transcript: self.ctx.tool_transcript(),
+            enrichment_cache_stats: self.ctx.enrichment_cache_stats(),
+        }
+    }
+
+    fn cancelled(&self, reason: String) -> UnifiedExecutionResult {
+        UnifiedExecutionResult {
+            cancelled: true,
+            cancel_reason: Some(reason),
+            ..self.result(false, None)
+        }
+    }
+}
+
+/// A single run of a stage, with its retries and acknowledgment, as spawned
+/// by [`UnifiedStageGraph::execute`].
+struct StageTask {
+    stage_name: String,
+    spec: super::StageSpec,
+    policy: KindPolicy,
+    ctx: Arc<PipelineContext>,
+    snapshot: Arc<ContextSnapshot>,
+    completed: Arc<parking_lot::RwLock<HashMap<String, StageOutput>>>,
+    policies: PolicyHandle,
+    checkpoint: Option<SharedCheckpoint>,
+    /// Retry state saved by a previous process, if the stage was retrying.
+    resumed_retry: Option<RetryState>,
+    acks: Arc<StageAckRegistry>,
+    ack_timeout: Duration,
+    run_key: String,
+    dead_letters: Option<Arc<dyn DeadLetterStore>>,
+    pipeline: String,
+}
+
+impl StageTask {
+    async fn run(self) -> Result<(String, StageOutput), StageflowError> {
+        let (prior_outputs, already_ran): (HashMap<String, StageOutput>, bool) = {
+            let lock = self.completed.read();
+            let prior = self
+                .spec
+                .dependencies
+                .iter()
+                .filter_map(|dep| lock.get(dep).cloned().map(|o| (dep.clone(), o)))
+                .collect();
+            (prior, lock.contains_key(&self.stage_name))
+        };
+        let stage_name = self.stage_name.clone();
+        let spec = &self.spec;
+        let ctx = &self.ctx;
+
+        if self.policy.require_idempotency && already_ran && !spec.idempotent {
+            ctx.try_emit_event(
+                "stage.policy_violation",
+                Some(serde_json::json!({
+                    "stage": stage_name,
+                    "kind": spec.kind,
+                    "policy": "require_idempotency",
+                })),
+            );
+            let error = format!(
+                "Stage '{stage_name}' is not idempotent and cannot run again under the {} kind policy",
+                spec.kind
+            );
+            return Ok((stage_name, StageOutput::fail(error)));
+        }
+
+        // Dependencies' data is shared with the inputs, not copied
+        let prior_data: HashMap<String, Arc<HashMap<String, serde_json::Value>>> = prior_outputs
+            .iter()
+            .map(|(name, output)| (name.clone(), output.shared_data_or_empty().into_arc()))
+            .collect();
+
+        if let Some(reason) = self.skip_reason(&prior_outputs, &prior_data) {
+            ctx.try_emit_event(
+                "stage.skipped",
+                Some(serde_json::json!({
+                    "stage": stage_name,
+                    "reason": reason,
+                })),
+            );
+            return Ok((stage_name, StageOutput::skip(reason)));
+        }
+
+        let inputs = StageInputs::from_shared(
+            prior_data,
+            spec.dependencies.clone(),
+            stage_name.clone(),
+            true,
+        )
+        .with_declared_keys(spec.input_keys.clone());
+        let mut output = run_stage(spec, ctx.clone(), inputs.clone(), self.snapshot.clone()).await;
+        // A resumed run keeps counting against the attempts made before the restart
+        let mut retry_state: RetryState = self.resumed_retry.clone().unwrap_or_default();
+        if retry_state.attempt > 0 {
+            ctx.try_emit_event(
+                "stage.retry_resumed",
+                Some(serde_json::json!({
+                    "stage": stage_name,
+                    "attempt": retry_state.attempt,
+                })),
+            );
+        }
+        let gave_up = self
+            .policies
+            .retry_stage(
+                &stage_name,
+                ctx,
+                self.checkpoint.as_ref(),
+                &mut retry_state,
+                &mut output,
+                || run_stage(spec, ctx.clone(), inputs.clone(), self.snapshot.clone()),
+            )
+            .await?;
+        if let Some(cp) = self.checkpoint.as_ref().filter(|_| retry_state.attempt > 0 && output.is_success()) {
+            cp.update(|cp| cp.clear_retry_state(&stage_name)).await?;
+        }
+
+        if gave_up {
+            self.dead_letter(retry_state.attempt, &output).await;
+        }
+
+        if spec.manual_ack && output.is_success() {
+            self.acks
+                .await_stage(ctx.as_ref(), &self.run_key, &stage_name, self.ack_timeout)
+                .await?;
+        }
+
+        Ok((stage_name, output))
+    }
+
+    /// Returns why the stage should be skipped given its dependencies'
+    /// outputs, if it should.
+    fn skip_reason(
+        &self,
+        prior_outputs: &HashMap<String, StageOutput>,
+        prior_data: &HashMap<String, Arc<HashMap<String, serde_json::Value>>>,
+    ) -> Option<String> {
+        let upstream_skip = if self.policy.propagate_skip {
+            let mut skipped: Vec<&String> = prior_outputs
+                .iter()
+                .filter(|(_, output)| output.status == StageStatus::Skip)
+                .map(|(name, _)| name)
+                .collect();
+            skipped.sort();
+            skipped
+                .first()
+                .map(|name| format!("Upstream stage '{name}' was skipped"))
+        } else {
+            None
+        };
+        upstream_skip.or_else(|| {
+            if self.spec.conditional {
+                find_skip_reason(prior_data)
+            } else {
+                None
+            }
         })
     }
+
+    /// Stores a dead letter for a stage that gave up retrying, if the graph
+    /// has a dead-letter store.
+    async fn dead_letter(&self, attempts: usize, output: &StageOutput) {
+        let Some(store) = &self.dead_letters else {
+            return;
+        };
+        let mut letter = DeadLetter::new(self.pipeline.clone(), self.stage_name.clone(), attempts, output.clone());
+        letter.run_id = self.ctx.pipeline_run_id().map(|id| id.to_string());
+        letter.dependencies = self.spec.dependencies.iter().cloned().collect();
+        letter.dependencies.sort();
+        letter.outputs.clone_from(&self.completed.read());
+        letter.snapshot.clone_from(&self.snapshot);
+        match store.put(&letter).await {
+            Ok(()) => self.ctx.try_emit_event(
+                "stage.dead_lettered",
+                Some(serde_json::json!({
+                    "stage": self.stage_name,
+                    "dead_letter_id": letter.id,
+                    "attempts": letter.attempts,
+                    "error": letter.error(),
+                })),
+            ),
+            Err(e) => {
+                tracing::warn!(stage = %self.stage_name, error = %e, "Failed to store dead letter");
+            }
+        }
+    }
 }
 
 fn find_skip_reason(
-    outputs: &HashMap<String, HashMap<String, serde_json::Value>>,
+    outputs: &HashMap<String, Arc<HashMap<String, serde_json::Value>>>,
 ) -> Option<String> {
     for output in outputs.values() {
         if let Some(value) = output.get("skip_reason") {
@@ -551,11 +1080,66 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_unified_conditional_skip() {
+    async fn test_consumers_share_producer_data() {
         let producer = Arc::new(FnStage::new("producer", |_ctx| {
-            StageOutput::ok(
-                [("skip_reason".to_string(), serde_json::json!("skip"))]
-                    .into_iter()
+            StageOutput::ok_value("blob", serde_json::json!("x".repeat(1 << 20)))
+        }));
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut builder = PipelineBuilder::new("test");
+        builder.add_stage_spec(super::super::StageSpec::new("producer", producer)).unwrap();
+        for name in ["left", "right"] {
+            let seen = seen.clone();
+            let consumer = Arc::new(FnStage::new(name, move |ctx| {
+                let data = ctx.inputs().get("producer").unwrap().unwrap();
+                seen.lock().push(std::ptr::from_ref(data) as usize);
+                StageOutput::ok_empty()
+            }));
+            builder
+                .add_stage_spec(super::super::StageSpec::new(name, consumer).with_dependency("producer"))
+                .unwrap();
+        }
+        let unified = UnifiedStageGraph::new(builder.build().unwrap());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+
+        assert!(result.success);
+        let blob = result.outputs["producer"].data.as_ref().unwrap();
+        let expected = std::ptr::from_ref::<HashMap<String, serde_json::Value>>(blob) as usize;
+        assert_eq!(*seen.lock(), vec![expected, expected]);
+    }
+
+    #[tokio::test]
+    async fn test_stages_share_one_snapshot() {
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut builder = PipelineBuilder::new("test");
+        for name in ["a", "b", "c"] {
+            let seen = seen.clone();
+            let stage = Arc::new(FnStage::new(name, move |ctx| {
+                seen.lock().push(Arc::as_ptr(ctx.shared_snapshot()) as usize);
+                StageOutput::ok_empty()
+            }));
+            builder.add_stage_spec(super::super::StageSpec::new(name, stage)).unwrap();
+        }
+        let unified = UnifiedStageGraph::new(builder.build().unwrap());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let result = unified
+            .execute(ctx, ContextSnapshot::new().with_input_text("shared"))
+            .await
+            .unwrap();
+        assert!(result.success);
+        let seen = seen.lock();
+        assert_eq!(seen.len(), 3);
+        assert!(seen.iter().all(|ptr| *ptr == seen[0]));
+    }
+
+    #[tokio::test]
+    async fn test_unified_conditional_skip() {
+        let producer = Arc::new(FnStage::new("producer", |_ctx| {
+            StageOutput::ok(
+                [("skip_reason".to_string(), serde_json::json!("skip"))]
+                    .into_iter()
                     .collect(),
             )
         }));
@@ -584,6 +1168,121 @@ mod tests {
         assert_eq!(result.outputs["consumer"].status, StageStatus::Skip);
     }
 
+    #[tokio::test]
+    async fn test_unified_execution_releases_contexts() {
+        let graph = PipelineBuilder::new("test")
+            .stage("stage1", noop("stage1"), &[])
+            .unwrap()
+            .stage("stage2", noop("stage2"), &["stage1"])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let detector = crate::context::LeakDetector::new().panic_on_drop();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_leak_detector(&detector));
+        let result = UnifiedStageGraph::new(graph)
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        detector.assert_no_leaks();
+    }
+
+    #[tokio::test]
+    async fn test_unified_result_includes_tool_transcript() {
+        use crate::tools::{ToolCallRecord, ToolCallStatus};
+
+        let agent = Arc::new(FnStage::new("agent", |ctx| {
+            ctx.record_tool_call(ToolCallRecord {
+                action_id: uuid::Uuid::new_v4(),
+                tool_name: "search".to_string(),
+                args_hash: "abc".to_string(),
+                stage: None,
+                status: ToolCallStatus::Completed,
+                approval: None,
+                duration_ms: 1.0,
+                error: None,
+            });
+            StageOutput::ok_empty()
+        }));
+        let audit = Arc::new(FnStage::new("audit", |ctx| {
+            StageOutput::ok(
+                [("seen".to_string(), serde_json::json!(ctx.tool_transcript().len()))]
+                    .into_iter()
+                    .collect(),
+            )
+        }));
+
+        let graph = PipelineBuilder::new("test")
+            .stage("agent", agent, &[])
+            .unwrap()
+            .stage("audit", audit, &["agent"])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = UnifiedStageGraph::new(graph)
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        assert_eq!(result.outputs["audit"].data.as_ref().unwrap()["seen"], 1);
+        assert_eq!(result.tool_transcript.len(), 1);
+        assert_eq!(result.tool_transcript.calls[0].stage.as_deref(), Some("agent"));
+    }
+
+    #[tokio::test]
+    async fn test_unified_result_includes_enrichment_cache_stats() {
+        #[derive(Debug)]
+        struct Enrich {
+            name: String,
+        }
+
+        #[async_trait::async_trait]
+        impl crate::stages::Stage for Enrich {
+            fn name(&self) -> &str {
+                &self.name
+            }
+
+            async fn execute(&self, ctx: &crate::context::StageContext) -> StageOutput {
+                let value = ctx
+                    .cached_enrichment("profile", &serde_json::json!({"id": 1}), || async {
+                        Ok::<_, std::convert::Infallible>(serde_json::json!("cached"))
+                    })
+                    .await
+                    .unwrap();
+                StageOutput::ok_value("profile", value)
+            }
+        }
+
+        let unified = UnifiedStageGraph::new(
+            PipelineBuilder::new("test")
+                .stage("enrich", Arc::new(Enrich { name: "enrich".to_string() }), &[])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        let cache = Arc::new(crate::context::EnrichmentCache::new());
+        let user = uuid::Uuid::new_v4();
+
+        let mut stats = Vec::new();
+        for _ in 0..2 {
+            let ctx = Arc::new(
+                PipelineContext::new(RunIdentity::new().with_user_id(user)).with_enrichment_cache(cache.clone()),
+            );
+            let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+            stats.push(result.enrichment_cache_stats.unwrap());
+        }
+        assert_eq!(stats[0], EnrichmentCacheStats { hits: 0, misses: 1 });
+        assert_eq!(stats[1], EnrichmentCacheStats { hits: 1, misses: 0 });
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert!(result.enrichment_cache_stats.is_none());
+    }
+
     #[tokio::test]
     async fn test_unified_guard_retry_schedules_retry_stage() {
         let retry = Arc::new(FnStage::new("retry", |_ctx| {
@@ -624,4 +1323,699 @@ mod tests {
         assert!(result.outputs.contains_key("retry"));
         assert!(result.outputs.contains_key("guard"));
     }
+
+    #[tokio::test]
+    async fn test_retry_policies_swapped_mid_run() {
+        use crate::pipeline::{GuardRetryPolicy, JitterStrategy, StageSpec};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::OnceLock;
+
+        let policy = |max_attempts| {
+            GuardRetryStrategy::new().with_policy(
+                "guard",
+                GuardRetryPolicy::new("draft").with_max_attempts(max_attempts).with_stagnation_limit(5),
+            )
+        };
+        let handle: Arc<OnceLock<PolicyHandle>> = Arc::new(OnceLock::new());
+        let guard_runs = Arc::new(AtomicUsize::new(0));
+        let guard = {
+            let handle = Arc::clone(&handle);
+            let guard_runs = Arc::clone(&guard_runs);
+            Arc::new(FnStage::new("guard", move |_ctx| {
+                let run = guard_runs.fetch_add(1, Ordering::SeqCst);
+                if run == 0 {
+                    // An operator raises the limit while the first attempt fails
+                    handle.get().unwrap().set_guard_retry_strategy(Some(policy(5))).unwrap();
+                }
+                if run < 2 { StageOutput::fail("not yet") } else { StageOutput::ok_empty() }
+            }))
+        };
+        let flaky_runs = Arc::new(AtomicUsize::new(0));
+        let flaky = {
+            let flaky_runs = Arc::clone(&flaky_runs);
+            Arc::new(FnStage::new("flaky", move |_ctx| {
+                if flaky_runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    StageOutput::retry("rate limited")
+                } else {
+                    StageOutput::ok_empty()
+                }
+            }))
+        };
+
+        let mut builder = PipelineBuilder::new("live");
+        builder.add_stage_spec(StageSpec::new("draft", noop("draft"))).unwrap();
+        builder
+            .add_stage_spec(StageSpec::new("guard", guard).with_dependency("draft").with_kind(StageKind::Guard))
+            .unwrap();
+        builder.add_stage_spec(StageSpec::new("flaky", flaky).with_dependency("guard")).unwrap();
+        let unified = UnifiedStageGraph::new(builder.build().unwrap())
+            .with_guard_retry_strategy(policy(1))
+            .unwrap()
+            .with_stage_retry(
+                "flaky",
+                RetryConfig::new().with_base_delay_ms(0).with_jitter(JitterStrategy::None),
+            )
+            .unwrap();
+        handle.set(unified.policies().clone()).unwrap();
+
+        let result = unified
+            .execute(Arc::new(PipelineContext::new(RunIdentity::new())), ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(guard_runs.load(Ordering::SeqCst), 3);
+        assert_eq!(flaky_runs.load(Ordering::SeqCst), 2);
+        assert_eq!(result.outputs["flaky"].status, StageStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_is_shared_across_stages() {
+        use crate::pipeline::{GuardRetryPolicy, JitterStrategy, RetryBudget, StageSpec};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counting = |name: &'static str, output: fn() -> StageOutput| -> Arc<dyn crate::stages::Stage> {
+            let runs = Arc::clone(&runs);
+            Arc::new(FnStage::new(name, move |_ctx| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                output()
+            }))
+        };
+        let config = RetryConfig::new()
+            .with_max_attempts(10)
+            .with_base_delay_ms(0)
+            .with_jitter(JitterStrategy::None);
+
+        // Each stage's policy allows nine retries; the run affords three
+        let graph = PipelineBuilder::new("flaky")
+            .stage("a", counting("a", || StageOutput::retry("busy")), &[])
+            .unwrap()
+            .stage("b", counting("b", || StageOutput::retry("busy")), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let unified = UnifiedStageGraph::new(graph)
+            .with_stage_retry("a", config.clone())
+            .unwrap()
+            .with_stage_retry("b", config)
+            .unwrap();
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_retry_budget(RetryBudget::new().with_max_retries(3)),
+        );
+        unified.execute(ctx.clone(), ContextSnapshot::new()).await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 5);
+        assert_eq!(ctx.retry_budget_usage().unwrap().retries, 3);
+        assert_eq!(sink.events_of_type("retry.budget_exhausted").len(), 2);
+
+        // Guard retries draw on the same budget
+        runs.store(0, Ordering::SeqCst);
+        let mut builder = PipelineBuilder::new("guarded");
+        builder.add_stage_spec(StageSpec::new("draft", noop("draft"))).unwrap();
+        builder
+            .add_stage_spec(
+                StageSpec::new("guard", counting("guard", || StageOutput::fail("no")))
+                    .with_dependency("draft")
+                    .with_kind(StageKind::Guard),
+            )
+            .unwrap();
+        let unified = UnifiedStageGraph::new(builder.build().unwrap())
+            .with_guard_retry_strategy(
+                GuardRetryStrategy::new().with_policy(
+                    "guard",
+                    GuardRetryPolicy::new("draft").with_max_attempts(5).with_stagnation_limit(5),
+                ),
+            )
+            .unwrap();
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new()).with_retry_budget(RetryBudget::new().with_max_retries(1)),
+        );
+        let result = unified.execute(ctx.clone(), ContextSnapshot::new()).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let usage = ctx.retry_budget_usage().unwrap();
+        assert_eq!((usage.retries, usage.refused), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_stage_is_dead_lettered_and_redriven() {
+        use crate::pipeline::{DeadLetterStore, InMemoryDeadLetterStore, JitterStrategy};
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        let quote_runs = Arc::new(AtomicUsize::new(0));
+        let quote = {
+            let quote_runs = Arc::clone(&quote_runs);
+            Arc::new(FnStage::new("quote", move |_ctx| {
+                quote_runs.fetch_add(1, Ordering::SeqCst);
+                StageOutput::ok_value("total", serde_json::json!(42))
+            }))
+        };
+        let declining = Arc::new(AtomicBool::new(true));
+        let charge = {
+            let declining = Arc::clone(&declining);
+            Arc::new(FnStage::new("charge", move |ctx| {
+                if declining.load(Ordering::SeqCst) {
+                    return StageOutput::retry("card declined");
+                }
+                let total = ctx.inputs().get_value("quote", "total").ok().flatten().cloned();
+                StageOutput::ok_value("charged", total.unwrap_or_default())
+            }))
+        };
+        let graph = PipelineBuilder::new("orders")
+            .stage("quote", quote, &[])
+            .unwrap()
+            .stage("charge", charge, &["quote"])
+            .unwrap()
+            .build()
+            .unwrap();
+        let store = Arc::new(InMemoryDeadLetterStore::new());
+        let unified = UnifiedStageGraph::new(graph)
+            .with_stage_retry(
+                "charge",
+                RetryConfig::new()
+                    .with_max_attempts(3)
+                    .with_base_delay_ms(0)
+                    .with_jitter(JitterStrategy::None),
+            )
+            .unwrap()
+            .with_dead_letter_store(store.clone());
+
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let identity = RunIdentity::new();
+        let run_id = identity.pipeline_run_id.unwrap().to_string();
+        let ctx = Arc::new(PipelineContext::new(identity).with_event_sink(sink.clone()));
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert_eq!(result.outputs["charge"].status, StageStatus::Retry);
+
+        let letters = store.list().await.unwrap();
+        assert_eq!(letters.len(), 1);
+        let letter = &letters[0];
+        assert_eq!((letter.stage.as_str(), letter.attempts), ("charge", 3));
+        assert_eq!(letter.run_id.as_deref(), Some(run_id.as_str()));
+        assert_eq!(letter.error(), Some("card declined"));
+        assert_eq!(letter.inputs()["quote"].get("total"), Some(&serde_json::json!(42)));
+        let events = sink.events_of_type("stage.dead_lettered");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].1.as_ref().unwrap()["dead_letter_id"], letter.id.as_str());
+
+        // Redriving reuses the recorded quote instead of running it again
+        declining.store(false, Ordering::SeqCst);
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = unified.redrive(letter, ctx).await.unwrap();
+        assert!(result.success);
+        assert_eq!(quote_runs.load(Ordering::SeqCst), 1);
+        assert_eq!(result.outputs["charge"].get("charged"), Some(&serde_json::json!(42)));
+        assert_eq!(store.len(), 1);
+
+        let mut stray = letter.clone();
+        stray.pipeline = "refunds".to_string();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        assert!(unified.redrive(&stray, ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_standard_kind_policies() {
+        use crate::pipeline::{GuardRetryPolicy, KindPolicies, StageSpec};
+
+        let guard = Arc::new(FnStage::new("guard", |ctx| {
+            let denied = ctx.write_data("verdict", serde_json::json!("ok")).is_err();
+            StageOutput::fail(format!("write denied: {denied}"))
+        }));
+        let mut builder = PipelineBuilder::new("policies");
+        builder.add_stage_spec(StageSpec::new("draft", noop("draft"))).unwrap();
+        builder
+            .add_stage_spec(StageSpec::new("guard", guard).with_dependency("draft").with_kind(StageKind::Guard))
+            .unwrap();
+        let unified = UnifiedStageGraph::new(builder.build().unwrap())
+            .with_guard_retry_strategy(
+                GuardRetryStrategy::new().with_policy("guard", GuardRetryPolicy::new("draft").with_max_attempts(3)),
+            )
+            .unwrap()
+            .with_kind_policies(KindPolicies::standard());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = unified.execute(ctx.clone(), ContextSnapshot::new()).await.unwrap();
+
+        // The non-idempotent work stage refuses the guard's retry
+        assert!(!result.success);
+        assert!(result.outputs["draft"].error.as_deref().unwrap().contains("not idempotent"));
+        assert_eq!(result.outputs["guard"].error.as_deref(), Some("write denied: true"));
+        assert!(ctx.data.get("verdict").is_none());
+
+        let skipper = Arc::new(FnStage::new("source", |_ctx| StageOutput::skip("no input")));
+        let mut builder = PipelineBuilder::new("skips");
+        builder.add_stage_spec(StageSpec::new("source", skipper)).unwrap();
+        builder
+            .add_stage_spec(StageSpec::new("profile", noop("profile")).with_dependency("source").with_kind(StageKind::Enrich))
+            .unwrap();
+        builder
+            .add_stage_spec(StageSpec::new("reply", noop("reply")).with_dependency("source").with_kind(StageKind::Transform))
+            .unwrap();
+        let result = UnifiedStageGraph::new(builder.build().unwrap())
+            .with_kind_policies(KindPolicies::standard())
+            .execute(Arc::new(PipelineContext::new(RunIdentity::new())), ContextSnapshot::new())
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.outputs["profile"].status, StageStatus::Skip);
+        assert_eq!(result.outputs["reply"].status, StageStatus::Ok);
+
+        // Writes through the guard's bag land in a private copy and fail it
+        let sneaky = Arc::new(FnStage::new("sneaky", |ctx| {
+            ctx.data().set("verdict", serde_json::json!("ok")).unwrap();
+            StageOutput::ok_empty()
+        }));
+        let mut builder = PipelineBuilder::new("read_only");
+        builder
+            .add_stage_spec(StageSpec::new("sneaky", sneaky).with_kind(StageKind::Guard))
+            .unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = UnifiedStageGraph::new(builder.build().unwrap())
+            .with_kind_policies(KindPolicies::standard())
+            .execute(ctx.clone(), ContextSnapshot::new())
+            .await
+            .unwrap();
+        assert!(result.outputs["sneaky"].error.as_deref().unwrap().contains("read-only context"));
+        assert!(ctx.data.get("verdict").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_budget_cancels_runaway_retries_and_slow_runs() {
+        use crate::pipeline::{BudgetLimit, RunBudget};
+
+        let mut builder = PipelineBuilder::new("test")
+            .with_budget(RunBudget::new().with_max_stage_retries(2));
+        builder
+            .add_stage_spec(super::super::StageSpec::new("retry", noop("retry")))
+            .unwrap();
+        builder
+            .add_stage_spec(
+                super::super::StageSpec::new("guard", Arc::new(FnStage::new("guard", |_ctx| StageOutput::fail("no"))))
+                    .with_dependency("retry")
+                    .with_kind(StageKind::Guard),
+            )
+            .unwrap();
+        let strategy = GuardRetryStrategy::new().with_policy(
+            "guard",
+            crate::pipeline::GuardRetryPolicy::new("retry").with_max_attempts(100),
+        );
+        let unified = UnifiedStageGraph::new(builder.build().unwrap())
+            .with_guard_retry_strategy(strategy)
+            .unwrap();
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = unified.execute(ctx.clone(), ContextSnapshot::new()).await.unwrap();
+        assert!(result.cancelled);
+        assert_eq!(ctx.cancel_cause().unwrap().budget_limit(), Some(BudgetLimit::StageRetries));
+        assert_eq!(ctx.budget_usage().unwrap().stage_retries, 2);
+
+        // A budget on the context applies to graphs built without one
+        let slow = Arc::new(crate::testing::SlowStage::new("slow", Duration::from_secs(5)));
+        let graph = PipelineBuilder::new("slow").stage("slow", slow, &[]).unwrap().build().unwrap();
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_budget(RunBudget::new().with_max_wall_clock(Duration::from_millis(20))),
+        );
+        let started = Instant::now();
+        let result = UnifiedStageGraph::new(graph).execute(ctx.clone(), ContextSnapshot::new()).await.unwrap();
+        assert!(result.cancelled);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(ctx.cancel_cause().unwrap().budget_limit(), Some(BudgetLimit::WallClock));
+    }
+
+    #[tokio::test]
+    async fn test_unified_guard_retry_resumes_from_checkpoint() {
+        use crate::pipeline::InMemoryRetryCheckpointStore;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let retry_runs = Arc::new(AtomicUsize::new(0));
+        let retry_runs_clone = retry_runs.clone();
+        let retry = Arc::new(FnStage::new("retry", move |_ctx| {
+            retry_runs_clone.fetch_add(1, Ordering::SeqCst);
+            StageOutput::ok_empty()
+        }));
+        let guard = Arc::new(FnStage::new("guard", |_ctx| StageOutput::fail("no")));
+
+        let mut builder = PipelineBuilder::new("test");
+        builder
+            .add_stage_spec(super::super::StageSpec::new("retry", retry))
+            .unwrap();
+        builder
+            .add_stage_spec(
+                super::super::StageSpec::new("guard", guard)
+                    .with_dependency("retry")
+                    .with_kind(StageKind::Guard),
+            )
+            .unwrap();
+        let graph = builder.build().unwrap();
+
+        let strategy = GuardRetryStrategy::new().with_policy(
+            "guard",
+            crate::pipeline::GuardRetryPolicy::new("retry")
+                .with_max_attempts(3)
+                .with_stagnation_limit(10),
+        );
+
+        let identity = RunIdentity::new();
+        let run_key = identity.pipeline_run_id.unwrap().to_string();
+
+        // Simulate a previous process that already consumed two attempts.
+        let store = Arc::new(InMemoryRetryCheckpointStore::new());
+        let mut checkpoint = RetryCheckpoint::new(run_key.clone());
+        let mut state = GuardRetryRuntimeState::new();
+        state.attempts = 2;
+        checkpoint.set_guard_state("guard", &state);
+        store.save(&checkpoint).await.unwrap();
+
+        let unified = UnifiedStageGraph::new(graph)
+            .with_guard_retry_strategy(strategy)
+            .unwrap()
+            .with_checkpoint_store(store.clone());
+
+        let ctx = Arc::new(PipelineContext::new(identity));
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+
+        assert!(!result.success);
+        // Only the initial run of the retry stage; the budget was already spent.
+        assert_eq!(retry_runs.load(Ordering::SeqCst), 1);
+        let saved = store.load(&run_key).await.unwrap().unwrap();
+        assert_eq!(saved.guard_retry_state["guard"].attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_stage_retry_state_survives_a_crash() {
+        use crate::pipeline::{InMemoryRetryCheckpointStore, JitterStrategy};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // The third run hangs, standing in for the process dying mid-retry
+        #[derive(Debug)]
+        struct Flaky {
+            name: String,
+            runs: Arc<AtomicUsize>,
+        }
+
+        #[async_trait::async_trait]
+        impl crate::stages::Stage for Flaky {
+            fn name(&self) -> &str {
+                &self.name
+            }
+
+            async fn execute(&self, _ctx: &crate::context::StageContext) -> StageOutput {
+                if self.runs.fetch_add(1, Ordering::SeqCst) == 2 {
+                    std::future::pending::<()>().await;
+                }
+                StageOutput::retry("busy")
+            }
+        }
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let flaky = Arc::new(Flaky { name: "flaky".to_string(), runs: runs.clone() });
+        let graph = PipelineBuilder::new("test").stage("flaky", flaky, &[]).unwrap().build().unwrap();
+        let store = Arc::new(InMemoryRetryCheckpointStore::new());
+        let unified = UnifiedStageGraph::new(graph)
+            .with_stage_retry(
+                "flaky",
+                RetryConfig::new()
+                    .with_max_attempts(3)
+                    .with_base_delay_ms(0)
+                    .with_jitter(JitterStrategy::None),
+            )
+            .unwrap()
+            .with_checkpoint_store(store.clone());
+        let identity = RunIdentity::new();
+        let run_key = identity.pipeline_run_id.unwrap().to_string();
+
+        let ctx = Arc::new(PipelineContext::new(identity.clone()));
+        let crashed = tokio::time::timeout(
+            Duration::from_millis(100),
+            unified.execute(ctx, ContextSnapshot::new()),
+        )
+        .await;
+        assert!(crashed.is_err());
+        assert_eq!(store.load(&run_key).await.unwrap().unwrap().retry_state["flaky"].attempt, 2);
+
+        // The resumed run has one attempt left rather than a fresh budget
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(identity).with_event_sink(sink.clone()));
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert_eq!(result.outputs["flaky"].status, StageStatus::Retry);
+        assert_eq!(runs.load(Ordering::SeqCst), 5);
+        assert_eq!(sink.events_of_type("stage.retry_resumed").len(), 1);
+        assert!(sink.events_of_type("checkpoint.snapshot_drift").is_empty());
+        assert!(store.load(&run_key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_a_different_snapshot_reports_drift() {
+        use crate::pipeline::InMemoryRetryCheckpointStore;
+
+        let identity = RunIdentity::new();
+        let store = Arc::new(InMemoryRetryCheckpointStore::new());
+        let mut checkpoint = RetryCheckpoint::new(identity.pipeline_run_id.unwrap().to_string());
+        checkpoint.snapshot = Some(ContextSnapshot::new().with_input_text("first").fingerprint());
+        store.save(&checkpoint).await.unwrap();
+
+        let graph = PipelineBuilder::new("test").stage("a", noop("a"), &[]).unwrap().build().unwrap();
+        let unified = UnifiedStageGraph::new(graph).with_checkpoint_store(store);
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(identity).with_event_sink(sink.clone()));
+        let snapshot = ContextSnapshot::new().with_input_text("second");
+        assert!(unified.execute(ctx, snapshot).await.unwrap().success);
+
+        let drift = sink.events_of_type("checkpoint.snapshot_drift");
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].1.as_ref().unwrap()["changed_sections"], serde_json::json!(["input"]));
+    }
+
+    fn ack_pipeline(
+        sink_runs: Arc<std::sync::atomic::AtomicUsize>,
+        notify_runs: Arc<std::sync::atomic::AtomicUsize>,
+    ) -> StageGraph {
+        use std::sync::atomic::Ordering;
+
+        let sink = Arc::new(FnStage::new("outbox", move |_ctx| {
+            sink_runs.fetch_add(1, Ordering::SeqCst);
+            StageOutput::ok_value("written", serde_json::json!(true))
+        }));
+        let notify = Arc::new(FnStage::new("notify", move |_ctx| {
+            notify_runs.fetch_add(1, Ordering::SeqCst);
+            StageOutput::ok_empty()
+        }));
+        let mut builder = PipelineBuilder::new("test");
+        builder
+            .add_stage_spec(super::super::StageSpec::new("outbox", sink).with_manual_ack())
+            .unwrap();
+        builder
+            .add_stage_spec(super::super::StageSpec::new("notify", notify).with_dependency("outbox"))
+            .unwrap();
+        builder.build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_manual_ack_gates_dependents() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let sink_runs = Arc::new(AtomicUsize::new(0));
+        let notify_runs = Arc::new(AtomicUsize::new(0));
+        let unified = UnifiedStageGraph::new(ack_pipeline(sink_runs.clone(), notify_runs.clone()))
+            .with_ack_timeout(Duration::from_millis(20));
+
+        // No acknowledgment: the run fails before the dependent runs
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let err = unified.execute(ctx, ContextSnapshot::new()).await.unwrap_err();
+        assert!(err.to_string().contains("not acknowledged"));
+        assert_eq!(notify_runs.load(Ordering::SeqCst), 0);
+
+        let identity = RunIdentity::new();
+        let run_key = identity.pipeline_run_id.unwrap().to_string();
+        unified.ack_registry().ack(&run_key, "outbox");
+        let ctx = Arc::new(PipelineContext::new(identity));
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert!(result.success);
+        assert_eq!(notify_runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_manual_ack_redelivers_only_unacked_stages_on_resume() {
+        use crate::pipeline::InMemoryRetryCheckpointStore;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let sink_runs = Arc::new(AtomicUsize::new(0));
+        let notify_runs = Arc::new(AtomicUsize::new(0));
+        let store = Arc::new(InMemoryRetryCheckpointStore::new());
+        let unified = UnifiedStageGraph::new(ack_pipeline(sink_runs.clone(), notify_runs.clone()))
+            .with_checkpoint_store(store.clone())
+            .with_ack_timeout(Duration::from_millis(20));
+        let identity = RunIdentity::new();
+        let run_key = identity.pipeline_run_id.unwrap().to_string();
+
+        // First attempt times out; the stage is not recorded as complete
+        let ctx = Arc::new(PipelineContext::new(identity.clone()));
+        assert!(unified.execute(ctx, ContextSnapshot::new()).await.is_err());
+        assert!(store.load(&run_key).await.unwrap().is_none());
+
+        // Resume redelivers the stage, which is acknowledged this time
+        unified.ack_registry().ack(&run_key, "outbox");
+        let ctx = Arc::new(PipelineContext::new(identity));
+        assert!(unified.execute(ctx, ContextSnapshot::new()).await.unwrap().success);
+        assert_eq!(sink_runs.load(Ordering::SeqCst), 2);
+
+        // A checkpoint holding the acknowledged output skips the stage entirely
+        let identity = RunIdentity::new();
+        let run_key = identity.pipeline_run_id.unwrap().to_string();
+        let mut checkpoint = RetryCheckpoint::new(run_key);
+        checkpoint.set_acknowledged("outbox", &StageOutput::ok_value("written", serde_json::json!(true)));
+        store.save(&checkpoint).await.unwrap();
+        let ctx = Arc::new(PipelineContext::new(identity));
+        let result = unified.execute(ctx, ContextSnapshot::new()).await.unwrap();
+        assert!(result.success);
+        assert!(result.outputs.contains_key("outbox"));
+        assert_eq!(sink_runs.load(Ordering::SeqCst), 2);
+        assert_eq!(notify_runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_controlled_run_pauses_steps_and_resumes() {
+        let started = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let recording = |name: &'static str| -> Arc<dyn crate::stages::Stage> {
+            let started = Arc::clone(&started);
+            Arc::new(FnStage::new(name, move |_ctx| {
+                started.lock().push(name);
+                StageOutput::ok_empty()
+            }))
+        };
+        let graph = PipelineBuilder::new("debug")
+            .stage("a", recording("a"), &[])
+            .unwrap()
+            .stage("b", recording("b"), &["a"])
+            .unwrap()
+            .stage("c", recording("c"), &["b"])
+            .unwrap()
+            .build()
+            .unwrap();
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+        let unified = UnifiedStageGraph::new(graph);
+
+        let (controller, run) = unified.execute_controlled(ctx, ContextSnapshot::new());
+        assert!(controller.pause());
+        assert!(!controller.pause());
+        let drive = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(started.lock().is_empty());
+            controller.step();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert_eq!(*started.lock(), vec!["a"]);
+            assert!(controller.resume());
+        };
+        let (result, ()) = tokio::join!(run, drive);
+
+        assert!(result.unwrap().success);
+        assert_eq!(*started.lock(), vec!["a", "b", "c"]);
+        assert_eq!(sink.events_of_type("pipeline.paused").len(), 1);
+        assert_eq!(sink.events_of_type("pipeline.resumed").len(), 1);
+        let stepped = sink.events_of_type("pipeline.stepped");
+        assert_eq!(stepped.len(), 1);
+        assert_eq!(stepped[0].1.as_ref().unwrap()["stage"], "a");
+    }
+
+    #[tokio::test]
+    async fn test_paused_run_can_be_cancelled() {
+        let graph = PipelineBuilder::new("test")
+            .stage("stage1", noop("stage1"), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let unified = UnifiedStageGraph::new(graph);
+
+        let (controller, run) = unified.execute_controlled(ctx.clone(), ContextSnapshot::new());
+        controller.pause();
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            ctx.mark_cancelled_with_reason("operator abort");
+        };
+        let (result, ()) = tokio::join!(run, cancel);
+
+        let result = result.unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.cancel_reason.as_deref(), Some("operator abort"));
+        assert!(result.outputs.is_empty());
+    }
+
+    fn panic_pipeline() -> StageGraph {
+        PipelineBuilder::new("test")
+            .stage("boom", Arc::new(FnStage::new("boom", |_ctx| panic!("kaboom"))), &[])
+            .unwrap()
+            .stage("after", noop("after"), &["boom"])
+            .unwrap()
+            .stage("other", noop("other"), &[])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stage_panic_fails_the_run_without_aborting_it() {
+        let sink = Arc::new(crate::events::CollectingEventSink::new());
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone()));
+        let result = UnifiedStageGraph::new(panic_pipeline())
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        let output = &result.outputs["boom"];
+        assert_eq!(output.error.as_deref(), Some("Stage 'boom' panicked: kaboom"));
+        let panic = StagePanic::from_output(output).unwrap();
+        assert_eq!(panic.message, "kaboom");
+        assert!(panic.backtrace.is_some());
+        assert_eq!(sink.events_of_type("stage.failed").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_continue_on_failure_skips_dependents_of_panicked_stage() {
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = UnifiedStageGraph::new(panic_pipeline())
+            .with_failure_mode(FailureMode::ContinueOnFailure)
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("kaboom"));
+        assert_eq!(result.outputs["other"].status, StageStatus::Ok);
+        let after = &result.outputs["after"];
+        assert_eq!(after.status, StageStatus::Skip);
+        assert_eq!(after.skip_reason.as_deref(), Some("Upstream stage 'boom' failed"));
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_runs_dependents_of_panicked_stage() {
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let result = UnifiedStageGraph::new(panic_pipeline())
+            .with_failure_mode(FailureMode::BestEffort)
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.outputs["after"].status, StageStatus::Ok);
+        assert_eq!(result.outputs["other"].status, StageStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_abort_panic_policy_errors_the_run() {
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let err = UnifiedStageGraph::new(panic_pipeline())
+            .with_failure_mode(FailureMode::BestEffort)
+            .with_panic_policy(PanicPolicy::Abort)
+            .execute(ctx, ContextSnapshot::new())
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("Stage 'boom' panicked: kaboom"));
+    }
 }
@@ -0,0 +1,188 @@
+//! Heartbeats and stuck-stage detection.
+//!
+//! A stage reports progress by calling
+//! [`StageContext::heartbeat`](crate::context::StageContext::heartbeat);
+//! events it emits count as progress too. A [`StallPolicy`] on its
+//! [`StageSpec`](super::StageSpec) has the executor watch that progress and
+//! emit `stage.stalled` once the stage has gone quiet for too long, and
+//! optionally stop it.
+
+use crate::context::{ExecutionContext, StageContext};
+use crate::core::StageOutput;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// What the executor does once a stage has stalled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallAction {
+    /// Emit `stage.stalled` and let the stage keep running (default).
+    #[default]
+    Report,
+    /// Emit `stage.stalled`, then stop the stage and fail it.
+    Cancel,
+}
+
+/// How long a stage may go without progress before it counts as stalled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StallPolicy {
+    /// Longest expected gap between heartbeats.
+    pub stall_after: Duration,
+    /// What to do once the gap is exceeded.
+    pub action: StallAction,
+}
+
+impl StallPolicy {
+    /// Reports stages quiet for longer than `stall_after`.
+    #[must_use]
+    pub fn new(stall_after: Duration) -> Self {
+        Self {
+            stall_after,
+            action: StallAction::Report,
+        }
+    }
+
+    /// Stops stalled stages as well as reporting them.
+    #[must_use]
+    pub fn cancelling(mut self) -> Self {
+        self.action = StallAction::Cancel;
+        self
+    }
+
+    /// Returns how often the executor checks for progress.
+    #[must_use]
+    pub fn check_interval(&self) -> Duration {
+        (self.stall_after / 4).max(Duration::from_millis(1))
+    }
+
+    /// Returns the failed output of a stage stopped after `quiet` without
+    /// progress.
+    #[must_use]
+    pub fn to_output(&self, stage: &str, quiet: Duration) -> StageOutput {
+        StageOutput::fail(format!(
+            "Stage '{stage}' stalled: no progress for {} ms",
+            quiet.as_millis()
+        ))
+        .add_metadata("stalled_ms", serde_json::json!(duration_ms(quiet)))
+    }
+}
+
+/// Progress reported by a running stage.
+#[derive(Debug)]
+pub struct Heartbeat {
+    started: Instant,
+    /// Milliseconds from `started` to the latest beat.
+    last_ms: AtomicU64,
+    beats: AtomicU64,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heartbeat {
+    /// Creates a heartbeat whose last progress is now.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_ms: AtomicU64::new(0),
+            beats: AtomicU64::new(0),
+        }
+    }
+
+    /// Records progress.
+    pub fn beat(&self) {
+        self.last_ms.fetch_max(duration_ms(self.started.elapsed()), Ordering::Relaxed);
+        self.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of beats recorded.
+    #[must_use]
+    pub fn beats(&self) -> u64 {
+        self.beats.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time since the latest beat, or since creation.
+    #[must_use]
+    pub fn quiet_for(&self) -> Duration {
+        let last = Duration::from_millis(self.last_ms.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last)
+    }
+}
+
+/// Watches a running stage, emitting `stage.stalled` each time it goes
+/// quiet for longer than the policy allows.
+///
+/// Resolves with the quiet time once a stage under [`StallAction::Cancel`]
+/// stalls; under [`StallAction::Report`] it never resolves.
+pub(crate) async fn watch_for_stall(ctx: &StageContext, policy: StallPolicy) -> Duration {
+    let mut reported = false;
+    loop {
+        tokio::time::sleep(policy.check_interval()).await;
+        let quiet = ctx.quiet_for();
+        if quiet < policy.stall_after {
+            reported = false;
+            continue;
+        }
+        if reported {
+            continue;
+        }
+        reported = true;
+        warn!(
+            stage = %ctx.stage_name(),
+            quiet_ms = duration_ms(quiet),
+            "Stage made no progress within its stall window"
+        );
+        // Emitted on the pipeline so the event does not count as progress
+        ctx.pipeline_ctx().try_emit_event(
+            "stage.stalled",
+            Some(serde_json::json!({
+                "stage": ctx.stage_name(),
+                "quiet_ms": duration_ms(quiet),
+                "stall_after_ms": duration_ms(policy.stall_after),
+                "heartbeats": ctx.heartbeats(),
+                "action": policy.action,
+            })),
+        );
+        if policy.action == StallAction::Cancel {
+            return quiet;
+        }
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_resets_quiet_time() {
+        let heartbeat = Heartbeat::new();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(heartbeat.quiet_for() >= Duration::from_millis(20));
+
+        heartbeat.beat();
+        assert!(heartbeat.quiet_for() < Duration::from_millis(20));
+        assert_eq!(heartbeat.beats(), 1);
+    }
+
+    #[test]
+    fn test_policy_defaults_to_reporting() {
+        let policy = StallPolicy::new(Duration::from_millis(100));
+        assert_eq!(policy.action, StallAction::Report);
+        assert_eq!(policy.check_interval(), Duration::from_millis(25));
+        assert_eq!(policy.cancelling().action, StallAction::Cancel);
+
+        let output = policy.to_output("poll", Duration::from_millis(250));
+        assert_eq!(output.error.as_deref(), Some("Stage 'poll' stalled: no progress for 250 ms"));
+        assert_eq!(output.metadata["stalled_ms"], 250);
+    }
+}
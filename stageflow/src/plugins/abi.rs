@@ -0,0 +1,80 @@
+//! The guest ABI, version 1.
+//!
+//! A plugin module imports nothing and exports:
+//!
+//! | Export | Signature | |
+//! |---|---|---|
+//! | `memory` | memory | Linear memory shared with the host |
+//! | `stageflow_abi_version` | `() -> i32` | Must return [`ABI_VERSION`] |
+//! | `stageflow_alloc` | `(len: i32) -> i32` | Returns a pointer to `len` writable bytes |
+//! | `stageflow_run` | `(ptr: i32, len: i32) -> i64` | Runs the stage |
+//!
+//! For each execution the host instantiates the module, allocates room for
+//! the UTF-8 JSON encoding of a [`PluginInput`] with `stageflow_alloc`,
+//! writes it there and calls `stageflow_run` with its location. The guest
+//! returns the location of a UTF-8 JSON
+//! [`StageOutput`](crate::core::StageOutput) in its memory, packed as
+//! `(ptr << 32) | len`. The instance is discarded afterwards, so guests
+//! need not free anything.
+
+use crate::context::StageContext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The ABI version this host implements.
+pub const ABI_VERSION: i32 = 1;
+
+/// Name of the exported linear memory.
+pub const MEMORY_EXPORT: &str = "memory";
+
+/// Name of the exported version function.
+pub const VERSION_EXPORT: &str = "stageflow_abi_version";
+
+/// Name of the exported allocator.
+pub const ALLOC_EXPORT: &str = "stageflow_alloc";
+
+/// Name of the exported entry point.
+pub const RUN_EXPORT: &str = "stageflow_run";
+
+/// What a plugin sees of its [`StageContext`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginInput {
+    /// The stage name.
+    pub stage: String,
+    /// The pipeline run id.
+    pub pipeline_run_id: Option<String>,
+    /// The snapshot's input text.
+    pub input_text: Option<String>,
+    /// The snapshot's metadata.
+    pub metadata: HashMap<String, serde_json::Value>,
+    /// Outputs of the stages this stage may read, keyed by stage name.
+    pub inputs: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// The stage's configuration, as given to
+    /// [`WasmStage::with_config`](super::WasmStage::with_config).
+    pub config: serde_json::Value,
+}
+
+impl PluginInput {
+    /// Projects `ctx` for a plugin with `config`.
+    #[must_use]
+    pub fn from_context(ctx: &StageContext, config: serde_json::Value) -> Self {
+        let snapshot = ctx.snapshot();
+        let inputs = ctx
+            .inputs()
+            .stages()
+            .into_iter()
+            .filter_map(|stage| {
+                let output = ctx.inputs().get(stage).ok().flatten()?;
+                Some((stage.clone(), output.clone()))
+            })
+            .collect();
+        Self {
+            stage: ctx.stage_name().to_string(),
+            pipeline_run_id: snapshot.run_id.pipeline_run_id.map(|id| id.to_string()),
+            input_text: snapshot.input_text.clone(),
+            metadata: snapshot.metadata.clone(),
+            inputs,
+            config,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Stages loaded as WebAssembly plugins.
+//!
+//! A plugin is a core WebAssembly module implementing the guest ABI
+//! described in [`abi`]: it receives a JSON [`PluginInput`] and returns a
+//! JSON [`StageOutput`](crate::core::StageOutput). [`WasmPlugin`] compiles
+//! a module once and [`WasmStage`] runs it as a pipeline stage, each call
+//! in a fresh sandbox with no host imports and bounded fuel and memory.
+//!
+//! Enabled by the `wasm` feature.
+
+pub mod abi;
+mod wasm;
+
+pub use abi::PluginInput;
+pub use wasm::{PluginError, WasmLimits, WasmPlugin, WasmStage};
@@ -0,0 +1,319 @@
+//! Loading and running plugin modules with wasmtime.
+
+use super::abi::{PluginInput, ABI_VERSION, ALLOC_EXPORT, MEMORY_EXPORT, RUN_EXPORT, VERSION_EXPORT};
+use crate::context::StageContext;
+use crate::core::StageOutput;
+use crate::stages::Stage;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+/// Error loading or running a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PluginError {
+    /// The module could not be read or compiled.
+    #[error("Failed to load plugin: {0}")]
+    Load(String),
+    /// The module does not implement the guest ABI.
+    #[error("Plugin does not implement the stageflow ABI: {0}")]
+    Abi(String),
+    /// The guest used up its fuel.
+    #[error("Plugin ran out of fuel")]
+    OutOfFuel,
+    /// The guest trapped or exceeded its memory limit.
+    #[error("Plugin trapped: {0}")]
+    Trap(String),
+    /// The guest returned something other than a stage output.
+    #[error("Invalid plugin output: {0}")]
+    Output(String),
+}
+
+impl PluginError {
+    fn from_call(error: &wasmtime::Error) -> Self {
+        match error.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => Self::OutOfFuel,
+            _ => Self::Trap(format!("{error:#}")),
+        }
+    }
+}
+
+/// Resource limits for one plugin execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmLimits {
+    /// Fuel the guest may consume; roughly one unit per instruction.
+    pub fuel: u64,
+    /// Maximum size of the guest's linear memory.
+    pub max_memory_bytes: usize,
+    /// Maximum size of the JSON output.
+    pub max_output_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 1_000_000_000,
+            max_memory_bytes: 64 * 1024 * 1024,
+            max_output_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// A compiled plugin module.
+///
+/// Compiling validates the module against the guest ABI, so a loaded
+/// plugin is known to export the required functions and report a
+/// supported version.
+pub struct WasmPlugin {
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmPlugin {
+    /// Compiles a plugin from a WebAssembly binary or text module.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, PluginError> {
+        let engine = Self::engine()?;
+        let module = Module::new(&engine, bytes).map_err(|e| PluginError::Load(format!("{e:#}")))?;
+        Self::validated(engine, module)
+    }
+
+    /// Compiles a plugin from a `.wasm` or `.wat` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PluginError> {
+        let engine = Self::engine()?;
+        let module = Module::from_file(&engine, path.as_ref())
+            .map_err(|e| PluginError::Load(format!("{}: {e:#}", path.as_ref().display())))?;
+        Self::validated(engine, module)
+    }
+
+    fn engine() -> Result<Engine, PluginError> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).map_err(|e| PluginError::Load(format!("{e:#}")))
+    }
+
+    fn validated(engine: Engine, module: Module) -> Result<Self, PluginError> {
+        if let Some(import) = module.imports().next() {
+            return Err(PluginError::Abi(format!(
+                "imports '{}.{}', but plugins may not import anything",
+                import.module(),
+                import.name()
+            )));
+        }
+        let plugin = Self { engine, module };
+        let (mut store, instance) = plugin.instantiate(&WasmLimits::default())?;
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, VERSION_EXPORT)
+            .map_err(|e| PluginError::Abi(format!("{VERSION_EXPORT}: {e:#}")))?
+            .call(&mut store, ())
+            .map_err(|e| PluginError::from_call(&e))?;
+        if version != ABI_VERSION {
+            return Err(PluginError::Abi(format!(
+                "plugin implements ABI version {version}, host supports {ABI_VERSION}"
+            )));
+        }
+        instance
+            .get_typed_func::<i32, i32>(&mut store, ALLOC_EXPORT)
+            .map_err(|e| PluginError::Abi(format!("{ALLOC_EXPORT}: {e:#}")))?;
+        instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, RUN_EXPORT)
+            .map_err(|e| PluginError::Abi(format!("{RUN_EXPORT}: {e:#}")))?;
+        Self::memory(&mut store, &instance)?;
+        Ok(plugin)
+    }
+
+    fn instantiate(&self, limits: &WasmLimits) -> Result<(Store<StoreLimits>, Instance), PluginError> {
+        let store_limits = StoreLimitsBuilder::new()
+            .memory_size(limits.max_memory_bytes)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, store_limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(limits.fuel).map_err(|e| PluginError::Load(format!("{e:#}")))?;
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(|e| PluginError::from_call(&e))?;
+        Ok((store, instance))
+    }
+
+    fn memory(store: &mut Store<StoreLimits>, instance: &Instance) -> Result<Memory, PluginError> {
+        instance
+            .get_memory(store, MEMORY_EXPORT)
+            .ok_or_else(|| PluginError::Abi(format!("missing '{MEMORY_EXPORT}' export")))
+    }
+
+    /// Runs the plugin once in a fresh instance.
+    ///
+    /// This blocks until the guest returns or runs out of fuel; call it
+    /// from a blocking context.
+    pub fn invoke(&self, input: &PluginInput, limits: &WasmLimits) -> Result<StageOutput, PluginError> {
+        let input = serde_json::to_vec(input).map_err(|e| PluginError::Output(e.to_string()))?;
+        let input_len =
+            i32::try_from(input.len()).map_err(|_| PluginError::Trap("input exceeds guest address space".to_string()))?;
+        let (mut store, instance) = self.instantiate(limits)?;
+        let memory = Self::memory(&mut store, &instance)?;
+        let abi = |e: wasmtime::Error| PluginError::Abi(format!("{e:#}"));
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, ALLOC_EXPORT).map_err(abi)?;
+        let run = instance.get_typed_func::<(i32, i32), i64>(&mut store, RUN_EXPORT).map_err(abi)?;
+
+        let ptr = alloc.call(&mut store, input_len).map_err(|e| PluginError::from_call(&e))?;
+        memory
+            .write(&mut store, guest_offset(ptr), &input)
+            .map_err(|_| PluginError::Trap(format!("{ALLOC_EXPORT} returned an out-of-bounds pointer")))?;
+        let packed = run.call(&mut store, (ptr, input_len)).map_err(|e| PluginError::from_call(&e))?;
+
+        // Guest pointers and lengths are 32-bit; the halves are unsigned
+        let packed = u64::from_ne_bytes(packed.to_ne_bytes());
+        let out_ptr = usize::try_from(packed >> 32).unwrap_or(usize::MAX);
+        let out_len = usize::try_from(packed & 0xFFFF_FFFF).unwrap_or(usize::MAX);
+        if out_len > limits.max_output_bytes {
+            return Err(PluginError::Output(format!(
+                "{out_len} bytes exceeds the {} byte limit",
+                limits.max_output_bytes
+            )));
+        }
+        let output = memory
+            .data(&store)
+            .get(out_ptr..out_ptr.saturating_add(out_len))
+            .ok_or_else(|| PluginError::Output("output is outside guest memory".to_string()))?;
+        serde_json::from_slice(output).map_err(|e| PluginError::Output(e.to_string()))
+    }
+}
+
+fn guest_offset(ptr: i32) -> usize {
+    usize::try_from(u32::from_ne_bytes(ptr.to_ne_bytes())).unwrap_or(usize::MAX)
+}
+
+impl std::fmt::Debug for WasmPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("module", &self.module.name())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A stage implemented by a [`WasmPlugin`].
+///
+/// Every execution gets a new instance, so nothing leaks between runs or
+/// between stages sharing a plugin. Guest failures, including running out
+/// of fuel or memory, become failed outputs.
+#[derive(Debug, Clone)]
+pub struct WasmStage {
+    name: String,
+    plugin: Arc<WasmPlugin>,
+    config: serde_json::Value,
+    limits: WasmLimits,
+}
+
+impl WasmStage {
+    /// Creates a stage running `plugin` with no configuration.
+    #[must_use]
+    pub fn new(name: impl Into<String>, plugin: Arc<WasmPlugin>) -> Self {
+        Self {
+            name: name.into(),
+            plugin,
+            config: serde_json::Value::Null,
+            limits: WasmLimits::default(),
+        }
+    }
+
+    /// Sets the configuration passed to the plugin as
+    /// [`PluginInput::config`].
+    #[must_use]
+    pub fn with_config(mut self, config: serde_json::Value) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the resource limits for each execution.
+    #[must_use]
+    pub fn with_limits(mut self, limits: WasmLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+#[async_trait]
+impl Stage for WasmStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let input = PluginInput::from_context(ctx, self.config.clone());
+        let plugin = Arc::clone(&self.plugin);
+        let limits = self.limits;
+        match tokio::task::spawn_blocking(move || plugin.invoke(&input, &limits)).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => StageOutput::fail(format!("Plugin stage '{}' failed: {e}", self.name)),
+            Err(e) => StageOutput::fail(format!("Plugin stage '{}' panicked: {e}", self.name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageInputs};
+    use crate::core::StageStatus;
+
+    /// Bump allocator and a `stageflow_run` body over `$ptr` and `$len`.
+    fn plugin(run_body: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (data (i32.const 16) "{{\"status\":\"ok\",\"data\":{{\"from\":\"wasm\"}}}}")
+                (func (export "stageflow_abi_version") (result i32) i32.const 1)
+                (func (export "stageflow_alloc") (param $len i32) (result i32)
+                    global.get $next
+                    global.get $next local.get $len i32.add global.set $next)
+                (func (export "stageflow_run") (param $ptr i32) (param $len i32) (result i64)
+                    {run_body}))"#
+        )
+    }
+
+    fn ctx() -> StageContext {
+        let snapshot = ContextSnapshot::new().with_input_text("hello");
+        StageContext::new(
+            Arc::new(PipelineContext::new(RunIdentity::new())),
+            "plugin",
+            StageInputs::default(),
+            snapshot,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_guest_output_and_echo() {
+        // Returns the 38-byte document at offset 16
+        let fixed = WasmPlugin::from_bytes(plugin("i64.const 0x10_0000_0026")).unwrap();
+        let output = WasmStage::new("plugin", Arc::new(fixed)).execute(&ctx()).await;
+        assert_eq!(output.status, StageStatus::Ok);
+        assert_eq!(output.data.unwrap()["from"], "wasm");
+
+        // Returns its input, which is not a stage output, proving the host
+        // wrote the projected context where the guest asked
+        let echo = WasmPlugin::from_bytes(plugin(
+            "local.get $ptr i64.extend_i32_u i64.const 32 i64.shl local.get $len i64.extend_i32_u i64.or",
+        ))
+        .unwrap();
+        let input = PluginInput::from_context(&ctx(), serde_json::json!({ "k": 1 }));
+        let error = echo.invoke(&input, &WasmLimits::default()).unwrap_err();
+        assert!(matches!(error, PluginError::Output(ref message) if message.contains("status")), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_limits_and_abi_checks() {
+        let spin = WasmPlugin::from_bytes(plugin("(loop $forever br $forever) unreachable")).unwrap();
+        let limits = WasmLimits {
+            fuel: 10_000,
+            ..WasmLimits::default()
+        };
+        let output = WasmStage::new("spin", Arc::new(spin)).with_limits(limits).execute(&ctx()).await;
+        assert_eq!(output.status, StageStatus::Fail);
+        assert!(output.error.unwrap().contains("ran out of fuel"));
+
+        let importing = r#"(module (import "env" "f" (func)) (memory (export "memory") 1))"#;
+        assert!(matches!(WasmPlugin::from_bytes(importing), Err(PluginError::Abi(_))));
+        let wrong_version = plugin("i64.const 0").replace("i32.const 1)", "i32.const 2)");
+        assert!(matches!(WasmPlugin::from_bytes(wrong_version), Err(PluginError::Abi(_))));
+        assert!(matches!(WasmPlugin::from_bytes("not wasm"), Err(PluginError::Load(_))));
+    }
+}
@@ -0,0 +1,345 @@
+//! Deterministic re-execution of recorded pipeline runs.
+//!
+//! A [`Recording`] holds the stage outputs of a past run, taken from a
+//! [`RunRecord`], an exported [`RunBundle`] or the run's event log. A
+//! [`Replayer`] executes the pipeline again with every stage answering from
+//! the recording, except the stages chosen to run live. Live stages receive
+//! exactly the inputs the production run produced, so a fix can be tried
+//! against an incident without calling the services the other stages use.
+
+use crate::context::{ContextSnapshot, PipelineContext, StageContext};
+use crate::core::{StageOutput, StageStatus};
+use crate::errors::StageflowError;
+use crate::events::{RecordedEvent, RunBundle, RunRecord};
+use crate::pipeline::{GraphExecutionResult, StageGraph};
+use crate::stages::Stage;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Error raised when a run cannot be replayed.
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    /// A stage that is not live has no recorded output.
+    #[error("No recorded output for stage '{0}'")]
+    MissingOutput(String),
+
+    /// A stage chosen to run live is not part of the pipeline.
+    #[error("Live stage '{0}' is not in the pipeline")]
+    UnknownStage(String),
+
+    /// The replayed pipeline failed to execute.
+    #[error(transparent)]
+    Execution(#[from] StageflowError),
+}
+
+/// Stage outputs and context captured from a past run.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    /// The context the run executed with.
+    pub snapshot: Option<ContextSnapshot>,
+    /// Stage outputs keyed by stage name.
+    pub outputs: HashMap<String, StageOutput>,
+}
+
+impl Recording {
+    /// Creates an empty recording.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a recording from a stored run record.
+    #[must_use]
+    pub fn from_record(record: &RunRecord) -> Self {
+        Self {
+            snapshot: record.snapshot.clone(),
+            outputs: record.outputs.clone(),
+        }
+    }
+
+    /// Builds a recording from an exported bundle.
+    ///
+    /// Stage outputs come with their inlined artifacts; stages that only
+    /// appear in the bundle's events are recovered from those.
+    #[must_use]
+    pub fn from_bundle(bundle: &RunBundle) -> Self {
+        let mut outputs = Self::from_events(&bundle.events).outputs;
+        outputs.extend(bundle.outputs_with_artifacts());
+        Self {
+            snapshot: bundle.snapshot.clone(),
+            outputs,
+        }
+    }
+
+    /// Recovers stage outcomes from a run's `stage.*` events.
+    ///
+    /// Lifecycle events carry a stage's status, error and skip or cancel
+    /// reason but not its data, so successful stages are recorded with
+    /// empty data. The last outcome of a retried stage wins.
+    #[must_use]
+    pub fn from_events(events: &[RecordedEvent]) -> Self {
+        let mut ordered: Vec<&RecordedEvent> = events.iter().collect();
+        ordered.sort_by_key(|event| event.seq);
+
+        let mut outputs = HashMap::new();
+        for event in ordered {
+            let Some(data) = &event.data else { continue };
+            let Some(stage) = data.get("stage").and_then(serde_json::Value::as_str) else {
+                continue;
+            };
+            let text = |key: &str| data.get(key).and_then(serde_json::Value::as_str).unwrap_or_default().to_string();
+            let output = match event.event_type.as_str() {
+                "stage.completed" => StageOutput::ok_empty(),
+                "stage.skipped" => StageOutput::skip(text("reason")),
+                "stage.failed" => StageOutput::fail(text("error")),
+                "stage.cancelled" => StageOutput::cancel(text("reason")),
+                _ => continue,
+            };
+            outputs.insert(stage.to_string(), output);
+        }
+        Self { snapshot: None, outputs }
+    }
+
+    /// Sets the context to replay with.
+    #[must_use]
+    pub fn with_snapshot(mut self, snapshot: ContextSnapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Records a stage output.
+    #[must_use]
+    pub fn with_output(mut self, stage: impl Into<String>, output: StageOutput) -> Self {
+        self.outputs.insert(stage.into(), output);
+        self
+    }
+
+    /// Returns a stage's recorded output.
+    #[must_use]
+    pub fn output(&self, stage: &str) -> Option<&StageOutput> {
+        self.outputs.get(stage)
+    }
+}
+
+/// The outcome of a replay.
+#[derive(Debug)]
+pub struct ReplayResult {
+    /// The replayed run.
+    pub result: GraphExecutionResult,
+    /// Stages that ran live, sorted.
+    pub live_stages: Vec<String>,
+    /// Live stages whose status or data differ from the recording, sorted.
+    pub divergent_stages: Vec<String>,
+}
+
+/// Re-executes a pipeline against a [`Recording`].
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    recording: Recording,
+    live: BTreeSet<String>,
+}
+
+impl Replayer {
+    /// Creates a replayer answering every stage from `recording`.
+    #[must_use]
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            live: BTreeSet::new(),
+        }
+    }
+
+    /// Runs `stage` live instead of substituting its recorded output.
+    #[must_use]
+    pub fn with_live_stage(mut self, stage: impl Into<String>) -> Self {
+        self.live.insert(stage.into());
+        self
+    }
+
+    /// Returns the recording.
+    #[must_use]
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Replays `graph` in `ctx`, with the recording's snapshot or an empty
+    /// one.
+    ///
+    /// Substituted stages go through the normal stage lifecycle, so the
+    /// replay emits the same events as a live run; their outputs carry
+    /// `replayed: true` in their metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReplayError::UnknownStage`] or [`ReplayError::MissingOutput`]
+    /// before running anything if the live stages or the recording do not
+    /// match the pipeline, and [`ReplayError::Execution`] if the engine fails.
+    pub async fn replay(&self, graph: &StageGraph, ctx: Arc<PipelineContext>) -> Result<ReplayResult, ReplayError> {
+        if let Some(unknown) = self.live.iter().find(|stage| graph.stage_spec(stage).is_none()) {
+            return Err(ReplayError::UnknownStage(unknown.clone()));
+        }
+
+        let mut specs = graph.stage_specs().clone();
+        for (name, spec) in &mut specs {
+            if self.live.contains(name) {
+                continue;
+            }
+            let output = self
+                .recording
+                .output(name)
+                .ok_or_else(|| ReplayError::MissingOutput(name.clone()))?;
+            spec.runner = Arc::new(RecordedStage {
+                name: name.clone(),
+                output: output.clone(),
+            });
+        }
+        let replayed = StageGraph::new(graph.name().to_string(), specs, graph.execution_order().to_vec())
+            .with_budget(*graph.budget());
+
+        let snapshot = self.recording.snapshot.clone().unwrap_or_default();
+        let result = replayed.execute(ctx, snapshot).await?;
+        let divergent_stages = self
+            .live
+            .iter()
+            .filter(|stage| {
+                match (self.recording.output(stage), result.outputs.get(stage.as_str())) {
+                    (Some(recorded), Some(replayed)) => {
+                        recorded.status != replayed.status || recorded.data != replayed.data
+                    }
+                    _ => false,
+                }
+            })
+            .cloned()
+            .collect();
+        Ok(ReplayResult {
+            result,
+            live_stages: self.live.iter().cloned().collect(),
+            divergent_stages,
+        })
+    }
+}
+
+/// A stage answering with a recorded output.
+#[derive(Debug)]
+struct RecordedStage {
+    name: String,
+    output: StageOutput,
+}
+
+#[async_trait]
+impl Stage for RecordedStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, _ctx: &StageContext) -> StageOutput {
+        let output = self.output.clone();
+        if output.status == StageStatus::Ok {
+            output.add_metadata("replayed", serde_json::json!(true))
+        } else {
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::RunIdentity;
+    use crate::pipeline::PipelineBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Outputs how many times it has run.
+    #[derive(Debug, Default)]
+    struct Source(AtomicUsize);
+
+    #[async_trait]
+    impl Stage for Source {
+        fn name(&self) -> &'static str {
+            "source"
+        }
+
+        async fn execute(&self, _ctx: &StageContext) -> StageOutput {
+            let runs = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            StageOutput::ok_value("value", serde_json::json!(runs))
+        }
+    }
+
+    /// Doubles its input `value`.
+    #[derive(Debug)]
+    struct Double;
+
+    #[async_trait]
+    impl Stage for Double {
+        fn name(&self) -> &'static str {
+            "double"
+        }
+
+        async fn execute(&self, ctx: &StageContext) -> StageOutput {
+            let value = ctx.inputs().find_value("value").and_then(serde_json::Value::as_i64).unwrap_or_default();
+            StageOutput::ok_value("doubled", serde_json::json!(value * 2))
+        }
+    }
+
+    fn graph(source: Arc<Source>) -> StageGraph {
+        PipelineBuilder::new("replayed")
+            .stage("source", source, &[])
+            .unwrap()
+            .stage("double", Arc::new(Double), &["source"])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay_substitutes_recorded_outputs() {
+        let source = Arc::new(Source::default());
+        let graph = graph(source.clone());
+        let recording = Recording::new()
+            .with_output("source", StageOutput::ok_value("value", serde_json::json!(21)))
+            .with_output("double", StageOutput::ok_value("doubled", serde_json::json!(2)));
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let replay = Replayer::new(recording.clone()).with_live_stage("double").replay(&graph, ctx).await.unwrap();
+        assert!(replay.result.success);
+        assert_eq!(source.0.load(Ordering::SeqCst), 0);
+        assert_eq!(replay.result.outputs["source"].metadata["replayed"], true);
+        assert_eq!(replay.result.outputs["double"].get("doubled"), Some(&serde_json::json!(42)));
+        assert_eq!(replay.live_stages, vec!["double"]);
+        assert_eq!(replay.divergent_stages, vec!["double"]);
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let err = Replayer::new(recording).with_live_stage("missing").replay(&graph, ctx).await.unwrap_err();
+        assert!(matches!(err, ReplayError::UnknownStage(stage) if stage == "missing"));
+    }
+
+    #[tokio::test]
+    async fn test_replay_from_events() {
+        let event = |seq, event_type: &str, data: serde_json::Value| RecordedEvent {
+            seq,
+            run_id: "run-1".to_string(),
+            event_type: event_type.to_string(),
+            timestamp: 0.0,
+            data: Some(data),
+        };
+        let events = vec![
+            event(3, "stage.failed", serde_json::json!({ "stage": "double", "error": "overflow" })),
+            event(1, "stage.started", serde_json::json!({ "stage": "source" })),
+            event(2, "stage.completed", serde_json::json!({ "stage": "source", "duration_ms": 1.0 })),
+        ];
+        let recording = Recording::from_events(&events);
+        assert_eq!(recording.outputs.len(), 2);
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let replay = Replayer::new(recording).replay(&graph(Arc::new(Source::default())), ctx).await.unwrap();
+        assert!(!replay.result.success);
+        assert_eq!(replay.result.outputs["double"].error.as_deref(), Some("overflow"));
+
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let partial = Recording::from_events(&events[..1]);
+        let err = Replayer::new(partial).replay(&graph(Arc::new(Source::default())), ctx).await.unwrap_err();
+        assert!(matches!(err, ReplayError::MissingOutput(stage) if stage == "source"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Five-field cron expressions.
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+/// Error returned for an expression [`CronSchedule`] cannot parse.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid cron expression '{expression}': {reason}")]
+pub struct CronParseError {
+    /// The expression that failed to parse.
+    pub expression: String,
+    /// Why it failed.
+    pub reason: String,
+}
+
+impl CronParseError {
+    fn new(expression: &str, reason: impl Into<String>) -> Self {
+        Self {
+            expression: expression.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// The set of values one cron field matches, as a bitmask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field {
+    bits: u64,
+    /// True if the field starts with `*`, like `*` or `*/2`, which matters
+    /// for the day fields.
+    any: bool,
+}
+
+impl Field {
+    fn parse(text: &str, name: &str, min: u32, max: u32) -> Result<Self, String> {
+        let mut bits = 0u64;
+        for part in text.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| format!("invalid step '{step}' in {name}"))?;
+                    if step == 0 {
+                        return Err(format!("step cannot be zero in {name}"));
+                    }
+                    (range, step)
+                }
+                None => (part, 1),
+            };
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (parse_value(start, name, min, max)?, parse_value(end, name, min, max)?)
+            } else {
+                let value = parse_value(range, name, min, max)?;
+                // `5/15` means every 15 starting at 5
+                (value, if step > 1 { max } else { value })
+            };
+            if start > end {
+                return Err(format!("range {start}-{end} is reversed in {name}"));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(Self { bits, any: text.starts_with('*') })
+    }
+
+    fn matches(self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+fn parse_value(text: &str, name: &str, min: u32, max: u32) -> Result<u32, String> {
+    let value: u32 = text.parse().map_err(|_| format!("invalid value '{text}' in {name}"))?;
+    if value < min || value > max {
+        return Err(format!("{value} is outside {min}-{max} in {name}"));
+    }
+    Ok(value)
+}
+
+/// A cron schedule: `minute hour day-of-month month day-of-week`, in UTC.
+///
+/// Fields accept `*`, values, ranges (`1-5`), lists (`1,15`) and steps
+/// (`*/10`, `0-30/5`). Day of week runs from 0 (Sunday) to 6, with 7 also
+/// meaning Sunday. As in classic cron, when both day fields are
+/// restricted a time matches if either does; a day field starting with `*`,
+/// such as `*/2`, does not count as restricted. The shorthands `@hourly`,
+/// `@daily`, `@weekly`, `@monthly` and `@yearly` are accepted too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+}
+
+impl CronSchedule {
+    /// Parses a cron expression.
+    pub fn parse(expression: &str) -> Result<Self, CronParseError> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(CronParseError::new(
+                expression,
+                format!("expected 5 fields, found {}", fields.len()),
+            ));
+        };
+        let field = |text, name, min, max| {
+            Field::parse(text, name, min, max).map_err(|reason| CronParseError::new(expression, reason))
+        };
+        let mut days_of_week = field(days_of_week, "day of week", 0, 7)?;
+        if days_of_week.matches(7) {
+            days_of_week.bits = (days_of_week.bits | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field(minutes, "minute", 0, 59)?,
+            hours: field(hours, "hour", 0, 23)?,
+            days_of_month: field(days_of_month, "day of month", 1, 31)?,
+            months: field(months, "month", 1, 12)?,
+            days_of_week,
+        })
+    }
+
+    /// Returns the expression as written.
+    #[must_use]
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Returns true if the schedule fires at the minute containing `time`.
+    #[must_use]
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        self.months.matches(time.month())
+            && self.day_matches(time)
+            && self.hours.matches(time.hour())
+            && self.minutes.matches(time.minute())
+    }
+
+    /// Returns the first time strictly after `after` the schedule fires,
+    /// or `None` if it never fires in the next five years (e.g. `0 0 31 2 *`).
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let limit = after + Duration::days(5 * 366);
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        while time <= limit {
+            if !self.months.matches(time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(time) {
+                time = time.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !self.hours.matches(time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !self.minutes.matches(time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, time: DateTime<Utc>) -> bool {
+        let dom = self.days_of_month.matches(time.day());
+        let dow = self.days_of_week.matches(time.weekday().num_days_from_sunday());
+        match (self.days_of_month.any, self.days_of_week.any) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_next_after_steps_ranges_and_day_fields() {
+        let every_quarter = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter.next_after(at(2024, 1, 1, 10, 7)), Some(at(2024, 1, 1, 10, 15)));
+        assert_eq!(every_quarter.next_after(at(2024, 1, 1, 10, 45)), Some(at(2024, 1, 1, 11, 0)));
+
+        // 09:30 on weekdays; 2024-01-06 is a Saturday
+        let weekdays = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(at(2024, 1, 5, 9, 30)), Some(at(2024, 1, 8, 9, 30)));
+
+        // Either the 1st of the month or a Sunday
+        let either = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert_eq!(either.next_after(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 7, 0, 0)));
+
+        // A starred step leaves the day of month unrestricted, so both must
+        // hold: odd days that are Mondays, the 1st then the 15th
+        let odd_mondays = CronSchedule::parse("0 0 */2 * 1").unwrap();
+        assert!(odd_mondays.matches(at(2024, 1, 1, 0, 0)));
+        assert_eq!(odd_mondays.next_after(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 15, 0, 0)));
+
+        let yearly = CronSchedule::parse("@yearly").unwrap();
+        assert_eq!(yearly.next_after(at(2024, 3, 1, 0, 0)), Some(at(2025, 1, 1, 0, 0)));
+        assert!(CronSchedule::parse("0 0 31 2 *").unwrap().next_after(at(2024, 1, 1, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in ["* * * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            let error = CronSchedule::parse(expression).unwrap_err();
+            assert_eq!(error.expression, expression);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Scheduled and recurring pipeline runs.
+//!
+//! A [`PipelineScheduler`] starts runs of a [`UnifiedStageGraph`](crate::pipeline::UnifiedStageGraph)
+//! on a fixed interval or a cron expression, decides what to do when a run
+//! is due while the previous one is still going, and reports what it did
+//! through an [`EventSink`](crate::events::EventSink).
+
+mod cron;
+mod runner;
+mod schedule;
+
+pub use cron::{CronParseError, CronSchedule};
+pub use runner::{PipelineScheduler, RunContextFactory, SchedulerHandle, SchedulerStats};
+pub use schedule::{OverlapPolicy, Schedule};
@@ -0,0 +1,409 @@
+//! The scheduler loop.
+
+use super::{OverlapPolicy, Schedule};
+use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+use crate::errors::StageflowError;
+use crate::events::{EventSink, NoOpEventSink};
+use crate::pipeline::{UnifiedExecutionResult, UnifiedStageGraph};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+use tokio::task::{JoinHandle, JoinSet};
+use uuid::Uuid;
+
+/// Builds the context and input snapshot of a scheduled run from its
+/// freshly generated identity.
+pub type RunContextFactory = Arc<dyn Fn(RunIdentity) -> (PipelineContext, ContextSnapshot) + Send + Sync>;
+
+type RunOutcome = (Option<Uuid>, Result<UnifiedExecutionResult, StageflowError>);
+
+/// The most recently started run.
+struct ActiveRun {
+    run_id: Option<Uuid>,
+    ctx: Arc<PipelineContext>,
+    supersede: oneshot::Sender<String>,
+}
+
+/// Counts of what a scheduler did, returned when it stops.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SchedulerStats {
+    /// Runs started.
+    pub started: u64,
+    /// Runs that succeeded.
+    pub succeeded: u64,
+    /// Runs that failed or returned an error.
+    pub failed: u64,
+    /// Runs that were cancelled, including those superseded under
+    /// [`OverlapPolicy::CancelPrevious`].
+    pub cancelled: u64,
+    /// Due runs dropped under [`OverlapPolicy::Skip`].
+    pub skipped: u64,
+}
+
+/// Runs a pipeline on a [`Schedule`].
+///
+/// Each run gets a new [`RunIdentity`] and, unless a
+/// [context factory](Self::with_context_factory) is set, a context that
+/// emits to the scheduler's event sink. The scheduler emits
+/// `scheduler.run_started`, `scheduler.run_completed`,
+/// `scheduler.run_skipped`, `scheduler.run_queued`,
+/// `scheduler.run_superseded` and `scheduler.stopped`. Record run history
+/// with [`UnifiedStageGraph::with_run_store`].
+pub struct PipelineScheduler {
+    graph: Arc<UnifiedStageGraph>,
+    schedule: Schedule,
+    overlap: OverlapPolicy,
+    jitter: Duration,
+    max_runs: Option<u64>,
+    event_sink: Arc<dyn EventSink>,
+    context_factory: Option<RunContextFactory>,
+}
+
+impl PipelineScheduler {
+    /// Creates a scheduler that skips runs that would overlap.
+    #[must_use]
+    pub fn new(graph: Arc<UnifiedStageGraph>, schedule: Schedule) -> Self {
+        Self {
+            graph,
+            schedule,
+            overlap: OverlapPolicy::default(),
+            jitter: Duration::ZERO,
+            max_runs: None,
+            event_sink: Arc::new(NoOpEventSink),
+            context_factory: None,
+        }
+    }
+
+    /// Sets what happens when a run is due while the previous one runs.
+    #[must_use]
+    pub fn with_overlap_policy(mut self, overlap: OverlapPolicy) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Delays each run by a random amount up to `jitter`, so schedulers
+    /// sharing a schedule do not all start at once.
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Stops scheduling after `max_runs` runs have started.
+    #[must_use]
+    pub fn with_max_runs(mut self, max_runs: u64) -> Self {
+        self.max_runs = Some(max_runs);
+        self
+    }
+
+    /// Sets the sink for scheduler events.
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.event_sink = sink;
+        self
+    }
+
+    /// Sets how each run's context and input snapshot are built.
+    #[must_use]
+    pub fn with_context_factory(
+        mut self,
+        factory: impl Fn(RunIdentity) -> (PipelineContext, ContextSnapshot) + Send + Sync + 'static,
+    ) -> Self {
+        self.context_factory = Some(Arc::new(factory));
+        self
+    }
+
+    /// Starts scheduling on the current Tokio runtime.
+    #[must_use]
+    pub fn start(self) -> SchedulerHandle {
+        let (stop, stopped) = watch::channel(false);
+        SchedulerHandle {
+            stop,
+            task: tokio::spawn(self.run(stopped)),
+        }
+    }
+
+    async fn run(self, mut stop: watch::Receiver<bool>) -> SchedulerStats {
+        let mut stats = SchedulerStats::default();
+        let mut running: JoinSet<RunOutcome> = JoinSet::new();
+        let mut current: Option<ActiveRun> = None;
+        let mut queued = 0u64;
+        let mut stopping = false;
+        let mut next = self.next_fire(Utc::now());
+
+        loop {
+            let budget_left = self.max_runs.map_or(true, |max| stats.started + queued < max);
+            let due = next.filter(|_| !stopping && budget_left);
+            if due.is_none() && running.is_empty() && queued == 0 {
+                break;
+            }
+            let wake_at = due.map_or_else(tokio::time::Instant::now, |(_, wake_at)| wake_at);
+
+            tokio::select! {
+                _ = stop.changed(), if !stopping => {
+                    stopping = true;
+                    queued = 0;
+                }
+                Some(joined) = running.join_next(), if !running.is_empty() => {
+                    let outcome = joined.unwrap_or_else(|e| {
+                        (None, Err(StageflowError::Internal(format!("Scheduled run panicked: {e}"))))
+                    });
+                    // A finished run has dropped its end of the supersede channel
+                    if current.as_ref().is_some_and(|run| run.supersede.is_closed()) {
+                        current = None;
+                    }
+                    self.record(&mut stats, outcome);
+                    if queued > 0 && current.is_none() {
+                        queued -= 1;
+                        current = Some(self.start_run(&mut running, &mut stats, Utc::now()));
+                    }
+                }
+                () = tokio::time::sleep_until(wake_at), if due.is_some() => {
+                    let scheduled_for = due.map_or_else(Utc::now, |(at, _)| at);
+                    next = self.next_fire(scheduled_for.max(Utc::now()));
+                    self.fire(&mut running, &mut stats, &mut current, &mut queued, scheduled_for);
+                }
+            }
+        }
+
+        self.emit("scheduler.stopped", serde_json::to_value(stats).unwrap_or_default());
+        stats
+    }
+
+    /// Returns the next fire time after `after` and when to wake for it,
+    /// jitter included.
+    fn next_fire(&self, after: DateTime<Utc>) -> Option<(DateTime<Utc>, tokio::time::Instant)> {
+        let at = self.schedule.next_after(after)?;
+        let jitter_ms = u64::try_from(self.jitter.as_millis()).unwrap_or(u64::MAX);
+        let jitter = Duration::from_millis(crate::utils::random_in_range(0..=jitter_ms));
+        let wait = (at - Utc::now()).to_std().unwrap_or_default() + jitter;
+        Some((at, tokio::time::Instant::now() + wait))
+    }
+
+    fn fire(
+        &self,
+        running: &mut JoinSet<RunOutcome>,
+        stats: &mut SchedulerStats,
+        current: &mut Option<ActiveRun>,
+        queued: &mut u64,
+        scheduled_for: DateTime<Utc>,
+    ) {
+        let Some(active) = current.take() else {
+            *current = Some(self.start_run(running, stats, scheduled_for));
+            return;
+        };
+        let overlap = serde_json::json!({
+            "pipeline": self.graph.name(),
+            "scheduled_for": scheduled_for.to_rfc3339(),
+            "running_run_id": active.run_id,
+            "overlap_policy": self.overlap.as_str(),
+        });
+        match self.overlap {
+            OverlapPolicy::Skip => {
+                stats.skipped += 1;
+                self.emit("scheduler.run_skipped", overlap);
+                *current = Some(active);
+            }
+            OverlapPolicy::Queue => {
+                *queued += 1;
+                self.emit("scheduler.run_queued", overlap);
+                *current = Some(active);
+            }
+            OverlapPolicy::CancelPrevious => {
+                let reason = "Superseded by a newer scheduled run";
+                active.ctx.mark_cancelled_with_reason(reason);
+                let _ = active.supersede.send(reason.to_string());
+                self.emit("scheduler.run_superseded", overlap);
+                *current = Some(self.start_run(running, stats, scheduled_for));
+            }
+        }
+    }
+
+    fn start_run(
+        &self,
+        running: &mut JoinSet<RunOutcome>,
+        stats: &mut SchedulerStats,
+        scheduled_for: DateTime<Utc>,
+    ) -> ActiveRun {
+        let identity = RunIdentity::new();
+        let run_id = identity.pipeline_run_id;
+        let (ctx, snapshot) = match &self.context_factory {
+            Some(factory) => factory(identity),
+            None => (
+                PipelineContext::new(identity).with_event_sink(Arc::clone(&self.event_sink)),
+                ContextSnapshot::new(),
+            ),
+        };
+        let ctx = Arc::new(ctx);
+        stats.started += 1;
+        self.emit(
+            "scheduler.run_started",
+            serde_json::json!({
+                "pipeline": self.graph.name(),
+                "run_id": run_id,
+                "scheduled_for": scheduled_for.to_rfc3339(),
+            }),
+        );
+
+        let graph = Arc::clone(&self.graph);
+        let run_ctx = Arc::clone(&ctx);
+        let (supersede, superseded) = oneshot::channel();
+        running.spawn(async move {
+            // Stages only check for cancellation between stages, so stop a
+            // superseded run outright
+            let result = tokio::select! {
+                result = graph.execute(run_ctx, snapshot) => result,
+                Ok(reason) = superseded => Err(StageflowError::Cancelled(reason)),
+            };
+            (run_id, result)
+        });
+        ActiveRun { run_id, ctx, supersede }
+    }
+
+    fn record(&self, stats: &mut SchedulerStats, (run_id, result): RunOutcome) {
+        let (outcome, error, duration_ms) = match &result {
+            Ok(result) if result.success => ("succeeded", None, Some(result.duration_ms)),
+            Ok(result) if result.cancelled => ("cancelled", result.cancel_reason.clone(), Some(result.duration_ms)),
+            Ok(result) => ("failed", result.error.clone(), Some(result.duration_ms)),
+            Err(StageflowError::Cancelled(reason)) => ("cancelled", Some(reason.clone()), None),
+            Err(e) => ("failed", Some(e.to_string()), None),
+        };
+        match outcome {
+            "succeeded" => stats.succeeded += 1,
+            "cancelled" => stats.cancelled += 1,
+            _ => stats.failed += 1,
+        }
+        self.emit(
+            "scheduler.run_completed",
+            serde_json::json!({
+                "pipeline": self.graph.name(),
+                "run_id": run_id,
+                "status": outcome,
+                "error": error,
+                "duration_ms": duration_ms,
+            }),
+        );
+    }
+
+    fn emit(&self, event_type: &str, data: serde_json::Value) {
+        self.event_sink.try_emit(event_type, Some(data));
+    }
+}
+
+impl std::fmt::Debug for PipelineScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineScheduler")
+            .field("pipeline", &self.graph.name())
+            .field("schedule", &self.schedule)
+            .field("overlap", &self.overlap)
+            .field("jitter", &self.jitter)
+            .field("max_runs", &self.max_runs)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Handle to a started [`PipelineScheduler`].
+#[derive(Debug)]
+pub struct SchedulerHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<SchedulerStats>,
+}
+
+impl SchedulerHandle {
+    /// Stops starting runs and drops queued ones. Runs in flight finish.
+    pub fn stop(&self) {
+        let _ = self.stop.send(true);
+    }
+
+    /// Returns true once the scheduler has stopped and its runs finished.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the scheduler to finish, either because it was stopped or
+    /// because the schedule or the run limit ran out.
+    pub async fn join(self) -> Result<SchedulerStats, StageflowError> {
+        self.task
+            .await
+            .map_err(|e| StageflowError::Internal(format!("Scheduler task failed: {e}")))
+    }
+
+    /// Stops the scheduler and waits for runs in flight to finish.
+    pub async fn shutdown(self) -> Result<SchedulerStats, StageflowError> {
+        self.stop();
+        self.join().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::CollectingEventSink;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::NoOpStage;
+    use crate::testing::SlowStage;
+
+    fn graph(stage: Arc<dyn crate::stages::Stage>) -> Arc<UnifiedStageGraph> {
+        let graph = PipelineBuilder::new("nightly").stage("work", stage, &[]).unwrap().build().unwrap();
+        Arc::new(UnifiedStageGraph::new(graph))
+    }
+
+    #[tokio::test]
+    async fn test_interval_runs_get_fresh_identities() {
+        let sink = Arc::new(CollectingEventSink::new());
+        let stats = PipelineScheduler::new(
+            graph(Arc::new(NoOpStage::new("work"))),
+            Schedule::every(Duration::from_millis(5)),
+        )
+        .with_max_runs(3)
+        .with_event_sink(sink.clone())
+        .start()
+        .join()
+        .await
+        .unwrap();
+
+        assert_eq!(stats.started, 3);
+        assert_eq!(stats.succeeded, 3);
+        let run_ids: std::collections::HashSet<String> = sink
+            .events_of_type("scheduler.run_started")
+            .into_iter()
+            .map(|(_, data)| data.unwrap()["run_id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(run_ids.len(), 3);
+        // Runs emit their own events through the same sink
+        assert_eq!(sink.events_of_type("stage.completed").len(), 3);
+        assert_eq!(sink.events_of_type("scheduler.stopped").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_overlap_policies() {
+        let run = |overlap| {
+            PipelineScheduler::new(
+                graph(Arc::new(SlowStage::with_delay_ms("work", 30))),
+                Schedule::every(Duration::from_millis(10)),
+            )
+            .with_overlap_policy(overlap)
+            .start()
+        };
+
+        let skipping = run(OverlapPolicy::Skip);
+        let queueing = run(OverlapPolicy::Queue);
+        let superseding = run(OverlapPolicy::CancelPrevious);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let skipped = skipping.shutdown().await.unwrap();
+        let queued = queueing.shutdown().await.unwrap();
+        let superseded = superseding.shutdown().await.unwrap();
+
+        assert!(skipped.skipped > 0);
+        assert_eq!(skipped.started, skipped.succeeded);
+        assert_eq!(queued.skipped, 0);
+        assert_eq!(queued.started, queued.succeeded);
+        // Every run but the last is replaced before its stage finishes
+        assert!(superseded.cancelled > 0);
+        assert_eq!(superseded.cancelled + superseded.succeeded, superseded.started);
+        assert_eq!(superseded.succeeded, 1);
+    }
+}
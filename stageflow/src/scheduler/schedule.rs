@@ -0,0 +1,65 @@
+//! When scheduled runs fire and what happens when they overlap.
+
+use super::{CronParseError, CronSchedule};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// When a [`PipelineScheduler`](super::PipelineScheduler) starts runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// A fixed interval, with the first run one interval after start.
+    Interval(Duration),
+    /// A cron expression, evaluated in UTC.
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    /// Fires every `interval`.
+    #[must_use]
+    pub fn every(interval: Duration) -> Self {
+        Self::Interval(interval)
+    }
+
+    /// Fires on a cron expression; see [`CronSchedule`] for the syntax.
+    pub fn cron(expression: &str) -> Result<Self, CronParseError> {
+        CronSchedule::parse(expression).map(Self::Cron)
+    }
+
+    /// Returns the first fire time strictly after `after`.
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Interval(interval) => chrono::Duration::from_std(*interval)
+                .ok()
+                .filter(|interval| *interval > chrono::Duration::zero())
+                .and_then(|interval| after.checked_add_signed(interval)),
+            Self::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+/// What to do when a run is due while the previous one is still running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+    /// Drop the due run.
+    #[default]
+    Skip,
+    /// Start the due run once the previous one finishes.
+    Queue,
+    /// Cancel the previous run and start the due one.
+    CancelPrevious,
+}
+
+impl OverlapPolicy {
+    /// Returns the policy name used in events.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Queue => "queue",
+            Self::CancelPrevious => "cancel_previous",
+        }
+    }
+}
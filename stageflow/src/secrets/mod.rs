@@ -0,0 +1,122 @@
+//! Secrets referenced from configuration and kept out of run records.
+//!
+//! Configuration refers to a secret as `secret://<provider>/<name>`, for
+//! example `secret://env/OPENAI_API_KEY`. References are kept as-is in
+//! manifests and tool definitions and resolved by a [`SecretResolver`]
+//! only when a stage or tool runs. Every resolved value is remembered by
+//! the resolver's [`Redactor`], and contexts with the resolver attached
+//! through [`PipelineContext::with_secrets`](crate::context::PipelineContext::with_secrets)
+//! redact those values from events and stage errors.
+//!
+//! Built-in providers read environment variables, files in a directory and
+//! an in-memory map; with the `vault` feature, a Vault KV version 2
+//! engine.
+
+mod providers;
+mod redact;
+mod resolver;
+#[cfg(feature = "vault")]
+mod vault;
+
+pub use providers::{EnvSecretsProvider, FileSecretsProvider, InMemorySecretsProvider};
+pub use redact::{RedactingSnapshotStore, Redactor, REDACTED};
+pub use resolver::SecretResolver;
+#[cfg(feature = "vault")]
+pub use vault::VaultSecretsProvider;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+/// Prefix of a secret reference.
+pub const SECRET_SCHEME: &str = "secret://";
+
+/// Error raised when a secret cannot be resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SecretError {
+    /// The string starts with `secret://` but is not a valid reference.
+    #[error("Invalid secret reference '{0}', expected secret://<provider>/<name>")]
+    InvalidReference(String),
+
+    /// No provider is registered under the reference's provider name.
+    #[error("Unknown secrets provider '{0}'")]
+    UnknownProvider(String),
+
+    /// The provider has no secret under the name.
+    #[error("Secret not found: {0}")]
+    NotFound(String),
+
+    /// The provider failed.
+    #[error("Secrets provider '{provider}' failed: {message}")]
+    Provider {
+        /// The provider name.
+        provider: String,
+        /// What went wrong.
+        message: String,
+    },
+}
+
+/// A parsed `secret://<provider>/<name>` reference.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SecretRef {
+    /// Name of the provider to ask.
+    pub provider: String,
+    /// Name of the secret within the provider.
+    pub name: String,
+}
+
+impl SecretRef {
+    /// Creates a reference.
+    #[must_use]
+    pub fn new(provider: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            name: name.into(),
+        }
+    }
+
+    /// Parses `text` if it is a secret reference.
+    ///
+    /// Returns `Ok(None)` for strings that do not start with `secret://`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SecretError::InvalidReference`] if the provider or name is empty.
+    pub fn parse(text: &str) -> Result<Option<Self>, SecretError> {
+        let Some(rest) = text.strip_prefix(SECRET_SCHEME) else {
+            return Ok(None);
+        };
+        match rest.split_once('/') {
+            Some((provider, name)) if !provider.is_empty() && !name.is_empty() => Ok(Some(Self::new(provider, name))),
+            _ => Err(SecretError::InvalidReference(text.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{SECRET_SCHEME}{}/{}", self.provider, self.name)
+    }
+}
+
+/// A source of secret values.
+#[async_trait]
+pub trait SecretsProvider: Send + Sync {
+    /// Returns the secret stored under `name`, or `None` if there is none.
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, SecretError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reference() {
+        let reference = SecretRef::parse("secret://vault/app/db#password").unwrap().unwrap();
+        assert_eq!(reference, SecretRef::new("vault", "app/db#password"));
+        assert_eq!(reference.to_string(), "secret://vault/app/db#password");
+
+        assert_eq!(SecretRef::parse("plain value").unwrap(), None);
+        assert!(matches!(SecretRef::parse("secret://env"), Err(SecretError::InvalidReference(_))));
+        assert!(matches!(SecretRef::parse("secret:///KEY"), Err(SecretError::InvalidReference(_))));
+    }
+}
@@ -0,0 +1,146 @@
+//! Built-in secrets providers.
+
+use super::{SecretError, SecretsProvider};
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Reads secrets from environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecretsProvider {
+    prefix: String,
+}
+
+impl EnvSecretsProvider {
+    /// Creates a provider reading the variable named after the secret.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepends `prefix` to secret names, so `API_KEY` reads `<prefix>API_KEY`.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for EnvSecretsProvider {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, SecretError> {
+        Ok(std::env::var(format!("{}{name}", self.prefix)).ok())
+    }
+}
+
+/// Reads secrets from files in a directory, one file per secret.
+///
+/// Names may contain `/` to reach subdirectories but cannot leave the
+/// directory. A single trailing newline is stripped, as written by most
+/// secret mounts and editors.
+#[derive(Debug, Clone)]
+pub struct FileSecretsProvider {
+    directory: PathBuf,
+}
+
+impl FileSecretsProvider {
+    /// Creates a provider reading files under `directory`.
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Returns the secrets directory.
+    #[must_use]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for FileSecretsProvider {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, SecretError> {
+        let relative = Path::new(name);
+        if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(SecretError::InvalidReference(format!("file/{name}")));
+        }
+        match tokio::fs::read_to_string(self.directory.join(relative)).await {
+            Ok(mut value) => {
+                if value.ends_with('\n') {
+                    value.pop();
+                    if value.ends_with('\r') {
+                        value.pop();
+                    }
+                }
+                Ok(Some(value))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(SecretError::Provider {
+                provider: "file".to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// Holds secrets in memory, for tests and secrets fetched by the caller.
+#[derive(Debug, Default)]
+pub struct InMemorySecretsProvider {
+    secrets: RwLock<HashMap<String, String>>,
+}
+
+impl InMemorySecretsProvider {
+    /// Creates an empty provider.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a secret.
+    #[must_use]
+    pub fn with_secret(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Adds or replaces a secret.
+    pub fn set(&self, name: impl Into<String>, value: impl Into<String>) {
+        self.secrets.write().insert(name.into(), value.into());
+    }
+
+    /// Removes a secret, returning whether it existed.
+    pub fn remove(&self, name: &str) -> bool {
+        self.secrets.write().remove(name).is_some()
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for InMemorySecretsProvider {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, SecretError> {
+        Ok(self.secrets.read().get(name).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("db")).unwrap();
+        std::fs::write(dir.path().join("db").join("password"), "hunter2\n").unwrap();
+        let provider = FileSecretsProvider::new(dir.path());
+
+        assert_eq!(provider.get_secret("db/password").await.unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(provider.get_secret("missing").await.unwrap(), None);
+        assert!(matches!(
+            provider.get_secret("../etc/passwd").await,
+            Err(SecretError::InvalidReference(_))
+        ));
+        assert!(provider.get_secret("/etc/passwd").await.is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Redaction of resolved secret values.
+
+use crate::context::{ContextSnapshot, SnapshotStore};
+use crate::errors::StageflowError;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Replacement for redacted secret values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Remembers secret values and replaces them wherever they appear.
+#[derive(Default)]
+pub struct Redactor {
+    secrets: RwLock<BTreeSet<String>>,
+}
+
+impl std::fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Redactor")
+            .field("secret_count", &self.len())
+            .finish()
+    }
+}
+
+impl Redactor {
+    /// Creates a redactor that knows no secrets.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value to redact. Empty values are ignored.
+    pub fn add(&self, secret: impl Into<String>) {
+        let secret = secret.into();
+        if !secret.is_empty() {
+            self.secrets.write().insert(secret);
+        }
+    }
+
+    /// Returns the number of values redacted.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.secrets.read().len()
+    }
+
+    /// Returns true if no values are redacted.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.secrets.read().is_empty()
+    }
+
+    /// Returns `text` with every known secret replaced by [`REDACTED`].
+    ///
+    /// Longer secrets are replaced first, so a secret containing another is
+    /// not left partly visible.
+    #[must_use]
+    pub fn redact(&self, text: &str) -> String {
+        let secrets = self.secrets.read();
+        let mut ordered: Vec<&String> = secrets.iter().filter(|secret| text.contains(secret.as_str())).collect();
+        ordered.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        ordered
+            .into_iter()
+            .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED))
+    }
+
+    /// Redacts every string in `value`, including object keys.
+    pub fn redact_value(&self, value: &mut serde_json::Value) {
+        if self.is_empty() {
+            return;
+        }
+        match value {
+            serde_json::Value::String(text) => {
+                let redacted = self.redact(text);
+                *text = redacted;
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            serde_json::Value::Object(map) => {
+                let entries = std::mem::take(map);
+                for (key, mut item) in entries {
+                    self.redact_value(&mut item);
+                    map.insert(self.redact(&key), item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns a copy of `snapshot` with known secrets redacted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StageflowError::Serialization`] if the redacted snapshot
+    /// no longer deserializes, which only happens when a secret appears in
+    /// a field that must keep its format, such as an id.
+    pub fn redact_snapshot(&self, snapshot: &ContextSnapshot) -> Result<ContextSnapshot, StageflowError> {
+        let mut value = serde_json::to_value(snapshot).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        self.redact_value(&mut value);
+        serde_json::from_value(value).map_err(|e| StageflowError::Serialization(e.to_string()))
+    }
+}
+
+/// Snapshot store that redacts known secrets before saving.
+pub struct RedactingSnapshotStore {
+    inner: Arc<dyn SnapshotStore>,
+    redactor: Arc<Redactor>,
+}
+
+impl RedactingSnapshotStore {
+    /// Wraps `inner`, redacting the values `redactor` knows.
+    #[must_use]
+    pub fn new(inner: Arc<dyn SnapshotStore>, redactor: Arc<Redactor>) -> Self {
+        Self { inner, redactor }
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for RedactingSnapshotStore {
+    async fn save(&self, key: &str, snapshot: &ContextSnapshot) -> Result<(), StageflowError> {
+        if self.redactor.is_empty() {
+            return self.inner.save(key, snapshot).await;
+        }
+        self.inner.save(key, &self.redactor.redact_snapshot(snapshot)?).await
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<ContextSnapshot>, StageflowError> {
+        self.inner.load(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StageflowError> {
+        self.inner.delete(key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{FileSnapshotStore, SnapshotStore};
+
+    #[tokio::test]
+    async fn test_redacts_values_and_snapshots() {
+        let redactor = Arc::new(Redactor::new());
+        redactor.add("sk-123");
+        redactor.add("sk-123456");
+        redactor.add("");
+        assert_eq!(redactor.len(), 2);
+        assert_eq!(redactor.redact("key sk-123456 and sk-123"), "key [REDACTED] and [REDACTED]");
+
+        let mut value = serde_json::json!({ "error": "bad key sk-123", "sk-123": [1, "sk-123456"] });
+        redactor.redact_value(&mut value);
+        assert_eq!(value, serde_json::json!({ "error": "bad key [REDACTED]", "[REDACTED]": [1, "[REDACTED]"] }));
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = RedactingSnapshotStore::new(Arc::new(FileSnapshotStore::new(dir.path())), redactor);
+        let snapshot = ContextSnapshot::new().with_input_text("my key is sk-123");
+        store.save("run", &snapshot).await.unwrap();
+        let loaded = store.load("run").await.unwrap().unwrap();
+        assert_eq!(loaded.input_text.as_deref(), Some("my key is [REDACTED]"));
+    }
+}
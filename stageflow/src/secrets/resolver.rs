@@ -0,0 +1,139 @@
+//! Resolution of secret references against registered providers.
+
+use super::{Redactor, SecretError, SecretRef, SecretsProvider};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Resolves `secret://` references and records the values for redaction.
+///
+/// Values are fetched from the provider on every call rather than cached,
+/// so rotated secrets are picked up by the next stage or tool that runs.
+#[derive(Clone, Default)]
+pub struct SecretResolver {
+    providers: HashMap<String, Arc<dyn SecretsProvider>>,
+    redactor: Arc<Redactor>,
+}
+
+impl std::fmt::Debug for SecretResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretResolver")
+            .field("providers", &self.provider_names())
+            .field("redactor", &self.redactor)
+            .finish()
+    }
+}
+
+impl SecretResolver {
+    /// Creates a resolver with no providers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a provider under `name`, the first path segment of a reference.
+    #[must_use]
+    pub fn with_provider(mut self, name: impl Into<String>, provider: Arc<dyn SecretsProvider>) -> Self {
+        self.providers.insert(name.into(), provider);
+        self
+    }
+
+    /// Records resolved values in `redactor` instead of a private one.
+    #[must_use]
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Returns the redactor holding every value resolved so far.
+    #[must_use]
+    pub fn redactor(&self) -> Arc<Redactor> {
+        Arc::clone(&self.redactor)
+    }
+
+    /// Returns the registered provider names, sorted.
+    #[must_use]
+    pub fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Resolves a single reference.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SecretError::UnknownProvider`] if no provider is registered
+    /// for the reference, [`SecretError::NotFound`] if the provider has no
+    /// such secret, or the provider's own error.
+    pub async fn resolve_ref(&self, reference: &SecretRef) -> Result<String, SecretError> {
+        let provider = self
+            .providers
+            .get(&reference.provider)
+            .ok_or_else(|| SecretError::UnknownProvider(reference.provider.clone()))?;
+        let value = provider
+            .get_secret(&reference.name)
+            .await?
+            .ok_or_else(|| SecretError::NotFound(reference.to_string()))?;
+        self.redactor.add(value.clone());
+        Ok(value)
+    }
+
+    /// Returns `value` with every string that is a whole secret reference
+    /// replaced by the secret.
+    ///
+    /// Strings that merely contain `secret://` somewhere other than the
+    /// start are left alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first [`SecretError`] raised by a malformed reference or
+    /// a failed lookup.
+    pub async fn resolve(&self, value: &serde_json::Value) -> Result<serde_json::Value, SecretError> {
+        let mut resolved = value.clone();
+        let mut pending = vec![&mut resolved];
+        let mut slots = Vec::new();
+        while let Some(current) = pending.pop() {
+            match current {
+                serde_json::Value::String(text) => {
+                    if let Some(reference) = SecretRef::parse(text)? {
+                        slots.push((text, reference));
+                    }
+                }
+                serde_json::Value::Array(items) => pending.extend(items.iter_mut()),
+                serde_json::Value::Object(map) => pending.extend(map.values_mut()),
+                _ => {}
+            }
+        }
+        for (slot, reference) in slots {
+            *slot = self.resolve_ref(&reference).await?;
+        }
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::InMemorySecretsProvider;
+
+    #[tokio::test]
+    async fn test_resolves_nested_references_and_records_values() {
+        let provider = InMemorySecretsProvider::new().with_secret("API_KEY", "sk-live");
+        let resolver = SecretResolver::new().with_provider("mem", Arc::new(provider));
+
+        let config = serde_json::json!({
+            "headers": { "authorization": "secret://mem/API_KEY" },
+            "urls": ["https://example.com", "see secret://mem/API_KEY"],
+            "retries": 3
+        });
+        let values = resolver.resolve(&config).await.unwrap();
+        assert_eq!(values["headers"]["authorization"], "sk-live");
+        assert_eq!(values["urls"][1], "see secret://mem/API_KEY");
+        assert_eq!(resolver.redactor().redact("token sk-live"), "token [REDACTED]");
+
+        let missing = resolver.resolve(&serde_json::json!("secret://mem/OTHER")).await;
+        assert_eq!(missing, Err(SecretError::NotFound("secret://mem/OTHER".to_string())));
+        let unknown = resolver.resolve_ref(&SecretRef::new("env", "X")).await;
+        assert_eq!(unknown, Err(SecretError::UnknownProvider("env".to_string())));
+    }
+}
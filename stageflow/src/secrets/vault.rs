@@ -0,0 +1,133 @@
+//! Vault KV version 2 secrets provider.
+
+use super::{SecretError, SecretsProvider};
+use async_trait::async_trait;
+
+/// Default KV v2 mount path.
+pub const DEFAULT_VAULT_MOUNT: &str = "secret";
+
+/// Field read when a secret name does not name one.
+pub const DEFAULT_VAULT_FIELD: &str = "value";
+
+/// Reads secrets from a Vault-compatible KV v2 engine over HTTP.
+///
+/// Secret names have the form `path#field`, e.g. `app/db#password` reads
+/// the `password` field of the latest version of `app/db`. Without a
+/// `#field` suffix the `value` field is read.
+#[derive(Debug, Clone)]
+pub struct VaultSecretsProvider {
+    client: reqwest::Client,
+    address: String,
+    token: String,
+    mount: String,
+    namespace: Option<String>,
+}
+
+impl VaultSecretsProvider {
+    /// Creates a provider for the server at `address` authenticating with `token`.
+    #[must_use]
+    pub fn new(address: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            address: address.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+            mount: DEFAULT_VAULT_MOUNT.to_string(),
+            namespace: None,
+        }
+    }
+
+    /// Reads from the KV engine mounted at `mount`.
+    #[must_use]
+    pub fn with_mount(mut self, mount: impl Into<String>) -> Self {
+        self.mount = mount.into().trim_matches('/').to_string();
+        self
+    }
+
+    /// Sends requests to the Vault Enterprise namespace `namespace`.
+    #[must_use]
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Uses `client` for requests, e.g. to set timeouts or TLS options.
+    #[must_use]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn failed(message: impl std::fmt::Display) -> SecretError {
+        SecretError::Provider {
+            provider: "vault".to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for VaultSecretsProvider {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, SecretError> {
+        let (path, field) = name.split_once('#').unwrap_or((name, DEFAULT_VAULT_FIELD));
+        let url = format!("{}/v1/{}/data/{}", self.address, self.mount, path.trim_start_matches('/'));
+        let mut request = self.client.get(url).header("X-Vault-Token", &self.token);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request.send().await.map_err(Self::failed)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(Self::failed(format!("HTTP {}", response.status())));
+        }
+        let body: serde_json::Value = response.json().await.map_err(Self::failed)?;
+        match body.pointer(&format!("/data/data/{field}")) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(value)) => Ok(Some(value.clone())),
+            Some(other) => Ok(Some(other.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every request with a KV v2 response for `/v1/kv/data/app/db`
+    /// when the token matches, and 404 otherwise.
+    async fn vault() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let read = socket.read(&mut buf).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..read]).to_lowercase();
+                let (code, body) = if head.starts_with("get /v1/kv/data/app/db ") && head.contains("x-vault-token: t0k") {
+                    (200, r#"{"data":{"data":{"password":"hunter2","port":5432},"metadata":{"version":3}}}"#)
+                } else {
+                    (404, r#"{"errors":[]}"#)
+                };
+                let response = format!(
+                    "HTTP/1.1 {code} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn test_reads_kv_v2_fields() {
+        let provider = VaultSecretsProvider::new(vault().await, "t0k").with_mount("kv");
+        assert_eq!(provider.get_secret("app/db#password").await.unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(provider.get_secret("app/db#port").await.unwrap().as_deref(), Some("5432"));
+        assert_eq!(provider.get_secret("app/db").await.unwrap(), None);
+        assert_eq!(provider.get_secret("app/other#password").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,12 @@
+//! HTTP API for embedding stageflow in a service.
+//!
+//! [`router`] builds an axum [`Router`](axum::Router) over a
+//! [`RunManager`](crate::pipeline::RunManager) that submits runs, polls
+//! their status, streams their events as server-sent events and cancels
+//! them. Nest it under a prefix to mount it in an existing application.
+//!
+//! Enabled by the `server` feature.
+
+mod router;
+
+pub use router::router;
@@ -0,0 +1,222 @@
+//! Routes and handlers.
+
+use crate::context::ContextSnapshot;
+use crate::pipeline::{RunInfo, RunManager, RunManagerError};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// Builds the API router.
+///
+/// | Method | Path | |
+/// |---|---|---|
+/// | `GET` | `/pipelines` | Names of the registered pipelines |
+/// | `POST` | `/pipelines/{pipeline}/runs` | Starts a run; the body is an optional [`ContextSnapshot`] |
+/// | `GET` | `/runs/{run_id}` | The run's [`RunInfo`] |
+/// | `GET` | `/runs/{run_id}/events` | The run's events as server-sent events |
+/// | `POST` | `/runs/{run_id}/cancel` | Cancels the run; the body is an optional `{"reason": ...}` |
+///
+/// Starting a run responds `202 Accepted` with the running [`RunInfo`], or
+/// with `?wait=true`, `200 OK` once the run finishes. Each server-sent
+/// event is named after the event type, carries its sequence number as
+/// the id and a [`RecordedEvent`](crate::events::RecordedEvent) as data;
+/// the stream closes when the run finishes. Errors are JSON objects with
+/// an `error` field.
+pub fn router(runs: Arc<RunManager>) -> Router {
+    Router::new()
+        .route("/pipelines", get(list_pipelines))
+        .route("/pipelines/:pipeline/runs", post(start_run))
+        .route("/runs/:run_id", get(run_status))
+        .route("/runs/:run_id/events", get(run_events))
+        .route("/runs/:run_id/cancel", post(cancel_run))
+        .with_state(runs)
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl From<RunManagerError> for ApiError {
+    fn from(error: RunManagerError) -> Self {
+        let status = match error {
+            RunManagerError::UnknownPipeline(_) | RunManagerError::UnknownRun(_) => StatusCode::NOT_FOUND,
+            RunManagerError::Lagged(_) => StatusCode::TOO_MANY_REQUESTS,
+            RunManagerError::Store(_) => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        Self {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// Parses an optional JSON body, treating an empty one as the default.
+fn optional_json<T: Default + for<'de> Deserialize<'de>>(body: &Bytes) -> Result<T, ApiError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|e| ApiError {
+        status: StatusCode::BAD_REQUEST,
+        message: format!("Invalid request body: {e}"),
+    })
+}
+
+#[derive(Serialize)]
+struct PipelineList<'a> {
+    pipelines: Vec<&'a str>,
+}
+
+async fn list_pipelines(State(runs): State<Arc<RunManager>>) -> Response {
+    Json(PipelineList {
+        pipelines: runs.pipeline_names(),
+    })
+    .into_response()
+}
+
+#[derive(Default, Deserialize)]
+struct StartParams {
+    #[serde(default)]
+    wait: bool,
+}
+
+async fn start_run(
+    State(runs): State<Arc<RunManager>>,
+    Path(pipeline): Path<String>,
+    Query(params): Query<StartParams>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<RunInfo>), ApiError> {
+    let snapshot: ContextSnapshot = optional_json(&body)?;
+    let run = runs.start(&pipeline, snapshot)?;
+    if !params.wait {
+        return Ok((StatusCode::ACCEPTED, Json(run)));
+    }
+    Ok((StatusCode::OK, Json(runs.wait(&run.run_id).await?)))
+}
+
+async fn run_status(State(runs): State<Arc<RunManager>>, Path(run_id): Path<String>) -> Result<Json<RunInfo>, ApiError> {
+    Ok(Json(runs.status(&run_id).await?))
+}
+
+async fn run_events(
+    State(runs): State<Arc<RunManager>>,
+    Path(run_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let events = runs.events(&run_id)?.map(|event| {
+        let event = match event {
+            Ok(event) => Event::default()
+                .id(event.seq.to_string())
+                .event(&event.event_type)
+                .json_data(&event),
+            Err(e) => Event::default().event("error").json_data(json!({ "error": e.to_string() })),
+        };
+        // Serializing a RecordedEvent cannot fail
+        Ok(event.unwrap_or_default())
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Default, Deserialize)]
+struct CancelBody {
+    #[serde(default)]
+    reason: String,
+}
+
+async fn cancel_run(
+    State(runs): State<Arc<RunManager>>,
+    Path(run_id): Path<String>,
+    body: Bytes,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body: CancelBody = optional_json(&body)?;
+    let cancelled = runs.cancel(&run_id, &body.reason)?;
+    Ok(Json(json!({ "cancelled": cancelled })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{PipelineBuilder, RunState, UnifiedStageGraph};
+    use crate::testing::SlowStage;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn app(delay_ms: u64) -> Router {
+        let stage = Arc::new(SlowStage::with_delay_ms("work", delay_ms));
+        let graph = PipelineBuilder::new("jobs").stage("work", stage, &[]).unwrap().build().unwrap();
+        router(Arc::new(RunManager::new().with_pipeline(Arc::new(UnifiedStageGraph::new(graph)))))
+    }
+
+    async fn call(app: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, Bytes) {
+        let request = Request::builder().method(method).uri(uri).body(Body::from(body.to_string())).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_submit_poll_and_stream() {
+        let app = app(0);
+        let (status, body) = call(&app, "GET", "/pipelines", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!({ "pipelines": ["jobs"] }));
+
+        let (status, body) = call(&app, "POST", "/pipelines/jobs/runs?wait=true", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let run: RunInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(run.state, RunState::Succeeded);
+
+        let (status, body) = call(&app, "GET", &format!("/runs/{}", run.run_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<RunInfo>(&body).unwrap().run_id, run.run_id);
+
+        // The run is over, so the event stream replays and closes
+        let (status, body) = call(&app, "GET", &format!("/runs/{}/events", run.run_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("id: 1\n"));
+        assert!(body.contains(&run.run_id));
+
+        let (status, _) = call(&app, "GET", "/runs/missing", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&app, "POST", "/pipelines/jobs/runs", "{not json").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_run() {
+        let app = app(5_000);
+        let (status, body) = call(&app, "POST", "/pipelines/jobs/runs", "").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let run: RunInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(run.state, RunState::Running);
+
+        let cancel = format!("/runs/{}/cancel", run.run_id);
+        let (status, body) = call(&app, "POST", &cancel, r#"{"reason": "operator"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!({ "cancelled": true }));
+
+        // Reading the events to the end waits for the cancelled run to finish
+        let (status, _) = call(&app, "GET", &format!("/runs/{}/events", run.run_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = call(&app, "GET", &format!("/runs/{}", run.run_id), "").await;
+        let run: RunInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(run.state, RunState::Cancelled);
+        assert!(run.error.unwrap().contains("operator"));
+    }
+}
@@ -3,10 +3,17 @@
 //! Stages are the fundamental units of work in a stageflow pipeline.
 
 mod ports;
+mod registry;
 mod result;
+mod tool_call;
 
 pub use ports::{AudioPorts, CorePorts, LLMPorts, StagePorts};
+pub use registry::{
+    clear_stage_registry, get_stage_registry, register_stage, StageConfig, StageFactory, StageFactoryError,
+    StageRegistry,
+};
 pub use result::{LegacyStageStatus, StageError, StageResult};
+pub use tool_call::{ToolCallFormat, ToolCallStage};
 
 use crate::context::StageContext;
 use crate::core::StageOutput;
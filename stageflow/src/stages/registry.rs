@@ -0,0 +1,227 @@
+//! Named stage factories for building pipelines from configuration.
+
+use super::Stage;
+use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// What a [`StageFactory`] is given to build a stage.
+///
+/// `secret://` references in `config` are passed through unresolved;
+/// stages resolve them when they run with
+/// [`StageContext::resolve_secrets`](crate::context::StageContext::resolve_secrets),
+/// so secret values never sit in a built pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageConfig {
+    /// Name of the stage being built.
+    pub name: String,
+    /// The stage's configuration blob.
+    pub config: serde_json::Value,
+}
+
+impl StageConfig {
+    /// Creates a stage configuration.
+    #[must_use]
+    pub fn new(name: impl Into<String>, config: serde_json::Value) -> Self {
+        Self {
+            name: name.into(),
+            config,
+        }
+    }
+
+    /// Deserializes the configuration blob.
+    ///
+    /// A null blob deserializes as an empty object, so config structs whose
+    /// fields all have defaults accept a stage with no configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StageFactoryError::InvalidConfig`] if the blob does not
+    /// match `T`.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, StageFactoryError> {
+        let config = if self.config.is_null() {
+            serde_json::Value::Object(serde_json::Map::new())
+        } else {
+            self.config.clone()
+        };
+        serde_json::from_value(config).map_err(|e| self.invalid(e.to_string()))
+    }
+
+    /// An [`InvalidConfig`](StageFactoryError::InvalidConfig) error for this stage.
+    #[must_use]
+    pub fn invalid(&self, message: impl Into<String>) -> StageFactoryError {
+        StageFactoryError::InvalidConfig {
+            stage: self.name.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Error raised when a stage cannot be built from configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StageFactoryError {
+    /// No factory is registered under the stage type.
+    #[error("Unknown stage type '{0}'")]
+    UnknownType(String),
+
+    /// The factory rejected the stage's configuration.
+    #[error("Invalid config for stage '{stage}': {message}")]
+    InvalidConfig {
+        /// Name of the stage.
+        stage: String,
+        /// Why the configuration was rejected.
+        message: String,
+    },
+}
+
+/// Factory function type for building stages from configuration.
+pub type StageFactory = Arc<dyn Fn(&StageConfig) -> Result<Arc<dyn Stage>, StageFactoryError> + Send + Sync>;
+
+/// Registry of stage factories keyed by stage type.
+///
+/// Manifests name a stage type and give a configuration blob; the registry
+/// turns the pair into a stage. Crates register their stages in the global
+/// registry with [`register_stage`].
+#[derive(Default)]
+pub struct StageRegistry {
+    factories: RwLock<HashMap<String, StageFactory>>,
+}
+
+impl StageRegistry {
+    /// Creates a new empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a factory, replacing any previous one for `stage_type`.
+    pub fn register<F>(&self, stage_type: impl Into<String>, factory: F)
+    where
+        F: Fn(&StageConfig) -> Result<Arc<dyn Stage>, StageFactoryError> + Send + Sync + 'static,
+    {
+        self.factories.write().insert(stage_type.into(), Arc::new(factory));
+    }
+
+    /// Removes the factory for `stage_type`, returning whether one existed.
+    pub fn unregister(&self, stage_type: &str) -> bool {
+        self.factories.write().remove(stage_type).is_some()
+    }
+
+    /// Checks if a factory is registered for `stage_type`.
+    #[must_use]
+    pub fn contains(&self, stage_type: &str) -> bool {
+        self.factories.read().contains_key(stage_type)
+    }
+
+    /// Returns the registered stage types, sorted.
+    #[must_use]
+    pub fn stage_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = self.factories.read().keys().cloned().collect();
+        types.sort();
+        types
+    }
+
+    /// Builds a stage of `stage_type` from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StageFactoryError::UnknownType`] if no factory is registered,
+    /// or whatever the factory returns.
+    pub fn create(&self, stage_type: &str, config: &StageConfig) -> Result<Arc<dyn Stage>, StageFactoryError> {
+        // Release the lock before running the factory so it may use the registry
+        let factory = self
+            .factories
+            .read()
+            .get(stage_type)
+            .cloned()
+            .ok_or_else(|| StageFactoryError::UnknownType(stage_type.to_string()))?;
+        factory(config)
+    }
+
+    /// Clears all registered factories.
+    pub fn clear(&self) {
+        self.factories.write().clear();
+    }
+}
+
+impl std::fmt::Debug for StageRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StageRegistry")
+            .field("stage_types", &self.stage_types())
+            .finish()
+    }
+}
+
+// Global registry
+static GLOBAL_REGISTRY: RwLock<Option<Arc<StageRegistry>>> = RwLock::new(None);
+
+/// Gets the global stage registry.
+pub fn get_stage_registry() -> Arc<StageRegistry> {
+    let read = GLOBAL_REGISTRY.read();
+    if let Some(ref registry) = *read {
+        return registry.clone();
+    }
+    drop(read);
+
+    let mut write = GLOBAL_REGISTRY.write();
+    write.get_or_insert_with(|| Arc::new(StageRegistry::new())).clone()
+}
+
+/// Clears the global stage registry.
+pub fn clear_stage_registry() {
+    *GLOBAL_REGISTRY.write() = None;
+}
+
+/// Registers a stage factory in the global registry.
+pub fn register_stage<F>(stage_type: impl Into<String>, factory: F)
+where
+    F: Fn(&StageConfig) -> Result<Arc<dyn Stage>, StageFactoryError> + Send + Sync + 'static,
+{
+    get_stage_registry().register(stage_type, factory);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stages::NoOpStage;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct NoOpConfig {
+        #[serde(default)]
+        label: Option<String>,
+    }
+
+    fn noop_factory(cfg: &StageConfig) -> Result<Arc<dyn Stage>, StageFactoryError> {
+        let config: NoOpConfig = cfg.parse()?;
+        let name = config.label.unwrap_or_else(|| cfg.name.clone());
+        Ok(Arc::new(NoOpStage::new(name)))
+    }
+
+    #[test]
+    fn test_create_from_config() {
+        let registry = StageRegistry::new();
+        registry.register("noop", noop_factory);
+        assert!(registry.contains("noop"));
+        assert_eq!(registry.stage_types(), vec!["noop".to_string()]);
+
+        let stage = registry.create("noop", &StageConfig::new("a", serde_json::Value::Null)).unwrap();
+        assert_eq!(stage.name(), "a");
+        let stage = registry
+            .create("noop", &StageConfig::new("a", serde_json::json!({ "label": "b" })))
+            .unwrap();
+        assert_eq!(stage.name(), "b");
+
+        let err = registry
+            .create("noop", &StageConfig::new("a", serde_json::json!({ "label": 3 })))
+            .unwrap_err();
+        assert!(matches!(err, StageFactoryError::InvalidConfig { ref stage, .. } if stage == "a"));
+        let err = registry.create("fetch", &StageConfig::new("a", serde_json::Value::Null)).unwrap_err();
+        assert_eq!(err, StageFactoryError::UnknownType("fetch".to_string()));
+
+        assert!(registry.unregister("noop"));
+        assert!(!registry.contains("noop"));
+    }
+}
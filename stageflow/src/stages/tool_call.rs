@@ -0,0 +1,471 @@
+//! Stage that executes tool calls produced by upstream stages.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Stage;
+use crate::context::{ExecutionContext, StageContext};
+use crate::core::StageOutput;
+use crate::errors::ToolError;
+use crate::tools::{
+    get_approval_service, get_undo_store, AdvancedToolExecutor, ApprovalService,
+    ResolvedToolCall, ToolInput, ToolOutput, ToolRegistry, ToolTranscript, UndoStore,
+    UnresolvedToolCall,
+};
+use crate::utils::generate_uuid;
+use uuid::Uuid;
+
+/// Input key searched upstream for raw tool calls when none is configured.
+const TOOL_CALLS_KEY: &str = "tool_calls";
+
+/// Field names used to read raw tool calls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCallFormat {
+    /// Field holding the call ID.
+    pub id_field: String,
+    /// Object wrapping name and arguments, if any.
+    pub function_wrapper: Option<String>,
+    /// Field holding the tool name.
+    pub name_field: String,
+    /// Field holding the arguments, as an object or a JSON string.
+    pub arguments_field: String,
+}
+
+impl Default for ToolCallFormat {
+    /// The `OpenAI` format: `{"id", "function": {"name", "arguments"}}`.
+    fn default() -> Self {
+        Self {
+            id_field: "id".to_string(),
+            function_wrapper: Some("function".to_string()),
+            name_field: "name".to_string(),
+            arguments_field: "arguments".to_string(),
+        }
+    }
+}
+
+impl ToolCallFormat {
+    /// A flat format: `{"id", "name", "arguments"}` without a wrapper.
+    #[must_use]
+    pub fn flat() -> Self {
+        Self {
+            function_wrapper: None,
+            ..Self::default()
+        }
+    }
+}
+
+/// Executes raw tool calls from stage inputs through [`AdvancedToolExecutor`].
+///
+/// Calls are read from the configured input, or else from the first
+/// upstream output with a `tool_calls` key, and resolved against the tool
+/// registry set with [`ToolCallStage::with_registry`], or else the run's
+/// [`tool_registry`](StageContext::tool_registry). Resolved calls run concurrently via
+/// [`AdvancedToolExecutor::execute_many`] with behavior gating, approval,
+/// undo metadata storage, events, and transcript recording; the context's
+/// execution mode is passed as the behavior. Results keep the call order.
+///
+/// Output data:
+/// - `tool_results`: one dict per resolved call with `call_id`, `tool`,
+///   `action_id`, `status`, `approval`, and the [`ToolOutput`] fields
+///   (`success`, `data`, `error`, ...)
+/// - `unresolved`: calls that could not be parsed or matched to a tool
+/// - `succeeded` / `failed`: counts over all calls, unresolved included
+///
+/// The stage succeeds even when calls fail, so the results can be fed back
+/// to a model, unless [`ToolCallStage::fail_on_error`] is set.
+///
+pub struct ToolCallStage {
+    name: String,
+    input: Option<(String, String)>,
+    format: ToolCallFormat,
+    registry: Option<Arc<ToolRegistry>>,
+    approval_service: Option<Arc<ApprovalService>>,
+    undo_store: Option<Arc<UndoStore>>,
+    approval_timeout: Option<Duration>,
+    max_concurrency: Option<usize>,
+    fail_on_error: bool,
+}
+
+impl std::fmt::Debug for ToolCallStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolCallStage")
+            .field("name", &self.name)
+            .field("input", &self.input)
+            .field("format", &self.format)
+            .field("fail_on_error", &self.fail_on_error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ToolCallStage {
+    /// Creates a tool call stage using the run's tool registry and the
+    /// global approval service and undo store.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            input: None,
+            format: ToolCallFormat::default(),
+            registry: None,
+            approval_service: None,
+            undo_store: None,
+            approval_timeout: None,
+            max_concurrency: None,
+            fail_on_error: false,
+        }
+    }
+
+    /// Reads tool calls from `key` in the output of `stage`.
+    #[must_use]
+    pub fn with_input(mut self, stage: impl Into<String>, key: impl Into<String>) -> Self {
+        self.input = Some((stage.into(), key.into()));
+        self
+    }
+
+    /// Sets the raw tool call format.
+    #[must_use]
+    pub fn with_format(mut self, format: ToolCallFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Resolves and executes tools from `registry` instead of the run's.
+    #[must_use]
+    pub fn with_registry(mut self, registry: Arc<ToolRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Sets the approval service instead of the global one.
+    #[must_use]
+    pub fn with_approval_service(mut self, service: Arc<ApprovalService>) -> Self {
+        self.approval_service = Some(service);
+        self
+    }
+
+    /// Sets the undo store instead of the global one.
+    #[must_use]
+    pub fn with_undo_store(mut self, store: Arc<UndoStore>) -> Self {
+        self.undo_store = Some(store);
+        self
+    }
+
+    /// Sets the approval timeout.
+    #[must_use]
+    pub fn with_approval_timeout(mut self, timeout: Duration) -> Self {
+        self.approval_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many calls run at once.
+    #[must_use]
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = Some(max);
+        self
+    }
+
+    /// Fails the stage if any call is unresolved or fails.
+    #[must_use]
+    pub fn fail_on_error(mut self) -> Self {
+        self.fail_on_error = true;
+        self
+    }
+
+    fn executor(&self, registry: Arc<ToolRegistry>) -> AdvancedToolExecutor {
+        let executor = AdvancedToolExecutor::new(
+            registry,
+            self.approval_service
+                .clone()
+                .unwrap_or_else(get_approval_service),
+            self.undo_store.clone().unwrap_or_else(get_undo_store),
+        );
+        let executor = match self.approval_timeout {
+            Some(timeout) => executor.with_approval_timeout(timeout),
+            None => executor,
+        };
+        match self.max_concurrency {
+            Some(max) => executor.with_max_concurrency(max),
+            None => executor,
+        }
+    }
+
+    fn tool_input(call: &ResolvedToolCall, ctx: &StageContext) -> ToolInput {
+        let behavior = Some(ctx.execution_mode().to_string()).filter(|m| !m.is_empty());
+        ToolInput::from_action(
+            generate_uuid(),
+            &call.name,
+            call.arguments.clone(),
+            behavior,
+            ctx.pipeline_run_id(),
+            ctx.request_id(),
+        )
+    }
+}
+
+/// Builds the output entry for one executed call.
+fn result_entry(
+    call: &ResolvedToolCall,
+    action_id: Uuid,
+    result: Result<ToolOutput, ToolError>,
+    transcript: &ToolTranscript,
+) -> HashMap<String, serde_json::Value> {
+    let mut entry = match result {
+        Ok(output) => output.to_dict(),
+        Err(e) => HashMap::from([
+            ("success".to_string(), serde_json::json!(false)),
+            ("error".to_string(), serde_json::json!(e.to_string())),
+        ]),
+    };
+    entry.insert("call_id".to_string(), serde_json::json!(call.id));
+    entry.insert("tool".to_string(), serde_json::json!(call.name));
+    entry.insert(
+        "action_id".to_string(),
+        serde_json::json!(action_id.to_string()),
+    );
+    if let Some(record) = transcript.calls.iter().find(|r| r.action_id == action_id) {
+        entry.insert("status".to_string(), serde_json::json!(record.status));
+        entry.insert("approval".to_string(), serde_json::json!(record.approval));
+    }
+    entry
+}
+
+#[async_trait]
+impl Stage for ToolCallStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let calls = match &self.input {
+            Some((stage, key)) => match ctx.inputs().get_value(stage, key) {
+                Ok(calls) => calls,
+                Err(e) => return StageOutput::fail(e.to_string()),
+            },
+            None => ctx.inputs().find_value(TOOL_CALLS_KEY),
+        };
+        let calls: Vec<serde_json::Value> = match calls {
+            Some(serde_json::Value::Array(calls)) => calls.clone(),
+            Some(call @ serde_json::Value::Object(_)) => vec![call.clone()],
+            _ => Vec::new(),
+        };
+        if calls.is_empty() {
+            return StageOutput::skip("No tool calls in stage inputs");
+        }
+
+        let registry = self.registry.clone().unwrap_or_else(|| ctx.tool_registry());
+        let executor = self.executor(registry.clone());
+        let (resolved, unresolved): (Vec<_>, Vec<_>) = registry
+            .parse_and_resolve(
+                &calls,
+                &self.format.id_field,
+                self.format.function_wrapper.as_deref(),
+                &self.format.name_field,
+                &self.format.arguments_field,
+            )
+            .into_iter()
+            .partition(Result::is_ok);
+        let mut unresolved: Vec<UnresolvedToolCall> =
+            unresolved.into_iter().filter_map(Result::err).collect();
+
+        let mut executed = Vec::with_capacity(resolved.len());
+        let mut batch = Vec::with_capacity(resolved.len());
+        for call in resolved.into_iter().filter_map(Result::ok) {
+            // The registry may have been cleared since resolution
+            let Some(tool) = registry.get_tool(&call.name) else {
+                unresolved.push(UnresolvedToolCall {
+                    error: format!("No tool registered for action type '{}'", call.name),
+                    id: Some(call.id),
+                    name: Some(call.name),
+                    raw: call.raw,
+                });
+                continue;
+            };
+            let input = Self::tool_input(&call, ctx);
+            executed.push((call, input.action_id));
+            batch.push((input, tool.definition()));
+        }
+
+        let outcomes = executor.execute_many(batch, ctx).await;
+        let transcript = ctx.tool_transcript();
+        let results: Vec<HashMap<String, serde_json::Value>> = executed
+            .iter()
+            .zip(outcomes)
+            .map(|((call, action_id), result)| result_entry(call, *action_id, result, &transcript))
+            .collect();
+
+        let succeeded = results
+            .iter()
+            .filter(|r| r.get("success") == Some(&serde_json::json!(true)))
+            .count();
+        let failed = calls.len() - succeeded;
+
+        let mut data = HashMap::new();
+        data.insert("tool_results".to_string(), serde_json::json!(results));
+        data.insert("unresolved".to_string(), serde_json::json!(unresolved));
+        data.insert("succeeded".to_string(), serde_json::json!(succeeded));
+        data.insert("failed".to_string(), serde_json::json!(failed));
+
+        if self.fail_on_error && failed > 0 {
+            return StageOutput::fail(format!("{failed} of {} tool calls failed", calls.len()))
+                .with_data(data);
+        }
+        StageOutput::ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageInputs};
+    use crate::core::StageStatus;
+    use crate::tools::{Tool, ToolDefinition};
+    use serde_json::json;
+
+    struct NoteTool {
+        definition: ToolDefinition,
+    }
+
+    #[async_trait]
+    impl Tool for NoteTool {
+        fn action_type(&self) -> &str {
+            &self.definition.action_type
+        }
+
+        fn name(&self) -> &str {
+            &self.definition.name
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            self.definition.clone()
+        }
+
+        async fn execute(&self, input: ToolInput) -> Result<ToolOutput, ToolError> {
+            let text = input.payload["text"].clone();
+            Ok(ToolOutput::ok_with_undo(
+                Some(json!({"saved": text})),
+                json!({"delete": text}),
+            ))
+        }
+    }
+
+    fn registry() -> Arc<ToolRegistry> {
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register(Box::new(NoteTool {
+            definition: ToolDefinition::new("note", "save_note").undoable(),
+        }));
+        registry.register(Box::new(NoteTool {
+            definition: ToolDefinition::new("wipe", "wipe_notes")
+                .requires_approval_with_message("Delete all notes?"),
+        }));
+        registry
+    }
+
+    fn context(calls: serde_json::Value) -> StageContext {
+        let outputs = HashMap::from([(
+            "llm".to_string(),
+            HashMap::from([("tool_calls".to_string(), calls)]),
+        )]);
+        StageContext::new(
+            Arc::new(PipelineContext::new(RunIdentity::new())),
+            "tools",
+            StageInputs::permissive(outputs, "tools"),
+            ContextSnapshot::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_executes_resolved_calls_and_reports_unresolved() {
+        let undo_store = Arc::new(UndoStore::default());
+        let stage = ToolCallStage::new("tools")
+            .with_registry(registry())
+            .with_approval_service(Arc::new(ApprovalService::new()))
+            .with_undo_store(undo_store.clone())
+            .with_approval_timeout(Duration::from_millis(20));
+        let ctx = context(json!([
+            {"id": "call_1", "function": {"name": "save_note", "arguments": "{\"text\": \"hi\"}"}},
+            {"id": "call_2", "function": {"name": "wipe_notes", "arguments": {}}},
+            {"id": "call_3", "function": {"name": "launch_rocket", "arguments": "{}"}},
+        ]));
+
+        let output = stage.execute(&ctx).await;
+        assert!(output.is_success());
+        assert_eq!(output.get("succeeded"), Some(&json!(1)));
+        assert_eq!(output.get("failed"), Some(&json!(2)));
+
+        let results = output.get("tool_results").unwrap().as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["call_id"], "call_1");
+        assert_eq!(results[0]["data"], json!({"saved": "hi"}));
+        assert_eq!(results[0]["status"], "completed");
+        assert_eq!(undo_store.len(), 1);
+
+        assert_eq!(results[1]["status"], "denied");
+        assert_eq!(results[1]["approval"], "timed_out");
+
+        let unresolved = output.get("unresolved").unwrap().as_array().unwrap();
+        assert_eq!(unresolved[0]["id"], "call_3");
+        assert!(unresolved[0]["error"]
+            .as_str()
+            .unwrap()
+            .contains("No tool registered"));
+
+        assert_eq!(ctx.tool_transcript().calls_by_stage("tools").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolves_tools_from_run_registry() {
+        let user_tools = Arc::new(ToolRegistry::overlay(registry()));
+        user_tools.register(Box::new(NoteTool {
+            definition: ToolDefinition::new("crm", "update_crm"),
+        }));
+        let outputs = HashMap::from([(
+            "llm".to_string(),
+            HashMap::from([(
+                "tool_calls".to_string(),
+                json!([
+                    {"id": "a", "function": {"name": "update_crm", "arguments": {}}},
+                    {"id": "b", "function": {"name": "save_note", "arguments": {"text": "x"}}},
+                ]),
+            )]),
+        )]);
+        let ctx = StageContext::new(
+            Arc::new(PipelineContext::new(RunIdentity::new()).with_tool_registry(user_tools)),
+            "tools",
+            StageInputs::permissive(outputs, "tools"),
+            ContextSnapshot::new(),
+        );
+
+        let output = ToolCallStage::new("tools")
+            .with_undo_store(Arc::new(UndoStore::default()))
+            .execute(&ctx)
+            .await;
+        assert_eq!(output.get("succeeded"), Some(&json!(2)));
+        assert!(!crate::tools::get_tool_registry().can_execute("update_crm"));
+    }
+
+    #[tokio::test]
+    async fn test_flat_format_and_fail_on_error() {
+        let stage = ToolCallStage::new("tools")
+            .with_registry(registry())
+            .with_format(ToolCallFormat::flat())
+            .fail_on_error();
+
+        let ok = stage
+            .execute(&context(
+                json!({"id": "a", "name": "save_note", "arguments": {"text": "x"}}),
+            ))
+            .await;
+        assert!(ok.is_success());
+
+        let failed = stage
+            .execute(&context(json!([{"id": "b", "name": "missing"}])))
+            .await;
+        assert_eq!(failed.status, StageStatus::Fail);
+        assert_eq!(failed.get("failed"), Some(&json!(1)));
+
+        let skipped = stage.execute(&context(json!([]))).await;
+        assert_eq!(skipped.status, StageStatus::Skip);
+    }
+}
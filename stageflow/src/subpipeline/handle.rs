@@ -0,0 +1,213 @@
+//! Handles to subpipelines running in the background.
+
+use super::{ChildRunTracker, SubpipelineResult};
+use crate::context::{ExecutionContext, PipelineContext};
+use crate::errors::StageflowError;
+use futures::FutureExt;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// A subpipeline started with
+/// [`SubpipelineSpawner::spawn_detached`](super::SubpipelineSpawner::spawn_detached).
+///
+/// Like a [`JoinHandle`], the handle hands out the child's result once.
+/// Dropping it does not stop the child; call [`abort`](Self::abort) for that.
+pub struct SubpipelineHandle {
+    child_run_id: Uuid,
+    child_ctx: Arc<PipelineContext>,
+    tracker: Arc<ChildRunTracker>,
+    task: JoinHandle<Result<SubpipelineResult, StageflowError>>,
+    taken: bool,
+}
+
+impl SubpipelineHandle {
+    pub(crate) fn new(
+        child_run_id: Uuid,
+        child_ctx: Arc<PipelineContext>,
+        tracker: Arc<ChildRunTracker>,
+        task: JoinHandle<Result<SubpipelineResult, StageflowError>>,
+    ) -> Self {
+        Self {
+            child_run_id,
+            child_ctx,
+            tracker,
+            task,
+            taken: false,
+        }
+    }
+
+    /// Returns the child's pipeline run ID.
+    #[must_use]
+    pub fn child_run_id(&self) -> Uuid {
+        self.child_run_id
+    }
+
+    /// Returns the context the child runs with.
+    #[must_use]
+    pub fn context(&self) -> &Arc<PipelineContext> {
+        &self.child_ctx
+    }
+
+    /// Returns true once the child has stopped running.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the child to finish, giving up after `timeout` if one is
+    /// given.
+    ///
+    /// A timed-out child keeps running and can be awaited again.
+    ///
+    /// # Errors
+    ///
+    /// Returns the child's own error, a [`StageflowError::Cancelled`] if it
+    /// was aborted, or a [`StageflowError::Internal`] if it timed out,
+    /// panicked, or its result was already taken.
+    pub async fn await_result(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<SubpipelineResult, StageflowError> {
+        if self.taken {
+            return Err(self.already_taken());
+        }
+        let joined = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut self.task).await.map_err(|_| {
+                StageflowError::Internal(format!(
+                    "Subpipeline {} did not finish within {} ms",
+                    self.child_run_id,
+                    timeout.as_millis()
+                ))
+            })?,
+            None => (&mut self.task).await,
+        };
+        self.taken = true;
+        self.flatten(joined)
+    }
+
+    /// Returns the child's result if it has finished, without waiting.
+    ///
+    /// Returns `None` while the child is running and after the result has
+    /// been taken.
+    pub fn try_poll(&mut self) -> Option<Result<SubpipelineResult, StageflowError>> {
+        if self.taken || !self.task.is_finished() {
+            return None;
+        }
+        let joined = (&mut self.task).now_or_never()?;
+        self.taken = true;
+        Some(self.flatten(joined))
+    }
+
+    /// Cancels the child and stops its task.
+    ///
+    /// The child's context is marked cancelled so stages holding it observe
+    /// the cancellation, and the child is unregistered from its tracker.
+    pub fn abort(&self) {
+        if self.task.is_finished() {
+            return;
+        }
+        self.child_ctx.mark_cancelled_with_reason("Aborted by parent");
+        self.task.abort();
+        if self.tracker.unregister(self.child_run_id).is_some() {
+            if let Some(parent) = self.child_ctx.parent() {
+                parent.try_emit_event(
+                    "pipeline.canceled",
+                    Some(serde_json::json!({
+                        "child_run_id": self.child_run_id.to_string(),
+                        "reason": "Aborted by parent",
+                    })),
+                );
+            }
+        }
+    }
+
+    fn already_taken(&self) -> StageflowError {
+        StageflowError::Internal(format!(
+            "Result of subpipeline {} was already taken",
+            self.child_run_id
+        ))
+    }
+
+    fn flatten(
+        &self,
+        joined: Result<Result<SubpipelineResult, StageflowError>, tokio::task::JoinError>,
+    ) -> Result<SubpipelineResult, StageflowError> {
+        match joined {
+            Ok(result) => result,
+            Err(err) if err.is_cancelled() => Err(StageflowError::Cancelled(format!(
+                "Subpipeline {} was aborted",
+                self.child_run_id
+            ))),
+            Err(err) => Err(StageflowError::Internal(format!(
+                "Subpipeline {} panicked: {err}",
+                self.child_run_id
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Debug for SubpipelineHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubpipelineHandle")
+            .field("child_run_id", &self.child_run_id)
+            .field("finished", &self.is_finished())
+            .field("taken", &self.taken)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Labelled handles shared between the stages that spawn detached
+/// subpipelines and the stages that join them.
+#[derive(Debug, Default)]
+pub struct SubpipelineHandleSet {
+    handles: Mutex<HashMap<String, SubpipelineHandle>>,
+}
+
+impl SubpipelineHandleSet {
+    /// Creates an empty set.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a handle under `label`, returning any handle it replaces.
+    pub fn insert(&self, label: impl Into<String>, handle: SubpipelineHandle) -> Option<SubpipelineHandle> {
+        self.handles.lock().insert(label.into(), handle)
+    }
+
+    /// Removes and returns the handle stored under `label`.
+    pub fn remove(&self, label: &str) -> Option<SubpipelineHandle> {
+        self.handles.lock().remove(label)
+    }
+
+    /// Returns the labels of the stored handles, sorted.
+    #[must_use]
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self.handles.lock().keys().cloned().collect();
+        labels.sort();
+        labels
+    }
+
+    /// Aborts every stored child and clears the set.
+    pub fn abort_all(&self) {
+        for (_, handle) in self.handles.lock().drain() {
+            handle.abort();
+        }
+    }
+
+    /// Returns the number of stored handles.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.handles.lock().len()
+    }
+
+    /// Returns true if no handles are stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.handles.lock().is_empty()
+    }
+}
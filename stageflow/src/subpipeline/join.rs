@@ -0,0 +1,120 @@
+//! Stage that joins detached subpipelines.
+
+use super::SubpipelineHandleSet;
+use crate::context::StageContext;
+use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::stages::Stage;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Waits for detached subpipelines and merges their outputs into the
+/// parent's output bag.
+///
+/// Each child stage output is stored under `"<label>.<stage>"`. The stage's
+/// own output maps every joined label to its run ID, success flag and
+/// duration. It fails if a selected child is missing, failed or timed out;
+/// a timed-out child is put back in the set so a later join can retry it.
+#[derive(Debug)]
+pub struct JoinSubpipelinesStage {
+    name: String,
+    handles: Arc<SubpipelineHandleSet>,
+    labels: Option<Vec<String>>,
+    timeout: Option<Duration>,
+}
+
+impl JoinSubpipelinesStage {
+    /// Creates a stage that joins every child in `handles`.
+    #[must_use]
+    pub fn new(name: impl Into<String>, handles: Arc<SubpipelineHandleSet>) -> Self {
+        Self {
+            name: name.into(),
+            handles,
+            labels: None,
+            timeout: None,
+        }
+    }
+
+    /// Joins only the children stored under these labels.
+    #[must_use]
+    pub fn with_children<S: Into<String>>(mut self, labels: impl IntoIterator<Item = S>) -> Self {
+        self.labels = Some(labels.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Limits how long each child is awaited.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+#[async_trait]
+impl Stage for JoinSubpipelinesStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let labels = self.labels.clone().unwrap_or_else(|| self.handles.labels());
+        let mut errors = Vec::new();
+        let mut handles = Vec::new();
+        for label in labels {
+            match self.handles.remove(&label) {
+                Some(handle) => handles.push((label, handle)),
+                None => errors.push(StageflowError::StageExecution(format!(
+                    "No subpipeline labelled '{label}' to join"
+                ))),
+            }
+        }
+
+        let joined = futures::future::join_all(handles.iter_mut().map(|(_, handle)| handle.await_result(self.timeout))).await;
+
+        let outputs = &ctx.pipeline_ctx().outputs;
+        let mut children = serde_json::Map::new();
+        for ((label, handle), result) in handles.iter().zip(joined) {
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    errors.push(StageflowError::StageExecution(format!("{label}: {err}")));
+                    continue;
+                }
+            };
+            for (stage, output) in &result.outputs {
+                if let Err(err) = outputs.set(format!("{label}.{stage}"), output.data_or_empty(), 1, true) {
+                    errors.push(err.into());
+                }
+            }
+            if !result.success {
+                errors.push(StageflowError::StageExecution(format!(
+                    "{label}: {}",
+                    result.error.as_deref().unwrap_or("failed")
+                )));
+            }
+            children.insert(
+                label.clone(),
+                serde_json::json!({
+                    "child_run_id": handle.child_run_id().to_string(),
+                    "success": result.success,
+                    "duration_ms": result.duration_ms,
+                }),
+            );
+        }
+
+        // Put back children that are still running
+        for (label, handle) in handles {
+            if !handle.is_finished() {
+                self.handles.insert(label, handle);
+            }
+        }
+
+        let data = HashMap::from([("children".to_string(), serde_json::Value::Object(children))]);
+        match StageflowError::from_errors(errors) {
+            Some(err) => StageOutput::fail(err.to_string()).with_data(data),
+            None => StageOutput::ok(data),
+        }
+    }
+}
@@ -1,9 +1,15 @@
-//! Subpipeline spawning and management.
+//! Subpipeline spawning and management, including mapping a subpipeline
+//! over a collection with bounded concurrency and joining children started
+//! in the background.
 
+mod handle;
+mod join;
 mod result;
 mod spawner;
 mod tracker;
 
-pub use result::SubpipelineResult;
-pub use spawner::SubpipelineSpawner;
+pub use handle::{SubpipelineHandle, SubpipelineHandleSet};
+pub use join::JoinSubpipelinesStage;
+pub use result::{SubpipelineMapResult, SubpipelineResult};
+pub use spawner::{MapConfig, SubpipelineSpawner, DEFAULT_MAP_CONCURRENCY};
 pub use tracker::{ChildRunInfo, ChildRunTracker};
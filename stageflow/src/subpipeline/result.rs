@@ -1,6 +1,8 @@
 //! Subpipeline execution result.
 
 use crate::core::StageOutput;
+use crate::errors::StageflowError;
+use crate::pipeline::FailureMode;
 use std::collections::HashMap;
 use uuid::Uuid;
 
@@ -78,6 +80,82 @@ impl SubpipelineResult {
     }
 }
 
+/// Aggregated results of mapping a subpipeline over a collection.
+#[derive(Debug, Clone)]
+pub struct SubpipelineMapResult {
+    /// One entry per item, in item order; `None` if the item was never
+    /// started because an earlier failure stopped the map.
+    pub results: Vec<Option<SubpipelineResult>>,
+    /// The failure mode the map ran under.
+    pub failure_mode: FailureMode,
+    /// Whether the map succeeded under its failure mode.
+    ///
+    /// Best-effort maps succeed if any child succeeded; the other modes
+    /// require every item to succeed.
+    pub success: bool,
+    /// Total duration in milliseconds.
+    pub duration_ms: f64,
+}
+
+impl SubpipelineMapResult {
+    /// Creates a result, deriving `success` from the failure mode.
+    #[must_use]
+    pub fn new(results: Vec<Option<SubpipelineResult>>, failure_mode: FailureMode, duration_ms: f64) -> Self {
+        let succeeded = |r: &Option<SubpipelineResult>| r.as_ref().is_some_and(|r| r.success);
+        let success = match failure_mode {
+            FailureMode::BestEffort => results.is_empty() || results.iter().any(succeeded),
+            FailureMode::FailFast | FailureMode::ContinueOnFailure => results.iter().all(succeeded),
+        };
+        Self {
+            results,
+            failure_mode,
+            success,
+            duration_ms,
+        }
+    }
+
+    /// Returns the successful child results.
+    pub fn succeeded(&self) -> impl Iterator<Item = &SubpipelineResult> {
+        self.results.iter().flatten().filter(|r| r.success)
+    }
+
+    /// Returns the failed child results.
+    pub fn failed(&self) -> impl Iterator<Item = &SubpipelineResult> {
+        self.results.iter().flatten().filter(|r| !r.success)
+    }
+
+    /// Returns the indices of items whose child failed.
+    #[must_use]
+    pub fn failed_indices(&self) -> Vec<usize> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.as_ref().is_some_and(|r| !r.success))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns every child failure as one error, each labelled with its
+    /// item index, or `None` if no child failed.
+    #[must_use]
+    pub fn error(&self) -> Option<StageflowError> {
+        StageflowError::from_errors(self.results.iter().enumerate().filter_map(|(index, r)| {
+            let r = r.as_ref().filter(|r| !r.success)?;
+            Some(StageflowError::StageExecution(format!(
+                "item {index} (child run {}): {}",
+                r.child_run_id,
+                r.error.as_deref().unwrap_or("failed")
+            )))
+        }))
+    }
+
+    /// Returns the number of items that were never started.
+    #[must_use]
+    pub fn not_started(&self) -> usize {
+        self.results.iter().filter(|r| r.is_none()).count()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,21 +1,80 @@
 //! Subpipeline spawner with depth enforcement.
 
-use super::{ChildRunInfo, ChildRunTracker, SubpipelineResult};
+use super::{ChildRunInfo, ChildRunTracker, SubpipelineHandle, SubpipelineMapResult, SubpipelineResult};
 use crate::context::{ContextSnapshot, ExecutionContext, PipelineContext, RunIdentity};
 use crate::errors::StageflowError;
-use crate::pipeline::StageGraph;
+use crate::pipeline::{FailureMode, GraphExecutionResult, StageGraph};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use uuid::Uuid;
 
 /// Default maximum subpipeline depth.
 pub const DEFAULT_MAX_DEPTH: u32 = 5;
 
+/// Default number of children [`SubpipelineSpawner::spawn_map`] runs at once.
+pub const DEFAULT_MAP_CONCURRENCY: usize = 4;
+
+/// Options for mapping a subpipeline over a collection.
+#[derive(Debug, Clone)]
+pub struct MapConfig {
+    /// Maximum number of children running at once.
+    pub concurrency: usize,
+    /// How child failures affect the remaining items.
+    pub failure_mode: FailureMode,
+    /// Context key each child receives its item under.
+    pub item_key: String,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_MAP_CONCURRENCY,
+            failure_mode: FailureMode::FailFast,
+            item_key: "item".to_string(),
+        }
+    }
+}
+
+impl MapConfig {
+    /// Creates the default configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of children running at once.
+    #[must_use]
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets how child failures are handled.
+    #[must_use]
+    pub fn with_failure_mode(mut self, mode: FailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
+    /// Sets the context key each child receives its item under.
+    #[must_use]
+    pub fn with_item_key(mut self, key: impl Into<String>) -> Self {
+        self.item_key = key.into();
+        self
+    }
+}
+
 /// Spawner for subpipelines with lifecycle event emission.
+#[derive(Clone)]
 pub struct SubpipelineSpawner {
     /// Maximum allowed depth.
     max_depth: u32,
     /// Child run tracker.
     tracker: Arc<ChildRunTracker>,
+    /// Whether children observe the parent's cancellation.
+    inherit_cancellation: bool,
 }
 
 impl SubpipelineSpawner {
@@ -25,6 +84,7 @@ impl SubpipelineSpawner {
         Self {
             max_depth: DEFAULT_MAX_DEPTH,
             tracker,
+            inherit_cancellation: true,
         }
     }
 
@@ -35,11 +95,23 @@ impl SubpipelineSpawner {
         self
     }
 
+    /// Sets whether children are cancelled along with their parent.
+    ///
+    /// Enabled by default. Detached children can still be stopped with
+    /// [`cancel_children`](Self::cancel_children).
+    #[must_use]
+    pub fn with_inherit_cancellation(mut self, inherit: bool) -> Self {
+        self.inherit_cancellation = inherit;
+        self
+    }
+
     /// Spawns a subpipeline.
     ///
     /// # Errors
     ///
-    /// Returns an error if max depth is exceeded.
+    /// Returns an error if max depth is exceeded, or a
+    /// [`StageflowError::Cancelled`] if the run's budget does not allow
+    /// another nesting level.
     pub async fn spawn(
         &self,
         parent_ctx: &Arc<PipelineContext>,
@@ -47,14 +119,182 @@ impl SubpipelineSpawner {
         snapshot: ContextSnapshot,
         current_depth: u32,
     ) -> Result<SubpipelineResult, StageflowError> {
-        // Check depth
+        self.check_depth(parent_ctx, current_depth)?;
+        let (child_run_id, child_ctx) = self.start_child(parent_ctx, current_depth);
+        let result = graph.execute(child_ctx, snapshot).await;
+        self.finish_child(parent_ctx, child_run_id, result)
+    }
+
+    /// Starts a subpipeline in the background and returns a handle to it.
+    ///
+    /// The child is registered, tracked and reported exactly as with
+    /// [`spawn`](Self::spawn); the parent keeps running and joins it later
+    /// through the handle or a [`JoinSubpipelinesStage`](super::JoinSubpipelinesStage).
+    ///
+    /// # Errors
+    ///
+    /// Returns the same depth and budget errors as [`spawn`](Self::spawn),
+    /// before anything is started.
+    pub fn spawn_detached(
+        &self,
+        parent_ctx: &Arc<PipelineContext>,
+        graph: Arc<StageGraph>,
+        snapshot: ContextSnapshot,
+        current_depth: u32,
+    ) -> Result<SubpipelineHandle, StageflowError> {
+        self.check_depth(parent_ctx, current_depth)?;
+        let (child_run_id, child_ctx) = self.start_child(parent_ctx, current_depth);
+        let spawner = self.clone();
+        let parent = parent_ctx.clone();
+        let ctx = child_ctx.clone();
+        let task = tokio::spawn(async move {
+            let result = graph.execute(ctx, snapshot).await;
+            spawner.finish_child(&parent, child_run_id, result)
+        });
+        Ok(SubpipelineHandle::new(child_run_id, child_ctx, self.tracker.clone(), task))
+    }
+
+    /// Spawns one subpipeline per item of a JSON array stored in the parent
+    /// context under `items_key`.
+    ///
+    /// See [`spawn_map_items`](Self::spawn_map_items).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key does not hold an array, or for the same
+    /// reasons as [`spawn`](Self::spawn).
+    pub async fn spawn_map(
+        &self,
+        parent_ctx: &Arc<PipelineContext>,
+        graph: &StageGraph,
+        items_key: &str,
+        snapshot: ContextSnapshot,
+        current_depth: u32,
+        config: &MapConfig,
+    ) -> Result<SubpipelineMapResult, StageflowError> {
+        let Some(serde_json::Value::Array(items)) = parent_ctx.data.get(items_key) else {
+            return Err(StageflowError::StageExecution(format!(
+                "Context key '{items_key}' does not hold an array to map over"
+            )));
+        };
+        self.spawn_map_items(parent_ctx, graph, items, snapshot, current_depth, config)
+            .await
+    }
+
+    /// Spawns one subpipeline per item, at most `config.concurrency` at a
+    /// time.
+    ///
+    /// Each child context receives its item under `config.item_key` and its
+    /// position under `<item_key>_index`. Results are returned in item order.
+    /// Under [`FailureMode::FailFast`] the first failure cancels running
+    /// children and no further items are started; the other modes run every
+    /// item.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if max depth is exceeded, or a
+    /// [`StageflowError::Cancelled`] if the run's budget does not allow
+    /// another nesting level. Failures of individual children are reported
+    /// in the result instead.
+    pub async fn spawn_map_items(
+        &self,
+        parent_ctx: &Arc<PipelineContext>,
+        graph: &StageGraph,
+        items: Vec<serde_json::Value>,
+        snapshot: ContextSnapshot,
+        current_depth: u32,
+        config: &MapConfig,
+    ) -> Result<SubpipelineMapResult, StageflowError> {
+        self.check_depth(parent_ctx, current_depth)?;
+        let start = Instant::now();
+        let concurrency = config.concurrency.max(1);
+        parent_ctx.try_emit_event(
+            "pipeline.map_started",
+            Some(serde_json::json!({
+                "items": items.len(),
+                "concurrency": concurrency,
+                "failure_mode": format!("{:?}", config.failure_mode),
+            })),
+        );
+
+        let mut results: Vec<Option<SubpipelineResult>> = vec![None; items.len()];
+        let mut pending = items.into_iter().enumerate();
+        let mut running = FuturesUnordered::new();
+        let mut running_ctxs: HashMap<usize, Arc<PipelineContext>> = HashMap::new();
+        let mut stopped = false;
+
+        loop {
+            while !stopped && running.len() < concurrency {
+                let Some((index, item)) = pending.next() else {
+                    break;
+                };
+                let (child_run_id, child_ctx) = self.start_child(parent_ctx, current_depth);
+                let seeded = child_ctx
+                    .data
+                    .set(config.item_key.clone(), item)
+                    .and_then(|()| {
+                        child_ctx
+                            .data
+                            .set(format!("{}_index", config.item_key), serde_json::json!(index))
+                    });
+                running_ctxs.insert(index, child_ctx.clone());
+                let snapshot = snapshot.clone();
+                running.push(async move {
+                    let result = match seeded {
+                        Ok(()) => graph.execute(child_ctx, snapshot).await,
+                        Err(e) => Err(e.into()),
+                    };
+                    (index, child_run_id, result)
+                });
+            }
+
+            let Some((index, child_run_id, result)) = running.next().await else {
+                break;
+            };
+            running_ctxs.remove(&index);
+            let result = self
+                .finish_child(parent_ctx, child_run_id, result)
+                .unwrap_or_else(|e| SubpipelineResult::failure(child_run_id, e.to_string(), HashMap::new(), 0.0));
+
+            if !result.success && config.failure_mode == FailureMode::FailFast && !stopped {
+                stopped = true;
+                for ctx in running_ctxs.values() {
+                    ctx.mark_cancelled_with_reason(format!("Sibling item {index} failed"));
+                }
+            }
+            results[index] = Some(result);
+        }
+
+        let map_result =
+            SubpipelineMapResult::new(results, config.failure_mode, start.elapsed().as_secs_f64() * 1000.0);
+        parent_ctx.try_emit_event(
+            "pipeline.map_completed",
+            Some(serde_json::json!({
+                "succeeded": map_result.succeeded().count(),
+                "failed": map_result.failed().count(),
+                "not_started": map_result.not_started(),
+                "success": map_result.success,
+                "duration_ms": map_result.duration_ms,
+            })),
+        );
+        Ok(map_result)
+    }
+
+    fn check_depth(&self, parent_ctx: &PipelineContext, current_depth: u32) -> Result<(), StageflowError> {
         if current_depth >= self.max_depth {
             return Err(StageflowError::Internal(format!(
                 "Maximum subpipeline depth ({}) exceeded",
                 self.max_depth
             )));
         }
+        if let Err(reason) = parent_ctx.enter_subpipeline(current_depth + 1) {
+            return Err(StageflowError::Cancelled(reason.to_string()));
+        }
+        Ok(())
+    }
 
+    /// Registers a child run and creates its context.
+    fn start_child(&self, parent_ctx: &Arc<PipelineContext>, current_depth: u32) -> (Uuid, Arc<PipelineContext>) {
         let child_run_id = RunIdentity::new();
         let child_pipeline_run_id = child_run_id.pipeline_run_id.unwrap_or_else(Uuid::new_v4);
 
@@ -76,12 +316,22 @@ impl SubpipelineSpawner {
             })),
         );
 
-        // Create child context
-        let child_ctx = parent_ctx.fork_for_subpipeline(child_run_id);
-
-        // Execute child pipeline
-        let result = graph.execute(child_ctx.clone(), snapshot).await;
+        let child_ctx = if self.inherit_cancellation {
+            parent_ctx.fork_for_subpipeline(child_run_id)
+        } else {
+            parent_ctx.fork_detached_for_subpipeline(child_run_id)
+        };
+        self.tracker.register_context(child_pipeline_run_id, child_ctx.clone());
+        (child_pipeline_run_id, child_ctx)
+    }
 
+    /// Unregisters a finished child and reports its outcome to the parent.
+    fn finish_child(
+        &self,
+        parent_ctx: &PipelineContext,
+        child_pipeline_run_id: Uuid,
+        result: Result<GraphExecutionResult, StageflowError>,
+    ) -> Result<SubpipelineResult, StageflowError> {
         // Unregister child
         self.tracker.unregister(child_pipeline_run_id);
 
@@ -134,9 +384,15 @@ impl SubpipelineSpawner {
         }
     }
 
-    /// Cancels all children of a parent.
-    pub fn cancel_children(&self, parent_run_id: Uuid, parent_ctx: &PipelineContext) {
-        let children = self.tracker.children_of(parent_run_id);
+    /// Cancels all descendants of a parent, running their task group
+    /// cleanup.
+    ///
+    /// See [`ChildRunTracker::cancel_all_children`].
+    pub async fn cancel_children(&self, parent_run_id: Uuid, parent_ctx: &PipelineContext) {
+        let children = self
+            .tracker
+            .cancel_all_children(parent_run_id, "Parent cancelled")
+            .await;
 
         for child in children {
             parent_ctx.try_emit_event(
@@ -146,8 +402,6 @@ impl SubpipelineSpawner {
                     "reason": "Parent cancelled",
                 })),
             );
-
-            self.tracker.unregister(child.child_run_id);
         }
     }
 }
@@ -161,6 +415,9 @@ impl Default for SubpipelineSpawner {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::StageOutput;
+    use crate::pipeline::PipelineBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn test_spawner_creation() {
@@ -173,4 +430,138 @@ mod tests {
         let spawner = SubpipelineSpawner::default().with_max_depth(3);
         assert_eq!(spawner.max_depth, 3);
     }
+
+    /// Doubles the mapped item, failing on 3, while tracking concurrency.
+    #[derive(Debug)]
+    struct Doubler {
+        active: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::stages::Stage for Doubler {
+        fn name(&self) -> &'static str {
+            "double"
+        }
+
+        async fn execute(&self, ctx: &crate::context::StageContext) -> StageOutput {
+            let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+
+            let item = ctx.pipeline_ctx().data.get("item").and_then(|v| v.as_i64()).unwrap_or(0);
+            if item == 3 {
+                return StageOutput::fail("three");
+            }
+            StageOutput::ok([("doubled".to_string(), serde_json::json!(item * 2))].into_iter().collect())
+        }
+    }
+
+    fn doubler(active: Arc<AtomicUsize>, peak: Arc<AtomicUsize>) -> StageGraph {
+        PipelineBuilder::new("child")
+            .stage("double", Arc::new(Doubler { active, peak }), &[])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spawn_map_bounds_concurrency_and_keeps_order() {
+        let (active, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let graph = doubler(active, peak.clone());
+        let tracker = Arc::new(ChildRunTracker::new());
+        let spawner = SubpipelineSpawner::new(tracker.clone());
+        let parent = Arc::new(PipelineContext::new(RunIdentity::new()));
+        parent.data.set("numbers", serde_json::json!([1, 2, 3, 4, 5])).unwrap();
+
+        let config = MapConfig::new()
+            .with_concurrency(2)
+            .with_failure_mode(FailureMode::ContinueOnFailure);
+        let result = spawner
+            .spawn_map(&parent, &graph, "numbers", ContextSnapshot::new(), 0, &config)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.failed_indices(), vec![2]);
+        assert!(result.error().unwrap().to_string().starts_with("Stage execution error: item 2 "));
+        assert_eq!(result.succeeded().count(), 4);
+        let doubled = result.results[4].as_ref().unwrap().get_output("double").unwrap();
+        assert_eq!(doubled.data.as_ref().unwrap()["doubled"], 10);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(tracker.len(), 0);
+
+        let best_effort = config.with_failure_mode(FailureMode::BestEffort);
+        let result = spawner
+            .spawn_map(&parent, &graph, "numbers", ContextSnapshot::new(), 0, &best_effort)
+            .await
+            .unwrap();
+        assert!(result.success);
+
+        assert!(spawner
+            .spawn_map(&parent, &graph, "missing", ContextSnapshot::new(), 0, &best_effort)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_map_fail_fast_stops_launching() {
+        let graph = doubler(Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let spawner = SubpipelineSpawner::default();
+        let parent = Arc::new(PipelineContext::new(RunIdentity::new()));
+
+        let items = (1..=6).map(|n| serde_json::json!(n)).collect();
+        let config = MapConfig::new().with_concurrency(1);
+        let result = spawner
+            .spawn_map_items(&parent, &graph, items, ContextSnapshot::new(), 0, &config)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.failed_indices(), vec![2]);
+        assert_eq!(result.not_started(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_detached_children_are_joined_into_parent_outputs() {
+        use crate::context::{StageContext, StageInputs};
+        use crate::stages::Stage;
+        use crate::subpipeline::{JoinSubpipelinesStage, SubpipelineHandleSet};
+        use std::time::Duration;
+
+        let graph = Arc::new(doubler(Arc::default(), Arc::default()));
+        let tracker = Arc::new(ChildRunTracker::new());
+        let spawner = SubpipelineSpawner::new(tracker.clone());
+        let parent = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let handles = Arc::new(SubpipelineHandleSet::new());
+        for label in ["a", "b"] {
+            let handle = spawner.spawn_detached(&parent, graph.clone(), ContextSnapshot::new(), 0).unwrap();
+            handles.insert(label, handle);
+        }
+
+        let slow = PipelineBuilder::new("slow")
+            .stage("wait", Arc::new(crate::testing::SlowStage::with_delay_ms("wait", 5_000)), &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut stuck = spawner.spawn_detached(&parent, Arc::new(slow), ContextSnapshot::new(), 0).unwrap();
+        assert!(stuck.try_poll().is_none());
+        assert!(stuck.await_result(Some(Duration::from_millis(10))).await.is_err());
+        stuck.abort();
+        assert!(matches!(stuck.await_result(None).await, Err(StageflowError::Cancelled(_))));
+        assert!(stuck.context().is_cancelled());
+
+        let join = JoinSubpipelinesStage::new("join", handles.clone()).with_children(["a", "b"]);
+        let stage_ctx = StageContext::new(parent.clone(), "join", StageInputs::default(), ContextSnapshot::new());
+        let output = join.execute(&stage_ctx).await;
+        assert!(output.is_success(), "{:?}", output.error);
+        assert_eq!(parent.outputs.get("a.double").unwrap()["doubled"], 0);
+        assert_eq!(output.get("children").unwrap()["b"]["success"], true);
+        assert!(handles.is_empty());
+        assert!(tracker.is_empty());
+
+        let output = join.execute(&stage_ctx).await;
+        assert!(output.is_failure());
+    }
 }
@@ -1,7 +1,10 @@
 //! Child run tracker for managing subpipeline references.
 
+use crate::cancellation::StructuredTaskGroup;
+use crate::context::PipelineContext;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Information about a child pipeline run.
@@ -18,9 +21,16 @@ pub struct ChildRunInfo {
 }
 
 /// Thread-safe tracker for child pipeline runs.
+///
+/// Besides run metadata, the tracker holds each child's context and a
+/// [`StructuredTaskGroup`] per parent so that
+/// [`cancel_all_children`](Self::cancel_all_children) can stop a parent's
+/// whole subtree and run its cleanup.
 #[derive(Default)]
 pub struct ChildRunTracker {
     children: RwLock<HashMap<Uuid, ChildRunInfo>>,
+    contexts: RwLock<HashMap<Uuid, Arc<PipelineContext>>>,
+    groups: RwLock<HashMap<Uuid, Arc<StructuredTaskGroup>>>,
 }
 
 impl ChildRunTracker {
@@ -35,11 +45,58 @@ impl ChildRunTracker {
         self.children.write().insert(info.child_run_id, info);
     }
 
+    /// Attaches the context a registered child runs with, so it can be
+    /// cancelled through the tracker.
+    pub fn register_context(&self, child_run_id: Uuid, ctx: Arc<PipelineContext>) {
+        self.contexts.write().insert(child_run_id, ctx);
+    }
+
     /// Unregisters a child run.
     pub fn unregister(&self, child_run_id: Uuid) -> Option<ChildRunInfo> {
+        self.contexts.write().remove(&child_run_id);
         self.children.write().remove(&child_run_id)
     }
 
+    /// Returns the task group for a parent's children, creating it if
+    /// needed.
+    ///
+    /// Tasks spawned in the group observe its cancellation token, and its
+    /// cleanup registry runs when the children are cancelled.
+    #[must_use]
+    pub fn task_group(&self, parent_run_id: Uuid) -> Arc<StructuredTaskGroup> {
+        self.groups.write().entry(parent_run_id).or_default().clone()
+    }
+
+    /// Cancels every descendant of a parent and unregisters them.
+    ///
+    /// Child contexts are marked cancelled with `reason`, including children
+    /// forked without inheriting cancellation. The task groups of the parent
+    /// and of each cancelled child are then cancelled and awaited, which
+    /// runs their cleanup. Returns the cancelled children, nearest first.
+    pub async fn cancel_all_children(&self, parent_run_id: Uuid, reason: &str) -> Vec<ChildRunInfo> {
+        let mut cancelled = Vec::new();
+        let mut parents = VecDeque::from([parent_run_id]);
+        let mut groups = Vec::new();
+        while let Some(parent) = parents.pop_front() {
+            groups.extend(self.groups.write().remove(&parent));
+            for child in self.children_of(parent) {
+                if let Some(ctx) = self.contexts.read().get(&child.child_run_id) {
+                    ctx.mark_cancelled_with_reason(reason);
+                }
+                self.unregister(child.child_run_id);
+                parents.push_back(child.child_run_id);
+                cancelled.push(child);
+            }
+        }
+
+        for group in groups {
+            group.cancel_all(reason);
+            // Task errors are expected once cancelled; only cleanup matters here.
+            let _ = group.wait().await;
+        }
+        cancelled
+    }
+
     /// Gets information about a child run.
     #[must_use]
     pub fn get(&self, child_run_id: Uuid) -> Option<ChildRunInfo> {
@@ -72,6 +129,8 @@ impl ChildRunTracker {
     /// Clears all tracked children.
     pub fn clear(&self) {
         self.children.write().clear();
+        self.contexts.write().clear();
+        self.groups.write().clear();
     }
 }
 
@@ -150,4 +209,45 @@ mod tests {
         let children = tracker.children_of(parent_id);
         assert_eq!(children.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_cancel_all_children_cancels_subtree_and_runs_cleanup() {
+        use crate::context::{ExecutionContext, RunIdentity};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let tracker = ChildRunTracker::new();
+        let root = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let (parent_id, child_id, grandchild_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let child = root.fork_detached_for_subpipeline(RunIdentity::new());
+        let grandchild = child.fork_detached_for_subpipeline(RunIdentity::new());
+        for (id, parent, depth, ctx) in [(child_id, parent_id, 1, &child), (grandchild_id, child_id, 2, &grandchild)] {
+            tracker.register(ChildRunInfo {
+                child_run_id: id,
+                parent_run_id: parent,
+                depth,
+                spawned_at: crate::utils::iso_timestamp(),
+            });
+            tracker.register_context(id, ctx.clone());
+        }
+
+        let cleaned = Arc::new(AtomicBool::new(false));
+        let flag = cleaned.clone();
+        let group = tracker.task_group(parent_id);
+        group.cleanup_registry().register(move || flag.store(true, Ordering::SeqCst), Some("close"));
+        group.spawn("worker", |token| async move {
+            while !token.is_cancelled() {
+                tokio::task::yield_now().await;
+            }
+            Ok(())
+        });
+
+        let cancelled = tracker.cancel_all_children(parent_id, "shutdown").await;
+        let ids: Vec<Uuid> = cancelled.iter().map(|info| info.child_run_id).collect();
+        assert_eq!(ids, vec![child_id, grandchild_id]);
+        assert!(child.is_cancelled() && grandchild.is_cancelled());
+        assert_eq!(grandchild.cancel_reason().as_deref(), Some("shutdown"));
+        assert!(cleaned.load(Ordering::SeqCst));
+        assert!(tracker.is_empty());
+        assert!(!root.is_cancelled());
+    }
 }
@@ -1,6 +1,18 @@
-//! Test assertions for stage outputs.
+//! Test assertions for stage outputs and event streams.
+//!
+//! [`assert_events_match_golden`] compares a run's events against a golden
+//! JSON file after [`EventNormalizer`] scrubs the ids, timestamps and
+//! timings that change between runs. Set [`UPDATE_GOLDEN_ENV`] to rewrite
+//! the golden files instead of comparing.
 
 use crate::core::{StageOutput, StageStatus};
+use crate::events::{with_event_sink, CollectingEventSink};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
 /// Asserts that the output indicates success.
 pub fn assert_output_succeeded(output: &StageOutput) {
@@ -85,10 +97,243 @@ pub fn assert_output_not_retryable(output: &StageOutput) {
     );
 }
 
+/// Environment variable that makes [`assert_events_match_golden`] write the
+/// golden file instead of comparing against it.
+pub const UPDATE_GOLDEN_ENV: &str = "STAGEFLOW_UPDATE_GOLDEN";
+
+/// Event fields scrubbed by default because they change between runs.
+pub const DEFAULT_SCRUBBED_EVENT_KEYS: &[&str] = &["emitted_at", "timestamp", "duration_ms", "latency_ms", "elapsed_ms"];
+
+/// An event as collected by [`CollectingEventSink`].
+pub type CollectedEvent = (String, Option<serde_json::Value>);
+
+fn uuid_pattern() -> &'static Regex {
+    static UUID: OnceLock<Regex> = OnceLock::new();
+    UUID.get_or_init(|| {
+        Regex::new(r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b")
+            .expect("built-in UUID pattern is valid")
+    })
+}
+
+fn timestamp_pattern() -> &'static Regex {
+    static TIMESTAMP: OnceLock<Regex> = OnceLock::new();
+    TIMESTAMP.get_or_init(|| {
+        Regex::new(r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?\b")
+            .expect("built-in timestamp pattern is valid")
+    })
+}
+
+/// Turns an event stream into a stable form for golden comparisons.
+///
+/// Normalizing drops the `sequence` number, replaces scrubbed fields with
+/// `"<scrubbed>"`, replaces timestamps in strings with `"<timestamp>"`, and
+/// replaces each distinct UUID with `"<id:N>"`, numbered by first
+/// appearance so events about the same run or action still match up.
+/// Events are then ordered by their `stage` field, keeping emission order
+/// within a stage, so stages running in parallel do not reorder the
+/// stream; events without a stage come first.
+#[derive(Debug, Clone)]
+pub struct EventNormalizer {
+    scrubbed_keys: BTreeSet<String>,
+    ignored_types: Vec<String>,
+    group_by_stage: bool,
+}
+
+impl Default for EventNormalizer {
+    fn default() -> Self {
+        Self {
+            scrubbed_keys: DEFAULT_SCRUBBED_EVENT_KEYS.iter().map(ToString::to_string).collect(),
+            ignored_types: Vec::new(),
+            group_by_stage: true,
+        }
+    }
+}
+
+impl EventNormalizer {
+    /// Creates a normalizer with the default scrubbed keys.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also scrubs the field `key`, at any depth.
+    #[must_use]
+    pub fn with_scrubbed_key(mut self, key: impl Into<String>) -> Self {
+        self.scrubbed_keys.insert(key.into());
+        self
+    }
+
+    /// Drops events whose type starts with `prefix`.
+    #[must_use]
+    pub fn with_ignored_type(mut self, prefix: impl Into<String>) -> Self {
+        self.ignored_types.push(prefix.into());
+        self
+    }
+
+    /// Keeps the events in emission order instead of grouping them by
+    /// stage, for pipelines that run one stage at a time.
+    #[must_use]
+    pub fn in_emitted_order(mut self) -> Self {
+        self.group_by_stage = false;
+        self
+    }
+
+    /// Returns the normalized events as a JSON array of
+    /// `{"type": ..., "data": ...}` objects.
+    #[must_use]
+    pub fn normalize(&self, events: &[CollectedEvent]) -> serde_json::Value {
+        let mut kept: Vec<(String, Option<serde_json::Value>)> = events
+            .iter()
+            .filter(|(event_type, _)| !self.ignored_types.iter().any(|prefix| event_type.starts_with(prefix.as_str())))
+            .cloned()
+            .collect();
+        if self.group_by_stage {
+            let stage = |data: &Option<serde_json::Value>| {
+                data.as_ref()
+                    .and_then(|d| d.get("stage"))
+                    .and_then(serde_json::Value::as_str)
+                    .map(str::to_string)
+            };
+            kept.sort_by_key(|(_, data)| stage(data));
+        }
+        let mut ids = HashMap::new();
+        let normalized = kept
+            .into_iter()
+            .map(|(event_type, data)| {
+                let mut data = data.unwrap_or(serde_json::Value::Null);
+                if let serde_json::Value::Object(map) = &mut data {
+                    map.remove("sequence");
+                }
+                self.scrub(&mut data, &mut ids);
+                serde_json::json!({ "type": event_type, "data": data })
+            })
+            .collect();
+        serde_json::Value::Array(normalized)
+    }
+
+    fn scrub(&self, value: &mut serde_json::Value, ids: &mut HashMap<String, usize>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    if self.scrubbed_keys.contains(key) {
+                        *field = serde_json::json!("<scrubbed>");
+                    } else {
+                        self.scrub(field, ids);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.scrub(item, ids)),
+            serde_json::Value::String(text) => {
+                let replaced = uuid_pattern().replace_all(text, |caps: &regex::Captures<'_>| {
+                    let next = ids.len() + 1;
+                    format!("<id:{}>", ids.entry(caps[0].to_lowercase()).or_insert(next))
+                });
+                *text = timestamp_pattern().replace_all(&replaced, "<timestamp>").into_owned();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Runs `future` with a collecting event sink scoped to it, returning its
+/// output and every event emitted by contexts created inside it.
+pub async fn capture_events<F: Future>(future: F) -> (F::Output, Vec<CollectedEvent>) {
+    let sink = Arc::new(CollectingEventSink::new());
+    let output = with_event_sink(sink.clone(), future).await;
+    (output, sink.events())
+}
+
+/// Asserts that `events`, normalized with the default [`EventNormalizer`],
+/// match the golden file at `path`.
+///
+/// See [`assert_events_match_golden_with`].
+pub fn assert_events_match_golden(events: &[CollectedEvent], path: impl AsRef<Path>) {
+    assert_events_match_golden_with(&EventNormalizer::default(), events, path);
+}
+
+/// Asserts that `events`, normalized with `normalizer`, match the golden
+/// file at `path`.
+///
+/// The golden file is written, with its directory, when it does not exist
+/// or when [`UPDATE_GOLDEN_ENV`] is set, so a new test records its
+/// expected stream on the first run.
+///
+/// # Panics
+///
+/// Panics with a line diff if the events differ from the golden file, or
+/// if the file cannot be read or written.
+pub fn assert_events_match_golden_with(normalizer: &EventNormalizer, events: &[CollectedEvent], path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let actual = serde_json::to_string_pretty(&normalizer.normalize(events)).unwrap_or_default() + "\n";
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() || !path.exists() {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .unwrap_or_else(|e| panic!("Could not create golden directory {}: {e}", parent.display()));
+        }
+        std::fs::write(path, &actual).unwrap_or_else(|e| panic!("Could not write golden file {}: {e}", path.display()));
+        return;
+    }
+    let expected = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Could not read golden file {}: {e}", path.display()));
+    assert!(
+        expected == actual,
+        "Event stream differs from golden file {} (set {UPDATE_GOLDEN_ENV}=1 to update it):\n{}",
+        path.display(),
+        golden_diff(&expected, &actual)
+    );
+}
+
+/// Returns a line diff from `expected` to `actual`, marking removed lines
+/// with `-` and added lines with `+`, with two lines of context around
+/// each change.
+#[must_use]
+pub fn golden_diff(expected: &str, actual: &str) -> String {
+    const CONTEXT: usize = 2;
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(('+', new[j]));
+            j += 1;
+        } else {
+            lines.push(('-', old[i]));
+            i += 1;
+        }
+    }
+
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, (tag, _))| *tag != ' ').map(|(n, _)| n).collect();
+    let mut out = String::new();
+    let mut last_shown: Option<usize> = None;
+    for (n, (tag, line)) in lines.iter().enumerate() {
+        if !changed.iter().any(|&c| c.abs_diff(n) <= CONTEXT) {
+            continue;
+        }
+        if last_shown.is_some_and(|last| n > last + 1) {
+            out.push_str("  ...\n");
+        }
+        let _ = writeln!(out, "{tag} {line}");
+        last_shown = Some(n);
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
 
     #[test]
     fn test_assert_output_succeeded() {
@@ -144,4 +389,73 @@ mod tests {
         let output = StageOutput::fail("error");
         assert_output_not_retryable(&output);
     }
+
+    #[test]
+    fn test_event_normalizer_scrubs_and_groups_by_stage() {
+        let run = "5f0c7c8e-3d4a-4f6b-9a1e-2b3c4d5e6f70";
+        let events = vec![
+            (
+                "stage.started".to_string(),
+                Some(serde_json::json!({"stage": "b", "pipeline_run_id": run, "sequence": 1, "emitted_at": "x"})),
+            ),
+            (
+                "stage.started".to_string(),
+                Some(serde_json::json!({"stage": "a", "pipeline_run_id": run, "sequence": 2})),
+            ),
+            (
+                "stage.completed".to_string(),
+                Some(serde_json::json!({"stage": "b", "duration_ms": 1.5, "note": "at 2026-01-02T03:04:05Z"})),
+            ),
+            ("debug.tick".to_string(), None),
+        ];
+
+        let normalized = EventNormalizer::new().with_ignored_type("debug.").normalize(&events);
+
+        assert_eq!(
+            normalized,
+            serde_json::json!([
+                {"type": "stage.started", "data": {"stage": "a", "pipeline_run_id": "<id:1>"}},
+                {"type": "stage.started", "data": {"stage": "b", "pipeline_run_id": "<id:1>", "emitted_at": "<scrubbed>"}},
+                {"type": "stage.completed", "data": {"stage": "b", "duration_ms": "<scrubbed>", "note": "at <timestamp>"}},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_golden_file_written_then_compared() {
+        use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+        use crate::pipeline::{PipelineBuilder, StageSpec, UnifiedStageGraph};
+        use crate::stages::NoOpStage;
+
+        let mut builder = PipelineBuilder::new("golden");
+        builder.add_stage_spec(StageSpec::new("a", Arc::new(NoOpStage::new("a")))).unwrap();
+        builder.add_stage_spec(StageSpec::new("b", Arc::new(NoOpStage::new("b")))).unwrap();
+        let graph = UnifiedStageGraph::new(builder.build().unwrap());
+        let run = || async {
+            let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+            graph.execute(ctx, ContextSnapshot::new()).await.unwrap()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("golden/run.json");
+        let (_, first) = capture_events(Box::pin(run())).await;
+        assert!(!first.is_empty());
+        assert_events_match_golden(&first, &path);
+        let (_, second) = capture_events(Box::pin(run())).await;
+        assert_events_match_golden(&second, &path);
+    }
+
+    #[test]
+    fn test_golden_mismatch_reports_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.json");
+        assert_events_match_golden(&[("stage.started".to_string(), Some(serde_json::json!({"stage": "a"})))], &path);
+
+        let mismatch = std::panic::catch_unwind(|| {
+            assert_events_match_golden(&[("stage.failed".to_string(), Some(serde_json::json!({"stage": "a"})))], &path);
+        });
+        let message = *mismatch.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("-     \"type\": \"stage.started\""), "{message}");
+        assert!(message.contains("+     \"type\": \"stage.failed\""), "{message}");
+    }
 }
@@ -0,0 +1,206 @@
+//! Pipeline benchmarks for measuring executor overhead.
+//!
+//! [`PipelineBench`] runs a pipeline of no-op stages, so the time measured
+//! is the executor's own scheduling, context and event overhead. It does
+//! not depend on a benchmarking framework: call [`PipelineBench::measure`]
+//! directly, or [`PipelineBench::run_once`] from criterion's `iter_custom`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+use crate::errors::StageflowError;
+use crate::events::{EventSink, NoOpEventSink};
+use crate::pipeline::{Percentiles, PipelineBuilder, StageSpec, UnifiedStageGraph};
+use crate::stages::NoOpStage;
+
+/// How the stages of a [`PipelineBench`] depend on each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchShape {
+    /// Each stage depends on the one before, so stages run one at a time.
+    Linear,
+    /// No stage depends on another, so all stages are ready at once.
+    Wide,
+    /// One root, the other stages but the last depending on it, and the
+    /// last depending on all of them.
+    Diamond,
+}
+
+/// A benchmark pipeline of no-op stages.
+pub struct PipelineBench {
+    shape: BenchShape,
+    stages: usize,
+    graph: UnifiedStageGraph,
+    sink: Arc<dyn EventSink>,
+    snapshot: ContextSnapshot,
+}
+
+impl PipelineBench {
+    /// Builds a benchmark of `stages` stages in the given shape.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `stages` is zero, or is below three for a
+    /// diamond.
+    pub fn new(shape: BenchShape, stages: usize) -> Result<Self, StageflowError> {
+        if shape == BenchShape::Diamond && stages < 3 {
+            return Err(StageflowError::Internal("A diamond benchmark needs at least 3 stages".to_string()));
+        }
+        let name = |i: usize| format!("stage_{i}");
+        let mut builder = PipelineBuilder::new(format!("bench_{shape:?}_{stages}").to_lowercase());
+        for i in 0..stages {
+            let spec = StageSpec::new(name(i), Arc::new(NoOpStage::new(name(i))));
+            let spec = match shape {
+                BenchShape::Linear if i > 0 => spec.with_dependency(name(i - 1)),
+                BenchShape::Diamond if i + 1 == stages => spec.with_dependencies((1..i).map(name)),
+                BenchShape::Diamond if i > 0 => spec.with_dependency(name(0)),
+                _ => spec,
+            };
+            builder.add_stage_spec(spec)?;
+        }
+        Ok(Self {
+            shape,
+            stages,
+            graph: UnifiedStageGraph::new(builder.build()?),
+            sink: Arc::new(NoOpEventSink),
+            snapshot: ContextSnapshot::new(),
+        })
+    }
+
+    /// Builds a linear benchmark of `stages` stages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `stages` is zero.
+    pub fn linear(stages: usize) -> Result<Self, StageflowError> {
+        Self::new(BenchShape::Linear, stages)
+    }
+
+    /// Builds a wide benchmark of `stages` independent stages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `stages` is zero.
+    pub fn wide(stages: usize) -> Result<Self, StageflowError> {
+        Self::new(BenchShape::Wide, stages)
+    }
+
+    /// Sends run events to `sink` instead of discarding them, to include
+    /// the sink's cost in the measurement.
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Starts each run from `snapshot` instead of an empty one, to include
+    /// the cost of handing a large snapshot to every stage.
+    #[must_use]
+    pub fn with_snapshot(mut self, snapshot: ContextSnapshot) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /// Returns the shape.
+    #[must_use]
+    pub fn shape(&self) -> BenchShape {
+        self.shape
+    }
+
+    /// Returns the number of stages.
+    #[must_use]
+    pub fn stage_count(&self) -> usize {
+        self.stages
+    }
+
+    /// Runs the pipeline once and returns how long it took.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run fails, which no-op stages never cause.
+    pub async fn run_once(&self) -> Result<Duration, StageflowError> {
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_event_sink(Arc::clone(&self.sink)));
+        let start = Instant::now();
+        let result = self.graph.execute(ctx, self.snapshot.clone()).await?;
+        let elapsed = start.elapsed();
+        if !result.success {
+            return Err(StageflowError::StageExecution(result.error.unwrap_or_default()));
+        }
+        Ok(elapsed)
+    }
+
+    /// Runs the pipeline `iterations` times, after one warm-up run, and
+    /// summarizes the timings.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first run error.
+    pub async fn measure(&self, iterations: usize) -> Result<BenchReport, StageflowError> {
+        self.run_once().await?;
+        let mut samples = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            samples.push(self.run_once().await?);
+        }
+        Ok(BenchReport::from_samples(self.stages, &samples))
+    }
+}
+
+/// Timings of repeated [`PipelineBench`] runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    /// Number of measured runs.
+    pub iterations: usize,
+    /// Stages per run.
+    pub stages: usize,
+    /// Run durations in milliseconds.
+    pub millis: Percentiles,
+}
+
+impl BenchReport {
+    /// Builds a report from run durations.
+    #[must_use]
+    pub fn from_samples(stages: usize, samples: &[Duration]) -> Self {
+        Self {
+            iterations: samples.len(),
+            stages,
+            millis: Percentiles::from_samples(samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect()),
+        }
+    }
+
+    /// Returns the mean executor overhead per stage, in milliseconds.
+    #[must_use]
+    pub fn per_stage_ms(&self) -> f64 {
+        self.millis.mean / f64::from(u32::try_from(self.stages).unwrap_or(u32::MAX).max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::CollectingEventSink;
+
+    #[tokio::test]
+    async fn test_bench_shapes_run_every_stage() {
+        for shape in [BenchShape::Linear, BenchShape::Wide, BenchShape::Diamond] {
+            let sink = Arc::new(CollectingEventSink::new());
+            let bench = PipelineBench::new(shape, 5).unwrap().with_event_sink(sink.clone());
+            let report = bench.measure(3).await.unwrap();
+
+            assert_eq!(report.iterations, 3);
+            assert_eq!(sink.events_of_type("stage.completed").len(), 4 * 5);
+            assert!(report.millis.min <= report.millis.p95);
+            assert!(report.per_stage_ms() <= report.millis.mean);
+        }
+        assert!(PipelineBench::new(BenchShape::Diamond, 2).is_err());
+    }
+
+    #[test]
+    fn test_report_statistics() {
+        let samples = [Duration::from_millis(30), Duration::from_millis(10), Duration::from_millis(20)];
+        let report = BenchReport::from_samples(2, &samples);
+        assert_eq!(report.iterations, 3);
+        assert!((report.millis.mean - 20.0).abs() < 1e-9);
+        assert!((report.millis.max - 30.0).abs() < 1e-9);
+        assert!((report.per_stage_ms() - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,381 @@
+//! Fault injection for stress-testing pipelines.
+//!
+//! A [`ChaosInterceptor`] injects failures, delays, cancellations and
+//! duplicated retries into stage executions, either at random with
+//! configured probabilities or on a targeted schedule, so failure modes,
+//! retry policies and cleanup can be checked under stress. Engines do not
+//! run interceptors themselves; wrap the stages under test with
+//! [`ChaosInterceptor::wrap`] or [`ChaosStage`].
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::context::{ExecutionContext, StageContext};
+use crate::core::StageOutput;
+use crate::interceptors::Interceptor;
+use crate::pipeline::StageSpec;
+use crate::stages::Stage;
+
+/// A fault injected into a stage execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// The stage fails permanently without running.
+    Fail,
+    /// The stage asks to be retried without running.
+    Transient,
+    /// The stage runs after a delay.
+    Delay(Duration),
+    /// The stage cancels the pipeline without running.
+    Cancel,
+    /// The stage runs, then its successful output is replaced by a retry
+    /// request, so it runs again as after a lost acknowledgment.
+    DuplicateRetry,
+}
+
+impl Fault {
+    /// Returns the fault name reported in events.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fail => "fail",
+            Self::Transient => "transient",
+            Self::Delay(_) => "delay",
+            Self::Cancel => "cancel",
+            Self::DuplicateRetry => "duplicate_retry",
+        }
+    }
+}
+
+/// A fault the interceptor injected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedFault {
+    /// The stage it was injected into.
+    pub stage: String,
+    /// The stage's execution count when it was injected, from 1.
+    pub attempt: u32,
+    /// The fault.
+    pub fault: Fault,
+}
+
+/// Interceptor that injects faults into stage executions.
+///
+/// Each execution first consults the schedule, keyed by stage and attempt;
+/// without a scheduled fault, one random draw picks at most one of the
+/// faults given a rate, in the order they were added. Attempts count every
+/// execution of a stage through this interceptor, from 1. Seed the
+/// interceptor with [`with_seed`](Self::with_seed) for reproducible runs.
+pub struct ChaosInterceptor {
+    rates: Vec<(Fault, f64)>,
+    schedule: HashMap<(String, u32), Fault>,
+    targets: Option<HashSet<String>>,
+    rng: Mutex<StdRng>,
+    attempts: Mutex<HashMap<String, u32>>,
+    pending_duplicates: Mutex<HashSet<String>>,
+    injected: Mutex<Vec<InjectedFault>>,
+}
+
+impl ChaosInterceptor {
+    /// Creates an interceptor that injects nothing until configured.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            rates: Vec::new(),
+            schedule: HashMap::new(),
+            targets: None,
+            rng: Mutex::new(StdRng::from_entropy()),
+            attempts: Mutex::new(HashMap::new()),
+            pending_duplicates: Mutex::new(HashSet::new()),
+            injected: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Seeds the random draws.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Injects `fault` into this fraction of executions.
+    #[must_use]
+    pub fn with_rate(mut self, fault: Fault, rate: f64) -> Self {
+        self.rates.push((fault, rate.clamp(0.0, 1.0)));
+        self
+    }
+
+    /// Fails this fraction of executions permanently.
+    #[must_use]
+    pub fn with_failure_rate(self, rate: f64) -> Self {
+        self.with_rate(Fault::Fail, rate)
+    }
+
+    /// Fails this fraction of executions with a retry request.
+    #[must_use]
+    pub fn with_transient_rate(self, rate: f64) -> Self {
+        self.with_rate(Fault::Transient, rate)
+    }
+
+    /// Delays this fraction of executions by `delay`.
+    #[must_use]
+    pub fn with_delay_rate(self, rate: f64, delay: Duration) -> Self {
+        self.with_rate(Fault::Delay(delay), rate)
+    }
+
+    /// Cancels the pipeline on this fraction of executions.
+    #[must_use]
+    pub fn with_cancel_rate(self, rate: f64) -> Self {
+        self.with_rate(Fault::Cancel, rate)
+    }
+
+    /// Retries this fraction of successful executions.
+    #[must_use]
+    pub fn with_duplicate_retry_rate(self, rate: f64) -> Self {
+        self.with_rate(Fault::DuplicateRetry, rate)
+    }
+
+    /// Injects `fault` into the given attempt of `stage`, regardless of
+    /// rates and targets.
+    #[must_use]
+    pub fn with_scheduled(mut self, stage: impl Into<String>, attempt: u32, fault: Fault) -> Self {
+        self.schedule.insert((stage.into(), attempt), fault);
+        self
+    }
+
+    /// Limits random faults to the given stages.
+    #[must_use]
+    pub fn with_targets(mut self, stages: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.targets = Some(stages.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the faults injected so far, in order.
+    #[must_use]
+    pub fn injected(&self) -> Vec<InjectedFault> {
+        self.injected.lock().clone()
+    }
+
+    /// Returns how many times `stage` has executed through the interceptor.
+    #[must_use]
+    pub fn attempts(&self, stage: &str) -> u32 {
+        self.attempts.lock().get(stage).copied().unwrap_or(0)
+    }
+
+    /// Clears attempt counts and the injection log.
+    pub fn reset(&self) {
+        self.attempts.lock().clear();
+        self.pending_duplicates.lock().clear();
+        self.injected.lock().clear();
+    }
+
+    /// Returns `spec` with its stage wrapped so this interceptor applies.
+    #[must_use]
+    pub fn wrap(self: &Arc<Self>, mut spec: StageSpec) -> StageSpec {
+        spec.runner = Arc::new(ChaosStage::new(spec.runner, Arc::clone(self)));
+        spec
+    }
+
+    fn choose(&self, stage: &str) -> Option<(u32, Fault)> {
+        let attempt = {
+            let mut attempts = self.attempts.lock();
+            let count = attempts.entry(stage.to_string()).or_insert(0);
+            *count += 1;
+            *count
+        };
+        if let Some(fault) = self.schedule.get(&(stage.to_string(), attempt)) {
+            return Some((attempt, fault.clone()));
+        }
+        if self.targets.as_ref().is_some_and(|targets| !targets.contains(stage)) {
+            return None;
+        }
+        let draw: f64 = self.rng.lock().gen();
+        let mut threshold = 0.0;
+        self.rates.iter().find_map(|(fault, rate)| {
+            threshold += rate;
+            (draw < threshold).then(|| (attempt, fault.clone()))
+        })
+    }
+}
+
+impl Default for ChaosInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ChaosInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChaosInterceptor")
+            .field("rates", &self.rates)
+            .field("schedule", &self.schedule)
+            .field("targets", &self.targets)
+            .field("injected", &self.injected.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Interceptor for ChaosInterceptor {
+    fn priority(&self) -> i32 {
+        -1000 // Run before anything that could short-circuit
+    }
+
+    async fn before(&self, ctx: &StageContext) -> Option<StageOutput> {
+        let stage = ctx.stage_name();
+        let (attempt, fault) = self.choose(stage)?;
+        ctx.try_emit_event(
+            "stage.chaos_injected",
+            Some(serde_json::json!({
+                "stage": stage,
+                "attempt": attempt,
+                "fault": fault.name(),
+            })),
+        );
+        self.injected.lock().push(InjectedFault {
+            stage: stage.to_string(),
+            attempt,
+            fault: fault.clone(),
+        });
+        match fault {
+            Fault::Fail => Some(StageOutput::fail(format!("chaos: injected failure in {stage}"))),
+            Fault::Transient => Some(StageOutput::retry(format!("chaos: injected transient failure in {stage}"))),
+            Fault::Cancel => Some(StageOutput::cancel(format!("chaos: injected cancellation in {stage}"))),
+            Fault::Delay(delay) => {
+                tokio::time::sleep(delay).await;
+                None
+            }
+            Fault::DuplicateRetry => {
+                self.pending_duplicates.lock().insert(stage.to_string());
+                None
+            }
+        }
+    }
+
+    async fn after(&self, ctx: &StageContext, output: StageOutput) -> StageOutput {
+        if self.pending_duplicates.lock().remove(ctx.stage_name()) && output.is_success() {
+            return StageOutput::retry(format!("chaos: duplicated retry of {}", ctx.stage_name()));
+        }
+        output
+    }
+}
+
+/// A stage run through a [`ChaosInterceptor`].
+#[derive(Debug)]
+pub struct ChaosStage {
+    inner: Arc<dyn Stage>,
+    chaos: Arc<ChaosInterceptor>,
+}
+
+impl ChaosStage {
+    /// Wraps `inner` so `chaos` applies to its executions.
+    #[must_use]
+    pub fn new(inner: Arc<dyn Stage>, chaos: Arc<ChaosInterceptor>) -> Self {
+        Self { inner, chaos }
+    }
+}
+
+#[async_trait]
+impl Stage for ChaosStage {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        if let Some(output) = self.chaos.before(ctx).await {
+            return output;
+        }
+        let output = self.inner.execute(ctx).await;
+        self.chaos.after(ctx, output).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+    use crate::core::StageStatus;
+    use crate::pipeline::{JitterStrategy, PipelineBuilder, RetryConfig, UnifiedExecutionResult, UnifiedStageGraph};
+    use crate::testing::RecordingStage;
+
+    async fn run(chaos: &Arc<ChaosInterceptor>, stages: &[Arc<RecordingStage>]) -> UnifiedExecutionResult {
+        let mut builder = PipelineBuilder::new("chaos");
+        let mut retried = Vec::new();
+        for (i, stage) in stages.iter().enumerate() {
+            let name = stage.name().to_string();
+            let mut spec = StageSpec::new(&name, Arc::clone(stage) as Arc<dyn Stage>);
+            if i > 0 {
+                spec = spec.with_dependency(stages[i - 1].name());
+            }
+            builder.add_stage_spec(chaos.wrap(spec)).unwrap();
+            retried.push(name);
+        }
+        let mut unified = UnifiedStageGraph::new(builder.build().unwrap());
+        for name in retried {
+            unified = unified
+                .with_stage_retry(&name, RetryConfig::new().with_base_delay_ms(0).with_jitter(JitterStrategy::None))
+                .unwrap();
+        }
+        unified
+            .execute(Arc::new(PipelineContext::new(RunIdentity::new())), ContextSnapshot::new())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_faults_exercise_retries() {
+        let chaos = Arc::new(
+            ChaosInterceptor::new()
+                .with_scheduled("fetch", 1, Fault::Transient)
+                .with_scheduled("store", 1, Fault::DuplicateRetry),
+        );
+        let fetch = Arc::new(RecordingStage::new("fetch"));
+        let store = Arc::new(RecordingStage::new("store"));
+
+        let result = run(&chaos, &[Arc::clone(&fetch), Arc::clone(&store)]).await;
+
+        assert!(result.success);
+        assert_eq!(fetch.execution_count(), 1);
+        assert_eq!(store.execution_count(), 2);
+        assert_eq!(chaos.attempts("fetch"), 2);
+        let faults: Vec<_> = chaos.injected().into_iter().map(|f| (f.stage, f.attempt, f.fault)).collect();
+        assert_eq!(
+            faults,
+            vec![
+                ("fetch".to_string(), 1, Fault::Transient),
+                ("store".to_string(), 1, Fault::DuplicateRetry),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_faults_only_hit_targets() {
+        let chaos = Arc::new(ChaosInterceptor::new().with_seed(7).with_failure_rate(1.0).with_targets(["load"]));
+        let parse = Arc::new(RecordingStage::new("parse"));
+        let load = Arc::new(RecordingStage::new("load"));
+
+        let result = run(&chaos, &[Arc::clone(&parse), Arc::clone(&load)]).await;
+
+        assert!(!result.success);
+        assert_eq!(parse.execution_count(), 1);
+        assert_eq!(load.execution_count(), 0);
+        assert_eq!(result.outputs.get("load").map(|o| o.status), Some(StageStatus::Fail));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_draws_are_reproducible() {
+        let draws = |seed| {
+            let chaos = ChaosInterceptor::new().with_seed(seed).with_failure_rate(0.3).with_cancel_rate(0.3);
+            (0..50).map(|_| chaos.choose("stage").map(|(_, fault)| fault)).collect::<Vec<_>>()
+        };
+        let first = draws(42);
+        assert_eq!(first, draws(42));
+        assert!(first.contains(&Some(Fault::Fail)));
+        assert!(first.contains(&Some(Fault::Cancel)));
+        assert!(first.contains(&None));
+    }
+}
@@ -1,4 +1,8 @@
 //! Test fixtures for pipeline testing.
+//!
+//! With the `proptest` feature, [`arb_dag`] generates random valid
+//! pipelines and the `check_*` helpers assert invariants every executor
+//! run must keep, so executors can be fuzzed with `proptest!`.
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -166,6 +170,424 @@ impl TestPipeline {
     }
 }
 
+#[cfg(any(test, feature = "proptest"))]
+pub use generators::{
+    arb_dag, check_cancellation_terminates, check_finalized_once, check_terminates, DagConfig, ExecutionLog,
+    GeneratedDag, GeneratedStage,
+};
+
+#[cfg(any(test, feature = "proptest"))]
+mod generators {
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+    use proptest::prelude::*;
+    use proptest::sample::Index;
+    use proptest::test_runner::TestCaseError;
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageContext};
+    use crate::core::{StageKind, StageOutput, StageStatus};
+    use crate::errors::StageflowError;
+    use crate::pipeline::{PipelineBuilder, StageSpec, UnifiedExecutionResult, UnifiedStageGraph};
+    use crate::stages::Stage;
+
+    /// Shape of the pipelines [`arb_dag`] generates.
+    #[derive(Debug, Clone)]
+    pub struct DagConfig {
+        /// Fewest stages.
+        pub min_stages: usize,
+        /// Most stages.
+        pub max_stages: usize,
+        /// Most dependencies of a stage.
+        pub max_dependencies: usize,
+        /// Most stages depending on a stage.
+        pub max_fan_out: usize,
+        /// Fraction of stages that are guards.
+        pub guard_ratio: f64,
+        /// Fraction of stages that are conditional, and of stages that ask
+        /// conditional dependents to skip.
+        pub conditional_ratio: f64,
+        /// Fraction of stages that fail.
+        pub failure_ratio: f64,
+        /// Longest a stage takes.
+        pub max_delay: Duration,
+    }
+
+    impl Default for DagConfig {
+        fn default() -> Self {
+            Self {
+                min_stages: 1,
+                max_stages: 8,
+                max_dependencies: 3,
+                max_fan_out: 3,
+                guard_ratio: 0.2,
+                conditional_ratio: 0.2,
+                failure_ratio: 0.0,
+                max_delay: Duration::ZERO,
+            }
+        }
+    }
+
+    impl DagConfig {
+        /// Creates the default shape.
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Sets the range of stage counts.
+        #[must_use]
+        pub fn with_stages(mut self, min: usize, max: usize) -> Self {
+            self.min_stages = min.max(1);
+            self.max_stages = max.max(self.min_stages);
+            self
+        }
+
+        /// Sets the most dependencies of a stage.
+        #[must_use]
+        pub fn with_max_dependencies(mut self, max: usize) -> Self {
+            self.max_dependencies = max;
+            self
+        }
+
+        /// Sets the most stages depending on a stage.
+        #[must_use]
+        pub fn with_max_fan_out(mut self, max: usize) -> Self {
+            self.max_fan_out = max;
+            self
+        }
+
+        /// Sets the fraction of guard stages.
+        #[must_use]
+        pub fn with_guard_ratio(mut self, ratio: f64) -> Self {
+            self.guard_ratio = ratio.clamp(0.0, 1.0);
+            self
+        }
+
+        /// Sets the fraction of conditional stages and of skip requests.
+        #[must_use]
+        pub fn with_conditional_ratio(mut self, ratio: f64) -> Self {
+            self.conditional_ratio = ratio.clamp(0.0, 1.0);
+            self
+        }
+
+        /// Sets the fraction of failing stages.
+        #[must_use]
+        pub fn with_failure_ratio(mut self, ratio: f64) -> Self {
+            self.failure_ratio = ratio.clamp(0.0, 1.0);
+            self
+        }
+
+        /// Sets the longest a stage takes.
+        #[must_use]
+        pub fn with_max_delay(mut self, delay: Duration) -> Self {
+            self.max_delay = delay;
+            self
+        }
+    }
+
+    /// A stage of a generated pipeline.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct GeneratedStage {
+        /// The stage name.
+        pub name: String,
+        /// Names of earlier stages it depends on.
+        pub dependencies: Vec<String>,
+        /// The stage kind.
+        pub kind: StageKind,
+        /// Whether the stage is skipped when a dependency asks.
+        pub conditional: bool,
+        /// Whether the stage asks its conditional dependents to skip.
+        pub requests_skip: bool,
+        /// Whether the stage fails.
+        pub fails: bool,
+        /// How long the stage takes.
+        pub delay: Duration,
+    }
+
+    /// A random pipeline whose stages are listed in a valid execution order.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct GeneratedDag {
+        /// The stages, each after its dependencies.
+        pub stages: Vec<GeneratedStage>,
+    }
+
+    impl GeneratedDag {
+        /// Returns the stage specs, with stages recording their executions
+        /// in `log`.
+        #[must_use]
+        pub fn specs(&self, log: &Arc<ExecutionLog>) -> Vec<StageSpec> {
+            self.stages
+                .iter()
+                .map(|stage| {
+                    let runner = Arc::new(GeneratedStageRunner { stage: stage.clone(), log: Arc::clone(log) });
+                    let mut spec = StageSpec::new(&stage.name, runner)
+                        .with_dependencies(stage.dependencies.iter().cloned())
+                        .with_kind(stage.kind);
+                    spec.conditional = stage.conditional;
+                    spec
+                })
+                .collect()
+        }
+
+        /// Builds the pipeline, with stages recording their executions in
+        /// `log`.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the builder rejects the pipeline, which a
+        /// generated pipeline should never cause.
+        pub fn build(&self, log: &Arc<ExecutionLog>) -> Result<UnifiedStageGraph, StageflowError> {
+            let mut builder = PipelineBuilder::new("generated");
+            for spec in self.specs(log) {
+                builder.add_stage_spec(spec)?;
+            }
+            Ok(UnifiedStageGraph::new(builder.build()?))
+        }
+
+        /// Returns true if any stage fails.
+        #[must_use]
+        pub fn has_failures(&self) -> bool {
+            self.stages.iter().any(|stage| stage.fails)
+        }
+    }
+
+    /// Executions of generated stages, by stage name.
+    #[derive(Debug, Default)]
+    pub struct ExecutionLog {
+        executions: Mutex<HashMap<String, usize>>,
+    }
+
+    impl ExecutionLog {
+        /// Creates an empty log.
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Returns how many times `stage` has run.
+        #[must_use]
+        pub fn executions(&self, stage: &str) -> usize {
+            self.executions.lock().get(stage).copied().unwrap_or(0)
+        }
+
+        fn record(&self, stage: &str) {
+            *self.executions.lock().entry(stage.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    #[derive(Debug)]
+    struct GeneratedStageRunner {
+        stage: GeneratedStage,
+        log: Arc<ExecutionLog>,
+    }
+
+    #[async_trait]
+    impl Stage for GeneratedStageRunner {
+        fn name(&self) -> &str {
+            &self.stage.name
+        }
+
+        async fn execute(&self, _ctx: &StageContext) -> StageOutput {
+            self.log.record(&self.stage.name);
+            if !self.stage.delay.is_zero() {
+                tokio::time::sleep(self.stage.delay).await;
+            }
+            if self.stage.fails {
+                StageOutput::fail(format!("generated failure in {}", self.stage.name))
+            } else if self.stage.requests_skip {
+                StageOutput::ok_value("skip_reason", serde_json::json!(format!("skipped by {}", self.stage.name)))
+            } else {
+                StageOutput::ok_empty()
+            }
+        }
+    }
+
+    /// Generates pipelines shaped by `config`.
+    ///
+    /// Dependencies only point at earlier stages, so every generated
+    /// pipeline is acyclic. Shrinking removes stages and dependencies.
+    pub fn arb_dag(config: &DagConfig) -> BoxedStrategy<GeneratedDag> {
+        let max_delay_ms = u64::try_from(config.max_delay.as_millis()).unwrap_or(u64::MAX);
+        let stage = (
+            proptest::collection::vec(any::<Index>(), 0..=config.max_dependencies),
+            proptest::bool::weighted(config.guard_ratio),
+            proptest::bool::weighted(config.conditional_ratio),
+            proptest::bool::weighted(config.conditional_ratio),
+            proptest::bool::weighted(config.failure_ratio),
+            0..=max_delay_ms,
+        );
+        let max_fan_out = config.max_fan_out;
+        proptest::collection::vec(stage, config.min_stages..=config.max_stages)
+            .prop_map(move |drafts| {
+                let mut fan_out = vec![0; drafts.len()];
+                let stages = drafts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (picks, guard, conditional, requests_skip, fails, delay_ms))| {
+                        let mut dependencies: Vec<usize> = Vec::new();
+                        if i > 0 {
+                            for pick in picks {
+                                let dep = pick.index(i);
+                                if fan_out[dep] < max_fan_out && !dependencies.contains(&dep) {
+                                    fan_out[dep] += 1;
+                                    dependencies.push(dep);
+                                }
+                            }
+                        }
+                        dependencies.sort_unstable();
+                        GeneratedStage {
+                            name: format!("stage_{i}"),
+                            dependencies: dependencies.into_iter().map(|dep| format!("stage_{dep}")).collect(),
+                            kind: if guard { StageKind::Guard } else { StageKind::Work },
+                            conditional,
+                            requests_skip,
+                            fails,
+                            delay: Duration::from_millis(delay_ms),
+                        }
+                    })
+                    .collect();
+                GeneratedDag { stages }
+            })
+            .boxed()
+    }
+
+    /// Checks that a run finalized every stage at most once, and every
+    /// stage once when it succeeded.
+    ///
+    /// A stage is finalized when it has an output; every stage with an
+    /// output either ran exactly once or was skipped without running.
+    ///
+    /// # Errors
+    ///
+    /// Returns a test failure naming the first stage that breaks the rule.
+    pub fn check_finalized_once(
+        dag: &GeneratedDag,
+        log: &ExecutionLog,
+        result: &UnifiedExecutionResult,
+    ) -> Result<(), TestCaseError> {
+        for stage in &dag.stages {
+            let runs = log.executions(&stage.name);
+            if runs > 1 {
+                return Err(TestCaseError::fail(format!("{} ran {runs} times", stage.name)));
+            }
+            match result.outputs.get(&stage.name) {
+                Some(output) if output.status == StageStatus::Skip && runs != 0 => {
+                    return Err(TestCaseError::fail(format!("{} was skipped after running", stage.name)));
+                }
+                Some(output) if output.status != StageStatus::Skip && runs != 1 => {
+                    return Err(TestCaseError::fail(format!("{} has an output but never ran", stage.name)));
+                }
+                None if result.success => {
+                    return Err(TestCaseError::fail(format!("{} has no output after a successful run", stage.name)));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Awaits `run`, failing if it does not finish within `timeout`, which
+    /// catches executors that deadlock.
+    ///
+    /// # Errors
+    ///
+    /// Returns a test failure if the run times out.
+    pub async fn check_terminates<F: Future>(run: F, timeout: Duration) -> Result<F::Output, TestCaseError> {
+        tokio::time::timeout(timeout, run)
+            .await
+            .map_err(|_| TestCaseError::fail(format!("run did not finish within {timeout:?}")))
+    }
+
+    /// Runs `dag`, cancels its context after `cancel_after`, and checks the
+    /// run finishes within `timeout` of starting, reporting a cancellation
+    /// unless it finished first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a test failure if the pipeline does not build or run, does
+    /// not finish in time, or ignores the cancellation.
+    pub async fn check_cancellation_terminates(
+        dag: &GeneratedDag,
+        cancel_after: Duration,
+        timeout: Duration,
+    ) -> Result<UnifiedExecutionResult, TestCaseError> {
+        let log = Arc::new(ExecutionLog::new());
+        let graph = dag.build(&log).map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let canceller = Arc::clone(&ctx);
+        let run = Box::pin(graph.execute(ctx, ContextSnapshot::new()));
+        let cancel = async move {
+            tokio::time::sleep(cancel_after).await;
+            canceller.mark_cancelled_with_reason("generated cancellation");
+        };
+        let (result, ()) = check_terminates(async { tokio::join!(run, cancel) }, timeout).await?;
+        let result = result.map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let finished = result.success || result.error.is_some();
+        if !result.cancelled && !finished {
+            return Err(TestCaseError::fail("run neither finished nor reported the cancellation"));
+        }
+        check_finalized_once(dag, &log, &result)?;
+        Ok(result)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(32))]
+
+            #[test]
+            fn test_generated_dags_respect_config(dag in arb_dag(&DagConfig::new().with_stages(2, 10).with_max_fan_out(2))) {
+                prop_assert!((2..=10).contains(&dag.stages.len()));
+                for (i, stage) in dag.stages.iter().enumerate() {
+                    prop_assert!(stage.dependencies.len() <= 3);
+                    for dep in &stage.dependencies {
+                        let position = dag.stages.iter().position(|s| &s.name == dep).unwrap();
+                        prop_assert!(position < i);
+                    }
+                    let dependents = dag.stages.iter().filter(|s| s.dependencies.contains(&stage.name)).count();
+                    prop_assert!(dependents <= 2);
+                }
+            }
+
+            #[test]
+            fn test_unified_executor_finalizes_each_stage_once(
+                dag in arb_dag(&DagConfig::new().with_failure_ratio(0.1))
+            ) {
+                runtime().block_on(async {
+                    let log = Arc::new(ExecutionLog::new());
+                    let graph = dag.build(&log).unwrap();
+                    let ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+                    let run = Box::pin(graph.execute(ctx, ContextSnapshot::new()));
+                    let result = check_terminates(run, Duration::from_secs(5)).await?.unwrap();
+                    prop_assert!(result.success || dag.has_failures());
+                    check_finalized_once(&dag, &log, &result)
+                })?;
+            }
+
+            #[test]
+            fn test_unified_executor_cancellation_terminates(
+                dag in arb_dag(&DagConfig::new().with_max_delay(Duration::from_millis(20)))
+            ) {
+                runtime().block_on(check_cancellation_terminates(
+                    &dag,
+                    Duration::from_millis(10),
+                    Duration::from_secs(5),
+                ))?;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
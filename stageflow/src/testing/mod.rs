@@ -3,17 +3,34 @@
 //! This module provides:
 //! - Mock stages and contexts
 //! - Test assertions for stage outputs
+//! - Golden-file assertions on normalized event streams
 //! - Pipeline test harness
+//! - Benchmarks of executor overhead
+//! - Saga compensation checks
+//! - Fault injection for chaos testing
+//! - Random pipeline generators and executor invariants (`proptest` feature)
 
 mod assertions;
+mod bench;
+mod chaos;
 mod fixtures;
 mod mocks;
+mod saga;
 
 pub use assertions::{
-    assert_output_contains, assert_output_failed, assert_output_has_data,
-    assert_output_status, assert_output_succeeded,
+    assert_events_match_golden, assert_events_match_golden_with, assert_output_contains, assert_output_failed,
+    assert_output_has_data, assert_output_status, assert_output_succeeded, capture_events, golden_diff,
+    CollectedEvent, EventNormalizer, DEFAULT_SCRUBBED_EVENT_KEYS, UPDATE_GOLDEN_ENV,
 };
+pub use bench::{BenchReport, BenchShape, PipelineBench};
+pub use chaos::{ChaosInterceptor, ChaosStage, Fault, InjectedFault};
 pub use fixtures::{TestContext, TestFixture, TestPipeline};
+#[cfg(any(test, feature = "proptest"))]
+pub use fixtures::{
+    arb_dag, check_cancellation_terminates, check_finalized_once, check_terminates, DagConfig, ExecutionLog,
+    GeneratedDag, GeneratedStage,
+};
 pub use mocks::{
     FailingStage, MockStage, RecordingStage, SlowStage, SuccessStage,
 };
+pub use saga::{MockExternalSystem, SagaHarness, SagaJournal, SagaOutcome};
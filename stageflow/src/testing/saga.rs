@@ -0,0 +1,432 @@
+//! Saga-style compensation testing.
+//!
+//! A saga undoes the work of completed stages when a later stage fails.
+//! [`SagaHarness`] checks that a pipeline does this correctly: it runs the
+//! pipeline with a failure injected right after a chosen stage, runs the
+//! compensations the stages registered in a [`SagaJournal`], and reports
+//! whether they ran in reverse order and left every [`MockExternalSystem`]
+//! as it was before the run.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::context::{ContextSnapshot, PipelineContext, RunIdentity, StageContext};
+use crate::core::{StageOutput, StageStatus};
+use crate::errors::StageflowError;
+use crate::pipeline::{CleanupRegistry, GraphExecutionResult, StageGraph};
+use crate::stages::Stage;
+use crate::tools::UndoStore;
+use crate::utils::DeterministicSource;
+
+/// Time allowed for all compensations of one run, in seconds.
+const COMPENSATION_TIMEOUT_SECS: f64 = 30.0;
+
+/// An in-memory stand-in for an external system a saga writes to.
+///
+/// Stages and their compensations change its state; the harness records a
+/// baseline before each run and compares against it afterwards.
+#[derive(Debug, Default)]
+pub struct MockExternalSystem {
+    name: String,
+    state: Mutex<BTreeMap<String, serde_json::Value>>,
+    baseline: Mutex<BTreeMap<String, serde_json::Value>>,
+}
+
+impl MockExternalSystem {
+    /// Creates an empty system.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.into(),
+            ..Self::default()
+        })
+    }
+
+    /// Returns the system name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sets a value, returning the previous one.
+    pub fn set(&self, key: impl Into<String>, value: serde_json::Value) -> Option<serde_json::Value> {
+        self.state.lock().insert(key.into(), value)
+    }
+
+    /// Removes a value, returning it.
+    pub fn remove(&self, key: &str) -> Option<serde_json::Value> {
+        self.state.lock().remove(key)
+    }
+
+    /// Returns a value.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.state.lock().get(key).cloned()
+    }
+
+    /// Returns a copy of the current state.
+    #[must_use]
+    pub fn snapshot(&self) -> BTreeMap<String, serde_json::Value> {
+        self.state.lock().clone()
+    }
+
+    /// Records the current state as the baseline.
+    pub fn mark_baseline(&self) {
+        *self.baseline.lock() = self.snapshot();
+    }
+
+    /// Puts the state back to the baseline.
+    pub fn restore_baseline(&self) {
+        *self.state.lock() = self.baseline.lock().clone();
+    }
+
+    /// Returns the keys whose values differ from the baseline, sorted.
+    #[must_use]
+    pub fn drift(&self) -> Vec<String> {
+        let state = self.state.lock();
+        let baseline = self.baseline.lock();
+        let keys: BTreeSet<&String> = state
+            .keys()
+            .chain(baseline.keys())
+            .filter(|key| state.get(*key) != baseline.get(*key))
+            .collect();
+        keys.into_iter().cloned().collect()
+    }
+}
+
+/// Compensations registered by the stages of a saga.
+///
+/// Stages register a compensation once their external work has succeeded;
+/// [`compensate`](Self::compensate) runs them newest first.
+#[derive(Debug, Default)]
+pub struct SagaJournal {
+    pending: CleanupRegistry,
+    registered: Mutex<Vec<String>>,
+    executed: Arc<Mutex<Vec<String>>>,
+}
+
+impl SagaJournal {
+    /// Creates an empty journal.
+    #[must_use]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Registers the compensation for a completed step.
+    pub fn register<F, Fut>(&self, step: impl Into<String>, compensation: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let step = step.into();
+        self.registered.lock().push(step.clone());
+        let executed = Arc::clone(&self.executed);
+        let label = step.clone();
+        self.pending.register(step, move || async move {
+            compensation().await;
+            executed.lock().push(label);
+        });
+    }
+
+    /// Returns the registered steps, oldest first.
+    #[must_use]
+    pub fn registered(&self) -> Vec<String> {
+        self.registered.lock().clone()
+    }
+
+    /// Returns the steps whose compensation finished, in execution order.
+    #[must_use]
+    pub fn executed(&self) -> Vec<String> {
+        self.executed.lock().clone()
+    }
+
+    /// Runs the pending compensations newest first.
+    ///
+    /// Returns the steps whose compensation failed or timed out, with the
+    /// reason.
+    pub async fn compensate(&self) -> Vec<(String, String)> {
+        self.pending.run_all(COMPENSATION_TIMEOUT_SECS).await.1
+    }
+
+    /// Forgets all registrations and executions.
+    pub fn reset(&self) {
+        self.pending.clear();
+        self.registered.lock().clear();
+        self.executed.lock().clear();
+    }
+}
+
+/// Result of one saga run with an injected failure.
+#[derive(Debug)]
+pub struct SagaOutcome {
+    /// The stage after which the failure was injected.
+    pub failed_after: String,
+    /// The pipeline result.
+    pub result: GraphExecutionResult,
+    /// Steps registered during the run, oldest first.
+    pub registered: Vec<String>,
+    /// Steps compensated, in execution order.
+    pub compensated: Vec<String>,
+    /// Compensations that failed or timed out.
+    pub compensation_failures: Vec<(String, String)>,
+    /// Keys differing from the baseline, per external system with drift.
+    pub drift: HashMap<String, Vec<String>>,
+    /// Undo entries left in the undo store, if one is checked.
+    pub undo_entries_left: usize,
+}
+
+impl SagaOutcome {
+    /// Returns true if every registered step was compensated in reverse
+    /// order.
+    #[must_use]
+    pub fn compensated_in_reverse(&self) -> bool {
+        self.compensation_failures.is_empty()
+            && self.compensated.iter().eq(self.registered.iter().rev())
+    }
+
+    /// Returns true if the run was fully rolled back: compensations ran in
+    /// reverse order, every external system is back at its baseline, and no
+    /// undo entries are left.
+    #[must_use]
+    pub fn is_rolled_back(&self) -> bool {
+        self.compensated_in_reverse() && self.drift.is_empty() && self.undo_entries_left == 0
+    }
+
+    /// Panics with the details if the run was not fully rolled back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`is_rolled_back`](Self::is_rolled_back) is false.
+    pub fn assert_rolled_back(&self) {
+        assert!(
+            self.is_rolled_back(),
+            "saga failing after '{}' was not rolled back: registered {:?}, compensated {:?}, \
+             failures {:?}, drift {:?}, undo entries left {}",
+            self.failed_after,
+            self.registered,
+            self.compensated,
+            self.compensation_failures,
+            self.drift,
+            self.undo_entries_left,
+        );
+    }
+}
+
+/// Runs a pipeline with injected failures and checks its compensations.
+///
+/// Stages run one at a time in topological order, so the steps registered
+/// before a failure are the same on every run.
+#[derive(Debug)]
+pub struct SagaHarness {
+    graph: StageGraph,
+    journal: Arc<SagaJournal>,
+    systems: Vec<Arc<MockExternalSystem>>,
+    undo_store: Option<Arc<UndoStore>>,
+}
+
+impl SagaHarness {
+    /// Creates a harness for `graph`, whose stages register their
+    /// compensations in `journal`.
+    #[must_use]
+    pub fn new(graph: StageGraph, journal: Arc<SagaJournal>) -> Self {
+        Self {
+            graph,
+            journal,
+            systems: Vec::new(),
+            undo_store: None,
+        }
+    }
+
+    /// Adds an external system that must return to its baseline.
+    #[must_use]
+    pub fn with_system(mut self, system: Arc<MockExternalSystem>) -> Self {
+        self.systems.push(system);
+        self
+    }
+
+    /// Adds an undo store that must be empty once compensation is done.
+    #[must_use]
+    pub fn with_undo_store(mut self, store: Arc<UndoStore>) -> Self {
+        self.undo_store = Some(store);
+        self
+    }
+
+    /// Runs the pipeline, failing it right after `stage` succeeds, then
+    /// compensates.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `stage` is not in the pipeline or the pipeline
+    /// errors instead of failing.
+    pub async fn run_failing_after(&self, stage: &str) -> Result<SagaOutcome, StageflowError> {
+        let mut specs = self.graph.stage_specs().clone();
+        let spec = specs.get_mut(stage).ok_or_else(|| {
+            StageflowError::Internal(format!("Stage '{stage}' is not in pipeline '{}'", self.graph.name()))
+        })?;
+        spec.runner = Arc::new(FailAfterStage {
+            inner: Arc::clone(&spec.runner),
+        });
+        let graph = StageGraph::new(
+            self.graph.name().to_string(),
+            specs,
+            self.graph.execution_order().to_vec(),
+        )
+        .with_budget(*self.graph.budget());
+
+        self.journal.reset();
+        for system in &self.systems {
+            system.mark_baseline();
+        }
+
+        let ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_deterministic_source(Arc::new(DeterministicSource::new(0))),
+        );
+        let result = graph.execute(ctx, ContextSnapshot::new()).await?;
+        let compensation_failures = self.journal.compensate().await;
+
+        Ok(SagaOutcome {
+            failed_after: stage.to_string(),
+            result,
+            registered: self.journal.registered(),
+            compensated: self.journal.executed(),
+            compensation_failures,
+            drift: self
+                .systems
+                .iter()
+                .map(|system| (system.name().to_string(), system.drift()))
+                .filter(|(_, drift)| !drift.is_empty())
+                .collect(),
+            undo_entries_left: self.undo_store.as_ref().map_or(0, |store| store.len()),
+        })
+    }
+
+    /// Runs [`run_failing_after`](Self::run_failing_after) once for every
+    /// stage, in execution order.
+    ///
+    /// External systems are restored to their baseline between runs, so a
+    /// broken compensation shows up only in the run that exposed it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from a run.
+    pub async fn run_every_failure_point(&self) -> Result<Vec<SagaOutcome>, StageflowError> {
+        let mut outcomes = Vec::new();
+        for stage in self.graph.execution_order() {
+            let outcome = self.run_failing_after(stage).await?;
+            for system in &self.systems {
+                system.restore_baseline();
+            }
+            if let Some(store) = &self.undo_store {
+                store.clear();
+            }
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Runs a stage, then fails in its place if it succeeded.
+#[derive(Debug)]
+struct FailAfterStage {
+    inner: Arc<dyn Stage>,
+}
+
+#[async_trait]
+impl Stage for FailAfterStage {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let output = self.inner.execute(ctx).await;
+        if output.status != StageStatus::Ok {
+            return output;
+        }
+        StageOutput::fail(format!("Injected failure after stage '{}'", ctx.stage_name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::PipelineBuilder;
+
+    /// Books a resource, registering a compensation that releases it.
+    #[derive(Debug)]
+    struct BookingStage {
+        name: String,
+        system: Arc<MockExternalSystem>,
+        journal: Arc<SagaJournal>,
+        leaky: bool,
+    }
+
+    #[async_trait]
+    impl Stage for BookingStage {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn execute(&self, _ctx: &StageContext) -> StageOutput {
+            self.system.set(self.name.clone(), serde_json::json!("booked"));
+            let system = Arc::clone(&self.system);
+            let key = if self.leaky { "elsewhere".to_string() } else { self.name.clone() };
+            self.journal.register(self.name.clone(), move || async move {
+                system.remove(&key);
+            });
+            StageOutput::ok_empty()
+        }
+    }
+
+    fn saga(leaky_stage: Option<&str>) -> (SagaHarness, Arc<MockExternalSystem>) {
+        let system = MockExternalSystem::new("bookings");
+        let journal = SagaJournal::new();
+        let stage = |name: &str| -> Arc<dyn Stage> {
+            Arc::new(BookingStage {
+                name: name.to_string(),
+                system: Arc::clone(&system),
+                journal: Arc::clone(&journal),
+                leaky: leaky_stage == Some(name),
+            })
+        };
+        let graph = PipelineBuilder::new("trip")
+            .stage("flight", stage("flight"), &[])
+            .unwrap()
+            .stage("hotel", stage("hotel"), &["flight"])
+            .unwrap()
+            .stage("car", stage("car"), &["hotel"])
+            .unwrap()
+            .build()
+            .unwrap();
+        let harness = SagaHarness::new(graph, journal).with_system(Arc::clone(&system));
+        (harness, system)
+    }
+
+    #[tokio::test]
+    async fn test_compensations_run_in_reverse_and_restore_baseline() {
+        let (harness, system) = saga(None);
+        system.set("existing", serde_json::json!(1));
+
+        let outcome = harness.run_failing_after("hotel").await.unwrap();
+        assert!(!outcome.result.success);
+        assert_eq!(outcome.registered, vec!["flight", "hotel"]);
+        assert_eq!(outcome.compensated, vec!["hotel", "flight"]);
+        outcome.assert_rolled_back();
+        assert_eq!(system.get("existing"), Some(serde_json::json!(1)));
+        assert!(harness.run_failing_after("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_broken_compensation_is_reported_at_its_failure_points() {
+        let (harness, _) = saga(Some("hotel"));
+        let outcomes = harness.run_every_failure_point().await.unwrap();
+
+        let rolled_back: Vec<bool> = outcomes.iter().map(SagaOutcome::is_rolled_back).collect();
+        assert_eq!(rolled_back, vec![true, false, false]);
+        assert_eq!(outcomes[1].drift["bookings"], vec!["hotel".to_string()]);
+        assert!(outcomes[1].compensated_in_reverse());
+    }
+}
@@ -1,10 +1,15 @@
 //! Approval service for human-in-the-loop workflows.
 
+use super::ApprovalQueueStore;
+use crate::errors::StageflowError;
+use async_trait::async_trait;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
+use tracing::warn;
 use uuid::Uuid;
 
 /// Approval request status.
@@ -22,6 +27,223 @@ pub enum ApprovalStatus {
     Cancelled,
 }
 
+impl ApprovalStatus {
+    /// Returns the status as a lowercase string.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Denied => "denied",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// An approval request as seen by an [`ApprovalBackend`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingApproval {
+    /// Request ID, usable with [`ApprovalService::approve`] and
+    /// [`ApprovalService::deny`].
+    pub request_id: Uuid,
+    /// Tool name.
+    pub tool_name: String,
+    /// Approval message.
+    pub message: String,
+}
+
+impl PendingApproval {
+    /// Creates a pending approval with a fresh request ID.
+    #[must_use]
+    pub fn new(tool_name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::with_id(Uuid::new_v4(), tool_name, message)
+    }
+
+    /// Creates a pending approval with the given request ID.
+    #[must_use]
+    pub fn with_id(request_id: Uuid, tool_name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            request_id,
+            tool_name: tool_name.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Decides approval requests outside the process.
+///
+/// Backends race against in-process [`ApprovalService::approve`] and
+/// [`ApprovalService::deny`] calls; the first decision wins. Returning
+/// `None` abstains and leaves the request to in-process decisions until
+/// the approval timeout.
+#[async_trait]
+pub trait ApprovalBackend: Send + Sync {
+    /// Decides a request: `Some(true)` approves, `Some(false)` denies.
+    async fn decide(&self, request: &PendingApproval) -> Option<bool>;
+}
+
+/// Backend that decides requests by tool name without human input.
+#[derive(Debug, Clone, Default)]
+pub struct AutoApproveBackend {
+    approved: HashSet<String>,
+    denied: HashSet<String>,
+    default: Option<bool>,
+}
+
+impl AutoApproveBackend {
+    /// Creates a backend that abstains on every tool.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a backend that approves every tool.
+    #[must_use]
+    pub fn approve_all() -> Self {
+        Self::new().with_default(Some(true))
+    }
+
+    /// Always approves the named tool.
+    #[must_use]
+    pub fn approve(mut self, tool_name: impl Into<String>) -> Self {
+        self.approved.insert(tool_name.into());
+        self
+    }
+
+    /// Always denies the named tool.
+    #[must_use]
+    pub fn deny(mut self, tool_name: impl Into<String>) -> Self {
+        self.denied.insert(tool_name.into());
+        self
+    }
+
+    /// Sets the decision for tools not listed explicitly.
+    #[must_use]
+    pub fn with_default(mut self, decision: Option<bool>) -> Self {
+        self.default = decision;
+        self
+    }
+}
+
+#[async_trait]
+impl ApprovalBackend for AutoApproveBackend {
+    async fn decide(&self, request: &PendingApproval) -> Option<bool> {
+        if self.denied.contains(&request.tool_name) {
+            Some(false)
+        } else if self.approved.contains(&request.tool_name) {
+            Some(true)
+        } else {
+            self.default
+        }
+    }
+}
+
+/// A request delivered by a [`ChannelApprovalBackend`].
+#[derive(Debug)]
+pub struct ApprovalPrompt {
+    /// The request to decide.
+    pub request: PendingApproval,
+    responder: oneshot::Sender<bool>,
+}
+
+impl ApprovalPrompt {
+    /// Approves the request.
+    pub fn approve(self) {
+        let _ = self.responder.send(true);
+    }
+
+    /// Denies the request.
+    pub fn deny(self) {
+        let _ = self.responder.send(false);
+    }
+}
+
+/// Backend that forwards requests over a channel, e.g. to a UI task.
+///
+/// Dropping a prompt without answering abstains.
+#[derive(Debug, Clone)]
+pub struct ChannelApprovalBackend {
+    tx: mpsc::Sender<ApprovalPrompt>,
+}
+
+impl ChannelApprovalBackend {
+    /// Creates a backend and the receiver its prompts are delivered to.
+    #[must_use]
+    pub fn new(buffer: usize) -> (Self, mpsc::Receiver<ApprovalPrompt>) {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        (Self { tx }, rx)
+    }
+}
+
+#[async_trait]
+impl ApprovalBackend for ChannelApprovalBackend {
+    async fn decide(&self, request: &PendingApproval) -> Option<bool> {
+        let (responder, response) = oneshot::channel();
+        let prompt = ApprovalPrompt {
+            request: request.clone(),
+            responder,
+        };
+        self.tx.send(prompt).await.ok()?;
+        response.await.ok()
+    }
+}
+
+/// Backend that posts requests to a webhook.
+///
+/// The request is sent as JSON. A response body of `{"approved": bool}`
+/// decides it; any other successful response abstains, so the receiver
+/// can answer later through [`ApprovalService::approve`] or
+/// [`ApprovalService::deny`] using the posted `request_id`.
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Clone)]
+pub struct WebhookApprovalBackend {
+    client: reqwest::Client,
+    url: String,
+    headers: HashMap<String, String>,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookApprovalBackend {
+    /// Creates a backend posting to `url`.
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Adds a header to every request.
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
+#[cfg(feature = "webhooks")]
+#[async_trait]
+impl ApprovalBackend for WebhookApprovalBackend {
+    async fn decide(&self, request: &PendingApproval) -> Option<bool> {
+        let mut builder = self.client.post(&self.url).json(request);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+
+        let response = match builder.send().await.and_then(reqwest::Response::error_for_status) {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!(url = %self.url, error = %e, "Approval webhook failed");
+                return None;
+            }
+        };
+        let body: serde_json::Value = response.json().await.ok()?;
+        body.get("approved").and_then(serde_json::Value::as_bool)
+    }
+}
+
 /// An approval request.
 #[derive(Debug)]
 struct ApprovalRequest {
@@ -38,10 +260,25 @@ struct ApprovalRequest {
 }
 
 /// Service for managing approval requests.
+///
+/// With an [`ApprovalQueueStore`] attached, pending requests are saved until
+/// decided. Requests still saved when the service is created are
+/// [recovered](Self::recovered_requests): they can be decided with
+/// [`approve`](Self::approve) and [`deny`](Self::deny) while nothing waits
+/// on them, and a later [`request`](Self::request) with the same ID
+/// resolves with that decision straight away.
 #[derive(Default)]
 pub struct ApprovalService {
     /// Pending requests.
     requests: RwLock<HashMap<Uuid, ApprovalRequest>>,
+    /// Optional out-of-process backend.
+    backend: Option<Arc<dyn ApprovalBackend>>,
+    /// Durable copy of the pending requests, if attached.
+    queue: Option<Arc<dyn ApprovalQueueStore>>,
+    /// Requests loaded from the queue that nothing waits on yet.
+    recovered: RwLock<HashMap<Uuid, PendingApproval>>,
+    /// Decisions made on recovered requests, awaiting a new request.
+    recovered_decisions: RwLock<HashMap<Uuid, bool>>,
 }
 
 impl ApprovalService {
@@ -51,6 +288,51 @@ impl ApprovalService {
         Self::default()
     }
 
+    /// Sets the backend that decides requests alongside in-process calls.
+    #[must_use]
+    pub fn with_backend(mut self, backend: Arc<dyn ApprovalBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Saves pending requests to `queue`, after recovering the requests it
+    /// holds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the saved requests cannot be loaded.
+    pub fn with_queue_store(mut self, queue: Arc<dyn ApprovalQueueStore>) -> Result<Self, StageflowError> {
+        let recovered = queue.load()?.into_iter().map(|request| (request.request_id, request)).collect();
+        self.recovered = RwLock::new(recovered);
+        self.queue = Some(queue);
+        Ok(self)
+    }
+
+    /// Returns requests recovered from the queue store that have not been
+    /// decided or requested again.
+    #[must_use]
+    pub fn recovered_requests(&self) -> Vec<PendingApproval> {
+        self.recovered.read().values().cloned().collect()
+    }
+
+    fn unqueue(&self, request_id: Uuid) {
+        if let Some(queue) = &self.queue {
+            if let Err(e) = queue.remove(request_id) {
+                warn!(request_id = %request_id, error = %e, "Failed to remove persisted approval request");
+            }
+        }
+    }
+
+    /// Records a decision on a recovered request.
+    fn decide_recovered(&self, request_id: Uuid, approved: bool) -> bool {
+        if self.recovered.write().remove(&request_id).is_none() {
+            return false;
+        }
+        self.recovered_decisions.write().insert(request_id, approved);
+        self.unqueue(request_id);
+        true
+    }
+
     /// Requests approval for a tool execution.
     ///
     /// Returns a future that resolves when the approval is decided.
@@ -60,37 +342,66 @@ impl ApprovalService {
         message: &str,
         timeout: Duration,
     ) -> Result<bool, ApprovalStatus> {
-        let request_id = Uuid::new_v4();
+        self.request(PendingApproval::new(tool_name, message), timeout)
+            .await
+    }
+
+    /// Requests approval for a prepared request.
+    ///
+    /// Resolves with the first decision from the backend or from
+    /// [`approve`](Self::approve)/[`deny`](Self::deny), or with
+    /// [`ApprovalStatus::TimedOut`] after `timeout`.
+    pub async fn request(
+        &self,
+        request: PendingApproval,
+        timeout: Duration,
+    ) -> Result<bool, ApprovalStatus> {
+        let request_id = request.request_id;
+        if let Some(approved) = self.recovered_decisions.write().remove(&request_id) {
+            return Ok(approved);
+        }
+        self.recovered.write().remove(&request_id);
+        if let Some(queue) = &self.queue {
+            if let Err(e) = queue.save(&request) {
+                warn!(request_id = %request_id, error = %e, "Failed to persist approval request");
+            }
+        }
         let (tx, rx) = oneshot::channel();
 
         {
-            let request = ApprovalRequest {
+            let pending = ApprovalRequest {
                 id: request_id,
-                tool_name: tool_name.to_string(),
-                message: message.to_string(),
+                tool_name: request.tool_name.clone(),
+                message: request.message.clone(),
                 created_at: Instant::now(),
                 response_tx: Some(tx),
             };
-            self.requests.write().insert(request_id, request);
+            self.requests.write().insert(request_id, pending);
         }
 
-        // Wait for response with timeout
-        match tokio::time::timeout(timeout, rx).await {
-            Ok(Ok(approved)) => {
-                self.requests.write().remove(&request_id);
-                Ok(approved)
-            }
-            Ok(Err(_)) => {
-                // Channel closed
-                self.requests.write().remove(&request_id);
-                Err(ApprovalStatus::Cancelled)
+        let decision = async {
+            // A closed channel means the request was cancelled
+            let mut manual = std::pin::pin!(async { rx.await.map_err(|_| ApprovalStatus::Cancelled) });
+            if let Some(backend) = &self.backend {
+                tokio::select! {
+                    result = &mut manual => return result,
+                    decision = backend.decide(&request) => {
+                        if let Some(approved) = decision {
+                            return Ok(approved);
+                        }
+                    }
+                }
             }
-            Err(_) => {
-                // Timeout
-                self.requests.write().remove(&request_id);
-                Err(ApprovalStatus::TimedOut)
-            }
-        }
+            manual.await
+        };
+
+        // Wait for response with timeout
+        let result = tokio::time::timeout(timeout, decision)
+            .await
+            .unwrap_or(Err(ApprovalStatus::TimedOut));
+        self.requests.write().remove(&request_id);
+        self.unqueue(request_id);
+        result
     }
 
     /// Approves a pending request.
@@ -101,7 +412,7 @@ impl ApprovalService {
                 return true;
             }
         }
-        false
+        self.decide_recovered(request_id, true)
     }
 
     /// Denies a pending request.
@@ -112,12 +423,17 @@ impl ApprovalService {
                 return true;
             }
         }
-        false
+        self.decide_recovered(request_id, false)
     }
 
-    /// Cancels a pending request.
+    /// Cancels a pending or recovered request.
     pub fn cancel(&self, request_id: Uuid) -> bool {
-        self.requests.write().remove(&request_id).is_some()
+        let cancelled = self.requests.write().remove(&request_id).is_some()
+            || self.recovered.write().remove(&request_id).is_some();
+        if cancelled {
+            self.unqueue(request_id);
+        }
+        cancelled
     }
 
     /// Returns the number of pending requests.
@@ -137,7 +453,9 @@ impl std::fmt::Debug for ApprovalService {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ApprovalService")
             .field("pending_count", &self.pending_count())
-            .finish()
+            .field("has_backend", &self.backend.is_some())
+            .field("recovered_count", &self.recovered.read().len())
+            .finish_non_exhaustive()
     }
 }
 
@@ -221,5 +539,71 @@ mod tests {
             .await;
 
         assert_eq!(result, Err(ApprovalStatus::TimedOut));
+        assert_eq!(service.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_auto_approve_backend() {
+        let backend = AutoApproveBackend::new().approve("search").deny("delete");
+        let service = ApprovalService::new().with_backend(Arc::new(backend));
+
+        let approved = service
+            .request_approval("search", "message", Duration::from_secs(5))
+            .await;
+        let denied = service
+            .request_approval("delete", "message", Duration::from_secs(5))
+            .await;
+        // Abstains, so nobody decides before the timeout
+        let undecided = service
+            .request_approval("other", "message", Duration::from_millis(50))
+            .await;
+
+        assert_eq!(approved, Ok(true));
+        assert_eq!(denied, Ok(false));
+        assert_eq!(undecided, Err(ApprovalStatus::TimedOut));
+    }
+
+    #[tokio::test]
+    async fn test_channel_backend() {
+        let (backend, mut prompts) = ChannelApprovalBackend::new(4);
+        let service = ApprovalService::new().with_backend(Arc::new(backend));
+
+        tokio::spawn(async move {
+            while let Some(prompt) = prompts.recv().await {
+                if prompt.request.tool_name == "send_email" {
+                    prompt.approve();
+                } else {
+                    prompt.deny();
+                }
+            }
+        });
+
+        let request = PendingApproval::new("send_email", "Send it?");
+        let request_id = request.request_id;
+        assert_eq!(service.request(request, Duration::from_secs(5)).await, Ok(true));
+        assert!(!service.pending_requests().contains(&request_id));
+        assert_eq!(
+            service
+                .request_approval("wire_money", "Send it?", Duration::from_secs(5))
+                .await,
+            Ok(false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_process_decision_wins_over_abstaining_backend() {
+        let (backend, _prompts) = ChannelApprovalBackend::new(4);
+        let service = Arc::new(ApprovalService::new().with_backend(Arc::new(backend)));
+        let service_clone = service.clone();
+
+        let request = PendingApproval::new("tool", "message");
+        let request_id = request.request_id;
+        let handle = tokio::spawn(async move {
+            service_clone.request(request, Duration::from_secs(5)).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(service.approve(request_id));
+        assert_eq!(handle.await.unwrap(), Ok(true));
     }
 }
@@ -1,5 +1,7 @@
 //! Tool definitions and I/O types.
 
+use crate::contracts::validate_json_schema;
+use crate::errors::ToolError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -13,7 +15,8 @@ pub struct ToolDefinition {
     pub action_type: String,
     /// Description of what the tool does.
     pub description: String,
-    /// JSON Schema for input validation.
+    /// JSON Schema for input validation. The default empty schema accepts
+    /// any input.
     pub input_schema: serde_json::Value,
     /// Allowed behaviors (empty = all allowed).
     pub allowed_behaviors: Vec<String>,
@@ -23,8 +26,14 @@ pub struct ToolDefinition {
     pub approval_message: Option<String>,
     /// Whether the tool supports undo.
     pub undoable: bool,
+    /// Whether repeated calls have no additional side effects, so the tool
+    /// may run for real during a dry run.
+    pub idempotent: bool,
     /// Artifact type produced by the tool.
     pub artifact_type: Option<String>,
+    /// Static configuration passed to every call, e.g. endpoints and
+    /// credentials. `secret://` references in it are resolved at each call.
+    pub config: serde_json::Value,
 }
 
 impl ToolDefinition {
@@ -40,7 +49,9 @@ impl ToolDefinition {
             requires_approval: false,
             approval_message: None,
             undoable: false,
+            idempotent: false,
             artifact_type: None,
+            config: serde_json::Value::Null,
         }
     }
 
@@ -80,6 +91,35 @@ impl ToolDefinition {
         self
     }
 
+    /// Marks the tool as idempotent, allowing it to run during dry runs.
+    #[must_use]
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// Sets the static configuration handed to the tool in
+    /// [`ToolInput::config`].
+    #[must_use]
+    pub fn with_config(mut self, config: serde_json::Value) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Validates an input payload against the input schema.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::InvalidInput`] listing every violation.
+    pub fn validate_input(&self, payload: &serde_json::Value) -> Result<(), ToolError> {
+        let violations = validate_json_schema(&self.input_schema, payload);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ToolError::invalid_input(&self.name, violations))
+        }
+    }
+
     /// Checks if a behavior is allowed.
     #[must_use]
     pub fn is_behavior_allowed(&self, behavior: &str) -> bool {
@@ -105,6 +145,10 @@ pub struct ToolInput {
     /// The request ID.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<Uuid>,
+    /// The definition's configuration with secrets resolved, filled in by
+    /// the executor. Never serialized, so secrets stay out of records.
+    #[serde(default, skip_serializing)]
+    pub config: serde_json::Value,
 }
 
 impl ToolInput {
@@ -118,6 +162,7 @@ impl ToolInput {
             behavior: None,
             pipeline_run_id: None,
             request_id: None,
+            config: serde_json::Value::Null,
         }
     }
 
@@ -138,6 +183,7 @@ impl ToolInput {
             behavior: execution_mode,
             pipeline_run_id,
             request_id,
+            config: serde_json::Value::Null,
         }
     }
 
@@ -284,6 +330,27 @@ mod tests {
         assert!(!def.is_behavior_allowed("development"));
     }
 
+    #[test]
+    fn test_validate_input() {
+        let def = ToolDefinition::new("email", "send_email").with_input_schema(serde_json::json!({
+            "type": "object",
+            "required": ["to"],
+            "properties": {"to": {"type": "string"}}
+        }));
+
+        assert!(def.validate_input(&serde_json::json!({"to": "a@b.c"})).is_ok());
+        let err = def.validate_input(&serde_json::json!({"to": 1})).unwrap_err();
+        match err {
+            ToolError::InvalidInput { ref name, ref violations } => {
+                assert_eq!(name, "email");
+                assert_eq!(violations[0].path, "$.to");
+            }
+            ref other => panic!("unexpected error: {other}"),
+        }
+        assert!(err.to_string().contains("$.to: expected string"));
+        assert!(ToolDefinition::new("any", "any").validate_input(&serde_json::json!(42)).is_ok());
+    }
+
     #[test]
     fn test_tool_input_creation() {
         let input = ToolInput::new("my_tool", serde_json::json!({"arg": "value"}));
@@ -0,0 +1,116 @@
+//! Dry-run handling for tool execution.
+
+use super::{ToolDefinition, ToolInput, ToolOutput};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Produces the output returned in place of a blocked tool call.
+pub type DryRunSimulator = Arc<dyn Fn(&ToolInput) -> ToolOutput + Send + Sync>;
+
+/// Decides which tools run during a dry run and what blocked tools return.
+///
+/// Tools marked [`ToolDefinition::idempotent`] run normally. Other tools
+/// are blocked unless allowed by action type, and return the output of
+/// their registered simulator, or by default a successful output echoing
+/// the call:
+///
+/// ```json
+/// {"simulated": true, "tool": "send_email", "payload": {...}}
+/// ```
+#[derive(Clone, Default)]
+pub struct DryRunGuard {
+    simulators: HashMap<String, DryRunSimulator>,
+    allowed: HashSet<String>,
+}
+
+impl DryRunGuard {
+    /// Creates a guard that blocks every non-idempotent tool.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets a tool run during dry runs even though it is not marked
+    /// idempotent, e.g. because it talks to a sandbox.
+    #[must_use]
+    pub fn allow(mut self, action_type: impl Into<String>) -> Self {
+        self.allowed.insert(action_type.into());
+        self
+    }
+
+    /// Registers the simulated output for a blocked tool.
+    #[must_use]
+    pub fn with_simulator<F>(mut self, action_type: impl Into<String>, simulator: F) -> Self
+    where
+        F: Fn(&ToolInput) -> ToolOutput + Send + Sync + 'static,
+    {
+        self.simulators
+            .insert(action_type.into(), Arc::new(simulator));
+        self
+    }
+
+    /// Returns true if the tool must not run during a dry run.
+    #[must_use]
+    pub fn blocks(&self, definition: &ToolDefinition) -> bool {
+        !definition.idempotent && !self.allowed.contains(&definition.action_type)
+    }
+
+    /// Returns the simulated output for a blocked call.
+    #[must_use]
+    pub fn simulate(&self, input: &ToolInput, definition: &ToolDefinition) -> ToolOutput {
+        match self.simulators.get(&definition.action_type) {
+            Some(simulator) => simulator(input),
+            None => ToolOutput::ok(Some(serde_json::json!({
+                "simulated": true,
+                "tool": input.tool_name,
+                "payload": input.payload,
+            }))),
+        }
+    }
+}
+
+impl std::fmt::Debug for DryRunGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut simulated: Vec<&String> = self.simulators.keys().collect();
+        simulated.sort();
+        f.debug_struct("DryRunGuard")
+            .field("simulators", &simulated)
+            .field("allowed", &self.allowed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_non_idempotent_tools() {
+        let guard = DryRunGuard::new().allow("sandbox_deploy");
+
+        assert!(guard.blocks(&ToolDefinition::new("email", "send_email")));
+        assert!(!guard.blocks(&ToolDefinition::new("search", "search").idempotent()));
+        assert!(!guard.blocks(&ToolDefinition::new("deploy", "sandbox_deploy")));
+    }
+
+    #[test]
+    fn test_simulated_outputs() {
+        let guard = DryRunGuard::new().with_simulator("charge_card", |input| {
+            ToolOutput::ok(Some(serde_json::json!({
+                "charge_id": "ch_dry_run",
+                "amount": input.payload["amount"],
+            })))
+        });
+
+        let charge = ToolInput::new("charge", serde_json::json!({"amount": 5}));
+        let output = guard.simulate(&charge, &ToolDefinition::new("charge", "charge_card"));
+        assert_eq!(output.data.unwrap()["charge_id"], "ch_dry_run");
+
+        let email = ToolInput::new("email", serde_json::json!({"to": "a@b.c"}));
+        let output = guard.simulate(&email, &ToolDefinition::new("email", "send_email"));
+        assert!(output.success);
+        let data = output.data.unwrap();
+        assert_eq!(data["simulated"], true);
+        assert_eq!(data["payload"]["to"], "a@b.c");
+    }
+}
@@ -1,13 +1,23 @@
 //! Advanced tool executor with approval and undo support.
 
-use super::{ApprovalService, Tool, ToolDefinition, ToolInput, ToolOutput, ToolRegistry, UndoMetadata, UndoStore};
+use super::{
+    ApprovalDecision, ApprovalService, ApprovalStatus, DryRunGuard, PendingApproval, Tool,
+    ToolCallRecord, ToolCallStatus, ToolDefinition, ToolInput, ToolOutput, ToolRegistry,
+    UndoMetadata, UndoStore,
+};
 use crate::context::ExecutionContext;
 use crate::errors::ToolError;
+use crate::pipeline::hash_parameters;
+use crate::secrets::SecretResolver;
 use async_trait::async_trait;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use tracing::warn;
 
+/// Default number of calls [`AdvancedToolExecutor::execute_many`] runs at once.
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
 /// Advanced tool executor with full lifecycle support.
 pub struct AdvancedToolExecutor {
     /// Tool registry.
@@ -18,6 +28,12 @@ pub struct AdvancedToolExecutor {
     undo_store: Arc<UndoStore>,
     /// Default approval timeout.
     approval_timeout: Duration,
+    /// Maximum concurrent calls in `execute_many`.
+    max_concurrency: usize,
+    /// Permits shared by all `execute_many` batches on this executor.
+    concurrency: Arc<Semaphore>,
+    /// Tools blocked and simulated during dry runs.
+    dry_run_guard: DryRunGuard,
 }
 
 impl AdvancedToolExecutor {
@@ -33,6 +49,9 @@ impl AdvancedToolExecutor {
             approval_service,
             undo_store,
             approval_timeout: Duration::from_secs(300), // 5 minutes default
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            concurrency: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENCY)),
+            dry_run_guard: DryRunGuard::default(),
         }
     }
 
@@ -43,12 +62,104 @@ impl AdvancedToolExecutor {
         self
     }
 
+    /// Sets which tools run during dry runs and what blocked tools return.
+    #[must_use]
+    pub fn with_dry_run_guard(mut self, guard: DryRunGuard) -> Self {
+        self.dry_run_guard = guard;
+        self
+    }
+
+    /// Sets how many calls [`Self::execute_many`] runs at once (minimum 1).
+    ///
+    /// The limit is shared by concurrent batches on the same executor.
+    #[must_use]
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = max.max(1);
+        self.concurrency = Arc::new(Semaphore::new(self.max_concurrency));
+        self
+    }
+
+    /// Returns the concurrency limit for [`Self::execute_many`].
+    #[must_use]
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Executes independent tool calls concurrently.
+    ///
+    /// Each call goes through the full [`Self::execute`] lifecycle, with at
+    /// most [`Self::max_concurrency`] running at once; a call waiting for
+    /// approval holds its slot. Results are returned in input order, one per
+    /// call, so a failing call does not affect the others.
+    pub async fn execute_many<C: ExecutionContext>(
+        &self,
+        calls: Vec<(ToolInput, ToolDefinition)>,
+        ctx: &C,
+    ) -> Vec<Result<ToolOutput, ToolError>> {
+        let runs = calls.into_iter().map(|(input, definition)| async move {
+            let _permit = self
+                .concurrency
+                .acquire()
+                .await
+                .expect("executor semaphore is never closed");
+            self.execute(input, &definition, ctx).await
+        });
+        futures::future::join_all(runs).await
+    }
+
     /// Executes a tool with full lifecycle.
+    ///
+    /// The input payload is validated against the definition's input schema
+    /// before anything else runs. Every invocation, including denied ones, is recorded on the context's
+    /// tool transcript. When the context is a dry run, tools blocked by the
+    /// [`DryRunGuard`] are not run and return a simulated output without
+    /// requesting approval. The definition's [`config`](ToolDefinition::config)
+    /// is handed to the tool with `secret://` references resolved through
+    /// the context's resolver, and resolved values are redacted from the
+    /// recorded error.
     pub async fn execute<C: ExecutionContext>(
         &self,
         input: ToolInput,
         definition: &ToolDefinition,
         ctx: &C,
+    ) -> Result<ToolOutput, ToolError> {
+        let start = Instant::now();
+        let mut trace = CallTrace::default();
+        let result = self
+            .run_lifecycle(&input, definition, ctx, &mut trace)
+            .await;
+
+        let (status, error) = match &result {
+            Ok(_) if trace.simulated => (ToolCallStatus::Simulated, None),
+            Ok(output) if output.success => (ToolCallStatus::Completed, None),
+            Ok(output) => (ToolCallStatus::Failed, output.error.clone()),
+            Err(e) if trace.denied => (ToolCallStatus::Denied, Some(e.to_string())),
+            Err(e) => (ToolCallStatus::Failed, Some(e.to_string())),
+        };
+        let error = match (error, ctx.secrets()) {
+            (Some(error), Some(secrets)) => Some(secrets.redactor().redact(&error)),
+            (error, _) => error,
+        };
+        ctx.record_tool_call(ToolCallRecord {
+            action_id: input.action_id,
+            tool_name: input.tool_name.clone(),
+            args_hash: hash_parameters(&input.payload, None),
+            stage: None,
+            status,
+            approval: trace.approval,
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+            error,
+        });
+
+        result
+    }
+
+    async fn run_lifecycle<C: ExecutionContext>(
+        &self,
+        input: &ToolInput,
+        definition: &ToolDefinition,
+        ctx: &C,
+        trace: &mut CallTrace,
     ) -> Result<ToolOutput, ToolError> {
         // Emit tool.invoked
         ctx.try_emit_event(
@@ -58,6 +169,57 @@ impl AdvancedToolExecutor {
                 "action_id": input.action_id.to_string(),
             })),
         );
+        Self::admit(input, definition, ctx, trace)?;
+
+        if ctx.is_dry_run() && self.dry_run_guard.blocks(definition) {
+            trace.simulated = true;
+            ctx.try_emit_event(
+                "tool.simulated",
+                Some(serde_json::json!({
+                    "tool": input.tool_name,
+                    "action_id": input.action_id.to_string(),
+                })),
+            );
+            return Ok(self.dry_run_guard.simulate(input, definition));
+        }
+        if definition.requires_approval {
+            self.await_approval(input, definition, ctx, trace).await?;
+        }
+        self.run_tool(input, definition, ctx).await
+    }
+
+    /// Rejects calls with invalid input, over the run's tool budget, or
+    /// with a behavior the definition does not allow.
+    fn admit<C: ExecutionContext>(
+        input: &ToolInput,
+        definition: &ToolDefinition,
+        ctx: &C,
+        trace: &mut CallTrace,
+    ) -> Result<(), ToolError> {
+        if let Err(e) = definition.validate_input(&input.payload) {
+            if let ToolError::InvalidInput { ref violations, .. } = e {
+                ctx.try_emit_event(
+                    "tool.invalid_input",
+                    Some(serde_json::json!({
+                        "tool": input.tool_name,
+                        "violations": violations,
+                    })),
+                );
+            }
+            return Err(e);
+        }
+
+        if let Err(reason) = ctx.reserve_tool_call() {
+            ctx.try_emit_event(
+                "tool.denied",
+                Some(serde_json::json!({
+                    "tool": input.tool_name,
+                    "reason": reason.budget_limit().map_or("run_cancelled", |_| "budget_exceeded"),
+                })),
+            );
+            trace.denied = true;
+            return Err(ToolError::denied(&input.tool_name, reason.to_string()));
+        }
 
         // Check behavior gating
         if let Some(ref behavior) = input.behavior {
@@ -71,71 +233,109 @@ impl AdvancedToolExecutor {
                     })),
                 );
 
+                trace.denied = true;
                 return Err(ToolError::denied(
                     &input.tool_name,
                     format!("Behavior '{}' not allowed", behavior),
                 ));
             }
         }
+        Ok(())
+    }
 
-        // Handle approval if required
-        if definition.requires_approval {
-            let message = definition
-                .approval_message
-                .as_deref()
-                .unwrap_or("Tool requires approval");
+    /// Requests approval for the call and waits for the decision.
+    async fn await_approval<C: ExecutionContext>(
+        &self,
+        input: &ToolInput,
+        definition: &ToolDefinition,
+        ctx: &C,
+        trace: &mut CallTrace,
+    ) -> Result<(), ToolError> {
+        let message = definition
+            .approval_message
+            .as_deref()
+            .unwrap_or("Tool requires approval");
+
+        let request = PendingApproval::with_id(input.action_id, &input.tool_name, message);
+        ctx.try_emit_event(
+            "approval.requested",
+            Some(serde_json::json!({
+                "tool": input.tool_name,
+                "message": message,
+                "request_id": request.request_id.to_string(),
+            })),
+        );
 
-            ctx.try_emit_event(
-                "approval.requested",
-                Some(serde_json::json!({
-                    "tool": input.tool_name,
-                    "message": message,
-                })),
-            );
+        let result = self
+            .approval_service
+            .request(request, self.approval_timeout)
+            .await;
+        let status = match result {
+            Ok(true) => ApprovalStatus::Approved,
+            Ok(false) => ApprovalStatus::Denied,
+            Err(ref status) => status.clone(),
+        };
+        ctx.try_emit_event(
+            "approval.resolved",
+            Some(serde_json::json!({
+                "tool": input.tool_name,
+                "request_id": input.action_id.to_string(),
+                "status": status.as_str(),
+            })),
+        );
 
-            match self
-                .approval_service
-                .request_approval(&input.tool_name, message, self.approval_timeout)
-                .await
-            {
-                Ok(true) => {
-                    ctx.try_emit_event(
-                        "approval.decided",
-                        Some(serde_json::json!({
-                            "tool": input.tool_name,
-                            "approved": true,
-                        })),
-                    );
-                }
-                Ok(false) => {
-                    ctx.try_emit_event(
-                        "approval.decided",
-                        Some(serde_json::json!({
-                            "tool": input.tool_name,
-                            "approved": false,
-                        })),
-                    );
+        match result {
+            Ok(true) => {
+                trace.approval = Some(ApprovalDecision::Approved);
+                ctx.try_emit_event(
+                    "approval.decided",
+                    Some(serde_json::json!({
+                        "tool": input.tool_name,
+                        "approved": true,
+                    })),
+                );
+            }
+            Ok(false) | Err(ApprovalStatus::Cancelled) => {
+                trace.approval = Some(ApprovalDecision::Denied);
+                trace.denied = true;
+                ctx.try_emit_event(
+                    "approval.decided",
+                    Some(serde_json::json!({
+                        "tool": input.tool_name,
+                        "approved": false,
+                    })),
+                );
 
-                    return Err(ToolError::approval_denied(&input.tool_name));
-                }
-                Err(status) => {
-                    ctx.try_emit_event(
-                        "tool.denied",
-                        Some(serde_json::json!({
-                            "tool": input.tool_name,
-                            "reason": "approval_timeout",
-                        })),
-                    );
+                return Err(ToolError::approval_denied(&input.tool_name));
+            }
+            Err(_) => {
+                trace.approval = Some(ApprovalDecision::TimedOut);
+                trace.denied = true;
+                ctx.try_emit_event(
+                    "tool.denied",
+                    Some(serde_json::json!({
+                        "tool": input.tool_name,
+                        "reason": "approval_timeout",
+                    })),
+                );
 
-                    return Err(ToolError::approval_timeout(
-                        &input.tool_name,
-                        input.action_id.to_string(),
-                        self.approval_timeout.as_secs_f64(),
-                    ));
-                }
+                return Err(ToolError::approval_timeout(
+                    &input.tool_name,
+                    input.action_id.to_string(),
+                    self.approval_timeout.as_secs_f64(),
+                ));
             }
         }
+        Ok(())
+    }
 
+    /// Runs the tool itself and keeps its undo metadata.
+    async fn run_tool<C: ExecutionContext>(
+        &self,
+        input: &ToolInput,
+        definition: &ToolDefinition,
+        ctx: &C,
+    ) -> Result<ToolOutput, ToolError> {
         // Emit tool.started
         ctx.try_emit_event(
             "tool.started",
@@ -149,7 +349,16 @@ impl AdvancedToolExecutor {
             .get_tool(&definition.action_type)
             .ok_or_else(|| ToolError::not_found(&definition.action_type))?;
 
-        let output = match tool.execute(input.clone()).await {
+        let mut call = input.clone();
+        if !definition.config.is_null() {
+            let resolved = match ctx.secrets() {
+                Some(resolver) => resolver.resolve(&definition.config).await,
+                None => SecretResolver::new().resolve(&definition.config).await,
+            };
+            call.config = resolved.map_err(|e| ToolError::execution_failed(&input.tool_name, e.to_string()))?;
+        }
+
+        let output = match tool.execute(call).await {
             Ok(out) => out,
             Err(e) => {
                 ctx.try_emit_event(
@@ -229,10 +438,20 @@ impl AdvancedToolExecutor {
     }
 }
 
+/// Lifecycle facts gathered for the transcript record.
+#[derive(Default)]
+struct CallTrace {
+    approval: Option<ApprovalDecision>,
+    denied: bool,
+    simulated: bool,
+}
+
 impl std::fmt::Debug for AdvancedToolExecutor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AdvancedToolExecutor")
             .field("approval_timeout", &self.approval_timeout)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("dry_run_guard", &self.dry_run_guard)
             .finish()
     }
 }
@@ -240,7 +459,12 @@ impl std::fmt::Debug for AdvancedToolExecutor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::context::{DictContextAdapter, PipelineContext, RunIdentity};
+    use crate::context::{
+        ContextSnapshot, DictContextAdapter, PipelineContext, RunIdentity, StageContext,
+        StageInputs,
+    };
+    use crate::events::CollectingEventSink;
+    use crate::tools::AutoApproveBackend;
     use std::collections::HashMap;
 
     struct TestTool {
@@ -320,4 +544,293 @@ mod tests {
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), ToolError::Denied { .. }));
     }
+
+    #[tokio::test]
+    async fn test_execute_records_transcript() {
+        let executor = create_executor();
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()));
+        let stage_ctx = StageContext::new(
+            pipeline_ctx.clone(),
+            "agent",
+            StageInputs::default(),
+            ContextSnapshot::new(),
+        );
+
+        let payload = serde_json::json!({"x": 1});
+        let input = ToolInput::new("test", payload.clone());
+        let definition = ToolDefinition::new("test", "test_action");
+        executor
+            .execute(input, &definition, &stage_ctx)
+            .await
+            .unwrap();
+
+        let mut denied = ToolInput::new("test", serde_json::json!({}));
+        denied.behavior = Some("development".to_string());
+        let gated = ToolDefinition::new("test", "test_action")
+            .with_allowed_behaviors(vec!["production".to_string()]);
+        let _ = executor.execute(denied, &gated, &stage_ctx).await;
+
+        let transcript = pipeline_ctx.tool_transcript();
+        assert_eq!(transcript.len(), 2);
+        let first = &transcript.calls[0];
+        assert_eq!(first.status, ToolCallStatus::Completed);
+        assert_eq!(first.stage.as_deref(), Some("agent"));
+        assert_eq!(first.args_hash, hash_parameters(&payload, None));
+        assert_eq!(transcript.calls[1].status, ToolCallStatus::Denied);
+        assert!(transcript.calls[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tool_budget_denies_extra_calls_and_cancels_run() {
+        let executor = create_executor();
+        let sink = Arc::new(CollectingEventSink::new());
+        let pipeline_ctx = Arc::new(
+            PipelineContext::new(RunIdentity::new())
+                .with_event_sink(sink.clone())
+                .with_budget(crate::pipeline::RunBudget::new().with_max_tool_calls(1)),
+        );
+        let stage_ctx = StageContext::new(pipeline_ctx.clone(), "agent", StageInputs::default(), ContextSnapshot::new());
+        let definition = ToolDefinition::new("test", "test_action");
+
+        let call = || ToolInput::new("test", serde_json::json!({}));
+        assert!(executor.execute(call(), &definition, &stage_ctx).await.is_ok());
+        let err = executor.execute(call(), &definition, &stage_ctx).await.unwrap_err();
+        assert!(matches!(err, ToolError::Denied { .. }));
+
+        assert!(pipeline_ctx.is_cancelled());
+        assert_eq!(
+            pipeline_ctx.cancel_cause().unwrap().budget_limit(),
+            Some(crate::pipeline::BudgetLimit::ToolCalls)
+        );
+        assert_eq!(sink.events_of_type("pipeline.budget_exceeded").len(), 1);
+        assert_eq!(pipeline_ctx.budget_usage().unwrap().tool_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_many_bounded_and_ordered() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct SlowTool {
+            definition: ToolDefinition,
+            running: Arc<AtomicUsize>,
+            peak: Arc<AtomicUsize>,
+        }
+
+        #[async_trait]
+        impl Tool for SlowTool {
+            fn action_type(&self) -> &str {
+                &self.definition.action_type
+            }
+
+            fn name(&self) -> &str {
+                &self.definition.name
+            }
+
+            fn definition(&self) -> ToolDefinition {
+                self.definition.clone()
+            }
+
+            async fn execute(&self, input: ToolInput) -> Result<ToolOutput, ToolError> {
+                let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(now, Ordering::SeqCst);
+                // Later calls finish first to check ordering
+                let n = input.payload["n"].as_u64().unwrap();
+                tokio::time::sleep(Duration::from_millis(40 - n * 5)).await;
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                if n == 3 {
+                    return Err(ToolError::execution_failed("slow", "n is 3"));
+                }
+                Ok(ToolOutput::ok(Some(input.payload)))
+            }
+        }
+
+        let peak = Arc::new(AtomicUsize::new(0));
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register(Box::new(SlowTool {
+            definition: ToolDefinition::new("slow", "slow"),
+            running: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+        }));
+        let executor = AdvancedToolExecutor::new(
+            registry,
+            Arc::new(ApprovalService::new()),
+            Arc::new(UndoStore::default()),
+        )
+        .with_max_concurrency(2);
+        let ctx = DictContextAdapter::new(HashMap::new());
+
+        let calls = (0..6)
+            .map(|n| {
+                (
+                    ToolInput::new("slow", serde_json::json!({ "n": n })),
+                    ToolDefinition::new("slow", "slow"),
+                )
+            })
+            .collect();
+        let results = executor.execute_many(calls, &ctx).await;
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        for (n, result) in results.iter().enumerate() {
+            match result {
+                Err(e) => {
+                    assert_eq!(n, 3);
+                    assert!(matches!(e, ToolError::ExecutionFailed { .. }));
+                }
+                Ok(output) => assert_eq!(output.data.as_ref().unwrap()["n"], n),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_simulates_non_idempotent_tools() {
+        let executor = create_executor().with_dry_run_guard(
+            DryRunGuard::new().with_simulator("test_action", |_| {
+                ToolOutput::ok(Some(serde_json::json!("fake")))
+            }),
+        );
+        let pipeline_ctx = Arc::new(PipelineContext::new(RunIdentity::new()).with_dry_run());
+        let stage_ctx = StageContext::new(
+            pipeline_ctx.clone(),
+            "agent",
+            StageInputs::default(),
+            ContextSnapshot::new(),
+        );
+        assert!(stage_ctx.is_dry_run());
+
+        // Approval would time out if the call were not simulated first
+        let guarded = ToolDefinition::new("test", "test_action")
+            .requires_approval_with_message("Really?")
+            .undoable();
+        let input = ToolInput::new("test", serde_json::json!({"x": 1}));
+        let output = executor.execute(input, &guarded, &stage_ctx).await.unwrap();
+        assert_eq!(output.data, Some(serde_json::json!("fake")));
+
+        let safe = ToolDefinition::new("test", "test_action").idempotent();
+        let input = ToolInput::new("test", serde_json::json!({"x": 2}));
+        let output = executor.execute(input, &safe, &stage_ctx).await.unwrap();
+        assert_eq!(output.data.unwrap()["echo"]["x"], 2);
+
+        let transcript = pipeline_ctx.tool_transcript();
+        assert_eq!(transcript.calls[0].status, ToolCallStatus::Simulated);
+        assert_eq!(transcript.calls[0].approval, None);
+        assert_eq!(transcript.calls[1].status, ToolCallStatus::Completed);
+        assert!(transcript.unsuccessful().is_empty());
+        assert!(executor.undo_store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input_rejected_before_execution() {
+        let executor = create_executor();
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone());
+        let definition = ToolDefinition::new("test", "test_action").with_input_schema(serde_json::json!({
+            "type": "object",
+            "required": ["x"],
+            "properties": {"x": {"type": "integer"}}
+        }));
+
+        let input = ToolInput::new("test", serde_json::json!({"x": "one"}));
+        let err = executor.execute(input, &definition, &ctx).await.unwrap_err();
+
+        assert!(matches!(err, ToolError::InvalidInput { ref violations, .. } if violations[0].path == "$.x"));
+        assert!(sink.events_of_type("tool.started").is_empty());
+        assert_eq!(sink.events_of_type("tool.invalid_input").len(), 1);
+        assert_eq!(ctx.tool_transcript().calls[0].status, ToolCallStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_approval_backend_resolves_with_events() {
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register(Box::new(TestTool {
+            action_type: "test_action".to_string(),
+            name: "test".to_string(),
+        }));
+        let backend = AutoApproveBackend::new().deny("blocked");
+        let executor = AdvancedToolExecutor::new(
+            registry,
+            Arc::new(ApprovalService::new().with_backend(Arc::new(backend))),
+            Arc::new(UndoStore::default()),
+        )
+        .with_approval_timeout(Duration::from_millis(50));
+
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = PipelineContext::new(RunIdentity::new()).with_event_sink(sink.clone());
+        let definition = ToolDefinition::new("test", "test_action").requires_approval_with_message("Run it?");
+
+        let denied = ToolInput::new("blocked", serde_json::json!({}));
+        let err = executor.execute(denied, &definition, &ctx).await.unwrap_err();
+        assert!(matches!(err, ToolError::ApprovalDenied { .. }));
+
+        // The backend abstains, so the request times out
+        let undecided = ToolInput::new("test", serde_json::json!({}));
+        let action_id = undecided.action_id.to_string();
+        let err = executor.execute(undecided, &definition, &ctx).await.unwrap_err();
+        assert!(matches!(err, ToolError::ApprovalTimeout { ref request_id, .. } if *request_id == action_id));
+
+        let resolved = sink.events_of_type("approval.resolved");
+        let statuses: Vec<_> = resolved
+            .iter()
+            .map(|(_, data)| data.as_ref().unwrap()["status"].clone())
+            .collect();
+        assert_eq!(statuses, vec!["denied", "timed_out"]);
+        assert_eq!(sink.events_of_type("approval.requested").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_config_secrets_resolved_and_redacted() {
+        struct KeyTool;
+
+        #[async_trait]
+        impl Tool for KeyTool {
+            fn action_type(&self) -> &'static str {
+                "key_action"
+            }
+
+            fn name(&self) -> &'static str {
+                "key"
+            }
+
+            fn definition(&self) -> ToolDefinition {
+                ToolDefinition::new("key", "key_action")
+            }
+
+            async fn execute(&self, input: ToolInput) -> Result<ToolOutput, ToolError> {
+                let key = input.config["api_key"].as_str().unwrap_or_default();
+                Err(ToolError::execution_failed("key", format!("key {key} rejected")))
+            }
+
+            async fn undo(&self, _metadata: &UndoMetadata) -> Result<(), ToolError> {
+                Ok(())
+            }
+        }
+
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register(Box::new(KeyTool));
+        let executor = AdvancedToolExecutor::new(registry, Arc::new(ApprovalService::new()), Arc::new(UndoStore::default()));
+        let secrets = crate::secrets::InMemorySecretsProvider::new().with_secret("KEY", "sk-live-42");
+        let resolver = Arc::new(SecretResolver::new().with_provider("mem", Arc::new(secrets)));
+        let sink = Arc::new(CollectingEventSink::new());
+        let ctx = PipelineContext::new(RunIdentity::new())
+            .with_event_sink(sink.clone())
+            .with_secrets(resolver);
+        let definition = ToolDefinition::new("key", "key_action").with_config(serde_json::json!({ "api_key": "secret://mem/KEY" }));
+
+        let err = executor
+            .execute(ToolInput::new("key", serde_json::json!({})), &definition, &ctx)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("sk-live-42"));
+
+        let failed = sink.events_of_type("tool.failed");
+        assert_eq!(failed[0].1.as_ref().unwrap()["error"], "Tool execution failed: key - key [REDACTED] rejected");
+        assert!(!ctx.tool_transcript().calls[0].error.as_ref().unwrap().contains("sk-live-42"));
+
+        let missing = ToolDefinition::new("key", "key_action").with_config(serde_json::json!({ "api_key": "secret://mem/OTHER" }));
+        let err = executor
+            .execute(ToolInput::new("key", serde_json::json!({})), &missing, &ctx)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::ExecutionFailed { .. }));
+    }
 }
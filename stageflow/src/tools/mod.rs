@@ -3,22 +3,40 @@
 //! This module provides:
 //! - Tool definitions and registry
 //! - Tool input/output types
-//! - Approval and undo workflows
+//! - Approval and undo workflows, with pluggable approval backends and
+//!   durable approval queues and undo logs
 //! - Advanced tool executor
+//! - Per-run tool call transcripts
+//! - Dry-run simulation of side-effecting tools
 
 mod approval;
 mod definitions;
+mod dry_run;
 mod errors;
 mod executor;
+mod persistence;
 mod registry;
+mod transcript;
 mod undo;
 
-pub use approval::ApprovalService;
+#[cfg(feature = "webhooks")]
+pub use approval::WebhookApprovalBackend;
+pub use approval::{
+    clear_approval_service, get_approval_service, ApprovalBackend, ApprovalPrompt,
+    ApprovalService, ApprovalStatus, AutoApproveBackend, ChannelApprovalBackend, PendingApproval,
+};
 pub use definitions::{ToolDefinition, ToolInput, ToolOutput};
+pub use dry_run::{DryRunGuard, DryRunSimulator};
 pub use errors::*;
 pub use executor::AdvancedToolExecutor;
 pub use registry::{
     clear_tool_registry, get_tool_registry, register_tool, ResolvedToolCall, Tool, ToolRegistry,
     UnresolvedToolCall,
 };
-pub use undo::{UndoMetadata, UndoStore};
+pub use transcript::{ApprovalDecision, ToolCallRecord, ToolCallStatus, ToolTranscript};
+pub use persistence::{
+    ApprovalQueueStore, FileApprovalQueue, FileUndoPersistence, UndoPersistence, UndoRecord,
+};
+#[cfg(feature = "sqlite")]
+pub use persistence::{SqliteApprovalQueue, SqliteUndoPersistence};
+pub use undo::{clear_undo_store, get_undo_store, set_undo_store, UndoMetadata, UndoStore};
@@ -0,0 +1,458 @@
+//! Durable backing for undo metadata and pending approvals.
+//!
+//! [`UndoStore`](super::UndoStore) and
+//! [`ApprovalService`](super::ApprovalService) keep their state in memory;
+//! attaching an [`UndoPersistence`] or [`ApprovalQueueStore`] writes every
+//! change through, so undo logs and pending approvals survive a restart.
+//! The file implementations keep one JSON document per store and replace it
+//! atomically on each change, which suits the small, slowly changing state
+//! these stores hold. With the `sqlite` feature, `SqliteUndoPersistence` and
+//! `SqliteApprovalQueue` keep one row per entry instead, and can share a
+//! database file.
+
+use super::{PendingApproval, UndoMetadata};
+use crate::errors::StageflowError;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Undo metadata with its wall-clock expiry, as persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoRecord {
+    /// The undo metadata.
+    pub metadata: UndoMetadata,
+    /// When the entry expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Durable storage behind an [`UndoStore`](super::UndoStore).
+pub trait UndoPersistence: Send + Sync {
+    /// Saves a record, replacing any previous one for the same action.
+    fn save(&self, record: &UndoRecord) -> Result<(), StageflowError>;
+
+    /// Removes the record of an action; removing a missing record is not an
+    /// error.
+    fn remove(&self, action_id: Uuid) -> Result<(), StageflowError>;
+
+    /// Loads every saved record.
+    fn load(&self) -> Result<Vec<UndoRecord>, StageflowError>;
+}
+
+/// Durable storage for the requests pending in an
+/// [`ApprovalService`](super::ApprovalService).
+pub trait ApprovalQueueStore: Send + Sync {
+    /// Saves a pending request.
+    fn save(&self, request: &PendingApproval) -> Result<(), StageflowError>;
+
+    /// Removes a request once decided, timed out or cancelled; removing a
+    /// missing request is not an error.
+    fn remove(&self, request_id: Uuid) -> Result<(), StageflowError>;
+
+    /// Loads every saved request.
+    fn load(&self) -> Result<Vec<PendingApproval>, StageflowError>;
+}
+
+/// A JSON object of records keyed by id, rewritten on every change.
+#[derive(Debug)]
+struct JsonFile {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read<V: DeserializeOwned>(&self) -> Result<BTreeMap<String, V>, StageflowError> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| StageflowError::Serialization(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update<V: Serialize + DeserializeOwned>(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<String, V>),
+    ) -> Result<(), StageflowError> {
+        let _guard = self.lock.lock();
+        let mut records = self.read()?;
+        change(&mut records);
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let bytes = serde_json::to_vec_pretty(&records).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+
+        // Write then rename so a crash mid-write never leaves a torn file.
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn values<V: DeserializeOwned>(&self) -> Result<Vec<V>, StageflowError> {
+        let _guard = self.lock.lock();
+        Ok(self.read()?.into_values().collect())
+    }
+}
+
+/// [`UndoPersistence`] in a single JSON file.
+#[derive(Debug)]
+pub struct FileUndoPersistence {
+    file: JsonFile,
+}
+
+impl FileUndoPersistence {
+    /// Creates a store writing to the given file.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: JsonFile::new(path.into()),
+        }
+    }
+
+    /// Returns the file path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.file.path
+    }
+}
+
+impl UndoPersistence for FileUndoPersistence {
+    fn save(&self, record: &UndoRecord) -> Result<(), StageflowError> {
+        self.file.update(|records| {
+            records.insert(record.metadata.action_id.to_string(), record.clone());
+        })
+    }
+
+    fn remove(&self, action_id: Uuid) -> Result<(), StageflowError> {
+        self.file.update::<UndoRecord>(|records| {
+            records.remove(&action_id.to_string());
+        })
+    }
+
+    fn load(&self) -> Result<Vec<UndoRecord>, StageflowError> {
+        self.file.values()
+    }
+}
+
+/// [`ApprovalQueueStore`] in a single JSON file.
+#[derive(Debug)]
+pub struct FileApprovalQueue {
+    file: JsonFile,
+}
+
+impl FileApprovalQueue {
+    /// Creates a store writing to the given file.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: JsonFile::new(path.into()),
+        }
+    }
+
+    /// Returns the file path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.file.path
+    }
+}
+
+impl ApprovalQueueStore for FileApprovalQueue {
+    fn save(&self, request: &PendingApproval) -> Result<(), StageflowError> {
+        self.file.update(|requests| {
+            requests.insert(request.request_id.to_string(), request.clone());
+        })
+    }
+
+    fn remove(&self, request_id: Uuid) -> Result<(), StageflowError> {
+        self.file.update::<PendingApproval>(|requests| {
+            requests.remove(&request_id.to_string());
+        })
+    }
+
+    fn load(&self) -> Result<Vec<PendingApproval>, StageflowError> {
+        self.file.values()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+const UNDO_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS stageflow_undo (
+        action_id TEXT PRIMARY KEY,
+        record TEXT NOT NULL
+    );
+";
+
+#[cfg(feature = "sqlite")]
+const APPROVAL_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS stageflow_approvals (
+        request_id TEXT PRIMARY KEY,
+        request TEXT NOT NULL
+    );
+";
+
+/// A table of JSON records keyed by id.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+struct SqliteTable {
+    db: crate::utils::sqlite::SqliteDb,
+    table: &'static str,
+    key: &'static str,
+    value: &'static str,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteTable {
+    fn save(&self, id: Uuid, record: &impl Serialize) -> Result<(), StageflowError> {
+        let json = serde_json::to_string(record).map_err(|e| StageflowError::Serialization(e.to_string()))?;
+        let sql = format!("INSERT OR REPLACE INTO {} ({}, {}) VALUES (?1, ?2)", self.table, self.key, self.value);
+        self.db
+            .call_blocking(|conn| conn.execute(&sql, rusqlite::params![id.to_string(), json]))?;
+        Ok(())
+    }
+
+    fn remove(&self, id: Uuid) -> Result<(), StageflowError> {
+        let sql = format!("DELETE FROM {} WHERE {} = ?1", self.table, self.key);
+        self.db.call_blocking(|conn| conn.execute(&sql, [id.to_string()]))?;
+        Ok(())
+    }
+
+    fn load<V: DeserializeOwned>(&self) -> Result<Vec<V>, StageflowError> {
+        let sql = format!("SELECT {} FROM {} ORDER BY {}", self.value, self.table, self.key);
+        let rows: Vec<String> = self.db.call_blocking(|conn| {
+            let mut statement = conn.prepare(&sql)?;
+            let rows = statement.query_map([], |row| row.get(0))?;
+            rows.collect()
+        })?;
+        rows.iter()
+            .map(|json| serde_json::from_str(json).map_err(|e| StageflowError::Serialization(e.to_string())))
+            .collect()
+    }
+}
+
+/// [`UndoPersistence`] in a `SQLite` database, one row per action.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteUndoPersistence {
+    table: SqliteTable,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteUndoPersistence {
+    /// Opens or creates the database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or its table
+    /// cannot be created.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StageflowError> {
+        Ok(Self::from_db(crate::utils::sqlite::SqliteDb::open(path, UNDO_SCHEMA)?))
+    }
+
+    /// Creates a store in a private in-memory database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be created.
+    pub fn in_memory() -> Result<Self, StageflowError> {
+        Ok(Self::from_db(crate::utils::sqlite::SqliteDb::open_in_memory(UNDO_SCHEMA)?))
+    }
+
+    fn from_db(db: crate::utils::sqlite::SqliteDb) -> Self {
+        Self {
+            table: SqliteTable {
+                db,
+                table: "stageflow_undo",
+                key: "action_id",
+                value: "record",
+            },
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl UndoPersistence for SqliteUndoPersistence {
+    fn save(&self, record: &UndoRecord) -> Result<(), StageflowError> {
+        self.table.save(record.metadata.action_id, record)
+    }
+
+    fn remove(&self, action_id: Uuid) -> Result<(), StageflowError> {
+        self.table.remove(action_id)
+    }
+
+    fn load(&self) -> Result<Vec<UndoRecord>, StageflowError> {
+        self.table.load()
+    }
+}
+
+/// [`ApprovalQueueStore`] in a `SQLite` database, one row per request.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteApprovalQueue {
+    table: SqliteTable,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteApprovalQueue {
+    /// Opens or creates the database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or its table
+    /// cannot be created.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StageflowError> {
+        Ok(Self::from_db(crate::utils::sqlite::SqliteDb::open(path, APPROVAL_SCHEMA)?))
+    }
+
+    /// Creates a store in a private in-memory database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be created.
+    pub fn in_memory() -> Result<Self, StageflowError> {
+        Ok(Self::from_db(crate::utils::sqlite::SqliteDb::open_in_memory(APPROVAL_SCHEMA)?))
+    }
+
+    fn from_db(db: crate::utils::sqlite::SqliteDb) -> Self {
+        Self {
+            table: SqliteTable {
+                db,
+                table: "stageflow_approvals",
+                key: "request_id",
+                value: "request",
+            },
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ApprovalQueueStore for SqliteApprovalQueue {
+    fn save(&self, request: &PendingApproval) -> Result<(), StageflowError> {
+        self.table.save(request.request_id, request)
+    }
+
+    fn remove(&self, request_id: Uuid) -> Result<(), StageflowError> {
+        self.table.remove(request_id)
+    }
+
+    fn load(&self) -> Result<Vec<PendingApproval>, StageflowError> {
+        self.table.load()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{ApprovalService, UndoStore};
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_undo_entries_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("undo.json");
+        let kept = Uuid::new_v4();
+        let dropped = Uuid::new_v4();
+
+        let store = UndoStore::new(Duration::from_secs(60))
+            .with_persistence(Arc::new(FileUndoPersistence::new(&path)))
+            .unwrap();
+        store.store(UndoMetadata::new(kept, "write_file", json!({"path": "a.txt"})));
+        store.store(UndoMetadata::new(dropped, "write_file", json!({"path": "b.txt"})));
+        assert!(store.remove(dropped));
+        drop(store);
+
+        let restarted = UndoStore::new(Duration::from_secs(60))
+            .with_persistence(Arc::new(FileUndoPersistence::new(&path)))
+            .unwrap();
+        assert_eq!(restarted.get(kept).unwrap().undo_data, json!({"path": "a.txt"}));
+        assert!(restarted.get(dropped).is_none());
+
+        // Expired records are dropped on load
+        let persistence = FileUndoPersistence::new(&path);
+        persistence
+            .save(&UndoRecord {
+                metadata: UndoMetadata::new(dropped, "write_file", json!({})),
+                expires_at: Utc::now() - chrono::Duration::seconds(1),
+            })
+            .unwrap();
+        let restarted = UndoStore::new(Duration::from_secs(60))
+            .with_persistence(Arc::new(FileUndoPersistence::new(&path)))
+            .unwrap();
+        assert_eq!(restarted.len(), 1);
+        assert_eq!(persistence.load().unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_backends_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tools.db");
+        let action = Uuid::new_v4();
+        let request = PendingApproval::new("delete_file", "Delete report.pdf?");
+
+        let undo = UndoStore::new(Duration::from_secs(60))
+            .with_persistence(Arc::new(SqliteUndoPersistence::open(&path).unwrap()))
+            .unwrap();
+        undo.store(UndoMetadata::new(action, "write_file", json!({"path": "a.txt"})));
+        SqliteApprovalQueue::open(&path).unwrap().save(&request).unwrap();
+        drop(undo);
+
+        // Both stores share the file and reload after a restart
+        let restarted = UndoStore::new(Duration::from_secs(60))
+            .with_persistence(Arc::new(SqliteUndoPersistence::open(&path).unwrap()))
+            .unwrap();
+        assert_eq!(restarted.get(action).unwrap().undo_data, json!({"path": "a.txt"}));
+        assert!(restarted.remove(action));
+        assert!(SqliteUndoPersistence::open(&path).unwrap().load().unwrap().is_empty());
+
+        let queue = Arc::new(SqliteApprovalQueue::open(&path).unwrap());
+        let service = ApprovalService::new().with_queue_store(queue.clone()).unwrap();
+        assert_eq!(service.recovered_requests(), vec![request.clone()]);
+        assert!(service.approve(request.request_id));
+        assert!(queue.load().unwrap().is_empty());
+
+        let in_memory = SqliteApprovalQueue::in_memory().unwrap();
+        in_memory.save(&request).unwrap();
+        in_memory.remove(request.request_id).unwrap();
+        in_memory.remove(request.request_id).unwrap();
+        assert!(in_memory.load().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pending_approvals_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("approvals.json");
+        let request = PendingApproval::new("delete_file", "Delete report.pdf?");
+
+        // As left behind by a process that stopped while the request was pending
+        let queue = Arc::new(FileApprovalQueue::new(&path));
+        queue.save(&request).unwrap();
+
+        let service = ApprovalService::new().with_queue_store(queue.clone()).unwrap();
+        assert_eq!(service.recovered_requests(), vec![request.clone()]);
+        assert!(service.approve(request.request_id));
+        assert!(service.recovered_requests().is_empty());
+        assert!(queue.load().unwrap().is_empty());
+
+        // Re-issuing the request picks up the decision made after restart
+        let approved = service.request(request.clone(), Duration::from_millis(10)).await;
+        assert_eq!(approved, Ok(true));
+
+        let timed_out = service
+            .request(PendingApproval::new("delete_file", "Delete old.pdf?"), Duration::from_millis(10))
+            .await;
+        assert!(timed_out.is_err());
+        assert!(queue.load().unwrap().is_empty());
+    }
+}
@@ -67,12 +67,20 @@ pub trait Tool: Send + Sync {
 }
 
 /// Registry for tool instances and factories.
+///
+/// A registry created with [`overlay`](Self::overlay) layers its own tools
+/// over a shared base registry: lookups check the overlay first, and
+/// registering only touches the overlay. Attach one to a run with
+/// [`PipelineContext::with_tool_registry`](crate::context::PipelineContext::with_tool_registry)
+/// to give it per-user tools without writing to the global registry.
 #[derive(Default)]
 pub struct ToolRegistry {
     /// Registered tool instances.
     instances: RwLock<HashMap<String, Arc<dyn Tool>>>,
     /// Registered tool factories.
     factories: RwLock<HashMap<String, ToolFactory>>,
+    /// Registry consulted for tools this one does not have.
+    base: Option<Arc<ToolRegistry>>,
 }
 
 impl ToolRegistry {
@@ -82,6 +90,21 @@ impl ToolRegistry {
         Self::default()
     }
 
+    /// Creates an empty registry layered over `base`.
+    #[must_use]
+    pub fn overlay(base: Arc<ToolRegistry>) -> Self {
+        Self {
+            base: Some(base),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the registry this one is layered over, if any.
+    #[must_use]
+    pub fn base(&self) -> Option<&Arc<ToolRegistry>> {
+        self.base.as_ref()
+    }
+
     /// Registers a tool instance.
     pub fn register(&self, tool: Box<dyn Tool>) {
         let action_type = tool.action_type().to_string();
@@ -96,13 +119,16 @@ impl ToolRegistry {
     /// Gets a tool by action type.
     ///
     /// If only a factory is registered, constructs and memoizes the tool.
+    /// Tools of an overlay shadow those of its base.
     pub fn get_tool(&self, action_type: &str) -> Option<Arc<dyn Tool>> {
         if let Some(tool) = self.instances.read().get(action_type) {
             return Some(tool.clone());
         }
 
         let factory = self.factories.read().get(action_type).cloned();
-        let factory = factory?;
+        let Some(factory) = factory else {
+            return self.base.as_ref()?.get_tool(action_type);
+        };
 
         let tool = (factory)();
         self.instances
@@ -116,16 +142,23 @@ impl ToolRegistry {
     pub fn can_execute(&self, action_type: &str) -> bool {
         self.instances.read().contains_key(action_type)
             || self.factories.read().contains_key(action_type)
+            || self.base.as_ref().is_some_and(|base| base.can_execute(action_type))
     }
 
-    /// Lists registered tool instances.
+    /// Lists registered tool instances, including those of the base.
     pub fn list_tools(&self) -> Vec<String> {
-        self.instances.read().keys().cloned().collect()
+        let mut tools: Vec<String> = self.instances.read().keys().cloned().collect();
+        if let Some(base) = &self.base {
+            let own: std::collections::HashSet<String> = tools.iter().cloned().collect();
+            tools.extend(base.list_tools().into_iter().filter(|name| !own.contains(name)));
+        }
+        tools
     }
 
     /// Parses and resolves tool calls from raw data.
     ///
-    /// Supports OpenAI-style format by default.
+    /// Supports OpenAI-style format by default. Calls whose arguments do not
+    /// match the tool's input schema are returned as unresolved.
     pub fn parse_and_resolve(
         &self,
         calls: &[serde_json::Value],
@@ -209,13 +242,22 @@ impl ToolRegistry {
         };
 
         // Check if tool exists
-        if !self.can_execute(&name_str) {
+        let Some(tool) = self.get_tool(&name_str) else {
             return Err(UnresolvedToolCall {
                 id,
                 name,
                 error: format!("No tool registered for action type '{}'", name_str),
                 raw: call.clone(),
             });
+        };
+
+        if let Err(e) = tool.definition().validate_input(&arguments) {
+            return Err(UnresolvedToolCall {
+                id,
+                name,
+                error: e.to_string(),
+                raw: call.clone(),
+            });
         }
 
         Ok(ResolvedToolCall {
@@ -226,7 +268,7 @@ impl ToolRegistry {
         })
     }
 
-    /// Clears all registered tools.
+    /// Clears all registered tools, leaving the base of an overlay intact.
     pub fn clear(&self) {
         self.instances.write().clear();
         self.factories.write().clear();
@@ -238,6 +280,7 @@ impl std::fmt::Debug for ToolRegistry {
         f.debug_struct("ToolRegistry")
             .field("instance_count", &self.instances.read().len())
             .field("factory_count", &self.factories.read().len())
+            .field("base", &self.base)
             .finish()
     }
 }
@@ -378,6 +421,83 @@ mod tests {
         assert!(err.error.contains("Invalid JSON"));
     }
 
+    struct SchemaTool;
+
+    impl Tool for SchemaTool {
+        fn action_type(&self) -> &'static str {
+            "get_weather"
+        }
+
+        fn name(&self) -> &'static str {
+            "weather"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition::new("weather", "get_weather").with_input_schema(serde_json::json!({
+                "type": "object",
+                "required": ["city"],
+                "properties": {"city": {"type": "string"}}
+            }))
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_arguments_violating_schema() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(SchemaTool));
+
+        let calls = vec![
+            serde_json::json!({"id": "ok", "function": {"name": "get_weather", "arguments": "{\"city\": \"Oslo\"}"}}),
+            serde_json::json!({"id": "bad", "function": {"name": "get_weather", "arguments": "{\"city\": 3}"}}),
+        ];
+
+        let results = registry.parse_and_resolve(&calls, "id", Some("function"), "name", "arguments");
+        assert!(results[0].is_ok());
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.id.as_deref(), Some("bad"));
+        assert!(err.error.contains("$.city: expected string"));
+    }
+
+    #[test]
+    fn test_overlay_shadows_base_without_mutating_it() {
+        let tool = |action_type: &str, name: &str| -> Box<dyn Tool> {
+            Box::new(TestTool {
+                action_type: action_type.to_string(),
+                name: name.to_string(),
+            })
+        };
+        let base = Arc::new(ToolRegistry::new());
+        base.register(tool("search", "base_search"));
+        base.register(tool("calendar", "base_calendar"));
+
+        let overlay = ToolRegistry::overlay(base.clone());
+        overlay.register(tool("calendar", "user_calendar"));
+        overlay.register_factory(
+            "drive",
+            Arc::new(|| -> Arc<dyn Tool> {
+                Arc::new(TestTool {
+                    action_type: "drive".to_string(),
+                    name: "user_drive".to_string(),
+                })
+            }),
+        );
+
+        assert_eq!(overlay.get_tool("calendar").unwrap().name(), "user_calendar");
+        assert_eq!(overlay.get_tool("search").unwrap().name(), "base_search");
+        assert!(overlay.can_execute("drive"));
+        assert_eq!(overlay.get_tool("drive").unwrap().name(), "user_drive");
+        let mut tools = overlay.list_tools();
+        tools.sort();
+        assert_eq!(tools, vec!["calendar", "drive", "search"]);
+
+        assert_eq!(base.get_tool("calendar").unwrap().name(), "base_calendar");
+        assert!(!base.can_execute("drive"));
+
+        overlay.clear();
+        assert_eq!(overlay.get_tool("calendar").unwrap().name(), "base_calendar");
+        assert_eq!(base.list_tools().len(), 2);
+    }
+
     #[test]
     fn test_global_registry() {
         clear_tool_registry();
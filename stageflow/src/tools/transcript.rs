@@ -0,0 +1,187 @@
+//! Per-run record of tool invocations.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Final status of a tool invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    /// The tool ran and reported success.
+    Completed,
+    /// The tool ran and failed, or could not be resolved.
+    Failed,
+    /// The call was rejected before running (e.g. behavior gating).
+    Denied,
+    /// The call was not run because of a dry run; a simulated output was
+    /// returned instead.
+    Simulated,
+}
+
+/// Outcome of an approval request for a tool invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// The call was approved.
+    Approved,
+    /// The call was explicitly denied.
+    Denied,
+    /// No decision arrived before the approval timeout.
+    TimedOut,
+}
+
+/// One tool invocation in a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    /// The action ID of the call.
+    pub action_id: Uuid,
+    /// The tool name.
+    pub tool_name: String,
+    /// Hash of the input payload, so identical arguments can be correlated
+    /// without storing them.
+    pub args_hash: String,
+    /// The stage that made the call, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    /// Final status.
+    pub status: ToolCallStatus,
+    /// Approval outcome, if approval was required.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalDecision>,
+    /// Time from invocation to outcome in milliseconds, including approval waits.
+    pub duration_ms: f64,
+    /// Error or denial reason.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ToolCallRecord {
+    /// Returns true if the call completed successfully or was simulated.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.status,
+            ToolCallStatus::Completed | ToolCallStatus::Simulated
+        )
+    }
+}
+
+/// Ordered list of tool invocations made during a run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolTranscript {
+    /// Calls in the order they finished.
+    pub calls: Vec<ToolCallRecord>,
+}
+
+impl ToolTranscript {
+    /// Creates an empty transcript.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a call.
+    pub fn record(&mut self, record: ToolCallRecord) {
+        self.calls.push(record);
+    }
+
+    /// Returns the number of recorded calls.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Returns true if no calls were recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Returns calls for a tool.
+    #[must_use]
+    pub fn calls_for(&self, tool_name: &str) -> Vec<&ToolCallRecord> {
+        self.calls.iter().filter(|c| c.tool_name == tool_name).collect()
+    }
+
+    /// Returns calls made by a stage.
+    #[must_use]
+    pub fn calls_by_stage(&self, stage: &str) -> Vec<&ToolCallRecord> {
+        self.calls
+            .iter()
+            .filter(|c| c.stage.as_deref() == Some(stage))
+            .collect()
+    }
+
+    /// Returns calls that did not complete successfully.
+    #[must_use]
+    pub fn unsuccessful(&self) -> Vec<&ToolCallRecord> {
+        self.calls.iter().filter(|c| !c.is_success()).collect()
+    }
+
+    /// Returns the summed duration of all calls in milliseconds.
+    #[must_use]
+    pub fn total_duration_ms(&self) -> f64 {
+        self.calls.iter().map(|c| c.duration_ms).sum()
+    }
+
+    /// Converts to a dictionary.
+    #[must_use]
+    pub fn to_dict(&self) -> HashMap<String, serde_json::Value> {
+        let mut dict = HashMap::new();
+        dict.insert("calls".to_string(), serde_json::json!(self.calls));
+        dict.insert("call_count".to_string(), serde_json::json!(self.calls.len()));
+        dict.insert(
+            "failed_count".to_string(),
+            serde_json::json!(self.unsuccessful().len()),
+        );
+        dict.insert(
+            "total_duration_ms".to_string(),
+            serde_json::json!(self.total_duration_ms()),
+        );
+        dict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(tool: &str, stage: &str, status: ToolCallStatus) -> ToolCallRecord {
+        ToolCallRecord {
+            action_id: Uuid::new_v4(),
+            tool_name: tool.to_string(),
+            args_hash: "abc".to_string(),
+            stage: Some(stage.to_string()),
+            status,
+            approval: None,
+            duration_ms: 5.0,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_transcript_queries() {
+        let mut transcript = ToolTranscript::new();
+        transcript.record(record("search", "agent", ToolCallStatus::Completed));
+        transcript.record(record("write", "agent", ToolCallStatus::Denied));
+        transcript.record(record("search", "review", ToolCallStatus::Failed));
+
+        assert_eq!(transcript.len(), 3);
+        assert_eq!(transcript.calls_for("search").len(), 2);
+        assert_eq!(transcript.calls_by_stage("agent").len(), 2);
+        assert_eq!(transcript.unsuccessful().len(), 2);
+        assert!((transcript.total_duration_ms() - 15.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_record_serialization() {
+        let mut call = record("search", "agent", ToolCallStatus::Completed);
+        call.approval = Some(ApprovalDecision::TimedOut);
+
+        let json = serde_json::to_value(&call).unwrap();
+        assert_eq!(json["status"], "completed");
+        assert_eq!(json["approval"], "timed_out");
+        assert!(json.get("error").is_none());
+    }
+}
@@ -1,10 +1,13 @@
 //! Undo metadata and store.
 
+use super::{UndoPersistence, UndoRecord};
+use crate::errors::StageflowError;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tracing::warn;
 use uuid::Uuid;
 
 /// Metadata for undoing a tool action.
@@ -76,11 +79,17 @@ struct UndoEntry {
 }
 
 /// Store for undo metadata with TTL.
+///
+/// With [`UndoPersistence`] attached, every change is written through and
+/// unexpired entries are reloaded when the store is created. Write failures
+/// are logged and leave the in-memory state authoritative.
 pub struct UndoStore {
     /// TTL for entries.
     ttl: Duration,
     /// Stored entries.
     entries: RwLock<HashMap<Uuid, UndoEntry>>,
+    /// Durable copy of the entries, if attached.
+    persistence: Option<Arc<dyn UndoPersistence>>,
 }
 
 impl UndoStore {
@@ -90,11 +99,48 @@ impl UndoStore {
         Self {
             ttl,
             entries: RwLock::new(HashMap::new()),
+            persistence: None,
         }
     }
 
+    /// Writes entries through to `persistence`, after loading the
+    /// unexpired entries it holds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the saved entries cannot be loaded.
+    pub fn with_persistence(mut self, persistence: Arc<dyn UndoPersistence>) -> Result<Self, StageflowError> {
+        let now = chrono::Utc::now();
+        {
+            let mut entries = self.entries.write();
+            for record in persistence.load()? {
+                match (record.expires_at - now).to_std() {
+                    Ok(remaining) if !remaining.is_zero() => {
+                        let entry = UndoEntry {
+                            expires_at: Instant::now() + remaining,
+                            metadata: record.metadata,
+                        };
+                        entries.insert(entry.metadata.action_id, entry);
+                    }
+                    _ => persistence.remove(record.metadata.action_id)?,
+                }
+            }
+        }
+        self.persistence = Some(persistence);
+        Ok(self)
+    }
+
     /// Stores undo metadata.
     pub fn store(&self, metadata: UndoMetadata) {
+        if let Some(persistence) = &self.persistence {
+            let record = UndoRecord {
+                metadata: metadata.clone(),
+                expires_at: chrono::Utc::now() + chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX),
+            };
+            if let Err(e) = persistence.save(&record) {
+                warn!(action_id = %metadata.action_id, error = %e, "Failed to persist undo metadata");
+            }
+        }
         let entry = UndoEntry {
             metadata: metadata.clone(),
             expires_at: Instant::now() + self.ttl,
@@ -102,6 +148,17 @@ impl UndoStore {
         self.entries.write().insert(metadata.action_id, entry);
     }
 
+    fn forget(&self, action_ids: &[Uuid]) {
+        let Some(persistence) = &self.persistence else {
+            return;
+        };
+        for action_id in action_ids {
+            if let Err(e) = persistence.remove(*action_id) {
+                warn!(action_id = %action_id, error = %e, "Failed to remove persisted undo metadata");
+            }
+        }
+    }
+
     /// Gets undo metadata for an action.
     ///
     /// Returns None if not found or expired.
@@ -115,6 +172,8 @@ impl UndoStore {
             } else {
                 // Expired, remove it
                 entries.remove(&action_id);
+                drop(entries);
+                self.forget(&[action_id]);
             }
         }
         None
@@ -122,12 +181,17 @@ impl UndoStore {
 
     /// Removes undo metadata.
     pub fn remove(&self, action_id: Uuid) -> bool {
-        self.entries.write().remove(&action_id).is_some()
+        let removed = self.entries.write().remove(&action_id).is_some();
+        if removed {
+            self.forget(&[action_id]);
+        }
+        removed
     }
 
     /// Clears all entries.
     pub fn clear(&self) {
-        self.entries.write().clear();
+        let removed: Vec<Uuid> = self.entries.write().drain().map(|(id, _)| id).collect();
+        self.forget(&removed);
     }
 
     /// Returns the number of entries (including potentially expired ones).
@@ -145,7 +209,15 @@ impl UndoStore {
     /// Cleans up expired entries.
     pub fn cleanup_expired(&self) {
         let now = Instant::now();
-        self.entries.write().retain(|_, entry| entry.expires_at > now);
+        let mut expired = Vec::new();
+        self.entries.write().retain(|id, entry| {
+            let live = entry.expires_at > now;
+            if !live {
+                expired.push(*id);
+            }
+            live
+        });
+        self.forget(&expired);
     }
 }
 
@@ -160,6 +232,7 @@ impl std::fmt::Debug for UndoStore {
         f.debug_struct("UndoStore")
             .field("ttl", &self.ttl)
             .field("entries", &self.len())
+            .field("persistent", &self.persistence.is_some())
             .finish()
     }
 }
@@ -0,0 +1,268 @@
+//! Deterministic sources of time, randomness, and identifiers.
+//!
+//! Reproducible runs need every UUID, timestamp, and jitter draw to come
+//! from a seeded source. A [`DeterministicSource`] is attached to a
+//! `PipelineContext`; the DAG engines scope it around each stage so the
+//! free functions in [`crate::utils`] pick it up without extra plumbing.
+//! Outside a scope those functions fall back to the system clock and RNG.
+
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+tokio::task_local! {
+    static DETERMINISTIC_SOURCE: Arc<DeterministicSource>;
+}
+
+/// A source of the current time.
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    /// Returns the current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock backed by the system wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that starts at a fixed instant and advances by a fixed step per read.
+#[derive(Debug)]
+pub struct ManualClock {
+    current: Mutex<DateTime<Utc>>,
+    step: chrono::Duration,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at `start` that advances by `step` on each read.
+    #[must_use]
+    pub fn new(start: DateTime<Utc>, step: chrono::Duration) -> Self {
+        Self {
+            current: Mutex::new(start),
+            step,
+        }
+    }
+
+    /// Creates a clock starting at the Unix epoch that advances 1ms per read.
+    #[must_use]
+    pub fn from_epoch() -> Self {
+        Self::new(
+            Utc.timestamp_opt(0, 0).single().unwrap_or_else(Utc::now),
+            chrono::Duration::milliseconds(1),
+        )
+    }
+
+    /// Moves the clock forward without reading it.
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.current.lock() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        let mut current = self.current.lock();
+        let now = *current;
+        *current += self.step;
+        now
+    }
+}
+
+/// Seeded generator for UUIDs, timestamps, and random draws.
+#[derive(Debug)]
+pub struct DeterministicSource {
+    seed: u64,
+    rng: Mutex<StdRng>,
+    clock: Arc<dyn Clock>,
+}
+
+impl DeterministicSource {
+    /// Creates a source with the given seed and a [`ManualClock`] at the epoch.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            clock: Arc::new(ManualClock::from_epoch()),
+        }
+    }
+
+    /// Replaces the clock.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the seed.
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the current time from the injected clock.
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Returns a seeded UUID v4.
+    #[must_use]
+    pub fn uuid_v4(&self) -> Uuid {
+        let bytes: [u8; 16] = self.rng.lock().gen();
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+
+    /// Returns a UUID v7 using the injected clock and seeded random bits.
+    #[must_use]
+    pub fn uuid_v7(&self) -> Uuid {
+        let millis = u64::try_from(self.now().timestamp_millis()).unwrap_or(0);
+        let bytes: [u8; 10] = self.rng.lock().gen();
+        uuid::Builder::from_unix_timestamp_millis(millis, &bytes).into_uuid()
+    }
+
+    /// Returns a seeded value in the inclusive range.
+    #[must_use]
+    pub fn gen_range(&self, range: RangeInclusive<u64>) -> u64 {
+        self.rng.lock().gen_range(range)
+    }
+
+    /// Returns a seeded value in `[0, 1)`.
+    #[must_use]
+    pub fn gen_f64(&self) -> f64 {
+        self.rng.lock().gen()
+    }
+}
+
+/// Runs a future with `source` as the active deterministic source.
+pub async fn with_deterministic_source<F: Future>(source: Arc<DeterministicSource>, future: F) -> F::Output {
+    DETERMINISTIC_SOURCE.scope(source, future).await
+}
+
+/// Returns the deterministic source active for the current task, if any.
+#[must_use]
+pub fn current_deterministic_source() -> Option<Arc<DeterministicSource>> {
+    DETERMINISTIC_SOURCE.try_with(Arc::clone).ok()
+}
+
+/// Returns a random value in the inclusive range, seeded when in a deterministic scope.
+#[must_use]
+pub fn random_in_range(range: RangeInclusive<u64>) -> u64 {
+    match current_deterministic_source() {
+        Some(source) => source.gen_range(range),
+        None => rand::thread_rng().gen_range(range),
+    }
+}
+
+/// Returns a random value in `[0, 1)`, seeded when in a deterministic scope.
+#[must_use]
+pub fn random_f64() -> f64 {
+    current_deterministic_source().map_or_else(rand::random::<f64>, |source| source.gen_f64())
+}
+
+/// Measures elapsed time on the active deterministic source's clock, or
+/// on the monotonic clock outside a deterministic scope.
+#[derive(Debug, Clone)]
+pub struct Stopwatch {
+    start: Instant,
+    clock_start: Option<(Arc<DeterministicSource>, DateTime<Utc>)>,
+}
+
+impl Stopwatch {
+    /// Starts measuring now.
+    #[must_use]
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            clock_start: current_deterministic_source().map(|source| {
+                let now = source.now();
+                (source, now)
+            }),
+        }
+    }
+
+    /// Returns the time since [`start`](Self::start).
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        match &self.clock_start {
+            Some((source, start)) => (source.now() - *start).to_std().unwrap_or_default(),
+            None => self.start.elapsed(),
+        }
+    }
+
+    /// Returns the time since [`start`](Self::start) in milliseconds.
+    #[must_use]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let a = DeterministicSource::new(7);
+        let b = DeterministicSource::new(7);
+
+        assert_eq!(a.uuid_v4(), b.uuid_v4());
+        assert_eq!(a.uuid_v7(), b.uuid_v7());
+        assert_eq!(a.gen_range(0..=1000), b.gen_range(0..=1000));
+        assert_eq!(a.now(), b.now());
+    }
+
+    #[test]
+    fn test_uuid_versions() {
+        let source = DeterministicSource::new(1);
+        assert_eq!(source.uuid_v4().get_version_num(), 4);
+        assert_eq!(source.uuid_v7().get_version_num(), 7);
+    }
+
+    #[test]
+    fn test_manual_clock_steps() {
+        let clock = ManualClock::from_epoch();
+        let first = clock.now();
+        let second = clock.now();
+        assert_eq!((second - first).num_milliseconds(), 1);
+
+        clock.advance(chrono::Duration::seconds(10));
+        assert_eq!((clock.now() - second).num_milliseconds(), 10_001);
+    }
+
+    #[tokio::test]
+    async fn test_scope_is_task_local() {
+        assert!(current_deterministic_source().is_none());
+
+        let source = Arc::new(DeterministicSource::new(3));
+        let inside = with_deterministic_source(source, async {
+            current_deterministic_source().map(|s| s.seed())
+        })
+        .await;
+
+        assert_eq!(inside, Some(3));
+        assert!(current_deterministic_source().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stopwatch_uses_source_clock() {
+        let source = Arc::new(DeterministicSource::new(3));
+        let elapsed = with_deterministic_source(source.clone(), async {
+            let stopwatch = Stopwatch::start();
+            let _ = source.now();
+            stopwatch.elapsed_ms()
+        })
+        .await;
+
+        assert!((elapsed - 2.0).abs() < f64::EPSILON);
+    }
+}
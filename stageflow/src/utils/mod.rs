@@ -3,16 +3,35 @@
 //! This module provides deterministic helpers for generating UUIDs and
 //! RFC3339/ISO timestamps consistent with Python's behavior.
 
+pub mod determinism;
+pub mod numbers;
+mod paths;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
 pub mod timestamps;
+pub mod ttl;
 mod uuid_utils;
 pub mod validation;
 
-pub use timestamps::{iso_timestamp, parse_timestamp, Timestamp, UnixPrecision};
-pub use uuid_utils::{generate_uuid, generate_uuid_v7, UuidCollisionMonitor, UuidEvent};
+pub use determinism::{
+    current_deterministic_source, random_f64, random_in_range, with_deterministic_source, Clock,
+    DeterministicSource, ManualClock, Stopwatch, SystemClock,
+};
+pub use numbers::{number_policy, parse_json, parse_json_with, set_number_policy, NumberPolicy};
+pub use paths::{decode_path_component, encode_path_component};
+pub use timestamps::{
+    iso_timestamp, parse_timestamp, DateOrder, Timestamp, TimestampFormat, TimestampParser,
+    TimestampPrecision, TimestampStyle, UnixPrecision,
+};
+pub use ttl::{Expiry, TtlClock, DEFAULT_SKEW_TOLERANCE};
+pub use uuid_utils::{
+    clear_uuid_monitor, generate_uuid, generate_uuid_v7, get_uuid_monitor, set_uuid_monitor,
+    UuidAlertHook, UuidCollisionMonitor, UuidEvent,
+};
 pub use validation::{
-    CycleError, InvalidNameError, MissingDependencyError, SelfDependencyError,
-    ValidationError, validate_all, validate_dag, validate_dependencies_exist,
-    validate_no_self_dependencies, validate_stage_name,
+    validate_all, validate_dag, validate_dependencies_exist, validate_no_self_dependencies,
+    validate_stage_name, CycleError, InvalidNameError, MissingDependencyError, SelfDependencyError,
+    ValidationError,
 };
 
 #[cfg(test)]
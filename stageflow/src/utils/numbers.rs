@@ -0,0 +1,273 @@
+//! Integer precision handling for JSON values.
+//!
+//! `serde_json::Value` stores integers as `i64` or `u64`. Anything larger is
+//! parsed through `f64` and silently rounded, which corrupts IDs and
+//! counters coming from Python or other languages with big integers. A
+//! [`NumberPolicy`] decides what happens to such integers wherever stageflow
+//! turns external data into JSON: the Python bindings and [`parse_json`],
+//! which snapshot loading uses.
+
+use crate::errors::{JsonParseError, NumberPrecisionError};
+use parking_lot::RwLock;
+use serde_json::{Number, Value};
+
+/// What to do with integers that do not fit in `i64` or `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberPolicy {
+    /// Keep the exact digits as a JSON string.
+    PreserveAsString,
+    /// Convert through `f64`, losing precision (the `serde_json` default).
+    #[default]
+    Lossy,
+    /// Reject the value.
+    Error,
+    /// Keep the exact digits as a JSON number.
+    ///
+    /// Requires the `arbitrary_precision` feature, which enables the
+    /// `serde_json` feature of the same name; without it, out-of-range
+    /// integers are rejected.
+    ArbitraryPrecision,
+}
+
+static NUMBER_POLICY: RwLock<NumberPolicy> = RwLock::new(NumberPolicy::Lossy);
+
+/// Returns the process-wide number policy.
+#[must_use]
+pub fn number_policy() -> NumberPolicy {
+    *NUMBER_POLICY.read()
+}
+
+/// Sets the process-wide number policy.
+pub fn set_number_policy(policy: NumberPolicy) {
+    *NUMBER_POLICY.write() = policy;
+}
+
+impl NumberPolicy {
+    /// Returns the policy name.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PreserveAsString => "preserve_as_string",
+            Self::Lossy => "lossy",
+            Self::Error => "error",
+            Self::ArbitraryPrecision => "arbitrary_precision",
+        }
+    }
+
+    /// Returns false for [`NumberPolicy::ArbitraryPrecision`] when the
+    /// `arbitrary_precision` feature is off.
+    #[must_use]
+    pub const fn is_available(self) -> bool {
+        !matches!(self, Self::ArbitraryPrecision) || cfg!(feature = "arbitrary_precision")
+    }
+
+    /// Parses a policy name as returned by [`as_str`](Self::as_str).
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "preserve_as_string" => Some(Self::PreserveAsString),
+            "lossy" => Some(Self::Lossy),
+            "error" => Some(Self::Error),
+            "arbitrary_precision" => Some(Self::ArbitraryPrecision),
+            _ => None,
+        }
+    }
+
+    /// Converts a decimal integer literal to JSON.
+    ///
+    /// Integers that fit in `i64` or `u64` always become numbers; larger
+    /// ones are handled according to the policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NumberPrecisionError`] if `digits` is not an integer
+    /// literal, or if it is out of range and the policy is `Error` or an
+    /// unavailable `ArbitraryPrecision`.
+    pub fn integer(self, digits: &str) -> Result<Value, NumberPrecisionError> {
+        if !is_integer_literal(digits) {
+            return Err(NumberPrecisionError::new(digits, "not an integer literal"));
+        }
+        if let Ok(n) = digits.parse::<i64>() {
+            return Ok(Value::from(n));
+        }
+        if let Ok(n) = digits.parse::<u64>() {
+            return Ok(Value::from(n));
+        }
+
+        match self {
+            Self::PreserveAsString => Ok(Value::String(digits.to_string())),
+            Self::Lossy => digits
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| NumberPrecisionError::new(digits, "not representable as f64")),
+            Self::Error => Err(NumberPrecisionError::new(
+                digits,
+                "integer does not fit in 64 bits",
+            )),
+            Self::ArbitraryPrecision if !self.is_available() => Err(NumberPrecisionError::new(
+                digits,
+                "arbitrary precision requires the `arbitrary_precision` feature",
+            )),
+            Self::ArbitraryPrecision => serde_json::from_str::<Number>(digits)
+                .map(Value::Number)
+                .map_err(|e| NumberPrecisionError::new(digits, e.to_string())),
+        }
+    }
+}
+
+/// Parses JSON text using the process-wide number policy.
+///
+/// # Errors
+///
+/// Returns [`JsonParseError`] if the text is not valid JSON or holds an
+/// integer the policy rejects.
+pub fn parse_json(text: &str) -> Result<Value, JsonParseError> {
+    parse_json_with(text, number_policy())
+}
+
+/// Parses JSON text, applying `policy` to integers beyond 64 bits.
+///
+/// # Errors
+///
+/// Returns [`JsonParseError`] if the text is not valid JSON or holds an
+/// integer the policy rejects.
+pub fn parse_json_with(text: &str, policy: NumberPolicy) -> Result<Value, JsonParseError> {
+    let parse = |text: &str| serde_json::from_str(text).map_err(JsonParseError::from);
+    if policy == NumberPolicy::Lossy || (policy == NumberPolicy::ArbitraryPrecision && policy.is_available()) {
+        return parse(text);
+    }
+
+    let oversized = oversized_integers(text);
+    let Some(first) = oversized.first() else {
+        return parse(text);
+    };
+    // Only `PreserveAsString` accepts the integer here; quote them all
+    if let Err(e) = policy.integer(&text[first.clone()]) {
+        return Err(e.into());
+    }
+
+    let mut quoted = String::with_capacity(text.len() + oversized.len() * 2);
+    let mut last = 0;
+    for range in oversized {
+        quoted.push_str(&text[last..range.start]);
+        quoted.push('"');
+        quoted.push_str(&text[range.clone()]);
+        quoted.push('"');
+        last = range.end;
+    }
+    quoted.push_str(&text[last..]);
+    parse(&quoted)
+}
+
+fn is_integer_literal(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Finds integer literals outside strings that overflow `i64` and `u64`.
+fn oversized_integers(text: &str) -> Vec<std::ops::Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut found = Vec::new();
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if in_string {
+            match b {
+                b'\\' => i += 1,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'-' | b'0'..=b'9' => {
+                let start = i;
+                while i < bytes.len()
+                    && matches!(bytes[i], b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-')
+                {
+                    i += 1;
+                }
+                let token = &text[start..i];
+                if is_integer_literal(token)
+                    && token.parse::<i64>().is_err()
+                    && token.parse::<u64>().is_err()
+                {
+                    found.push(start..i);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const BIG: &str = "123456789012345678901234567890";
+
+    #[test]
+    fn test_integer_policies() {
+        assert_eq!(NumberPolicy::Error.integer("-42").unwrap(), json!(-42));
+        assert_eq!(
+            NumberPolicy::Error.integer("18446744073709551615").unwrap(),
+            json!(u64::MAX)
+        );
+
+        assert_eq!(NumberPolicy::PreserveAsString.integer(BIG).unwrap(), json!(BIG));
+        assert!(NumberPolicy::Lossy.integer(BIG).unwrap().is_f64());
+        assert!(NumberPolicy::Error.integer(BIG).is_err());
+        assert!(NumberPolicy::Lossy.integer("1.5").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_preserves_big_integers() {
+        let text = format!(r#"{{"id": {BIG}, "note": "\"{BIG}\"", "n": [-{BIG}, 7, 2.5]}}"#);
+        let value = parse_json_with(&text, NumberPolicy::PreserveAsString).unwrap();
+        assert_eq!(value["id"], json!(BIG));
+        assert_eq!(value["note"], json!(format!("\"{BIG}\"")));
+        assert_eq!(value["n"], json!([format!("-{BIG}"), 7, 2.5]));
+
+        let err = parse_json_with(&text, NumberPolicy::Error).unwrap_err();
+        assert!(matches!(err, JsonParseError::Precision(e) if e.value == BIG));
+        assert!(matches!(
+            parse_json_with("{", NumberPolicy::Error),
+            Err(JsonParseError::Invalid(_))
+        ));
+        #[cfg(not(feature = "arbitrary_precision"))]
+        assert!(parse_json_with(&text, NumberPolicy::Lossy).unwrap()["id"].is_f64());
+    }
+
+    #[test]
+    fn test_policy_names_round_trip() {
+        for policy in [NumberPolicy::PreserveAsString, NumberPolicy::Lossy, NumberPolicy::Error] {
+            assert_eq!(NumberPolicy::from_name(policy.as_str()), Some(policy));
+        }
+        assert_eq!(NumberPolicy::from_name("float"), None);
+        assert_eq!(NumberPolicy::default(), NumberPolicy::Lossy);
+    }
+
+    #[cfg(not(feature = "arbitrary_precision"))]
+    #[test]
+    fn test_arbitrary_precision_rejects_without_feature() {
+        let policy = NumberPolicy::from_name("arbitrary_precision").unwrap();
+        assert!(!policy.is_available());
+        assert!(policy.integer(BIG).is_err());
+        assert_eq!(policy.integer("7").unwrap(), json!(7));
+
+        let text = format!(r#"{{"id": {BIG}}}"#);
+        assert!(matches!(parse_json_with(&text, policy), Err(JsonParseError::Precision(_))));
+        assert_eq!(parse_json_with("[1]", policy).unwrap(), json!([1]));
+    }
+}
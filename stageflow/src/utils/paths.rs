@@ -0,0 +1,64 @@
+//! Encoding of run ids, stage names and keys into file names and object keys.
+
+use std::fmt::Write;
+
+/// Encodes `name` as a single path component or object key segment.
+///
+/// ASCII letters, digits, `-` and `_` are kept and every other byte is
+/// written as `%XX`, so distinct names always give distinct components and
+/// none contains a separator or `..`.
+#[must_use]
+pub fn encode_path_component(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+/// Reverses [`encode_path_component`], or returns `None` if `component`
+/// is not an encoded name.
+#[must_use]
+pub fn decode_path_component(component: &str) -> Option<String> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = component.get(i + 1..i + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            byte if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' => {
+                decoded.push(byte);
+                i += 1;
+            }
+            _ => return None,
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_is_collision_free_and_reversible() {
+        let names = ["fetch.page", "fetch_page", "run/2", "run_2", "a%2Fb", "..", "städte", ""];
+        let encoded: Vec<String> = names.iter().map(|name| encode_path_component(name)).collect();
+        for (i, a) in encoded.iter().enumerate() {
+            assert!(!a.contains(['/', '.', '\\']), "{a}");
+            assert_eq!(decode_path_component(a).as_deref(), Some(names[i]));
+            assert!(encoded[i + 1..].iter().all(|b| a != b), "{a} collides");
+        }
+        assert_eq!(encode_path_component("run/2"), "run%2F2");
+        assert_eq!(decode_path_component("run_2.json"), None);
+        assert_eq!(decode_path_component("bad%2"), None);
+    }
+}
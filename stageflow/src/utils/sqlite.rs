@@ -0,0 +1,53 @@
+//! `SQLite` connection shared by the stores of the `sqlite` feature.
+
+use crate::errors::StageflowError;
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A `SQLite` connection whose statements run on the blocking thread pool.
+#[derive(Debug, Clone)]
+pub(crate) struct SqliteDb {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDb {
+    /// Opens or creates the database at `path` and applies `schema`.
+    pub(crate) fn open(path: impl AsRef<Path>, schema: &str) -> Result<Self, StageflowError> {
+        Self::with_schema(Connection::open(path)?, schema)
+    }
+
+    /// Opens a private in-memory database and applies `schema`.
+    pub(crate) fn open_in_memory(schema: &str) -> Result<Self, StageflowError> {
+        Self::with_schema(Connection::open_in_memory()?, schema)
+    }
+
+    fn with_schema(conn: Connection, schema: &str) -> Result<Self, StageflowError> {
+        conn.execute_batch(schema)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` with the connection on the calling thread.
+    pub(crate) fn call_blocking<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, StageflowError> {
+        Ok(f(&mut self.conn.lock())?)
+    }
+
+    /// Runs `f` with the connection on the blocking thread pool.
+    pub(crate) async fn call<T, F>(&self, f: F) -> Result<T, StageflowError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || f(&mut conn.lock()))
+            .await
+            .map_err(|e| StageflowError::Internal(format!("SQLite task failed: {e}")))?
+            .map_err(Into::into)
+    }
+}
@@ -1,6 +1,6 @@
 //! Timestamp utilities matching Python's datetime behavior.
 
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Offset, TimeZone, Utc};
 use std::str::FromStr;
 use thiserror::Error;
 
@@ -54,13 +54,13 @@ pub enum UnixPrecision {
 /// ```
 #[must_use]
 pub fn iso_timestamp() -> String {
-    Utc::now().format("%Y-%m-%dT%H:%M:%S%.6f+00:00").to_string()
+    now_utc().format("%Y-%m-%dT%H:%M:%S%.6f+00:00").to_string()
 }
 
 /// Returns the current UTC timestamp.
 #[must_use]
 pub fn now_utc() -> Timestamp {
-    Utc::now()
+    super::determinism::current_deterministic_source().map_or_else(Utc::now, |source| source.now())
 }
 
 /// Detects the precision of a Unix timestamp based on digit count.
@@ -97,9 +97,13 @@ pub fn detect_unix_precision(timestamp: f64) -> Result<UnixPrecision, TimestampE
 ///
 /// Supports:
 /// - Unix timestamps (seconds, milliseconds, microseconds)
-/// - ISO 8601 strings
-/// - RFC 2822 strings
-/// - Common human-readable formats
+/// - ISO 8601 strings, including the basic `20231005T143000Z` form
+/// - RFC 2822 strings and HTTP asctime dates
+/// - Common database formats (Postgres, SQL Server, Oracle)
+/// - Common human-readable formats, month-first when ambiguous
+///
+/// Naive inputs are assumed to be UTC. Use [`TimestampParser`] to change the
+/// date order or the assumed offset.
 ///
 /// # Arguments
 ///
@@ -113,33 +117,122 @@ pub fn detect_unix_precision(timestamp: f64) -> Result<UnixPrecision, TimestampE
 ///
 /// Returns `TimestampError` if the input cannot be parsed.
 pub fn parse_timestamp(input: &str) -> Result<Timestamp, TimestampError> {
-    let trimmed = input.trim();
+    TimestampParser::default().parse(input)
+}
+
+/// Order of day, month, and year in ambiguous numeric dates like `05/10/2023`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateOrder {
+    /// `MM/DD/YYYY` (US).
+    #[default]
+    MonthFirst,
+    /// `DD/MM/YYYY` and `DD.MM.YYYY` (most of Europe, Latin America).
+    DayFirst,
+    /// `YYYY/MM/DD` (East Asia).
+    YearFirst,
+}
+
+/// Timestamp parser with locale-dependent settings.
+///
+/// The preferred [`DateOrder`] is tried first for numeric dates; the other
+/// orders remain as fallbacks so unambiguous dates like `25/12/2023` parse
+/// either way.
+#[derive(Debug, Clone, Copy)]
+pub struct TimestampParser {
+    date_order: DateOrder,
+    assumed_offset: FixedOffset,
+}
 
-    if trimmed.is_empty() {
-        return Err(TimestampError::EmptyString);
+impl Default for TimestampParser {
+    fn default() -> Self {
+        Self {
+            date_order: DateOrder::default(),
+            assumed_offset: Utc.fix(),
+        }
     }
+}
 
-    // Try parsing as a number first
-    if let Ok(num) = trimmed.parse::<f64>() {
-        return parse_unix_timestamp(num);
+impl TimestampParser {
+    /// Creates a parser with month-first dates and UTC for naive inputs.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    // Try ISO 8601
-    if let Ok(dt) = parse_iso8601(trimmed) {
-        return Ok(dt);
+    /// Sets the preferred order for ambiguous numeric dates.
+    #[must_use]
+    pub fn with_date_order(mut self, order: DateOrder) -> Self {
+        self.date_order = order;
+        self
     }
 
-    // Try RFC 2822
-    if let Ok(dt) = DateTime::parse_from_rfc2822(trimmed) {
-        return Ok(dt.with_timezone(&Utc));
+    /// Sets the offset assumed for inputs without timezone information.
+    #[must_use]
+    pub fn with_assumed_offset(mut self, offset: FixedOffset) -> Self {
+        self.assumed_offset = offset;
+        self
     }
 
-    // Try common formats
-    if let Ok(dt) = parse_human_readable(trimmed) {
-        return Ok(dt);
+    /// Parses a timestamp string.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TimestampError` if the input cannot be parsed.
+    pub fn parse(&self, input: &str) -> Result<Timestamp, TimestampError> {
+        let trimmed = input.trim();
+
+        if trimmed.is_empty() {
+            return Err(TimestampError::EmptyString);
+        }
+
+        // Try parsing as a number first
+        if let Ok(num) = trimmed.parse::<f64>() {
+            return parse_unix_timestamp(num);
+        }
+
+        if let Some(dt) = parse_with_offset(trimmed) {
+            return Ok(dt);
+        }
+
+        // "2023-10-05 14:30:00 UTC" style suffixes name the offset explicitly
+        let (naive_input, offset) = match strip_utc_suffix(trimmed) {
+            Some(rest) => (rest, Utc.fix()),
+            None => (trimmed, self.assumed_offset),
+        };
+
+        let formats = NAIVE_FORMATS
+            .iter()
+            .chain(numeric_date_formats(self.date_order))
+            .chain(HUMAN_FORMATS);
+        for fmt in formats {
+            if let Some(naive) = parse_naive(naive_input, fmt) {
+                return offset
+                    .from_local_datetime(&naive)
+                    .single()
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .ok_or_else(|| TimestampError::InvalidFormat(trimmed.to_string()));
+            }
+        }
+
+        Err(TimestampError::InvalidFormat(trimmed.to_string()))
     }
 
-    Err(TimestampError::InvalidFormat(trimmed.to_string()))
+    /// Parses a JSON string or number.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TimestampError::UnsupportedType` for other JSON types, or a
+    /// parse error from [`TimestampParser::parse`].
+    pub fn parse_value(&self, value: &serde_json::Value) -> Result<Timestamp, TimestampError> {
+        match value {
+            serde_json::Value::String(s) => self.parse(s),
+            serde_json::Value::Number(n) => n
+                .as_f64()
+                .ok_or_else(|| TimestampError::InvalidFormat(n.to_string()))
+                .and_then(parse_unix_timestamp),
+            _ => Err(TimestampError::UnsupportedType),
+        }
+    }
 }
 
 /// Parses a Unix timestamp with automatic precision detection.
@@ -182,64 +275,95 @@ pub fn parse_unix_timestamp(value: f64) -> Result<Timestamp, TimestampError> {
     }
 }
 
-fn parse_iso8601(s: &str) -> Result<Timestamp, TimestampError> {
+/// Formats carrying their own offset, tried before naive formats.
+const OFFSET_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%#z",
+    "%Y-%m-%d %H:%M:%S%.f%#z",
+    "%Y-%m-%d %H:%M:%S%.f %#z",
+    "%Y%m%dT%H%M%S%.f%#z",
+];
+
+/// ISO, database, and HTTP formats without an offset.
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d",
+    "%Y%m%dT%H%M%S",
+    "%Y%m%d%H%M%S",
+    "%a %b %e %H:%M:%S %Y",    // Thu Oct  5 14:30:00 2023 (asctime)
+    "%d-%b-%y %I.%M.%S%.f %p", // 05-OCT-23 02.30.00.000000 PM (Oracle)
+    "%d-%b-%Y %H:%M:%S",       // 05-Oct-2023 14:30:00
+];
+
+const MONTH_FIRST: &[&str] = &[
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%m/%d/%Y",
+    "%m-%d-%Y",
+];
+const DAY_FIRST: &[&str] = &[
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d/%m/%Y",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y",
+    "%d-%m-%Y",
+];
+const YEAR_FIRST: &[&str] = &["%Y/%m/%d %H:%M:%S", "%Y/%m/%d", "%Y.%m.%d"];
+
+const HUMAN_FORMATS: &[&str] = &[
+    "%B %d, %Y",          // October 5, 2023
+    "%b %d, %Y",          // Oct 5, 2023
+    "%d %B %Y",           // 5 October 2023
+    "%d %b %Y",           // 5 Oct 2023
+    "%B %d, %Y %H:%M:%S", // October 5, 2023 14:30:00
+    "%b %d, %Y %H:%M:%S", // Oct 5, 2023 14:30:00
+];
+
+fn numeric_date_formats(order: DateOrder) -> impl Iterator<Item = &'static &'static str> {
+    let groups: [&[&str]; 3] = match order {
+        DateOrder::MonthFirst => [MONTH_FIRST, DAY_FIRST, YEAR_FIRST],
+        DateOrder::DayFirst => [DAY_FIRST, MONTH_FIRST, YEAR_FIRST],
+        DateOrder::YearFirst => [YEAR_FIRST, MONTH_FIRST, DAY_FIRST],
+    };
+    groups.into_iter().flatten()
+}
+
+fn parse_with_offset(s: &str) -> Option<Timestamp> {
     // Handle 'Z' suffix by replacing with +00:00
-    let normalized = s.replace('Z', "+00:00");
+    let normalized = match s.strip_suffix('Z') {
+        Some(rest) => format!("{rest}+00:00"),
+        None => s.to_string(),
+    };
 
-    // Try parsing with timezone
     if let Ok(dt) = DateTime::parse_from_rfc3339(&normalized) {
-        return Ok(dt.with_timezone(&Utc));
-    }
-
-    // Try various ISO formats
-    let formats = [
-        "%Y-%m-%dT%H:%M:%S%.f%:z",
-        "%Y-%m-%dT%H:%M:%S%:z",
-        "%Y-%m-%dT%H:%M:%S%.f",
-        "%Y-%m-%dT%H:%M:%S",
-        "%Y-%m-%d %H:%M:%S%.f",
-        "%Y-%m-%d %H:%M:%S",
-        "%Y-%m-%d",
-    ];
-
-    for fmt in formats {
-        if let Ok(naive) = NaiveDateTime::parse_from_str(&normalized, fmt) {
-            return Ok(Utc.from_utc_datetime(&naive));
-        }
-        // Try date-only formats
-        if let Ok(naive_date) = chrono::NaiveDate::parse_from_str(&normalized, fmt) {
-            let naive_dt = naive_date.and_hms_opt(0, 0, 0).unwrap();
-            return Ok(Utc.from_utc_datetime(&naive_dt));
+        return Some(dt.with_timezone(&Utc));
+    }
+    for fmt in OFFSET_FORMATS {
+        if let Ok(dt) = DateTime::parse_from_str(&normalized, fmt) {
+            return Some(dt.with_timezone(&Utc));
         }
     }
-
-    Err(TimestampError::InvalidFormat(s.to_string()))
+    DateTime::parse_from_rfc2822(s)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
 }
 
-fn parse_human_readable(s: &str) -> Result<Timestamp, TimestampError> {
-    let formats = [
-        "%B %d, %Y",           // October 5, 2023
-        "%b %d, %Y",           // Oct 5, 2023
-        "%d %B %Y",            // 5 October 2023
-        "%d %b %Y",            // 5 Oct 2023
-        "%m/%d/%Y",            // 10/05/2023
-        "%d/%m/%Y",            // 05/10/2023
-        "%Y/%m/%d",            // 2023/10/05
-        "%B %d, %Y %H:%M:%S",  // October 5, 2023 14:30:00
-        "%b %d, %Y %H:%M:%S",  // Oct 5, 2023 14:30:00
-    ];
-
-    for fmt in formats {
-        if let Ok(naive) = NaiveDateTime::parse_from_str(s, fmt) {
-            return Ok(Utc.from_utc_datetime(&naive));
-        }
-        if let Ok(naive_date) = chrono::NaiveDate::parse_from_str(s, fmt) {
-            let naive_dt = naive_date.and_hms_opt(0, 0, 0).unwrap();
-            return Ok(Utc.from_utc_datetime(&naive_dt));
-        }
-    }
+fn strip_utc_suffix(s: &str) -> Option<&str> {
+    [" UTC", " GMT"]
+        .iter()
+        .find_map(|suffix| s.strip_suffix(suffix))
+        .map(str::trim_end)
+}
 
-    Err(TimestampError::InvalidFormat(s.to_string()))
+fn parse_naive(s: &str, fmt: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, fmt).ok().or_else(|| {
+        chrono::NaiveDate::parse_from_str(s, fmt)
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
 }
 
 /// Formats a timestamp as ISO 8601 string.
@@ -248,10 +372,168 @@ pub fn format_iso8601(dt: &Timestamp) -> String {
     dt.format("%Y-%m-%dT%H:%M:%S%.6f+00:00").to_string()
 }
 
+/// Sub-second precision used when formatting timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampPrecision {
+    /// Whole seconds.
+    Seconds,
+    /// Milliseconds.
+    Millis,
+    /// Microseconds (Python's `isoformat()` default).
+    #[default]
+    Micros,
+    /// Nanoseconds.
+    Nanos,
+}
+
+/// Output representation of a formatted timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampStyle {
+    /// ISO 8601 / RFC 3339 string.
+    #[default]
+    Iso8601,
+    /// RFC 2822 string, e.g. `Thu, 5 Oct 2023 14:30:00 +0000`.
+    Rfc2822,
+    /// Unix epoch integer in the unit given by the precision.
+    Unix,
+}
+
+/// Timestamp output format for a particular consumer.
+///
+/// The default matches [`format_iso8601`]: ISO 8601 with microseconds and a
+/// `+00:00` offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampFormat {
+    style: TimestampStyle,
+    precision: TimestampPrecision,
+    offset: FixedOffset,
+    zulu: bool,
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        Self::iso8601()
+    }
+}
+
+impl TimestampFormat {
+    /// ISO 8601 with microseconds in UTC.
+    #[must_use]
+    pub fn iso8601() -> Self {
+        Self {
+            style: TimestampStyle::Iso8601,
+            precision: TimestampPrecision::Micros,
+            offset: Utc.fix(),
+            zulu: false,
+        }
+    }
+
+    /// RFC 2822 in UTC.
+    #[must_use]
+    pub fn rfc2822() -> Self {
+        Self {
+            style: TimestampStyle::Rfc2822,
+            ..Self::iso8601()
+        }
+    }
+
+    /// Unix epoch integer in the given unit.
+    #[must_use]
+    pub fn unix(precision: TimestampPrecision) -> Self {
+        Self {
+            style: TimestampStyle::Unix,
+            precision,
+            ..Self::iso8601()
+        }
+    }
+
+    /// Sets the precision.
+    #[must_use]
+    pub fn with_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Sets the offset timestamps are rendered in.
+    #[must_use]
+    pub fn with_offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Renders a zero ISO 8601 offset as `Z` instead of `+00:00`.
+    #[must_use]
+    pub fn with_zulu(mut self) -> Self {
+        self.zulu = true;
+        self
+    }
+
+    /// Returns the output style.
+    #[must_use]
+    pub fn style(&self) -> TimestampStyle {
+        self.style
+    }
+
+    /// Formats a timestamp as a string.
+    #[must_use]
+    pub fn format(&self, dt: &Timestamp) -> String {
+        let local = dt.with_timezone(&self.offset);
+        match self.style {
+            TimestampStyle::Iso8601 => {
+                let fraction = match self.precision {
+                    TimestampPrecision::Seconds => "",
+                    TimestampPrecision::Millis => "%.3f",
+                    TimestampPrecision::Micros => "%.6f",
+                    TimestampPrecision::Nanos => "%.9f",
+                };
+                let offset = if self.zulu && self.offset.local_minus_utc() == 0 {
+                    "Z"
+                } else {
+                    "%:z"
+                };
+                local
+                    .format(&format!("%Y-%m-%dT%H:%M:%S{fraction}{offset}"))
+                    .to_string()
+            }
+            TimestampStyle::Rfc2822 => local.to_rfc2822(),
+            TimestampStyle::Unix => self.unix_value(dt).to_string(),
+        }
+    }
+
+    /// Formats a timestamp as JSON: a number for [`TimestampStyle::Unix`],
+    /// otherwise a string.
+    #[must_use]
+    pub fn to_value(&self, dt: &Timestamp) -> serde_json::Value {
+        match self.style {
+            TimestampStyle::Unix => serde_json::json!(self.unix_value(dt)),
+            _ => serde_json::json!(self.format(dt)),
+        }
+    }
+
+    /// Parses `input` with [`parse_timestamp`] and formats it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TimestampError` if the input cannot be parsed.
+    pub fn reformat(&self, input: &str) -> Result<String, TimestampError> {
+        parse_timestamp(input).map(|dt| self.format(&dt))
+    }
+
+    fn unix_value(self, dt: &Timestamp) -> i64 {
+        match self.precision {
+            TimestampPrecision::Seconds => dt.timestamp(),
+            TimestampPrecision::Millis => dt.timestamp_millis(),
+            TimestampPrecision::Micros => dt.timestamp_micros(),
+            // Out of i64 range only after the year 2262
+            TimestampPrecision::Nanos => dt.timestamp_nanos_opt().unwrap_or(i64::MAX),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Datelike;
+    use chrono::{Datelike, Timelike};
 
     #[test]
     fn test_detect_unix_precision_seconds() {
@@ -305,4 +587,89 @@ mod tests {
         assert!(ts.contains('T'));
         assert!(ts.ends_with("+00:00"));
     }
+
+    #[test]
+    fn test_parse_database_formats() {
+        let expected = Utc.with_ymd_and_hms(2023, 10, 5, 14, 30, 0).unwrap();
+        for input in [
+            "2023-10-05 16:30:00+02",       // PostgreSQL
+            "2023-10-05 14:30:00",          // MySQL
+            "2023-10-05 14:30:00.0000000",  // SQL Server datetime2
+            "05-OCT-23 02.30.00.000000 PM", // Oracle
+            "20231005T143000Z",             // ISO 8601 basic
+            "2023-10-05 14:30:00 UTC",
+            "Thu Oct  5 14:30:00 2023",      // asctime
+            "Thu, 05 Oct 2023 14:30:00 GMT", // RFC 2822 / HTTP
+        ] {
+            assert_eq!(parse_timestamp(input).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parser_date_order_and_offset() {
+        let day_first = TimestampParser::new().with_date_order(DateOrder::DayFirst);
+        assert_eq!(day_first.parse("05/10/2023").unwrap().month(), 10);
+        assert_eq!(parse_timestamp("05/10/2023").unwrap().month(), 5);
+        // Unambiguous dates still parse with the other order
+        assert_eq!(parse_timestamp("25/12/2023").unwrap().day(), 25);
+        assert_eq!(day_first.parse("05.10.2023").unwrap().day(), 5);
+
+        let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+        let parser = TimestampParser::new().with_assumed_offset(tokyo);
+        let dt = parser.parse("2023-10-05 09:00:00").unwrap();
+        assert_eq!(dt, Utc.with_ymd_and_hms(2023, 10, 5, 0, 0, 0).unwrap());
+        // Explicit offsets win over the assumed one
+        let dt = parser.parse("2023-10-05T09:00:00Z").unwrap();
+        assert_eq!(dt.hour(), 9);
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let parser = TimestampParser::new();
+        assert!(parser
+            .parse_value(&serde_json::json!(1_696_512_000))
+            .is_ok());
+        assert!(parser.parse_value(&serde_json::json!("2023-10-05")).is_ok());
+        assert!(matches!(
+            parser.parse_value(&serde_json::json!(true)),
+            Err(TimestampError::UnsupportedType)
+        ));
+    }
+
+    #[test]
+    fn test_timestamp_format_precision_and_offset() {
+        let dt = Utc.timestamp_opt(1_696_516_200, 123_456_789).unwrap();
+
+        assert_eq!(TimestampFormat::default().format(&dt), format_iso8601(&dt));
+        assert_eq!(
+            TimestampFormat::iso8601()
+                .with_precision(TimestampPrecision::Millis)
+                .with_zulu()
+                .format(&dt),
+            "2023-10-05T14:30:00.123Z"
+        );
+        let ist = FixedOffset::east_opt(5 * 3600 + 1800).unwrap();
+        assert_eq!(
+            TimestampFormat::iso8601()
+                .with_precision(TimestampPrecision::Seconds)
+                .with_offset(ist)
+                .format(&dt),
+            "2023-10-05T20:00:00+05:30"
+        );
+        assert_eq!(
+            TimestampFormat::rfc2822().format(&dt),
+            "Thu, 5 Oct 2023 14:30:00 +0000"
+        );
+        let millis = TimestampFormat::unix(TimestampPrecision::Millis);
+        assert_eq!(
+            millis.to_value(&dt),
+            serde_json::json!(1_696_516_200_123_i64)
+        );
+        assert_eq!(
+            TimestampFormat::unix(TimestampPrecision::Seconds)
+                .reformat("2023-10-05T14:30:00Z")
+                .unwrap(),
+            "1696516200"
+        );
+    }
 }
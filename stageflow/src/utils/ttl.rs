@@ -0,0 +1,185 @@
+//! Clock-skew tolerant TTL arithmetic.
+//!
+//! Expiry times are stored as wall-clock Unix seconds so they survive
+//! serialization, but wall clocks jump. A [`TtlClock`] reads time from a
+//! [`Clock`] and, for expiries it created itself, also keeps a monotonic
+//! deadline that clock adjustments cannot move. Expiries loaded from
+//! elsewhere fall back to the wall clock, widened by a skew tolerance.
+
+use super::determinism::{Clock, SystemClock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default extra time granted to wall-clock expiries: none.
+pub const DEFAULT_SKEW_TOLERANCE: Duration = Duration::ZERO;
+
+/// When something expires.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Expiry {
+    /// Wall-clock expiry as Unix seconds.
+    pub expires_at: f64,
+    /// Monotonic deadline, known only in the process that created the
+    /// expiry.
+    #[serde(skip)]
+    deadline: Option<Instant>,
+}
+
+impl Expiry {
+    /// Creates an expiry from a stored wall-clock time, without a monotonic
+    /// deadline.
+    #[must_use]
+    pub fn at(expires_at: f64) -> Self {
+        Self {
+            expires_at,
+            deadline: None,
+        }
+    }
+
+    /// Returns true if the expiry carries a monotonic deadline.
+    #[must_use]
+    pub fn is_monotonic(&self) -> bool {
+        self.deadline.is_some()
+    }
+}
+
+/// Time source for TTL checks.
+///
+/// The default reads the system clock and trusts monotonic deadlines. A
+/// clock set with [`new`](Self::new) is authoritative instead, so tests and
+/// deterministic runs can expire entries by advancing it.
+#[derive(Debug, Clone)]
+pub struct TtlClock {
+    clock: Arc<dyn Clock>,
+    monotonic: bool,
+    skew_tolerance: Duration,
+}
+
+impl Default for TtlClock {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            monotonic: true,
+            skew_tolerance: DEFAULT_SKEW_TOLERANCE,
+        }
+    }
+}
+
+impl TtlClock {
+    /// Creates a TTL clock that reads only from `clock`.
+    #[must_use]
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            monotonic: false,
+            skew_tolerance: DEFAULT_SKEW_TOLERANCE,
+        }
+    }
+
+    /// Sets how much longer than their wall-clock expiry entries are kept
+    /// when no monotonic deadline is available.
+    #[must_use]
+    pub fn with_skew_tolerance(mut self, tolerance: Duration) -> Self {
+        self.skew_tolerance = tolerance;
+        self
+    }
+
+    /// Sets whether expiries created by this clock carry a monotonic
+    /// deadline.
+    #[must_use]
+    pub fn with_monotonic(mut self, monotonic: bool) -> Self {
+        self.monotonic = monotonic;
+        self
+    }
+
+    /// Returns the skew tolerance.
+    #[must_use]
+    pub fn skew_tolerance(&self) -> Duration {
+        self.skew_tolerance
+    }
+
+    /// Returns the current wall-clock time as Unix seconds.
+    #[must_use]
+    pub fn now_unix_secs(&self) -> f64 {
+        (self.clock.now() - DateTime::<Utc>::UNIX_EPOCH)
+            .to_std()
+            .map_or(0.0, |since_epoch| since_epoch.as_secs_f64())
+    }
+
+    /// Returns the expiry `ttl` from now.
+    #[must_use]
+    pub fn expiry_after(&self, ttl: Duration) -> Expiry {
+        self.expiry_from(self.now_unix_secs(), ttl)
+    }
+
+    /// Returns the expiry `ttl` after `created_at`, a Unix time read from
+    /// this clock just now.
+    #[must_use]
+    pub fn expiry_from(&self, created_at: f64, ttl: Duration) -> Expiry {
+        Expiry {
+            expires_at: created_at + ttl.as_secs_f64(),
+            deadline: self.monotonic.then(|| Instant::now() + ttl),
+        }
+    }
+
+    /// Returns true once `expiry` has passed.
+    #[must_use]
+    pub fn is_expired(&self, expiry: &Expiry) -> bool {
+        self.remaining(expiry).is_none()
+    }
+
+    /// Returns the time left before `expiry`, or `None` if it has passed.
+    #[must_use]
+    pub fn remaining(&self, expiry: &Expiry) -> Option<Duration> {
+        if let Some(deadline) = expiry.deadline.filter(|_| self.monotonic) {
+            return deadline
+                .checked_duration_since(Instant::now())
+                .filter(|left| !left.is_zero());
+        }
+        let left = expiry.expires_at + self.skew_tolerance.as_secs_f64() - self.now_unix_secs();
+        (left > 0.0).then(|| Duration::from_secs_f64(left))
+    }
+
+    /// Returns the wall-clock time before which entries written `ttl` ago
+    /// are considered expired, allowing for skew.
+    #[must_use]
+    pub fn cutoff(&self, ttl: Duration) -> f64 {
+        self.now_unix_secs() - ttl.as_secs_f64() - self.skew_tolerance.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::ManualClock;
+
+    #[test]
+    fn test_monotonic_deadline_ignores_wall_clock_jumps() {
+        let manual = Arc::new(ManualClock::new(chrono::Utc::now(), chrono::Duration::zero()));
+        let ttl = TtlClock::new(manual.clone()).with_monotonic(true);
+        let expiry = ttl.expiry_after(Duration::from_secs(60));
+        assert!(expiry.is_monotonic());
+
+        // The wall clock jumps an hour ahead, but the deadline holds
+        manual.advance(chrono::Duration::hours(1));
+        assert!(!ttl.is_expired(&expiry));
+
+        // A stored expiry only has the wall clock to go by
+        let stored: Expiry = serde_json::from_value(serde_json::to_value(expiry).unwrap()).unwrap();
+        assert!(!stored.is_monotonic());
+        assert!(ttl.is_expired(&stored));
+        assert!(!ttl.clone().with_skew_tolerance(Duration::from_secs(7200)).is_expired(&stored));
+    }
+
+    #[test]
+    fn test_injected_clock_is_authoritative() {
+        let manual = Arc::new(ManualClock::new(chrono::Utc::now(), chrono::Duration::zero()));
+        let ttl = TtlClock::new(manual.clone());
+        let expiry = ttl.expiry_after(Duration::from_secs(60));
+        assert!(ttl.remaining(&expiry).is_some());
+        manual.advance(chrono::Duration::seconds(61));
+        assert!(ttl.is_expired(&expiry));
+        assert!(TtlClock::default().is_expired(&Expiry::at(0.0)));
+    }
+}
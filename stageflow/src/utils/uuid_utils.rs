@@ -2,22 +2,63 @@
 
 use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
+use rand::Rng;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::events::EventSink;
+
 /// Generates a new UUID v4.
+///
+/// Reported to the global [`UuidCollisionMonitor`] when one is set.
 #[must_use]
 pub fn generate_uuid() -> Uuid {
-    Uuid::new_v4()
+    super::determinism::current_deterministic_source()
+        .map_or_else(|| monitored(Uuid::new_v4()), |source| source.uuid_v4())
 }
 
 /// Generates a new UUID v7 (time-ordered).
+///
+/// Reported to the global [`UuidCollisionMonitor`] when one is set.
 #[must_use]
 pub fn generate_uuid_v7() -> Uuid {
-    Uuid::now_v7()
+    super::determinism::current_deterministic_source()
+        .map_or_else(|| monitored(Uuid::now_v7()), |source| source.uuid_v7())
+}
+
+// Deterministic UUIDs repeat across replays by design, so only random ones
+// are reported.
+fn monitored(id: Uuid) -> Uuid {
+    if let Some(monitor) = get_uuid_monitor() {
+        monitor.sample(&id.to_string());
+    }
+    id
 }
 
+static GLOBAL_MONITOR: RwLock<Option<Arc<UuidCollisionMonitor>>> = RwLock::new(None);
+
+/// Sets the monitor that observes UUIDs from [`generate_uuid`] and
+/// [`generate_uuid_v7`].
+pub fn set_uuid_monitor(monitor: Arc<UuidCollisionMonitor>) {
+    *GLOBAL_MONITOR.write() = Some(monitor);
+}
+
+/// Clears the global UUID monitor.
+pub fn clear_uuid_monitor() {
+    *GLOBAL_MONITOR.write() = None;
+}
+
+/// Gets the global UUID monitor, if one is set.
+#[must_use]
+pub fn get_uuid_monitor() -> Option<Arc<UuidCollisionMonitor>> {
+    GLOBAL_MONITOR.read().clone()
+}
+
+/// Callback invoked with UUID events.
+pub type UuidAlertHook = Arc<dyn Fn(&UuidEvent) + Send + Sync>;
+
 /// Event emitted when a UUID is observed.
 #[derive(Debug, Clone)]
 pub struct UuidEvent {
@@ -53,8 +94,27 @@ pub struct UuidCollisionMonitor {
     window: RwLock<VecDeque<WindowEntry>>,
     /// Listeners to notify on UUID events.
     listeners: RwLock<Vec<Arc<dyn Fn(UuidEvent) + Send + Sync>>>,
+    /// Hooks to notify on collisions only.
+    alert_hooks: RwLock<Vec<UuidAlertHook>>,
     /// Optional category for emitted events.
     category: Option<String>,
+    /// Fraction of generated UUIDs passed to [`Self::sample`] that are observed.
+    sample_rate: f64,
+    /// Number of UUIDs observed.
+    observed: AtomicU64,
+    /// Number of collisions detected.
+    collisions: AtomicU64,
+}
+
+impl std::fmt::Debug for UuidCollisionMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UuidCollisionMonitor")
+            .field("ttl_seconds", &self.ttl_seconds)
+            .field("max_entries", &self.max_entries)
+            .field("category", &self.category)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
 }
 
 impl UuidCollisionMonitor {
@@ -72,10 +132,45 @@ impl UuidCollisionMonitor {
             max_entries,
             window: RwLock::new(VecDeque::new()),
             listeners: RwLock::new(Vec::new()),
+            alert_hooks: RwLock::new(Vec::new()),
             category,
+            sample_rate: 1.0,
+            observed: AtomicU64::new(0),
+            collisions: AtomicU64::new(0),
         }
     }
 
+    /// Sets the fraction of UUIDs observed by [`Self::sample`], clamped to
+    /// `0.0..=1.0`.
+    ///
+    /// Sampling trades detection probability for less locking on hot ID
+    /// generation paths.
+    #[must_use]
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = if rate.is_nan() {
+            0.0
+        } else {
+            rate.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    /// Returns the sample rate.
+    #[must_use]
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Observes a UUID subject to the sample rate.
+    ///
+    /// Returns `None` if the UUID was not sampled, otherwise whether it
+    /// collided.
+    pub fn sample(&self, uuid: &str) -> Option<bool> {
+        let sampled = self.sample_rate >= 1.0
+            || (self.sample_rate > 0.0 && rand::thread_rng().gen::<f64>() < self.sample_rate);
+        sampled.then(|| self.observe(uuid))
+    }
+
     /// Observes a UUID and returns whether it was a collision.
     ///
     /// If the UUID is already in the window, this returns `true` (collision).
@@ -107,6 +202,12 @@ impl UuidCollisionMonitor {
             uuid: uuid.to_string(),
             timestamp: now,
         });
+        drop(window);
+
+        self.observed.fetch_add(1, Ordering::Relaxed);
+        if collision {
+            self.collisions.fetch_add(1, Ordering::Relaxed);
+        }
 
         // Notify listeners
         let event = UuidEvent {
@@ -117,6 +218,12 @@ impl UuidCollisionMonitor {
             skew_ms: None,
         };
 
+        if collision {
+            for hook in self.alert_hooks.read().iter() {
+                hook(&event);
+            }
+        }
+
         let listeners = self.listeners.read();
         for listener in listeners.iter() {
             listener(event.clone());
@@ -125,6 +232,40 @@ impl UuidCollisionMonitor {
         collision
     }
 
+    /// Registers a hook called only when a collision is detected.
+    pub fn on_collision<F>(&self, hook: F)
+    where
+        F: Fn(&UuidEvent) + Send + Sync + 'static,
+    {
+        self.alert_hooks.write().push(Arc::new(hook));
+    }
+
+    /// Emits a `uuid.collision` event to `sink` for every collision.
+    pub fn alert_to_sink(&self, sink: Arc<dyn EventSink>) {
+        self.on_collision(move |event| {
+            sink.try_emit(
+                "uuid.collision",
+                Some(serde_json::json!({
+                    "uuid": event.value,
+                    "category": event.category,
+                    "observed_at": event.observed_at.to_rfc3339(),
+                })),
+            );
+        });
+    }
+
+    /// Returns the number of UUIDs observed.
+    #[must_use]
+    pub fn observed_count(&self) -> u64 {
+        self.observed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of collisions detected.
+    #[must_use]
+    pub fn collision_count(&self) -> u64 {
+        self.collisions.load(Ordering::Relaxed)
+    }
+
     /// Registers a listener to be notified on UUID observations.
     pub fn add_listener<F>(&self, listener: F)
     where
@@ -218,4 +359,52 @@ mod tests {
         monitor.observe("test-uuid");
         assert!(notified.load(Ordering::SeqCst));
     }
+
+    #[test]
+    fn test_sample_rate() {
+        let never = UuidCollisionMonitor::default().with_sample_rate(0.0);
+        assert_eq!(never.sample("uuid-1"), None);
+        assert_eq!(never.observed_count(), 0);
+
+        let always = UuidCollisionMonitor::default().with_sample_rate(7.0);
+        assert!((always.sample_rate() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(always.sample("uuid-1"), Some(false));
+        assert_eq!(always.sample("uuid-1"), Some(true));
+        assert_eq!(always.observed_count(), 2);
+        assert_eq!(always.collision_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_collision_alert_to_sink() {
+        use crate::events::CollectingEventSink;
+
+        let monitor = UuidCollisionMonitor::new(10.0, 100, Some("run_id".to_string()));
+        let sink = Arc::new(CollectingEventSink::new());
+        monitor.alert_to_sink(sink.clone());
+
+        monitor.observe("dup");
+        assert!(sink.events().is_empty());
+        monitor.observe("dup");
+
+        let events = sink.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "uuid.collision");
+        let data = events[0].1.as_ref().unwrap();
+        assert_eq!(data["uuid"], "dup");
+        assert_eq!(data["category"], "run_id");
+    }
+
+    #[test]
+    fn test_global_monitor_observes_generated_uuids() {
+        let monitor = Arc::new(UuidCollisionMonitor::default());
+        set_uuid_monitor(monitor.clone());
+        let id = generate_uuid();
+        let id_v7 = generate_uuid_v7();
+        clear_uuid_monitor();
+
+        // Other tests may generate UUIDs concurrently, so only check ours
+        assert!(monitor.observe(&id.to_string()));
+        assert!(monitor.observe(&id_v7.to_string()));
+        assert!(get_uuid_monitor().is_none());
+    }
 }
@@ -483,6 +483,88 @@ impl WebSearchConfig {
     }
 }
 
+/// Configuration for breadth-first site crawling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlConfig {
+    /// Maximum link depth from the start URL (0 fetches only the start page).
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    /// Maximum number of pages to fetch.
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+    /// Whether to only follow links on the start URL's domain.
+    #[serde(default = "default_true")]
+    pub same_domain_only: bool,
+    /// Minimum delay between requests to the same domain, in seconds.
+    #[serde(default = "default_politeness_delay")]
+    pub politeness_delay_seconds: f64,
+}
+
+fn default_max_depth() -> usize {
+    2
+}
+
+fn default_max_pages() -> usize {
+    50
+}
+
+fn default_politeness_delay() -> f64 {
+    0.5
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_depth(),
+            max_pages: default_max_pages(),
+            same_domain_only: true,
+            politeness_delay_seconds: default_politeness_delay(),
+        }
+    }
+}
+
+impl CrawlConfig {
+    /// Creates a new crawl configuration with defaults.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum depth.
+    #[must_use]
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the maximum number of pages.
+    #[must_use]
+    pub fn with_max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    /// Allows following links to other domains.
+    #[must_use]
+    pub fn allow_external(mut self) -> Self {
+        self.same_domain_only = false;
+        self
+    }
+
+    /// Sets the per-domain politeness delay in seconds.
+    #[must_use]
+    pub fn with_politeness_delay(mut self, seconds: f64) -> Self {
+        self.politeness_delay_seconds = seconds;
+        self
+    }
+
+    /// Gets the politeness delay as a Duration.
+    #[must_use]
+    pub fn politeness_delay(&self) -> Duration {
+        Duration::from_secs_f64(self.politeness_delay_seconds.max(0.0))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -570,4 +652,17 @@ mod tests {
         assert!(!config.auto_extract);
         assert!(config.auto_navigate);
     }
+
+    #[test]
+    fn test_crawl_config() {
+        let config = CrawlConfig::new()
+            .with_max_depth(3)
+            .with_politeness_delay(0.25)
+            .allow_external();
+
+        assert_eq!(config.max_depth, 3);
+        assert_eq!(config.max_pages, 50);
+        assert!(!config.same_domain_only);
+        assert_eq!(config.politeness_delay(), Duration::from_millis(250));
+    }
 }
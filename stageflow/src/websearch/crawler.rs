@@ -0,0 +1,543 @@
+//! Breadth-first site crawler.
+
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use super::config::{CrawlConfig, WebSearchConfig};
+use super::models::WebPage;
+use super::protocols::{ContentExtractor, FetchResult, Fetcher, Navigator};
+use super::robots::{split_origin, RobotsPolicy};
+use super::run_utils::{extract_domain, extract_unique_links, same_domain, FetchProgress, SiteMap};
+use super::sitemap::fetch_sitemap_urls;
+
+/// Callback invoked after every page fetch with the crawl's running progress.
+pub type CrawlProgressCallback = Arc<dyn Fn(&FetchProgress) + Send + Sync>;
+
+/// Crawls a site breadth-first from a start URL and builds a [`SiteMap`].
+///
+/// Each depth level is fetched concurrently, up to
+/// [`WebSearchConfig::max_concurrent`] requests at a time, while requests to
+/// the same domain are spaced by the politeness delay from [`CrawlConfig`].
+/// URLs are deduplicated ignoring fragments and trailing slashes.
+///
+/// With a [`RobotsPolicy`] attached, disallowed URLs are never requested and
+/// a declared `Crawl-delay` raises the politeness delay for that domain.
+pub struct Crawler {
+    fetcher: Arc<dyn Fetcher>,
+    extractor: Arc<dyn ContentExtractor>,
+    navigator: Option<Arc<dyn Navigator>>,
+    config: WebSearchConfig,
+    crawl: CrawlConfig,
+    on_progress: Option<CrawlProgressCallback>,
+    robots: Option<Arc<RobotsPolicy>>,
+    seed_from_sitemaps: bool,
+}
+
+impl std::fmt::Debug for Crawler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Crawler")
+            .field("config", &self.config)
+            .field("crawl", &self.crawl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Crawler {
+    /// Creates a crawler with the default [`CrawlConfig`].
+    #[must_use]
+    pub fn new(
+        fetcher: Arc<dyn Fetcher>,
+        extractor: Arc<dyn ContentExtractor>,
+        config: WebSearchConfig,
+    ) -> Self {
+        Self {
+            fetcher,
+            extractor,
+            navigator: None,
+            config,
+            crawl: CrawlConfig::default(),
+            on_progress: None,
+            robots: None,
+            seed_from_sitemaps: false,
+        }
+    }
+
+    /// Sets the navigator used to detect navigation actions and pagination.
+    #[must_use]
+    pub fn with_navigator(mut self, navigator: Arc<dyn Navigator>) -> Self {
+        self.navigator = Some(navigator);
+        self
+    }
+
+    /// Sets the crawl limits.
+    #[must_use]
+    pub fn with_crawl_config(mut self, crawl: CrawlConfig) -> Self {
+        self.crawl = crawl;
+        self
+    }
+
+    /// Sets a callback invoked after every page fetch.
+    #[must_use]
+    pub fn with_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&FetchProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets the robots.txt policy consulted before every request.
+    #[must_use]
+    pub fn with_robots_policy(mut self, robots: Arc<RobotsPolicy>) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    /// Seeds the first level with URLs from the site's sitemaps.
+    ///
+    /// Sitemaps declared in robots.txt are used when a robots policy is
+    /// attached; otherwise `/sitemap.xml` on the start URL's origin is tried.
+    #[must_use]
+    pub fn seed_from_sitemaps(mut self) -> Self {
+        self.seed_from_sitemaps = true;
+        self
+    }
+
+    /// Gets the crawl configuration.
+    #[must_use]
+    pub fn crawl_config(&self) -> &CrawlConfig {
+        &self.crawl
+    }
+
+    /// Crawls from `start_url`.
+    ///
+    /// Failed fetches are kept in the site map as error pages rather than
+    /// aborting the crawl.
+    pub async fn crawl(&self, start_url: &str) -> SiteMap {
+        let started = Instant::now();
+        let mut site_map = SiteMap::new(start_url);
+        let politeness = Politeness::default();
+        let max_concurrent = self.config.max_concurrent.max(1);
+
+        let start = strip_fragment(start_url).to_string();
+        let mut seen = HashSet::from([dedupe_key(&start)]);
+        let mut frontier = Vec::new();
+        if self.is_permitted(&start).await {
+            frontier.push(start);
+        }
+        if self.seed_from_sitemaps {
+            for seed in self.sitemap_seeds(start_url).await {
+                if seen.len() >= self.crawl.max_pages {
+                    break;
+                }
+                let url = strip_fragment(&seed);
+                if self.admits(start_url, url, &seen).await {
+                    seen.insert(dedupe_key(url));
+                    frontier.push(url.to_string());
+                }
+            }
+        }
+        let progress = Mutex::new(FetchProgress::new(frontier.len()));
+
+        for depth in 0..=self.crawl.max_depth {
+            if frontier.is_empty() {
+                break;
+            }
+
+            let pages: Vec<WebPage> = stream::iter(&frontier)
+                .map(|url| self.visit(url, &politeness, &progress, started))
+                .buffered(max_concurrent)
+                .collect()
+                .await;
+            site_map.depth_reached = depth;
+
+            let mut next = Vec::new();
+            if depth < self.crawl.max_depth {
+                let links = pages.iter().filter(|p| p.success()).flat_map(|p| &p.links);
+                for link in links {
+                    if seen.len() >= self.crawl.max_pages {
+                        break;
+                    }
+                    let url = strip_fragment(&link.url);
+                    if self.admits(start_url, url, &seen).await {
+                        seen.insert(dedupe_key(url));
+                        next.push(url.to_string());
+                    }
+                }
+                progress.lock().total += next.len();
+            }
+
+            site_map.pages.extend(pages);
+            frontier = next;
+        }
+
+        site_map.internal_links = extract_unique_links(&site_map.pages, true, false);
+        site_map.external_links = extract_unique_links(&site_map.pages, false, true);
+        site_map.duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        site_map
+    }
+
+    /// Returns true if `url` is new, in scope, and allowed by robots.txt.
+    async fn admits(&self, start_url: &str, url: &str, seen: &HashSet<String>) -> bool {
+        let is_http = url.starts_with("http://") || url.starts_with("https://");
+        let in_scope = !self.crawl.same_domain_only || same_domain(start_url, url);
+        is_http && in_scope && !seen.contains(&dedupe_key(url)) && self.is_permitted(url).await
+    }
+
+    async fn is_permitted(&self, url: &str) -> bool {
+        match self.robots {
+            Some(ref robots) => {
+                let allowed = robots.is_allowed(url).await;
+                if !allowed {
+                    debug!("Skipping {} disallowed by robots.txt", url);
+                }
+                allowed
+            }
+            None => true,
+        }
+    }
+
+    async fn sitemap_seeds(&self, start_url: &str) -> Vec<String> {
+        let mut sitemaps = match self.robots {
+            Some(ref robots) => robots.sitemaps(start_url).await,
+            None => Vec::new(),
+        };
+        if sitemaps.is_empty() {
+            sitemaps.extend(split_origin(start_url).map(|(origin, _)| format!("{origin}/sitemap.xml")));
+        }
+
+        let mut seeds = Vec::new();
+        for sitemap_url in sitemaps {
+            match fetch_sitemap_urls(self.fetcher.as_ref(), &sitemap_url, self.crawl.max_pages).await {
+                Ok(entries) => seeds.extend(entries.into_iter().map(|e| e.loc)),
+                Err(e) => debug!("Sitemap {} unavailable: {}", sitemap_url, e),
+            }
+        }
+        seeds
+    }
+
+    async fn visit(
+        &self,
+        url: &str,
+        politeness: &Politeness,
+        progress: &Mutex<FetchProgress>,
+        started: Instant,
+    ) -> WebPage {
+        let mut delay = self.crawl.politeness_delay();
+        if let Some(ref robots) = self.robots {
+            delay = delay.max(robots.crawl_delay(url).await.unwrap_or_default());
+        }
+        politeness.wait(url, delay).await;
+
+        let fetch_start = Instant::now();
+        let page = match self.fetcher.fetch(url, None, None).await {
+            Ok(result) => self.build_page(url, &result),
+            Err(e) => WebPage::error_result(
+                url,
+                e.to_string(),
+                fetch_start.elapsed().as_secs_f64() * 1000.0,
+            ),
+        };
+
+        let snapshot = {
+            let mut progress = progress.lock();
+            let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+            if page.success() {
+                progress.record_success(url, elapsed_ms);
+            } else {
+                progress.record_error(url, elapsed_ms);
+            }
+            progress.clone()
+        };
+        if let Some(ref callback) = self.on_progress {
+            callback(&snapshot);
+        }
+
+        page
+    }
+
+    fn build_page(&self, url: &str, result: &FetchResult) -> WebPage {
+        let mut page = fetched_page(url, result);
+        if result.is_success() && result.is_html() {
+            let navigator = self
+                .navigator
+                .as_deref()
+                .filter(|_| self.config.auto_navigate);
+            extract_into(
+                &mut page,
+                &result.text,
+                self.extractor.as_ref(),
+                navigator,
+                self.config.auto_extract,
+            );
+        }
+        page
+    }
+}
+
+/// Builds an unextracted page from a fetch result.
+pub(super) fn fetched_page(url: &str, result: &FetchResult) -> WebPage {
+    WebPage {
+        url: url.to_string(),
+        final_url: Some(result.final_url.clone()),
+        status_code: result.status_code,
+        fetch_duration_ms: result.duration_ms,
+        fetched_at: Some(Utc::now().format("%Y-%m-%dT%H:%M:%S%.6f+00:00").to_string()),
+        ..Default::default()
+    }
+}
+
+/// Fills a page's content, links, and navigation from its HTML.
+///
+/// Without `full_extract` only links are extracted.
+pub(super) fn extract_into(
+    page: &mut WebPage,
+    html: &str,
+    extractor: &dyn ContentExtractor,
+    navigator: Option<&dyn Navigator>,
+    full_extract: bool,
+) {
+    let extract_start = Instant::now();
+    let base_url = Some(page.final_url.as_deref().unwrap_or(&page.url));
+    if full_extract {
+        let extracted = extractor.extract(html, base_url, None);
+        page.markdown = extracted.markdown;
+        page.plain_text = extracted.plain_text;
+        page.metadata = extracted.metadata;
+        page.links = extracted.links;
+        page.word_count = extracted.word_count;
+    } else {
+        page.links = extractor.extract_links(html, base_url, None);
+    }
+
+    if let Some(navigator) = navigator {
+        let navigation = navigator.analyze(html, base_url);
+        page.navigation_actions = navigation.actions;
+        page.pagination = navigation.pagination;
+    }
+    page.extract_duration_ms = extract_start.elapsed().as_secs_f64() * 1000.0;
+}
+
+/// Hands out per-domain request slots spaced by a delay.
+#[derive(Default)]
+struct Politeness {
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl Politeness {
+    async fn wait(&self, url: &str, delay: Duration) {
+        if delay.is_zero() {
+            return;
+        }
+        let domain = extract_domain(url).unwrap_or_default();
+        let slot = {
+            let mut slots = self.next_slot.lock();
+            let now = Instant::now();
+            let slot = slots.get(&domain).map_or(now, |&next| next.max(now));
+            slots.insert(domain, slot + delay);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+fn strip_fragment(url: &str) -> &str {
+    url.split_once('#').map_or(url, |(base, _)| base)
+}
+
+fn dedupe_key(url: &str) -> String {
+    url.trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::FetchConfig;
+    use super::super::extractor::HtmlContentExtractor;
+    use super::super::fetcher::test_server::serve;
+    use super::super::fetcher::HttpFetcher;
+    use super::*;
+
+    fn page(links: &[&str]) -> (u16, Vec<(String, String)>, String) {
+        let anchors: String = links
+            .iter()
+            .map(|href| ["<a href=\"", href, "\">link</a>"].concat())
+            .collect();
+        (
+            200,
+            vec![("Content-Type".to_string(), "text/html".to_string())],
+            format!("<html><body><p>content</p>{anchors}</body></html>"),
+        )
+    }
+
+    fn site(path: &str) -> (u16, Vec<(String, String)>, String) {
+        match path {
+            "/" => page(&["/a", "/b", "https://other.example/x"]),
+            "/a" => page(&["/c", "/a#section", "/"]),
+            "/c" => page(&["/d"]),
+            "/b" | "/d" => page(&[]),
+            _ => (404, Vec::new(), String::new()),
+        }
+    }
+
+    fn crawler(config: WebSearchConfig, crawl: CrawlConfig) -> Crawler {
+        Crawler::new(
+            Arc::new(HttpFetcher::new(FetchConfig::new().with_timeout(5.0)).unwrap()),
+            Arc::new(HtmlContentExtractor::default()),
+            config,
+        )
+        .with_crawl_config(crawl)
+    }
+
+    #[tokio::test]
+    async fn test_crawl_breadth_first_with_dedupe() {
+        let (base, requests) = serve(site).await;
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let recorded = updates.clone();
+
+        let site_map = crawler(
+            WebSearchConfig::new(),
+            CrawlConfig::new().with_max_depth(2).with_politeness_delay(0.0),
+        )
+        .with_progress(move |p| recorded.lock().push(p.clone()))
+        .crawl(&format!("{base}/"))
+        .await;
+
+        let paths: Vec<String> = site_map
+            .pages
+            .iter()
+            .map(|p| p.url.trim_start_matches(&base).to_string())
+            .collect();
+        assert_eq!(paths, vec!["/", "/a", "/b", "/c"]);
+        assert_eq!(requests.lock().len(), 4);
+        assert_eq!(site_map.depth_reached, 2);
+        assert!(site_map.pages.iter().all(WebPage::success));
+        assert!(site_map
+            .external_links
+            .iter()
+            .any(|l| l.url == "https://other.example/x"));
+
+        let updates = updates.lock();
+        let last = updates.last().unwrap();
+        assert_eq!(updates.len(), 4);
+        assert_eq!(last.completed, 4);
+        assert_eq!(last.total, 4);
+        assert_eq!(last.success_count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_respects_max_pages() {
+        let (base, requests) = serve(site).await;
+
+        let site_map = crawler(
+            WebSearchConfig::new(),
+            CrawlConfig::new()
+                .with_max_depth(5)
+                .with_max_pages(2)
+                .with_politeness_delay(0.0),
+        )
+        .crawl(&base)
+        .await;
+
+        assert_eq!(site_map.pages.len(), 2);
+        assert_eq!(requests.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_spaces_requests_to_same_domain() {
+        let (base, _) = serve(site).await;
+
+        let site_map = crawler(
+            WebSearchConfig::new().with_max_concurrent(8),
+            CrawlConfig::new().with_max_depth(1).with_politeness_delay(0.1),
+        )
+        .crawl(&base)
+        .await;
+
+        // Start page plus /a and /b: two gaps of at least 100ms each
+        assert_eq!(site_map.pages.len(), 3);
+        assert!(site_map.duration_ms >= 200.0);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_records_failed_pages() {
+        let (base, _) = serve(|path| match path {
+            "/" => page(&["/missing"]),
+            _ => (404, Vec::new(), String::new()),
+        })
+        .await;
+
+        let site_map = crawler(
+            WebSearchConfig::new(),
+            CrawlConfig::new().with_politeness_delay(0.0),
+        )
+        .crawl(&base)
+        .await;
+
+        assert_eq!(site_map.pages.len(), 2);
+        assert_eq!(site_map.pages[1].status_code, 404);
+        assert!(!site_map.pages[1].success());
+    }
+
+    #[tokio::test]
+    async fn test_crawl_honors_robots_and_sitemap_seeds() {
+        let origin = Arc::new(Mutex::new(String::new()));
+        let host = origin.clone();
+        let (base, requests) = serve(move |path| {
+            let base = host.lock().clone();
+            match path {
+                "/robots.txt" => (
+                    200,
+                    Vec::new(),
+                    format!("User-agent: *\nDisallow: /b\nSitemap: {base}/map.xml"),
+                ),
+                "/map.xml" => (
+                    200,
+                    Vec::new(),
+                    format!("<urlset><url><loc>{base}/e</loc></url><url><loc>{base}/b</loc></url></urlset>"),
+                ),
+                "/e" => page(&[]),
+                other => site(other),
+            }
+        })
+        .await;
+        *origin.lock() = base.clone();
+
+        let fetcher: Arc<dyn Fetcher> =
+            Arc::new(HttpFetcher::new(FetchConfig::new().with_timeout(5.0)).unwrap());
+        let site_map = Crawler::new(
+            fetcher.clone(),
+            Arc::new(HtmlContentExtractor::default()),
+            WebSearchConfig::new(),
+        )
+        .with_crawl_config(CrawlConfig::new().with_max_depth(1).with_politeness_delay(0.0))
+        .with_robots_policy(Arc::new(RobotsPolicy::new(fetcher)))
+        .seed_from_sitemaps()
+        .crawl(&format!("{base}/"))
+        .await;
+
+        let paths: Vec<String> = site_map
+            .pages
+            .iter()
+            .map(|p| p.url.trim_start_matches(&base).to_string())
+            .collect();
+        assert_eq!(paths, vec!["/", "/e", "/a"]);
+        let requested: Vec<String> = requests
+            .lock()
+            .iter()
+            .filter_map(|r| r.split_whitespace().nth(1).map(String::from))
+            .collect();
+        assert!(!requested.contains(&"/b".to_string()));
+        assert_eq!(requested.iter().filter(|p| *p == "/robots.txt").count(), 1);
+    }
+
+    #[test]
+    fn test_url_normalization() {
+        assert_eq!(strip_fragment("https://a.com/x#top"), "https://a.com/x");
+        assert_eq!(dedupe_key("https://a.com/"), dedupe_key("https://a.com"));
+    }
+}
@@ -0,0 +1,643 @@
+//! HTML content extraction backed by scraper.
+
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::config::ExtractionConfig;
+use super::models::{ExtractedLink, PageMetadata};
+use super::protocols::{ContentExtractor, ExtractionResult, HeadingOutline};
+
+/// Maximum characters of surrounding text kept as link context.
+const LINK_CONTEXT_CHARS: usize = 200;
+
+/// [`ContentExtractor`] that converts HTML to markdown.
+///
+/// Elements matching `remove_selectors` are dropped, the first match of
+/// `main_content_selectors` (falling back to `<body>`) is used as the content
+/// root, and each `preserve_*` flag controls whether the corresponding
+/// markup is kept as markdown or flattened to text. Invalid selectors in the
+/// configuration are ignored.
+#[derive(Debug, Clone)]
+pub struct HtmlContentExtractor {
+    config: ExtractionConfig,
+    remove: Vec<Selector>,
+    main_content: Vec<Selector>,
+}
+
+impl Default for HtmlContentExtractor {
+    fn default() -> Self {
+        Self::new(ExtractionConfig::default())
+    }
+}
+
+impl HtmlContentExtractor {
+    /// Creates an extractor from a configuration.
+    #[must_use]
+    pub fn new(config: ExtractionConfig) -> Self {
+        Self {
+            remove: parse_selectors(&config.remove_selectors),
+            main_content: parse_selectors(&config.main_content_selectors),
+            config,
+        }
+    }
+
+    /// Finds the element content is extracted from.
+    fn content_root<'a>(&self, document: &'a Html, selector: Option<&str>) -> ElementRef<'a> {
+        let explicit = selector
+            .and_then(|s| Selector::parse(s).ok())
+            .and_then(|s| document.select(&s).next());
+        explicit
+            .or_else(|| {
+                self.main_content
+                    .iter()
+                    .find_map(|s| document.select(s).next())
+            })
+            .or_else(|| {
+                Selector::parse("body")
+                    .ok()
+                    .and_then(|s| document.select(&s).next())
+            })
+            .unwrap_or_else(|| document.root_element())
+    }
+
+    fn is_removed(&self, element: &ElementRef<'_>) -> bool {
+        self.remove.iter().any(|s| s.matches(element))
+    }
+
+    fn links_in(&self, root: ElementRef<'_>, base_url: Option<&str>) -> Vec<ExtractedLink> {
+        let mut seen = HashSet::new();
+        let mut links = Vec::new();
+        self.collect_links(root, base_url, &mut seen, &mut links);
+        links
+    }
+
+    fn collect_links(
+        &self,
+        element: ElementRef<'_>,
+        base_url: Option<&str>,
+        seen: &mut HashSet<String>,
+        links: &mut Vec<ExtractedLink>,
+    ) {
+        for child in element.child_elements() {
+            if self.is_removed(&child) {
+                continue;
+            }
+            if child.value().name() == "a" {
+                if let Some(href) = child.attr("href").map(str::trim) {
+                    if is_followable(href) {
+                        let text = truncate(&collapse_whitespace(&child.text().collect::<String>()), self.config.max_link_text_length);
+                        let context = child
+                            .parent()
+                            .and_then(ElementRef::wrap)
+                            .map(|parent| truncate(&collapse_whitespace(&parent.text().collect::<String>()), LINK_CONTEXT_CHARS))
+                            .filter(|context| !context.is_empty() && *context != text);
+                        let link = ExtractedLink::from_element(
+                            href,
+                            &text,
+                            base_url,
+                            child.attr("title"),
+                            child.attr("rel"),
+                            context.as_deref(),
+                        );
+                        if seen.insert(link.url.clone()) {
+                            links.push(link);
+                        }
+                    }
+                }
+            }
+            self.collect_links(child, base_url, seen, links);
+        }
+    }
+}
+
+impl ContentExtractor for HtmlContentExtractor {
+    fn extract(&self, html: &str, base_url: Option<&str>, selector: Option<&str>) -> ExtractionResult {
+        let document = Html::parse_document(html);
+        let root = self.content_root(&document, selector);
+
+        let mut renderer = MarkdownRenderer::new(self, base_url);
+        renderer.render_children(root);
+        let markdown = tidy_markdown(&renderer.out);
+
+        let mut plain = String::new();
+        self.plain_text(root, &mut plain);
+        let plain_text = tidy_plain_text(&plain);
+
+        ExtractionResult {
+            word_count: plain_text.split_whitespace().count(),
+            markdown,
+            plain_text,
+            metadata: extract_page_metadata(&document, base_url),
+            links: self.links_in(root, base_url),
+            heading_outline: renderer.headings,
+        }
+    }
+
+    fn extract_metadata(&self, html: &str) -> PageMetadata {
+        extract_page_metadata(&Html::parse_document(html), None)
+    }
+
+    fn extract_links(&self, html: &str, base_url: Option<&str>, selector: Option<&str>) -> Vec<ExtractedLink> {
+        let document = Html::parse_document(html);
+        self.links_in(self.content_root(&document, selector), base_url)
+    }
+
+    fn config(&self) -> &ExtractionConfig {
+        &self.config
+    }
+}
+
+impl HtmlContentExtractor {
+    fn plain_text(&self, element: ElementRef<'_>, out: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => {
+                    let text = collapse_whitespace(text);
+                    if text.chars().count() >= self.config.min_text_length {
+                        if !out.is_empty() && !out.ends_with(['\n', ' ']) {
+                            out.push(' ');
+                        }
+                        out.push_str(&text);
+                    }
+                }
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else { continue };
+                    if self.is_removed(&child) {
+                        continue;
+                    }
+                    let block = is_block(child.value().name());
+                    if block {
+                        out.push('\n');
+                    }
+                    self.plain_text(child, out);
+                    if block {
+                        out.push('\n');
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Walks an element tree and writes markdown.
+struct MarkdownRenderer<'a> {
+    extractor: &'a HtmlContentExtractor,
+    config: &'a ExtractionConfig,
+    base_url: Option<&'a str>,
+    out: String,
+    headings: Vec<HeadingOutline>,
+    list_depth: usize,
+}
+
+impl<'a> MarkdownRenderer<'a> {
+    fn new(extractor: &'a HtmlContentExtractor, base_url: Option<&'a str>) -> Self {
+        Self {
+            extractor,
+            config: &extractor.config,
+            base_url,
+            out: String::new(),
+            headings: Vec::new(),
+            list_depth: 0,
+        }
+    }
+
+    fn render_children(&mut self, element: ElementRef<'_>) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        if !self.extractor.is_removed(&child) {
+                            self.render_element(child);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Renders an element's children into a separate string.
+    fn capture(&mut self, element: ElementRef<'_>) -> String {
+        let saved = std::mem::take(&mut self.out);
+        self.render_children(element);
+        std::mem::replace(&mut self.out, saved)
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let collapsed = collapse_whitespace(text);
+        if collapsed.chars().count() < self.config.min_text_length.max(1) {
+            if text.chars().any(char::is_whitespace) && !self.out.is_empty() && !self.out.ends_with([' ', '\n']) {
+                self.out.push(' ');
+            }
+            return;
+        }
+        let leading = text.starts_with(char::is_whitespace);
+        if leading && !self.out.is_empty() && !self.out.ends_with([' ', '\n']) {
+            self.out.push(' ');
+        }
+        self.out.push_str(&collapsed);
+        if text.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+
+    fn block_break(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push_str(if self.out.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+    }
+
+    fn render_element(&mut self, element: ElementRef<'_>) {
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.render_heading(element, name),
+            "ul" | "ol" => self.render_list(element, name == "ol"),
+            "a" => self.render_link(element),
+            "strong" | "b" => self.render_wrapped(element, "**", self.config.preserve_emphasis),
+            "em" | "i" => self.render_wrapped(element, "_", self.config.preserve_emphasis),
+            "code" => self.render_wrapped(element, "`", self.config.preserve_code),
+            "pre" => self.render_pre(element),
+            "blockquote" => self.render_blockquote(element),
+            "table" => self.render_table(element),
+            "br" => self.out.push('\n'),
+            "hr" => {
+                self.block_break();
+                self.out.push_str("---");
+                self.block_break();
+            }
+            "img" | "picture" | "video" | "audio" | "canvas" | "head" | "template" => {}
+            _ if is_block(name) => {
+                self.block_break();
+                self.render_children(element);
+                self.block_break();
+            }
+            _ => self.render_children(element),
+        }
+    }
+
+    fn render_heading(&mut self, element: ElementRef<'_>, name: &str) {
+        let level = name[1..].parse::<u8>().unwrap_or(1);
+        let text = truncate(&collapse_whitespace(&element.text().collect::<String>()), self.config.max_heading_length);
+        if text.is_empty() {
+            return;
+        }
+        self.headings.push(HeadingOutline {
+            level,
+            text: text.clone(),
+            id: element.value().id().map(String::from),
+        });
+
+        self.block_break();
+        if self.config.preserve_headings {
+            self.out.push_str(&"#".repeat(usize::from(level)));
+            self.out.push(' ');
+        }
+        self.out.push_str(&text);
+        self.block_break();
+    }
+
+    fn render_list(&mut self, element: ElementRef<'_>, ordered: bool) {
+        if self.list_depth == 0 {
+            self.block_break();
+        } else if !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.list_depth += 1;
+
+        let indent = "  ".repeat(self.list_depth - 1);
+        let items = element
+            .child_elements()
+            .filter(|child| child.value().name() == "li" && !self.extractor.is_removed(child));
+        for (index, item) in items.enumerate() {
+            let body = self.capture(item);
+            let body = body.trim();
+            if body.is_empty() {
+                continue;
+            }
+            let marker = match (self.config.preserve_lists, ordered) {
+                (true, true) => format!("{}. ", index + 1),
+                (true, false) => "- ".to_string(),
+                (false, _) => String::new(),
+            };
+            self.out.push_str(&indent);
+            self.out.push_str(&marker);
+            self.out.push_str(body);
+            self.out.push('\n');
+        }
+
+        self.list_depth -= 1;
+        if self.list_depth == 0 {
+            self.block_break();
+        }
+    }
+
+    fn render_link(&mut self, element: ElementRef<'_>) {
+        let text = self.capture(element);
+        let text = truncate(text.trim(), self.config.max_link_text_length);
+        let href = element.attr("href").map(str::trim).filter(|href| is_followable(href));
+        match href {
+            Some(href) if self.config.preserve_links && self.config.include_link_urls && !text.is_empty() => {
+                let url = ExtractedLink::from_element(href, &text, self.base_url, None, None, None).url;
+                self.push_inline(&format!("[{text}]({url})"));
+            }
+            _ => self.push_inline(&text),
+        }
+    }
+
+    fn render_wrapped(&mut self, element: ElementRef<'_>, marker: &str, preserve: bool) {
+        let text = self.capture(element);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        if preserve {
+            self.push_inline(&format!("{marker}{text}{marker}"));
+        } else {
+            self.push_inline(text);
+        }
+    }
+
+    fn push_inline(&mut self, text: &str) {
+        if !self.out.is_empty() && !self.out.ends_with([' ', '\n', '(', '[']) && !text.starts_with(['.', ',', ';', ':', '!', '?', ')']) {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    fn render_pre(&mut self, element: ElementRef<'_>) {
+        let code: String = element.text().collect();
+        let code = code.trim_matches('\n');
+        if code.trim().is_empty() {
+            return;
+        }
+        self.block_break();
+        if self.config.preserve_code {
+            self.out.push_str("```\n");
+            self.out.push_str(code);
+            self.out.push_str("\n```");
+        } else {
+            self.out.push_str(code);
+        }
+        self.block_break();
+    }
+
+    fn render_blockquote(&mut self, element: ElementRef<'_>) {
+        let body = tidy_markdown(&self.capture(element));
+        if body.is_empty() {
+            return;
+        }
+        self.block_break();
+        if self.config.preserve_blockquotes {
+            let quoted: Vec<String> = body
+                .lines()
+                .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {line}") })
+                .collect();
+            self.out.push_str(&quoted.join("\n"));
+        } else {
+            self.out.push_str(&body);
+        }
+        self.block_break();
+    }
+
+    fn render_table(&mut self, element: ElementRef<'_>) {
+        let rows: Vec<Vec<String>> = element
+            .descendent_elements()
+            .filter(|row| row.value().name() == "tr")
+            .map(|row| {
+                row.child_elements()
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .map(|cell| collapse_whitespace(&self.capture(cell)).replace('|', "\\|"))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| !cells.is_empty())
+            .collect();
+        if rows.is_empty() {
+            return;
+        }
+
+        self.block_break();
+        if self.config.preserve_tables {
+            let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+            for (index, row) in rows.iter().enumerate() {
+                let mut cells = row.clone();
+                cells.resize(width, String::new());
+                let _ = writeln!(self.out, "| {} |", cells.join(" | "));
+                if index == 0 {
+                    let _ = writeln!(self.out, "|{}", " --- |".repeat(width));
+                }
+            }
+        } else {
+            let lines: Vec<String> = rows.iter().map(|row| row.join(" ")).collect();
+            self.out.push_str(&lines.join("\n"));
+        }
+        self.block_break();
+    }
+}
+
+/// Extracts page metadata from `<head>` tags.
+fn extract_page_metadata(document: &Html, base_url: Option<&str>) -> PageMetadata {
+    let meta = |attr: &str, value: &str| -> Option<String> {
+        let selector = Selector::parse(&format!("meta[{attr}=\"{value}\"]")).ok()?;
+        document
+            .select(&selector)
+            .find_map(|el| el.attr("content"))
+            .map(|content| content.trim().to_string())
+            .filter(|content| !content.is_empty())
+    };
+    let first = |selector: &str, attr: Option<&str>| -> Option<String> {
+        let selector = Selector::parse(selector).ok()?;
+        let element = document.select(&selector).next()?;
+        let value = match attr {
+            Some(attr) => element.attr(attr)?.to_string(),
+            None => collapse_whitespace(&element.text().collect::<String>()),
+        };
+        Some(value.trim().to_string()).filter(|value| !value.is_empty())
+    };
+
+    PageMetadata {
+        title: first("title", None).or_else(|| meta("property", "og:title")),
+        description: meta("name", "description").or_else(|| meta("property", "og:description")),
+        language: first("html", Some("lang")),
+        author: meta("name", "author").or_else(|| meta("property", "article:author")),
+        published_date: meta("property", "article:published_time").or_else(|| meta("name", "date")),
+        canonical_url: first("link[rel=\"canonical\"]", Some("href"))
+            .map(|href| ExtractedLink::from_element(&href, "", base_url, None, None, None).url),
+        og_image: meta("property", "og:image"),
+        content_type: None,
+        keywords: meta("name", "keywords")
+            .map(|keywords| {
+                keywords
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_selectors(selectors: &[String]) -> Vec<Selector> {
+    selectors.iter().filter_map(|s| Selector::parse(s).ok()).collect()
+}
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div" | "section" | "article" | "main" | "header" | "footer" | "aside" | "nav"
+            | "li" | "ul" | "ol" | "table" | "tr" | "pre" | "blockquote" | "figure"
+            | "figcaption" | "form" | "dl" | "dt" | "dd" | "h1" | "h2" | "h3" | "h4" | "h5"
+            | "h6" | "body" | "html"
+    )
+}
+
+fn is_followable(href: &str) -> bool {
+    !(href.is_empty()
+        || href.starts_with('#')
+        || href.starts_with("javascript:")
+        || href.starts_with("mailto:")
+        || href.starts_with("tel:"))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        text.chars().take(max_chars).collect::<String>().trim_end().to_string()
+    }
+}
+
+/// Trims trailing spaces and collapses runs of blank lines.
+fn tidy_markdown(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut blank_run = 0;
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+fn tidy_plain_text(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!doctype html>
+<html lang="en">
+<head>
+  <title>Rust Guide</title>
+  <meta name="description" content="Learn Rust">
+  <meta property="og:image" content="https://example.com/cover.png">
+  <meta name="keywords" content="rust, systems">
+  <link rel="canonical" href="/guide">
+</head>
+<body>
+  <nav><a href="/home">Home</a></nav>
+  <article>
+    <h1 id="intro">Getting <em>started</em></h1>
+    <p>Rust is <strong>fast</strong> and <a href="/safety" title="Safety">safe</a>.</p>
+    <ul><li>Ownership</li><li>Borrowing</li></ul>
+    <blockquote><p>Fearless concurrency</p></blockquote>
+    <pre><code>fn main() {}</code></pre>
+    <table><tr><th>Tool</th><th>Use</th></tr><tr><td>cargo</td><td>build</td></tr></table>
+    <script>alert(1)</script>
+    <a href="https://other.com/x">External</a>
+  </article>
+</body>
+</html>"#;
+
+    #[test]
+    fn test_extract_markdown_structure() {
+        let result = HtmlContentExtractor::default().extract(PAGE, Some("https://example.com/docs/page"), None);
+
+        assert!(result.markdown.starts_with("# Getting started"));
+        assert!(result.markdown.contains("Rust is **fast** and [safe](https://example.com/safety)."));
+        assert!(result.markdown.contains("- Ownership\n- Borrowing"));
+        assert!(result.markdown.contains("> Fearless concurrency"));
+        assert!(result.markdown.contains("```\nfn main() {}\n```"));
+        assert!(result.markdown.contains("| Tool | Use |\n| --- | --- |\n| cargo | build |"));
+        assert!(!result.markdown.contains("alert"));
+        assert!(!result.markdown.contains("Home"));
+
+        assert_eq!(result.heading_outline.len(), 1);
+        assert_eq!(result.heading_outline[0].id.as_deref(), Some("intro"));
+        assert!(result.word_count > 10);
+    }
+
+    #[test]
+    fn test_preserve_flags_flatten_markup() {
+        let config = ExtractionConfig {
+            preserve_headings: false,
+            preserve_lists: false,
+            preserve_links: false,
+            preserve_emphasis: false,
+            preserve_tables: false,
+            ..ExtractionConfig::default()
+        };
+        let result = HtmlContentExtractor::new(config).extract(PAGE, None, None);
+
+        assert!(result.markdown.starts_with("Getting started"));
+        assert!(result.markdown.contains("Rust is fast and safe."));
+        assert!(!result.markdown.contains("- Ownership"));
+        assert!(!result.markdown.contains("| Tool"));
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let links = HtmlContentExtractor::default().extract_links(PAGE, Some("https://example.com/docs/page"), None);
+
+        let urls: Vec<&str> = links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/safety", "https://other.com/x"]);
+        assert!(links[0].is_internal);
+        assert_eq!(links[0].title.as_deref(), Some("Safety"));
+        assert!(!links[1].is_internal);
+    }
+
+    #[test]
+    fn test_extract_metadata() {
+        let extractor = HtmlContentExtractor::default();
+        let metadata = extractor.extract(PAGE, Some("https://example.com/docs/page"), None).metadata;
+
+        assert_eq!(metadata.title.as_deref(), Some("Rust Guide"));
+        assert_eq!(metadata.description.as_deref(), Some("Learn Rust"));
+        assert_eq!(metadata.language.as_deref(), Some("en"));
+        assert_eq!(metadata.og_image.as_deref(), Some("https://example.com/cover.png"));
+        assert_eq!(metadata.canonical_url.as_deref(), Some("https://example.com/guide"));
+        assert_eq!(metadata.keywords, vec!["rust", "systems"]);
+    }
+
+    #[test]
+    fn test_explicit_selector_and_body_fallback() {
+        let html = "<html><body><div class=\"post\"><p>Chosen</p></div><p>Other</p></body></html>";
+        let extractor = HtmlContentExtractor::default();
+
+        assert_eq!(extractor.extract(html, None, Some(".post")).markdown, "Chosen");
+        assert_eq!(extractor.extract(html, None, None).markdown, "Chosen\n\nOther");
+    }
+}
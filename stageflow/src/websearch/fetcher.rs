@@ -0,0 +1,419 @@
+//! HTTP fetcher backed by reqwest.
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::config::FetchConfig;
+use super::protocols::{FetchObserver, FetchResult, Fetcher, NoOpFetchObserver};
+use super::robots::RobotsPolicy;
+use crate::errors::StageflowError;
+use crate::utils::generate_uuid;
+
+/// [`Fetcher`] implementation that performs real HTTP requests.
+///
+/// Honors the timeout, redirect limit, response size cap, user agent, SSL
+/// verification, and default headers from [`FetchConfig`]. Transport errors
+/// and responses with a status in `retry.retry_status_codes` are retried
+/// with the configured exponential backoff.
+pub struct HttpFetcher {
+    config: FetchConfig,
+    client: reqwest::Client,
+    observer: Arc<dyn FetchObserver>,
+    robots: Option<Arc<RobotsPolicy>>,
+}
+
+impl std::fmt::Debug for HttpFetcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpFetcher")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpFetcher {
+    /// Creates a fetcher from a configuration.
+    pub fn new(config: FetchConfig) -> Result<Self, StageflowError> {
+        let redirect = if config.max_redirects == 0 {
+            reqwest::redirect::Policy::none()
+        } else {
+            reqwest::redirect::Policy::limited(config.max_redirects)
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout())
+            .redirect(redirect)
+            .user_agent(config.user_agent.clone())
+            .danger_accept_invalid_certs(!config.verify_ssl)
+            .default_headers(to_header_map(&config.headers)?)
+            .build()
+            .map_err(|e| StageflowError::Internal(format!("Failed to build HTTP client: {e}")))?;
+
+        Ok(Self {
+            config,
+            client,
+            observer: Arc::new(NoOpFetchObserver),
+            robots: None,
+        })
+    }
+
+    /// Sets the observer notified of fetch start, completion, and errors.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn FetchObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Refuses URLs disallowed by robots.txt.
+    ///
+    /// The policy needs its own fetcher for robots.txt requests; it must not
+    /// wrap this one.
+    #[must_use]
+    pub fn with_robots_policy(mut self, robots: Arc<RobotsPolicy>) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    async fn fetch_once(
+        &self,
+        url: &str,
+        timeout: Option<f64>,
+        headers: Option<&HashMap<String, String>>,
+        start: Instant,
+    ) -> Result<FetchResult, AttemptError> {
+        let mut request = self.client.get(url);
+        if let Some(seconds) = timeout {
+            request = request.timeout(Duration::from_secs_f64(seconds));
+        }
+        if let Some(headers) = headers {
+            request = request.headers(to_header_map(headers).map_err(AttemptError::fatal)?);
+        }
+
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| AttemptError::transient(format!("Request to {url} failed: {e}")))?;
+
+        let max_size = self.config.max_response_size;
+        let declared = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+        if declared.is_some_and(|len| len > max_size) {
+            return Err(AttemptError::too_large(url, max_size));
+        }
+
+        let status_code = response.status().as_u16();
+        let final_url = response.url().to_string();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let response_headers: HashMap<String, String> = response
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.to_string(), v.to_string())))
+            .collect();
+
+        // Stream the body so oversized responses without a Content-Length stop early
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AttemptError::transient(format!("Reading {url} failed: {e}")))?
+        {
+            if body.len() + chunk.len() > max_size {
+                return Err(AttemptError::too_large(url, max_size));
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(FetchResult {
+            status_code,
+            headers: response_headers,
+            text: String::from_utf8_lossy(&body).into_owned(),
+            final_url,
+            content_type,
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+#[async_trait]
+impl Fetcher for HttpFetcher {
+    async fn fetch(
+        &self,
+        url: &str,
+        timeout: Option<f64>,
+        headers: Option<&HashMap<String, String>>,
+    ) -> Result<FetchResult, StageflowError> {
+        let request_id = generate_uuid().to_string();
+        let retry = &self.config.retry;
+        let start = Instant::now();
+        self.observer.on_fetch_start(url, &request_id);
+
+        if let Some(ref robots) = self.robots {
+            if !robots.is_allowed(url).await {
+                let error = format!("Fetching {url} is disallowed by robots.txt");
+                self.observer.on_fetch_error(url, &request_id, &error);
+                return Err(StageflowError::Internal(error));
+            }
+        }
+
+        let mut attempt = 0;
+        loop {
+            let result = self.fetch_once(url, timeout, headers, start).await;
+            let retryable = match &result {
+                Ok(fetched) => retry.should_retry_status(fetched.status_code),
+                Err(err) => err.retryable,
+            };
+
+            if !retryable || attempt >= retry.max_retries {
+                return match result {
+                    Ok(fetched) => {
+                        self.observer.on_fetch_complete(
+                            url,
+                            &request_id,
+                            fetched.duration_ms,
+                            fetched.status_code,
+                        );
+                        Ok(fetched)
+                    }
+                    Err(err) => {
+                        self.observer.on_fetch_error(url, &request_id, &err.error.to_string());
+                        Err(err.error)
+                    }
+                };
+            }
+
+            tokio::time::sleep(retry.delay_for_attempt(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    fn config(&self) -> &FetchConfig {
+        &self.config
+    }
+}
+
+fn to_header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, StageflowError> {
+    let mut map = HeaderMap::new();
+    for (key, value) in headers {
+        let name = HeaderName::from_bytes(key.as_bytes())
+            .map_err(|e| StageflowError::Internal(format!("Invalid header name '{key}': {e}")))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| StageflowError::Internal(format!("Invalid header value for '{key}': {e}")))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// Failure of a single fetch attempt.
+struct AttemptError {
+    error: StageflowError,
+    retryable: bool,
+}
+
+impl AttemptError {
+    fn transient(message: String) -> Self {
+        Self {
+            error: StageflowError::Internal(message),
+            retryable: true,
+        }
+    }
+
+    fn fatal(error: StageflowError) -> Self {
+        Self {
+            error,
+            retryable: false,
+        }
+    }
+
+    fn too_large(url: &str, max_size: usize) -> Self {
+        Self::fatal(StageflowError::Internal(format!(
+            "Response from {url} exceeds max_response_size of {max_size} bytes"
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_server {
+    //! Minimal HTTP/1.1 server for exercising real fetches in tests.
+
+    use parking_lot::Mutex;
+    use std::fmt::Write;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `responder(path)` for every request and records raw requests.
+    pub(crate) async fn serve<F>(responder: F) -> (String, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(&str) -> (u16, Vec<(String, String)>, String) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let responder = Arc::new(responder);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let responder = responder.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let raw = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let path = raw.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    recorded.lock().push(raw);
+
+                    let (status, headers, body) = responder(&path);
+                    let mut response = format!(
+                        "HTTP/1.1 {status} OK\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        body.len()
+                    );
+                    for (k, v) in headers {
+                        let _ = write!(response, "{k}: {v}\r\n");
+                    }
+                    response.push_str("\r\n");
+                    response.push_str(&body);
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        (base, requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_server::serve;
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn html(body: &str) -> (u16, Vec<(String, String)>, String) {
+        (
+            200,
+            vec![("Content-Type".to_string(), "text/html".to_string())],
+            body.to_string(),
+        )
+    }
+
+    fn fast_retry_config() -> FetchConfig {
+        let mut config = FetchConfig::new().with_timeout(5.0);
+        config.retry.retry_delay_seconds = 0.0;
+        config
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sends_configured_headers() {
+        let (base, requests) = serve(|_| html("<p>hi</p>")).await;
+        let fetcher = HttpFetcher::new(fast_retry_config().with_header("X-Team", "search")).unwrap();
+
+        let mut extra = HashMap::new();
+        extra.insert("X-Request".to_string(), "1".to_string());
+        let result = fetcher.fetch(&format!("{base}/page"), None, Some(&extra)).await.unwrap();
+
+        assert!(result.is_success());
+        assert!(result.is_html());
+        assert_eq!(result.text, "<p>hi</p>");
+        let raw = requests.lock()[0].to_lowercase();
+        assert!(raw.contains("x-team: search"));
+        assert!(raw.contains("x-request: 1"));
+        assert!(raw.contains("user-agent: stageflow-websearch"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_retryable_status() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let (base, _) = serve(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                (503, Vec::new(), String::new())
+            } else {
+                html("ok")
+            }
+        })
+        .await;
+
+        let fetcher = HttpFetcher::new(fast_retry_config()).unwrap();
+        let result = fetcher.fetch(&base, None, None).await.unwrap();
+
+        assert_eq!(result.status_code, 200);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gives_up_after_max_retries() {
+        let (base, requests) = serve(|_| (503, Vec::new(), String::new())).await;
+        let mut config = fast_retry_config();
+        config.retry.max_retries = 1;
+
+        let result = HttpFetcher::new(config).unwrap().fetch(&base, None, None).await.unwrap();
+
+        assert_eq!(result.status_code, 503);
+        assert_eq!(requests.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_rejects_oversized_response() {
+        let (base, requests) = serve(|_| html(&"x".repeat(64))).await;
+        let mut config = fast_retry_config();
+        config.max_response_size = 16;
+
+        let err = HttpFetcher::new(config).unwrap().fetch(&base, None, None).await.unwrap_err();
+
+        assert!(err.to_string().contains("max_response_size"));
+        assert_eq!(requests.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_redirects() {
+        let (base, _) = serve(|path| {
+            if path == "/old" {
+                (301, vec![("Location".to_string(), "/new".to_string())], String::new())
+            } else {
+                html("moved")
+            }
+        })
+        .await;
+
+        let fetcher = HttpFetcher::new(fast_retry_config()).unwrap();
+        let result = fetcher.fetch(&format!("{base}/old"), None, None).await.unwrap();
+        assert!(result.final_url.ends_with("/new"));
+
+        let mut config = fast_retry_config();
+        config.max_redirects = 0;
+        let result = HttpFetcher::new(config)
+            .unwrap()
+            .fetch(&format!("{base}/old"), None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 301);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refuses_robots_disallowed_url() {
+        let (base, requests) = serve(|path| match path {
+            "/robots.txt" => (200, Vec::new(), "User-agent: *\nDisallow: /private".to_string()),
+            _ => html("ok"),
+        })
+        .await;
+        let robots = RobotsPolicy::new(Arc::new(HttpFetcher::new(fast_retry_config()).unwrap()));
+        let fetcher = HttpFetcher::new(fast_retry_config())
+            .unwrap()
+            .with_robots_policy(Arc::new(robots));
+
+        let err = fetcher.fetch(&format!("{base}/private/a"), None, None).await.unwrap_err();
+        assert!(err.to_string().contains("disallowed by robots.txt"));
+        assert!(fetcher.fetch(&format!("{base}/public"), None, None).await.is_ok());
+        assert_eq!(requests.lock().len(), 2);
+    }
+}
@@ -5,17 +5,31 @@
 //! - Content extraction from HTML
 //! - Navigation and pagination detection
 //! - Configuration for fetching and extraction
+//! - An HTTP fetcher backed by reqwest
+//! - An HTML-to-markdown extractor backed by scraper
+//! - A breadth-first site crawler
+//! - robots.txt policies and sitemap.xml parsing
+//! - Ready-made fetch and extraction pipeline stages
 //! - Protocol traits for pluggable components
 //! - Run utilities for common operations
 
 mod config;
+mod crawler;
+mod extractor;
+mod fetcher;
 mod models;
 mod protocols;
+mod robots;
 mod run_utils;
+mod sitemap;
+mod stages;
 
 pub use config::{
-    ExtractionConfig, FetchConfig, NavigationConfig, RetryConfig, WebSearchConfig,
+    CrawlConfig, ExtractionConfig, FetchConfig, NavigationConfig, RetryConfig, WebSearchConfig,
 };
+pub use crawler::{CrawlProgressCallback, Crawler};
+pub use extractor::HtmlContentExtractor;
+pub use fetcher::HttpFetcher;
 pub use models::{
     ExtractedLink, NavigationAction, PageMetadata, PaginationInfo, WebPage,
 };
@@ -23,8 +37,11 @@ pub use protocols::{
     ContentExtractor, ExtractionResult, FetchObserver, FetchResult, Fetcher,
     HeadingOutline, NavigationResult, Navigator, NoOpFetchObserver,
 };
+pub use robots::{RobotsPolicy, RobotsRules, MAX_CRAWL_DELAY};
 pub use run_utils::{
     FetchProgress, SearchResult, SiteMap, calculate_relevance_score, calculate_retry_delay,
     create_error_result, extract_domain, extract_unique_links, filter_relevant_pages,
     same_domain,
 };
+pub use sitemap::{fetch_sitemap_urls, Sitemap, SitemapEntry};
+pub use stages::{ExtractContentStage, FetchPageStage};
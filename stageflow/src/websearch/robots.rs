@@ -0,0 +1,410 @@
+//! robots.txt parsing and per-domain policy caching.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+use super::protocols::Fetcher;
+
+/// Longest crawl delay honored; larger declared delays are capped to it.
+pub const MAX_CRAWL_DELAY: Duration = Duration::from_secs(3600);
+
+/// A single Allow or Disallow rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RobotsRule {
+    allow: bool,
+    pattern: String,
+}
+
+/// Rules declared for one set of user agents.
+#[derive(Debug, Clone, Default)]
+struct RobotsGroup {
+    agents: Vec<String>,
+    rules: Vec<RobotsRule>,
+    crawl_delay: Option<Duration>,
+}
+
+/// Parsed contents of a robots.txt file.
+///
+/// Follows RFC 9309: the most specific user-agent group applies (falling back
+/// to `*`), the longest matching rule wins with ties going to Allow, and
+/// patterns support `*` wildcards and a trailing `$` anchor.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsRules {
+    groups: Vec<RobotsGroup>,
+    sitemaps: Vec<String>,
+}
+
+impl RobotsRules {
+    /// Parses robots.txt content. Unknown and malformed lines are ignored.
+    #[must_use]
+    pub fn parse(content: &str) -> Self {
+        let mut rules = Self::default();
+        let mut current: Option<RobotsGroup> = None;
+        let mut last_was_agent = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "user-agent" => {
+                    if !last_was_agent {
+                        rules.groups.extend(current.take());
+                    }
+                    current
+                        .get_or_insert_with(RobotsGroup::default)
+                        .agents
+                        .push(value.to_ascii_lowercase());
+                    last_was_agent = true;
+                    continue;
+                }
+                "allow" | "disallow" => {
+                    // An empty Disallow allows everything, so it adds no rule
+                    if let (Some(group), false) = (current.as_mut(), value.is_empty()) {
+                        group.rules.push(RobotsRule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    if let Some(group) = current.as_mut() {
+                        // Negative, non-finite and overflowing delays are ignored
+                        group.crawl_delay = value
+                            .parse::<f64>()
+                            .ok()
+                            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                            .map(|delay| delay.min(MAX_CRAWL_DELAY));
+                    }
+                }
+                "sitemap" if !value.is_empty() => {
+                    rules.sitemaps.push(value.to_string());
+                }
+                _ => {}
+            }
+            last_was_agent = false;
+        }
+        rules.groups.extend(current);
+        rules
+    }
+
+    /// Rules that allow every path.
+    #[must_use]
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Rules that disallow every path for every agent.
+    #[must_use]
+    pub fn disallow_all() -> Self {
+        Self::parse("User-agent: *\nDisallow: /")
+    }
+
+    /// Returns true if `user_agent` may fetch `path` (path plus query).
+    #[must_use]
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        let best = self
+            .matching_groups(user_agent)
+            .flat_map(|g| &g.rules)
+            .filter(|r| pattern_matches(&r.pattern, path))
+            .max_by_key(|r| (r.pattern.len(), r.allow));
+        best.map_or(true, |r| r.allow)
+    }
+
+    /// Returns the crawl delay declared for `user_agent`, if any, capped at
+    /// [`MAX_CRAWL_DELAY`].
+    #[must_use]
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+        self.matching_groups(user_agent).find_map(|g| g.crawl_delay)
+    }
+
+    /// Returns the sitemap URLs listed in the file.
+    #[must_use]
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    fn matching_groups<'a>(&'a self, user_agent: &str) -> impl Iterator<Item = &'a RobotsGroup> {
+        let token = product_token(user_agent);
+        let best = self
+            .groups
+            .iter()
+            .flat_map(|g| &g.agents)
+            .filter_map(|a| agent_specificity(a, &token))
+            .max();
+
+        self.groups.iter().filter(move |g| match best {
+            Some(len) => g.agents.iter().any(|a| agent_specificity(a, &token) == Some(len)),
+            None => g.agents.iter().any(|a| a == "*"),
+        })
+    }
+}
+
+/// Fetches and caches robots.txt per origin.
+///
+/// A 4xx response is treated as "allow all" and a 5xx response or transport
+/// error as "disallow all", as RFC 9309 recommends. Concurrent lookups for
+/// the same origin share a single fetch.
+pub struct RobotsPolicy {
+    fetcher: Arc<dyn Fetcher>,
+    user_agent: String,
+    cache: Mutex<HashMap<String, Arc<OnceCell<Arc<RobotsRules>>>>>,
+}
+
+impl std::fmt::Debug for RobotsPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RobotsPolicy")
+            .field("user_agent", &self.user_agent)
+            .field("cached_origins", &self.cache.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RobotsPolicy {
+    /// Creates a policy that fetches robots.txt with `fetcher`, matching
+    /// rules against the fetcher's configured user agent.
+    #[must_use]
+    pub fn new(fetcher: Arc<dyn Fetcher>) -> Self {
+        let user_agent = fetcher.config().user_agent.clone();
+        Self {
+            fetcher,
+            user_agent,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Overrides the user agent used to select rule groups.
+    #[must_use]
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Returns the rules for the origin of `url`, fetching them on first use.
+    pub async fn rules_for(&self, url: &str) -> Arc<RobotsRules> {
+        let Some((origin, _)) = split_origin(url) else {
+            return Arc::new(RobotsRules::allow_all());
+        };
+        let cell = self.cache.lock().entry(origin.to_string()).or_default().clone();
+        cell.get_or_init(|| self.load(origin)).await.clone()
+    }
+
+    /// Returns true if `url` may be fetched.
+    pub async fn is_allowed(&self, url: &str) -> bool {
+        let Some((_, path)) = split_origin(url) else {
+            return true;
+        };
+        self.rules_for(url).await.is_allowed(&self.user_agent, &path)
+    }
+
+    /// Returns the crawl delay declared for the origin of `url`.
+    pub async fn crawl_delay(&self, url: &str) -> Option<Duration> {
+        self.rules_for(url).await.crawl_delay(&self.user_agent)
+    }
+
+    /// Returns the sitemap URLs declared for the origin of `url`.
+    pub async fn sitemaps(&self, url: &str) -> Vec<String> {
+        self.rules_for(url).await.sitemaps().to_vec()
+    }
+
+    /// Drops all cached rules.
+    pub fn clear_cache(&self) {
+        self.cache.lock().clear();
+    }
+
+    async fn load(&self, origin: &str) -> Arc<RobotsRules> {
+        let robots_url = format!("{origin}/robots.txt");
+        let rules = match self.fetcher.fetch(&robots_url, None, None).await {
+            Ok(result) if result.is_success() => RobotsRules::parse(&result.text),
+            Ok(result) if (400..500).contains(&result.status_code) => RobotsRules::allow_all(),
+            Ok(result) => {
+                debug!("robots.txt at {} returned {}", robots_url, result.status_code);
+                RobotsRules::disallow_all()
+            }
+            Err(e) => {
+                debug!("robots.txt at {} unreachable: {}", robots_url, e);
+                RobotsRules::disallow_all()
+            }
+        };
+        Arc::new(rules)
+    }
+}
+
+/// Splits a URL into its origin (`scheme://host[:port]`) and path plus query.
+pub(crate) fn split_origin(url: &str) -> Option<(&str, String)> {
+    let scheme_end = url.find("://")? + 3;
+    let host_len = url[scheme_end..]
+        .find(['/', '?', '#'])
+        .unwrap_or(url.len() - scheme_end);
+    let (origin, rest) = url.split_at(scheme_end + host_len);
+    let rest = rest.split('#').next().unwrap_or("");
+    let path = if rest.starts_with('/') {
+        rest.to_string()
+    } else {
+        format!("/{rest}")
+    };
+    Some((origin, path))
+}
+
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Returns the match length if a non-wildcard group `agent` applies to `token`.
+fn agent_specificity(agent: &str, token: &str) -> Option<usize> {
+    (agent != "*" && token.starts_with(agent)).then_some(agent.len())
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !path.starts_with(first) {
+        return false;
+    }
+
+    let rest: Vec<&str> = parts.collect();
+    let mut pos = first.len();
+    for (i, part) in rest.iter().enumerate() {
+        if anchored && i == rest.len() - 1 {
+            return path.len() - pos >= part.len() && path.ends_with(part);
+        }
+        match path[pos..].find(part) {
+            Some(idx) => pos += idx + part.len(),
+            None => return false,
+        }
+    }
+    !anchored || pos == path.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::FetchConfig;
+    use super::super::fetcher::test_server::serve;
+    use super::super::fetcher::HttpFetcher;
+    use super::*;
+
+    const ROBOTS: &str = "\
+# comment
+User-agent: *
+Disallow: /private/
+Allow: /private/public-*.html$
+Crawl-delay: 2
+
+User-agent: stageflow-websearch
+User-agent: otherbot
+Disallow: /internal
+Allow: /internal/docs
+
+Sitemap: https://example.com/sitemap.xml
+";
+
+    #[test]
+    fn test_group_selection() {
+        let rules = RobotsRules::parse(ROBOTS);
+
+        // Specific group: /private is not mentioned there
+        assert!(rules.is_allowed("stageflow-websearch/1.0", "/private/x"));
+        assert!(!rules.is_allowed("stageflow-websearch/1.0", "/internal/page"));
+        assert!(rules.is_allowed("stageflow-websearch/1.0", "/internal/docs/a"));
+        assert_eq!(rules.crawl_delay("stageflow-websearch/1.0"), None);
+
+        // Wildcard group
+        assert!(!rules.is_allowed("somebot", "/private/x"));
+        assert!(rules.is_allowed("somebot", "/private/public-a.html"));
+        assert!(!rules.is_allowed("somebot", "/private/public-a.html?x=1"));
+        assert_eq!(rules.crawl_delay("somebot"), Some(Duration::from_secs(2)));
+        assert_eq!(rules.sitemaps(), ["https://example.com/sitemap.xml"]);
+    }
+
+    #[test]
+    fn test_unusable_crawl_delays() {
+        let delay = |value: &str| RobotsRules::parse(&format!("User-agent: *\nCrawl-delay: {value}")).crawl_delay("bot");
+        assert_eq!(delay("inf"), None);
+        assert_eq!(delay("NaN"), None);
+        assert_eq!(delay("-1"), None);
+        assert_eq!(delay("1e30"), None);
+        assert_eq!(delay("86400"), Some(MAX_CRAWL_DELAY));
+        assert_eq!(delay("0.5"), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_allow_wins_ties_and_empty_disallow() {
+        let rules = RobotsRules::parse("User-agent: *\nDisallow: /page\nAllow: /page\nDisallow:");
+        assert!(rules.is_allowed("bot", "/page"));
+        assert!(RobotsRules::parse("User-agent: *\nDisallow:").is_allowed("bot", "/"));
+        assert!(!RobotsRules::disallow_all().is_allowed("bot", "/"));
+        assert!(RobotsRules::disallow_all().is_allowed("bot", "/robots.txt"));
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("/a", "/abc"));
+        assert!(pattern_matches("/*.pdf$", "/docs/file.pdf"));
+        assert!(!pattern_matches("/*.pdf$", "/docs/file.pdf?x"));
+        assert!(pattern_matches("/a*c*e", "/abcde"));
+        assert!(!pattern_matches("/a$", "/ab"));
+        assert!(pattern_matches("/a$", "/a"));
+    }
+
+    #[test]
+    fn test_split_origin() {
+        assert_eq!(
+            split_origin("https://example.com:8080/a/b?q=1#frag"),
+            Some(("https://example.com:8080", "/a/b?q=1".to_string()))
+        );
+        assert_eq!(
+            split_origin("https://example.com"),
+            Some(("https://example.com", "/".to_string()))
+        );
+        assert_eq!(split_origin("not a url"), None);
+    }
+
+    #[tokio::test]
+    async fn test_policy_fetches_once_per_origin() {
+        let (base, requests) = serve(|path| match path {
+            "/robots.txt" => (200, Vec::new(), "User-agent: *\nDisallow: /admin".to_string()),
+            _ => (200, Vec::new(), String::new()),
+        })
+        .await;
+        let fetcher = Arc::new(HttpFetcher::new(FetchConfig::new().with_timeout(5.0)).unwrap());
+        let policy = RobotsPolicy::new(fetcher);
+
+        let (admin, blog) = (format!("{base}/admin/users"), format!("{base}/blog"));
+        let (a, b) = tokio::join!(policy.is_allowed(&admin), policy.is_allowed(&blog));
+
+        assert!(!a);
+        assert!(b);
+        assert_eq!(requests.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_policy_status_handling() {
+        let (missing, _) = serve(|_| (404, Vec::new(), String::new())).await;
+        let (failing, _) = serve(|_| (500, Vec::new(), String::new())).await;
+        let mut config = FetchConfig::new().with_timeout(5.0);
+        config.retry.max_retries = 0;
+        let policy = RobotsPolicy::new(Arc::new(HttpFetcher::new(config).unwrap()));
+
+        assert!(policy.is_allowed(&format!("{missing}/page")).await);
+        assert!(!policy.is_allowed(&format!("{failing}/page")).await);
+    }
+}
@@ -0,0 +1,234 @@
+//! sitemap.xml parsing and discovery.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::debug;
+
+use super::protocols::Fetcher;
+use crate::errors::StageflowError;
+
+/// Maximum nesting of sitemap indexes followed by [`fetch_sitemap_urls`].
+const MAX_INDEX_DEPTH: usize = 3;
+
+/// A `<url>` entry in a sitemap.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SitemapEntry {
+    /// Page URL.
+    pub loc: String,
+    /// Last modification date as written in the sitemap.
+    pub lastmod: Option<String>,
+    /// Change frequency hint.
+    pub changefreq: Option<String>,
+    /// Priority hint between 0.0 and 1.0.
+    pub priority: Option<f64>,
+}
+
+/// A parsed sitemap or sitemap index.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sitemap {
+    /// Page entries from a `<urlset>`.
+    pub urls: Vec<SitemapEntry>,
+    /// Nested sitemap URLs from a `<sitemapindex>`.
+    pub sitemaps: Vec<String>,
+}
+
+impl Sitemap {
+    /// Parses sitemap XML. Entries without a `<loc>` are skipped.
+    #[must_use]
+    pub fn parse(xml: &str) -> Self {
+        let urls = elements(xml, "url")
+            .into_iter()
+            .filter_map(|block| {
+                Some(SitemapEntry {
+                    loc: element_text(block, "loc")?,
+                    lastmod: element_text(block, "lastmod"),
+                    changefreq: element_text(block, "changefreq"),
+                    priority: element_text(block, "priority").and_then(|p| p.parse().ok()),
+                })
+            })
+            .collect();
+        let sitemaps = elements(xml, "sitemap")
+            .into_iter()
+            .filter_map(|block| element_text(block, "loc"))
+            .collect();
+        Self { urls, sitemaps }
+    }
+
+    /// Returns true if this is a sitemap index.
+    #[must_use]
+    pub fn is_index(&self) -> bool {
+        !self.sitemaps.is_empty()
+    }
+}
+
+/// Fetches a sitemap and returns up to `max_urls` page entries.
+///
+/// Sitemap indexes are followed up to three levels deep. Nested sitemaps
+/// that fail to load are skipped; only a failure on `sitemap_url` itself is
+/// returned as an error.
+pub async fn fetch_sitemap_urls(
+    fetcher: &dyn Fetcher,
+    sitemap_url: &str,
+    max_urls: usize,
+) -> Result<Vec<SitemapEntry>, StageflowError> {
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![(sitemap_url.to_string(), 0)];
+
+    while let Some((url, depth)) = pending.pop() {
+        if entries.len() >= max_urls || !visited.insert(url.clone()) {
+            continue;
+        }
+        let sitemap = match load(fetcher, &url).await {
+            Ok(sitemap) => sitemap,
+            Err(e) if url == sitemap_url => return Err(e),
+            Err(e) => {
+                debug!("Skipping sitemap {}: {}", url, e);
+                continue;
+            }
+        };
+
+        let remaining = max_urls - entries.len();
+        entries.extend(sitemap.urls.into_iter().take(remaining));
+        if depth < MAX_INDEX_DEPTH {
+            // Reverse so nested sitemaps are visited in document order
+            pending.extend(sitemap.sitemaps.into_iter().rev().map(|s| (s, depth + 1)));
+        }
+    }
+    Ok(entries)
+}
+
+async fn load(fetcher: &dyn Fetcher, url: &str) -> Result<Sitemap, StageflowError> {
+    let result = fetcher.fetch(url, None, None).await?;
+    if !result.is_success() {
+        return Err(StageflowError::Internal(format!(
+            "Sitemap {url} returned status {}",
+            result.status_code
+        )));
+    }
+    Ok(Sitemap::parse(&result.text))
+}
+
+/// Returns the inner content of each `<tag>...</tag>` element.
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut found = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // Skip longer tag names sharing the prefix, e.g. <urlset> for <url>
+        if !after.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            rest = after;
+            continue;
+        }
+        let Some(body_start) = after.find('>') else {
+            break;
+        };
+        let body = &after[body_start + 1..];
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        found.push(&body[..end]);
+        rest = &body[end + close.len()..];
+    }
+    found
+}
+
+/// Returns the unescaped text of the first `<tag>` element, if non-empty.
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let raw = elements(xml, tag).into_iter().next()?.trim();
+    let text = match raw.strip_prefix("<![CDATA[").and_then(|r| r.strip_suffix("]]>")) {
+        Some(cdata) => cdata.trim().to_string(),
+        None => unescape(raw),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::FetchConfig;
+    use super::super::fetcher::test_server::serve;
+    use super::super::fetcher::HttpFetcher;
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_urlset() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/a?x=1&amp;y=2</loc>
+    <lastmod>2024-01-01</lastmod>
+    <priority>0.8</priority>
+  </url>
+  <url><loc><![CDATA[https://example.com/b]]></loc><changefreq>daily</changefreq></url>
+  <url><lastmod>2024-01-01</lastmod></url>
+</urlset>"#;
+
+        let sitemap = Sitemap::parse(xml);
+        assert!(!sitemap.is_index());
+        assert_eq!(sitemap.urls.len(), 2);
+        assert_eq!(sitemap.urls[0].loc, "https://example.com/a?x=1&y=2");
+        assert_eq!(sitemap.urls[0].priority, Some(0.8));
+        assert_eq!(sitemap.urls[1].loc, "https://example.com/b");
+        assert_eq!(sitemap.urls[1].changefreq.as_deref(), Some("daily"));
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let xml = "<sitemapindex>\
+            <sitemap><loc>https://example.com/s1.xml</loc></sitemap>\
+            <sitemap><loc>https://example.com/s2.xml</loc></sitemap>\
+            </sitemapindex>";
+
+        let sitemap = Sitemap::parse(xml);
+        assert!(sitemap.is_index());
+        assert_eq!(sitemap.sitemaps.len(), 2);
+        assert!(sitemap.urls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_index() {
+        let origin = Arc::new(Mutex::new(String::new()));
+        let host = origin.clone();
+        let (base, _) = serve(move |path| {
+            let base = host.lock().clone();
+            let body = match path {
+                "/sitemap.xml" => format!(
+                    "<sitemapindex><sitemap><loc>{base}/one.xml</loc></sitemap>\
+                     <sitemap><loc>{base}/missing.xml</loc></sitemap>\
+                     <sitemap><loc>{base}/two.xml</loc></sitemap></sitemapindex>"
+                ),
+                "/one.xml" => format!("<urlset><url><loc>{base}/a</loc></url></urlset>"),
+                "/two.xml" => format!(
+                    "<urlset><url><loc>{base}/b</loc></url><url><loc>{base}/c</loc></url></urlset>"
+                ),
+                _ => return (404, Vec::new(), String::new()),
+            };
+            (200, Vec::new(), body)
+        })
+        .await;
+        *origin.lock() = base.clone();
+        let fetcher = HttpFetcher::new(FetchConfig::new().with_timeout(5.0)).unwrap();
+
+        let entries = fetch_sitemap_urls(&fetcher, &format!("{base}/sitemap.xml"), 2)
+            .await
+            .unwrap();
+        let locs: Vec<String> = entries.iter().map(|e| e.loc.replace(&base, "")).collect();
+        assert_eq!(locs, vec!["/a", "/b"]);
+
+        let missing = fetch_sitemap_urls(&fetcher, &format!("{base}/missing.xml"), 10).await;
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,487 @@
+//! Ready-made pipeline stages for fetching and extracting web pages.
+//!
+//! [`FetchPageStage`] reads URLs from upstream outputs and emits fetched
+//! pages, keeping the raw HTML under `html`. [`ExtractContentStage`] reads
+//! those pages and replaces the HTML with extracted content, so the two can
+//! be chained with a single dependency:
+//!
+//! ```ignore
+//! let builder = builder
+//!     .stage("fetch", Arc::new(FetchPageStage::new("fetch", fetcher)), &["search"])?
+//!     .stage("extract", Arc::new(ExtractContentStage::new("extract", extractor)), &["fetch"])?;
+//! ```
+
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::config::WebSearchConfig;
+use super::crawler::{extract_into, fetched_page};
+use super::models::WebPage;
+use super::protocols::{ContentExtractor, Fetcher, Navigator};
+use crate::context::{StageContext, StageInputs};
+use crate::core::StageOutput;
+use crate::errors::UndeclaredDependencyError;
+use crate::stages::Stage;
+
+/// Keys searched in upstream outputs for URLs when no input is configured.
+const URL_KEYS: &[&str] = &["urls", "url"];
+
+/// Keys searched in upstream outputs for pages when no input is configured.
+const PAGE_KEYS: &[&str] = &["pages", "page"];
+
+/// Fetches URLs taken from stage inputs and emits them as `WebPage` dicts.
+///
+/// URLs are read from the configured input, or else from the first upstream
+/// output (by stage name) with a `urls` or `url` key. The value may be a
+/// string, an array of strings, or an array of objects with a `url` field,
+/// such as search results or extracted links. Duplicates are dropped.
+///
+/// Output data:
+/// - `pages`: one `WebPage` dict per URL, with the raw body under `html`
+///   for successful HTML responses
+/// - `fetched`: number of successful fetches
+/// - `failed`: number of failed fetches
+///
+/// Failed fetches become error pages rather than failing the stage; the
+/// stage skips if the inputs contain no URLs.
+pub struct FetchPageStage {
+    name: String,
+    fetcher: Arc<dyn Fetcher>,
+    input: Option<(String, String)>,
+    max_urls: Option<usize>,
+    max_concurrent: usize,
+}
+
+impl std::fmt::Debug for FetchPageStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FetchPageStage")
+            .field("name", &self.name)
+            .field("input", &self.input)
+            .field("max_urls", &self.max_urls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FetchPageStage {
+    /// Creates a fetch stage.
+    pub fn new(name: impl Into<String>, fetcher: Arc<dyn Fetcher>) -> Self {
+        Self {
+            name: name.into(),
+            fetcher,
+            input: None,
+            max_urls: None,
+            max_concurrent: WebSearchConfig::default().max_concurrent,
+        }
+    }
+
+    /// Reads URLs from `key` in the output of `stage`.
+    #[must_use]
+    pub fn with_input(mut self, stage: impl Into<String>, key: impl Into<String>) -> Self {
+        self.input = Some((stage.into(), key.into()));
+        self
+    }
+
+    /// Fetches at most `max` URLs, in input order.
+    #[must_use]
+    pub fn with_max_urls(mut self, max: usize) -> Self {
+        self.max_urls = Some(max);
+        self
+    }
+
+    /// Sets the number of concurrent requests.
+    #[must_use]
+    pub fn with_max_concurrent(mut self, max: usize) -> Self {
+        self.max_concurrent = max;
+        self
+    }
+
+    async fn fetch(&self, url: String) -> HashMap<String, serde_json::Value> {
+        let start = Instant::now();
+        match self.fetcher.fetch(&url, None, None).await {
+            Ok(result) => {
+                let mut dict = fetched_page(&url, &result).to_dict();
+                if result.is_success() && result.is_html() {
+                    dict.insert("html".to_string(), serde_json::json!(result.text));
+                }
+                dict
+            }
+            Err(e) => {
+                WebPage::error_result(url, e.to_string(), start.elapsed().as_secs_f64() * 1000.0)
+                    .to_dict()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Stage for FetchPageStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let value = match find_input(ctx.inputs(), self.input.as_ref(), URL_KEYS) {
+            Ok(Some(value)) => value,
+            Ok(None) => return StageOutput::skip("No URLs in stage inputs"),
+            Err(e) => return StageOutput::fail(e.to_string()),
+        };
+
+        let mut urls = Vec::new();
+        collect_urls(value, &mut urls);
+        let mut seen = HashSet::new();
+        urls.retain(|url| seen.insert(url.clone()));
+        if let Some(max) = self.max_urls {
+            urls.truncate(max);
+        }
+        if urls.is_empty() {
+            return StageOutput::skip("No URLs in stage inputs");
+        }
+
+        let pages: Vec<HashMap<String, serde_json::Value>> = stream::iter(urls)
+            .map(|url| self.fetch(url))
+            .buffered(self.max_concurrent.max(1))
+            .collect()
+            .await;
+        let failed = pages.iter().filter(|p| !page_succeeded(p)).count();
+
+        let mut data = HashMap::new();
+        data.insert(
+            "fetched".to_string(),
+            serde_json::json!(pages.len() - failed),
+        );
+        data.insert("failed".to_string(), serde_json::json!(failed));
+        data.insert("pages".to_string(), serde_json::json!(pages));
+        StageOutput::ok(data)
+    }
+}
+
+/// Extracts content from fetched pages taken from stage inputs.
+///
+/// Pages are read from the configured input, or else from the first
+/// upstream output with a `pages` or `page` key, in the shape emitted by
+/// [`FetchPageStage`]. Pages carrying `html` get markdown, plain text,
+/// metadata, links, and (with a navigator) navigation filled in; the HTML
+/// itself is dropped from the output. Pages without HTML, such as failed
+/// fetches, pass through unchanged.
+///
+/// Output data:
+/// - `pages`: the `WebPage` dicts
+/// - `extracted`: number of pages that had HTML to extract
+pub struct ExtractContentStage {
+    name: String,
+    extractor: Arc<dyn ContentExtractor>,
+    navigator: Option<Arc<dyn Navigator>>,
+    input: Option<(String, String)>,
+    max_chars: Option<usize>,
+}
+
+impl std::fmt::Debug for ExtractContentStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExtractContentStage")
+            .field("name", &self.name)
+            .field("input", &self.input)
+            .field("max_chars", &self.max_chars)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExtractContentStage {
+    /// Creates an extraction stage.
+    pub fn new(name: impl Into<String>, extractor: Arc<dyn ContentExtractor>) -> Self {
+        Self {
+            name: name.into(),
+            extractor,
+            navigator: None,
+            input: None,
+            max_chars: None,
+        }
+    }
+
+    /// Sets the navigator used to detect navigation actions and pagination.
+    #[must_use]
+    pub fn with_navigator(mut self, navigator: Arc<dyn Navigator>) -> Self {
+        self.navigator = Some(navigator);
+        self
+    }
+
+    /// Reads pages from `key` in the output of `stage`.
+    #[must_use]
+    pub fn with_input(mut self, stage: impl Into<String>, key: impl Into<String>) -> Self {
+        self.input = Some((stage.into(), key.into()));
+        self
+    }
+
+    /// Truncates extracted content with [`WebPage::truncate`].
+    #[must_use]
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self
+    }
+
+    fn extract(&self, dict: &serde_json::Map<String, serde_json::Value>) -> (WebPage, bool) {
+        let mut page = page_from_dict(dict);
+        let Some(html) = dict.get("html").and_then(serde_json::Value::as_str) else {
+            return (page, false);
+        };
+        extract_into(
+            &mut page,
+            html,
+            self.extractor.as_ref(),
+            self.navigator.as_deref(),
+            true,
+        );
+        if let Some(max_chars) = self.max_chars {
+            page = page.truncate(max_chars);
+        }
+        (page, true)
+    }
+}
+
+#[async_trait]
+impl Stage for ExtractContentStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn execute(&self, ctx: &StageContext) -> StageOutput {
+        let value = match find_input(ctx.inputs(), self.input.as_ref(), PAGE_KEYS) {
+            Ok(Some(value)) => value,
+            Ok(None) => return StageOutput::skip("No pages in stage inputs"),
+            Err(e) => return StageOutput::fail(e.to_string()),
+        };
+
+        let dicts: Vec<&serde_json::Map<String, serde_json::Value>> = match value {
+            serde_json::Value::Array(items) => items
+                .iter()
+                .filter_map(serde_json::Value::as_object)
+                .collect(),
+            serde_json::Value::Object(dict) => vec![dict],
+            _ => Vec::new(),
+        };
+        if dicts.is_empty() {
+            return StageOutput::skip("No pages in stage inputs");
+        }
+
+        let mut extracted = 0;
+        let pages: Vec<HashMap<String, serde_json::Value>> = dicts
+            .into_iter()
+            .map(|dict| {
+                let (page, had_html) = self.extract(dict);
+                extracted += usize::from(had_html);
+                page.to_dict()
+            })
+            .collect();
+
+        let mut data = HashMap::new();
+        data.insert("extracted".to_string(), serde_json::json!(extracted));
+        data.insert("pages".to_string(), serde_json::json!(pages));
+        StageOutput::ok(data)
+    }
+}
+
+/// Looks up the configured input, or else the first of `default_keys` found
+/// upstream.
+fn find_input<'a>(
+    inputs: &'a StageInputs,
+    configured: Option<&(String, String)>,
+    default_keys: &[&str],
+) -> Result<Option<&'a serde_json::Value>, UndeclaredDependencyError> {
+    if let Some((stage, key)) = configured {
+        return inputs.get_value(stage, key);
+    }
+    Ok(default_keys.iter().find_map(|key| inputs.find_value(key)))
+}
+
+fn collect_urls(value: &serde_json::Value, urls: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(url) if !url.trim().is_empty() => {
+            urls.push(url.trim().to_string());
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_urls(item, urls);
+            }
+        }
+        serde_json::Value::Object(dict) => {
+            if let Some(url) = dict.get("url").filter(|u| u.is_string()) {
+                collect_urls(url, urls);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn page_succeeded(dict: &HashMap<String, serde_json::Value>) -> bool {
+    let status = dict.get("status_code").and_then(serde_json::Value::as_u64);
+    !dict.contains_key("error") && status.is_some_and(|s| (200..400).contains(&s))
+}
+
+/// Rebuilds the fetch fields of a page dict; content is re-extracted.
+fn page_from_dict(dict: &serde_json::Map<String, serde_json::Value>) -> WebPage {
+    let text = |key: &str| {
+        dict.get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(String::from)
+    };
+    WebPage {
+        url: text("url").unwrap_or_default(),
+        final_url: text("final_url"),
+        status_code: dict
+            .get("status_code")
+            .and_then(serde_json::Value::as_u64)
+            .and_then(|s| u16::try_from(s).ok())
+            .unwrap_or_default(),
+        fetch_duration_ms: dict
+            .get("fetch_duration_ms")
+            .and_then(serde_json::Value::as_f64)
+            .unwrap_or_default(),
+        fetched_at: text("fetched_at"),
+        error: text("error"),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::FetchConfig;
+    use super::super::extractor::HtmlContentExtractor;
+    use super::super::protocols::FetchResult;
+    use super::*;
+    use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+    use crate::core::StageStatus;
+    use crate::errors::StageflowError;
+    use serde_json::json;
+
+    /// Serves canned HTML for `/ok` paths and errors for everything else.
+    struct CannedFetcher {
+        config: FetchConfig,
+    }
+
+    #[async_trait]
+    impl Fetcher for CannedFetcher {
+        async fn fetch(
+            &self,
+            url: &str,
+            _timeout: Option<f64>,
+            _headers: Option<&HashMap<String, String>>,
+        ) -> Result<FetchResult, StageflowError> {
+            if !url.contains("/ok") {
+                return Err(StageflowError::Internal(format!("refused {url}")));
+            }
+            Ok(FetchResult {
+                status_code: 200,
+                headers: HashMap::new(),
+                text: "<html><head><title>Hello</title></head>\
+                       <body><p>Some words here</p><a href=\"/next\">next</a></body></html>"
+                    .to_string(),
+                final_url: url.to_string(),
+                content_type: Some("text/html".to_string()),
+                duration_ms: 1.0,
+            })
+        }
+
+        fn config(&self) -> &FetchConfig {
+            &self.config
+        }
+    }
+
+    fn context(outputs: HashMap<String, HashMap<String, serde_json::Value>>) -> StageContext {
+        StageContext::new(
+            Arc::new(PipelineContext::new(RunIdentity::new())),
+            "stage",
+            StageInputs::permissive(outputs, "stage"),
+            ContextSnapshot::new(),
+        )
+    }
+
+    fn fetch_stage() -> FetchPageStage {
+        FetchPageStage::new(
+            "fetch",
+            Arc::new(CannedFetcher {
+                config: FetchConfig::new(),
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stage_reads_urls_from_inputs() {
+        let search = HashMap::from([(
+            "urls".to_string(),
+            json!([
+                "https://example.com/ok/1",
+                {"url": "https://example.com/ok/2", "title": "Two"},
+                "https://example.com/ok/1",
+                "https://example.com/broken",
+            ]),
+        )]);
+        let ctx = context(HashMap::from([("search".to_string(), search)]));
+
+        let output = fetch_stage().execute(&ctx).await;
+        assert!(output.is_success());
+        assert_eq!(output.get("fetched"), Some(&json!(2)));
+        assert_eq!(output.get("failed"), Some(&json!(1)));
+
+        let pages = output.get("pages").unwrap().as_array().unwrap();
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0]["html"].as_str().unwrap().contains("Hello"));
+        assert_eq!(pages[2]["url"], "https://example.com/broken");
+        assert!(pages[2]["error"].as_str().unwrap().contains("refused"));
+        assert!(pages[2].get("html").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stage_configured_input_and_limit() {
+        let upstream = HashMap::from([
+            ("first".to_string(), json!("https://example.com/ok/a")),
+            (
+                "links".to_string(),
+                json!(["https://example.com/ok/b", "https://example.com/ok/c"]),
+            ),
+        ]);
+        let ctx = context(HashMap::from([("router".to_string(), upstream)]));
+
+        let stage = fetch_stage().with_input("router", "links").with_max_urls(1);
+        let output = stage.execute(&ctx).await;
+        let pages = output.get("pages").unwrap().as_array().unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0]["url"], "https://example.com/ok/b");
+
+        let skipped = fetch_stage().execute(&context(HashMap::new())).await;
+        assert_eq!(skipped.status, StageStatus::Skip);
+    }
+
+    #[tokio::test]
+    async fn test_extract_stage_replaces_html_with_content() {
+        let fetched = fetch_stage()
+            .execute(&context(HashMap::from([(
+                "search".to_string(),
+                HashMap::from([(
+                    "urls".to_string(),
+                    json!(["https://example.com/ok", "https://example.com/down"]),
+                )]),
+            )])))
+            .await;
+        let ctx = context(HashMap::from([(
+            "fetch".to_string(),
+            fetched.data_or_empty(),
+        )]));
+
+        let stage = ExtractContentStage::new("extract", Arc::new(HtmlContentExtractor::default()));
+        let output = stage.execute(&ctx).await;
+        assert!(output.is_success());
+        assert_eq!(output.get("extracted"), Some(&json!(1)));
+
+        let pages = output.get("pages").unwrap().as_array().unwrap();
+        assert_eq!(pages[0]["metadata"]["title"], "Hello");
+        assert!(pages[0]["markdown"]
+            .as_str()
+            .unwrap()
+            .contains("Some words here"));
+        assert_eq!(pages[0]["links"][0]["url"], "https://example.com/next");
+        assert!(pages[0].get("html").is_none());
+        assert!(pages[1].get("error").is_some());
+    }
+}
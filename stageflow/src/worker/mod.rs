@@ -0,0 +1,12 @@
+//! Queue-driven pipeline runs.
+//!
+//! A [`PipelineWorker`] consumes [`Trigger`]s from a [`TriggerQueue`], runs a
+//! [`UnifiedStageGraph`](crate::pipeline::UnifiedStageGraph) for each with
+//! bounded concurrency, and acknowledges each trigger based on how its run
+//! ended.
+
+mod queue;
+mod runner;
+
+pub use queue::{Acknowledgement, ChannelQueue, Trigger, TriggerQueue};
+pub use runner::{PipelineWorker, SnapshotMapper, WorkerHandle, WorkerStats};
@@ -0,0 +1,130 @@
+//! Trigger queues a [`PipelineWorker`](super::PipelineWorker) consumes.
+
+use crate::errors::StageflowError;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tokio::sync::mpsc;
+
+/// A message asking for a pipeline run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    /// Message id, used to acknowledge it.
+    pub id: String,
+    /// Message body, mapped to the run's input snapshot.
+    pub payload: serde_json::Value,
+    /// How many times the message has been delivered, starting at 1.
+    #[serde(default = "first_delivery")]
+    pub delivery: u32,
+}
+
+fn first_delivery() -> u32 {
+    1
+}
+
+impl Trigger {
+    /// Creates a trigger on its first delivery.
+    #[must_use]
+    pub fn new(id: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            payload,
+            delivery: first_delivery(),
+        }
+    }
+}
+
+/// How a worker settles a trigger once its run is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Acknowledgement {
+    /// Done with; remove it from the queue.
+    Ack,
+    /// Deliver it again.
+    Retry,
+    /// Give up on it, e.g. move it to a dead-letter queue.
+    Reject,
+}
+
+impl Acknowledgement {
+    /// Returns the name used in events.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ack => "ack",
+            Self::Retry => "retry",
+            Self::Reject => "reject",
+        }
+    }
+}
+
+/// A source of [`Trigger`]s with acknowledgment.
+///
+/// Adapters for brokers such as Redis streams or SQS implement this trait.
+#[async_trait]
+pub trait TriggerQueue: Send + Sync {
+    /// Waits for the next trigger, or returns `None` once the queue is
+    /// closed and drained.
+    ///
+    /// Must be cancel-safe: the worker drops this future when it stops or
+    /// a run finishes first, and no trigger may be lost when it does.
+    async fn receive(&self) -> Option<Trigger>;
+
+    /// Settles a received trigger.
+    async fn settle(&self, trigger: Trigger, acknowledgement: Acknowledgement) -> Result<(), StageflowError>;
+}
+
+/// In-process [`TriggerQueue`] fed through a Tokio channel.
+///
+/// Retried triggers are delivered again ahead of new ones; rejected ones
+/// are kept as dead letters.
+#[derive(Debug)]
+pub struct ChannelQueue {
+    receiver: tokio::sync::Mutex<mpsc::Receiver<Trigger>>,
+    redeliveries: Mutex<VecDeque<Trigger>>,
+    dead_letters: Mutex<Vec<Trigger>>,
+}
+
+impl ChannelQueue {
+    /// Creates a queue holding up to `capacity` unreceived triggers and the
+    /// sender that feeds it. The queue closes when every sender is dropped.
+    #[must_use]
+    pub fn new(capacity: usize) -> (Self, mpsc::Sender<Trigger>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let queue = Self {
+            receiver: tokio::sync::Mutex::new(receiver),
+            redeliveries: Mutex::new(VecDeque::new()),
+            dead_letters: Mutex::new(Vec::new()),
+        };
+        (queue, sender)
+    }
+
+    /// Returns the rejected triggers.
+    #[must_use]
+    pub fn dead_letters(&self) -> Vec<Trigger> {
+        self.dead_letters.lock().clone()
+    }
+}
+
+#[async_trait]
+impl TriggerQueue for ChannelQueue {
+    async fn receive(&self) -> Option<Trigger> {
+        if let Some(trigger) = self.redeliveries.lock().pop_front() {
+            return Some(trigger);
+        }
+        self.receiver.lock().await.recv().await
+    }
+
+    async fn settle(&self, mut trigger: Trigger, acknowledgement: Acknowledgement) -> Result<(), StageflowError> {
+        match acknowledgement {
+            Acknowledgement::Ack => {}
+            Acknowledgement::Retry => {
+                trigger.delivery += 1;
+                self.redeliveries.lock().push_back(trigger);
+            }
+            Acknowledgement::Reject => self.dead_letters.lock().push(trigger),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+//! The worker loop.
+
+use super::{Acknowledgement, Trigger, TriggerQueue};
+use crate::context::{ContextSnapshot, PipelineContext, RunIdentity};
+use crate::errors::StageflowError;
+use crate::events::{EventSink, NoOpEventSink};
+use crate::pipeline::{BackpressureConfig, BackpressureTracker, FailureMode, UnifiedExecutionResult, UnifiedStageGraph};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// Builds a run's input snapshot from its trigger.
+pub type SnapshotMapper = Arc<dyn Fn(&Trigger) -> Result<ContextSnapshot, StageflowError> + Send + Sync>;
+
+type RunOutcome = (Trigger, Result<UnifiedExecutionResult, StageflowError>);
+
+/// Counts of what a worker did, returned when it stops.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WorkerStats {
+    /// Triggers received.
+    pub received: u64,
+    /// Runs that succeeded.
+    pub succeeded: u64,
+    /// Runs that failed, were cancelled or returned an error.
+    pub failed: u64,
+    /// Triggers acknowledged.
+    pub acked: u64,
+    /// Triggers handed back for redelivery.
+    pub retried: u64,
+    /// Triggers rejected, including unmappable and dropped ones.
+    pub rejected: u64,
+    /// Triggers rejected without running because the worker was full.
+    pub dropped: u64,
+}
+
+/// Runs a pipeline for every [`Trigger`] on a [`TriggerQueue`].
+///
+/// At most [`BackpressureConfig::max_concurrent`] runs are in flight. When
+/// full, the worker stops receiving until a run finishes, or, with
+/// [`BackpressureConfig::drop_on_full`], receives and rejects triggers
+/// without running them.
+///
+/// A finished run settles its trigger according to the [`FailureMode`]:
+///
+/// | Outcome | `FailFast` | `ContinueOnFailure` | `BestEffort` |
+/// |---|---|---|---|
+/// | Succeeded | ack | ack | ack |
+/// | Failed | retry | reject | ack |
+/// | Cancelled or error | retry | retry | retry |
+///
+/// Retries past the [delivery limit](Self::with_max_deliveries) are
+/// rejected, and triggers the snapshot mapper refuses are rejected
+/// without running.
+pub struct PipelineWorker {
+    graph: Arc<UnifiedStageGraph>,
+    queue: Arc<dyn TriggerQueue>,
+    backpressure: BackpressureConfig,
+    failure_mode: FailureMode,
+    max_deliveries: u32,
+    event_sink: Arc<dyn EventSink>,
+    snapshot_mapper: Option<SnapshotMapper>,
+}
+
+impl PipelineWorker {
+    /// Creates a worker with the default backpressure and fail-fast
+    /// acknowledgment.
+    #[must_use]
+    pub fn new(graph: Arc<UnifiedStageGraph>, queue: Arc<dyn TriggerQueue>) -> Self {
+        Self {
+            graph,
+            queue,
+            backpressure: BackpressureConfig::default(),
+            failure_mode: FailureMode::default(),
+            max_deliveries: 3,
+            event_sink: Arc::new(NoOpEventSink),
+            snapshot_mapper: None,
+        }
+    }
+
+    /// Sets the concurrency limit and what happens when it is reached.
+    #[must_use]
+    pub fn with_backpressure(mut self, config: BackpressureConfig) -> Self {
+        self.backpressure = config;
+        self
+    }
+
+    /// Sets how run outcomes map to acknowledgments.
+    #[must_use]
+    pub fn with_failure_mode(mut self, mode: FailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
+    /// Sets how many deliveries a trigger gets before a retry becomes a
+    /// rejection.
+    #[must_use]
+    pub fn with_max_deliveries(mut self, max_deliveries: u32) -> Self {
+        self.max_deliveries = max_deliveries;
+        self
+    }
+
+    /// Sets the sink for worker events, which runs also emit to.
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.event_sink = sink;
+        self
+    }
+
+    /// Sets how a trigger becomes the run's input snapshot.
+    ///
+    /// By default the payload is stored under the `trigger` metadata key.
+    /// The snapshot's run identity is always replaced with the run's.
+    #[must_use]
+    pub fn with_snapshot_mapper(
+        mut self,
+        mapper: impl Fn(&Trigger) -> Result<ContextSnapshot, StageflowError> + Send + Sync + 'static,
+    ) -> Self {
+        self.snapshot_mapper = Some(Arc::new(mapper));
+        self
+    }
+
+    /// Starts consuming on the current Tokio runtime.
+    #[must_use]
+    pub fn start(self) -> WorkerHandle {
+        let (stop, stopped) = watch::channel(false);
+        WorkerHandle {
+            stop,
+            task: tokio::spawn(self.run(stopped)),
+        }
+    }
+
+    async fn run(self, mut stop: watch::Receiver<bool>) -> WorkerStats {
+        let mut stats = WorkerStats::default();
+        let mut tracker = BackpressureTracker::new(self.backpressure.clone());
+        let mut running: JoinSet<RunOutcome> = JoinSet::new();
+        let mut stopping = false;
+        // The queue reported closed; runs in flight may still retry into it
+        let mut drained = false;
+
+        loop {
+            let full = tracker.should_apply_backpressure() && !self.backpressure.drop_on_full;
+            let receiving = !stopping && !drained && !full;
+            if !receiving && running.is_empty() && (stopping || drained) {
+                break;
+            }
+
+            tokio::select! {
+                _ = stop.changed(), if !stopping => stopping = true,
+                Some(joined) = running.join_next(), if !running.is_empty() => {
+                    tracker.release();
+                    drained = false;
+                    match joined {
+                        Ok((trigger, result)) => self.finish(&mut stats, trigger, &result).await,
+                        Err(e) => tracing::error!(error = %e, "Worker run panicked; its trigger is left unsettled"),
+                    }
+                }
+                received = self.queue.receive(), if receiving => match received {
+                    Some(trigger) => {
+                        stats.received += 1;
+                        self.dispatch(&mut stats, &mut tracker, &mut running, trigger).await;
+                    }
+                    None => drained = true,
+                },
+            }
+        }
+
+        self.emit("worker.stopped", serde_json::to_value(stats).unwrap_or_default());
+        stats
+    }
+
+    async fn dispatch(
+        &self,
+        stats: &mut WorkerStats,
+        tracker: &mut BackpressureTracker,
+        running: &mut JoinSet<RunOutcome>,
+        trigger: Trigger,
+    ) {
+        if !tracker.acquire() {
+            stats.dropped += 1;
+            self.emit(
+                "worker.trigger_dropped",
+                serde_json::json!({ "trigger_id": trigger.id, "in_flight": running.len() }),
+            );
+            self.settle(stats, trigger, Acknowledgement::Reject).await;
+            return;
+        }
+
+        let identity = RunIdentity::new();
+        let snapshot = match &self.snapshot_mapper {
+            Some(mapper) => mapper(&trigger),
+            None => Ok(ContextSnapshot::new().with_metadata("trigger", trigger.payload.clone())),
+        };
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot.with_run_id(identity.clone()),
+            Err(e) => {
+                tracker.release();
+                self.emit(
+                    "worker.trigger_invalid",
+                    serde_json::json!({ "trigger_id": trigger.id, "error": e.to_string() }),
+                );
+                self.settle(stats, trigger, Acknowledgement::Reject).await;
+                return;
+            }
+        };
+        self.emit(
+            "worker.run_started",
+            serde_json::json!({
+                "trigger_id": trigger.id,
+                "delivery": trigger.delivery,
+                "run_id": identity.pipeline_run_id,
+            }),
+        );
+
+        let ctx = Arc::new(PipelineContext::new(identity).with_event_sink(Arc::clone(&self.event_sink)));
+        let graph = Arc::clone(&self.graph);
+        running.spawn(async move {
+            let result = graph.execute(ctx, snapshot).await;
+            (trigger, result)
+        });
+    }
+
+    async fn finish(&self, stats: &mut WorkerStats, trigger: Trigger, result: &Result<UnifiedExecutionResult, StageflowError>) {
+        let succeeded = result.as_ref().is_ok_and(|result| result.success);
+        let failed_run = result.as_ref().is_ok_and(|result| !result.success && !result.cancelled);
+        let acknowledgement = match (succeeded, failed_run, self.failure_mode) {
+            (true, _, _) | (false, true, FailureMode::BestEffort) => Acknowledgement::Ack,
+            (false, true, FailureMode::ContinueOnFailure) => Acknowledgement::Reject,
+            _ => Acknowledgement::Retry,
+        };
+        if succeeded {
+            stats.succeeded += 1;
+        } else {
+            stats.failed += 1;
+        }
+        let error = match result {
+            Ok(result) => result.error.clone().or_else(|| result.cancel_reason.clone()),
+            Err(e) => Some(e.to_string()),
+        };
+        self.emit(
+            "worker.run_completed",
+            serde_json::json!({
+                "trigger_id": trigger.id,
+                "success": succeeded,
+                "error": error,
+                "duration_ms": result.as_ref().ok().map(|result| result.duration_ms),
+            }),
+        );
+        self.settle(stats, trigger, acknowledgement).await;
+    }
+
+    async fn settle(&self, stats: &mut WorkerStats, trigger: Trigger, acknowledgement: Acknowledgement) {
+        let acknowledgement = if acknowledgement == Acknowledgement::Retry && trigger.delivery >= self.max_deliveries {
+            Acknowledgement::Reject
+        } else {
+            acknowledgement
+        };
+        match acknowledgement {
+            Acknowledgement::Ack => stats.acked += 1,
+            Acknowledgement::Retry => stats.retried += 1,
+            Acknowledgement::Reject => stats.rejected += 1,
+        }
+        self.emit(
+            "worker.trigger_settled",
+            serde_json::json!({
+                "trigger_id": trigger.id,
+                "delivery": trigger.delivery,
+                "acknowledgement": acknowledgement.as_str(),
+            }),
+        );
+        let id = trigger.id.clone();
+        if let Err(e) = self.queue.settle(trigger, acknowledgement).await {
+            tracing::warn!(trigger_id = %id, error = %e, "Failed to settle trigger");
+        }
+    }
+
+    fn emit(&self, event_type: &str, data: serde_json::Value) {
+        self.event_sink.try_emit(event_type, Some(data));
+    }
+}
+
+impl std::fmt::Debug for PipelineWorker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineWorker")
+            .field("pipeline", &self.graph.name())
+            .field("backpressure", &self.backpressure)
+            .field("failure_mode", &self.failure_mode)
+            .field("max_deliveries", &self.max_deliveries)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Handle to a started [`PipelineWorker`].
+#[derive(Debug)]
+pub struct WorkerHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<WorkerStats>,
+}
+
+impl WorkerHandle {
+    /// Stops receiving triggers. Runs in flight finish and are settled.
+    pub fn stop(&self) {
+        let _ = self.stop.send(true);
+    }
+
+    /// Waits for the worker to finish, either because it was stopped or
+    /// because the queue closed.
+    pub async fn join(self) -> Result<WorkerStats, StageflowError> {
+        self.task
+            .await
+            .map_err(|e| StageflowError::Internal(format!("Worker task failed: {e}")))
+    }
+
+    /// Stops the worker and waits for runs in flight to be settled.
+    pub async fn shutdown(self) -> Result<WorkerStats, StageflowError> {
+        self.stop();
+        self.join().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StageOutput;
+    use crate::pipeline::PipelineBuilder;
+    use crate::stages::FnStage;
+    use crate::worker::ChannelQueue;
+
+    /// A pipeline that fails when the trigger payload says so.
+    fn graph() -> Arc<UnifiedStageGraph> {
+        let stage = Arc::new(FnStage::new("work", |ctx| {
+            if ctx.snapshot().metadata.get("trigger") == Some(&serde_json::json!("bad")) {
+                StageOutput::fail("bad input")
+            } else {
+                StageOutput::ok_empty()
+            }
+        }));
+        let graph = PipelineBuilder::new("jobs").stage("work", stage, &[]).unwrap().build().unwrap();
+        Arc::new(UnifiedStageGraph::new(graph))
+    }
+
+    async fn consume(mode: FailureMode, payloads: &[&str]) -> (WorkerStats, Vec<Trigger>) {
+        let (queue, sender) = ChannelQueue::new(8);
+        let queue = Arc::new(queue);
+        for (i, payload) in payloads.iter().enumerate() {
+            sender.send(Trigger::new(format!("t{i}"), serde_json::json!(payload))).await.unwrap();
+        }
+        drop(sender);
+
+        let stats = PipelineWorker::new(graph(), queue.clone())
+            .with_failure_mode(mode)
+            .with_max_deliveries(2)
+            .with_backpressure(BackpressureConfig {
+                max_concurrent: 2,
+                ..BackpressureConfig::default()
+            })
+            .start()
+            .join()
+            .await
+            .unwrap();
+        (stats, queue.dead_letters())
+    }
+
+    #[tokio::test]
+    async fn test_failure_mode_decides_acknowledgment() {
+        let (stats, dead) = consume(FailureMode::FailFast, &["ok", "bad", "ok"]).await;
+        // The bad trigger is delivered twice, then rejected
+        assert_eq!((stats.received, stats.acked, stats.retried, stats.rejected), (4, 2, 1, 1));
+        assert_eq!(dead.len(), 1);
+        assert_eq!((dead[0].id.as_str(), dead[0].delivery), ("t1", 2));
+
+        let (stats, dead) = consume(FailureMode::ContinueOnFailure, &["ok", "bad"]).await;
+        assert_eq!((stats.received, stats.acked, stats.retried, stats.rejected), (2, 1, 0, 1));
+        assert_eq!(dead.len(), 1);
+
+        let (stats, dead) = consume(FailureMode::BestEffort, &["bad"]).await;
+        assert_eq!((stats.acked, stats.failed), (1, 1));
+        assert!(dead.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drop_on_full_sheds_load() {
+        let (queue, sender) = ChannelQueue::new(8);
+        let queue = Arc::new(queue);
+        let slow = Arc::new(crate::testing::SlowStage::with_delay_ms("work", 30));
+        let graph = PipelineBuilder::new("jobs").stage("work", slow, &[]).unwrap().build().unwrap();
+        for i in 0..3 {
+            sender.send(Trigger::new(format!("t{i}"), serde_json::Value::Null)).await.unwrap();
+        }
+        drop(sender);
+
+        let stats = PipelineWorker::new(Arc::new(UnifiedStageGraph::new(graph)), queue.clone())
+            .with_backpressure(BackpressureConfig {
+                max_concurrent: 1,
+                drop_on_full: true,
+                ..BackpressureConfig::default()
+            })
+            .start()
+            .join()
+            .await
+            .unwrap();
+
+        assert_eq!((stats.received, stats.succeeded, stats.dropped), (3, 1, 2));
+        assert_eq!(queue.dead_letters().len(), 2);
+    }
+}